@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use proto::fuzz;
+
+fuzz_target!(|data: &[u8]| {
+    fuzz::frames(data);
+});
@@ -1,9 +1,9 @@
 #![no_main]
 
 use libfuzzer_sys::fuzz_target;
-use proto::{Side, transport_parameters::TransportParameters};
+use proto::{Side, fuzz};
 
 fuzz_target!(|data: &[u8]| {
-    let mut data = data;
-    let _ = TransportParameters::read(Side::Client, &mut data);
+    fuzz::transport_parameters(Side::Client, data);
+    fuzz::transport_parameters(Side::Server, data);
 });
@@ -0,0 +1,14 @@
+#![no_main]
+
+use std::sync::Arc;
+
+use libfuzzer_sys::fuzz_target;
+use proto::{EndpointConfig, fuzz};
+
+fuzz_target!(|datagrams: Vec<Vec<u8>>| {
+    fuzz::cid_routing(
+        Arc::new(EndpointConfig::default()),
+        None,
+        datagrams.iter().map(|x| &x[..]),
+    );
+});
@@ -0,0 +1,20 @@
+#!/bin/bash
+set -e
+
+# Set up the routing needed for the simulation
+/setup.sh
+
+if [ -n "$TESTCASE" ]; then
+    case "$TESTCASE" in
+        handshake|transfer|multiplexing|retry|resumption|zerortt|chacha20|keyupdate|ecn) ;;
+        *) exit 127 ;;
+    esac
+fi
+
+if [ "$ROLE" == "client" ]; then
+    # Wait for the simulator to start up
+    /wait-for-it.sh sim:57832 -s -t 30
+    exec quinn-interop client $CLIENT_PARAMS
+elif [ "$ROLE" == "server" ]; then
+    exec quinn-interop server $SERVER_PARAMS
+fi
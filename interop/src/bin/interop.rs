@@ -0,0 +1,51 @@
+use clap::{Parser, Subcommand};
+use tracing::error;
+use tracing_subscriber::EnvFilter;
+
+use interop::{EXIT_UNSUPPORTED, TestCase, client, server};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let opt = Cli::parse();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env()
+                .or_else(|_| EnvFilter::try_new("info"))
+                .unwrap(),
+        )
+        .init();
+
+    let case = match opt.testcase.map_or_else(TestCase::from_env, |x| x.parse()) {
+        Ok(case) => case,
+        Err(e) => {
+            error!("{e}");
+            std::process::exit(EXIT_UNSUPPORTED);
+        }
+    };
+    let r = match opt.command {
+        Commands::Server(opt) => server::run(opt, case).await,
+        Commands::Client(opt) => client::run(opt, case).await,
+    };
+    if let Err(e) = r {
+        error!("{:#}", e);
+        std::process::exit(1);
+    }
+}
+
+#[derive(Parser)]
+#[clap(long_about = None)]
+struct Cli {
+    /// Test case to run, overriding the `TESTCASE` environment variable
+    #[clap(long)]
+    testcase: Option<String>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Run as an interop server
+    Server(server::Opt),
+    /// Run as an interop client
+    Client(client::Opt),
+}
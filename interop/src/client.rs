@@ -0,0 +1,223 @@
+use std::{
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Parser;
+use quinn::crypto::rustls::QuicClientConfig;
+use rustls::{
+    crypto::ring::cipher_suite,
+    pki_types::{CertificateDer, ServerName, UnixTime},
+};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+use url::Url;
+
+use crate::{ALPN, TestCase, transport_config};
+
+/// Downloads files from an interop runner server
+#[derive(Parser)]
+#[clap(name = "client")]
+pub struct Opt {
+    /// Directory to store downloaded files in
+    #[clap(long = "downloads", default_value = "/downloads")]
+    downloads: PathBuf,
+    /// URLs to download, overriding the space-separated list in `REQUESTS`
+    requests: Vec<Url>,
+}
+
+pub async fn run(opt: Opt, case: TestCase) -> Result<()> {
+    let requests = match opt.requests.is_empty() {
+        true => std::env::var("REQUESTS")
+            .context("no requests given")?
+            .split_whitespace()
+            .map(Url::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .context("invalid request URL")?,
+        false => opt.requests,
+    };
+    let Some(first) = requests.first() else {
+        bail!("no requests given");
+    };
+    let host = first
+        .host_str()
+        .context("request URL without host")?
+        .to_owned();
+    let port = first.port_or_known_default().unwrap_or(443);
+    let remote = tokio::net::lookup_host((host.as_str(), port))
+        .await?
+        .next()
+        .with_context(|| format!("failed to resolve {host}"))?;
+
+    let local = match remote {
+        SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+    };
+    let endpoint = quinn::Endpoint::client(local)?;
+    endpoint.set_default_client_config(client_config(case)?);
+
+    let download = Download {
+        endpoint: &endpoint,
+        remote,
+        host: &host,
+        downloads: &opt.downloads,
+    };
+    match case {
+        TestCase::Resumption | TestCase::ZeroRtt => {
+            download.connection(&requests[..1], false, false).await?;
+            download
+                .connection(&requests[1..], case == TestCase::ZeroRtt, false)
+                .await?;
+        }
+        _ => {
+            download
+                .connection(&requests, false, case == TestCase::KeyUpdate)
+                .await?
+        }
+    }
+
+    endpoint.wait_idle().await;
+    Ok(())
+}
+
+fn client_config(case: TestCase) -> Result<quinn::ClientConfig> {
+    let mut provider = rustls::crypto::ring::default_provider();
+    if case == TestCase::ChaCha20 {
+        provider.cipher_suites = vec![cipher_suite::TLS13_CHACHA20_POLY1305_SHA256];
+    }
+    let provider = Arc::new(provider);
+    let mut crypto = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .unwrap()
+        .dangerous()
+        .with_custom_certificate_verifier(SkipServerVerification::new(provider))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    crypto.enable_early_data = true;
+    crypto.key_log = Arc::new(rustls::KeyLogFile::new());
+
+    // Initial packets are always protected with AES-128-GCM, whichever suites are offered
+    let initial = cipher_suite::TLS13_AES_128_GCM_SHA256
+        .tls13()
+        .and_then(|x| x.quic_suite())
+        .unwrap();
+    let crypto = QuicClientConfig::with_initial(Arc::new(crypto), initial)?;
+    let mut config = quinn::ClientConfig::new(Arc::new(crypto));
+    config.transport_config(transport_config());
+    Ok(config)
+}
+
+struct Download<'a> {
+    endpoint: &'a quinn::Endpoint,
+    remote: SocketAddr,
+    host: &'a str,
+    downloads: &'a PathBuf,
+}
+
+impl Download<'_> {
+    /// Download `requests` over a new connection
+    async fn connection(&self, requests: &[Url], zero_rtt: bool, key_update: bool) -> Result<()> {
+        let connecting = self.endpoint.connect(self.remote, self.host)?;
+        let connection = match zero_rtt {
+            true => match connecting.into_0rtt() {
+                Ok(connection) => connection,
+                Err(connecting) => {
+                    warn!("0-RTT unavailable");
+                    connecting.await?
+                }
+            },
+            false => connecting.await?,
+        };
+        info!(remote = %connection.remote_address(), "connected");
+        if key_update {
+            connection.force_key_update();
+        }
+
+        let mut tasks = JoinSet::new();
+        for url in requests {
+            let connection = connection.clone();
+            let path = url.path().to_owned();
+            let file = match path.rsplit('/').next() {
+                Some(name) if !name.is_empty() => self.downloads.join(name),
+                _ => bail!("request URL {url} doesn't name a file"),
+            };
+            tasks.spawn(async move {
+                let (mut send, mut recv) = connection.open_bi().await?;
+                send.write_all(format!("GET {path}\r\n").as_bytes())
+                    .await
+                    .map_err(|e| anyhow!("failed to send request: {e}"))?;
+                send.finish().unwrap();
+                let data = recv
+                    .read_to_end(usize::MAX)
+                    .await
+                    .map_err(|e| anyhow!("failed to read response: {e}"))?;
+                tokio::fs::write(&file, data)
+                    .await
+                    .with_context(|| format!("writing {}", file.display()))
+            });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result??;
+        }
+
+        connection.close(0u32.into(), b"done");
+        Ok(())
+    }
+}
+
+/// Accepts any server certificate, as the runner's certificates aren't signed by a trusted CA
+#[derive(Debug)]
+struct SkipServerVerification(Arc<rustls::crypto::CryptoProvider>);
+
+impl SkipServerVerification {
+    fn new(provider: Arc<rustls::crypto::CryptoProvider>) -> Arc<Self> {
+        Arc::new(Self(provider))
+    }
+}
+
+impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp: &[u8],
+        _now: UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
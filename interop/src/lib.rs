@@ -0,0 +1,93 @@
+//! Endpoints for the [QUIC Interop Runner](https://github.com/quic-interop/quic-interop-runner)
+//!
+//! The runner starts a client and a server in separate containers, selects the scenario through
+//! the `TESTCASE` environment variable and passes the URLs the client should download in
+//! `REQUESTS`. Files are served from `/www` and downloaded to `/downloads` over HTTP/0.9, using the
+//! `hq-interop` ALPN. Endpoints exit with status 127 for test cases they don't implement.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use anyhow::Result;
+use quinn::TransportConfig;
+
+pub mod client;
+pub mod server;
+
+/// ALPN protocol of HTTP/0.9 over QUIC as used by the interop runner
+pub const ALPN: &[u8] = b"hq-interop";
+
+/// Exit status signalling an unsupported test case to the runner
+pub const EXIT_UNSUPPORTED: i32 = 127;
+
+/// Scenarios of the interop runner implemented by these endpoints
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TestCase {
+    /// Plain handshake, downloading a single file
+    Handshake,
+    /// Flow control and congestion control while downloading several large files
+    Transfer,
+    /// Many small files downloaded over concurrent streams
+    Multiplexing,
+    /// The server sends a Retry before accepting the connection
+    Retry,
+    /// A second connection resumes the TLS session of the first
+    Resumption,
+    /// Like `Resumption`, with the remaining requests sent as 0-RTT data
+    ZeroRtt,
+    /// The client only offers the ChaCha20-Poly1305 cipher suite
+    ChaCha20,
+    /// The client updates its keys right after the handshake
+    KeyUpdate,
+    /// Packets are marked with ECN
+    Ecn,
+}
+
+impl TestCase {
+    /// Read the test case from the `TESTCASE` environment variable
+    pub fn from_env() -> Result<Self, UnsupportedTestCase> {
+        std::env::var("TESTCASE")
+            .map_err(|_| UnsupportedTestCase(String::new()))?
+            .parse()
+    }
+}
+
+impl FromStr for TestCase {
+    type Err = UnsupportedTestCase;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "handshake" => Self::Handshake,
+            "transfer" => Self::Transfer,
+            "multiplexing" => Self::Multiplexing,
+            "retry" => Self::Retry,
+            "resumption" => Self::Resumption,
+            "zerortt" => Self::ZeroRtt,
+            "chacha20" => Self::ChaCha20,
+            "keyupdate" => Self::KeyUpdate,
+            "ecn" => Self::Ecn,
+            _ => return Err(UnsupportedTestCase(s.to_owned())),
+        })
+    }
+}
+
+/// A test case not implemented by these endpoints
+#[derive(Debug, Clone)]
+pub struct UnsupportedTestCase(pub String);
+
+impl fmt::Display for UnsupportedTestCase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unsupported test case {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnsupportedTestCase {}
+
+/// Transport parameters shared by client and server
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    // The multiplexing test requests a few thousand files at once
+    config
+        .max_concurrent_bidi_streams(1000u32.into())
+        .max_concurrent_uni_streams(0u8.into());
+    Arc::new(config)
+}
@@ -0,0 +1,138 @@
+use std::{
+    net::SocketAddr,
+    path::{Component, Path, PathBuf},
+    str,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::Parser;
+use quinn::crypto::rustls::QuicServerConfig;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject};
+use tracing::{error, info};
+
+use crate::{ALPN, TestCase, transport_config};
+
+/// Serves files to interop runner clients
+#[derive(Parser)]
+#[clap(name = "server")]
+pub struct Opt {
+    /// Address to listen on
+    #[clap(long = "listen", default_value = "[::]:443")]
+    listen: SocketAddr,
+    /// Directory to serve files from
+    #[clap(long = "root", default_value = "/www")]
+    root: PathBuf,
+    /// TLS private key in PEM format
+    #[clap(short = 'k', long = "key", default_value = "/certs/priv.key")]
+    key: PathBuf,
+    /// TLS certificate chain in PEM format
+    #[clap(short = 'c', long = "cert", default_value = "/certs/cert.pem")]
+    cert: PathBuf,
+}
+
+pub async fn run(opt: Opt, case: TestCase) -> Result<()> {
+    let key = PrivateKeyDer::from_pem_file(&opt.key).context("reading private key")?;
+    let cert = CertificateDer::pem_file_iter(&opt.cert)
+        .context("reading certificate chain file")?
+        .collect::<Result<_, _>>()
+        .context("reading certificate chain")?;
+
+    let mut crypto = rustls::ServerConfig::builder_with_provider(
+        rustls::crypto::ring::default_provider().into(),
+    )
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .unwrap()
+    .with_no_client_auth()
+    .with_single_cert(cert, key)?;
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    crypto.max_early_data_size = u32::MAX;
+    crypto.key_log = Arc::new(rustls::KeyLogFile::new());
+
+    let mut config =
+        quinn::ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(crypto)?));
+    config.transport_config(transport_config());
+
+    let root = Arc::<Path>::from(opt.root);
+    let endpoint = quinn::Endpoint::server(config, opt.listen)?;
+    info!("listening on {}", endpoint.local_addr()?);
+
+    while let Some(incoming) = endpoint.accept().await {
+        if case == TestCase::Retry && !incoming.remote_address_validated() {
+            info!("requiring address validation");
+            incoming.retry().unwrap();
+            continue;
+        }
+        let root = root.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(root, incoming).await {
+                error!("connection failed: {e:#}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn handle_connection(root: Arc<Path>, incoming: quinn::Incoming) -> Result<()> {
+    let connecting = incoming.accept()?;
+    // Serve requests received as 0-RTT data right away
+    let connection = match connecting.into_0rtt() {
+        Ok(connection) => connection,
+        Err(connecting) => connecting.await?,
+    };
+    info!(remote = %connection.remote_address(), "established");
+    loop {
+        let stream = match connection.accept_bi().await {
+            Ok(stream) => stream,
+            Err(quinn::ConnectionError::ApplicationClosed(_)) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let root = root.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(&root, stream).await {
+                error!("request failed: {e:#}");
+            }
+        });
+    }
+}
+
+async fn handle_request(
+    root: &Path,
+    (mut send, mut recv): (quinn::SendStream, quinn::RecvStream),
+) -> Result<()> {
+    let request = recv
+        .read_to_end(64 * 1024)
+        .await
+        .map_err(|e| anyhow!("failed reading request: {e}"))?;
+    let path = resolve(root, &request)?;
+    let data = tokio::fs::read(&path)
+        .await
+        .with_context(|| format!("reading {}", path.display()))?;
+    send.write_all(&data)
+        .await
+        .map_err(|e| anyhow!("failed to send response: {e}"))?;
+    send.finish().unwrap();
+    Ok(())
+}
+
+/// Map an HTTP/0.9 `GET` request to a file below `root`
+fn resolve(root: &Path, request: &[u8]) -> Result<PathBuf> {
+    let Some(request) = request.strip_prefix(b"GET ") else {
+        bail!("missing GET");
+    };
+    let request = request.strip_suffix(b"\r\n").unwrap_or(request);
+    let path = str::from_utf8(request).context("path is malformed UTF-8")?;
+    let mut components = Path::new(path).components();
+    if components.next() != Some(Component::RootDir) {
+        bail!("path must be absolute");
+    }
+    let mut resolved = root.to_path_buf();
+    for component in components {
+        match component {
+            Component::Normal(x) => resolved.push(x),
+            x => bail!("illegal component in path: {x:?}"),
+        }
+    }
+    Ok(resolved)
+}
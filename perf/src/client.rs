@@ -1,25 +1,28 @@
 #[cfg(feature = "json-output")]
-use std::path::PathBuf;
+use std::path::Path;
 use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
-    path::Path,
+    path::PathBuf,
     sync::Arc,
     time::{Duration, Instant},
 };
 
 use anyhow::{Context, Result};
-use bytes::Bytes;
+use bytes::{BufMut, Bytes, BytesMut};
 use clap::Parser;
 use quinn::{TokioRuntime, crypto::rustls::QuicClientConfig};
 use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
-use tokio::sync::Semaphore;
+use tokio::{
+    sync::Semaphore,
+    time::{Interval, MissedTickBehavior},
+};
 use tracing::{debug, error, info};
 
 use crate::{
     CommonOpt, PERF_CIPHER_SUITES,
     noprotection::NoProtectionClientConfig,
     parse_byte_size,
-    stats::{OpenStreamStats, Stats},
+    stats::{DatagramStats, OpenStreamStats, Stats},
 };
 
 /// Connects to a QUIC perf server and maintains a specified pattern of requests until interrupted
@@ -53,6 +56,21 @@ pub struct Opt {
     /// 1MiB, 10G will transfer 10GiB.
     #[clap(long, default_value = "1M", value_parser = parse_byte_size)]
     upload_size: u64,
+    /// Number of requests to start per second, for each of the request kinds
+    ///
+    /// By default, a new request is started as soon as a previous one completes. With a rate,
+    /// requests are started on a fixed schedule instead, still limited by `--uni-requests` and
+    /// `--bi-requests`. Request latency is measured from the scheduled start, so time spent
+    /// waiting for a free slot is included.
+    #[clap(long)]
+    request_rate: Option<f64>,
+    /// Number of datagrams to send per second, which are echoed by the server to measure
+    /// round-trip times
+    #[clap(long, default_value = "0")]
+    datagram_rate: f64,
+    /// Size of the datagrams used to measure round-trip times
+    #[clap(long, default_value = "64", value_parser = clap::value_parser!(u16).range(8..))]
+    datagram_size: u16,
     /// The time to run in seconds
     #[clap(long, default_value = "60")]
     duration: u64,
@@ -63,6 +81,9 @@ pub struct Opt {
     #[cfg(feature = "json-output")]
     #[clap(long)]
     json: Option<PathBuf>,
+    /// Directory to write latency distributions to, as HdrHistogram percentile files
+    #[clap(long)]
+    hgrm: Option<PathBuf>,
     /// Common options
     #[command(flatten)]
     common: CommonOpt,
@@ -135,6 +156,7 @@ pub async fn run(opt: Opt) -> Result<()> {
     config.transport_config(Arc::new(transport));
 
     let stream_stats = OpenStreamStats::default();
+    let datagram_stats = DatagramStats::default();
 
     let connection = endpoint
         .connect_with(config, addr, host_name)?
@@ -149,6 +171,7 @@ pub async fn run(opt: Opt) -> Result<()> {
                 connection.clone(),
                 stream_stats.clone(),
                 opt.uni_requests,
+                opt.request_rate,
                 opt.upload_size,
                 opt.download_size
             ),
@@ -156,8 +179,15 @@ pub async fn run(opt: Opt) -> Result<()> {
                 connection.clone(),
                 stream_stats.clone(),
                 opt.bi_requests,
+                opt.request_rate,
                 opt.upload_size,
                 opt.download_size
+            ),
+            drive_datagrams(
+                connection.clone(),
+                datagram_stats.clone(),
+                opt.datagram_rate,
+                opt.datagram_size
             )
         )
     };
@@ -176,7 +206,7 @@ pub async fn run(opt: Opt) -> Result<()> {
             let start = Instant::now();
             tokio::time::sleep(interval_duration).await;
             {
-                stats.on_interval(start, &stream_stats);
+                stats.on_interval(start, &stream_stats, &datagram_stats);
 
                 if allow_table_output {
                     stats.print();
@@ -209,11 +239,18 @@ pub async fn run(opt: Opt) -> Result<()> {
         stats.print_json(path.as_path())?;
     }
 
+    if let Some(dir) = opt.hgrm {
+        stats
+            .write_hgrm(&dir)
+            .with_context(|| format!("writing latency distributions to {}", dir.display()))?;
+    }
+
     Ok(())
 }
 
 async fn drain_stream(
     mut stream: quinn::RecvStream,
+    start: Instant,
     download: u64,
     stream_stats: OpenStreamStats,
 ) -> Result<()> {
@@ -249,6 +286,7 @@ async fn drain_stream(
     if first_byte {
         recv_stream_stats.on_first_byte(download_start.elapsed());
     }
+    recv_stream_stats.on_response(start.elapsed());
     recv_stream_stats.finish(download_start.elapsed());
 
     debug!("response finished on {}", stream.id());
@@ -259,6 +297,7 @@ async fn drive_uni(
     connection: quinn::Connection,
     stream_stats: OpenStreamStats,
     concurrency: u64,
+    rate: Option<f64>,
     upload: u64,
     download: u64,
 ) -> Result<()> {
@@ -267,8 +306,10 @@ async fn drive_uni(
     }
 
     let sem = Arc::new(Semaphore::new(concurrency as usize));
+    let mut schedule = rate.map(request_schedule);
 
     loop {
+        let start = next_start(&mut schedule).await;
         let permit = sem.clone().acquire_owned().await.unwrap();
         let send = connection.open_uni().await?;
         let stream_stats = stream_stats.clone();
@@ -276,7 +317,9 @@ async fn drive_uni(
         debug!("sending request on {}", send.id());
         let connection = connection.clone();
         tokio::spawn(async move {
-            if let Err(e) = request_uni(send, connection, upload, download, stream_stats).await {
+            if let Err(e) =
+                request_uni(send, connection, start, upload, download, stream_stats).await
+            {
                 error!("sending request failed: {:#}", e);
             }
 
@@ -288,13 +331,14 @@ async fn drive_uni(
 async fn request_uni(
     send: quinn::SendStream,
     conn: quinn::Connection,
+    start: Instant,
     upload: u64,
     download: u64,
     stream_stats: OpenStreamStats,
 ) -> Result<()> {
     request(send, upload, download, stream_stats.clone()).await?;
     let recv = conn.accept_uni().await?;
-    drain_stream(recv, download, stream_stats).await?;
+    drain_stream(recv, start, download, stream_stats).await?;
     Ok(())
 }
 
@@ -335,6 +379,7 @@ async fn drive_bi(
     connection: quinn::Connection,
     stream_stats: OpenStreamStats,
     concurrency: u64,
+    rate: Option<f64>,
     upload: u64,
     download: u64,
 ) -> Result<()> {
@@ -343,15 +388,17 @@ async fn drive_bi(
     }
 
     let sem = Arc::new(Semaphore::new(concurrency as usize));
+    let mut schedule = rate.map(request_schedule);
 
     loop {
+        let start = next_start(&mut schedule).await;
         let permit = sem.clone().acquire_owned().await.unwrap();
         let (send, recv) = connection.open_bi().await?;
         let stream_stats = stream_stats.clone();
 
         debug!("sending request on {}", send.id());
         tokio::spawn(async move {
-            if let Err(e) = request_bi(send, recv, upload, download, stream_stats).await {
+            if let Err(e) = request_bi(send, recv, start, upload, download, stream_stats).await {
                 error!("request failed: {:#}", e);
             }
 
@@ -363,15 +410,72 @@ async fn drive_bi(
 async fn request_bi(
     send: quinn::SendStream,
     recv: quinn::RecvStream,
+    start: Instant,
     upload: u64,
     download: u64,
     stream_stats: OpenStreamStats,
 ) -> Result<()> {
     request(send, upload, download, stream_stats.clone()).await?;
-    drain_stream(recv, download, stream_stats).await?;
+    drain_stream(recv, start, download, stream_stats).await?;
     Ok(())
 }
 
+fn request_schedule(rate: f64) -> Interval {
+    let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+    // Catch up on requests that couldn't be started in time, rather than skipping them
+    interval.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    interval
+}
+
+/// Wait for the next request to be due, returning the time it was scheduled for
+async fn next_start(schedule: &mut Option<Interval>) -> Instant {
+    match schedule {
+        Some(interval) => interval.tick().await.into_std(),
+        None => Instant::now(),
+    }
+}
+
+/// Send timestamped datagrams at `rate`, recording the round-trip times of their echoes
+async fn drive_datagrams(
+    connection: quinn::Connection,
+    datagram_stats: DatagramStats,
+    rate: f64,
+    size: u16,
+) -> Result<()> {
+    if rate <= 0.0 {
+        return Ok(());
+    }
+
+    let epoch = Instant::now();
+    let send = async {
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / rate));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let mut datagram = BytesMut::with_capacity(size.into());
+            datagram.put_u64(epoch.elapsed().as_micros() as u64);
+            datagram.resize(size.into(), 0);
+            if let Err(e) = connection.send_datagram(datagram.freeze()) {
+                debug!("failed to send datagram: {}", e);
+            }
+        }
+    };
+    let recv = async {
+        loop {
+            let datagram = connection.read_datagram().await?;
+            let Some(sent) = datagram.get(..8) else {
+                continue;
+            };
+            let sent = Duration::from_micros(u64::from_be_bytes(sent.try_into().unwrap()));
+            datagram_stats.on_rtt(epoch.elapsed().saturating_sub(sent));
+        }
+    };
+    tokio::select! {
+        () = send => Ok(()),
+        r = recv => r,
+    }
+}
+
 #[derive(Debug)]
 struct SkipServerVerification(Arc<rustls::crypto::CryptoProvider>);
 
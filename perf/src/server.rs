@@ -105,6 +105,7 @@ async fn handle(handshake: quinn::Incoming, opt: Arc<Opt>) -> Result<()> {
     tokio::try_join!(
         drive_uni(connection.clone()),
         drive_bi(connection.clone()),
+        drive_datagrams(connection.clone()),
         conn_stats(connection, opt)
     )?;
     Ok(())
@@ -151,6 +152,16 @@ async fn drive_bi(connection: quinn::Connection) -> Result<()> {
     Ok(())
 }
 
+/// Echo datagrams back to the client, which uses them to measure round-trip times
+async fn drive_datagrams(connection: quinn::Connection) -> Result<()> {
+    while let Ok(datagram) = connection.read_datagram().await {
+        if let Err(e) = connection.send_datagram(datagram) {
+            debug!("failed to echo datagram: {}", e);
+        }
+    }
+    Ok(())
+}
+
 async fn handle_bi(send: quinn::SendStream, recv: quinn::RecvStream) -> Result<()> {
     let bytes = read_req(recv).await?;
     respond(bytes, send).await?;
@@ -1,10 +1,11 @@
 use hdrhistogram::Histogram;
 use quinn::StreamId;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant, SystemTime};
-#[cfg(feature = "json-output")]
-use {std::fs::File, std::io, std::path::Path};
 
 pub struct Stats {
     /// Test start time
@@ -17,6 +18,10 @@ pub struct Stats {
     download_duration: Histogram<u64>,
     /// Time from finishing the upload until receiving the first byte of the response
     fbl: Histogram<u64>,
+    /// Time from the scheduled start of a request until receiving the end of the response
+    request_latency: Histogram<u64>,
+    /// Round-trip times of datagrams echoed by the server
+    datagram_rtt: Histogram<u64>,
     /// Throughput for uploads
     upload_throughput: Histogram<u64>,
     /// Throughput for downloads
@@ -35,6 +40,8 @@ impl Default for Stats {
             upload_duration: Histogram::new(3).unwrap(),
             download_duration: Histogram::new(3).unwrap(),
             fbl: Histogram::new(3).unwrap(),
+            request_latency: Histogram::new(3).unwrap(),
+            datagram_rtt: Histogram::new(3).unwrap(),
             upload_throughput: Histogram::new(3).unwrap(),
             download_throughput: Histogram::new(3).unwrap(),
             requests: 0,
@@ -44,8 +51,18 @@ impl Default for Stats {
 }
 
 impl Stats {
-    pub fn on_interval(&mut self, start: Instant, stream_stats: &OpenStreamStats) {
+    pub fn on_interval(
+        &mut self,
+        start: Instant,
+        stream_stats: &OpenStreamStats,
+        datagram_stats: &DatagramStats,
+    ) {
         let mut interval = Interval::new(start - self.start_instant, self.start_instant.elapsed());
+        {
+            let mut rtts = datagram_stats.0.lock().unwrap();
+            self.datagram_rtt.add(&*rtts).unwrap();
+            rtts.reset();
+        }
         let mut guard = stream_stats.0.lock().unwrap();
 
         guard.retain(|stream_stats| {
@@ -72,6 +89,9 @@ impl Stats {
                 self.fbl
                     .record(stream_stats.first_byte_latency.load(Ordering::SeqCst))
                     .unwrap();
+                self.request_latency
+                    .record(stream_stats.request_latency.load(Ordering::SeqCst))
+                    .unwrap();
                 self.requests += 1;
             }
         }
@@ -116,6 +136,47 @@ impl Stats {
         print_metric("P90 ", |hist| hist.value_at_quantile(0.90));
         print_metric("P100", |hist| hist.value_at_quantile(1.00));
         println!();
+
+        println!("Latency metrics:\n");
+
+        println!("      │ Request Latency │ Datagram RTT");
+        println!("──────┼─────────────────┼─────────────");
+
+        let print_latency = |label: &'static str, quantile: f64| {
+            println!(
+                " {} │ {:>15.2?} │ {:>12.2?}",
+                label,
+                Duration::from_micros(self.request_latency.value_at_quantile(quantile)),
+                Duration::from_micros(self.datagram_rtt.value_at_quantile(quantile)),
+            );
+        };
+
+        print_latency("P50 ", 0.50);
+        print_latency("P90 ", 0.90);
+        print_latency("P99 ", 0.99);
+        print_latency("P999", 0.999);
+        print_latency("P100", 1.00);
+        println!();
+    }
+
+    /// Write the latency distributions to `dir`, one file per metric
+    ///
+    /// Files use the percentile distribution format of HdrHistogram, with values in
+    /// milliseconds, which can be plotted with e.g. the HdrHistogram plotter.
+    pub fn write_hgrm(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        for (name, hist) in [
+            ("upload_duration", &self.upload_duration),
+            ("download_duration", &self.download_duration),
+            ("fbl", &self.fbl),
+            ("request_latency", &self.request_latency),
+            ("datagram_rtt", &self.datagram_rtt),
+        ] {
+            let mut out = BufWriter::new(File::create(dir.join(format!("{name}.hgrm")))?);
+            write_percentiles(hist, &mut out)?;
+            out.flush()?;
+        }
+        Ok(())
     }
 
     #[cfg(feature = "json-output")]
@@ -130,6 +191,63 @@ impl Stats {
     }
 }
 
+/// Write `hist`, recorded in microseconds, in HdrHistogram's percentile distribution format
+fn write_percentiles(hist: &Histogram<u64>, out: &mut impl Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>12} {:>14} {:>10} {:>14}\n",
+        "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+    )?;
+    let mut count = 0;
+    for value in hist.iter_quantiles(1) {
+        count += value.count_since_last_iteration();
+        let quantile = value.quantile_iterated_to();
+        let inverse = match quantile < 1.0 {
+            true => format!("{:>14.2}", 1.0 / (1.0 - quantile)),
+            false => String::new(),
+        };
+        writeln!(
+            out,
+            "{:>12.3} {:>14.12} {:>10} {}",
+            value.value_iterated_to() as f64 / 1000.0,
+            quantile,
+            count,
+            inverse,
+        )?;
+    }
+    writeln!(
+        out,
+        "#[Mean    = {:>12.3}, StdDeviation   = {:>12.3}]",
+        hist.mean() / 1000.0,
+        hist.stdev() / 1000.0,
+    )?;
+    writeln!(
+        out,
+        "#[Max     = {:>12.3}, Total count    = {:>12}]",
+        hist.max() as f64 / 1000.0,
+        hist.len(),
+    )
+}
+
+/// Round-trip times of datagrams received since the last interval, in microseconds
+#[derive(Clone)]
+pub struct DatagramStats(Arc<Mutex<Histogram<u64>>>);
+
+impl DatagramStats {
+    pub fn on_rtt(&self, rtt: Duration) {
+        self.0
+            .lock()
+            .unwrap()
+            .saturating_record(rtt.as_micros() as u64);
+    }
+}
+
+impl Default for DatagramStats {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Histogram::new(3).unwrap())))
+    }
+}
+
 /// Statistics for the currently open streams
 #[derive(Clone, Default)]
 pub struct OpenStreamStats(Arc<Mutex<Vec<Arc<StreamStats>>>>);
@@ -144,6 +262,7 @@ impl OpenStreamStats {
             finished: Default::default(),
             duration: Default::default(),
             first_byte_latency: Default::default(),
+            request_latency: Default::default(),
         };
         let send_stream_stats = Arc::new(send_stream_stats);
         self.push(send_stream_stats.clone());
@@ -159,6 +278,7 @@ impl OpenStreamStats {
             finished: Default::default(),
             duration: Default::default(),
             first_byte_latency: Default::default(),
+            request_latency: Default::default(),
         };
         let recv_stream_stats = Arc::new(recv_stream_stats);
         self.push(recv_stream_stats.clone());
@@ -178,6 +298,7 @@ pub struct StreamStats {
     finished: AtomicBool,
     duration: AtomicU64,
     first_byte_latency: AtomicU64,
+    request_latency: AtomicU64,
 }
 
 impl StreamStats {
@@ -190,6 +311,11 @@ impl StreamStats {
         self.bytes.fetch_add(bytes, Ordering::SeqCst);
     }
 
+    pub fn on_response(&self, latency: Duration) {
+        self.request_latency
+            .store(latency.as_micros() as u64, Ordering::SeqCst);
+    }
+
     pub fn finish(&self, duration: Duration) {
         self.duration
             .store(duration.as_micros() as u64, Ordering::SeqCst);
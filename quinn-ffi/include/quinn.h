@@ -0,0 +1,111 @@
+/*
+ * C bindings for quinn-proto
+ *
+ * See the crate documentation of quinn-ffi for usage. Times are microseconds on a monotonic clock
+ * chosen by the caller. Connections and streams are identified by integers scoped to their
+ * endpoint.
+ */
+
+#ifndef QUINN_H
+#define QUINN_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum QuinnResult {
+    QUINN_OK = 0,
+    QUINN_INVALID_ARGUMENT = 1,
+    QUINN_BLOCKED = 2,
+    QUINN_BUFFER_TOO_SMALL = 3,
+    QUINN_CONNECTION_CLOSED = 4,
+    QUINN_STREAM_CLOSED = 5,
+    /* An internal error occurred; the endpoint should be freed */
+    QUINN_PANICKED = 6,
+} QuinnResult;
+
+typedef struct QuinnAddr {
+    /* Network order; only the first 4 bytes are used for IPv4 */
+    uint8_t ip[16];
+    /* Host order */
+    uint16_t port;
+    /* 4 or 6 */
+    uint8_t family;
+} QuinnAddr;
+
+typedef enum QuinnEventKind {
+    QUINN_EVENT_CONNECTED = 1,
+    /* `stream` is a QuinnCloseReason, `error_code` the application or transport error code */
+    QUINN_EVENT_CONNECTION_LOST = 2,
+    /* `stream` is 0 for bidirectional and 1 for unidirectional streams */
+    QUINN_EVENT_STREAM_OPENED = 3,
+    QUINN_EVENT_STREAM_READABLE = 4,
+    QUINN_EVENT_STREAM_WRITABLE = 5,
+    QUINN_EVENT_STREAM_FINISHED = 6,
+    QUINN_EVENT_STREAM_STOPPED = 7,
+    /* `stream` is 0 for bidirectional and 1 for unidirectional streams */
+    QUINN_EVENT_STREAM_AVAILABLE = 8,
+} QuinnEventKind;
+
+typedef enum QuinnCloseReason {
+    QUINN_CLOSE_APPLICATION_CLOSED = 0,
+    QUINN_CLOSE_CONNECTION_CLOSED = 1,
+    QUINN_CLOSE_TRANSPORT_ERROR = 2,
+    QUINN_CLOSE_TIMED_OUT = 3,
+    QUINN_CLOSE_RESET = 4,
+    QUINN_CLOSE_LOCALLY_CLOSED = 5,
+    QUINN_CLOSE_VERSION_MISMATCH = 6,
+    QUINN_CLOSE_CIDS_EXHAUSTED = 7,
+} QuinnCloseReason;
+
+typedef struct QuinnEvent {
+    QuinnEventKind kind;
+    uint64_t stream;
+    uint64_t error_code;
+} QuinnEvent;
+
+typedef struct QuinnEndpoint QuinnEndpoint;
+
+QuinnEndpoint *quinn_endpoint_new_server(const uint8_t *cert, size_t cert_len, const uint8_t *key,
+                                         size_t key_len);
+QuinnEndpoint *quinn_endpoint_new_client(const uint8_t *root, size_t root_len);
+void quinn_endpoint_free(QuinnEndpoint *ep);
+
+QuinnResult quinn_endpoint_connect(QuinnEndpoint *ep, uint64_t now, const QuinnAddr *remote,
+                                   const char *server_name, uint64_t *out_conn);
+QuinnResult quinn_endpoint_handle_datagram(QuinnEndpoint *ep, uint64_t now,
+                                           const QuinnAddr *remote, const uint8_t *data,
+                                           size_t len);
+bool quinn_endpoint_accept(QuinnEndpoint *ep, uint64_t *out_conn);
+QuinnResult quinn_endpoint_poll_transmit(QuinnEndpoint *ep, uint64_t now,
+                                         QuinnAddr *out_destination, uint8_t *buf, size_t buf_len,
+                                         size_t *out_len);
+/* Returns UINT64_MAX if no timer is armed */
+uint64_t quinn_endpoint_poll_timeout(QuinnEndpoint *ep);
+void quinn_endpoint_handle_timeout(QuinnEndpoint *ep, uint64_t now);
+
+bool quinn_connection_poll_event(QuinnEndpoint *ep, uint64_t conn, QuinnEvent *out_event);
+QuinnResult quinn_connection_close(QuinnEndpoint *ep, uint64_t conn, uint64_t now,
+                                   uint64_t error_code, const uint8_t *reason, size_t reason_len);
+bool quinn_connection_is_drained(QuinnEndpoint *ep, uint64_t conn);
+void quinn_connection_free(QuinnEndpoint *ep, uint64_t conn);
+
+QuinnResult quinn_stream_open(QuinnEndpoint *ep, uint64_t conn, bool unidirectional,
+                              uint64_t *out_stream);
+QuinnResult quinn_stream_accept(QuinnEndpoint *ep, uint64_t conn, bool unidirectional,
+                                uint64_t *out_stream);
+QuinnResult quinn_stream_write(QuinnEndpoint *ep, uint64_t conn, uint64_t stream,
+                               const uint8_t *data, size_t len, size_t *out_written);
+QuinnResult quinn_stream_finish(QuinnEndpoint *ep, uint64_t conn, uint64_t stream);
+QuinnResult quinn_stream_read(QuinnEndpoint *ep, uint64_t conn, uint64_t stream, uint8_t *buf,
+                              size_t len, size_t *out_read, bool *out_fin);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* QUINN_H */
@@ -0,0 +1,1113 @@
+//! C bindings for quinn-proto
+//!
+//! Exposes the sans-IO endpoint and connection state machines over a C ABI, so that networking
+//! stacks written in other languages can embed them. The caller owns all I/O: it feeds received
+//! UDP datagrams to [`quinn_endpoint_handle_datagram`], sends whatever
+//! [`quinn_endpoint_poll_transmit`] yields, and calls [`quinn_endpoint_handle_timeout`] once the
+//! time reported by [`quinn_endpoint_poll_timeout`] is reached. All of these should be repeated
+//! after any other call, until no more datagrams are produced. The declarations for C are in
+//! `include/quinn.h`.
+//!
+//! Times are expressed in microseconds on a monotonic clock of the caller's choosing; all times
+//! passed to one endpoint must come from the same clock. Connections and streams are identified by
+//! integers scoped to their endpoint.
+//!
+//! # Safety
+//!
+//! Pointers must be valid for the duration of the call and buffers must be at least as long as
+//! the accompanying length. An endpoint must not be used from several threads at once, and must
+//! not be used after [`quinn_endpoint_free`]. Passing a null endpoint or required pointer yields
+//! [`QuinnResult::InvalidArgument`].
+//!
+//! Panics never unwind into the caller. A function that panics returns [`QuinnResult::Panicked`],
+//! or the same value as for invalid arguments if it doesn't return a [`QuinnResult`]. The endpoint
+//! may be left inconsistent, so it should only be freed afterwards.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    ffi::{CStr, c_char},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use bytes::{Bytes, BytesMut};
+use proto::{
+    ClientConfig, Connection, ConnectionError, ConnectionHandle, DatagramEvent, Dir, Endpoint,
+    EndpointConfig, EndpointEvent, Event, ReadError, ServerConfig, StreamEvent, StreamId, VarInt,
+    WriteError,
+};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+/// Outcome of a fallible operation
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuinnResult {
+    /// The operation succeeded
+    Ok = 0,
+    /// A pointer was null, or an argument was malformed or refers to nothing
+    InvalidArgument = 1,
+    /// The operation can't make progress at this time
+    Blocked = 2,
+    /// The output buffer is too small; the required length is reported
+    BufferTooSmall = 3,
+    /// The connection is closed
+    ConnectionClosed = 4,
+    /// The stream was reset, stopped or finished by either peer
+    StreamClosed = 5,
+    /// An internal error occurred; the endpoint should be freed
+    Panicked = 6,
+}
+
+/// An IPv4 or IPv6 socket address
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct QuinnAddr {
+    /// Address bytes in network order; only the first 4 are used for IPv4
+    pub ip: [u8; 16],
+    /// Port in host order
+    pub port: u16,
+    /// 4 for IPv4, 6 for IPv6
+    pub family: u8,
+}
+
+impl QuinnAddr {
+    fn to_socket_addr(self) -> Option<SocketAddr> {
+        let ip = match self.family {
+            4 => IpAddr::V4(Ipv4Addr::new(
+                self.ip[0], self.ip[1], self.ip[2], self.ip[3],
+            )),
+            6 => IpAddr::V6(Ipv6Addr::from(self.ip)),
+            _ => return None,
+        };
+        Some(SocketAddr::new(ip, self.port))
+    }
+
+    fn from_socket_addr(addr: SocketAddr) -> Self {
+        let mut ip = [0; 16];
+        let family = match addr.ip() {
+            IpAddr::V4(v4) => {
+                ip[..4].copy_from_slice(&v4.octets());
+                4
+            }
+            IpAddr::V6(v6) => {
+                ip = v6.octets();
+                6
+            }
+        };
+        Self {
+            ip,
+            port: addr.port(),
+            family,
+        }
+    }
+}
+
+/// Kind of a [`QuinnEvent`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuinnEventKind {
+    /// The handshake completed
+    Connected = 1,
+    /// The connection was closed by the peer, an error or a timeout; `stream` is a
+    /// [`QuinnCloseReason`], and `error_code` the application or transport error code, if any
+    ConnectionLost = 2,
+    /// Streams opened by the peer may be accepted; `stream` is 0 for bidirectional streams and 1
+    /// for unidirectional streams
+    StreamOpened = 3,
+    /// `stream` may have data to read
+    StreamReadable = 4,
+    /// `stream` may accept more data
+    StreamWritable = 5,
+    /// All data written to `stream` was acknowledged
+    StreamFinished = 6,
+    /// The peer asked to stop sending on `stream`, with `error_code`
+    StreamStopped = 7,
+    /// More streams may be opened; `stream` is 0 for bidirectional streams and 1 for
+    /// unidirectional streams
+    StreamAvailable = 8,
+}
+
+/// Why a connection was lost, see [`QuinnEventKind::ConnectionLost`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QuinnCloseReason {
+    /// The peer closed the connection with an application error code
+    ApplicationClosed = 0,
+    /// The peer closed the connection with a transport error code
+    ConnectionClosed = 1,
+    /// The peer violated the protocol; `error_code` is the transport error code sent to it
+    TransportError = 2,
+    /// The peer stopped responding
+    TimedOut = 3,
+    /// The peer lost the state of the connection
+    Reset = 4,
+    /// The connection was closed by [`quinn_connection_close`]
+    LocallyClosed = 5,
+    /// The peer doesn't support any QUIC version supported by this endpoint
+    VersionMismatch = 6,
+    /// No connection IDs were left to use
+    CidsExhausted = 7,
+}
+
+impl QuinnCloseReason {
+    /// The reason and error code to report for `error`
+    fn from_error(error: &ConnectionError) -> (Self, u64) {
+        match error {
+            ConnectionError::ApplicationClosed(close) => {
+                (Self::ApplicationClosed, close.error_code.into_inner())
+            }
+            ConnectionError::ConnectionClosed(close) => {
+                (Self::ConnectionClosed, close.error_code.into())
+            }
+            ConnectionError::TransportError(error) => (Self::TransportError, error.code.into()),
+            ConnectionError::TimedOut(_) => (Self::TimedOut, 0),
+            ConnectionError::Reset => (Self::Reset, 0),
+            ConnectionError::LocallyClosed => (Self::LocallyClosed, 0),
+            ConnectionError::VersionMismatch => (Self::VersionMismatch, 0),
+            ConnectionError::CidsExhausted => (Self::CidsExhausted, 0),
+        }
+    }
+}
+
+/// An event of interest to the application, see [`quinn_connection_poll_event`]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct QuinnEvent {
+    /// What happened
+    pub kind: QuinnEventKind,
+    /// The stream concerned, a direction or a [`QuinnCloseReason`], depending on `kind`
+    pub stream: u64,
+    /// Error code supplied by the peer or sent to it, if any
+    pub error_code: u64,
+}
+
+/// A QUIC endpoint and its connections
+pub struct QuinnEndpoint {
+    endpoint: Endpoint,
+    connections: HashMap<ConnectionHandle, ConnectionState>,
+    /// Order in which connections get to send, starting with the one that sent least recently
+    send_order: VecDeque<ConnectionHandle>,
+    client_config: Option<ClientConfig>,
+    /// Connections accepted but not yet reported by [`quinn_endpoint_accept`]
+    accepted: VecDeque<ConnectionHandle>,
+    /// Datagrams waiting to be returned by [`quinn_endpoint_poll_transmit`]
+    outgoing: VecDeque<(SocketAddr, Vec<u8>)>,
+    /// Instant corresponding to time zero of the caller's clock
+    epoch: Instant,
+    buf: Vec<u8>,
+}
+
+struct ConnectionState {
+    conn: Connection,
+    /// Whether the connection emitted its last endpoint event
+    ///
+    /// The endpoint is only informed once the connection is freed, so that its handle isn't reused
+    /// while the application might still refer to it.
+    drained: bool,
+}
+
+impl QuinnEndpoint {
+    fn new(server_config: Option<ServerConfig>, client_config: Option<ClientConfig>) -> Self {
+        Self {
+            endpoint: Endpoint::new(
+                Arc::new(EndpointConfig::default()),
+                server_config.map(Arc::new),
+                false,
+            ),
+            connections: HashMap::new(),
+            send_order: VecDeque::new(),
+            client_config,
+            accepted: VecDeque::new(),
+            outgoing: VecDeque::new(),
+            epoch: Instant::now(),
+            buf: Vec::new(),
+        }
+    }
+
+    fn instant(&self, micros: u64) -> Instant {
+        self.epoch + Duration::from_micros(micros)
+    }
+
+    fn micros(&self, instant: Instant) -> u64 {
+        // Round up, so that timers have expired when the caller reports the time
+        let nanos = instant.saturating_duration_since(self.epoch).as_nanos();
+        u64::try_from(nanos.div_ceil(1000)).unwrap_or(u64::MAX)
+    }
+
+    fn insert(&mut self, ch: ConnectionHandle, conn: Connection) {
+        self.connections.insert(
+            ch,
+            ConnectionState {
+                conn,
+                drained: false,
+            },
+        );
+        self.send_order.push_back(ch);
+    }
+
+    /// Exchange events between a connection and the endpoint
+    fn process(&mut self, ch: ConnectionHandle) {
+        let Some(state) = self.connections.get_mut(&ch) else {
+            return;
+        };
+        while let Some(event) = state.conn.poll_endpoint_events() {
+            if event.is_drained() {
+                state.drained = true;
+                continue;
+            }
+            if let Some(event) = self.endpoint.handle_event(ch, event) {
+                state.conn.handle_event(event);
+            }
+        }
+    }
+
+    fn connection(&mut self, conn: u64) -> Option<(ConnectionHandle, &mut Connection)> {
+        let ch = ConnectionHandle(usize::try_from(conn).ok()?);
+        let state = self.connections.get_mut(&ch)?;
+        Some((ch, &mut state.conn))
+    }
+}
+
+/// Create a server endpoint presenting a single certificate
+///
+/// `cert` is a DER-encoded certificate and `key` its DER-encoded PKCS #8 private key. Returns null
+/// if they are invalid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_new_server(
+    cert: *const u8,
+    cert_len: usize,
+    key: *const u8,
+    key_len: usize,
+) -> *mut QuinnEndpoint {
+    guard(ptr::null_mut(), || {
+        if cert.is_null() || key.is_null() {
+            return ptr::null_mut();
+        }
+        let cert = unsafe { slice::from_raw_parts(cert, cert_len) };
+        let key = unsafe { slice::from_raw_parts(key, key_len) };
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key.to_vec()));
+        match ServerConfig::with_single_cert(vec![CertificateDer::from(cert.to_vec())], key) {
+            Ok(config) => Box::into_raw(Box::new(QuinnEndpoint::new(Some(config), None))),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Create a client endpoint trusting a single root certificate
+///
+/// `root` is a DER-encoded certificate. Returns null if it is invalid.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_new_client(
+    root: *const u8,
+    root_len: usize,
+) -> *mut QuinnEndpoint {
+    guard(ptr::null_mut(), || {
+        if root.is_null() {
+            return ptr::null_mut();
+        }
+        let root = unsafe { slice::from_raw_parts(root, root_len) };
+        let mut roots = rustls::RootCertStore::empty();
+        if roots.add(CertificateDer::from(root.to_vec())).is_err() {
+            return ptr::null_mut();
+        }
+        match ClientConfig::with_root_certificates(Arc::new(roots)) {
+            Ok(config) => Box::into_raw(Box::new(QuinnEndpoint::new(None, Some(config)))),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Destroy an endpoint and all of its connections
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_free(ep: *mut QuinnEndpoint) {
+    guard((), || {
+        if !ep.is_null() {
+            drop(unsafe { Box::from_raw(ep) });
+        }
+    })
+}
+
+/// Initiate a connection to `remote`, writing its identifier to `out_conn`
+///
+/// `server_name` is a null-terminated string used to verify the server's certificate.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_connect(
+    ep: *mut QuinnEndpoint,
+    now: u64,
+    remote: *const QuinnAddr,
+    server_name: *const c_char,
+    out_conn: *mut u64,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), Some(remote), false, false) = (
+            unsafe { ep.as_mut() },
+            unsafe { remote.as_ref() }.and_then(|x| x.to_socket_addr()),
+            server_name.is_null(),
+            out_conn.is_null(),
+        ) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let Ok(server_name) = unsafe { CStr::from_ptr(server_name) }.to_str() else {
+            return QuinnResult::InvalidArgument;
+        };
+        let Some(config) = ep.client_config.clone() else {
+            return QuinnResult::InvalidArgument;
+        };
+        let now = ep.instant(now);
+        match ep.endpoint.connect(now, config, remote, server_name) {
+            Ok((ch, conn)) => {
+                ep.insert(ch, conn);
+                unsafe { *out_conn = ch.0 as u64 };
+                QuinnResult::Ok
+            }
+            Err(_) => QuinnResult::InvalidArgument,
+        }
+    })
+}
+
+/// Process a UDP datagram received from `remote`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_handle_datagram(
+    ep: *mut QuinnEndpoint,
+    now: u64,
+    remote: *const QuinnAddr,
+    data: *const u8,
+    len: usize,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), Some(remote), false) = (
+            unsafe { ep.as_mut() },
+            unsafe { remote.as_ref() }.and_then(|x| x.to_socket_addr()),
+            data.is_null(),
+        ) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let data = BytesMut::from(unsafe { slice::from_raw_parts(data, len) });
+        let now = ep.instant(now);
+        let mut buf = std::mem::take(&mut ep.buf);
+        buf.clear();
+        match ep.endpoint.handle(now, remote, None, None, data, &mut buf) {
+            Some(DatagramEvent::ConnectionEvent(ch, event)) => {
+                if let Some(state) = ep.connections.get_mut(&ch) {
+                    state.conn.handle_event(event);
+                    ep.process(ch);
+                }
+            }
+            Some(DatagramEvent::NewConnection(incoming)) => {
+                buf.clear();
+                match ep.endpoint.accept(incoming, now, &mut buf, None) {
+                    Ok((ch, conn)) => {
+                        ep.insert(ch, conn);
+                        ep.accepted.push_back(ch);
+                        ep.process(ch);
+                    }
+                    Err(e) => {
+                        if let Some(transmit) = e.response {
+                            ep.outgoing
+                                .push_back((transmit.destination, buf[..transmit.size].to_vec()));
+                        }
+                    }
+                }
+            }
+            Some(DatagramEvent::Response(transmit)) => {
+                ep.outgoing
+                    .push_back((transmit.destination, buf[..transmit.size].to_vec()));
+            }
+            None => {}
+        }
+        ep.buf = buf;
+        QuinnResult::Ok
+    })
+}
+
+/// Take a connection initiated by a peer, writing its identifier to `out_conn`
+///
+/// Returns false if there is none.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_accept(ep: *mut QuinnEndpoint, out_conn: *mut u64) -> bool {
+    guard(false, || {
+        let (Some(ep), false) = (unsafe { ep.as_mut() }, out_conn.is_null()) else {
+            return false;
+        };
+        let Some(ch) = ep.accepted.pop_front() else {
+            return false;
+        };
+        unsafe { *out_conn = ch.0 as u64 };
+        true
+    })
+}
+
+/// Produce the next UDP datagram to send
+///
+/// On success, writes the datagram to `buf`, its length to `out_len` and its destination to
+/// `out_destination`. Returns [`QuinnResult::Blocked`] if there is nothing to send. If `buf` is too
+/// small, the required length is written to `out_len` and the datagram is retained.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_poll_transmit(
+    ep: *mut QuinnEndpoint,
+    now: u64,
+    out_destination: *mut QuinnAddr,
+    buf: *mut u8,
+    buf_len: usize,
+    out_len: *mut usize,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), false, false, false) = (
+            unsafe { ep.as_mut() },
+            out_destination.is_null(),
+            buf.is_null(),
+            out_len.is_null(),
+        ) else {
+            return QuinnResult::InvalidArgument;
+        };
+        if ep.outgoing.is_empty() {
+            let now = ep.instant(now);
+            let mut scratch = std::mem::take(&mut ep.buf);
+            // Move each connection polled to the back, so that a busy connection can't starve others
+            for _ in 0..ep.send_order.len() {
+                let ch = ep.send_order.pop_front().unwrap();
+                ep.send_order.push_back(ch);
+                scratch.clear();
+                let state = ep.connections.get_mut(&ch).unwrap();
+                let transmit = state.conn.poll_transmit(now, 1, &mut scratch);
+                ep.process(ch);
+                if let Some(transmit) = transmit {
+                    ep.outgoing
+                        .push_back((transmit.destination, scratch[..transmit.size].to_vec()));
+                    break;
+                }
+            }
+            ep.buf = scratch;
+        }
+
+        let Some((destination, datagram)) = ep.outgoing.front() else {
+            return QuinnResult::Blocked;
+        };
+        unsafe { *out_len = datagram.len() };
+        if datagram.len() > buf_len {
+            return QuinnResult::BufferTooSmall;
+        }
+        unsafe {
+            buf.copy_from_nonoverlapping(datagram.as_ptr(), datagram.len());
+            *out_destination = QuinnAddr::from_socket_addr(*destination);
+        }
+        ep.outgoing.pop_front();
+        QuinnResult::Ok
+    })
+}
+
+/// Time at which [`quinn_endpoint_handle_timeout`] should next be called
+///
+/// Returns `UINT64_MAX` if no timer is armed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_poll_timeout(ep: *mut QuinnEndpoint) -> u64 {
+    guard(u64::MAX, || {
+        let Some(ep) = (unsafe { ep.as_mut() }) else {
+            return u64::MAX;
+        };
+        ep.connections
+            .values_mut()
+            .filter_map(|state| state.conn.poll_timeout())
+            .min()
+            .map_or(u64::MAX, |timeout| ep.micros(timeout))
+    })
+}
+
+/// Process timers that expired by `now`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_endpoint_handle_timeout(ep: *mut QuinnEndpoint, now: u64) {
+    guard((), || {
+        let Some(ep) = (unsafe { ep.as_mut() }) else {
+            return;
+        };
+        let now = ep.instant(now);
+        let handles = ep.connections.keys().copied().collect::<Vec<_>>();
+        for ch in handles {
+            let state = ep.connections.get_mut(&ch).unwrap();
+            if state
+                .conn
+                .poll_timeout()
+                .is_some_and(|timeout| timeout <= now)
+            {
+                state.conn.handle_timeout(now);
+                ep.process(ch);
+            }
+        }
+    })
+}
+
+/// Take the next event of a connection, writing it to `out_event`
+///
+/// Returns false if there is none.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_connection_poll_event(
+    ep: *mut QuinnEndpoint,
+    conn: u64,
+    out_event: *mut QuinnEvent,
+) -> bool {
+    guard(false, || {
+        let (Some(ep), false) = (unsafe { ep.as_mut() }, out_event.is_null()) else {
+            return false;
+        };
+        let Some((_, conn)) = ep.connection(conn) else {
+            return false;
+        };
+        let dir = |dir: Dir| match dir {
+            Dir::Bi => 0,
+            Dir::Uni => 1,
+        };
+        while let Some(event) = conn.poll() {
+            let (kind, stream, error_code) = match event {
+                Event::Connected => (QuinnEventKind::Connected, 0, 0),
+                Event::ConnectionLost { reason } => {
+                    let (reason, error_code) = QuinnCloseReason::from_error(&reason);
+                    (QuinnEventKind::ConnectionLost, reason as u64, error_code)
+                }
+                Event::Stream(StreamEvent::Opened { dir: d }) => {
+                    (QuinnEventKind::StreamOpened, dir(d), 0)
+                }
+                Event::Stream(StreamEvent::Readable { id }) => {
+                    (QuinnEventKind::StreamReadable, id.into(), 0)
+                }
+                Event::Stream(StreamEvent::Writable { id }) => {
+                    (QuinnEventKind::StreamWritable, id.into(), 0)
+                }
+                Event::Stream(StreamEvent::Finished { id }) => {
+                    (QuinnEventKind::StreamFinished, id.into(), 0)
+                }
+                Event::Stream(StreamEvent::Stopped { id, error_code }) => (
+                    QuinnEventKind::StreamStopped,
+                    id.into(),
+                    error_code.into_inner(),
+                ),
+                Event::Stream(StreamEvent::Available { dir: d }) => {
+                    (QuinnEventKind::StreamAvailable, dir(d), 0)
+                }
+                _ => continue,
+            };
+            unsafe {
+                *out_event = QuinnEvent {
+                    kind,
+                    stream,
+                    error_code,
+                };
+            }
+            return true;
+        }
+        false
+    })
+}
+
+/// Close a connection immediately, with an application error code and reason
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_connection_close(
+    ep: *mut QuinnEndpoint,
+    conn: u64,
+    now: u64,
+    error_code: u64,
+    reason: *const u8,
+    reason_len: usize,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let Some(ep) = (unsafe { ep.as_mut() }) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let reason = match reason.is_null() {
+            true => Bytes::new(),
+            false => Bytes::copy_from_slice(unsafe { slice::from_raw_parts(reason, reason_len) }),
+        };
+        let Ok(error_code) = VarInt::from_u64(error_code) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let now = ep.instant(now);
+        let Some((ch, conn)) = ep.connection(conn) else {
+            return QuinnResult::InvalidArgument;
+        };
+        conn.close(now, error_code, reason);
+        ep.process(ch);
+        QuinnResult::Ok
+    })
+}
+
+/// Whether a connection has been fully shut down, and can be freed without affecting the peer
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_connection_is_drained(ep: *mut QuinnEndpoint, conn: u64) -> bool {
+    guard(false, || {
+        let Some(ep) = (unsafe { ep.as_mut() }) else {
+            return false;
+        };
+        let Ok(ch) = usize::try_from(conn).map(ConnectionHandle) else {
+            return false;
+        };
+        ep.connections.get(&ch).is_some_and(|state| state.drained)
+    })
+}
+
+/// Release the state of a connection
+///
+/// Connections that aren't drained are abandoned without notifying the peer.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_connection_free(ep: *mut QuinnEndpoint, conn: u64) {
+    guard((), || {
+        let Some(ep) = (unsafe { ep.as_mut() }) else {
+            return;
+        };
+        let Ok(ch) = usize::try_from(conn).map(ConnectionHandle) else {
+            return;
+        };
+        if ep.connections.remove(&ch).is_some() {
+            ep.accepted.retain(|&x| x != ch);
+            ep.send_order.retain(|&x| x != ch);
+            ep.endpoint.handle_event(ch, EndpointEvent::drained());
+        }
+    })
+}
+
+/// Open a stream, writing its identifier to `out_stream`
+///
+/// Returns [`QuinnResult::Blocked`] if the peer doesn't permit more streams at this time.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_stream_open(
+    ep: *mut QuinnEndpoint,
+    conn: u64,
+    unidirectional: bool,
+    out_stream: *mut u64,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), false) = (unsafe { ep.as_mut() }, out_stream.is_null()) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let Some((_, conn)) = ep.connection(conn) else {
+            return QuinnResult::InvalidArgument;
+        };
+        if conn.is_closed() {
+            return QuinnResult::ConnectionClosed;
+        }
+        match conn.streams().open(direction(unidirectional)) {
+            Some(id) => {
+                unsafe { *out_stream = id.into() };
+                QuinnResult::Ok
+            }
+            None => QuinnResult::Blocked,
+        }
+    })
+}
+
+/// Take a stream opened by the peer, writing its identifier to `out_stream`
+///
+/// Returns [`QuinnResult::Blocked`] if there is none.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_stream_accept(
+    ep: *mut QuinnEndpoint,
+    conn: u64,
+    unidirectional: bool,
+    out_stream: *mut u64,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), false) = (unsafe { ep.as_mut() }, out_stream.is_null()) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let Some((_, conn)) = ep.connection(conn) else {
+            return QuinnResult::InvalidArgument;
+        };
+        match conn.streams().accept(direction(unidirectional)) {
+            Some(id) => {
+                unsafe { *out_stream = id.into() };
+                QuinnResult::Ok
+            }
+            None => QuinnResult::Blocked,
+        }
+    })
+}
+
+/// Write data to a stream, writing the number of bytes accepted to `out_written`
+///
+/// Returns [`QuinnResult::Blocked`] if no data could be accepted due to flow control.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_stream_write(
+    ep: *mut QuinnEndpoint,
+    conn: u64,
+    stream: u64,
+    data: *const u8,
+    len: usize,
+    out_written: *mut usize,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), Some(stream), false, false) = (
+            unsafe { ep.as_mut() },
+            stream_id(stream),
+            data.is_null(),
+            out_written.is_null(),
+        ) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let data = unsafe { slice::from_raw_parts(data, len) };
+        let Some((ch, conn)) = ep.connection(conn) else {
+            return QuinnResult::InvalidArgument;
+        };
+        if conn.is_closed() {
+            return QuinnResult::ConnectionClosed;
+        }
+        let result = conn.send_stream(stream).write(data);
+        ep.process(ch);
+        match result {
+            Ok(written) => {
+                unsafe { *out_written = written };
+                QuinnResult::Ok
+            }
+            Err(WriteError::Blocked) => QuinnResult::Blocked,
+            Err(_) => QuinnResult::StreamClosed,
+        }
+    })
+}
+
+/// Indicate that no more data will be written to a stream
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_stream_finish(
+    ep: *mut QuinnEndpoint,
+    conn: u64,
+    stream: u64,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), Some(stream)) = (unsafe { ep.as_mut() }, stream_id(stream)) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let Some((ch, conn)) = ep.connection(conn) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let result = conn.send_stream(stream).finish();
+        ep.process(ch);
+        match result {
+            Ok(()) => QuinnResult::Ok,
+            Err(_) => QuinnResult::StreamClosed,
+        }
+    })
+}
+
+/// Read data from a stream in order
+///
+/// Writes the number of bytes read to `out_read`, and whether the end of the stream was reached to
+/// `out_fin`. Returns [`QuinnResult::Blocked`] if no data is available yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn quinn_stream_read(
+    ep: *mut QuinnEndpoint,
+    conn: u64,
+    stream: u64,
+    buf: *mut u8,
+    len: usize,
+    out_read: *mut usize,
+    out_fin: *mut bool,
+) -> QuinnResult {
+    guard(QuinnResult::Panicked, || {
+        let (Some(ep), Some(stream), false, false, false) = (
+            unsafe { ep.as_mut() },
+            stream_id(stream),
+            buf.is_null(),
+            out_read.is_null(),
+            out_fin.is_null(),
+        ) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let buf = unsafe { slice::from_raw_parts_mut(buf, len) };
+        let Some((ch, conn)) = ep.connection(conn) else {
+            return QuinnResult::InvalidArgument;
+        };
+        let mut recv = conn.recv_stream(stream);
+        let Ok(mut chunks) = recv.read(true) else {
+            return QuinnResult::StreamClosed;
+        };
+        let mut read = 0;
+        let mut fin = false;
+        let mut result = QuinnResult::Ok;
+        while read < buf.len() {
+            match chunks.next(buf.len() - read) {
+                Ok(Some(chunk)) => {
+                    buf[read..read + chunk.bytes.len()].copy_from_slice(&chunk.bytes);
+                    read += chunk.bytes.len();
+                }
+                Ok(None) => {
+                    fin = true;
+                    break;
+                }
+                Err(ReadError::Blocked) => {
+                    if read == 0 {
+                        result = QuinnResult::Blocked;
+                    }
+                    break;
+                }
+                Err(ReadError::Reset(_)) => {
+                    result = QuinnResult::StreamClosed;
+                    break;
+                }
+            }
+        }
+        // Flow control credit is sent by the next `quinn_endpoint_poll_transmit`
+        let _ = chunks.finalize();
+        ep.process(ch);
+        unsafe {
+            *out_read = read;
+            *out_fin = fin;
+        }
+        result
+    })
+}
+
+/// Run `f`, returning `fallback` if it panics, so that panics don't unwind across the C ABI
+fn guard<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(fallback)
+}
+
+fn direction(unidirectional: bool) -> Dir {
+    match unidirectional {
+        true => Dir::Uni,
+        false => Dir::Bi,
+    }
+}
+
+fn stream_id(id: u64) -> Option<StreamId> {
+    VarInt::from_u64(id).ok().map(StreamId::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Exchange datagrams and fire timers until neither endpoint has anything to send
+    unsafe fn drive(now: &mut u64, a: *mut QuinnEndpoint, b: *mut QuinnEndpoint) {
+        let addr_a = QuinnAddr::from_socket_addr("127.0.0.1:1".parse().unwrap());
+        let addr_b = QuinnAddr::from_socket_addr("127.0.0.1:2".parse().unwrap());
+        let mut buf = [0; 1500];
+        let mut destination = addr_a;
+        let mut len = 0;
+        for _ in 0..100 {
+            let mut idle = true;
+            for (from, to, from_addr) in [(a, b, addr_a), (b, a, addr_b)] {
+                while unsafe {
+                    quinn_endpoint_poll_transmit(
+                        from,
+                        *now,
+                        &mut destination,
+                        buf.as_mut_ptr(),
+                        buf.len(),
+                        &mut len,
+                    )
+                } == QuinnResult::Ok
+                {
+                    idle = false;
+                    let result = unsafe {
+                        quinn_endpoint_handle_datagram(to, *now, &from_addr, buf.as_ptr(), len)
+                    };
+                    assert_eq!(result, QuinnResult::Ok);
+                }
+            }
+            if idle {
+                return;
+            }
+            *now += 1_000;
+            unsafe {
+                quinn_endpoint_handle_timeout(a, *now);
+                quinn_endpoint_handle_timeout(b, *now);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_exchange() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert_der = cert.cert.der().to_vec();
+        let key_der = cert.signing_key.serialize_der();
+        let mut now = 0;
+        unsafe {
+            let server = quinn_endpoint_new_server(
+                cert_der.as_ptr(),
+                cert_der.len(),
+                key_der.as_ptr(),
+                key_der.len(),
+            );
+            let client = quinn_endpoint_new_client(cert_der.as_ptr(), cert_der.len());
+            assert!(!server.is_null() && !client.is_null());
+
+            let server_addr = QuinnAddr::from_socket_addr("127.0.0.1:2".parse().unwrap());
+            let mut client_conn = u64::MAX;
+            assert_eq!(
+                quinn_endpoint_connect(
+                    client,
+                    now,
+                    &server_addr,
+                    c"localhost".as_ptr(),
+                    &mut client_conn
+                ),
+                QuinnResult::Ok
+            );
+            drive(&mut now, client, server);
+            let mut server_conn = u64::MAX;
+            assert!(quinn_endpoint_accept(server, &mut server_conn));
+            let mut event = QuinnEvent {
+                kind: QuinnEventKind::ConnectionLost,
+                stream: 0,
+                error_code: 0,
+            };
+            let mut connected = false;
+            while quinn_connection_poll_event(client, client_conn, &mut event) {
+                connected |= event.kind == QuinnEventKind::Connected;
+            }
+            assert!(connected);
+
+            let mut stream = 0;
+            assert_eq!(
+                quinn_stream_open(client, client_conn, true, &mut stream),
+                QuinnResult::Ok
+            );
+            let mut written = 0;
+            assert_eq!(
+                quinn_stream_write(
+                    client,
+                    client_conn,
+                    stream,
+                    b"hello".as_ptr(),
+                    5,
+                    &mut written
+                ),
+                QuinnResult::Ok
+            );
+            assert_eq!(written, 5);
+            assert_eq!(
+                quinn_stream_finish(client, client_conn, stream),
+                QuinnResult::Ok
+            );
+            drive(&mut now, client, server);
+
+            let mut accepted = u64::MAX;
+            assert_eq!(
+                quinn_stream_accept(server, server_conn, true, &mut accepted),
+                QuinnResult::Ok
+            );
+            assert_eq!(accepted, stream);
+            let mut buf = [0; 16];
+            let (mut read, mut fin) = (0, false);
+            assert_eq!(
+                quinn_stream_read(
+                    server,
+                    server_conn,
+                    accepted,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut read,
+                    &mut fin
+                ),
+                QuinnResult::Ok
+            );
+            assert_eq!(&buf[..read], b"hello");
+            assert!(fin);
+
+            assert_eq!(
+                quinn_connection_close(client, client_conn, now, 42, ptr::null(), 0),
+                QuinnResult::Ok
+            );
+            drive(&mut now, client, server);
+            let mut lost = None;
+            while quinn_connection_poll_event(server, server_conn, &mut event) {
+                if event.kind == QuinnEventKind::ConnectionLost {
+                    lost = Some((event.stream, event.error_code));
+                }
+            }
+            assert_eq!(lost, Some((QuinnCloseReason::ApplicationClosed as u64, 42)));
+
+            quinn_connection_free(client, client_conn);
+            quinn_connection_free(server, server_conn);
+            quinn_endpoint_free(client);
+            quinn_endpoint_free(server);
+        }
+    }
+
+    #[test]
+    fn transmit_round_robin() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert_der = cert.cert.der().to_vec();
+        let key_der = cert.signing_key.serialize_der();
+        let mut now = 0;
+        unsafe {
+            let server = quinn_endpoint_new_server(
+                cert_der.as_ptr(),
+                cert_der.len(),
+                key_der.as_ptr(),
+                key_der.len(),
+            );
+            let client = quinn_endpoint_new_client(cert_der.as_ptr(), cert_der.len());
+            let server_addr = QuinnAddr::from_socket_addr("127.0.0.1:2".parse().unwrap());
+            let client_addr = QuinnAddr::from_socket_addr("127.0.0.1:1".parse().unwrap());
+            let mut conns = [(0, 0); 2];
+            for (client_conn, server_conn) in &mut conns {
+                quinn_endpoint_connect(
+                    client,
+                    now,
+                    &server_addr,
+                    c"localhost".as_ptr(),
+                    client_conn,
+                );
+                drive(&mut now, client, server);
+                assert!(quinn_endpoint_accept(server, server_conn));
+            }
+
+            // Give both connections more to send than fits in a few datagrams
+            let data = [0; 20_000];
+            for &(client_conn, _) in &conns {
+                let mut stream = 0;
+                quinn_stream_open(client, client_conn, true, &mut stream);
+                let mut written = 0;
+                quinn_stream_write(
+                    client,
+                    client_conn,
+                    stream,
+                    data.as_ptr(),
+                    data.len(),
+                    &mut written,
+                );
+                assert_eq!(written, data.len());
+            }
+            let mut buf = [0; 1500];
+            let mut destination = server_addr;
+            let mut len = 0;
+            for _ in 0..4 {
+                let result = quinn_endpoint_poll_transmit(
+                    client,
+                    now,
+                    &mut destination,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut len,
+                );
+                assert_eq!(result, QuinnResult::Ok);
+                quinn_endpoint_handle_datagram(server, now, &client_addr, buf.as_ptr(), len);
+            }
+
+            // Both connections got to send
+            for &(_, server_conn) in &conns {
+                let mut stream = u64::MAX;
+                assert_eq!(
+                    quinn_stream_accept(server, server_conn, true, &mut stream),
+                    QuinnResult::Ok
+                );
+            }
+            quinn_endpoint_free(client);
+            quinn_endpoint_free(server);
+        }
+    }
+
+    #[test]
+    fn panic_caught() {
+        assert_eq!(
+            guard(QuinnResult::Panicked, || panic!("unexpected")),
+            QuinnResult::Panicked
+        );
+        assert_eq!(
+            guard(QuinnResult::Panicked, || QuinnResult::Ok),
+            QuinnResult::Ok
+        );
+    }
+}
@@ -18,6 +18,8 @@ pub(crate) struct CidQueue {
     ///
     /// The sequence number of the active CID; must be the smallest among CIDs in `buffer`.
     offset: u64,
+    /// Number of CIDs retired so far
+    retired: u64,
 }
 
 impl CidQueue {
@@ -28,6 +30,7 @@ impl CidQueue {
             buffer,
             cursor: 0,
             offset: 0,
+            retired: 0,
         }
     }
 
@@ -73,6 +76,8 @@ impl CidQueue {
         self.cursor = (self.cursor + i) % Self::LEN;
         let orig_offset = self.offset;
         self.offset = cid.retire_prior_to + i as u64;
+        let retired = orig_offset..self.offset.min(orig_offset + Self::LEN as u64);
+        self.retired += retired.end - retired.start;
         // We don't immediately retire CIDs in the range (orig_offset +
         // Self::LEN)..self.offset. These are CIDs that we haven't yet received from a
         // NEW_CONNECTION_ID frame, since having previously received them would violate the
@@ -80,7 +85,7 @@ impl CidQueue {
         // in the future, e.g. due to reordering, we'll retire it then. This ensures we can't be
         // made to buffer an arbitrarily large number of RETIRE_CONNECTION_ID frames.
         Ok(Some((
-            orig_offset..self.offset.min(orig_offset + Self::LEN as u64),
+            retired,
             token.expect("non-initial CID missing reset token"),
         )))
     }
@@ -94,6 +99,7 @@ impl CidQueue {
         let orig_offset = self.offset;
         self.offset += i as u64;
         self.cursor = (self.cursor + i) % Self::LEN;
+        self.retired += i as u64;
         Some((cid_data.1.unwrap(), orig_offset..self.offset))
     }
 
@@ -121,6 +127,18 @@ impl CidQueue {
         self.offset
     }
 
+    /// CIDs received but not yet used, excluding the active CID, with their sequence numbers
+    pub(crate) fn available(&self) -> impl Iterator<Item = (u64, ConnectionId)> + '_ {
+        self.iter()
+            .skip(1)
+            .map(|(i, (cid, _))| (self.offset + i as u64, cid))
+    }
+
+    /// Number of CIDs retired so far
+    pub(crate) fn retired(&self) -> u64 {
+        self.retired
+    }
+
     pub(crate) const LEN: usize = 5;
 }
 
@@ -28,7 +28,11 @@ use crate::{
 mod transport;
 #[cfg(feature = "qlog")]
 pub use transport::QlogConfig;
-pub use transport::{AckFrequencyConfig, IdleTimeout, MtuDiscoveryConfig, TransportConfig};
+pub use transport::{
+    AckFrequencyConfig, AckPolicyConfig, AdaptiveKeepAliveConfig, CidRotationConfig, GreaseConfig,
+    IdleTimeout, MtuDiscoveryConfig, PaddingPolicy, PeerLimitsConfig, PortHoppingConfig,
+    RttEstimatorConfig, TransportConfig,
+};
 
 /// Global configuration for the endpoint, affecting all connections
 ///
@@ -48,6 +52,8 @@ pub struct EndpointConfig {
     pub(crate) min_reset_interval: Duration,
     /// Optional seed to be used internally for random number generation
     pub(crate) rng_seed: Option<[u8; 32]>,
+    pub(crate) socket: SocketConfig,
+    pub(crate) zero_length_client_cids: bool,
 }
 
 impl EndpointConfig {
@@ -63,6 +69,8 @@ impl EndpointConfig {
             grease_quic_bit: true,
             min_reset_interval: Duration::from_millis(20),
             rng_seed: None,
+            socket: SocketConfig::default(),
+            zero_length_client_cids: false,
         }
     }
 
@@ -159,6 +167,38 @@ impl EndpointConfig {
         self.rng_seed = seed;
         self
     }
+
+    /// Options to apply to the endpoint's UDP socket
+    ///
+    /// quinn-proto never touches sockets itself; these are applied by the I/O layer, e.g. by
+    /// `quinn::Endpoint::new` and `quinn::Endpoint::rebind`. Defaults to leaving all socket options
+    /// as configured by the operating system.
+    pub fn socket_config(&mut self, config: SocketConfig) -> &mut Self {
+        self.socket = config;
+        self
+    }
+
+    /// Get the current value of [`socket_config`](Self::socket_config)
+    //
+    // Exposed for the same reason as `get_max_udp_payload_size`.
+    pub fn get_socket_config(&self) -> &SocketConfig {
+        &self.socket
+    }
+
+    /// Whether outgoing connections use zero-length local connection IDs
+    ///
+    /// Saves the space of a connection ID in every packet the peer sends, which matters on
+    /// constrained links. Packets are then attributed to connections based on the peer's address
+    /// alone, so such connections can't survive a change of the peer's address, and at most one of
+    /// them may exist per peer address; further connections to the same address use regular
+    /// connection IDs. Incoming connections keep using the
+    /// [`cid_generator`](Self::cid_generator).
+    ///
+    /// Defaults to `false`.
+    pub fn zero_length_client_cids(&mut self, value: bool) -> &mut Self {
+        self.zero_length_client_cids = value;
+        self
+    }
 }
 
 impl fmt::Debug for EndpointConfig {
@@ -170,10 +210,169 @@ impl fmt::Debug for EndpointConfig {
             .field("supported_versions", &self.supported_versions)
             .field("grease_quic_bit", &self.grease_quic_bit)
             .field("rng_seed", &self.rng_seed)
+            .field("socket", &self.socket)
+            .field("zero_length_client_cids", &self.zero_length_client_cids)
             .finish_non_exhaustive()
     }
 }
 
+/// Options for the UDP socket underlying an endpoint
+///
+/// See [`EndpointConfig::socket_config`]. Options left unset keep the operating system's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct SocketConfig {
+    pub(crate) recv_buffer_size: Option<usize>,
+    pub(crate) send_buffer_size: Option<usize>,
+    pub(crate) bind_device: Option<Vec<u8>>,
+    pub(crate) busy_poll: Option<Duration>,
+    pub(crate) recv_buffer_pool: Option<RecvBufferPoolConfig>,
+}
+
+impl SocketConfig {
+    /// Size of the socket receive buffer (`SO_RCVBUF`)
+    ///
+    /// Raising this reduces packet loss when the endpoint can't keep up with bursts of incoming
+    /// datagrams. The operating system may round or cap the value; Linux for example doubles it
+    /// and caps it to `net.core.rmem_max`.
+    pub fn recv_buffer_size(&mut self, value: Option<usize>) -> &mut Self {
+        self.recv_buffer_size = value;
+        self
+    }
+
+    /// Get the current value of [`recv_buffer_size`](Self::recv_buffer_size)
+    pub fn get_recv_buffer_size(&self) -> Option<usize> {
+        self.recv_buffer_size
+    }
+
+    /// Size of the socket send buffer (`SO_SNDBUF`)
+    ///
+    /// The operating system may round or cap the value; Linux for example doubles it and caps it
+    /// to `net.core.wmem_max`.
+    pub fn send_buffer_size(&mut self, value: Option<usize>) -> &mut Self {
+        self.send_buffer_size = value;
+        self
+    }
+
+    /// Get the current value of [`send_buffer_size`](Self::send_buffer_size)
+    pub fn get_send_buffer_size(&self) -> Option<usize> {
+        self.send_buffer_size
+    }
+
+    /// Name of the network interface to bind the socket to (`SO_BINDTODEVICE`)
+    ///
+    /// Only supported on Linux, Android and Fuchsia, and usually requires elevated privileges.
+    /// Applying this option fails on other platforms.
+    pub fn bind_device(&mut self, interface: Option<Vec<u8>>) -> &mut Self {
+        self.bind_device = interface;
+        self
+    }
+
+    /// Get the current value of [`bind_device`](Self::bind_device)
+    pub fn get_bind_device(&self) -> Option<&[u8]> {
+        self.bind_device.as_deref()
+    }
+
+    /// Busy poll for incoming datagrams for up to this long before going to sleep
+    ///
+    /// Trades CPU time for lower and more predictable receive latency. After receiving datagrams,
+    /// the endpoint driver keeps polling the socket instead of waiting to be woken up, until no
+    /// datagram has arrived for this long. On Linux, this additionally enables busy polling of
+    /// the device queue in the kernel (`SO_BUSY_POLL` and `SO_PREFER_BUSY_POLL`), which requires
+    /// the `CAP_NET_ADMIN` capability for values above `net.core.busy_read`; failing to enable
+    /// that is logged and otherwise ignored.
+    ///
+    /// Durations are rounded down to whole microseconds. Disabled by default.
+    pub fn busy_poll(&mut self, value: Option<Duration>) -> &mut Self {
+        self.busy_poll = value;
+        self
+    }
+
+    /// Get the current value of [`busy_poll`](Self::busy_poll)
+    pub fn get_busy_poll(&self) -> Option<Duration> {
+        self.busy_poll
+    }
+
+    /// Recycle the buffers that received datagrams are stored in
+    ///
+    /// By default, every received datagram is copied into a fresh allocation, which is also where
+    /// it's decrypted and where received stream data is referenced from until the application
+    /// reads it. With a pool, datagrams are instead packed into larger buffers that are reused
+    /// once nothing refers to their contents anymore, which reduces allocator pressure at high
+    /// packet rates.
+    pub fn recv_buffer_pool(&mut self, value: Option<RecvBufferPoolConfig>) -> &mut Self {
+        self.recv_buffer_pool = value;
+        self
+    }
+
+    /// Get the current value of [`recv_buffer_pool`](Self::recv_buffer_pool)
+    pub fn get_recv_buffer_pool(&self) -> Option<&RecvBufferPoolConfig> {
+        self.recv_buffer_pool.as_ref()
+    }
+}
+
+/// Sizing of the receive buffer pool, see [`SocketConfig::recv_buffer_pool`]
+///
+/// When every pooled buffer is still referenced, datagrams fall back to individual allocations
+/// rather than growing the pool beyond its caps.
+#[derive(Debug, Clone)]
+pub struct RecvBufferPoolConfig {
+    pub(crate) buffer_size: usize,
+    pub(crate) max_buffers: usize,
+    pub(crate) buffers_per_connection: usize,
+}
+
+impl RecvBufferPoolConfig {
+    /// Size of each pooled buffer, in bytes
+    ///
+    /// Datagrams larger than this are allocated individually. Larger buffers mean fewer
+    /// allocations, but a single datagram that is still referenced keeps its whole buffer from
+    /// being reused.
+    pub fn buffer_size(&mut self, value: usize) -> &mut Self {
+        self.buffer_size = value;
+        self
+    }
+
+    /// Maximum number of buffers in the pool
+    pub fn max_buffers(&mut self, value: usize) -> &mut Self {
+        self.max_buffers = value;
+        self
+    }
+
+    /// Maximum number of buffers in the pool per open connection
+    ///
+    /// Keeps the pool small while the endpoint has few connections, and lets it shrink again
+    /// after connections close. The pool may always hold at least this many buffers.
+    pub fn buffers_per_connection(&mut self, value: usize) -> &mut Self {
+        self.buffers_per_connection = value;
+        self
+    }
+
+    /// Get the current value of [`buffer_size`](Self::buffer_size)
+    pub fn get_buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Get the current value of [`max_buffers`](Self::max_buffers)
+    pub fn get_max_buffers(&self) -> usize {
+        self.max_buffers
+    }
+
+    /// Get the current value of [`buffers_per_connection`](Self::buffers_per_connection)
+    pub fn get_buffers_per_connection(&self) -> usize {
+        self.buffers_per_connection
+    }
+}
+
+impl Default for RecvBufferPoolConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: 64 * 1024,
+            max_buffers: 1024,
+            buffers_per_connection: 8,
+        }
+    }
+}
+
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
 impl Default for EndpointConfig {
     fn default() -> Self {
@@ -212,6 +411,9 @@ pub struct ServerConfig {
     /// Duration after a retry token was issued for which it's considered valid
     pub(crate) retry_token_lifetime: Duration,
 
+    /// Records used retry tokens to reject replays, if set
+    pub(crate) retry_token_log: Option<Arc<dyn TokenLog>>,
+
     /// Whether to allow clients to migrate to new addresses
     ///
     /// Improves behavior for clients that move between different internet connections or suffer NAT
@@ -240,6 +442,7 @@ impl ServerConfig {
 
             token_key,
             retry_token_lifetime: Duration::from_secs(15),
+            retry_token_log: None,
 
             migration: true,
 
@@ -285,6 +488,20 @@ impl ServerConfig {
         self
     }
 
+    /// Accept each retry token at most once within its lifetime
+    ///
+    /// Retry tokens are otherwise accepted any number of times until they expire, so an attacker
+    /// observing a token could replay it from a spoofed address before it expires. Connection
+    /// attempts presenting a token the log reports as reused are refused. The log is consulted
+    /// with the [`retry_token_lifetime`](Self::retry_token_lifetime); a shared implementation
+    /// extends the protection across the servers of a distributed deployment.
+    ///
+    /// Defaults to `None`. A [`BloomTokenLog`](crate::BloomTokenLog) is a suitable choice.
+    pub fn retry_token_log(&mut self, value: Option<Arc<dyn TokenLog>>) -> &mut Self {
+        self.retry_token_log = value;
+        self
+    }
+
     /// Whether to allow clients to migrate to new addresses
     ///
     /// Improves behavior for clients that move between different internet connections or suffer NAT
@@ -416,6 +633,7 @@ impl fmt::Debug for ServerConfig {
             // crypto not debug
             // token not debug
             .field("retry_token_lifetime", &self.retry_token_lifetime)
+            // retry_token_log not debug
             .field("validation_token", &self.validation_token)
             .field("migration", &self.migration)
             .field("preferred_address_v4", &self.preferred_address_v4)
@@ -482,6 +700,7 @@ pub struct ValidationTokenConfig {
     pub(crate) lifetime: Duration,
     pub(crate) log: Arc<dyn TokenLog>,
     pub(crate) sent: u32,
+    pub(crate) resumption_hints: bool,
 }
 
 impl ValidationTokenConfig {
@@ -519,6 +738,24 @@ impl ValidationTokenConfig {
         self.sent = value;
         self
     }
+
+    /// Whether to embed the path's RTT and bandwidth in address validation tokens
+    ///
+    /// When a client reconnects with such a token from the same IP address, and the RTT measured
+    /// during the handshake is comparable to the embedded one, the congestion controller may jump
+    /// to half of the previous bandwidth-delay product instead of starting from its initial
+    /// window. It retreats if that turns out to cause loss, following
+    /// draft-ietf-tsvwg-careful-resume. Tokens are issued early in a connection, before much is
+    /// known about the path; see [`Connection::refresh_validation_tokens`][1] to issue new ones
+    /// later on.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [1]: crate::Connection::refresh_validation_tokens
+    pub fn resumption_hints(&mut self, value: bool) -> &mut Self {
+        self.resumption_hints = value;
+        self
+    }
 }
 
 impl Default for ValidationTokenConfig {
@@ -531,6 +768,7 @@ impl Default for ValidationTokenConfig {
             lifetime: Duration::from_secs(2 * 7 * 24 * 60 * 60),
             log,
             sent: if cfg!(feature = "bloom") { 2 } else { 0 },
+            resumption_hints: false,
         }
     }
 }
@@ -541,6 +779,7 @@ impl fmt::Debug for ValidationTokenConfig {
             .field("lifetime", &self.lifetime)
             // log not debug
             .field("sent", &self.sent)
+            .field("resumption_hints", &self.resumption_hints)
             .finish_non_exhaustive()
     }
 }
@@ -658,6 +897,16 @@ pub enum ConfigError {
     OutOfBounds,
 }
 
+/// Error returned by [`Connection::tune()`] when fields can't be changed on a live connection
+///
+/// [`Connection::tune()`]: crate::Connection::tune
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("fields can't be changed on a live connection: {}", fields.join(", "))]
+pub struct TuneError {
+    /// Names of the offending fields, as reported by [`TransportConfig::diff()`]
+    pub fields: Vec<&'static str>,
+}
+
 impl From<TryFromIntError> for ConfigError {
     fn from(_: TryFromIntError) -> Self {
         Self::OutOfBounds
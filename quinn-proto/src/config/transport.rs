@@ -1,4 +1,4 @@
-use std::{fmt, sync::Arc};
+use std::{fmt, num::NonZeroU32, sync::Arc};
 #[cfg(feature = "qlog")]
 use std::{io, sync::Mutex, time::Instant};
 
@@ -8,8 +8,8 @@ use qlog::streamer::QlogStreamer;
 #[cfg(feature = "qlog")]
 use crate::QlogStream;
 use crate::{
-    Duration, INITIAL_MTU, MAX_UDP_PAYLOAD, VarInt, VarIntBoundsExceeded, congestion,
-    connection::qlog::QlogSink,
+    DatagramClassifier, Duration, INITIAL_MTU, LOC_CID_COUNT, MAX_UDP_PAYLOAD, TransmitMarker,
+    VarInt, VarIntBoundsExceeded, congestion, connection::qlog::QlogSink, loss,
 };
 
 /// Parameters governing the core QUIC state machine
@@ -23,7 +23,9 @@ use crate::{
 /// tuned for a particular expected round trip time, link capacity, and memory availability. Tuning
 /// for higher bandwidths and latencies increases worst-case memory consumption, but does not impair
 /// performance at lower bandwidths and latencies. The default configuration is tuned for a 100Mbps
-/// link with a 100ms round trip time.
+/// link with a 100ms round trip time. Presets such as [`TransportConfig::satellite`] adjust these
+/// values coherently for other kinds of networks, and can be tuned further.
+#[derive(Clone)]
 pub struct TransportConfig {
     pub(crate) max_concurrent_bidi_streams: VarInt,
     pub(crate) max_concurrent_uni_streams: VarInt,
@@ -32,27 +34,56 @@ pub struct TransportConfig {
     pub(crate) receive_window: VarInt,
     pub(crate) send_window: u64,
     pub(crate) send_fairness: bool,
+    pub(crate) retransmit_at_stream_priority: bool,
 
     pub(crate) packet_threshold: u32,
     pub(crate) time_threshold: f32,
+    pub(crate) pto_backoff_base: f32,
+    pub(crate) max_pto_backoff: u32,
+    pub(crate) max_pto_count: Option<NonZeroU32>,
+    pub(crate) max_retransmission_time: Option<Duration>,
     pub(crate) initial_rtt: Duration,
+    pub(crate) rtt_estimator: RttEstimatorConfig,
+    pub(crate) rtt_histogram_buckets: Arc<[Duration]>,
     pub(crate) initial_mtu: u16,
     pub(crate) min_mtu: u16,
     pub(crate) mtu_discovery_config: Option<MtuDiscoveryConfig>,
     pub(crate) pad_to_mtu: bool,
+    pub(crate) padding_policy: PaddingPolicy,
     pub(crate) ack_frequency_config: Option<AckFrequencyConfig>,
+    pub(crate) ack_policy_config: AckPolicyConfig,
+    pub(crate) max_ack_ranges: usize,
+    pub(crate) peer_limits: PeerLimitsConfig,
     pub(crate) max_outgoing_bytes_per_second: Option<u64>,
+    pub(crate) anti_amplification_factor: u8,
 
     pub(crate) persistent_congestion_threshold: u32,
     pub(crate) keep_alive_interval: Option<Duration>,
+    pub(crate) adaptive_keep_alive: Option<AdaptiveKeepAliveConfig>,
+    pub(crate) keep_alive_while_active: bool,
+    pub(crate) cid_rotation: Option<CidRotationConfig>,
+    pub(crate) max_issued_cids: u64,
+    pub(crate) max_deferred_packets: usize,
+    pub(crate) max_deferred_bytes: u64,
+    pub(crate) port_hopping: Option<PortHoppingConfig>,
+    pub(crate) grease: GreaseConfig,
+    pub(crate) timer_coalescing: Option<Duration>,
     pub(crate) crypto_buffer_size: usize,
     pub(crate) allow_spin: bool,
+    pub(crate) dscp: Option<u8>,
+    pub(crate) transmit_marker: Option<Arc<dyn TransmitMarker>>,
+    pub(crate) flow_label: bool,
+    pub(crate) send_observed_address_reports: bool,
+    pub(crate) receive_observed_address_reports: bool,
     pub(crate) datagram_receive_buffer_size: Option<usize>,
     pub(crate) datagram_send_buffer_size: usize,
+    pub(crate) datagram_classifier: Option<Arc<dyn DatagramClassifier>>,
+    pub(crate) datagram_flow_receive_buffer_size: Option<usize>,
     #[cfg(test)]
     pub(crate) deterministic_packet_numbers: bool,
 
     pub(crate) congestion_controller_factory: Arc<dyn congestion::ControllerFactory + Send + Sync>,
+    pub(crate) loss_detector_factory: Arc<dyn loss::LossDetectorFactory + Send + Sync>,
 
     pub(crate) enable_segmentation_offload: bool,
 
@@ -60,6 +91,89 @@ pub struct TransportConfig {
 }
 
 impl TransportConfig {
+    /// Preset for networks within a data center
+    ///
+    /// Tuned for 10Gbps links with a 1ms round trip time. ACKs are sent for every tenth packet to
+    /// reduce CPU consumption, and MTU discovery probes for jumbo frames.
+    pub fn datacenter() -> Self {
+        let mut config = Self::tuned_for_path(10_000_000_000 / 8, Duration::from_millis(1));
+        let mut ack_policy = AckPolicyConfig::default();
+        ack_policy
+            .ack_eliciting_threshold(VarInt(9))
+            .max_ack_delay(Duration::from_millis(1));
+        let mut mtu_discovery = MtuDiscoveryConfig::default();
+        mtu_discovery.upper_bound(8952);
+        config
+            .ack_policy_config(ack_policy)
+            .mtu_discovery_config(Some(mtu_discovery))
+            .max_idle_timeout(Some(IdleTimeout(VarInt(10_000))));
+        config
+    }
+
+    /// Preset for geostationary satellite links
+    ///
+    /// Tuned for 100Mbps links with a 600ms round trip time. BBR congestion control sustains
+    /// throughput despite the random loss common on such links, and ACKs are sent for every fourth
+    /// packet to spare the often narrow return link.
+    pub fn satellite() -> Self {
+        let mut config = Self::tuned_for_path(100_000_000 / 8, Duration::from_millis(600));
+        let mut ack_policy = AckPolicyConfig::default();
+        ack_policy.ack_eliciting_threshold(VarInt(3));
+        config
+            .ack_policy_config(ack_policy)
+            .congestion_controller_factory(Arc::new(congestion::BbrConfig::default()))
+            .max_idle_timeout(Some(IdleTimeout(VarInt(60_000))));
+        config
+    }
+
+    /// Preset for cellular and other wireless networks
+    ///
+    /// Tuned for 50Mbps links with a 100ms round trip time that varies widely. BBR congestion
+    /// control tolerates random loss, and [adaptive keep-alives](AdaptiveKeepAliveConfig) keep NAT
+    /// bindings open without draining batteries. MTU discovery is disabled, as cellular paths
+    /// rarely support more than the minimum.
+    pub fn mobile() -> Self {
+        let mut config = Self::tuned_for_path(50_000_000 / 8, Duration::from_millis(100));
+        config
+            .initial_rtt(Duration::from_millis(333))
+            .congestion_controller_factory(Arc::new(congestion::BbrConfig::default()))
+            .mtu_discovery_config(None)
+            .adaptive_keep_alive(Some(AdaptiveKeepAliveConfig::default()));
+        config
+    }
+
+    /// Preset for connections dedicated to transferring large amounts of data
+    ///
+    /// Tuned for 1Gbps links with a 100ms round trip time. Streams are sent one after the other
+    /// rather than interleaved, so that each completes as early as possible, and ACKs are sent for
+    /// every fourth packet.
+    pub fn bulk_transfer() -> Self {
+        let mut config = Self::tuned_for_path(1_000_000_000 / 8, Duration::from_millis(100));
+        let mut ack_policy = AckPolicyConfig::default();
+        ack_policy.ack_eliciting_threshold(VarInt(3));
+        config
+            .ack_policy_config(ack_policy)
+            .send_fairness(false)
+            .congestion_controller_factory(Arc::new(congestion::BbrConfig::default()));
+        config
+    }
+
+    /// Size flow control windows and buffers for the bandwidth-delay product of a path
+    fn tuned_for_path(bytes_per_second: u64, rtt: Duration) -> Self {
+        // Leave headroom for RTT variation and for the window to be extended before it's exhausted
+        let bdp = (2 * bytes_per_second)
+            .saturating_mul(rtt.as_micros() as u64)
+            .div_ceil(1_000_000)
+            .max(u64::from(STREAM_RWND));
+        let mut config = Self::default();
+        config
+            .stream_receive_window(VarInt::from_u64(bdp).unwrap_or(VarInt::MAX))
+            .send_window(8 * bdp)
+            .initial_rtt(rtt)
+            .datagram_receive_buffer_size(Some(bdp as usize));
+        config
+    }
+
     /// Maximum number of incoming bidirectional streams that may be open concurrently
     ///
     /// Must be nonzero for the peer to open any bidirectional streams.
@@ -154,6 +268,20 @@ impl TransportConfig {
         self
     }
 
+    /// Whether lost stream data is rescheduled at its stream's current priority
+    ///
+    /// When enabled, a stream with data to retransmit is requeued according to its priority at the
+    /// time the loss is detected, and a stream that was partially written out while
+    /// [`send_fairness`](Self::send_fairness) is disabled gives way to higher priority streams.
+    /// When disabled, a stream keeps its place in the queue, so a low priority stream suffering
+    /// losses may delay higher priority streams opened after it.
+    ///
+    /// Defaults to `true`.
+    pub fn retransmit_at_stream_priority(&mut self, value: bool) -> &mut Self {
+        self.retransmit_at_stream_priority = value;
+        self
+    }
+
     /// Maximum reordering in packet number space before FACK style loss detection considers a
     /// packet lost. Should not be less than 3, per RFC5681.
     pub fn packet_threshold(&mut self, value: u32) -> &mut Self {
@@ -168,12 +296,73 @@ impl TransportConfig {
         self
     }
 
+    /// Factor by which the probe timeout grows after each consecutive probe timeout
+    ///
+    /// Clamped to at least 1, with NaN treated as 1, and to at most `f32::MAX`. Defaults to 2, per
+    /// RFC 9002 §6.2.1.
+    pub fn pto_backoff_base(&mut self, value: f32) -> &mut Self {
+        self.pto_backoff_base = if value.is_nan() {
+            1.0
+        } else {
+            value.clamp(1.0, f32::MAX)
+        };
+        self
+    }
+
+    /// Upper bound on the factor by which the probe timeout grows due to consecutive probe
+    /// timeouts
+    ///
+    /// Clamped to at least 1. Defaults to 2^16.
+    pub fn max_pto_backoff(&mut self, value: u32) -> &mut Self {
+        self.max_pto_backoff = value.max(1);
+        self
+    }
+
+    /// Number of consecutive probe timeouts after which the connection is abandoned
+    ///
+    /// Detects a peer that became unreachable sooner than the idle timeout would, as the peer
+    /// fails to acknowledge data well before the idle timer expires. The connection is closed with
+    /// a [`TimeoutTimer::Retransmission`](crate::TimeoutTimer::Retransmission) timeout when the
+    /// `value`th consecutive probe timeout fires. Defaults to `None`, i.e. unlimited.
+    pub fn max_pto_count(&mut self, value: Option<NonZeroU32>) -> &mut Self {
+        self.max_pto_count = value;
+        self
+    }
+
+    /// Time spent retransmitting without acknowledgement after which the connection is abandoned
+    ///
+    /// Measured from the first of a sequence of consecutive probe timeouts, and checked whenever a
+    /// probe timeout fires, so may be exceeded by up to the current probe timeout. See
+    /// [`max_pto_count()`](Self::max_pto_count). Defaults to `None`, i.e. unlimited.
+    pub fn max_retransmission_time(&mut self, value: Option<Duration>) -> &mut Self {
+        self.max_retransmission_time = value;
+        self
+    }
+
     /// The RTT used before an RTT sample is taken
     pub fn initial_rtt(&mut self, value: Duration) -> &mut Self {
         self.initial_rtt = value;
         self
     }
 
+    /// How RTT samples are combined into the estimates driving loss detection and congestion
+    /// control (see [`RttEstimatorConfig`] for details)
+    pub fn rtt_estimator(&mut self, value: RttEstimatorConfig) -> &mut Self {
+        self.rtt_estimator = value;
+        self
+    }
+
+    /// Upper bounds of the buckets of the [`RttHistogram`](crate::RttHistogram) of a connection
+    ///
+    /// Samples exceeding all bounds are counted in an additional bucket. The default has four
+    /// buckets per doubling of the RTT, ranging from 100µs to about 13 seconds.
+    pub fn rtt_histogram_buckets(&mut self, mut upper_bounds: Vec<Duration>) -> &mut Self {
+        upper_bounds.sort_unstable();
+        upper_bounds.dedup();
+        self.rtt_histogram_buckets = upper_bounds.into();
+        self
+    }
+
     /// The initial value to be used as the maximum UDP payload size before running MTU discovery
     /// (see [`TransportConfig::mtu_discovery_config`]).
     ///
@@ -231,6 +420,17 @@ impl TransportConfig {
         self
     }
 
+    /// How to pad UDP datagrams carrying application data, to obscure the size of their contents
+    ///
+    /// Applies in addition to [`pad_to_mtu()`](Self::pad_to_mtu). Bytes added are counted in
+    /// [`ConnectionStats::padding_bytes`](crate::ConnectionStats::padding_bytes).
+    ///
+    /// Defaults to [`PaddingPolicy::None`].
+    pub fn padding_policy(&mut self, value: PaddingPolicy) -> &mut Self {
+        self.padding_policy = value;
+        self
+    }
+
     /// Specifies the ACK frequency config (see [`AckFrequencyConfig`] for details)
     ///
     /// The provided configuration will be ignored if the peer does not support the acknowledgement
@@ -244,14 +444,53 @@ impl TransportConfig {
         self
     }
 
+    /// Specifies how received packets are acknowledged (see [`AckPolicyConfig`] for details)
+    pub fn ack_policy_config(&mut self, value: AckPolicyConfig) -> &mut Self {
+        self.ack_policy_config = value;
+        self
+    }
+
+    /// Maximum number of ranges of received packet numbers tracked for acknowledgement, per packet
+    /// number space
+    ///
+    /// Loss and reordering split received packets into ranges. Beyond this limit, the lowest range
+    /// is no longer acknowledged, which bounds the memory and time spent on a peer that reorders
+    /// packets adversarially, at the cost of the peer retransmitting data it doesn't learn was
+    /// received. ACK frames are truncated to the room left in a packet regardless. Clamped to at
+    /// least 1. Defaults to 64, which always fits in a minimum-size packet.
+    pub fn max_ack_ranges(&mut self, value: usize) -> &mut Self {
+        self.max_ack_ranges = value.max(1);
+        self
+    }
+
+    /// Limits on the rate of costly actions of the peer (see [`PeerLimitsConfig`] for details)
+    pub fn peer_limits(&mut self, value: PeerLimitsConfig) -> &mut Self {
+        self.peer_limits = value;
+        self
+    }
+
     /// Configures an outbound rate limit (in bytes per second) for each connection.
     ///
-    /// Defaults to `None`, which disables rate limiting.
+    /// Enforced by pacing, independently of congestion control. The limit of an individual
+    /// connection can be changed with
+    /// [`Connection::set_max_outgoing_bytes_per_second()`](crate::Connection::set_max_outgoing_bytes_per_second),
+    /// e.g. to apply per-tenant quotas. Defaults to `None`, which disables rate limiting.
     pub fn max_outgoing_bytes_per_second(&mut self, value: Option<u64>) -> &mut Self {
         self.max_outgoing_bytes_per_second = value;
         self
     }
 
+    /// Multiple of the data received from an unvalidated address that may be sent to it
+    ///
+    /// Servers are limited to sending three times the amount of data received until the client's
+    /// address is validated, to prevent their use for amplification attacks. Lower values help
+    /// reproduce handshake stalls caused by this limit, e.g. with large certificate chains.
+    /// Clamped to between 1 and 3. Defaults to 3, per RFC 9000 §8.
+    pub fn anti_amplification_factor(&mut self, value: u8) -> &mut Self {
+        self.anti_amplification_factor = value.clamp(1, 3);
+        self
+    }
+
     /// Number of consecutive PTOs after which network is considered to be experiencing persistent congestion.
     pub fn persistent_congestion_threshold(&mut self, value: u32) -> &mut Self {
         self.persistent_congestion_threshold = value;
@@ -270,6 +509,120 @@ impl TransportConfig {
         self
     }
 
+    /// Discover the longest keep-alive interval that NAT bindings on the path tolerate
+    ///
+    /// Rather than relying on a fixed [`keep_alive_interval`](Self::keep_alive_interval), which
+    /// this takes precedence over, keep-alives are spaced further apart until the peer observes
+    /// that this endpoint's address changed, revealing the binding timeout. See
+    /// [`AdaptiveKeepAliveConfig`] for details.
+    ///
+    /// Defaults to `None`, which disables discovery.
+    pub fn adaptive_keep_alive(&mut self, value: Option<AdaptiveKeepAliveConfig>) -> &mut Self {
+        self.adaptive_keep_alive = value;
+        self
+    }
+
+    /// Only send keep-alive packets while the connection is in use
+    ///
+    /// The connection counts as in use while any stream is open, datagrams are queued, or
+    /// ack-eliciting packets are awaiting acknowledgement. Otherwise, keep-alives are suspended so
+    /// that an idle connection doesn't keep radios awake or NAT bindings alive, and the idle
+    /// timeout may close it. Keep-alives resume with the next packet received after the connection
+    /// is used again.
+    ///
+    /// Defaults to `false`, which sends keep-alives regardless.
+    pub fn keep_alive_while_active(&mut self, value: bool) -> &mut Self {
+        self.keep_alive_while_active = value;
+        self
+    }
+
+    /// Periodically switch to a fresh connection ID for sending, to limit linkability
+    ///
+    /// An on-path observer can otherwise correlate all packets of a long-lived connection through
+    /// the connection ID they carry. Rotation requires the peer to have issued spare connection
+    /// IDs; see [`CidRotationConfig`] for details.
+    ///
+    /// Defaults to `None`, which rotates connection IDs only on migration.
+    pub fn cid_rotation(&mut self, value: Option<CidRotationConfig>) -> &mut Self {
+        self.cid_rotation = value;
+        self
+    }
+
+    /// Maximum number of connection IDs issued to the peer at any time
+    ///
+    /// Connection IDs are issued proactively after the handshake so that the peer can switch to
+    /// an unlinkable one when migrating or rotating, and replaced as the peer retires them. Fewer
+    /// connection IDs reduce the state load balancers must route for each connection, but limit
+    /// how often the peer can switch. A value of 1 issues no connection IDs beyond the one used
+    /// during the handshake, preventing the peer from migrating. The peer's
+    /// `active_connection_id_limit` transport parameter further caps the number issued.
+    ///
+    /// Values below 1 are treated as 1. Defaults to 8.
+    pub fn max_issued_cids(&mut self, value: u64) -> &mut Self {
+        self.max_issued_cids = value.max(1);
+        self
+    }
+
+    /// Maximum number of packets held back because they arrived before they could be processed
+    ///
+    /// During the handshake, reordering may deliver packets before the keys needed to decrypt
+    /// them, e.g. 0.5-RTT data from the server ahead of the handshake messages it follows. Such
+    /// packets are buffered and processed once the handshake progresses, rather than dropped and
+    /// left for loss recovery. Packets exceeding this limit or
+    /// [`max_deferred_bytes()`](Self::max_deferred_bytes) are dropped, and counted in
+    /// [`ConnectionStats::deferred_packets_dropped`](crate::ConnectionStats::deferred_packets_dropped).
+    ///
+    /// Defaults to 10. Setting this to zero disables buffering.
+    pub fn max_deferred_packets(&mut self, value: usize) -> &mut Self {
+        self.max_deferred_packets = value;
+        self
+    }
+
+    /// Maximum number of bytes of packets held back because they arrived before they could be
+    /// processed
+    ///
+    /// See [`max_deferred_packets()`](Self::max_deferred_packets). Defaults to 32 KiB.
+    pub fn max_deferred_bytes(&mut self, value: u64) -> &mut Self {
+        self.max_deferred_bytes = value;
+        self
+    }
+
+    /// Periodically move the connection to another port of the server
+    ///
+    /// Only applies to clients. Hopping between many ports makes a long-lived connection harder to
+    /// identify and block by its UDP flow; see [`PortHoppingConfig`] for details.
+    ///
+    /// Defaults to `None`, which keeps sending to the port the connection was established with.
+    pub fn port_hopping(&mut self, value: Option<PortHoppingConfig>) -> &mut Self {
+        self.port_hopping = value;
+        self
+    }
+
+    /// How much to exercise the protocol's extension points, so that middleboxes and peers don't
+    /// come to depend on them going unused
+    ///
+    /// See [`GreaseConfig`] for details. Defaults to sending a single reserved transport
+    /// parameter.
+    pub fn grease(&mut self, value: GreaseConfig) -> &mut Self {
+        self.grease = value;
+        self
+    }
+
+    /// Granularity to which the expiry of non-critical timers is rounded up
+    ///
+    /// Applies to the idle timeout, keep-alives, key discarding and connection ID rotation, which
+    /// may fire up to this much later than they otherwise would. All connections of an endpoint
+    /// round to the same grid, so their timers expire in batches rather than individually, which
+    /// reduces timer churn and wakeups for servers with many mostly idle connections. Timers
+    /// governing loss recovery, acknowledgments and pacing are unaffected.
+    ///
+    /// The keep-alive interval plus this granularity should remain below the idle timeout of both
+    /// peers. `None` to disable, which is the default.
+    pub fn timer_coalescing(&mut self, value: Option<Duration>) -> &mut Self {
+        self.timer_coalescing = value;
+        self
+    }
+
     /// Maximum quantity of out-of-order crypto layer data to buffer
     pub fn crypto_buffer_size(&mut self, value: usize) -> &mut Self {
         self.crypto_buffer_size = value;
@@ -285,6 +638,75 @@ impl TransportConfig {
         self
     }
 
+    /// Differentiated Services Code Point to mark outgoing packets with
+    ///
+    /// Allows routers to prioritize latency-critical connections, e.g. by marking them as Expedited
+    /// Forwarding (46), while bulk transfers use an Assured Forwarding class. Only the lower 6
+    /// bits are used. `None` leaves the DSCP unset, which is the default. Can be changed for a live
+    /// connection with `Connection::set_dscp`.
+    ///
+    /// Applied by `quinn-udp` on platforms that allow setting the IP Type of Service/Traffic
+    /// Class per packet; ignored elsewhere. Will only take effect if the networks between both
+    /// peers honor the marking.
+    pub fn dscp(&mut self, value: Option<u8>) -> &mut Self {
+        self.dscp = value;
+        self
+    }
+
+    /// Hook adjusting the DSCP and ECN marking of individual outgoing transmits
+    ///
+    /// Invoked for every transmit with its kind, e.g. to mark loss probes differently from
+    /// regular traffic or to apply the Lower Effort PHB (DSCP 1) to scavenger traffic, and with
+    /// the marking the connection would otherwise use, derived from [`dscp`](Self::dscp) and ECN
+    /// validation state. `None`, the default, leaves marking unchanged. Can be changed for a live
+    /// connection with `Connection::set_transmit_marker`.
+    ///
+    /// Subject to the same platform support as [`dscp`](Self::dscp).
+    pub fn transmit_marker(&mut self, value: Option<Arc<dyn TransmitMarker>>) -> &mut Self {
+        self.transmit_marker = value;
+        self
+    }
+
+    /// Whether to set a random IPv6 flow label on outgoing packets
+    ///
+    /// When enabled, each path gets a stable flow label so that routers performing ECMP keep
+    /// its packets on the same route, regardless of how the operating system would otherwise
+    /// label them. The label is replaced whenever we migrate or switch to a new remote connection
+    /// ID, so that it cannot be used to link the connection across those events. Disabled by
+    /// default, leaving flow labels up to the operating system.
+    ///
+    /// Only has an effect on IPv6 paths, and on platforms where `quinn-udp` supports setting the
+    /// flow label per packet.
+    pub fn flow_label(&mut self, value: bool) -> &mut Self {
+        self.flow_label = value;
+        self
+    }
+
+    /// Whether to tell the peer which address its packets are seen to come from
+    ///
+    /// Implements the sending side of the
+    /// [QUIC Address Discovery extension](https://datatracker.ietf.org/doc/html/draft-ietf-quic-address-discovery).
+    /// Reports are only sent if the peer asked for them by enabling
+    /// [`receive_observed_address_reports`](Self::receive_observed_address_reports), and are
+    /// repeated whenever the peer's address changes. Disabled by default.
+    pub fn send_observed_address_reports(&mut self, value: bool) -> &mut Self {
+        self.send_observed_address_reports = value;
+        self
+    }
+
+    /// Whether to ask the peer to report the address our packets are seen to come from
+    ///
+    /// If the peer agrees by enabling
+    /// [`send_observed_address_reports`](Self::send_observed_address_reports), the reflexive
+    /// transport address it observes is surfaced through
+    /// [`TransportEvent::ObservedAddr`](crate::TransportEvent::ObservedAddr) and
+    /// `Connection::observed_address`, e.g. to learn the public address a NAT maps us to. Disabled
+    /// by default.
+    pub fn receive_observed_address_reports(&mut self, value: bool) -> &mut Self {
+        self.receive_observed_address_reports = value;
+        self
+    }
+
     /// Maximum number of incoming application datagram bytes to buffer, or None to disable
     /// incoming datagrams
     ///
@@ -307,6 +729,28 @@ impl TransportConfig {
         self
     }
 
+    /// How to assign incoming datagrams to flows, or None to treat them as a single flow
+    ///
+    /// Datagrams of a flow can be received separately with `Datagrams::recv_flow`, and
+    /// `Datagrams::flow_stats` reports how many were dropped. When the receive buffer overflows,
+    /// datagrams are dropped from the flow buffering the most data rather than in order of
+    /// arrival, so that one busy flow can't evict the datagrams of others. Statistics are kept for
+    /// every flow seen, so flow identifiers should be drawn from a bounded set.
+    pub fn datagram_classifier(&mut self, value: Option<Arc<dyn DatagramClassifier>>) -> &mut Self {
+        self.datagram_classifier = value;
+        self
+    }
+
+    /// Maximum number of incoming datagram bytes to buffer per flow, or None to only bound the
+    /// connection's total
+    ///
+    /// Only applies if a [`datagram_classifier`](Self::datagram_classifier) is set. When a flow
+    /// exceeds this limit, its oldest datagrams are dropped.
+    pub fn datagram_flow_receive_buffer_size(&mut self, value: Option<usize>) -> &mut Self {
+        self.datagram_flow_receive_buffer_size = value;
+        self
+    }
+
     /// Whether to force every packet number to be used
     ///
     /// By default, packet numbers are occasionally skipped to ensure peers aren't ACKing packets
@@ -336,6 +780,18 @@ impl TransportConfig {
         self
     }
 
+    /// How to construct new `loss::LossDetector`s
+    ///
+    /// Experimental. Defaults to a `loss::ThresholdLossDetectorConfig`, which applies the
+    /// [`packet_threshold`](Self::packet_threshold) and [`time_threshold`](Self::time_threshold).
+    pub fn loss_detector_factory(
+        &mut self,
+        factory: Arc<dyn loss::LossDetectorFactory + Send + Sync + 'static>,
+    ) -> &mut Self {
+        self.loss_detector_factory = factory;
+        self
+    }
+
     /// Whether to use "Generic Segmentation Offload" to accelerate transmits, when supported by the
     /// environment
     ///
@@ -357,16 +813,256 @@ impl TransportConfig {
         self.qlog_sink = stream.into();
         self
     }
+
+    /// Names of the fields whose values differ between `self` and `other`
+    ///
+    /// Names match the corresponding setters. Trait objects, such as the congestion controller
+    /// factory, are only considered equal if both configurations share the same instance.
+    pub fn diff(&self, other: &Self) -> Vec<&'static str> {
+        let Self {
+            max_concurrent_bidi_streams,
+            max_concurrent_uni_streams,
+            max_idle_timeout,
+            stream_receive_window,
+            receive_window,
+            send_window,
+            send_fairness,
+            retransmit_at_stream_priority,
+            packet_threshold,
+            time_threshold,
+            pto_backoff_base,
+            max_pto_backoff,
+            max_pto_count,
+            max_retransmission_time,
+            initial_rtt,
+            rtt_estimator,
+            rtt_histogram_buckets,
+            initial_mtu,
+            min_mtu,
+            mtu_discovery_config,
+            pad_to_mtu,
+            padding_policy,
+            ack_frequency_config,
+            ack_policy_config,
+            max_ack_ranges,
+            peer_limits,
+            max_outgoing_bytes_per_second,
+            anti_amplification_factor,
+            persistent_congestion_threshold,
+            keep_alive_interval,
+            adaptive_keep_alive,
+            keep_alive_while_active,
+            cid_rotation,
+            max_issued_cids,
+            max_deferred_packets,
+            max_deferred_bytes,
+            port_hopping,
+            grease,
+            timer_coalescing,
+            crypto_buffer_size,
+            allow_spin,
+            dscp,
+            transmit_marker,
+            flow_label,
+            send_observed_address_reports,
+            receive_observed_address_reports,
+            datagram_receive_buffer_size,
+            datagram_send_buffer_size,
+            datagram_classifier,
+            datagram_flow_receive_buffer_size,
+            #[cfg(test)]
+                deterministic_packet_numbers: _,
+            congestion_controller_factory,
+            loss_detector_factory,
+            enable_segmentation_offload,
+            qlog_sink,
+        } = self;
+        let mut diff = Vec::new();
+        if *max_concurrent_bidi_streams != other.max_concurrent_bidi_streams {
+            diff.push("max_concurrent_bidi_streams");
+        }
+        if *max_concurrent_uni_streams != other.max_concurrent_uni_streams {
+            diff.push("max_concurrent_uni_streams");
+        }
+        if *max_idle_timeout != other.max_idle_timeout {
+            diff.push("max_idle_timeout");
+        }
+        if *stream_receive_window != other.stream_receive_window {
+            diff.push("stream_receive_window");
+        }
+        if *receive_window != other.receive_window {
+            diff.push("receive_window");
+        }
+        if *send_window != other.send_window {
+            diff.push("send_window");
+        }
+        if *send_fairness != other.send_fairness {
+            diff.push("send_fairness");
+        }
+        if *retransmit_at_stream_priority != other.retransmit_at_stream_priority {
+            diff.push("retransmit_at_stream_priority");
+        }
+        if *packet_threshold != other.packet_threshold {
+            diff.push("packet_threshold");
+        }
+        if *time_threshold != other.time_threshold {
+            diff.push("time_threshold");
+        }
+        if *pto_backoff_base != other.pto_backoff_base {
+            diff.push("pto_backoff_base");
+        }
+        if *max_pto_backoff != other.max_pto_backoff {
+            diff.push("max_pto_backoff");
+        }
+        if *max_pto_count != other.max_pto_count {
+            diff.push("max_pto_count");
+        }
+        if *max_retransmission_time != other.max_retransmission_time {
+            diff.push("max_retransmission_time");
+        }
+        if *initial_rtt != other.initial_rtt {
+            diff.push("initial_rtt");
+        }
+        if *rtt_estimator != other.rtt_estimator {
+            diff.push("rtt_estimator");
+        }
+        if *rtt_histogram_buckets != other.rtt_histogram_buckets {
+            diff.push("rtt_histogram_buckets");
+        }
+        if *initial_mtu != other.initial_mtu {
+            diff.push("initial_mtu");
+        }
+        if *min_mtu != other.min_mtu {
+            diff.push("min_mtu");
+        }
+        if *mtu_discovery_config != other.mtu_discovery_config {
+            diff.push("mtu_discovery_config");
+        }
+        if *pad_to_mtu != other.pad_to_mtu {
+            diff.push("pad_to_mtu");
+        }
+        if *padding_policy != other.padding_policy {
+            diff.push("padding_policy");
+        }
+        if *ack_frequency_config != other.ack_frequency_config {
+            diff.push("ack_frequency_config");
+        }
+        if *ack_policy_config != other.ack_policy_config {
+            diff.push("ack_policy_config");
+        }
+        if *max_ack_ranges != other.max_ack_ranges {
+            diff.push("max_ack_ranges");
+        }
+        if *peer_limits != other.peer_limits {
+            diff.push("peer_limits");
+        }
+        if *max_outgoing_bytes_per_second != other.max_outgoing_bytes_per_second {
+            diff.push("max_outgoing_bytes_per_second");
+        }
+        if *anti_amplification_factor != other.anti_amplification_factor {
+            diff.push("anti_amplification_factor");
+        }
+        if *persistent_congestion_threshold != other.persistent_congestion_threshold {
+            diff.push("persistent_congestion_threshold");
+        }
+        if *keep_alive_interval != other.keep_alive_interval {
+            diff.push("keep_alive_interval");
+        }
+        if *adaptive_keep_alive != other.adaptive_keep_alive {
+            diff.push("adaptive_keep_alive");
+        }
+        if *keep_alive_while_active != other.keep_alive_while_active {
+            diff.push("keep_alive_while_active");
+        }
+        if *cid_rotation != other.cid_rotation {
+            diff.push("cid_rotation");
+        }
+        if *max_issued_cids != other.max_issued_cids {
+            diff.push("max_issued_cids");
+        }
+        if *max_deferred_packets != other.max_deferred_packets {
+            diff.push("max_deferred_packets");
+        }
+        if *max_deferred_bytes != other.max_deferred_bytes {
+            diff.push("max_deferred_bytes");
+        }
+        if *port_hopping != other.port_hopping {
+            diff.push("port_hopping");
+        }
+        if *grease != other.grease {
+            diff.push("grease");
+        }
+        if *timer_coalescing != other.timer_coalescing {
+            diff.push("timer_coalescing");
+        }
+        if *crypto_buffer_size != other.crypto_buffer_size {
+            diff.push("crypto_buffer_size");
+        }
+        if *allow_spin != other.allow_spin {
+            diff.push("allow_spin");
+        }
+        if *dscp != other.dscp {
+            diff.push("dscp");
+        }
+        if !opt_ptr_eq(transmit_marker, &other.transmit_marker) {
+            diff.push("transmit_marker");
+        }
+        if *flow_label != other.flow_label {
+            diff.push("flow_label");
+        }
+        if *send_observed_address_reports != other.send_observed_address_reports {
+            diff.push("send_observed_address_reports");
+        }
+        if *receive_observed_address_reports != other.receive_observed_address_reports {
+            diff.push("receive_observed_address_reports");
+        }
+        if *datagram_receive_buffer_size != other.datagram_receive_buffer_size {
+            diff.push("datagram_receive_buffer_size");
+        }
+        if *datagram_send_buffer_size != other.datagram_send_buffer_size {
+            diff.push("datagram_send_buffer_size");
+        }
+        if !opt_ptr_eq(datagram_classifier, &other.datagram_classifier) {
+            diff.push("datagram_classifier");
+        }
+        if *datagram_flow_receive_buffer_size != other.datagram_flow_receive_buffer_size {
+            diff.push("datagram_flow_receive_buffer_size");
+        }
+        if !Arc::ptr_eq(
+            congestion_controller_factory,
+            &other.congestion_controller_factory,
+        ) {
+            diff.push("congestion_controller_factory");
+        }
+        if !Arc::ptr_eq(loss_detector_factory, &other.loss_detector_factory) {
+            diff.push("loss_detector_factory");
+        }
+        if *enable_segmentation_offload != other.enable_segmentation_offload {
+            diff.push("enable_segmentation_offload");
+        }
+        if !qlog_sink.ptr_eq(&other.qlog_sink) {
+            diff.push("qlog_stream");
+        }
+        diff
+    }
+}
+
+const EXPECTED_RTT: u32 = 100; // ms
+const MAX_STREAM_BANDWIDTH: u32 = 12500 * 1000; // bytes/s
+// Window size needed to avoid pipeline
+// stalls
+const STREAM_RWND: u32 = MAX_STREAM_BANDWIDTH / 1000 * EXPECTED_RTT;
+
+fn opt_ptr_eq<T: ?Sized>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
 }
 
 impl Default for TransportConfig {
     fn default() -> Self {
-        const EXPECTED_RTT: u32 = 100; // ms
-        const MAX_STREAM_BANDWIDTH: u32 = 12500 * 1000; // bytes/s
-        // Window size needed to avoid pipeline
-        // stalls
-        const STREAM_RWND: u32 = MAX_STREAM_BANDWIDTH / 1000 * EXPECTED_RTT;
-
         Self {
             max_concurrent_bidi_streams: 100u32.into(),
             max_concurrent_uni_streams: 100u32.into(),
@@ -376,27 +1072,58 @@ impl Default for TransportConfig {
             receive_window: VarInt::MAX,
             send_window: (8 * STREAM_RWND).into(),
             send_fairness: true,
+            retransmit_at_stream_priority: true,
 
             packet_threshold: 3,
             time_threshold: 9.0 / 8.0,
+            pto_backoff_base: 2.0,
+            max_pto_backoff: 1 << 16,
+            max_pto_count: None,
+            max_retransmission_time: None,
             initial_rtt: Duration::from_millis(333), // per spec, intentionally distinct from EXPECTED_RTT
+            rtt_estimator: RttEstimatorConfig::default(),
+            rtt_histogram_buckets: (0..69)
+                .map(|i| Duration::from_secs_f64(100e-6 * 2f64.powf(i as f64 / 4.0)))
+                .collect(),
             initial_mtu: INITIAL_MTU,
             min_mtu: INITIAL_MTU,
             mtu_discovery_config: Some(MtuDiscoveryConfig::default()),
             pad_to_mtu: false,
+            padding_policy: PaddingPolicy::None,
             ack_frequency_config: None,
+            ack_policy_config: AckPolicyConfig::default(),
+            max_ack_ranges: 64,
+            peer_limits: PeerLimitsConfig::default(),
             max_outgoing_bytes_per_second: None,
+            anti_amplification_factor: 3,
 
             persistent_congestion_threshold: 3,
             keep_alive_interval: None,
+            adaptive_keep_alive: None,
+            keep_alive_while_active: false,
+            cid_rotation: None,
+            max_issued_cids: LOC_CID_COUNT,
+            max_deferred_packets: 10,
+            max_deferred_bytes: 32 * 1024,
+            port_hopping: None,
+            grease: GreaseConfig::default(),
+            timer_coalescing: None,
             crypto_buffer_size: 16 * 1024,
             allow_spin: true,
+            dscp: None,
+            transmit_marker: None,
+            flow_label: false,
+            send_observed_address_reports: false,
+            receive_observed_address_reports: false,
             datagram_receive_buffer_size: Some(STREAM_RWND as usize),
             datagram_send_buffer_size: 1024 * 1024,
+            datagram_classifier: None,
+            datagram_flow_receive_buffer_size: None,
             #[cfg(test)]
             deterministic_packet_numbers: false,
 
             congestion_controller_factory: Arc::new(congestion::CubicConfig::default()),
+            loss_detector_factory: Arc::new(loss::ThresholdLossDetectorConfig::default()),
 
             enable_segmentation_offload: true,
 
@@ -415,24 +1142,53 @@ impl fmt::Debug for TransportConfig {
             receive_window,
             send_window,
             send_fairness,
+            retransmit_at_stream_priority,
             packet_threshold,
             time_threshold,
+            pto_backoff_base,
+            max_pto_backoff,
+            max_pto_count,
+            max_retransmission_time,
             initial_rtt,
+            rtt_estimator,
+            rtt_histogram_buckets,
             initial_mtu,
             min_mtu,
             mtu_discovery_config,
             pad_to_mtu,
+            padding_policy,
             ack_frequency_config,
+            ack_policy_config,
+            max_ack_ranges,
+            peer_limits,
             max_outgoing_bytes_per_second,
+            anti_amplification_factor,
             persistent_congestion_threshold,
             keep_alive_interval,
+            adaptive_keep_alive,
+            keep_alive_while_active,
+            cid_rotation,
+            max_issued_cids,
+            max_deferred_packets,
+            max_deferred_bytes,
+            port_hopping,
+            grease,
+            timer_coalescing,
             crypto_buffer_size,
             allow_spin,
+            dscp,
+            transmit_marker: _,
+            flow_label,
+            send_observed_address_reports,
+            receive_observed_address_reports,
             datagram_receive_buffer_size,
             datagram_send_buffer_size,
+            datagram_classifier: _,
+            datagram_flow_receive_buffer_size,
             #[cfg(test)]
                 deterministic_packet_numbers: _,
             congestion_controller_factory: _,
+            loss_detector_factory: _,
             enable_segmentation_offload,
             qlog_sink,
         } = self;
@@ -445,27 +1201,67 @@ impl fmt::Debug for TransportConfig {
             .field("receive_window", receive_window)
             .field("send_window", send_window)
             .field("send_fairness", send_fairness)
+            .field(
+                "retransmit_at_stream_priority",
+                retransmit_at_stream_priority,
+            )
             .field("packet_threshold", packet_threshold)
             .field("time_threshold", time_threshold)
+            .field("pto_backoff_base", pto_backoff_base)
+            .field("max_pto_backoff", max_pto_backoff)
+            .field("max_pto_count", max_pto_count)
+            .field("max_retransmission_time", max_retransmission_time)
             .field("initial_rtt", initial_rtt)
+            .field("rtt_estimator", rtt_estimator)
+            .field("rtt_histogram_buckets", &rtt_histogram_buckets.len())
             .field("initial_mtu", initial_mtu)
             .field("min_mtu", min_mtu)
             .field("mtu_discovery_config", mtu_discovery_config)
             .field("pad_to_mtu", pad_to_mtu)
+            .field("padding_policy", padding_policy)
             .field("ack_frequency_config", ack_frequency_config)
+            .field("ack_policy_config", ack_policy_config)
+            .field("max_ack_ranges", max_ack_ranges)
+            .field("peer_limits", peer_limits)
             .field(
                 "max_outgoing_bytes_per_second",
                 max_outgoing_bytes_per_second,
             )
+            .field("anti_amplification_factor", anti_amplification_factor)
             .field(
                 "persistent_congestion_threshold",
                 persistent_congestion_threshold,
             )
             .field("keep_alive_interval", keep_alive_interval)
+            .field("adaptive_keep_alive", adaptive_keep_alive)
+            .field("keep_alive_while_active", keep_alive_while_active)
+            .field("cid_rotation", cid_rotation)
+            .field("max_issued_cids", max_issued_cids)
+            .field("max_deferred_packets", max_deferred_packets)
+            .field("max_deferred_bytes", max_deferred_bytes)
+            .field("port_hopping", port_hopping)
+            .field("grease", grease)
+            .field("timer_coalescing", timer_coalescing)
             .field("crypto_buffer_size", crypto_buffer_size)
             .field("allow_spin", allow_spin)
+            .field("dscp", dscp)
+            // transmit_marker not debug
+            .field("flow_label", flow_label)
+            .field(
+                "send_observed_address_reports",
+                send_observed_address_reports,
+            )
+            .field(
+                "receive_observed_address_reports",
+                receive_observed_address_reports,
+            )
             .field("datagram_receive_buffer_size", datagram_receive_buffer_size)
             .field("datagram_send_buffer_size", datagram_send_buffer_size)
+            // datagram_classifier not debug
+            .field(
+                "datagram_flow_receive_buffer_size",
+                datagram_flow_receive_buffer_size,
+            )
             // congestion_controller_factory not debug
             .field("enable_segmentation_offload", enable_segmentation_offload);
         if cfg!(feature = "qlog") {
@@ -487,7 +1283,7 @@ impl fmt::Debug for TransportConfig {
 /// The defaults produce behavior slightly different than the behavior without this extension,
 /// because they change the way reordered packets are handled (see
 /// [`AckFrequencyConfig::reordering_threshold`] for details).
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AckFrequencyConfig {
     pub(crate) ack_eliciting_threshold: VarInt,
     pub(crate) max_ack_delay: Option<Duration>,
@@ -554,6 +1350,432 @@ impl Default for AckFrequencyConfig {
     }
 }
 
+/// Parameters governing when received packets are acknowledged
+///
+/// Acknowledging more aggressively lets the peer detect loss and grow its congestion window
+/// sooner, at the cost of sending more packets. Acknowledging less often saves processing and
+/// energy, but may slow down the peer.
+///
+/// These apply to application data packets until the peer requests different values through the
+/// [QUIC Acknowledgement Frequency extension](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency-04).
+/// Handshake packets are always acknowledged immediately.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AckPolicyConfig {
+    pub(crate) ack_eliciting_threshold: VarInt,
+    pub(crate) max_ack_delay: Duration,
+    pub(crate) reordering_threshold: VarInt,
+    pub(crate) redundancy: u32,
+}
+
+impl AckPolicyConfig {
+    /// Number of ack-eliciting packets that may be received without immediately sending an ACK
+    ///
+    /// A value of 0 acknowledges every ack-eliciting packet immediately. Defaults to 1, which
+    /// sends ACK frames for every other ack-eliciting packet, per RFC 9000 §13.2.2.
+    pub fn ack_eliciting_threshold(&mut self, value: VarInt) -> &mut Self {
+        self.ack_eliciting_threshold = value;
+        self
+    }
+
+    /// Maximum amount of time an ACK is delayed when the ack-eliciting threshold hasn't been
+    /// reached
+    ///
+    /// Advertised to the peer, which takes it into account for loss detection. Rounded down to
+    /// whole milliseconds, and clamped to between 1ms and 2^14 - 1 milliseconds. Defaults to 25ms.
+    pub fn max_ack_delay(&mut self, value: Duration) -> &mut Self {
+        self.max_ack_delay = value;
+        self
+    }
+
+    /// Number of out-of-order packets that trigger an immediate ACK
+    ///
+    /// A value of 0 indicates out-of-order packets do not elicit an immediate ACK. A value of 1
+    /// immediately acknowledges any packet that is received out of order, per RFC 9000 §13.2.1.
+    /// Larger values only trigger an immediate ACK if it would allow the peer to declare a packet
+    /// lost, as specified by the acknowledgement frequency extension. Defaults to 1.
+    pub fn reordering_threshold(&mut self, value: VarInt) -> &mut Self {
+        self.reordering_threshold = value;
+        self
+    }
+
+    /// Number of further packets each ACK frame is repeated in
+    ///
+    /// Makes acknowledgements survive the loss of some of the packets carrying them, at the cost
+    /// of up to this many additional ACK frames, sent in ACK-only packets if nothing else is being
+    /// sent. Worthwhile when the path towards the peer is much lossier than the one from it, as
+    /// with some satellite and cellular uplinks, where lost ACKs would otherwise cause the peer
+    /// to needlessly retransmit and reduce its sending rate. Unlike the thresholds, this isn't
+    /// affected by requests of the peer. Defaults to 0.
+    pub fn redundancy(&mut self, value: u32) -> &mut Self {
+        self.redundancy = value;
+        self
+    }
+
+    /// `max_ack_delay` as advertised in the transport parameters
+    pub(crate) fn max_ack_delay_millis(&self) -> u64 {
+        (self.max_ack_delay.as_millis() as u64).clamp(1, (1 << 14) - 1)
+    }
+}
+
+impl Default for AckPolicyConfig {
+    fn default() -> Self {
+        Self {
+            ack_eliciting_threshold: VarInt(1),
+            max_ack_delay: Duration::from_millis(25),
+            reordering_threshold: VarInt(1),
+            redundancy: 0,
+        }
+    }
+}
+
+/// Parameters governing the discovery of the keep-alive interval
+///
+/// Each keep-alive tests whether the path survives the inactivity preceding it. Once a keep-alive
+/// is answered, the interval grows by the [`growth_factor`](Self::growth_factor). When the peer
+/// observes a new address for this endpoint shortly after a keep-alive, the NAT binding expired
+/// during the inactivity, and the interval settles on the longest one that was answered. The
+/// settled interval is reported through [`TransportEvent::KeepAliveSettled`].
+///
+/// Only one side of a connection, typically the one behind a NAT, should enable discovery.
+/// Discovery relies on the peer validating the new address, so it is ineffective if the peer
+/// doesn't support migration.
+///
+/// [`TransportEvent::KeepAliveSettled`]: crate::TransportEvent::KeepAliveSettled
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdaptiveKeepAliveConfig {
+    pub(crate) initial_interval: Duration,
+    pub(crate) min_interval: Duration,
+    pub(crate) max_interval: Duration,
+    pub(crate) growth_factor: f32,
+}
+
+impl AdaptiveKeepAliveConfig {
+    /// Interval to start discovery from
+    ///
+    /// Defaults to 15 seconds, which preserves the bindings of most NATs.
+    pub fn initial_interval(&mut self, value: Duration) -> &mut Self {
+        self.initial_interval = value;
+        self
+    }
+
+    /// Lower bound on the interval, used if even the initial interval was too long
+    ///
+    /// Defaults to 5 seconds.
+    pub fn min_interval(&mut self, value: Duration) -> &mut Self {
+        self.min_interval = value;
+        self
+    }
+
+    /// Upper bound on the interval
+    ///
+    /// The interval is further limited to half the connection's idle timeout. Defaults to 5
+    /// minutes, the minimum binding timeout recommended by RFC 4787.
+    pub fn max_interval(&mut self, value: Duration) -> &mut Self {
+        self.max_interval = value;
+        self
+    }
+
+    /// Factor by which the interval grows after each answered keep-alive
+    ///
+    /// Larger factors speed up discovery, but settle on a less accurate interval. A factor of 1
+    /// settles on the initial interval right away. Clamped to at least 1, with NaN treated as 1,
+    /// and to at most `f32::MAX`. Defaults to 1.5.
+    pub fn growth_factor(&mut self, value: f32) -> &mut Self {
+        self.growth_factor = if value.is_nan() {
+            1.0
+        } else {
+            value.clamp(1.0, f32::MAX)
+        };
+        self
+    }
+}
+
+impl Default for AdaptiveKeepAliveConfig {
+    fn default() -> Self {
+        Self {
+            initial_interval: Duration::from_secs(15),
+            min_interval: Duration::from_secs(5),
+            max_interval: Duration::from_secs(300),
+            growth_factor: 1.5,
+        }
+    }
+}
+
+/// Limits on the rate of actions that are cheap for the peer but costly to process
+///
+/// Defends against resource exhaustion by a misbehaving peer, as described in RFC 9000 §21.9.
+/// Rates are counted over one-second windows. Datagrams exceeding their limit are dropped before
+/// being decrypted, whereas exceeding any of the other limits closes the connection with an
+/// `INTERNAL_ERROR`, as the peer's behavior is legal but excessive. Frames repeating an earlier
+/// reset or retirement, e.g. due to retransmission, don't count towards the limits.
+///
+/// All limits are disabled by default, since suitable values depend on the application.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerLimitsConfig {
+    pub(crate) max_datagrams_per_second: Option<u32>,
+    pub(crate) max_cid_retirements_per_second: Option<u32>,
+    pub(crate) max_stream_resets_per_second: Option<u32>,
+    pub(crate) max_frames_per_packet: Option<u32>,
+}
+
+impl PeerLimitsConfig {
+    /// Number of datagrams received per second beyond which further datagrams are dropped
+    pub fn max_datagrams_per_second(&mut self, value: Option<u32>) -> &mut Self {
+        self.max_datagrams_per_second = value;
+        self
+    }
+
+    /// Number of `RETIRE_CONNECTION_ID` frames the peer may send per second
+    pub fn max_cid_retirements_per_second(&mut self, value: Option<u32>) -> &mut Self {
+        self.max_cid_retirements_per_second = value;
+        self
+    }
+
+    /// Number of `RESET_STREAM` and `STOP_SENDING` frames the peer may send per second
+    pub fn max_stream_resets_per_second(&mut self, value: Option<u32>) -> &mut Self {
+        self.max_stream_resets_per_second = value;
+        self
+    }
+
+    /// Number of frames a single packet may contain, excluding padding
+    ///
+    /// Guards against packets packed with many tiny frames, each of which must be processed
+    /// separately.
+    pub fn max_frames_per_packet(&mut self, value: Option<u32>) -> &mut Self {
+        self.max_frames_per_packet = value;
+        self
+    }
+}
+
+/// Parameters of the RTT estimation, see [`TransportConfig::rtt_estimator`]
+///
+/// The defaults follow RFC 9002 §5. Paths with stable latency, as in datacenters, may react
+/// faster with larger gains, while the minimum RTT of paths whose latency changes over time, as
+/// with satellites in motion, can be kept current by expiring it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RttEstimatorConfig {
+    pub(crate) smoothing_gain: f64,
+    pub(crate) variance_gain: f64,
+    pub(crate) min_rtt_window: Option<Duration>,
+}
+
+impl RttEstimatorConfig {
+    /// Weight of each new RTT sample in the smoothed RTT, between 0 and 1
+    ///
+    /// NaN is replaced by the default of 1/8.
+    pub fn smoothing_gain(&mut self, value: f64) -> &mut Self {
+        self.smoothing_gain = clamp_gain(value, Self::default().smoothing_gain);
+        self
+    }
+
+    /// Weight of each new RTT sample's deviation in the RTT variation, between 0 and 1
+    ///
+    /// NaN is replaced by the default of 1/4.
+    pub fn variance_gain(&mut self, value: f64) -> &mut Self {
+        self.variance_gain = clamp_gain(value, Self::default().variance_gain);
+        self
+    }
+
+    /// Time span of the samples the minimum RTT is taken from
+    ///
+    /// Once the minimum is older than the window, the smallest sample seen since within the window
+    /// takes its place, so the minimum follows a lasting rise in latency after at most a window.
+    /// `None` keeps the minimum over the lifetime of the path. Defaults to `None`.
+    pub fn min_rtt_window(&mut self, value: Option<Duration>) -> &mut Self {
+        self.min_rtt_window = value;
+        self
+    }
+}
+
+fn clamp_gain(value: f64, default: f64) -> f64 {
+    match value.is_nan() {
+        true => default,
+        false => value.clamp(0.0, 1.0),
+    }
+}
+
+impl Default for RttEstimatorConfig {
+    fn default() -> Self {
+        Self {
+            smoothing_gain: 1.0 / 8.0,
+            variance_gain: 1.0 / 4.0,
+            min_rtt_window: None,
+        }
+    }
+}
+
+/// Parameters governing the rotation of connection IDs, see [`TransportConfig::cid_rotation`]
+///
+/// The connection ID used for sending is replaced once either limit is reached, when the next
+/// packet is sent. Rotation is skipped while the peer hasn't issued a spare connection ID, and the
+/// limits restart whenever the connection ID changes for other reasons, such as migration.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CidRotationConfig {
+    pub(crate) interval: Option<Duration>,
+    pub(crate) bytes: Option<u64>,
+}
+
+impl CidRotationConfig {
+    /// Time after which a connection ID is replaced
+    ///
+    /// Defaults to 1 minute.
+    pub fn interval(&mut self, value: Option<Duration>) -> &mut Self {
+        self.interval = value;
+        self
+    }
+
+    /// Number of UDP payload bytes sent after which a connection ID is replaced
+    ///
+    /// Defaults to `None`.
+    pub fn bytes(&mut self, value: Option<u64>) -> &mut Self {
+        self.bytes = value;
+        self
+    }
+}
+
+impl Default for CidRotationConfig {
+    fn default() -> Self {
+        Self {
+            interval: Some(Duration::from_secs(60)),
+            bytes: None,
+        }
+    }
+}
+
+/// Parameters governing UDP port hopping, see [`TransportConfig::port_hopping`]
+///
+/// Once the interval elapsed, the next packet is sent to a randomly chosen port other than the
+/// current one, along with a fresh connection ID and a `PATH_CHALLENGE`. If the server doesn't
+/// answer from the new port within a few PTOs, the connection falls back to the previous port.
+/// Hops are postponed while the server has issued no spare connection ID, since reusing the
+/// current one would link the two ports. Packets from the server are accepted from any of the
+/// configured ports, as well as the port the connection was established with.
+///
+/// The server must receive on all of the ports, e.g. through a firewall rule redirecting them to
+/// the port its endpoint is bound to. Clients using zero-length connection IDs can't hop, as their
+/// endpoint routes packets by the server's address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortHoppingConfig {
+    pub(crate) ports: Vec<u16>,
+    pub(crate) interval: Duration,
+}
+
+impl PortHoppingConfig {
+    /// Ports of the server to hop between, e.g. a range like `20000..=20999`
+    ///
+    /// Defaults to none, which disables hopping.
+    pub fn ports(&mut self, value: impl IntoIterator<Item = u16>) -> &mut Self {
+        self.ports = value.into_iter().collect();
+        self.ports.sort_unstable();
+        self.ports.dedup();
+        self
+    }
+
+    /// Time spent on a port before hopping to the next
+    ///
+    /// Defaults to 30 seconds.
+    pub fn interval(&mut self, value: Duration) -> &mut Self {
+        self.interval = value;
+        self
+    }
+}
+
+impl Default for PortHoppingConfig {
+    fn default() -> Self {
+        Self {
+            ports: Vec::new(),
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Parameters governing anti-ossification greasing, see [`TransportConfig::grease`]
+///
+/// Reserved transport parameters ([RFC 9000 §18.1]) carry random payloads and must be ignored by
+/// the peer. Random amounts of `PADDING` vary the sizes of 1-RTT packets, so that observers can't
+/// come to rely on them. QUIC version 1 reserves no frame types, and peers are required to close
+/// the connection on receipt of one they don't know, so frame types are never greased.
+///
+/// Greasing of the fixed bit is configured separately, through
+/// [`EndpointConfig::grease_quic_bit`](crate::EndpointConfig::grease_quic_bit).
+///
+/// [RFC 9000 §18.1]: https://www.rfc-editor.org/rfc/rfc9000.html#section-18.1
+#[derive(Clone, Debug, PartialEq)]
+pub struct GreaseConfig {
+    pub(crate) transport_parameters: u8,
+    pub(crate) padding_frequency: f64,
+    pub(crate) max_padding: u16,
+}
+
+impl GreaseConfig {
+    /// Number of reserved transport parameters to send
+    ///
+    /// Defaults to 1.
+    pub fn transport_parameters(&mut self, value: u8) -> &mut Self {
+        self.transport_parameters = value;
+        self
+    }
+
+    /// Fraction of ack-eliciting 1-RTT packets to extend with a random amount of `PADDING`
+    ///
+    /// Clamped to the range 0 to 1, with NaN treated as 0. Defaults to 0.
+    pub fn padding_frequency(&mut self, value: f64) -> &mut Self {
+        self.padding_frequency = match value.is_nan() {
+            true => 0.0,
+            false => value.clamp(0.0, 1.0),
+        };
+        self
+    }
+
+    /// Largest number of `PADDING` bytes to add to a packet, space permitting
+    ///
+    /// Defaults to 32.
+    pub fn max_padding(&mut self, value: u16) -> &mut Self {
+        self.max_padding = value;
+        self
+    }
+}
+
+impl Default for GreaseConfig {
+    fn default() -> Self {
+        Self {
+            transport_parameters: 1,
+            padding_frequency: 0.0,
+            max_padding: 32,
+        }
+    }
+}
+
+/// How UDP datagrams carrying application data are padded, see
+/// [`TransportConfig::padding_policy`]
+///
+/// Padding hides the exact size of what a datagram carries from network observers, at the cost
+/// of bandwidth. It is applied to the last packet of each datagram, and never grows a datagram
+/// beyond the current maximum UDP payload size, or the minimum MTU for loss probes. Datagrams
+/// sent in a single batch using GSO share the size of the first.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub enum PaddingPolicy {
+    /// Only pad datagrams where required by the protocol
+    #[default]
+    None,
+    /// Pad every datagram to this size
+    Fixed(u16),
+    /// Pad every datagram to the smallest of these sizes it fits in
+    ///
+    /// Datagrams larger than all of the sizes are padded to the maximum UDP payload size.
+    Buckets(Arc<[u16]>),
+    /// Pad datagrams by a uniformly random number of bytes
+    Random {
+        /// Largest number of bytes to add to a single datagram
+        max: u16,
+        /// Limit on the total padding, as a fraction of all UDP payload bytes sent
+        ///
+        /// Datagrams aren't padded while this would be exceeded.
+        budget: f32,
+    },
+}
+
 /// Configuration for qlog trace logging
 #[cfg(feature = "qlog")]
 pub struct QlogConfig {
@@ -584,6 +1806,10 @@ impl QlogConfig {
     }
 
     /// Epoch qlog event times are recorded relative to
+    ///
+    /// Defaults to the time the configuration was constructed, read from the operating system.
+    /// Should be set when the `now` passed to quinn-proto isn't derived from the system clock, e.g.
+    /// in simulations.
     pub fn start_time(&mut self, start_time: Instant) -> &mut Self {
         self.start_time = start_time;
         self
@@ -695,12 +1921,13 @@ impl Default for QlogConfig {
 /// There is no guarantee that the MTU on the path between A and B is the same as the MTU of the
 /// path between B and A. Therefore, each peer in the connection needs to run MTU discovery
 /// independently in order to discover the path's MTU.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct MtuDiscoveryConfig {
     pub(crate) interval: Duration,
     pub(crate) upper_bound: u16,
     pub(crate) minimum_change: u16,
     pub(crate) black_hole_cooldown: Duration,
+    pub(crate) probe_sizes: Vec<u16>,
 }
 
 impl MtuDiscoveryConfig {
@@ -742,6 +1969,16 @@ impl MtuDiscoveryConfig {
         self.minimum_change = value;
         self
     }
+
+    /// Specifies the UDP payload sizes to probe, instead of binary searching up to the upper bound
+    ///
+    /// Sizes are probed in ascending order, skipping those beyond the upper bound, until a probe
+    /// fails. Useful when the MTUs of the networks in use are known, e.g. to check for jumbo frames
+    /// with a single probe. Defaults to empty, i.e. binary search.
+    pub fn probe_sizes(&mut self, value: Vec<u16>) -> &mut Self {
+        self.probe_sizes = value;
+        self
+    }
 }
 
 impl Default for MtuDiscoveryConfig {
@@ -751,6 +1988,7 @@ impl Default for MtuDiscoveryConfig {
             upper_bound: 1452,
             black_hole_cooldown: Duration::from_secs(60),
             minimum_change: 20,
+            probe_sizes: Vec::new(),
         }
     }
 }
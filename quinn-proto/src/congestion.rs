@@ -66,6 +66,13 @@ pub trait Controller: Send + Sync {
     /// of packet reordering) are acknowledged after the congestion event was raised.
     fn on_spurious_congestion_event(&mut self) {}
 
+    /// A previous connection over what appears to be the same path sustained a window of `window`
+    ///
+    /// Controllers may jump to `window` rather than probe for it, but must retreat if the path
+    /// turns out not to support it. Ignored by default.
+    #[allow(unused_variables)]
+    fn on_resume_hint(&mut self, now: Instant, window: u64) {}
+
     /// The known MTU for the current network path has been updated
     fn on_mtu_update(&mut self, new_mtu: u16);
 
@@ -110,3 +117,39 @@ pub trait ControllerFactory {
 }
 
 const BASE_DATAGRAM_SIZE: u64 = 1200;
+
+/// A congestion window resumed from a previous connection, until it's validated
+///
+/// Implements the unvalidated phase of draft-ietf-tsvwg-careful-resume: once as many bytes as the
+/// resumed window have been acknowledged, the window is known to be safe. If a packet sent after
+/// the jump is lost before that, the window retreats to half of the data actually delivered.
+#[derive(Debug, Clone)]
+struct Resume {
+    /// When the window was resumed
+    start: Instant,
+    /// The resumed window
+    window: u64,
+    /// Bytes acknowledged since `start`
+    acked: u64,
+}
+
+impl Resume {
+    fn new(now: Instant, window: u64) -> Self {
+        Self {
+            start: now,
+            window,
+            acked: 0,
+        }
+    }
+
+    /// Count acknowledged bytes, returning whether the window is now validated
+    fn on_ack(&mut self, bytes: u64) -> bool {
+        self.acked += bytes;
+        self.acked >= self.window
+    }
+
+    /// Window to retreat to if a packet sent at `sent` was lost
+    fn retreat(&self, sent: Instant, minimum_window: u64) -> Option<u64> {
+        (sent > self.start).then(|| (self.acked / 2).max(minimum_window))
+    }
+}
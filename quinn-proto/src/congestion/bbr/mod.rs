@@ -5,6 +5,7 @@ use std::sync::Arc;
 use rand::{RngExt, SeedableRng};
 use rand_pcg::Pcg32;
 
+use crate::ConfigError;
 use crate::congestion::ControllerMetrics;
 use crate::congestion::bbr::bw_estimation::BandwidthEstimation;
 use crate::congestion::bbr::min_max::MinMax;
@@ -39,7 +40,7 @@ pub struct Bbr {
     cwnd_gain: f32,
     high_cwnd_gain: f32,
     last_cycle_start: Option<Instant>,
-    current_cycle_offset: u8,
+    current_cycle_offset: usize,
     init_cwnd: u64,
     min_cwnd: u64,
     prev_in_flight_count: u64,
@@ -110,19 +111,21 @@ impl Bbr {
 
     fn enter_probe_bandwidth_mode(&mut self, now: Instant) {
         self.mode = Mode::ProbeBw;
-        self.cwnd_gain = K_DERIVED_HIGH_CWNDGAIN;
+        self.cwnd_gain = self.config.cwnd_gain;
         self.last_cycle_start = Some(now);
-        // Pick a random offset for the gain cycle out of {0, 2..7} range. 1 is
+        // Pick a random offset for the gain cycle out of {0, 2..len} range. 1 is
         // excluded because in that case increased gain and decreased gain would not
         // follow each other.
-        let mut rand_index = self
-            .random_number_generator
-            .random_range(0..K_PACING_GAIN.len() as u8 - 1);
+        let cycle_len = self.config.pacing_gain_cycle.len();
+        let mut rand_index = match cycle_len {
+            1 => 0,
+            _ => self.random_number_generator.random_range(0..cycle_len - 1),
+        };
         if rand_index >= 1 {
             rand_index += 1;
         }
         self.current_cycle_offset = rand_index;
-        self.pacing_gain = K_PACING_GAIN[rand_index as usize];
+        self.pacing_gain = self.config.pacing_gain_cycle[rand_index];
     }
 
     fn update_recovery_state(&mut self, is_round_start: bool) {
@@ -184,18 +187,20 @@ impl Bbr {
         }
 
         if should_advance_gain_cycling {
-            self.current_cycle_offset = (self.current_cycle_offset + 1) % K_PACING_GAIN.len() as u8;
+            self.current_cycle_offset =
+                (self.current_cycle_offset + 1) % self.config.pacing_gain_cycle.len();
             self.last_cycle_start = Some(now);
             // Stay in low gain mode until the target BDP is hit.  Low gain mode
             // will be exited immediately when the target BDP is achieved.
             if DRAIN_TO_TARGET
                 && self.pacing_gain < 1.0
-                && (K_PACING_GAIN[self.current_cycle_offset as usize] - 1.0).abs() < f32::EPSILON
+                && (self.config.pacing_gain_cycle[self.current_cycle_offset] - 1.0).abs()
+                    < f32::EPSILON
                 && in_flight > self.get_target_cwnd(1.0)
             {
                 return;
             }
-            self.pacing_gain = K_PACING_GAIN[self.current_cycle_offset as usize];
+            self.pacing_gain = self.config.pacing_gain_cycle[self.current_cycle_offset];
         }
     }
 
@@ -211,10 +216,13 @@ impl Bbr {
     }
 
     fn is_min_rtt_expired(&self, now: Instant, app_limited: bool) -> bool {
+        let Some(interval) = self.config.probe_rtt_interval else {
+            return false;
+        };
         !app_limited
             && self
                 .probe_rtt_last_started_at
-                .map(|last| now.saturating_duration_since(last) > Duration::from_secs(10))
+                .map(|last| now.saturating_duration_since(last) > interval)
                 .unwrap_or(true)
     }
 
@@ -408,7 +416,11 @@ impl Controller for Bbr {
         self.max_bandwidth
             .on_ack(now, sent, bytes, self.round_count, app_limited);
         self.acked_bytes += bytes;
-        if self.is_min_rtt_expired(now, app_limited) || self.min_rtt > rtt.min() {
+        // Seeded by the first sample even if PROBE_RTT is disabled
+        if self.min_rtt.is_zero()
+            || self.is_min_rtt_expired(now, app_limited)
+            || self.min_rtt > rtt.min()
+        {
             self.min_rtt = rtt.min();
         }
     }
@@ -517,6 +529,9 @@ impl Controller for Bbr {
 #[derive(Debug, Clone)]
 pub struct BbrConfig {
     initial_window: u64,
+    pacing_gain_cycle: Arc<[f32]>,
+    probe_rtt_interval: Option<Duration>,
+    cwnd_gain: f32,
 }
 
 impl BbrConfig {
@@ -527,12 +542,60 @@ impl BbrConfig {
         self.initial_window = value;
         self
     }
+
+    /// Pacing gains cycled through in PROBE_BW mode, one phase per round trip
+    ///
+    /// The cycle starts at a random phase other than the second, so that a probing phase is
+    /// followed by the draining phase after it. An empty cycle is treated as a constant gain of 1.
+    /// Gains must be finite and positive.
+    ///
+    /// Defaults to `[1.25, 0.75, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]`.
+    pub fn pacing_gain_cycle(&mut self, gains: Vec<f32>) -> Result<&mut Self, ConfigError> {
+        if !gains.iter().copied().all(is_valid_gain) {
+            return Err(ConfigError::OutOfBounds);
+        }
+        self.pacing_gain_cycle = match gains.is_empty() {
+            true => [1.0].into(),
+            false => gains.into(),
+        };
+        Ok(self)
+    }
+
+    /// Interval after which the window is reduced for 200ms to measure the minimum RTT afresh
+    ///
+    /// `None` disables PROBE_RTT mode, at the cost of the minimum RTT going stale when the path
+    /// changes. Worthwhile for short, latency-sensitive connections, for which the dip in
+    /// throughput would be noticeable.
+    ///
+    /// Defaults to 10 seconds.
+    pub fn probe_rtt_interval(&mut self, value: Option<Duration>) -> &mut Self {
+        self.probe_rtt_interval = value;
+        self
+    }
+
+    /// Multiple of the estimated bandwidth-delay product the window is grown to in PROBE_BW mode
+    ///
+    /// Must be finite and positive. Defaults to 2.
+    pub fn cwnd_gain(&mut self, value: f32) -> Result<&mut Self, ConfigError> {
+        if !is_valid_gain(value) {
+            return Err(ConfigError::OutOfBounds);
+        }
+        self.cwnd_gain = value;
+        Ok(self)
+    }
+}
+
+fn is_valid_gain(gain: f32) -> bool {
+    gain.is_finite() && gain > 0.0
 }
 
 impl Default for BbrConfig {
     fn default() -> Self {
         Self {
             initial_window: K_MAX_INITIAL_CONGESTION_WINDOW * BASE_DATAGRAM_SIZE,
+            pacing_gain_cycle: K_PACING_GAIN.into(),
+            probe_rtt_interval: Some(Duration::from_secs(10)),
+            cwnd_gain: K_DERIVED_HIGH_CWNDGAIN,
         }
     }
 }
@@ -650,3 +713,80 @@ const K_MAX_INITIAL_CONGESTION_WINDOW: u64 = 200;
 
 const PROBE_RTT_BASED_ON_BDP: bool = true;
 const DRAIN_TO_TARGET: bool = true;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configured_gains() {
+        let now = Instant::now();
+        let mut config = BbrConfig::default();
+        config
+            .pacing_gain_cycle(vec![1.5])
+            .unwrap()
+            .cwnd_gain(3.0)
+            .unwrap();
+        let mut bbr = Bbr::new(Arc::new(config), BASE_DATAGRAM_SIZE as u16);
+
+        bbr.enter_probe_bandwidth_mode(now);
+        assert_eq!(bbr.pacing_gain, 1.5);
+        assert_eq!(bbr.cwnd_gain, 3.0);
+    }
+
+    #[test]
+    fn probe_rtt_interval() {
+        let now = Instant::now();
+        let bbr = Bbr::new(Arc::new(BbrConfig::default()), BASE_DATAGRAM_SIZE as u16);
+        assert!(bbr.is_min_rtt_expired(now, false));
+
+        let mut config = BbrConfig::default();
+        config.probe_rtt_interval(None);
+        let mut bbr = Bbr::new(Arc::new(config), BASE_DATAGRAM_SIZE as u16);
+        assert!(!bbr.is_min_rtt_expired(now, false));
+        bbr.maybe_enter_or_exit_probe_rtt(now, true, 0, false);
+        assert_ne!(bbr.mode, Mode::ProbeRtt);
+    }
+
+    #[test]
+    fn invalid_gains() {
+        let mut config = BbrConfig::default();
+        for gain in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(config.cwnd_gain(gain).is_err());
+            assert!(config.pacing_gain_cycle(vec![1.25, gain]).is_err());
+        }
+        assert_eq!(config.cwnd_gain, K_DERIVED_HIGH_CWNDGAIN);
+        assert_eq!(&config.pacing_gain_cycle[..], K_PACING_GAIN);
+    }
+
+    #[test]
+    fn min_rtt_without_probe_rtt() {
+        let mut config = BbrConfig::default();
+        config.probe_rtt_interval(None);
+        let mut bbr = Bbr::new(Arc::new(config), BASE_DATAGRAM_SIZE as u16);
+        let initial_window = bbr.window();
+        let rtt_sample = Duration::from_millis(50);
+        let mut rtt = RttEstimator::new(Duration::from_millis(333), &Default::default());
+        let mut now = Instant::now();
+        let mut packet = 0;
+        for _ in 0..5 {
+            let sent = now;
+            for _ in 0..10 {
+                bbr.on_sent(sent, BASE_DATAGRAM_SIZE, packet);
+                packet += 1;
+            }
+            now += rtt_sample;
+            rtt.update(now, Duration::ZERO, rtt_sample);
+            for _ in 0..10 {
+                bbr.on_ack(now, sent, BASE_DATAGRAM_SIZE, false, &rtt);
+            }
+            bbr.on_end_acks(now, 0, false, Some(packet - 1));
+        }
+        assert_eq!(bbr.min_rtt, rtt_sample);
+        assert_ne!(bbr.pacing_rate, 0);
+        // The window follows the estimated bandwidth-delay product
+        assert_eq!(bbr.mode, Mode::ProbeBw);
+        assert_ne!(bbr.get_target_cwnd(1.0), bbr.init_cwnd);
+        assert!(bbr.window() < initial_window);
+    }
+}
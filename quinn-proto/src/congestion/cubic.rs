@@ -2,7 +2,7 @@ use std::any::Any;
 use std::cmp;
 use std::sync::Arc;
 
-use super::{BASE_DATAGRAM_SIZE, Controller, ControllerFactory};
+use super::{BASE_DATAGRAM_SIZE, Controller, ControllerFactory, Resume};
 use crate::connection::RttEstimator;
 use crate::{Duration, Instant};
 
@@ -79,6 +79,8 @@ pub struct Cubic {
     state: State,
     /// Copy of the controller state to restore when a spurious congestion event is detected.
     pre_congestion_state: Option<State>,
+    /// Window resumed from a previous connection which hasn't been validated yet
+    resume: Option<Resume>,
 }
 
 impl Cubic {
@@ -92,6 +94,7 @@ impl Cubic {
             },
             current_mtu: current_mtu as u64,
             pre_congestion_state: None,
+            resume: None,
             config,
         }
     }
@@ -110,6 +113,9 @@ impl Controller for Cubic {
         app_limited: bool,
         rtt: &RttEstimator,
     ) {
+        if self.resume.as_mut().is_some_and(|x| x.on_ack(bytes)) {
+            self.resume = None;
+        }
         if app_limited
             || self
                 .state
@@ -197,6 +203,22 @@ impl Controller for Cubic {
             return;
         }
 
+        if let Some(window) = self
+            .resume
+            .take()
+            .and_then(|x| x.retreat(sent, self.minimum_window()))
+        {
+            // Safe retreat isn't undone by a spurious congestion event
+            self.pre_congestion_state = None;
+            self.state.recovery_start_time = Some(now);
+            self.state.w_max = window as f64;
+            self.state.k = 0.0;
+            self.state.cwnd_inc = 0;
+            self.state.ssthresh = window;
+            self.state.window = window;
+            return;
+        }
+
         // Save state in case this event ends up being spurious
         if !is_ecn {
             self.pre_congestion_state = Some(self.state.clone());
@@ -245,6 +267,11 @@ impl Controller for Cubic {
         }
     }
 
+    fn on_resume_hint(&mut self, now: Instant, window: u64) {
+        self.state.window = window;
+        self.resume = Some(Resume::new(now, window));
+    }
+
     fn on_mtu_update(&mut self, new_mtu: u16) {
         self.current_mtu = new_mtu as u64;
         self.state.window = self.state.window.max(self.minimum_window());
@@ -329,7 +356,7 @@ mod tests {
     #[test]
     fn congestion_avoidance_preserves_excess_cwnd_increment() {
         let now = Instant::now();
-        let rtt = RttEstimator::new(Duration::from_millis(100));
+        let rtt = RttEstimator::new(Duration::from_millis(100), &Default::default());
         let config = Arc::new(CubicConfig::default());
         let mut cubic = Cubic::new(config, now, BASE_DATAGRAM_SIZE as u16);
 
@@ -356,4 +383,26 @@ mod tests {
         assert_eq!(cubic.state.window, window + BASE_DATAGRAM_SIZE);
         assert_eq!(cubic.state.cwnd_inc, BASE_DATAGRAM_SIZE + 1);
     }
+
+    #[test]
+    fn resume_retreats_on_unvalidated_loss() {
+        let now = Instant::now();
+        let rtt = RttEstimator::new(Duration::from_millis(100), &Default::default());
+        let config = Arc::new(CubicConfig::default());
+        let mut cubic = Cubic::new(config, now, BASE_DATAGRAM_SIZE as u16);
+
+        let window = 100 * BASE_DATAGRAM_SIZE;
+        cubic.on_resume_hint(now, window);
+        assert_eq!(cubic.window(), window);
+
+        let sent = now + Duration::from_millis(1);
+        let acked = 40 * BASE_DATAGRAM_SIZE;
+        cubic.on_ack(now + rtt.get(), sent, acked, false, &rtt);
+        // Lost before the resumed window was validated
+        cubic.on_congestion_event(now + rtt.get(), sent, false, false, BASE_DATAGRAM_SIZE);
+        assert_eq!(cubic.state.window, acked / 2);
+        assert_eq!(cubic.state.ssthresh, acked / 2);
+        cubic.on_spurious_congestion_event();
+        assert_eq!(cubic.state.window, acked / 2);
+    }
 }
@@ -1,7 +1,7 @@
 use std::any::Any;
 use std::sync::Arc;
 
-use super::{BASE_DATAGRAM_SIZE, Controller, ControllerFactory};
+use super::{BASE_DATAGRAM_SIZE, Controller, ControllerFactory, Resume};
 use crate::Instant;
 use crate::connection::RttEstimator;
 
@@ -20,6 +20,8 @@ pub struct NewReno {
     recovery_start_time: Instant,
     /// Bytes which had been acked by the peer since leaving slow start
     bytes_acked: u64,
+    /// Window resumed from a previous connection which hasn't been validated yet
+    resume: Option<Resume>,
 }
 
 impl NewReno {
@@ -32,6 +34,7 @@ impl NewReno {
             current_mtu: current_mtu as u64,
             config,
             bytes_acked: 0,
+            resume: None,
         }
     }
 
@@ -49,6 +52,9 @@ impl Controller for NewReno {
         app_limited: bool,
         _rtt: &RttEstimator,
     ) {
+        if self.resume.as_mut().is_some_and(|x| x.on_ack(bytes)) {
+            self.resume = None;
+        }
         if app_limited || sent <= self.recovery_start_time {
             return;
         }
@@ -94,6 +100,17 @@ impl Controller for NewReno {
             return;
         }
 
+        if let Some(window) = self
+            .resume
+            .take()
+            .and_then(|x| x.retreat(sent, self.minimum_window()))
+        {
+            self.recovery_start_time = now;
+            self.window = window;
+            self.ssthresh = window;
+            return;
+        }
+
         self.recovery_start_time = now;
         self.window = (self.window as f32 * self.config.loss_reduction_factor) as u64;
         self.window = self.window.max(self.minimum_window());
@@ -104,6 +121,11 @@ impl Controller for NewReno {
         }
     }
 
+    fn on_resume_hint(&mut self, now: Instant, window: u64) {
+        self.window = window;
+        self.resume = Some(Resume::new(now, window));
+    }
+
     fn on_mtu_update(&mut self, new_mtu: u16) {
         self.current_mtu = new_mtu as u64;
         self.window = self.window.max(self.minimum_window());
@@ -21,17 +21,22 @@ pub(super) struct AckFrequencyState {
 }
 
 impl AckFrequencyState {
-    pub(super) fn new(default_max_ack_delay: Duration) -> Self {
+    pub(super) fn new(default_peer_max_ack_delay: Duration, max_ack_delay: Duration) -> Self {
         Self {
             in_flight_ack_frequency_frame: None,
             next_outgoing_sequence_number: VarInt(0),
-            peer_max_ack_delay: default_max_ack_delay,
+            peer_max_ack_delay: default_peer_max_ack_delay,
 
             last_ack_frequency_frame: None,
-            max_ack_delay: default_max_ack_delay,
+            max_ack_delay,
         }
     }
 
+    /// Whether the peer requested ACK parameters through an ACK_FREQUENCY frame
+    pub(super) fn peer_requested(&self) -> bool {
+        self.last_ack_frequency_frame.is_some()
+    }
+
     /// Returns the `max_ack_delay` that should be requested of the peer when sending an
     /// ACK_FREQUENCY frame
     pub(super) fn candidate_max_ack_delay(
@@ -2,6 +2,7 @@ use std::{
     cmp::Ordering,
     collections::{BinaryHeap, binary_heap::PeekMut},
     mem,
+    ops::Range,
 };
 
 use bytes::{Buf, Bytes, BytesMut};
@@ -22,6 +23,8 @@ pub(super) struct Assembler {
     /// aka the stream offset.
     bytes_read: u64,
     end: u64,
+    /// Caller-owned storage that data is reassembled into, if any
+    ring: Option<Ring>,
 }
 
 impl Assembler {
@@ -40,6 +43,9 @@ impl Assembler {
     pub(super) fn ensure_ordering(&mut self, ordered: bool) -> Result<(), IllegalOrderedRead> {
         if ordered && !self.state.is_ordered() {
             return Err(IllegalOrderedRead);
+        } else if !ordered && self.ring.is_some() {
+            // Ring buffers only hold the window following the read offset
+            return Err(IllegalOrderedRead);
         } else if !ordered && self.state.is_ordered() {
             // Enter unordered mode
             if !self.data.is_empty() {
@@ -58,6 +64,18 @@ impl Assembler {
 
     /// Get the the next chunk
     pub(super) fn read(&mut self, max_length: usize, ordered: bool) -> Option<Chunk> {
+        if self.ring.is_some() {
+            // Fall back to copying out of the ring buffer
+            let offset = self.bytes_read;
+            let (data, _) = self.ring_readable();
+            if data.is_empty() {
+                return None;
+            }
+            let bytes = Bytes::copy_from_slice(&data[..data.len().min(max_length)]);
+            self.consume(bytes.len());
+            return Some(Chunk::new(offset, bytes));
+        }
+
         loop {
             let mut chunk = self.data.peek_mut()?;
 
@@ -186,6 +204,12 @@ impl Assembler {
                 bytes.advance(diff as usize);
             }
         }
+        if let Some(ring) = &mut self.ring {
+            // Only data beyond the end of the ring buffer is buffered separately
+            let len = ring.write(self.bytes_read, offset, &bytes);
+            offset += len as u64;
+            bytes.advance(len);
+        }
 
         if bytes.is_empty() {
             return Ok(());
@@ -224,11 +248,133 @@ impl Assembler {
         self.bytes_read
     }
 
+    /// Reassemble data into `buf` from now on
+    ///
+    /// Data buffered so far is moved into `buf` as far as it fits.
+    pub(super) fn set_ring(&mut self, buf: Box<[u8]>) -> Result<(), IllegalOrderedRead> {
+        if !self.state.is_ordered() {
+            return Err(IllegalOrderedRead);
+        }
+        if let Some(old) = self.ring.take() {
+            // Move unread data out of the previous ring buffer
+            for range in old.recvd.iter() {
+                let (first, second) = old.get(range.clone());
+                let bytes = Bytes::from([first, second].concat());
+                self.buffered += bytes.len();
+                self.allocated += bytes.len();
+                self.data.push(Buffer::new_defragmented(range.start, bytes));
+            }
+        }
+        self.ring = Some(Ring {
+            buf,
+            recvd: RangeSet::new(),
+        });
+        self.fill_ring();
+        Ok(())
+    }
+
+    /// Contiguous data in the ring buffer following the read offset
+    ///
+    /// The second slice is non-empty if the data wraps around the end of the ring buffer.
+    pub(super) fn ring_readable(&self) -> (&[u8], &[u8]) {
+        let Some(ring) = &self.ring else {
+            return (&[], &[]);
+        };
+        match ring.recvd.peek_min() {
+            Some(range) if range.start == self.bytes_read => ring.get(range),
+            _ => (&[], &[]),
+        }
+    }
+
+    /// Release `len` bytes of [`ring_readable()`](Self::ring_readable)
+    pub(super) fn consume(&mut self, len: usize) {
+        let ring = self.ring.as_mut().expect("no ring buffer");
+        let end = self.bytes_read + len as u64;
+        assert!(
+            ring.recvd
+                .peek_min()
+                .is_some_and(|x| x.start == self.bytes_read && x.end >= end),
+            "consumed more than readable"
+        );
+        ring.recvd.remove(self.bytes_read..end);
+        self.bytes_read = end;
+        self.fill_ring();
+    }
+
+    /// Move buffered data into space freed up in the ring buffer
+    fn fill_ring(&mut self) {
+        let Some(ring) = &mut self.ring else {
+            return;
+        };
+        let ring_end = self.bytes_read + ring.buf.len() as u64;
+        loop {
+            let Some(chunk) = self.data.peek_mut() else {
+                break;
+            };
+            if chunk.offset >= ring_end {
+                break;
+            }
+            let mut chunk = PeekMut::pop(chunk);
+            self.buffered -= chunk.bytes.len();
+            self.allocated -= chunk.allocation_size;
+            if chunk.offset < self.bytes_read {
+                let duplicate = (self.bytes_read - chunk.offset).min(chunk.bytes.len() as u64);
+                chunk.offset += duplicate;
+                chunk.bytes.advance(duplicate as usize);
+            }
+            let len = ring.write(self.bytes_read, chunk.offset, &chunk.bytes);
+            chunk.offset += len as u64;
+            chunk.bytes.advance(len);
+            if !chunk.bytes.is_empty() {
+                self.buffered += chunk.bytes.len();
+                self.allocated += chunk.allocation_size;
+                self.data.push(chunk);
+            }
+        }
+    }
+
     /// Discard all buffered data
     pub(super) fn clear(&mut self) {
         self.data.clear();
         self.buffered = 0;
         self.allocated = 0;
+        if let Some(ring) = &mut self.ring {
+            ring.recvd = RangeSet::new();
+        }
+    }
+}
+
+/// Caller-owned storage for the stream data following the read offset
+///
+/// Stream offset `x` is stored at `x % buf.len()`.
+#[derive(Debug)]
+struct Ring {
+    buf: Box<[u8]>,
+    /// Offsets stored in `buf`
+    recvd: RangeSet,
+}
+
+impl Ring {
+    /// Copy the prefix of `data` at `offset` that fits, returning its length
+    fn write(&mut self, bytes_read: u64, offset: u64, data: &[u8]) -> usize {
+        let end = (bytes_read + self.buf.len() as u64).min(offset + data.len() as u64);
+        if end <= offset {
+            return 0;
+        }
+        let len = (end - offset) as usize;
+        let start = (offset % self.buf.len() as u64) as usize;
+        let first = len.min(self.buf.len() - start);
+        self.buf[start..start + first].copy_from_slice(&data[..first]);
+        self.buf[..len - first].copy_from_slice(&data[first..len]);
+        self.recvd.insert(offset..end);
+        len
+    }
+
+    fn get(&self, range: Range<u64>) -> (&[u8], &[u8]) {
+        let len = (range.end - range.start) as usize;
+        let start = (range.start % self.buf.len() as u64) as usize;
+        let first = len.min(self.buf.len() - start);
+        (&self.buf[start..start + first], &self.buf[..len - first])
     }
 }
 
@@ -663,6 +809,35 @@ mod test {
         assert_eq!(x.read(3, false), None);
     }
 
+    #[test]
+    fn ring() {
+        let mut x = Assembler::new();
+        x.insert(0, Bytes::from_static(b"12"), 2).unwrap();
+        x.set_ring(vec![0; 4].into()).unwrap();
+        assert_eq!(x.ring_readable(), (&b"12"[..], &b""[..]));
+        // Out of order, and partly beyond the end of the ring buffer
+        x.insert(3, Bytes::from_static(b"4567"), 4).unwrap();
+        assert_eq!(x.ring_readable(), (&b"12"[..], &b""[..]));
+        x.insert(2, Bytes::from_static(b"3"), 1).unwrap();
+        assert_eq!(x.ring_readable(), (&b"1234"[..], &b""[..]));
+        // Consuming makes room for buffered data, which wraps around
+        x.consume(3);
+        assert_eq!(x.ring_readable(), (&b"4"[..], &b"567"[..]));
+        assert_eq!(x.data.len(), 0);
+        // Reads copy out one contiguous slice at a time
+        assert_matches!(next(&mut x, 32), Some(ref y) if &y[..] == b"4");
+        assert_matches!(next(&mut x, 1), Some(ref y) if &y[..] == b"5");
+        assert_matches!(x.ensure_ordering(false), Err(IllegalOrderedRead));
+
+        // Replacing the ring buffer keeps unread data
+        x.set_ring(vec![0; 8].into()).unwrap();
+        x.insert(7, Bytes::from_static(b"8"), 1).unwrap();
+        assert_eq!(x.ring_readable(), (&b"678"[..], &b""[..]));
+        x.consume(3);
+        assert_eq!(x.bytes_read(), 8);
+        assert_eq!(x.ring_readable(), (&b""[..], &b""[..]));
+    }
+
     fn next_unordered(x: &mut Assembler) -> Chunk {
         x.read(usize::MAX, false).unwrap()
     }
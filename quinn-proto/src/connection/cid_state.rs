@@ -1,10 +1,36 @@
 //! Maintain the state of local connection IDs
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
-use rustc_hash::FxHashSet;
 use tracing::{debug, trace};
 
-use crate::{Duration, Instant, TransportError, shared::IssuedCid};
+use crate::{ConnectionId, Duration, Instant, TransportError, shared::IssuedCid};
+
+/// Connection IDs in use by a connection, see [`Connection::connection_ids()`]
+///
+/// Connection IDs let an observer link packets to a connection, so switching to a fresh one, e.g.
+/// when migrating, hides that packets on the new path belong to the same connection. This
+/// describes which connection IDs are live at the moment, and how many were retired so far.
+///
+/// [`Connection::connection_ids()`]: crate::Connection::connection_ids
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ConnectionIdState {
+    /// Connection IDs issued to the peer and not yet retired by it, with their sequence numbers
+    ///
+    /// Ordered by sequence number. Includes the connection ID used during the handshake until
+    /// the peer retires it.
+    pub local_active: Vec<(u64, ConnectionId)>,
+    /// Number of connection IDs issued to the peer that it has retired
+    pub local_retired: u64,
+    /// Sequence number and value of the connection ID used to address the peer
+    pub remote_active: (u64, ConnectionId),
+    /// Connection IDs supplied by the peer that can be switched to, e.g. upon migration
+    ///
+    /// Ordered by sequence number.
+    pub remote_available: Vec<(u64, ConnectionId)>,
+    /// Number of connection IDs supplied by the peer that were retired
+    pub remote_retired: u64,
+}
 
 /// Local connection ID management
 pub(super) struct CidState {
@@ -12,8 +38,10 @@ pub(super) struct CidState {
     retire_timestamp: VecDeque<CidTimestamp>,
     /// Number of local connection IDs that have been issued in NEW_CONNECTION_ID frames.
     issued: u64,
-    /// Sequence numbers of local connection IDs not yet retired by the peer
-    active_seq: FxHashSet<u64>,
+    /// Local connection IDs not yet retired by the peer, by sequence number
+    active: BTreeMap<u64, ConnectionId>,
+    /// Number of local connection IDs retired by the peer
+    retired: u64,
     /// Sequence number the peer has already retired all CIDs below at our request via `retire_prior_to`
     prev_retire_seq: u64,
     /// Sequence number to set in retire_prior_to field in NEW_CONNECTION_ID frame
@@ -29,17 +57,16 @@ impl CidState {
         cid_len: usize,
         cid_lifetime: Option<Duration>,
         now: Instant,
-        issued: u64,
+        initial: impl IntoIterator<Item = ConnectionId>,
     ) -> Self {
-        let mut active_seq = FxHashSet::default();
-        // Add sequence number of CIDs used in handshaking into tracking set
-        for seq in 0..issued {
-            active_seq.insert(seq);
-        }
+        // Add CIDs used in handshaking into tracking set
+        let active = (0..).zip(initial).collect::<BTreeMap<_, _>>();
+        let issued = active.len() as u64;
         let mut this = Self {
             retire_timestamp: VecDeque::new(),
             issued,
-            active_seq,
+            active,
+            retired: 0,
             prev_retire_seq: 0,
             retire_seq: 0,
             cid_len,
@@ -94,7 +121,7 @@ impl CidState {
     pub(crate) fn on_cid_timeout(&mut self) -> bool {
         // Whether the peer hasn't retired all the CIDs we asked it to yet
         let unretired_ids_found =
-            (self.prev_retire_seq..self.retire_seq).any(|seq| self.active_seq.contains(&seq));
+            (self.prev_retire_seq..self.retire_seq).any(|seq| self.active.contains_key(&seq));
 
         let current_retire_prior_to = self.retire_seq;
         let next_retire_sequence = self
@@ -126,7 +153,7 @@ impl CidState {
         //
         // If yes (return true), a new CID must be pushed with updated `retire_prior_to` field to remote peer.
         // If no (return false), it means CIDs that reach the end of lifetime have been retired already. Do not push a new CID in order to avoid violating above RFC.
-        (current_retire_prior_to..self.retire_seq).any(|seq| self.active_seq.contains(&seq))
+        (current_retire_prior_to..self.retire_seq).any(|seq| self.active.contains_key(&seq))
     }
 
     /// Update cid state when `NewIdentifiers` event is received
@@ -139,7 +166,7 @@ impl CidState {
         // Record the timestamp of CID with the largest seq number
         let sequence = last_cid.sequence;
         ids.iter().for_each(|frame| {
-            self.active_seq.insert(frame.sequence);
+            self.active.insert(frame.sequence, frame.id);
         });
         self.track_lifetime(sequence, now);
     }
@@ -166,13 +193,15 @@ impl CidState {
                 "RETIRE_CONNECTION_ID for unissued sequence number",
             ));
         }
-        self.active_seq.remove(&sequence);
+        if self.active.remove(&sequence).is_some() {
+            self.retired += 1;
+        }
         // Consider a scenario where peer A has active remote cid 0,1,2.
         // Peer B first send a NEW_CONNECTION_ID with cid 3 and retire_prior_to set to 1.
         // Peer A processes this NEW_CONNECTION_ID frame; update remote cid to 1,2,3
         // and meanwhile send a RETIRE_CONNECTION_ID to retire cid 0 to peer B.
         // If peer B doesn't check the cid limit here and send a new cid again, peer A will then face CONNECTION_ID_LIMIT_ERROR
-        Ok(limit > self.active_seq.len() as u64)
+        Ok(limit > self.active.len() as u64)
     }
 
     /// Length of local Connection IDs
@@ -180,6 +209,16 @@ impl CidState {
         self.cid_len
     }
 
+    /// Local connection IDs not yet retired by the peer, ordered by sequence number
+    pub(crate) fn active(&self) -> impl Iterator<Item = (u64, ConnectionId)> + '_ {
+        self.active.iter().map(|(&seq, &id)| (seq, id))
+    }
+
+    /// Number of local connection IDs retired by the peer
+    pub(crate) fn retired(&self) -> u64 {
+        self.retired
+    }
+
     /// The value for `retire_prior_to` field in `NEW_CONNECTION_ID` frame
     pub(crate) fn retire_prior_to(&self) -> u64 {
         self.retire_seq
@@ -189,7 +228,7 @@ impl CidState {
     pub(crate) fn active_seq(&self) -> (u64, u64) {
         let mut min = u64::MAX;
         let mut max = u64::MIN;
-        for n in self.active_seq.iter() {
+        for n in self.active.keys() {
             if n < &min {
                 min = *n;
             }
@@ -203,7 +242,7 @@ impl CidState {
     #[cfg(test)]
     pub(crate) fn assign_retire_seq(&mut self, v: u64) -> u64 {
         // Cannot retire more CIDs than what have been issued
-        debug_assert!(v <= *self.active_seq.iter().max().unwrap() + 1);
+        debug_assert!(v <= *self.active.keys().max().unwrap() + 1);
         let n = v.checked_sub(self.retire_seq).unwrap();
         self.retire_seq = v;
         n
@@ -1,12 +1,13 @@
-use std::collections::VecDeque;
+use std::{collections::VecDeque, mem};
 
 use bytes::Bytes;
+use rustc_hash::FxHashMap;
 use thiserror::Error;
 use tracing::{debug, trace};
 
-use super::Connection;
+use super::{Connection, StreamsState};
 use crate::{
-    TransportError,
+    StreamId, TransportConfig, TransportError,
     frame::{Datagram, FrameStruct},
 };
 
@@ -26,6 +27,35 @@ impl Datagrams<'_> {
     ///
     /// Returns `Err` iff a `len`-byte datagram cannot currently be sent.
     pub fn send(&mut self, data: Bytes, drop: bool) -> Result<(), SendDatagramError> {
+        self.queue(data, drop, None)
+    }
+
+    /// Queue a datagram to be transmitted only once the data written to `stream` below `offset`
+    /// has been sent
+    ///
+    /// Lets a datagram refer to data on a reliable stream, e.g. media referencing signaling,
+    /// without the datagram overtaking that data at the sender. The stream data isn't required to
+    /// have been acknowledged, and may be lost and retransmitted after the datagram. Datagrams
+    /// queued later are held back along with this one. If `stream` is closed or reset before
+    /// reaching `offset`, the datagram is no longer held back.
+    ///
+    /// Behaves like [`send`](Self::send) otherwise.
+    pub fn send_after(
+        &mut self,
+        data: Bytes,
+        drop: bool,
+        stream: StreamId,
+        offset: u64,
+    ) -> Result<(), SendDatagramError> {
+        self.queue(data, drop, Some((stream, offset)))
+    }
+
+    fn queue(
+        &mut self,
+        data: Bytes,
+        drop: bool,
+        after: Option<(StreamId, u64)>,
+    ) -> Result<(), SendDatagramError> {
         if self.conn.config.datagram_receive_buffer_size.is_none() {
             return Err(SendDatagramError::Disabled);
         }
@@ -49,7 +79,10 @@ impl Datagrams<'_> {
             return Err(SendDatagramError::Blocked(data));
         }
         self.conn.datagrams.outgoing_total += data.len();
-        self.conn.datagrams.outgoing.push_back(Datagram { data });
+        self.conn.datagrams.outgoing.push_back(OutgoingDatagram {
+            datagram: Datagram { data },
+            after,
+        });
         Ok(())
     }
 
@@ -83,6 +116,25 @@ impl Datagrams<'_> {
         self.conn.datagrams.recv()
     }
 
+    /// Receive the oldest buffered datagram of `flow`
+    ///
+    /// Flows are assigned by the configured
+    /// [`DatagramClassifier`](crate::TransportConfig::datagram_classifier). Fails if none is
+    /// configured, as no datagram would ever be assigned to `flow`.
+    pub fn recv_flow(&mut self, flow: u64) -> Result<Option<Bytes>, RecvFlowError> {
+        if self.conn.config.datagram_classifier.is_none() {
+            return Err(RecvFlowError::NoClassifier);
+        }
+        Ok(self.conn.datagrams.pop_flow(flow))
+    }
+
+    /// Statistics of the incoming datagrams of `flow`
+    ///
+    /// Returns `None` if no datagram of `flow` has been received.
+    pub fn flow_stats(&self, flow: u64) -> Option<DatagramFlowStats> {
+        self.conn.datagrams.flows.get(&flow).copied()
+    }
+
     /// Bytes available in the outgoing datagram buffer
     ///
     /// When greater than zero, [`send`](Self::send)ing a datagram of at most this size is
@@ -100,40 +152,124 @@ pub(super) struct DatagramState {
     /// Number of bytes of datagrams that have been received by the local transport but not
     /// delivered to the application
     pub(super) recv_buffered: usize,
-    pub(super) incoming: VecDeque<Datagram>,
-    pub(super) outgoing: VecDeque<Datagram>,
+    /// Received datagrams that weren't assigned a flow, oldest first
+    pub(super) incoming: VecDeque<IncomingDatagram>,
+    /// Received datagrams of each flow that has any buffered, oldest first
+    flow_incoming: FxHashMap<u64, VecDeque<IncomingDatagram>>,
+    /// Sequence number of the next received datagram, ordering datagrams across flows
+    next_incoming: u64,
+    /// Statistics of each flow assigned by the configured `DatagramClassifier`
+    flows: FxHashMap<u64, DatagramFlowStats>,
+    pub(super) outgoing: VecDeque<OutgoingDatagram>,
     pub(super) outgoing_total: usize,
     pub(super) send_blocked: bool,
+    /// Received datagrams discarded since the last call to `take_dropped`
+    dropped_incoming: u64,
+    /// Outgoing datagrams discarded since the last call to `take_dropped`
+    dropped_outgoing: u64,
+}
+
+/// A datagram queued for transmission
+pub(super) struct OutgoingDatagram {
+    pub(super) datagram: Datagram,
+    /// Stream and offset below which stream data must be sent before this datagram
+    after: Option<(StreamId, u64)>,
+}
+
+impl OutgoingDatagram {
+    /// Whether the stream data this datagram waits for, if any, has been sent
+    pub(super) fn is_ready(&self, streams: &StreamsState) -> bool {
+        self.after
+            .is_none_or(|(id, offset)| streams.is_sent_up_to(id, offset))
+    }
 }
 
 impl DatagramState {
+    /// Buffer a received datagram
+    ///
+    /// Returns whether the application should be notified, i.e. whether no datagrams, or no
+    /// datagrams of the same flow, were buffered.
     pub(super) fn received(
         &mut self,
         datagram: Datagram,
-        window: &Option<usize>,
+        config: &TransportConfig,
     ) -> Result<bool, TransportError> {
-        let window = match window {
-            None => {
-                return Err(TransportError::PROTOCOL_VIOLATION(
-                    "unexpected DATAGRAM frame",
-                ));
-            }
-            Some(x) => *x,
+        let Some(window) = config.datagram_receive_buffer_size else {
+            return Err(TransportError::PROTOCOL_VIOLATION(
+                "unexpected DATAGRAM frame",
+            ));
         };
 
-        if datagram.data.len() > window {
+        let len = datagram.data.len();
+        if len > window {
             return Err(TransportError::PROTOCOL_VIOLATION("oversized datagram"));
         }
 
-        let was_empty = self.recv_buffered == 0;
-        while datagram.data.len() + self.recv_buffered > window {
+        let flow = config
+            .datagram_classifier
+            .as_ref()
+            .map(|classifier| classifier.classify(&datagram.data));
+        let mut notify = self.recv_buffered == 0;
+        if let Some(flow) = flow {
+            let stats = self.flows.entry(flow).or_default();
+            notify |= stats.buffered_bytes == 0;
+            if let Some(flow_window) = config.datagram_flow_receive_buffer_size {
+                if len > flow_window {
+                    debug!(flow, "dropping datagram exceeding flow buffer");
+                    stats.dropped += 1;
+                    self.dropped_incoming += 1;
+                    return Ok(false);
+                }
+                while len + self.flows[&flow].buffered_bytes > flow_window {
+                    debug!(flow, "dropping stale datagram of flow");
+                    self.drop_oldest(Some(flow));
+                }
+            }
+        }
+
+        while len + self.recv_buffered > window {
             debug!("dropping stale datagram");
-            self.recv();
+            // Evict from the flow buffering the most, so one busy flow can't starve the others
+            let victim = self
+                .flow_incoming
+                .keys()
+                .max_by_key(|flow| self.flows[flow].buffered_bytes)
+                .copied();
+            self.drop_oldest(victim);
+        }
+
+        self.recv_buffered += len;
+        let datagram = IncomingDatagram {
+            data: datagram.data,
+            seq: self.next_incoming,
+        };
+        self.next_incoming += 1;
+        match flow {
+            Some(flow) => {
+                self.flows.get_mut(&flow).unwrap().buffered_bytes += len;
+                self.flow_incoming
+                    .entry(flow)
+                    .or_default()
+                    .push_back(datagram);
+            }
+            None => self.incoming.push_back(datagram),
         }
+        Ok(notify)
+    }
 
-        self.recv_buffered += datagram.data.len();
-        self.incoming.push_back(datagram);
-        Ok(was_empty)
+    /// Discard the oldest buffered datagram of `flow`, or of those without a flow if `None`
+    fn drop_oldest(&mut self, flow: Option<u64>) {
+        let dropped = match flow {
+            Some(flow) => self.pop_flow(flow),
+            None => self.pop_unclassified(),
+        };
+        if dropped.is_none() {
+            return;
+        }
+        if let Some(flow) = flow {
+            self.flows.get_mut(&flow).unwrap().dropped += 1;
+        }
+        self.dropped_incoming += 1;
     }
 
     fn make_space_for(&mut self, datagram_len: usize, send_buffer_size: usize) {
@@ -141,8 +277,10 @@ impl DatagramState {
             let Some(prev) = self.outgoing.pop_front() else {
                 break;
             };
-            trace!(len = prev.data.len(), "dropping outgoing datagram");
-            self.outgoing_total -= prev.data.len();
+            let len = prev.datagram.data.len();
+            trace!(len, "dropping outgoing datagram");
+            self.outgoing_total -= len;
+            self.dropped_outgoing += 1;
         }
     }
 
@@ -154,6 +292,14 @@ impl DatagramState {
         total <= send_buffer_size
     }
 
+    /// Number of incoming and outgoing datagrams discarded since the last call
+    pub(super) fn take_dropped(&mut self) -> (u64, u64) {
+        (
+            mem::take(&mut self.dropped_incoming),
+            mem::take(&mut self.dropped_outgoing),
+        )
+    }
+
     /// Discard outgoing datagrams with a payload larger than `max_payload` bytes
     ///
     /// Returns whether any datagrams were dropped.
@@ -162,7 +308,7 @@ impl DatagramState {
     /// queued but can't send it.
     pub(super) fn drop_oversized(&mut self, max_payload: usize) -> bool {
         let mut dropped_any = false;
-        self.outgoing.retain(|datagram| {
+        self.outgoing.retain(|OutgoingDatagram { datagram, .. }| {
             let result = datagram.data.len() < max_payload;
             if !result {
                 trace!(
@@ -171,6 +317,7 @@ impl DatagramState {
                     max_payload
                 );
                 self.outgoing_total -= datagram.data.len();
+                self.dropped_outgoing += 1;
                 dropped_any = true;
             }
             result
@@ -180,32 +327,70 @@ impl DatagramState {
 
     /// Attempt to write a datagram frame into `buf`, consuming it from `self.outgoing`
     ///
-    /// Returns whether a frame was written. At most `max_size` bytes will be written, including
-    /// framing.
-    pub(super) fn write(&mut self, buf: &mut Vec<u8>, max_size: usize) -> bool {
-        let Some(datagram) = self.outgoing.pop_front() else {
-            return false;
-        };
+    /// Returns the length of the datagram written, if any. At most `max_size` bytes will be
+    /// written, including framing. Datagrams waiting for stream data that hasn't been sent yet are
+    /// held back.
+    pub(super) fn write(
+        &mut self,
+        buf: &mut Vec<u8>,
+        max_size: usize,
+        streams: &StreamsState,
+    ) -> Option<usize> {
+        let next = self.outgoing.front()?;
 
-        if buf.len() + datagram.size(true) > max_size {
+        if !next.is_ready(streams) || buf.len() + next.datagram.size(true) > max_size {
             // Future work: we could be more clever about cramming small datagrams into
             // mostly-full packets when a larger one is queued first
-            self.outgoing.push_front(datagram);
-            return false;
+            return None;
         }
 
-        trace!(len = datagram.data.len(), "DATAGRAM");
+        let datagram = self.outgoing.pop_front().unwrap().datagram;
+        let len = datagram.data.len();
+        trace!(len, "DATAGRAM");
 
-        self.outgoing_total -= datagram.data.len();
+        self.outgoing_total -= len;
         datagram.encode(true, buf);
-        true
+        Some(len)
     }
 
+    /// Take the oldest buffered datagram, of any flow
     pub(super) fn recv(&mut self) -> Option<Bytes> {
-        let x = self.incoming.pop_front()?.data;
-        self.recv_buffered -= x.len();
-        Some(x)
+        let oldest_flow = self
+            .flow_incoming
+            .iter()
+            .map(|(&flow, queue)| (queue[0].seq, flow))
+            .min();
+        match (oldest_flow, self.incoming.front()) {
+            (Some((seq, flow)), front) if front.is_none_or(|x| x.seq > seq) => self.pop_flow(flow),
+            _ => self.pop_unclassified(),
+        }
+    }
+
+    /// Take the oldest buffered datagram of `flow`
+    fn pop_flow(&mut self, flow: u64) -> Option<Bytes> {
+        let queue = self.flow_incoming.get_mut(&flow)?;
+        let IncomingDatagram { data, .. } = queue.pop_front()?;
+        if queue.is_empty() {
+            self.flow_incoming.remove(&flow);
+        }
+        self.flows.get_mut(&flow).unwrap().buffered_bytes -= data.len();
+        self.recv_buffered -= data.len();
+        Some(data)
     }
+
+    /// Take the oldest buffered datagram that wasn't assigned a flow
+    fn pop_unclassified(&mut self) -> Option<Bytes> {
+        let IncomingDatagram { data, .. } = self.incoming.pop_front()?;
+        self.recv_buffered -= data.len();
+        Some(data)
+    }
+}
+
+/// A received datagram awaiting the application
+pub(super) struct IncomingDatagram {
+    data: Bytes,
+    /// Order of arrival among all received datagrams
+    seq: u64,
 }
 
 #[cfg(test)]
@@ -215,27 +400,23 @@ mod tests {
     #[test]
     fn make_space_for_accounts_for_new_datagram() {
         let mut state = DatagramState::default();
-        state.outgoing.push_back(Datagram {
-            data: Bytes::from_static(&[0; 7]),
-        });
-        state.outgoing.push_back(Datagram {
-            data: Bytes::from_static(&[0; 2]),
-        });
+        state.outgoing.push_back(outgoing(&[0; 7]));
+        state.outgoing.push_back(outgoing(&[0; 2]));
         state.outgoing_total = 9;
 
         state.make_space_for(4, 10);
 
         assert_eq!(state.outgoing.len(), 1);
-        assert_eq!(state.outgoing[0].data.len(), 2);
+        assert_eq!(state.outgoing[0].datagram.data.len(), 2);
         assert_eq!(state.outgoing_total, 2);
+        assert_eq!(state.take_dropped(), (0, 1));
+        assert_eq!(state.take_dropped(), (0, 0));
     }
 
     #[test]
     fn make_space_for_handles_overflowing_capacity_check() {
         let mut state = DatagramState::default();
-        state.outgoing.push_back(Datagram {
-            data: Bytes::from_static(&[0]),
-        });
+        state.outgoing.push_back(outgoing(&[0]));
         state.outgoing_total = usize::MAX - 1;
 
         state.make_space_for(2, usize::MAX);
@@ -243,6 +424,48 @@ mod tests {
         assert!(state.outgoing.is_empty());
         assert_eq!(state.outgoing_total, usize::MAX - 2);
     }
+
+    fn outgoing(data: &'static [u8]) -> OutgoingDatagram {
+        OutgoingDatagram {
+            datagram: Datagram {
+                data: Bytes::from_static(data),
+            },
+            after: None,
+        }
+    }
+}
+
+/// Maps incoming datagrams to application-defined flows, see
+/// [`TransportConfig::datagram_classifier`](crate::TransportConfig::datagram_classifier)
+///
+/// Implemented for closures of the appropriate signature.
+pub trait DatagramClassifier: Send + Sync {
+    /// Identify the flow the datagram with payload `data` belongs to
+    fn classify(&self, data: &[u8]) -> u64;
+}
+
+impl<F: Fn(&[u8]) -> u64 + Send + Sync> DatagramClassifier for F {
+    fn classify(&self, data: &[u8]) -> u64 {
+        self(data)
+    }
+}
+
+/// Statistics of a flow of incoming datagrams, see [`Datagrams::flow_stats`]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DatagramFlowStats {
+    /// Bytes of datagrams of the flow buffered for the application
+    pub buffered_bytes: usize,
+    /// Datagrams of the flow discarded because its buffer or the connection's was full
+    pub dropped: u64,
+}
+
+/// Errors that can arise when receiving the datagrams of a flow
+#[derive(Debug, Error, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum RecvFlowError {
+    /// No [`DatagramClassifier`] is configured, so received datagrams aren't assigned to flows
+    #[error("no datagram classifier configured")]
+    NoClassifier,
 }
 
 /// Errors that can arise when sending a datagram
@@ -0,0 +1,164 @@
+//! Snapshots of connection internals for diagnostics
+
+use std::ops::Range;
+
+use crate::{Duration, Side, SpaceId, StreamId, VarInt};
+
+/// Snapshot of the internal state of a connection, see [`Connection::debug_state()`]
+///
+/// Intended for attaching to bug reports and for exposition on administrative endpoints; the
+/// structure closely follows the implementation and may change in any release. Serializable when
+/// the `serde` feature is enabled.
+///
+/// [`Connection::debug_state()`]: crate::Connection::debug_state
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct ConnectionDebugState {
+    /// Whether the connection was initiated locally or by the peer
+    pub side: Side,
+    /// Name of the connection's state, e.g. `"handshake"` or `"established"`
+    pub state: &'static str,
+    /// Packet number spaces, in the order Initial, Handshake, Data
+    pub spaces: Vec<SpaceDebugState>,
+    /// Connection-level flow control
+    pub flow_control: FlowControlDebugState,
+    /// Streams with a half that is open, ordered by ID
+    pub streams: Vec<StreamDebugState>,
+    /// Congestion control and recovery
+    pub congestion: CongestionDebugState,
+}
+
+/// State of a packet number space, see [`ConnectionDebugState`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct SpaceDebugState {
+    /// The packet number space
+    pub space: SpaceId,
+    /// Whether keys for this space are available, i.e. it wasn't yet discarded
+    pub has_keys: bool,
+    /// Number of the next packet that will be sent
+    pub next_packet_number: u64,
+    /// Largest packet number the peer acknowledged
+    pub largest_acked_packet: Option<u64>,
+    /// Largest packet number received from the peer
+    pub largest_received_packet: u64,
+    /// Numbers of sent packets that are neither acknowledged nor deemed lost
+    pub in_flight: Vec<Range<u64>>,
+    /// Bytes of `in_flight` packets that count towards congestion control
+    pub in_flight_bytes: u64,
+    /// Numbers of received packets that are yet to be acknowledged
+    pub pending_acks: Vec<Range<u64>>,
+    /// Number of packets deemed lost that might still be acknowledged
+    pub lost_packets: usize,
+    /// Whether frames are queued for retransmission
+    pub retransmits_pending: bool,
+    /// Number of probe packets queued
+    pub loss_probes: u32,
+    /// Bytes of cryptographic handshake data sent
+    pub crypto_offset: u64,
+}
+
+/// Connection-level flow control, see [`ConnectionDebugState`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct FlowControlDebugState {
+    /// Limit on stream data that may be sent, dictated by the peer
+    pub max_data: u64,
+    /// Stream data sent so far
+    pub data_sent: u64,
+    /// Limit on stream data that may be received, as advertised to the peer
+    pub local_max_data: u64,
+    /// Upper bound on the stream data received so far
+    pub data_received: u64,
+    /// Stream data sent but not yet acknowledged
+    pub unacked_data: u64,
+    /// Limit on `unacked_data`
+    pub send_window: u64,
+    /// Number of bidirectional and unidirectional streams that may be opened locally
+    pub max_streams: [u64; 2],
+    /// Number of bidirectional and unidirectional streams that the peer may open
+    pub max_remote_streams: [u64; 2],
+}
+
+/// State of a stream, see [`ConnectionDebugState`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct StreamDebugState {
+    /// Identity of the stream
+    pub id: StreamId,
+    /// The sending half, if the stream has one that isn't yet closed
+    pub send: Option<SendStreamDebugState>,
+    /// The receiving half, if the stream has one that isn't yet closed
+    pub recv: Option<RecvStreamDebugState>,
+}
+
+/// State of the sending half of a stream, see [`StreamDebugState`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct SendStreamDebugState {
+    /// Name of the stream's state, e.g. `"ready"` or `"reset_sent"`
+    pub state: &'static str,
+    /// Bytes written by the application
+    pub offset: u64,
+    /// Bytes written but not yet acknowledged
+    pub unacked: u64,
+    /// Limit on the stream's data, dictated by the peer
+    pub max_data: u64,
+    /// Priority of the stream
+    pub priority: i32,
+    /// Whether data is queued for retransmission
+    pub retransmits_pending: bool,
+    /// Error code of a `STOP_SENDING` frame received from the peer
+    pub stop_reason: Option<VarInt>,
+}
+
+/// State of the receiving half of a stream, see [`StreamDebugState`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct RecvStreamDebugState {
+    /// Name of the stream's state, e.g. `"recv"` or `"reset_recvd"`
+    pub state: &'static str,
+    /// Bytes read by the application
+    pub bytes_read: u64,
+    /// Highest offset of data received
+    pub end: u64,
+    /// Limit on the stream's data, as advertised to the peer
+    pub max_data: u64,
+    /// Size of the stream, if known
+    pub final_size: Option<u64>,
+    /// Error code of a `RESET_STREAM` frame received from the peer
+    pub reset_code: Option<VarInt>,
+    /// Whether the application stopped reading
+    pub stopped: bool,
+}
+
+/// Congestion control and recovery state, see [`ConnectionDebugState`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct CongestionDebugState {
+    /// Congestion window, in bytes
+    pub window: u64,
+    /// Slow start threshold, in bytes, if reported by the congestion controller
+    pub ssthresh: Option<u64>,
+    /// Pacing rate, in bits per second, if reported by the congestion controller
+    pub pacing_rate: Option<u64>,
+    /// Bytes in flight
+    pub bytes_in_flight: u64,
+    /// Smoothed round-trip time
+    pub smoothed_rtt: Duration,
+    /// Minimum observed round-trip time
+    pub min_rtt: Duration,
+    /// Most recent round-trip time sample
+    pub latest_rtt: Duration,
+    /// Number of consecutive probe timeouts
+    pub pto_count: u32,
+    /// Current maximum UDP payload size
+    pub mtu: u16,
+}
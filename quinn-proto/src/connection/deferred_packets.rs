@@ -0,0 +1,51 @@
+use std::{collections::VecDeque, net::SocketAddr};
+
+use crate::{EcnCodepoint, packet::PartialDecode};
+
+/// Packets received before they could be processed, e.g. because keys weren't available yet
+///
+/// Bounded by [`TransportConfig::max_deferred_packets`] and
+/// [`TransportConfig::max_deferred_bytes`].
+///
+/// [`TransportConfig::max_deferred_packets`]: crate::TransportConfig::max_deferred_packets
+/// [`TransportConfig::max_deferred_bytes`]: crate::TransportConfig::max_deferred_bytes
+#[derive(Debug, Default)]
+pub(super) struct DeferredPackets {
+    packets: VecDeque<DeferredPacket>,
+    bytes: u64,
+}
+
+impl DeferredPackets {
+    /// Store `packet` for later processing, returning whether it fit within the limits
+    pub(super) fn push(
+        &mut self,
+        packet: DeferredPacket,
+        max_count: usize,
+        max_bytes: u64,
+    ) -> bool {
+        let len = packet.decode.len() as u64;
+        if self.packets.len() >= max_count || self.bytes + len > max_bytes {
+            return false;
+        }
+        self.bytes += len;
+        self.packets.push_back(packet);
+        true
+    }
+
+    /// Remove all packets, in the order they were received
+    pub(super) fn take(&mut self) -> VecDeque<DeferredPacket> {
+        self.bytes = 0;
+        std::mem::take(&mut self.packets)
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.packets.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct DeferredPacket {
+    pub(super) remote: SocketAddr,
+    pub(super) ecn: Option<EcnCodepoint>,
+    pub(super) decode: PartialDecode,
+}
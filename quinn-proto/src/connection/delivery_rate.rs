@@ -0,0 +1,230 @@
+use crate::{Duration, Instant};
+
+/// Rate at which data is being delivered to the peer, see [`Connection::bandwidth_estimate`]
+///
+/// [`Connection::bandwidth_estimate`]: super::Connection::bandwidth_estimate
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BandwidthEstimate {
+    /// Bytes acknowledged per second, including QUIC framing overhead
+    pub bytes_per_second: u64,
+    /// Whether the sender didn't always have data to send while the estimate was measured
+    ///
+    /// An app-limited estimate is a lower bound of the bandwidth available on the path.
+    pub app_limited: bool,
+}
+
+/// Estimates the delivery rate independently of the congestion controller
+///
+/// Follows draft-cheng-iccrg-delivery-rate-estimation: each packet records how much data was
+/// delivered when it was sent, and its acknowledgement yields a sample of the data delivered in
+/// between, divided by the longer of the send and acknowledgement intervals.
+#[derive(Debug, Default)]
+pub(super) struct DeliveryRateEstimator {
+    /// Bytes acknowledged so far
+    delivered: u64,
+    /// When `delivered` last increased
+    delivered_time: Option<Instant>,
+    /// Send time of the newest packet acknowledged so far
+    first_sent_time: Option<Instant>,
+    /// Sample under construction while processing an ACK frame
+    sample: Option<Sample>,
+    estimate: Option<BandwidthEstimate>,
+    /// Value of `delivered` up to which packets are sent app-limited, see
+    /// [`Connection::mark_app_limited`]
+    ///
+    /// [`Connection::mark_app_limited`]: super::Connection::mark_app_limited
+    app_limited_until: Option<u64>,
+}
+
+impl DeliveryRateEstimator {
+    pub(super) fn estimate(&self) -> Option<BandwidthEstimate> {
+        self.estimate
+    }
+
+    /// Consider the sender app-limited until `bytes_in_flight` more bytes are acknowledged
+    pub(super) fn mark_app_limited(&mut self, bytes_in_flight: u64) {
+        // Nothing in flight still covers the next packet sent
+        self.app_limited_until = Some(self.delivered + bytes_in_flight.max(1));
+    }
+
+    /// Whether the sender was marked app-limited and the data in flight since is unacknowledged
+    pub(super) fn is_app_limited(&self) -> bool {
+        self.app_limited_until.is_some()
+    }
+
+    /// A packet counted towards bytes in flight is sent
+    pub(super) fn on_sent(
+        &mut self,
+        now: Instant,
+        bytes_in_flight: u64,
+        app_limited: bool,
+    ) -> DeliverySnapshot {
+        if bytes_in_flight == 0 {
+            // Don't count the idle period towards the send interval
+            self.first_sent_time = Some(now);
+            self.delivered_time = Some(now);
+        }
+        let micros = |since: Option<Instant>| {
+            let elapsed = since.map_or(Duration::ZERO, |x| now.saturating_duration_since(x));
+            u32::try_from(elapsed.as_micros()).unwrap_or(u32::MAX)
+        };
+        DeliverySnapshot {
+            delivered: self.delivered,
+            since_delivered: micros(self.delivered_time),
+            since_first_sent: micros(self.first_sent_time),
+            app_limited,
+        }
+    }
+
+    /// A packet counted towards bytes in flight is acknowledged
+    pub(super) fn on_ack(
+        &mut self,
+        now: Instant,
+        time_sent: Instant,
+        size: u16,
+        snapshot: &DeliverySnapshot,
+    ) {
+        self.delivered += u64::from(size);
+        self.delivered_time = Some(now);
+        // Sample the newest packet acknowledged
+        if self
+            .sample
+            .as_ref()
+            .is_some_and(|sample| sample.prior_delivered > snapshot.delivered)
+        {
+            return;
+        }
+        self.sample = Some(Sample {
+            prior_delivered: snapshot.delivered,
+            app_limited: snapshot.app_limited,
+            interval: Ord::max(
+                Duration::from_micros(snapshot.since_first_sent.into()),
+                now.saturating_duration_since(time_sent)
+                    + Duration::from_micros(snapshot.since_delivered.into()),
+            ),
+        });
+        self.first_sent_time = Some(time_sent);
+    }
+
+    /// All packets acknowledged by an ACK frame have been processed
+    pub(super) fn on_end_acks(&mut self, min_rtt: Duration) {
+        if self.app_limited_until.is_some_and(|x| self.delivered >= x) {
+            self.app_limited_until = None;
+        }
+        let Some(sample) = self.sample.take() else {
+            return;
+        };
+        // Shorter intervals are the result of ACK compression, and overestimate the rate
+        if sample.interval.is_zero() || sample.interval < min_rtt {
+            return;
+        }
+        // Packets sent before a reset of the estimator may claim more prior deliveries
+        let delivered = self.delivered.saturating_sub(sample.prior_delivered);
+        let bytes_per_second = (delivered as f64 / sample.interval.as_secs_f64()) as u64;
+        // App-limited samples reflect the application rather than the path, unless they're higher
+        if sample.app_limited
+            && self
+                .estimate
+                .is_some_and(|x| x.bytes_per_second > bytes_per_second)
+        {
+            return;
+        }
+        self.estimate = Some(BandwidthEstimate {
+            bytes_per_second,
+            app_limited: sample.app_limited,
+        });
+    }
+}
+
+/// Delivery state when a packet was sent
+///
+/// Times are stored relative to the packet's send time to keep [`SentPacket`] small.
+///
+/// [`SentPacket`]: super::spaces::SentPacket
+#[derive(Debug, Copy, Clone)]
+pub(super) struct DeliverySnapshot {
+    delivered: u64,
+    /// Microseconds since `delivered` last increased
+    since_delivered: u32,
+    /// Microseconds since the newest acknowledged packet was sent
+    since_first_sent: u32,
+    app_limited: bool,
+}
+
+#[derive(Debug)]
+struct Sample {
+    prior_delivered: u64,
+    app_limited: bool,
+    interval: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    #[test]
+    fn steady_rate() {
+        let mut estimator = DeliveryRateEstimator::default();
+        let start = Instant::now();
+        let rtt = Duration::from_millis(100);
+        // A packet of 1000 bytes every 10ms, each acknowledged an RTT later
+        let mut in_flight = VecDeque::new();
+        for i in 0..30 {
+            let now = start + Duration::from_millis(10 * i);
+            if in_flight
+                .front()
+                .is_some_and(|&(sent, _)| sent + rtt <= now)
+            {
+                let (sent, snapshot) = in_flight.pop_front().unwrap();
+                estimator.on_ack(now, sent, 1000, &snapshot);
+                estimator.on_end_acks(rtt);
+            }
+            let snapshot = estimator.on_sent(now, in_flight.len() as u64 * 1000, false);
+            in_flight.push_back((now, snapshot));
+        }
+        assert_eq!(
+            estimator.estimate(),
+            Some(BandwidthEstimate {
+                bytes_per_second: 100_000,
+                app_limited: false,
+            })
+        );
+    }
+
+    #[test]
+    fn app_limited_samples_only_raise() {
+        let mut estimator = DeliveryRateEstimator {
+            estimate: Some(BandwidthEstimate {
+                bytes_per_second: 100_000,
+                app_limited: false,
+            }),
+            ..Default::default()
+        };
+        let now = Instant::now();
+        let snapshot = estimator.on_sent(now, 0, true);
+        estimator.on_ack(now + Duration::from_secs(1), now, 1000, &snapshot);
+        estimator.on_end_acks(Duration::ZERO);
+        assert_eq!(estimator.estimate().unwrap().bytes_per_second, 100_000);
+    }
+
+    #[test]
+    fn marked_app_limited() {
+        let mut estimator = DeliveryRateEstimator::default();
+        let now = Instant::now();
+        let first = estimator.on_sent(now, 0, false);
+        estimator.mark_app_limited(1000);
+        let second = estimator.on_sent(now, 1000, estimator.is_app_limited());
+        assert!(second.app_limited);
+
+        // The mark lasts until the data in flight when it was set is acknowledged
+        estimator.on_ack(now + Duration::from_secs(1), now, 1000, &first);
+        estimator.on_end_acks(Duration::ZERO);
+        assert!(!estimator.is_app_limited());
+        estimator.on_ack(now + Duration::from_secs(2), now, 1000, &second);
+        estimator.on_end_acks(Duration::ZERO);
+        assert!(estimator.estimate().unwrap().app_limited);
+    }
+}
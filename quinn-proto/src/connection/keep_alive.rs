@@ -0,0 +1,178 @@
+use crate::{AdaptiveKeepAliveConfig, Duration, Instant};
+
+/// Discovers the longest keep-alive interval that NAT bindings on the path tolerate
+///
+/// Each keep-alive is sent after the current interval passed without receiving anything, and
+/// tests whether the path survives that much silence. Once a keep-alive is answered, the interval
+/// grows. If the peer observes a new address shortly after a keep-alive, the binding expired in
+/// the meantime, and the interval settles on the longest one that was answered.
+#[derive(Debug)]
+pub(super) struct KeepAliveDiscovery {
+    config: AdaptiveKeepAliveConfig,
+    interval: Duration,
+    /// Longest interval after which a keep-alive was answered without a rebind
+    last_good: Option<Duration>,
+    /// The most recent keep-alive, if discovery hasn't settled
+    probe: Option<Probe>,
+    settled: bool,
+}
+
+impl KeepAliveDiscovery {
+    pub(super) fn new(config: AdaptiveKeepAliveConfig) -> Self {
+        Self {
+            interval: config.initial_interval,
+            config,
+            last_good: None,
+            probe: None,
+            settled: false,
+        }
+    }
+
+    /// Period of inactivity before sending the next keep-alive
+    pub(super) fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// A keep-alive is sent after [`Self::interval`] passed without receiving anything
+    ///
+    /// A rebind within `rebind_window` is attributed to this keep-alive. Returns the final interval
+    /// if discovery settled.
+    pub(super) fn on_keep_alive(
+        &mut self,
+        now: Instant,
+        rebind_window: Duration,
+    ) -> Option<Duration> {
+        if self.settled {
+            return None;
+        }
+        if let Some(probe) = self.probe.take() {
+            if probe.answered {
+                self.last_good = Some(probe.interval);
+            }
+        }
+        if self.last_good.is_some_and(|good| good >= self.interval) {
+            // The interval couldn't grow any further
+            self.settled = true;
+            return Some(self.interval);
+        }
+        self.probe = Some(Probe {
+            interval: self.interval,
+            deadline: now + rebind_window,
+            answered: false,
+        });
+        None
+    }
+
+    /// A packet was received
+    ///
+    /// `max_interval` is the upper bound imposed by the idle timeout, if any.
+    pub(super) fn on_packet_received(&mut self, max_interval: Option<Duration>) {
+        let Some(probe) = self.probe.as_mut().filter(|probe| !probe.answered) else {
+            return;
+        };
+        probe.answered = true;
+        let max = max_interval.map_or(self.config.max_interval, |x| {
+            x.min(self.config.max_interval)
+        });
+        let grown = probe.interval.as_secs_f64() * f64::from(self.config.growth_factor);
+        // Saturate rather than overflow for huge factors
+        self.interval = Duration::try_from_secs_f64(grown).map_or(max, |x| x.min(max));
+    }
+
+    /// The peer observed a new address for this endpoint, i.e. a NAT binding expired
+    ///
+    /// Returns the final interval if discovery settled.
+    pub(super) fn on_rebind(&mut self, now: Instant) -> Option<Duration> {
+        let probe = self.probe.take_if(|probe| now <= probe.deadline)?;
+        if let Some(good) = self.last_good {
+            self.interval = good;
+            self.settled = true;
+            return Some(good);
+        }
+        // Even the first interval was too long
+        self.interval = probe
+            .interval
+            .div_f32(self.config.growth_factor)
+            .max(self.config.min_interval);
+        if self.interval >= probe.interval {
+            self.settled = true;
+            return Some(self.interval);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Probe {
+    /// Inactivity preceding the keep-alive
+    interval: Duration,
+    /// Until when a rebind is attributed to the keep-alive
+    deadline: Instant,
+    /// Whether a packet was received since sending the keep-alive
+    answered: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settles_below_binding_timeout() {
+        let mut config = AdaptiveKeepAliveConfig::default();
+        config
+            .initial_interval(Duration::from_secs(10))
+            .growth_factor(2.0);
+        let mut discovery = KeepAliveDiscovery::new(config);
+        let window = Duration::from_secs(1);
+        let mut now = Instant::now();
+
+        // 10s and 20s are answered
+        for expected in [10, 20] {
+            assert_eq!(discovery.interval(), Duration::from_secs(expected));
+            now += discovery.interval();
+            assert_eq!(discovery.on_keep_alive(now, window), None);
+            discovery.on_packet_received(None);
+        }
+
+        // 40s causes a rebind
+        assert_eq!(discovery.interval(), Duration::from_secs(40));
+        now += discovery.interval();
+        assert_eq!(discovery.on_keep_alive(now, window), None);
+        discovery.on_packet_received(None);
+        assert_eq!(discovery.on_rebind(now), Some(Duration::from_secs(20)));
+        assert_eq!(discovery.interval(), Duration::from_secs(20));
+
+        // Settled
+        now += discovery.interval();
+        assert_eq!(discovery.on_keep_alive(now, window), None);
+        discovery.on_packet_received(None);
+        assert_eq!(discovery.interval(), Duration::from_secs(20));
+    }
+
+    #[test]
+    fn settles_at_max() {
+        let mut discovery = KeepAliveDiscovery::new(AdaptiveKeepAliveConfig::default());
+        let max = Duration::from_secs(15);
+        let mut now = Instant::now();
+        assert_eq!(discovery.interval(), max);
+        now += max;
+        assert_eq!(discovery.on_keep_alive(now, Duration::ZERO), None);
+        discovery.on_packet_received(Some(max));
+        now += max;
+        assert_eq!(discovery.on_keep_alive(now, Duration::ZERO), Some(max));
+        // Rebinds long after a keep-alive aren't attributed to it
+        assert_eq!(discovery.on_rebind(now + Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn huge_growth_factor() {
+        let mut config = AdaptiveKeepAliveConfig::default();
+        config.growth_factor(f32::INFINITY);
+        let mut discovery = KeepAliveDiscovery::new(config);
+        let mut now = Instant::now();
+        now += discovery.interval();
+        assert_eq!(discovery.on_keep_alive(now, Duration::ZERO), None);
+        discovery.on_packet_received(None);
+        assert_eq!(discovery.interval(), Duration::from_secs(300));
+    }
+}
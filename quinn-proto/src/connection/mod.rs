@@ -2,8 +2,9 @@ use std::{
     cmp,
     collections::VecDeque,
     convert::TryFrom,
-    fmt, io, mem,
+    fmt, io, iter, mem,
     net::{IpAddr, SocketAddr},
+    ops::Range,
     sync::Arc,
 };
 
@@ -16,12 +17,12 @@ use tracing::{debug, error, trace, trace_span, warn};
 
 use crate::{
     Dir, Duration, EndpointConfig, Frame, INITIAL_MTU, Instant, MAX_CID_SIZE, MAX_STREAM_COUNT,
-    MIN_INITIAL_SIZE, Side, StreamId, TIMER_GRANULARITY, TokenStore, Transmit, TransportError,
-    TransportErrorCode, VarInt,
+    MIN_INITIAL_SIZE, Side, StreamId, TIMER_GRANULARITY, TokenStore, Transmit, TransmitInfo,
+    TransmitKind, TransmitMarker, TransmitMarking, TransportError, TransportErrorCode, VarInt,
     cid_generator::ConnectionIdGenerator,
     cid_queue::CidQueue,
     coding::BufMutExt,
-    config::{ServerConfig, TransportConfig},
+    config::{MtuDiscoveryConfig, PaddingPolicy, ServerConfig, TransportConfig, TuneError},
     connection::spaces::LostPacket,
     crypto::{self, KeyPair, Keys, PacketKey},
     frame::{self, Close, Datagram, FrameStruct, NewConnectionId, NewToken},
@@ -34,8 +35,8 @@ use crate::{
         ConnectionEvent, ConnectionEventInner, ConnectionId, DatagramConnectionEvent, EcnCodepoint,
         EndpointEvent, EndpointEventInner,
     },
-    token::{ResetToken, Token, TokenPayload},
-    transport_parameters::TransportParameters,
+    token::{ResetToken, ResumptionHint, Token, TokenPayload},
+    transport_parameters::{Extension, PeerTransportParameters, TransportParameters},
 };
 
 mod ack_frequency;
@@ -46,24 +47,49 @@ pub use assembler::Chunk;
 
 mod cid_state;
 use cid_state::CidState;
+pub use cid_state::ConnectionIdState;
 
 mod datagrams;
 use datagrams::DatagramState;
-pub use datagrams::{Datagrams, SendDatagramError};
+pub use datagrams::{
+    DatagramClassifier, DatagramFlowStats, Datagrams, RecvFlowError, SendDatagramError,
+};
+
+mod delivery_rate;
+pub use delivery_rate::BandwidthEstimate;
+
+mod deferred_packets;
+use deferred_packets::{DeferredPacket, DeferredPackets};
+
+mod debug_state;
+pub use debug_state::{
+    CongestionDebugState, ConnectionDebugState, FlowControlDebugState, RecvStreamDebugState,
+    SendStreamDebugState, SpaceDebugState, StreamDebugState,
+};
+
+mod keep_alive;
+use keep_alive::KeepAliveDiscovery;
 
 mod mtud;
+pub use mtud::{MtuProbe, MtuProbeOutcome};
 mod pacing;
 
 mod packet_builder;
 use packet_builder::PacketBuilder;
 
 mod packet_crypto;
+
+#[cfg(feature = "packet-trace")]
+mod packet_trace;
 use packet_crypto::{PrevCrypto, ZeroRttCrypto};
 
 mod paths;
 pub use paths::RttEstimator;
 use paths::{PathData, PathResponses};
 
+mod peer_limits;
+use peer_limits::PeerLimits;
+
 pub(crate) mod qlog;
 
 mod send_buffer;
@@ -76,7 +102,10 @@ use spaces::Retransmits;
 use spaces::{PacketNumberFilter, PacketSpace, SendableFrames, SentPacket, ThinRetransmits};
 
 mod stats;
-pub use stats::{ConnectionStats, FrameStats, PathStats, UdpStats};
+pub use stats::{
+    ConnectionStats, DatagramSizeHistogram, FrameStats, HandshakeTimings, PathStats, RttHistogram,
+    UdpStats,
+};
 
 mod streams;
 #[cfg(fuzzing)]
@@ -84,12 +113,14 @@ pub use streams::StreamsState;
 #[cfg(not(fuzzing))]
 use streams::StreamsState;
 pub use streams::{
-    Chunks, ClosedStream, FinishError, ReadError, ReadableError, RecvStream, SendStream,
-    ShouldTransmit, StreamEvent, Streams, WriteError, Written,
+    Chunks, ClosedStream, FinishError, ReadError, ReadableError, RecvStream, SendBlockReason,
+    SendFlowControl, SendStream, ShouldTransmit, StreamEvent, StreamGroupStats,
+    StreamSchedulerStats, Streams, WriteError, Written,
 };
 
 mod timer;
 use crate::congestion::Controller;
+use crate::loss::{LossDetector, LossThresholds, LossVerdict, UnackedPacket};
 use timer::{Timer, TimerTable};
 
 /// Protocol state and logic for a single QUIC connection
@@ -143,6 +174,10 @@ pub struct Connection {
     /// The "real" local IP address which was was used to receive the initial packet.
     /// This is only populated for the server case, and if known
     local_ip: Option<IpAddr>,
+    /// DSCP to mark outgoing packets with
+    dscp: Option<u8>,
+    /// Hook adjusting the marking of individual transmits
+    transmit_marker: Option<Arc<dyn TransmitMarker>>,
     path: PathData,
     /// Incremented every time we see a new path
     ///
@@ -150,7 +185,13 @@ pub struct Connection {
     path_counter: u64,
     /// Whether MTU detection is supported in this environment
     allow_mtud: bool,
+    /// MTU discovery configuration for new paths, see [`Connection::set_mtu_discovery_config`]
+    mtud_config: Option<MtuDiscoveryConfig>,
     prev_path: Option<(ConnectionId, PathData)>,
+    /// Remote port the connection was established with, for [`TransportConfig::port_hopping`]
+    original_remote_port: u16,
+    /// When the remote port last changed, for [`TransportConfig::port_hopping`]
+    port_hopped: Instant,
     state: State,
     side: ConnectionSide,
     /// Whether or not 0-RTT was enabled during the handshake. Does not imply acceptance.
@@ -164,12 +205,26 @@ pub struct Connection {
     peer_params: TransportParameters,
     /// Source ConnectionId of the first packet received from the peer
     orig_rem_cid: ConnectionId,
+    /// Application-supplied context that qlog events are tagged with
+    trace_context: Option<String>,
     /// Destination ConnectionId sent by the client on the first Initial
     initial_dst_cid: ConnectionId,
     /// The value that the server included in the Source Connection ID field of a Retry packet, if
     /// one was received
     retry_src_cid: Option<ConnectionId>,
     events: VecDeque<Event>,
+    /// Observations for [`Connection::poll_transport_event`], bounded by
+    /// [`MAX_TRANSPORT_EVENTS`]
+    transport_events: VecDeque<TransportEvent>,
+    /// MTU most recently reported through a [`TransportEvent::MtuChanged`]
+    reported_mtu: u16,
+    /// Number of OBSERVED_ADDRESS reports queued for the peer, determining their sequence numbers
+    observed_addr_reports: u64,
+    /// Sequence number and address of the latest OBSERVED_ADDRESS report received from the peer
+    observed_addr: Option<(VarInt, SocketAddr)>,
+    /// Path characteristics of a previous connection from the same client, applied once the
+    /// handshake is confirmed
+    resumption_hint: Option<ResumptionHint>,
     endpoint_events: VecDeque<EndpointEventInner>,
     /// Whether the spin bit is in use for this connection
     spin_enabled: bool,
@@ -189,9 +244,27 @@ pub struct Connection {
     accepted_0rtt: bool,
     /// Whether the idle timer should be reset the next time an ack-eliciting packet is transmitted.
     permit_idle_reset: bool,
-    /// Negotiated idle timeout
+    /// Whether ACKs were received since the loss detection timer was last armed
+    loss_detection_timer_stale: bool,
+    /// Effective idle timeout, the lesser of `negotiated_idle_timeout` and `local_idle_timeout`
     idle_timeout: Option<Duration>,
+    /// Idle timeout negotiated with the peer
+    negotiated_idle_timeout: Option<Duration>,
+    /// Idle timeout set through [`Connection::set_idle_timeout`]
+    local_idle_timeout: Option<Duration>,
+    /// When the last packet was sent, for reporting timeouts
+    last_sent: Option<Instant>,
+    /// When the last authenticated packet was received, for reporting timeouts
+    last_received: Option<Instant>,
+    /// Set if [`TransportConfig::adaptive_keep_alive`] is
+    keep_alive_discovery: Option<KeepAliveDiscovery>,
+    peer_limits: PeerLimits,
+    /// Packets received before they could be processed, see [`Self::must_defer`]
+    deferred_packets: DeferredPackets,
     timers: TimerTable,
+    /// Origin of the grid that coalesced timers are rounded to, shared by the endpoint's
+    /// connections
+    timer_epoch: Instant,
     /// Number of packets received which could not be authenticated
     authentication_failures: u64,
     /// Why the connection was lost, if it has been
@@ -205,6 +278,10 @@ pub struct Connection {
     /// Responses to PATH_CHALLENGE frames
     path_responses: PathResponses,
     close: bool,
+    /// Number of times CONNECTION_CLOSE was retransmitted by `close_and_await_peer`
+    close_retransmits: u32,
+    /// Whether the peer answered our CONNECTION_CLOSE with its own
+    close_answered: bool,
 
     //
     // ACK frequency
@@ -216,6 +293,16 @@ pub struct Connection {
     //
     /// The number of times a PTO has been sent without receiving an ack.
     pto_count: u32,
+    /// When the first of the current sequence of consecutive probe timeouts fired
+    first_pto: Option<Instant>,
+    /// Decides which packets are lost, see [`TransportConfig::loss_detector_factory`]
+    loss_detector: Box<dyn LossDetector>,
+    /// RTT measurement requested through [`Connection::probe_rtt`]
+    rtt_probe: Option<RttProbe>,
+    /// Result of the last RTT probe, until reported through a [`TransportEvent::RttProbed`]
+    probed_rtt: Option<Duration>,
+    /// Path validations requested by the application
+    path_probes: Vec<PathProbe>,
 
     //
     // Congestion Control
@@ -227,16 +314,32 @@ pub struct Connection {
     /// Whether the last `poll_transmit` call yielded no data because there was
     /// no outgoing application data.
     app_limited: bool,
+    /// Whether STREAM and DATAGRAM frames are held back, see [`Connection::pause_sending`]
+    sending_paused: bool,
+    /// Application close requested by a server before its handshake flight was sent, see
+    /// [`Connection::close`]
+    deferred_close: Option<frame::ApplicationClose>,
+    /// Whether CONNECTION_CLOSE is only sent in 1-RTT packets, so that the peer learns the
+    /// application's error code and reason even though the handshake isn't complete
+    close_1rtt_only: bool,
+    /// When `poll_transmit` first found the congestion window full, if it still is
+    congestion_blocked_since: Option<Instant>,
+    /// When `poll_transmit` first found writes blocked by connection-level flow control, if they
+    /// still are
+    flow_control_blocked_since: Option<Instant>,
 
     streams: StreamsState,
     /// Surplus remote CIDs for future use on new paths
     rem_cids: CidQueue,
+    /// When the active remote CID was adopted, for [`TransportConfig::cid_rotation`]
+    rem_cid_adopted: RemCidAdopted,
     // Attributes of CIDs generated by local peer
     local_cid_state: CidState,
     /// State of the unreliable datagram extension
     datagrams: DatagramState,
     /// Connection level statistics
     stats: ConnectionStats,
+    rtt_histogram: RttHistogram,
     /// QUIC version used for the connection.
     version: u32,
 }
@@ -253,6 +356,7 @@ impl Connection {
         crypto: Box<dyn crypto::Session>,
         cid_gen: &dyn ConnectionIdGenerator,
         now: Instant,
+        timer_epoch: Instant,
         version: u32,
         allow_mtud: bool,
         rng_seed: [u8; 32],
@@ -260,18 +364,33 @@ impl Connection {
     ) -> Self {
         let pref_addr_cid = side_args.pref_addr_cid();
         let path_validated = side_args.path_validated();
+        let resumption_hint = side_args.resumption_hint();
         let connection_side = ConnectionSide::from(side_args);
         let side = connection_side.side();
         let initial_space = PacketSpace {
             crypto: Some(crypto.initial_keys(init_cid, side)),
             ..PacketSpace::new(now)
         };
+        let idle_timeout = match config.max_idle_timeout {
+            None | Some(VarInt(0)) => None,
+            Some(dur) => Some(Duration::from_millis(dur.0)),
+        };
+        let mut data_space = PacketSpace::new(now);
+        let ack_policy = &config.ack_policy_config;
+        data_space.pending_acks.set_thresholds(
+            ack_policy.ack_eliciting_threshold.into_inner(),
+            ack_policy.reordering_threshold.into_inner(),
+        );
+        data_space
+            .pending_acks
+            .set_redundancy(ack_policy.redundancy);
         let state = State::Handshake(state::Handshake {
             rem_cid_set: side.is_server(),
             expected_token: Bytes::new(),
             client_hello: None,
         });
         let mut rng = StdRng::from_seed(rng_seed);
+        let rtt_histogram = RttHistogram::new(config.rtt_histogram_buckets.clone());
         let mut this = Self {
             endpoint_config,
             crypto,
@@ -281,13 +400,25 @@ impl Connection {
                 cid_gen.cid_len(),
                 cid_gen.cid_lifetime(),
                 now,
-                if pref_addr_cid.is_some() { 2 } else { 1 },
+                iter::once(loc_cid).chain(pref_addr_cid),
+            ),
+            path: PathData::new(
+                remote,
+                config.mtu_discovery_config.as_ref().filter(|_| allow_mtud),
+                None,
+                0,
+                now,
+                &config,
             ),
-            path: PathData::new(remote, allow_mtud, None, 0, now, &config),
             path_counter: 0,
             allow_mtud,
+            mtud_config: config.mtu_discovery_config.clone(),
             local_ip,
+            dscp: config.dscp,
+            transmit_marker: config.transmit_marker.clone(),
             prev_path: None,
+            original_remote_port: remote.port(),
+            port_hopped: now,
             state,
             side: connection_side,
             zero_rtt_enabled: false,
@@ -302,23 +433,38 @@ impl Connection {
             key_phase_size: rng.random_range(10..1000),
             peer_params: TransportParameters::default(),
             orig_rem_cid: rem_cid,
+            trace_context: None,
             initial_dst_cid: init_cid,
             retry_src_cid: None,
             events: VecDeque::new(),
+            transport_events: VecDeque::new(),
+            reported_mtu: 0,
+            observed_addr_reports: 0,
+            observed_addr: None,
+            resumption_hint,
             endpoint_events: VecDeque::new(),
             spin_enabled: config.allow_spin && rng.random_ratio(7, 8),
             spin: false,
-            spaces: [initial_space, PacketSpace::new(now), PacketSpace::new(now)],
+            spaces: [initial_space, PacketSpace::new(now), data_space],
             highest_space: SpaceId::Initial,
             prev_crypto: None,
             next_crypto: None,
             accepted_0rtt: false,
             permit_idle_reset: true,
-            idle_timeout: match config.max_idle_timeout {
-                None | Some(VarInt(0)) => None,
-                Some(dur) => Some(Duration::from_millis(dur.0)),
-            },
+            loss_detection_timer_stale: false,
+            idle_timeout,
+            negotiated_idle_timeout: idle_timeout,
+            local_idle_timeout: None,
+            last_sent: None,
+            last_received: None,
+            keep_alive_discovery: config
+                .adaptive_keep_alive
+                .clone()
+                .map(KeepAliveDiscovery::new),
+            peer_limits: PeerLimits::new(&config.peer_limits, now),
+            deferred_packets: DeferredPackets::default(),
             timers: TimerTable::default(),
+            timer_epoch,
             authentication_failures: 0,
             error: None,
             #[cfg(test)]
@@ -331,14 +477,30 @@ impl Connection {
 
             path_responses: PathResponses::default(),
             close: false,
+            close_retransmits: 0,
+            close_answered: false,
 
-            ack_frequency: AckFrequencyState::new(get_max_ack_delay(
-                &TransportParameters::default(),
-            )),
+            ack_frequency: AckFrequencyState::new(
+                get_max_ack_delay(&TransportParameters::default()),
+                Duration::from_millis(config.ack_policy_config.max_ack_delay_millis()),
+            ),
 
             pto_count: 0,
+            first_pto: None,
+            loss_detector: config.loss_detector_factory.clone().build(LossThresholds {
+                packet: config.packet_threshold,
+                time: config.time_threshold,
+            }),
+            rtt_probe: None,
+            probed_rtt: None,
+            path_probes: Vec::new(),
 
             app_limited: false,
+            sending_paused: false,
+            deferred_close: None,
+            close_1rtt_only: false,
+            congestion_blocked_since: None,
+            flow_control_blocked_since: None,
             receiving_ecn: false,
             total_authed_packets: 0,
 
@@ -353,16 +515,24 @@ impl Connection {
             datagrams: DatagramState::default(),
             config,
             rem_cids: CidQueue::new(rem_cid),
+            rem_cid_adopted: RemCidAdopted {
+                sequence: 0,
+                time: now,
+                bytes: 0,
+            },
             rng,
             stats: ConnectionStats::default(),
+            rtt_histogram,
             version,
         };
+        this.path.flow_label = this.new_flow_label();
+        this.reported_mtu = this.path.current_mtu();
         if path_validated {
             this.on_path_validated();
         }
         if side.is_client() {
             // Kick off the connection
-            this.write_crypto();
+            this.write_crypto(now);
             this.init_0rtt();
         }
         this
@@ -402,6 +572,49 @@ impl Connection {
         None
     }
 
+    /// Return observations about the internal behavior of the transport
+    ///
+    /// Unlike [`poll`](Self::poll), polling these is optional: only the most recent
+    /// observations are retained.
+    #[must_use]
+    pub fn poll_transport_event(&mut self) -> Option<TransportEvent> {
+        if let Some(x) = self.transport_events.pop_front() {
+            return Some(x);
+        }
+
+        if let Some(rtt) = self.probed_rtt.take() {
+            return Some(TransportEvent::RttProbed { rtt });
+        }
+
+        let mtu = self.path.current_mtu();
+        if mtu != self.reported_mtu {
+            self.reported_mtu = mtu;
+            return Some(TransportEvent::MtuChanged { mtu });
+        }
+
+        if let Some(dir) = self.streams.poll_blocked() {
+            return Some(TransportEvent::StreamsBlocked { dir });
+        }
+
+        if let Some(id) = self.streams.poll_opened() {
+            return Some(TransportEvent::StreamOpened { id });
+        }
+
+        let (incoming, outgoing) = self.datagrams.take_dropped();
+        if incoming > 0 || outgoing > 0 {
+            return Some(TransportEvent::DatagramsDropped { incoming, outgoing });
+        }
+
+        None
+    }
+
+    fn transport_event(&mut self, event: TransportEvent) {
+        if self.transport_events.len() >= MAX_TRANSPORT_EVENTS {
+            self.transport_events.pop_front();
+        }
+        self.transport_events.push_back(event);
+    }
+
     /// Return endpoint-facing events
     #[must_use]
     pub fn poll_endpoint_events(&mut self) -> Option<EndpointEvent> {
@@ -440,6 +653,37 @@ impl Connection {
         }
     }
 
+    /// Send-side flow control state of stream `id`
+    ///
+    /// Reports the limits granted by the peer for the stream and the connection, how much of them
+    /// was consumed, and which limit, if any, is currently holding the stream back. This allows
+    /// attributing backpressure observed by the application to its cause.
+    pub fn send_flow_control(&self, id: StreamId) -> Result<SendFlowControl, ClosedStream> {
+        let cwnd_full = self.path.in_flight.bytes >= self.path.congestion.window();
+        self.streams.send_flow_control(id, cwnd_full)
+    }
+
+    /// Reset the send halves and stop the receive halves of all open streams in `group`
+    ///
+    /// Returns the final statistics of the group, which is forgotten, or `None` if no stream was
+    /// assigned to it with [`Streams::set_group`].
+    pub fn close_stream_group(
+        &mut self,
+        group: u64,
+        error_code: VarInt,
+    ) -> Option<StreamGroupStats> {
+        let (streams, stats) = self.streams.remove_group(group)?;
+        for id in streams {
+            if id.dir() == Dir::Bi || id.initiator() == self.side.side() {
+                let _ = self.send_stream(id).reset(error_code);
+            }
+            if id.dir() == Dir::Bi || id.initiator() != self.side.side() {
+                let _ = self.recv_stream(id).stop(error_code);
+            }
+        }
+        Some(stats)
+    }
+
     /// Returns packets to transmit
     ///
     /// Connections should be polled for transmit after:
@@ -467,10 +711,17 @@ impl Connection {
         // packets, this can be earlier than the start of the current QUIC packet.
         let mut datagram_start = 0;
         let mut segment_size = usize::from(self.path.current_mtu());
+        let mut transmit_kind = TransmitKind::Data;
 
         if let Some(challenge) = self.send_path_challenge(now, buf) {
             return Some(challenge);
         }
+        if let Some(challenge) = self.send_path_probe(now, buf) {
+            return Some(challenge);
+        }
+
+        self.maybe_rotate_rem_cid(now);
+        self.maybe_hop_port(now);
 
         // If we need to send a probe, make sure we have something to send.
         for space in SpaceId::iter() {
@@ -479,6 +730,10 @@ impl Connection {
             self.spaces[space].maybe_queue_probe(request_immediate_ack, &self.streams);
         }
 
+        if let Some(reason) = self.deferred_close.take() {
+            self.close_after_handshake_flight(now, reason);
+        }
+
         // Check whether we need to send a close message
         let close = match self.state {
             State::Drained => {
@@ -516,7 +771,10 @@ impl Connection {
         let mut sent_frames = None;
         let mut pad_datagram = false;
         let mut pad_datagram_to_mtu = false;
+        // Whether the current datagram is subject to `TransportConfig::padding_policy`
+        let mut pad_datagram_by_policy = false;
         let mut congestion_blocked = false;
+        let mut cwnd_full = false;
 
         // Iterate over all spaces and find data to send
         let mut space_idx = 0;
@@ -537,7 +795,9 @@ impl Connection {
 
             // Is there data or a close message to send in this space?
             let can_send = self.space_can_send(space_id, frame_space_1rtt);
-            if can_send.is_empty() && (!close || self.spaces[space_id].crypto.is_none()) {
+            if (can_send.is_empty() && (!close || self.spaces[space_id].crypto.is_none()))
+                || (close && self.close_1rtt_only && space_id != SpaceId::Data)
+            {
                 space_idx += 1;
                 continue;
             }
@@ -578,6 +838,15 @@ impl Connection {
                     break;
                 }
 
+                // Leave repeated ACKs to the next transmit rather than appending them to this
+                // batch, so that a burst of losses is less likely to take all copies
+                if num_datagrams > 0
+                    && !ack_eliciting
+                    && self.spaces[space_id].pending_acks.only_repetitions()
+                {
+                    break;
+                }
+
                 // Anti-amplification is only based on `total_sent`, which gets
                 // updated at the end of this method. Therefore we pass the amount
                 // of bytes for datagrams that are already created, as well as 1 byte
@@ -589,6 +858,7 @@ impl Connection {
                     .anti_amplification_blocked(segment_size as u64 * (num_datagrams as u64) + 1)
                 {
                     trace!("blocked by anti-amplification");
+                    self.stats.path.anti_amplification_blocked += 1;
                     break;
                 }
 
@@ -607,6 +877,7 @@ impl Connection {
                     if self.path.in_flight.bytes + bytes_to_send >= self.path.congestion.window() {
                         space_idx += 1;
                         congestion_blocked = true;
+                        cwnd_full = true;
                         // We continue instead of breaking here in order to avoid
                         // blocking loss probes queued for higher spaces.
                         trace!("blocked by congestion control");
@@ -636,6 +907,9 @@ impl Connection {
                     if pad_datagram {
                         builder.pad_to(MIN_INITIAL_SIZE);
                     }
+                    if pad_datagram_by_policy {
+                        self.apply_padding_policy(&mut builder, buf.len(), buf_capacity);
+                    }
 
                     if num_datagrams > 1 || pad_datagram_to_mtu {
                         // If too many padding bytes would be required to continue the GSO batch
@@ -672,6 +946,7 @@ impl Connection {
                     }
 
                     builder.finish_and_track(now, self, sent_frames.take(), buf);
+                    pad_datagram_by_policy = false;
 
                     if num_datagrams == 1 {
                         // Set the segment size for this GSO batch to the size of the first UDP
@@ -706,6 +981,7 @@ impl Connection {
                     0 => segment_size,
                     _ => {
                         self.spaces[space_id].loss_probes -= 1;
+                        transmit_kind = TransmitKind::LossProbe;
                         // Clamp the datagram to at most the minimum MTU to ensure that loss probes
                         // can get through and enable recovery even if the path MTU has shrank
                         // unexpectedly.
@@ -795,13 +1071,15 @@ impl Connection {
                         &mut SentFrames::default(),
                         &mut self.spaces[space_id],
                         buf,
+                        builder
+                            .max_size
+                            .saturating_sub(frame::ConnectionClose::SIZE_BOUND + 1),
                         &mut self.stats,
                     );
                 }
 
-                // Since there only 64 ACK frames there will always be enough space
-                // to encode the ConnectionClose frame too. However we still have the
-                // check here to prevent crashes if something changes.
+                // ACKs are truncated to leave space for the ConnectionClose frame. However we
+                // still have the check here to prevent crashes if something changes.
                 debug_assert!(
                     buf.len() + frame::ConnectionClose::SIZE_BOUND < builder.max_size,
                     "ACKs should leave space for ConnectionClose"
@@ -867,14 +1145,19 @@ impl Connection {
                         }),
                         buf,
                     );
-                    self.stats.udp_tx.on_sent(1, buf.len());
-                    return Some(Transmit {
-                        destination: remote,
-                        size: buf.len(),
-                        ecn: None,
-                        segment_size: None,
-                        src_ip: self.local_ip,
-                    });
+                    self.stats.udp_tx.on_sent(1, buf.len(), buf.len());
+                    return Some(self.mark_transmit(
+                        TransmitKind::PathValidation,
+                        Transmit {
+                            destination: remote,
+                            size: buf.len(),
+                            ecn: None,
+                            dscp: self.dscp,
+                            flow_label: None,
+                            segment_size: None,
+                            src_ip: self.local_ip,
+                        },
+                    ));
                 }
             }
 
@@ -896,8 +1179,9 @@ impl Connection {
                 "SendableFrames was {can_send:?}, but only ACKs have been written"
             );
             pad_datagram |= sent.requires_padding;
+            pad_datagram_by_policy |= space_id == SpaceId::Data;
 
-            if sent.largest_acked.is_some() {
+            if sent.acked.is_some() {
                 self.spaces[space_id].pending_acks.acks_sent();
                 self.timers.stop(Timer::MaxAckDelay);
             }
@@ -914,6 +1198,9 @@ impl Connection {
             if pad_datagram {
                 builder.pad_to(MIN_INITIAL_SIZE);
             }
+            if pad_datagram_by_policy {
+                self.apply_padding_policy(&mut builder, buf.len(), buf_capacity);
+            }
 
             // If this datagram is a loss probe and `segment_size` is larger than `INITIAL_MTU`,
             // then padding it to `segment_size` would risk failure to recover from a reduction in
@@ -935,10 +1222,13 @@ impl Connection {
                 &mut self.path,
                 now,
                 self.orig_rem_cid,
+                self.trace_context.as_deref(),
             );
         }
 
         self.app_limited = buf.is_empty() && !congestion_blocked;
+        self.update_blocked_time(now, cwnd_full);
+        self.streams.on_transmit_attempt(cwnd_full);
 
         // Send MTU probe if necessary
         if buf.is_empty() && self.state.is_established() {
@@ -981,6 +1271,7 @@ impl Connection {
 
             self.stats.path.sent_plpmtud_probes += 1;
             num_datagrams = 1;
+            transmit_kind = TransmitKind::MtuProbe;
 
             trace!(?probe_size, "writing MTUD probe");
         }
@@ -992,22 +1283,49 @@ impl Connection {
         trace!("sending {} bytes in {} datagrams", buf.len(), num_datagrams);
         self.path.total_sent = self.path.total_sent.saturating_add(buf.len() as u64);
 
-        self.stats.udp_tx.on_sent(num_datagrams as u64, buf.len());
-
-        Some(Transmit {
-            destination: self.path.remote,
-            size: buf.len(),
-            ecn: if self.path.sending_ecn {
-                Some(EcnCodepoint::Ect0)
-            } else {
-                None
-            },
-            segment_size: match num_datagrams {
-                1 => None,
-                _ => Some(segment_size),
+        self.stats
+            .udp_tx
+            .on_sent(num_datagrams as u64, buf.len(), segment_size);
+
+        Some(self.mark_transmit(
+            transmit_kind,
+            Transmit {
+                destination: self.path.remote,
+                size: buf.len(),
+                ecn: if self.path.sending_ecn {
+                    Some(EcnCodepoint::Ect0)
+                } else {
+                    None
+                },
+                dscp: self.dscp,
+                flow_label: self.path.flow_label,
+                segment_size: match num_datagrams {
+                    1 => None,
+                    _ => Some(segment_size),
+                },
+                src_ip: self.local_ip,
             },
-            src_ip: self.local_ip,
-        })
+        ))
+    }
+
+    /// Apply the configured [`TransmitMarker`], if any, to an outgoing transmit
+    fn mark_transmit(&self, kind: TransmitKind, mut transmit: Transmit) -> Transmit {
+        let Some(marker) = &self.transmit_marker else {
+            return transmit;
+        };
+        let info = TransmitInfo {
+            kind,
+            destination: transmit.destination,
+            size: transmit.size,
+        };
+        let mut marking = TransmitMarking {
+            dscp: transmit.dscp,
+            ecn: transmit.ecn,
+        };
+        marker.mark(&info, &mut marking);
+        transmit.dscp = marking.dscp;
+        transmit.ecn = marking.ecn;
+        transmit
     }
 
     /// Send PATH_CHALLENGE for a previous path if necessary
@@ -1021,31 +1339,75 @@ impl Connection {
             .challenge
             .expect("previous path challenge pending without token");
         let destination = prev_path.remote;
+        let flow_label = prev_path.flow_label;
         debug_assert_eq!(
             self.highest_space,
             SpaceId::Data,
             "PATH_CHALLENGE queued without 1-RTT keys"
         );
-        buf.reserve(MIN_INITIAL_SIZE as usize);
-
-        let buf_capacity = buf.capacity();
-
+        trace!("validating previous path with PATH_CHALLENGE {:08x}", token);
         // Use the previous CID to avoid linking the new path with the previous path. We
         // don't bother accounting for possible retirement of that prev_cid because this is
         // sent once, immediately after migration, when the CID is known to be valid. Even
         // if a post-migration packet caused the CID to be retired, it's fair to pretend
         // this is sent first.
-        let mut builder = PacketBuilder::new(
-            now,
-            SpaceId::Data,
-            *prev_cid,
-            buf,
-            buf_capacity,
-            0,
-            false,
-            self,
-        )?;
-        trace!("validating previous path with PATH_CHALLENGE {:08x}", token);
+        let cid = *prev_cid;
+        self.send_challenge_datagram(now, buf, cid, destination, flow_label, token)
+    }
+
+    /// Send PATH_CHALLENGE for a path validation requested by the application if necessary
+    fn send_path_probe(&mut self, now: Instant, buf: &mut Vec<u8>) -> Option<Transmit> {
+        // Wait for 1-RTT keys
+        self.spaces[SpaceId::Data].crypto.as_ref()?;
+        let retry_at = now + self.pto(SpaceId::Data);
+        let i = self.path_probes.iter().position(|x| x.retry_at.is_none())?;
+        self.path_probes[i].retry_at = Some(retry_at);
+        self.set_path_probe_timer();
+        let PathProbe {
+            remote: destination,
+            cid,
+            token,
+            ..
+        } = self.path_probes[i];
+        let (cid, flow_label) = if destination == self.path.remote {
+            (self.rem_cids.active(), self.path.flow_label)
+        } else {
+            // The CID may have been retired or taken into use by the current path since
+            let usable = |cid: ConnectionId| {
+                cid.is_empty() || self.rem_cids.available().any(|(_, x)| x == cid)
+            };
+            let cid = match cid.filter(|&cid| usable(cid)) {
+                Some(cid) => cid,
+                None => {
+                    // Wait for the peer to issue another one until the probe times out
+                    self.path_probes[i].cid = None;
+                    let cid = self.spare_probe_cid()?;
+                    self.path_probes[i].cid = Some(cid);
+                    cid
+                }
+            };
+            (cid, None)
+        };
+        trace!(%destination, "validating path with PATH_CHALLENGE {:08x}", token);
+        self.send_challenge_datagram(now, buf, cid, destination, flow_label, token)
+    }
+
+    /// Send a datagram consisting of a PATH_CHALLENGE frame to `destination`
+    fn send_challenge_datagram(
+        &mut self,
+        now: Instant,
+        buf: &mut Vec<u8>,
+        cid: ConnectionId,
+        destination: SocketAddr,
+        flow_label: Option<u32>,
+        token: u64,
+    ) -> Option<Transmit> {
+        buf.reserve(MIN_INITIAL_SIZE as usize);
+
+        let buf_capacity = buf.capacity();
+
+        let mut builder =
+            PacketBuilder::new(now, SpaceId::Data, cid, buf, buf_capacity, 0, false, self)?;
         buf.write(frame::FrameType::PATH_CHALLENGE);
         buf.write(token);
         self.stats.frame_tx.path_challenge += 1;
@@ -1057,15 +1419,20 @@ impl Connection {
         builder.pad_to(MIN_INITIAL_SIZE);
 
         builder.finish(self, now, buf);
-        self.stats.udp_tx.on_sent(1, buf.len());
-
-        Some(Transmit {
-            destination,
-            size: buf.len(),
-            ecn: None,
-            segment_size: None,
-            src_ip: self.local_ip,
-        })
+        self.stats.udp_tx.on_sent(1, buf.len(), buf.len());
+
+        Some(self.mark_transmit(
+            TransmitKind::PathValidation,
+            Transmit {
+                destination,
+                size: buf.len(),
+                ecn: None,
+                dscp: self.dscp,
+                flow_label,
+                segment_size: None,
+                src_ip: self.local_ip,
+            },
+        ))
     }
 
     /// Indicate what types of frames are ready to send for the given space
@@ -1093,53 +1460,8 @@ impl Connection {
     pub fn handle_event(&mut self, event: ConnectionEvent) {
         use ConnectionEventInner::*;
         match event.0 {
-            Datagram(DatagramConnectionEvent {
-                now,
-                remote,
-                ecn,
-                first_decode,
-                remaining,
-            }) => {
-                // If this packet could initiate a migration and we're a client or a server that
-                // forbids migration, drop the datagram. This could be relaxed to heuristically
-                // permit NAT-rebinding-like migration.
-                if remote != self.path.remote && !self.side.remote_may_migrate() {
-                    trace!("discarding packet from unrecognized peer {}", remote);
-                    return;
-                }
-
-                let was_anti_amplification_blocked = self.path.anti_amplification_blocked(1);
-
-                self.stats.udp_rx.datagrams += 1;
-                self.stats.udp_rx.bytes += first_decode.len() as u64;
-                let data_len = first_decode.len();
-
-                self.handle_decode(now, remote, ecn, first_decode);
-                // The current `path` might have changed inside `handle_decode`,
-                // since the packet could have triggered a migration. Make sure
-                // the data received is accounted for the most recent path by accessing
-                // `path` after `handle_decode`.
-                self.path.total_recvd = self.path.total_recvd.saturating_add(data_len as u64);
-
-                if let Some(data) = remaining {
-                    self.stats.udp_rx.bytes += data.len() as u64;
-                    self.handle_coalesced(now, remote, ecn, data);
-                }
-
-                self.config.qlog_sink.emit_recovery_metrics(
-                    self.pto_count,
-                    &mut self.path,
-                    now,
-                    self.orig_rem_cid,
-                );
-
-                if was_anti_amplification_blocked {
-                    // A prior attempt to set the loss detection timer may have failed due to
-                    // anti-amplification, so ensure it's set now. Prevents a handshake deadlock if
-                    // the server's first flight is lost.
-                    self.set_loss_detection_timer(now);
-                }
-            }
+            Datagram(datagram) => self.handle_datagrams(iter::once(datagram)),
+            Datagrams(batch) => self.handle_datagrams(batch),
             NewIdentifiers(ids, now) => {
                 self.local_cid_state.new_cids(&ids, now);
                 ids.into_iter().rev().for_each(|frame| {
@@ -1153,6 +1475,75 @@ impl Connection {
         }
     }
 
+    /// Process datagrams received in the same batch
+    ///
+    /// Bookkeeping that only depends on the state after the whole batch, like arming the loss
+    /// detection timer, is done once at the end.
+    fn handle_datagrams(&mut self, batch: impl IntoIterator<Item = DatagramConnectionEvent>) {
+        // Receiving can only lift the anti-amplification limit, so checking once suffices
+        let was_anti_amplification_blocked = self.path.anti_amplification_blocked(1);
+        let mut last_handled = None;
+        for DatagramConnectionEvent {
+            now,
+            remote,
+            ecn,
+            first_decode,
+            remaining,
+        } in batch
+        {
+            // If this packet could initiate a migration and we're a client or a server that
+            // forbids migration, drop the datagram. This could be relaxed to heuristically
+            // permit NAT-rebinding-like migration.
+            if remote != self.path.remote
+                && !self.side.remote_may_migrate()
+                && !self.is_hopping_port(remote)
+                && !self.is_probed_remote(remote)
+            {
+                trace!("discarding packet from unrecognized peer {}", remote);
+                continue;
+            }
+
+            let data_len = first_decode.len();
+            let datagram_len = data_len + remaining.as_ref().map_or(0, |data| data.len());
+            self.stats.udp_rx.on_received(datagram_len);
+            if !self.peer_limits.on_datagram(now) {
+                trace!("discarding datagram exceeding the rate limit");
+                self.stats.rate_limited_datagrams += 1;
+                continue;
+            }
+
+            self.handle_decode(now, remote, ecn, first_decode);
+            // The current `path` might have changed inside `handle_decode`,
+            // since the packet could have triggered a migration. Make sure
+            // the data received is accounted for the most recent path by accessing
+            // `path` after `handle_decode`.
+            self.path.total_recvd = self.path.total_recvd.saturating_add(data_len as u64);
+
+            if let Some(data) = remaining {
+                self.handle_coalesced(now, remote, ecn, data);
+            }
+            last_handled = Some(now);
+        }
+
+        let Some(now) = last_handled else {
+            return;
+        };
+        self.config.qlog_sink.emit_recovery_metrics(
+            self.pto_count,
+            &mut self.path,
+            now,
+            self.orig_rem_cid,
+            self.trace_context.as_deref(),
+        );
+
+        // A prior attempt to set the loss detection timer may have failed due to
+        // anti-amplification, so ensure it's set now. Prevents a handshake deadlock if the
+        // server's first flight is lost.
+        if mem::take(&mut self.loss_detection_timer_stale) || was_anti_amplification_blocked {
+            self.set_loss_detection_timer(now);
+        }
+    }
+
     /// Process timer expirations
     ///
     /// Executes protocol logic, potentially preparing signals (including application `Event`s,
@@ -1175,11 +1566,24 @@ impl Connection {
                     self.endpoint_events.push_back(EndpointEventInner::Drained);
                 }
                 Timer::Idle => {
-                    self.kill(ConnectionError::TimedOut);
+                    let cause = self.timeout_cause(now);
+                    self.kill(ConnectionError::TimedOut(cause));
                 }
                 Timer::KeepAlive => {
+                    if !self.keep_alive_needed() {
+                        trace!("connection unused, suspending keep-alives");
+                        continue;
+                    }
                     trace!("sending keep-alive");
                     self.ping();
+                    let rebind_window = 3 * self.pto(SpaceId::Data);
+                    if let Some(interval) = self
+                        .keep_alive_discovery
+                        .as_mut()
+                        .and_then(|x| x.on_keep_alive(now, rebind_window))
+                    {
+                        self.transport_event(TransportEvent::KeepAliveSettled { interval });
+                    }
                 }
                 Timer::LossDetection => {
                     self.on_loss_detection_timeout(now);
@@ -1189,6 +1593,7 @@ impl Connection {
                         &mut self.path,
                         now,
                         self.orig_rem_cid,
+                        self.trace_context.as_deref(),
                     );
                 }
                 Timer::KeyDiscard => {
@@ -1198,8 +1603,12 @@ impl Connection {
                 Timer::PathValidation => {
                     debug!("path validation failed");
                     if let Some((_, prev)) = self.prev_path.take() {
+                        let remote = self.path.remote;
                         self.path = prev;
                         self.set_loss_detection_timer(now);
+                        if remote != self.path.remote {
+                            self.queue_observed_addr();
+                        }
                     }
                     self.path.challenge = None;
                     self.path.challenge_pending = false;
@@ -1224,6 +1633,36 @@ impl Connection {
                         .pending_acks
                         .on_max_ack_delay_timeout()
                 }
+                Timer::PathProbe => {
+                    let events = &mut self.events;
+                    self.path_probes.retain_mut(|probe| {
+                        if probe.deadline <= now {
+                            debug!(remote = %probe.remote, "path validation failed");
+                            events.push_back(Event::PathValidation {
+                                remote: probe.remote,
+                                validated: false,
+                            });
+                            return false;
+                        }
+                        if probe.retry_at.is_some_and(|x| x <= now) {
+                            probe.retry_at = None;
+                        }
+                        true
+                    });
+                    self.set_path_probe_timer();
+                }
+                Timer::CloseRetransmit => {
+                    if let State::Closed(_) = self.state {
+                        trace!("retransmitting CONNECTION_CLOSE");
+                        self.close = true;
+                        self.close_retransmits = self.close_retransmits.saturating_add(1);
+                        let backoff = 2u32.saturating_pow(self.close_retransmits);
+                        self.timers.set(
+                            Timer::CloseRetransmit,
+                            now + self.pto(self.highest_space).saturating_mul(backoff),
+                        );
+                    }
+                }
             }
         }
     }
@@ -1238,12 +1677,79 @@ impl Connection {
     /// If [`Streams::send_streams`] returns 0, all outstanding stream data has been
     /// delivered. There may still be data from the peer that has not been received.
     ///
+    /// A server may close a connection before the handshake completes, e.g. to refuse it for
+    /// reasons only known to the application. If it can already send 1-RTT packets, the close is
+    /// deferred until its handshake messages have been sent, so that the client can decrypt the
+    /// application's `error_code` and `reason`. Otherwise, and if the client turns out to be unable
+    /// to decrypt it, the client only learns of a generic `APPLICATION_ERROR`, as RFC 9000 forbids
+    /// revealing application state in Initial and Handshake packets.
+    ///
     /// [`StreamEvent::Finished`]: crate::StreamEvent::Finished
     pub fn close(&mut self, now: Instant, error_code: VarInt, reason: Bytes) {
-        self.close_inner(
-            now,
-            Close::Application(frame::ApplicationClose { error_code, reason }),
-        )
+        if self.deferred_close.is_some() {
+            return;
+        }
+        let reason = frame::ApplicationClose { error_code, reason };
+        if self.side.is_server()
+            && self.state.is_handshake()
+            && self.spaces[SpaceId::Data].crypto.is_some()
+        {
+            self.close_after_handshake_flight(now, reason);
+            return;
+        }
+        self.close_inner(now, Close::Application(reason))
+    }
+
+    /// Close with an application error in 1-RTT packets once no handshake data is left to send
+    fn close_after_handshake_flight(&mut self, now: Instant, reason: frame::ApplicationClose) {
+        let handshake_pending = [SpaceId::Initial, SpaceId::Handshake]
+            .into_iter()
+            .any(|space| !self.spaces[space].pending.crypto.is_empty());
+        if handshake_pending && !self.state.is_closed() {
+            self.deferred_close = Some(reason);
+            return;
+        }
+        self.close_1rtt_only = !self.state.is_closed();
+        self.close_inner(now, Close::Application(reason));
+    }
+
+    /// Close a connection, retransmitting CONNECTION_CLOSE until the peer closes in turn
+    ///
+    /// Unlike [`close()`](Self::close), which only resends CONNECTION_CLOSE in response to
+    /// packets from the peer, this retransmits it after a PTO with exponential backoff, so that
+    /// the peer learns `error_code` and `reason` even if the first CONNECTION_CLOSE is lost. The
+    /// connection remains in the closing state until the peer answers with a CONNECTION_CLOSE of
+    /// its own, as reported by [`is_close_answered()`](Self::is_close_answered), or until
+    /// `timeout` elapses, whichever is first. Does nothing if the connection is already closed.
+    ///
+    /// CONNECTION_CLOSE isn't ack-eliciting, and a peer that received it stops acknowledging
+    /// packets, so the peer's own CONNECTION_CLOSE is the only sign of delivery. A peer is only
+    /// required to send one if it receives further packets, so the answer may never come even
+    /// though the close was delivered; `timeout` bounds the wait.
+    pub fn close_and_await_peer(
+        &mut self,
+        now: Instant,
+        error_code: VarInt,
+        reason: Bytes,
+        timeout: Duration,
+    ) {
+        if self.state.is_closed() {
+            return;
+        }
+        self.close(now, error_code, reason);
+        let pto = self.pto(self.highest_space);
+        self.timers
+            .set(Timer::Close, now + Ord::max(timeout, 3 * pto));
+        self.timers.set(Timer::CloseRetransmit, now + pto);
+    }
+
+    /// Whether the peer answered our CONNECTION_CLOSE with its own
+    ///
+    /// This indicates that the peer received our CONNECTION_CLOSE, including its error code and
+    /// reason. `false` doesn't mean that it wasn't received, see
+    /// [`close_and_await_peer()`](Self::close_and_await_peer).
+    pub fn is_close_answered(&self) -> bool {
+        self.close_answered
     }
 
     fn close_inner(&mut self, now: Instant, reason: Close) {
@@ -1265,12 +1771,105 @@ impl Connection {
     pub fn stats(&self) -> ConnectionStats {
         let mut stats = self.stats;
         stats.path.rtt = self.path.rtt.get();
+        stats.path.latest_rtt = self.path.rtt.latest();
+        stats.path.rtt_var = self.path.rtt.var();
+        stats.path.min_rtt = self.path.rtt.min();
         stats.path.cwnd = self.path.congestion.window();
         stats.path.current_mtu = self.path.mtud.current_mtu();
+        stats.path.retransmitted_bytes = self.streams.retransmitted_bytes();
+        stats.path.rtt_p50 = self.rtt_histogram.quantile(0.5).unwrap_or_default();
+        stats.path.rtt_p95 = self.rtt_histogram.quantile(0.95).unwrap_or_default();
+        stats.path.rtt_p99 = self.rtt_histogram.quantile(0.99).unwrap_or_default();
+        stats.ack_ranges = self
+            .spaces
+            .iter()
+            .map(|space| space.pending_acks.ranges().len() as u64)
+            .sum();
 
         stats
     }
 
+    /// Snapshot of the connection's internal state, for diagnostics
+    ///
+    /// Unlike [`stats()`](Self::stats), this is costly to compute and its structure may change in
+    /// any release, so it should not be polled routinely.
+    pub fn debug_state(&self) -> ConnectionDebugState {
+        let metrics = self.path.congestion.metrics();
+        ConnectionDebugState {
+            side: self.side(),
+            state: self.state.name(),
+            spaces: SpaceId::iter()
+                .map(|space| self.spaces[space].debug_state(space, &self.streams))
+                .collect(),
+            flow_control: self.streams.flow_control_debug_state(),
+            streams: self.streams.streams_debug_state(),
+            congestion: CongestionDebugState {
+                window: metrics.congestion_window,
+                ssthresh: metrics.ssthresh,
+                pacing_rate: metrics.pacing_rate,
+                bytes_in_flight: self.path.in_flight.bytes,
+                smoothed_rtt: self.path.rtt.get(),
+                min_rtt: self.path.rtt.min(),
+                latest_rtt: self.path.rtt.latest(),
+                pto_count: self.pto_count,
+                mtu: self.path.mtud.current_mtu(),
+            },
+        }
+    }
+
+    /// Connection IDs currently in use in either direction
+    ///
+    /// The number of connection IDs issued to the peer is governed by
+    /// [`TransportConfig::max_issued_cids`].
+    pub fn connection_ids(&self) -> ConnectionIdState {
+        ConnectionIdState {
+            local_active: self.local_cid_state.active().collect(),
+            local_retired: self.local_cid_state.retired(),
+            remote_active: (self.rem_cids.active_seq(), self.rem_cids.active()),
+            remote_available: self.rem_cids.available().collect(),
+            remote_retired: self.rem_cids.retired(),
+        }
+    }
+
+    /// The transport parameters sent by the peer
+    ///
+    /// `None` until they have been received during the handshake.
+    pub fn peer_transport_parameters(&self) -> Option<PeerTransportParameters> {
+        let params = self.crypto.transport_parameters().ok()??;
+        Some((&params).into())
+    }
+
+    /// Protocol extensions supported by both endpoints
+    ///
+    /// Until the peer's transport parameters are received, this is based on those remembered for
+    /// 0-RTT, if any.
+    pub fn negotiated_extensions(&self) -> Vec<Extension> {
+        let mut extensions = Vec::new();
+        if self.peer_params.max_datagram_frame_size.is_some()
+            && self.config.datagram_receive_buffer_size.is_some()
+        {
+            extensions.push(Extension::Datagram);
+        }
+        if self.peer_supports_ack_frequency() {
+            extensions.push(Extension::AckFrequency);
+        }
+        if self.peer_params.grease_quic_bit && self.endpoint_config.grease_quic_bit {
+            extensions.push(Extension::GreaseQuicBit);
+        }
+        if self.peer_params.address_discovery.is_some_and(|x| {
+            x.receives() && self.config.send_observed_address_reports
+                || x.sends() && self.config.receive_observed_address_reports
+        }) {
+            extensions.push(Extension::AddressDiscovery);
+        }
+        extensions
+    }
+
+    /// Distribution of the RTT samples taken on the connection
+    pub fn rtt_histogram(&self) -> &RttHistogram {
+        &self.rtt_histogram
+    }
+
     /// Ping the remote endpoint
     ///
     /// Causes an ACK-eliciting packet to be transmitted.
@@ -1278,6 +1877,124 @@ impl Connection {
         self.spaces[self.highest_space].ping_pending = true;
     }
 
+    /// Stop sending application data until [`resume_sending()`](Self::resume_sending) is called
+    ///
+    /// No STREAM or DATAGRAM frames are sent in the meantime, including retransmissions. Everything
+    /// else, such as acknowledgements, keep-alives and flow control updates, is sent as usual.
+    /// Writes to streams and outgoing datagrams are still buffered, up to the limits set by
+    /// [`TransportConfig::send_window`] and [`TransportConfig::datagram_send_buffer_size`], so that
+    /// an application scheduling its own traffic across connections can halt one cheaply.
+    pub fn pause_sending(&mut self) {
+        self.sending_paused = true;
+    }
+
+    /// Undo [`pause_sending()`](Self::pause_sending)
+    pub fn resume_sending(&mut self) {
+        self.sending_paused = false;
+    }
+
+    /// Whether sending application data is paused, see [`pause_sending()`](Self::pause_sending)
+    pub fn is_sending_paused(&self) -> bool {
+        self.sending_paused
+    }
+
+    /// Measure the round-trip time on demand
+    ///
+    /// Causes an ACK-eliciting packet to be transmitted, which the peer is asked to acknowledge
+    /// immediately if it supports the acknowledgement frequency extension. Once it is acknowledged,
+    /// [`TransportEvent::RttProbed`] is emitted. The probe is repeated if the packet is lost. Calls
+    /// made while a probe is outstanding share its result.
+    pub fn probe_rtt(&mut self) {
+        if self.rtt_probe.is_none() {
+            self.queue_rtt_probe();
+        }
+    }
+
+    fn queue_rtt_probe(&mut self) {
+        self.rtt_probe = Some(RttProbe::Queued);
+        self.ping();
+        if !self.is_handshaking() && self.peer_supports_ack_frequency() {
+            self.immediate_ack();
+        }
+    }
+
+    /// Check that the peer is still reachable at its current address
+    ///
+    /// See [`validate_path_to()`](Self::validate_path_to).
+    pub fn validate_path(&mut self, now: Instant) {
+        let remote = self.path.remote;
+        self.validate_path_to(now, remote)
+            .expect("the current path needs no spare CID");
+    }
+
+    /// Check that the peer is reachable at `remote`, e.g. before migrating to a backup address
+    ///
+    /// Sends PATH_CHALLENGE frames to `remote` until the peer responds, or three probe timeouts
+    /// have passed. Either way, [`Event::PathValidation`] is emitted. The connection keeps using
+    /// its current path regardless of the outcome. Calls made while `remote` is being validated
+    /// share the result.
+    ///
+    /// Challenges to an address other than the current one are sent with a connection ID the peer
+    /// issued but that isn't in use, so that the two paths can't be linked. Fails if there is
+    /// none.
+    pub fn validate_path_to(
+        &mut self,
+        now: Instant,
+        remote: SocketAddr,
+    ) -> Result<(), ValidatePathError> {
+        if self.path_probes.iter().any(|x| x.remote == remote) {
+            return Ok(());
+        }
+        let mut pto = self.pto(SpaceId::Data);
+        let mut cid = None;
+        if remote != self.path.remote {
+            cid = Some(
+                self.spare_probe_cid()
+                    .ok_or(ValidatePathError::NoConnectionId)?,
+            );
+            // Nothing is known about the RTT of the new path
+            pto = cmp::max(pto, 3 * self.config.initial_rtt);
+        }
+        self.path_probes.push(PathProbe {
+            remote,
+            cid,
+            token: self.rng.random(),
+            deadline: now + 3 * pto,
+            retry_at: None,
+        });
+        self.set_path_probe_timer();
+        Ok(())
+    }
+
+    /// A remote CID to probe an alternative address with, not used by the current path or other
+    /// probes
+    fn spare_probe_cid(&self) -> Option<ConnectionId> {
+        let active = self.rem_cids.active();
+        if active.is_empty() {
+            // The peer can't tell paths apart by CID either way
+            return Some(active);
+        }
+        // Rotation of the current path's CID takes the oldest spare ones first
+        self.rem_cids
+            .available()
+            .map(|(_, cid)| cid)
+            .filter(|cid| !self.path_probes.iter().any(|x| x.cid == Some(*cid)))
+            .last()
+    }
+
+    fn set_path_probe_timer(&mut self) {
+        match self
+            .path_probes
+            .iter()
+            .flat_map(|x| [Some(x.deadline), x.retry_at])
+            .flatten()
+            .min()
+        {
+            Some(time) => self.timers.set(Timer::PathProbe, time),
+            None => self.timers.stop(Timer::PathProbe),
+        }
+    }
+
     /// Update traffic keys spontaneously
     ///
     /// This can be useful for testing key updates, as they otherwise only happen infrequently.
@@ -1372,11 +2089,200 @@ impl Connection {
         self.local_ip
     }
 
+    /// The address the peer most recently reported to see our packets coming from
+    ///
+    /// This is our reflexive transport address, e.g. the public address a NAT maps us to. Only
+    /// available if enabled through [`TransportConfig::receive_observed_address_reports`] and
+    /// supported by the peer.
+    pub fn observed_address(&self) -> Option<SocketAddr> {
+        self.observed_addr.map(|(_, address)| address)
+    }
+
     /// Current best estimate of this connection's latency (round-trip-time)
     pub fn rtt(&self) -> Duration {
         self.path.rtt.get()
     }
 
+    /// Change the DSCP outgoing packets are marked with
+    ///
+    /// See [`TransportConfig::dscp()`]
+    pub fn set_dscp(&mut self, dscp: Option<u8>) {
+        self.dscp = dscp;
+        Arc::make_mut(&mut self.config).dscp = dscp;
+    }
+
+    /// Change the hook adjusting the marking of individual outgoing transmits
+    ///
+    /// See [`TransportConfig::transmit_marker()`]
+    pub fn set_transmit_marker(&mut self, marker: Option<Arc<dyn TransmitMarker>>) {
+        Arc::make_mut(&mut self.config).transmit_marker = marker.clone();
+        self.transmit_marker = marker;
+    }
+
+    /// The transport configuration of this connection
+    ///
+    /// Reflects changes made through [`tune()`](Self::tune) and the individual setters, such as
+    /// [`set_receive_window()`](Self::set_receive_window). A clone can serve as the starting point for a configuration passed to
+    /// [`tune()`](Self::tune).
+    pub fn config(&self) -> &Arc<TransportConfig> {
+        &self.config
+    }
+
+    /// Apply a new transport configuration to this connection
+    ///
+    /// Only some fields can change on a live connection:
+    /// - [`max_outgoing_bytes_per_second`](TransportConfig::max_outgoing_bytes_per_second)
+    /// - [`ack_policy_config`](TransportConfig::ack_policy_config), except for its
+    ///   `max_ack_delay`, which was advertised to the peer. The new thresholds don't apply if the
+    ///   peer requested others through the ACK frequency extension, unlike the new redundancy.
+    /// - [`keep_alive_interval`](TransportConfig::keep_alive_interval),
+    ///   [`keep_alive_while_active`](TransportConfig::keep_alive_while_active) and
+    ///   [`adaptive_keep_alive`](TransportConfig::adaptive_keep_alive), where a change restarts
+    ///   discovery
+    /// - [`mtu_discovery_config`](TransportConfig::mtu_discovery_config)
+    /// - [`send_window`](TransportConfig::send_window) and
+    ///   [`receive_window`](TransportConfig::receive_window)
+    /// - [`max_concurrent_bidi_streams`](TransportConfig::max_concurrent_bidi_streams) and
+    ///   [`max_concurrent_uni_streams`](TransportConfig::max_concurrent_uni_streams)
+    /// - [`dscp`](TransportConfig::dscp) and
+    ///   [`transmit_marker`](TransportConfig::transmit_marker)
+    ///
+    /// Fields are compared with the current configuration using [`TransportConfig::diff()`]. If
+    /// any other field differs, the connection is left unchanged and the error names the offending
+    /// fields. Changes take effect immediately, counting from `now`.
+    pub fn tune(&mut self, now: Instant, config: Arc<TransportConfig>) -> Result<(), TuneError> {
+        let diff = self.config.diff(&config);
+        let fields = diff
+            .iter()
+            .copied()
+            .filter(|&field| match field {
+                "ack_policy_config" => {
+                    self.config.ack_policy_config.max_ack_delay_millis()
+                        != config.ack_policy_config.max_ack_delay_millis()
+                }
+                _ => !RUNTIME_MUTABLE_FIELDS.contains(&field),
+            })
+            .collect::<Vec<_>>();
+        if !fields.is_empty() {
+            return Err(TuneError { fields });
+        }
+
+        let keep_alive_changed = diff.iter().any(|field| field.starts_with("keep_alive"));
+        for field in diff {
+            match field {
+                "max_outgoing_bytes_per_second" => {
+                    self.set_max_outgoing_bytes_per_second(config.max_outgoing_bytes_per_second)
+                }
+                "ack_policy_config" => {
+                    let ack_policy = &config.ack_policy_config;
+                    let pending_acks = &mut self.spaces[SpaceId::Data].pending_acks;
+                    pending_acks.set_redundancy(ack_policy.redundancy);
+                    if !self.ack_frequency.peer_requested() {
+                        pending_acks.set_thresholds(
+                            ack_policy.ack_eliciting_threshold.into_inner(),
+                            ack_policy.reordering_threshold.into_inner(),
+                        );
+                    }
+                }
+                "adaptive_keep_alive" => {
+                    self.keep_alive_discovery = config
+                        .adaptive_keep_alive
+                        .clone()
+                        .map(KeepAliveDiscovery::new);
+                }
+                "mtu_discovery_config" => {
+                    self.set_mtu_discovery_config(config.mtu_discovery_config.clone())
+                }
+                "send_window" => self.set_send_window(config.send_window),
+                "receive_window" => self.set_receive_window(config.receive_window),
+                "max_concurrent_bidi_streams" => {
+                    self.set_max_concurrent_streams(Dir::Bi, config.max_concurrent_bidi_streams)
+                }
+                "max_concurrent_uni_streams" => {
+                    self.set_max_concurrent_streams(Dir::Uni, config.max_concurrent_uni_streams)
+                }
+                "dscp" => self.set_dscp(config.dscp),
+                "transmit_marker" => self.set_transmit_marker(config.transmit_marker.clone()),
+                // Read from the configuration when needed
+                _ => {}
+            }
+        }
+        self.config = config;
+        if keep_alive_changed {
+            self.timers.stop(Timer::KeepAlive);
+            self.reset_keep_alive(now);
+        }
+        Ok(())
+    }
+
+    /// Number of bytes that may currently be sent, as limited by congestion control and
+    /// anti-amplification
+    ///
+    /// Data already queued for transmission, such as buffered stream data, will consume this
+    /// budget first. Pacing may further delay transmission.
+    pub fn send_budget(&self) -> u64 {
+        let congestion = self
+            .path
+            .congestion
+            .window()
+            .saturating_sub(self.path.in_flight.bytes);
+        match self.path.anti_amplification_budget() {
+            Some(budget) => congestion.min(budget),
+            None => congestion,
+        }
+    }
+
+    /// Number of bytes the anti-amplification limit permits sending, or `None` once the peer's
+    /// address is validated
+    ///
+    /// Only servers are limited, see [`TransportConfig::anti_amplification_factor()`].
+    pub fn anti_amplification_budget(&self) -> Option<u64> {
+        self.path.anti_amplification_budget()
+    }
+
+    /// Current rate at which data is delivered to the peer on the active path
+    ///
+    /// Measured from acknowledgements independently of the congestion controller. `None` until
+    /// enough data has been acknowledged.
+    pub fn bandwidth_estimate(&self) -> Option<BandwidthEstimate> {
+        self.path.delivery_rate.estimate()
+    }
+
+    /// Treat the connection as app-limited until the data currently in flight is acknowledged
+    ///
+    /// The connection is only found to be app-limited once it runs out of data to send. An
+    /// application sending bursts, such as responses to requests, can call this after writing the
+    /// last of a burst, so that the acknowledgements of its tail and of the start of the next
+    /// burst neither grow the congestion window nor shrink the [`bandwidth_estimate()`] for
+    /// lack of data rather than of capacity.
+    ///
+    /// [`bandwidth_estimate()`]: Self::bandwidth_estimate
+    pub fn mark_app_limited(&mut self) {
+        self.path
+            .delivery_rate
+            .mark_app_limited(self.path.in_flight.bytes);
+    }
+
+    /// Whether the last transmit ran out of data to send, or the sender was marked app-limited
+    fn is_app_limited(&self) -> bool {
+        self.app_limited || self.path.delivery_rate.is_app_limited()
+    }
+
+    /// Send the client new address validation tokens reflecting the current state of the path
+    ///
+    /// Tokens are issued once the client's address is validated, early in the connection. If
+    /// [`ValidationTokenConfig::resumption_hints`] is enabled, calling this later, e.g. after a
+    /// bulk transfer, lets a future connection start from a more accurate bandwidth estimate. The
+    /// client keeps the newest tokens. Has no effect on clients.
+    ///
+    /// [`ValidationTokenConfig::resumption_hints`]: crate::ValidationTokenConfig::resumption_hints
+    pub fn refresh_validation_tokens(&mut self) {
+        if !self.path.validated {
+            return;
+        }
+        self.queue_new_tokens();
+    }
+
     /// Current state of this connection's congestion controller, for debugging purposes
     pub fn congestion_state(&self) -> &dyn Controller {
         self.path.congestion.as_ref()
@@ -1396,11 +2302,39 @@ impl Connection {
         self.path.reset(now, &self.config);
     }
 
+    /// Handle a datagram of `size` bytes that could not be sent because it exceeds the path MTU
+    ///
+    /// Call this when the operating system rejects a transmit because its datagrams are too big,
+    /// e.g. with `EMSGSIZE`, passing the size of a single datagram. This lets MTU discovery react
+    /// immediately rather than through loss detection: a rejected MTU probe is declared lost and
+    /// not retransmitted, and if even regular packets are rejected, the MTU is lowered to the
+    /// minimum and discovery resumes below `size`.
+    pub fn handle_too_big(&mut self, now: Instant, size: u16) {
+        if size > self.path.mtud.current_mtu() {
+            if let Some(packet) = self.path.mtud.in_flight_mtu_probe() {
+                if let Some(info) = self.spaces[SpaceId::Data].take(packet) {
+                    self.remove_in_flight(&info);
+                    self.path.mtud.on_probe_lost();
+                    self.stats.path.lost_plpmtud_probes += 1;
+                    self.set_loss_detection_timer(now);
+                }
+            }
+        }
+        if self.path.mtud.on_too_big(size) {
+            self.on_mtu_lowered();
+        }
+    }
+
     /// Modify the number of remotely initiated streams that may be concurrently open
     ///
     /// No streams may be opened by the peer unless fewer than `count` are already open. Large
     /// `count`s increase both minimum and worst-case memory consumption.
     pub fn set_max_concurrent_streams(&mut self, dir: Dir, count: VarInt) {
+        let config = Arc::make_mut(&mut self.config);
+        match dir {
+            Dir::Bi => config.max_concurrent_bidi_streams = count,
+            Dir::Uni => config.max_concurrent_uni_streams = count,
+        }
         self.streams.set_max_concurrent(dir, count);
         // If the limit was reduced, then a flow control update previously deemed insignificant may
         // now be significant.
@@ -1419,11 +2353,31 @@ impl Connection {
 
     /// See [`TransportConfig::send_window()`]
     pub fn set_send_window(&mut self, send_window: u64) {
+        Arc::make_mut(&mut self.config).send_window = send_window;
         self.streams.set_send_window(send_window);
     }
 
+    /// Limit the rate at which data is sent on this connection, in bytes per second
+    ///
+    /// Enforced by pacing, independently of congestion control, so the rate may be lower but
+    /// never higher. `None` lifts the limit. See
+    /// [`TransportConfig::max_outgoing_bytes_per_second()`].
+    pub fn set_max_outgoing_bytes_per_second(&mut self, value: Option<u64>) {
+        Arc::make_mut(&mut self.config).max_outgoing_bytes_per_second = value;
+        self.path.pacing.set_max_bytes_per_second(value);
+        if let Some((_, prev)) = &mut self.prev_path {
+            prev.pacing.set_max_bytes_per_second(value);
+        }
+    }
+
+    /// The current limit on the rate at which data is sent on this connection, in bytes per second
+    pub fn max_outgoing_bytes_per_second(&self) -> Option<u64> {
+        self.path.pacing.max_bytes_per_second()
+    }
+
     /// See [`TransportConfig::receive_window()`]
     pub fn set_receive_window(&mut self, receive_window: VarInt) {
+        Arc::make_mut(&mut self.config).receive_window = receive_window;
         if self.streams.set_receive_window(receive_window) {
             self.spaces[SpaceId::Data].pending.max_data = true;
         }
@@ -1454,7 +2408,7 @@ impl Connection {
             }
         };
 
-        if self.detect_spurious_loss(&ack, space) {
+        if self.detect_spurious_loss(now, &ack, space) {
             self.stats.path.spurious_congestion_events += 1;
             self.path.congestion.on_spurious_congestion_event();
         }
@@ -1475,13 +2429,14 @@ impl Connection {
         let mut ack_eliciting_acked = false;
         for packet in newly_acked.elts() {
             if let Some(info) = self.spaces[space].take(packet) {
-                if let Some(acked) = info.largest_acked {
-                    // Assume ACKs for all packets below the largest acknowledged in `packet` have
-                    // been received. This can cause the peer to spuriously retransmit if some of
-                    // our earlier ACKs were lost, but allows for simpler state tracking. See
+                if let Some(acked) = info.acked.clone() {
+                    // Assume ACKs for all packets in the span acknowledged by `packet` have been
+                    // received. This can cause the peer to spuriously retransmit if some of our
+                    // earlier ACKs were lost, but allows for simpler state tracking. See
                     // discussion at
                     // https://www.rfc-editor.org/rfc/rfc9000.html#name-limiting-ranges-by-tracking
-                    self.spaces[space].pending_acks.subtract_below(acked);
+                    // Ranges left out of a truncated frame stay pending.
+                    self.spaces[space].pending_acks.subtract(acked);
                 }
                 ack_eliciting_acked |= info.ack_eliciting;
 
@@ -1496,14 +2451,27 @@ impl Connection {
                 // Notify ack frequency that a packet was acked, because it might contain an ACK_FREQUENCY frame
                 self.ack_frequency.on_acked(packet);
 
+                if self.rtt_probe
+                    == Some(RttProbe::Sent {
+                        space,
+                        number: packet,
+                    })
+                {
+                    self.rtt_probe = None;
+                    let rtt = now.saturating_duration_since(info.time_sent);
+                    self.probed_rtt = Some(rtt);
+                }
+
                 self.on_packet_acked(now, info);
             }
         }
 
+        let app_limited = self.is_app_limited();
+        self.path.delivery_rate.on_end_acks(self.path.rtt.min());
         self.path.congestion.on_end_acks(
             now,
             self.path.in_flight.bytes,
-            self.app_limited,
+            app_limited,
             self.spaces[space].largest_acked_packet,
         );
 
@@ -1517,7 +2485,8 @@ impl Connection {
                 )
             };
             let rtt = now.saturating_duration_since(self.spaces[space].largest_acked_packet_sent);
-            self.path.rtt.update(ack_delay, rtt);
+            self.path.rtt.update(now, ack_delay, rtt);
+            self.rtt_histogram.record(rtt);
             if self.path.first_packet_after_rtt_sample.is_none() {
                 self.path.first_packet_after_rtt_sample =
                     Some((space, self.spaces[space].next_packet_number));
@@ -1529,6 +2498,7 @@ impl Connection {
 
         if self.peer_completed_address_validation() {
             self.pto_count = 0;
+            self.first_pto = None;
         }
 
         // Explicit congestion notification
@@ -1549,11 +2519,12 @@ impl Connection {
             }
         }
 
-        self.set_loss_detection_timer(now);
+        // Deferred until the whole batch of received datagrams is processed
+        self.loss_detection_timer_stale = true;
         Ok(())
     }
 
-    fn detect_spurious_loss(&mut self, ack: &frame::Ack, space: SpaceId) -> bool {
+    fn detect_spurious_loss(&mut self, now: Instant, ack: &frame::Ack, space: SpaceId) -> bool {
         let lost_packets = &mut self.spaces[space].lost_packets;
 
         if lost_packets.is_empty() {
@@ -1567,8 +2538,11 @@ impl Connection {
                 .copied()
                 .collect();
 
+            self.stats.path.spurious_lost_packets += spurious_losses.len() as u64;
             for pn in spurious_losses {
                 lost_packets.remove(&pn);
+                self.loss_detector
+                    .on_spurious_loss(now, space, pn, ack.largest);
             }
         }
 
@@ -1621,6 +2595,13 @@ impl Connection {
     // high-latency handshakes
     fn on_packet_acked(&mut self, now: Instant, info: SentPacket) {
         self.remove_in_flight(&info);
+        if let Some(delivery) = &info.delivery {
+            if info.path_generation == self.path.generation() {
+                self.path
+                    .delivery_rate
+                    .on_ack(now, info.time_sent, info.size, delivery);
+            }
+        }
         if info.ack_eliciting && self.path.challenge.is_none() {
             // Only pass ACKs to the congestion controller if we are not validating the current
             // path, so as to ignore any ACKs from older paths still coming in.
@@ -1628,7 +2609,7 @@ impl Connection {
                 now,
                 info.time_sent,
                 info.size.into(),
-                self.app_limited,
+                self.is_app_limited(),
                 &self.path.rtt,
             );
         }
@@ -1657,8 +2638,33 @@ impl Connection {
                 .expect("update not acknowledged yet")
                 .1
         };
-        self.timers
-            .set(Timer::KeyDiscard, start + self.pto(space) * 3);
+        self.set_coalesced_timer(Timer::KeyDiscard, start + self.pto(space) * 3);
+    }
+
+    /// Account for time spent blocked by congestion control or flow control
+    fn update_blocked_time(&mut self, now: Instant, cwnd_full: bool) {
+        fn update(since: &mut Option<Instant>, blocked: bool, now: Instant, total: &mut Duration) {
+            match (blocked, *since) {
+                (true, None) => *since = Some(now),
+                (false, Some(start)) => {
+                    *total += now.saturating_duration_since(start);
+                    *since = None;
+                }
+                _ => {}
+            }
+        }
+        update(
+            &mut self.congestion_blocked_since,
+            cwnd_full,
+            now,
+            &mut self.stats.path.congestion_blocked_time,
+        );
+        update(
+            &mut self.flow_control_blocked_since,
+            self.streams.flow_control_blocked(),
+            now,
+            &mut self.stats.flow_control_blocked_time,
+        );
     }
 
     fn on_loss_detection_timeout(&mut self, now: Instant) {
@@ -1692,6 +2698,30 @@ impl Connection {
         };
         self.spaces[space].loss_probes = self.spaces[space].loss_probes.saturating_add(count);
         self.pto_count = self.pto_count.saturating_add(1);
+        self.stats.path.pto_count += 1;
+        let retransmitting = now.saturating_duration_since(*self.first_pto.get_or_insert(now));
+        if self
+            .config
+            .max_pto_count
+            .is_some_and(|max| self.pto_count >= max.get())
+            || self
+                .config
+                .max_retransmission_time
+                .is_some_and(|max| retransmitting >= max)
+        {
+            debug!(
+                count = self.pto_count,
+                ?retransmitting,
+                "abandoning unresponsive connection"
+            );
+            let cause = TimeoutCause {
+                timer: TimeoutTimer::Retransmission,
+                timeout: retransmitting,
+                ..self.timeout_cause(now)
+            };
+            self.kill(ConnectionError::TimedOut(cause));
+            return;
+        }
         self.set_loss_detection_timer(now);
     }
 
@@ -1703,8 +2733,9 @@ impl Connection {
         let loss_delay = cmp::max(rtt.mul_f32(self.config.time_threshold), TIMER_GRANULARITY);
 
         let largest_acked_packet = self.spaces[pn_space].largest_acked_packet.unwrap();
-        let packet_threshold = self.config.packet_threshold as u64;
         let mut size_of_lost_packets = 0u64;
+        let mut loss_reports = Vec::<(LossTrigger, Vec<u64>, u64)>::new();
+        let after_pto = self.pto_count > 0;
 
         // InPersistentCongestion: Determine if all packets in the time period before the newest
         // lost packet, including the edges, are marked lost. PTO computation must always
@@ -1725,11 +2756,19 @@ impl Connection {
                 persistent_congestion_start = None;
             }
 
-            // Packets sent before now - loss_delay are deemed lost.
-            // However, we avoid this subtraction as it can panic and there's no
-            // saturating equivalent of this substraction operation with a Duration.
-            let packet_too_old = now.saturating_duration_since(info.time_sent) >= loss_delay;
-            if packet_too_old || largest_acked_packet >= packet + packet_threshold {
+            let verdict = self.loss_detector.check(
+                now,
+                &self.path.rtt,
+                largest_acked_packet,
+                &UnackedPacket {
+                    space: pn_space,
+                    number: packet,
+                    time_sent: info.time_sent,
+                    size: info.size,
+                    ack_eliciting: info.ack_eliciting,
+                },
+            );
+            if let LossVerdict::Lost(trigger) = verdict {
                 if Some(packet) == in_flight_mtu_probe {
                     // Lost MTU probes are not included in `lost_packets`, because they should not
                     // trigger a congestion control response
@@ -1737,6 +2776,17 @@ impl Connection {
                 } else {
                     lost_packets.push(packet);
                     size_of_lost_packets += info.size as u64;
+                    let trigger = match after_pto {
+                        true => LossTrigger::ProbeTimeout,
+                        false => trigger,
+                    };
+                    match loss_reports.iter_mut().find(|(t, ..)| *t == trigger) {
+                        Some((_, packets, bytes)) => {
+                            packets.push(packet);
+                            *bytes += info.size as u64;
+                        }
+                        None => loss_reports.push((trigger, vec![packet], info.size as u64)),
+                    }
                     if info.ack_eliciting && due_to_ack {
                         match persistent_congestion_start {
                             // Two ACK-eliciting packets lost more than congestion_period apart, with no
@@ -1757,12 +2807,16 @@ impl Connection {
                     }
                 }
             } else {
-                let next_loss_time = info.time_sent + loss_delay;
-                space.loss_time = Some(
-                    space
-                        .loss_time
-                        .map_or(next_loss_time, |x| cmp::min(x, next_loss_time)),
-                );
+                if let LossVerdict::Pending {
+                    deadline: Some(next_loss_time),
+                } = verdict
+                {
+                    space.loss_time = Some(
+                        space
+                            .loss_time
+                            .map_or(next_loss_time, |x| cmp::min(x, next_loss_time)),
+                    );
+                }
                 persistent_congestion_start = None;
             }
 
@@ -1781,6 +2835,14 @@ impl Connection {
                 "packets lost: {:?}, bytes lost: {}",
                 lost_packets, size_of_lost_packets
             );
+            for (trigger, packets, bytes) in loss_reports {
+                self.transport_event(TransportEvent::PacketsLost {
+                    space: pn_space,
+                    trigger,
+                    packets,
+                    bytes,
+                });
+            }
 
             for &packet in &lost_packets {
                 let info = self.spaces[pn_space].take(packet).unwrap(); // safe: lost_packets is populated just above
@@ -1791,11 +2853,21 @@ impl Connection {
                     pn_space,
                     now,
                     self.orig_rem_cid,
+                    self.trace_context.as_deref(),
                 );
 
                 self.remove_in_flight(&info);
+                if self.rtt_probe
+                    == Some(RttProbe::Sent {
+                        space: pn_space,
+                        number: packet,
+                    })
+                {
+                    self.queue_rtt_probe();
+                }
                 for frame in info.stream_frames {
-                    self.streams.retransmit(frame);
+                    self.streams
+                        .retransmit(frame, self.config.retransmit_at_stream_priority);
                 }
                 self.spaces[pn_space].pending |= info.retransmits;
                 self.path.mtud.on_non_probe_lost(packet, info.size);
@@ -1808,19 +2880,14 @@ impl Connection {
                 );
             }
 
+            let previous_mtu = self.path.mtud.current_mtu();
             if self.path.mtud.black_hole_detected(now) {
                 self.stats.path.black_holes_detected += 1;
-                self.path
-                    .congestion
-                    .on_mtu_update(self.path.mtud.current_mtu());
-                if let Some(max_datagram_size) = self.datagrams().max_size() {
-                    if self.datagrams.drop_oversized(max_datagram_size)
-                        && self.datagrams.send_blocked
-                    {
-                        self.datagrams.send_blocked = false;
-                        self.events.push_back(Event::DatagramsUnblocked);
-                    }
-                }
+                self.transport_event(TransportEvent::MtuBlackHoleDetected {
+                    previous_mtu,
+                    mtu: self.path.mtud.current_mtu(),
+                });
+                self.on_mtu_lowered();
             }
 
             // Don't apply congestion penalty for lost ack-only packets
@@ -1847,6 +2914,19 @@ impl Connection {
         }
     }
 
+    /// Adapt to a reduction of the current MTU
+    fn on_mtu_lowered(&mut self) {
+        self.path
+            .congestion
+            .on_mtu_update(self.path.mtud.current_mtu());
+        if let Some(max_datagram_size) = self.datagrams().max_size() {
+            if self.datagrams.drop_oversized(max_datagram_size) && self.datagrams.send_blocked {
+                self.datagrams.send_blocked = false;
+                self.events.push_back(Event::DatagramsUnblocked);
+            }
+        }
+    }
+
     fn loss_time_and_space(&self) -> Option<(Instant, SpaceId)> {
         SpaceId::iter()
             .filter_map(|id| Some((self.spaces[id].loss_time?, id)))
@@ -1854,8 +2934,12 @@ impl Connection {
     }
 
     fn pto_time_and_space(&self, now: Instant) -> Option<(Instant, SpaceId)> {
-        let backoff = 2u32.pow(self.pto_count.min(MAX_BACKOFF_EXPONENT));
-        let mut duration = self.path.rtt.pto_base() * backoff;
+        let backoff = self
+            .config
+            .pto_backoff_base
+            .powi(self.pto_count.min(i32::MAX as u32) as i32)
+            .min(self.config.max_pto_backoff as f32);
+        let mut duration = self.path.rtt.pto_base().mul_f32(backoff);
 
         if self.path.in_flight.ack_eliciting == 0 {
             debug_assert!(!self.peer_completed_address_validation());
@@ -1877,7 +2961,7 @@ impl Connection {
                     return result;
                 }
                 // Include max_ack_delay and backoff for ApplicationData.
-                duration += self.ack_frequency.max_ack_delay_for_pto() * backoff;
+                duration += self.ack_frequency.max_ack_delay_for_pto().mul_f32(backoff);
             }
             let Some(last_ack_eliciting) = self.spaces[space].time_of_last_ack_eliciting_packet
             else {
@@ -1960,6 +3044,11 @@ impl Connection {
         is_1rtt: bool,
     ) {
         self.total_authed_packets += 1;
+        self.last_received = Some(now);
+        self.stats.handshake.on_received(now, space_id, is_1rtt);
+        if let Some(discovery) = &mut self.keep_alive_discovery {
+            discovery.on_packet_received(self.idle_timeout.map(|x| x / 2));
+        }
         self.reset_keep_alive(now);
         self.reset_idle_timeout(now, space_id);
         self.permit_idle_reset = true;
@@ -1987,7 +3076,12 @@ impl Connection {
             }
         }
         let space = &mut self.spaces[space_id];
-        space.pending_acks.insert_one(packet, now);
+        if space
+            .pending_acks
+            .insert_one(packet, now, self.config.max_ack_ranges)
+        {
+            self.stats.ack_ranges_evicted += 1;
+        }
         if packet >= space.rx_packet {
             space.rx_packet = packet;
             // Update outgoing spin bit, inverting iff we're the client
@@ -2000,11 +3094,13 @@ impl Connection {
             !is_1rtt,
             now,
             self.orig_rem_cid,
+            self.trace_context.as_deref(),
         );
     }
 
     fn reset_idle_timeout(&mut self, now: Instant, space: SpaceId) {
         let Some(timeout) = self.idle_timeout else {
+            self.timers.stop(Timer::Idle);
             return;
         };
         if self.state.is_closed() {
@@ -2012,20 +3108,78 @@ impl Connection {
             return;
         }
         let dt = cmp::max(timeout, 3 * self.pto(space));
-        self.timers.set(Timer::Idle, now + dt);
+        self.set_coalesced_timer(Timer::Idle, now + dt);
+    }
+
+    /// Describe the circumstances of the idle timer expiring at `now`
+    fn timeout_cause(&self, now: Instant) -> TimeoutCause {
+        TimeoutCause {
+            timer: match self.state.is_handshake() {
+                true => TimeoutTimer::Handshake,
+                false => TimeoutTimer::Idle,
+            },
+            timeout: self.idle_timeout.unwrap_or_default(),
+            since_last_sent: self.last_sent.map(|t| now.saturating_duration_since(t)),
+            since_last_received: self.last_received.map(|t| now.saturating_duration_since(t)),
+            keep_alive: self.keep_alive_interval().is_some()
+                && !self.state.is_handshake()
+                && self.keep_alive_needed(),
+        }
+    }
+
+    /// Period of inactivity before sending a keep-alive packet, if enabled
+    ///
+    /// Reflects the progress of discovery if [`TransportConfig::adaptive_keep_alive`] is set.
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        match &self.keep_alive_discovery {
+            Some(discovery) => Some(discovery.interval()),
+            None => self.config.keep_alive_interval,
+        }
+    }
+
+    /// Whether keep-alives should be sent, see [`TransportConfig::keep_alive_while_active`]
+    fn keep_alive_needed(&self) -> bool {
+        !self.config.keep_alive_while_active
+            || self.streams.has_open_streams()
+            || !self.datagrams.outgoing.is_empty()
+            || self.path.in_flight.ack_eliciting > 0
     }
 
     fn reset_keep_alive(&mut self, now: Instant) {
-        let interval = match self.config.keep_alive_interval {
+        let interval = match self.keep_alive_interval() {
             Some(x) if self.state.is_established() => x,
             _ => return,
         };
-        self.timers.set(Timer::KeepAlive, now + interval);
+        self.set_coalesced_timer(Timer::KeepAlive, now + interval);
+    }
+
+    /// The peer observed a new address for this endpoint, or vice versa
+    fn on_rebind(&mut self, now: Instant) {
+        let Some(interval) = self
+            .keep_alive_discovery
+            .as_mut()
+            .and_then(|x| x.on_rebind(now))
+        else {
+            return;
+        };
+        self.transport_event(TransportEvent::KeepAliveSettled { interval });
+        self.reset_keep_alive(now);
+    }
+
+    /// Set a timer that may expire later than `time`, to batch it with other connections' timers
+    ///
+    /// See [`TransportConfig::timer_coalescing`].
+    fn set_coalesced_timer(&mut self, timer: Timer, time: Instant) {
+        let time = match self.config.timer_coalescing {
+            Some(granularity) => timer::round_up(time, self.timer_epoch, granularity),
+            None => time,
+        };
+        self.timers.set(timer, time);
     }
 
     fn reset_cid_retirement(&mut self) {
         if let Some(t) = self.local_cid_state.next_timeout() {
-            self.timers.set(Timer::PushNewCid, t);
+            self.set_coalesced_timer(Timer::PushNewCid, t);
         }
     }
 
@@ -2074,6 +3228,7 @@ impl Connection {
             &mut self.path,
             now,
             self.orig_rem_cid,
+            self.trace_context.as_deref(),
         );
 
         Ok(())
@@ -2165,17 +3320,17 @@ impl Connection {
         Ok(())
     }
 
-    fn write_crypto(&mut self) {
+    fn write_crypto(&mut self, now: Instant) {
         loop {
             let space = self.highest_space;
             let mut outgoing = Vec::new();
             if let Some(crypto) = self.crypto.write_handshake(&mut outgoing) {
                 match space {
                     SpaceId::Initial => {
-                        self.upgrade_crypto(SpaceId::Handshake, crypto);
+                        self.upgrade_crypto(now, SpaceId::Handshake, crypto);
                     }
                     SpaceId::Handshake => {
-                        self.upgrade_crypto(SpaceId::Data, crypto);
+                        self.upgrade_crypto(now, SpaceId::Data, crypto);
                     }
                     _ => unreachable!("got updated secrets during 1-RTT"),
                 }
@@ -2205,12 +3360,15 @@ impl Connection {
     }
 
     /// Switch to stronger cryptography during handshake
-    fn upgrade_crypto(&mut self, space: SpaceId, crypto: Keys) {
+    fn upgrade_crypto(&mut self, now: Instant, space: SpaceId, crypto: Keys) {
         debug_assert!(
             self.spaces[space].crypto.is_none(),
             "already reached packet space {space:?}"
         );
         trace!("{:?} keys ready", space);
+        if space == SpaceId::Handshake {
+            self.stats.handshake.handshake_keys = Some(now);
+        }
         if space == SpaceId::Data {
             // Precompute the first key update
             self.next_crypto = Some(
@@ -2246,6 +3404,9 @@ impl Connection {
         for packet in sent_packets.into_values() {
             self.remove_in_flight(&packet);
         }
+        if matches!(self.rtt_probe, Some(RttProbe::Sent { space, .. }) if space == space_id) {
+            self.queue_rtt_probe();
+        }
         self.set_loss_detection_timer(now)
     }
 
@@ -2284,6 +3445,36 @@ impl Connection {
         ecn: Option<EcnCodepoint>,
         partial_decode: PartialDecode,
     ) {
+        if self.must_defer(&partial_decode) {
+            let packet = DeferredPacket {
+                remote,
+                ecn,
+                decode: partial_decode,
+            };
+            if self.deferred_packets.push(
+                packet,
+                self.config.max_deferred_packets,
+                self.config.max_deferred_bytes,
+            ) {
+                trace!("deferring packet until the handshake progresses");
+                self.stats.deferred_packets += 1;
+            } else {
+                debug!("dropping packet exceeding the deferred packet limit");
+                self.stats.deferred_packets_dropped += 1;
+            }
+            return;
+        }
+        self.process_decode(now, remote, ecn, partial_decode);
+    }
+
+    fn process_decode(
+        &mut self,
+        now: Instant,
+        remote: SocketAddr,
+        ecn: Option<EcnCodepoint>,
+        partial_decode: PartialDecode,
+    ) {
+        let progress = (self.highest_space, self.state.is_handshake());
         if let Some(decoded) = packet_crypto::unprotect_header(
             partial_decode,
             &self.spaces,
@@ -2292,6 +3483,40 @@ impl Connection {
         ) {
             self.handle_packet(now, remote, ecn, decoded.packet, decoded.stateless_reset);
         }
+        if !self.deferred_packets.is_empty()
+            && (self.highest_space, self.state.is_handshake()) != progress
+        {
+            self.process_deferred_packets(now);
+        }
+    }
+
+    /// Whether a packet can't be processed yet, but might be once the handshake progresses
+    ///
+    /// Reordering may deliver Handshake packets before the Initial packet carrying the keys to
+    /// decrypt them, and 1-RTT packets, e.g. 0.5-RTT data from the server or data the client sent
+    /// right after its Finished message, before the handshake completes.
+    fn must_defer(&self, partial_decode: &PartialDecode) -> bool {
+        if !self.state.is_handshake() || partial_decode.is_0rtt() {
+            return false;
+        }
+        match partial_decode.space() {
+            Some(SpaceId::Handshake) => self.spaces[SpaceId::Handshake].crypto.is_none(),
+            Some(SpaceId::Data) => true,
+            _ => false,
+        }
+    }
+
+    /// Process deferred packets that can be handled now, deferring the rest again
+    fn process_deferred_packets(&mut self, now: Instant) {
+        for packet in self.deferred_packets.take() {
+            if self.must_defer(&packet.decode) {
+                // Can't exceed the limits, as at most the removed packets are restored
+                self.deferred_packets.push(packet, usize::MAX, u64::MAX);
+                continue;
+            }
+            trace!("processing deferred packet");
+            self.process_decode(now, packet.remote, packet.ecn, packet.decode);
+        }
     }
 
     fn handle_packet(
@@ -2320,6 +3545,10 @@ impl Connection {
 
         let was_closed = self.state.is_closed();
         let was_drained = self.state.is_drained();
+        #[cfg(feature = "packet-trace")]
+        let size = packet
+            .as_ref()
+            .map_or(0, |x| x.payload.len() + x.header_data.len());
 
         let decrypted = match packet {
             None => Err(None),
@@ -2364,7 +3593,7 @@ impl Connection {
                     debug!("discarding possible duplicate packet");
                     return;
                 } else if self.state.is_handshake() && packet.header.is_short() {
-                    // TODO: SHOULD buffer these to improve reordering tolerance.
+                    // Deferred by `handle_decode`, so this should be unreachable
                     trace!("dropping short packet during handshake");
                     return;
                 } else {
@@ -2380,6 +3609,10 @@ impl Connection {
                         }
                     }
 
+                    if self.state.is_closed() && !packet.header.is_1rtt() {
+                        // The peer may not be able to decrypt 1-RTT packets yet
+                        self.close_1rtt_only = false;
+                    }
                     if !self.state.is_closed() {
                         let spin = match packet.header {
                             Header::Short { spin, .. } => spin,
@@ -2395,7 +3628,19 @@ impl Connection {
                         );
                     }
 
-                    self.process_decrypted_packet(now, remote, number, packet)
+                    #[cfg(feature = "packet-trace")]
+                    let trace = packet_trace::PacketTrace {
+                        space: packet.header.space(),
+                        number,
+                        size,
+                        ecn,
+                        remote,
+                        frames_before: self.stats.frame_rx,
+                    };
+                    let result = self.process_decrypted_packet(now, remote, number, packet);
+                    #[cfg(feature = "packet-trace")]
+                    trace.received(&self.stats.frame_rx);
+                    result
                 }
             }
         };
@@ -2411,7 +3656,7 @@ impl Connection {
                     code: TransportErrorCode::AEAD_LIMIT_REACHED,
                     ..
                 }) => State::Drained,
-                ConnectionError::TimedOut => {
+                ConnectionError::TimedOut(_) => {
                     unreachable!("timeouts aren't generated by packet processing");
                 }
                 ConnectionError::TransportError(err) => {
@@ -2484,6 +3729,13 @@ impl Connection {
                     if let Frame::Close(_) = frame {
                         trace!("draining");
                         self.state = State::Draining;
+                        self.close_answered = true;
+                        self.timers.stop(Timer::CloseRetransmit);
+                        // `close_and_await_peer` may have extended the closing period
+                        let drain_end = now + 3 * self.pto(self.highest_space);
+                        if self.timers.get(Timer::Close).is_some_and(|x| x > drain_end) {
+                            self.timers.set(Timer::Close, drain_end);
+                        }
                         break;
                     }
                 }
@@ -2631,7 +3883,10 @@ impl Connection {
                     self.spaces[SpaceId::Data].pending.handshake_done = true;
                     self.discard_space(now, SpaceId::Handshake);
                     self.events.push_back(Event::HandshakeConfirmed);
+                    self.transport_event(TransportEvent::HandshakeConfirmed);
+                    self.stats.handshake.confirmed = Some(now);
                     trace!("handshake confirmed");
+                    self.apply_resumption_hint(now);
                 }
 
                 self.events.push_back(Event::Connected);
@@ -2758,7 +4013,7 @@ impl Connection {
                 .set_immediate_ack_required();
         }
 
-        self.write_crypto();
+        self.write_crypto(now);
         Ok(())
     }
 
@@ -2774,12 +4029,15 @@ impl Connection {
         let mut close = None;
         let payload_len = payload.len();
         let mut ack_eliciting = false;
+        let mut frames = 0;
         for result in frame::Iter::new(payload)? {
             let frame = result?;
             let span = match frame {
                 Frame::Padding => continue,
                 _ => Some(trace_span!("frame", ty = %frame.ty())),
             };
+            frames += 1;
+            self.peer_limits.on_frame(frames)?;
 
             self.stats.frame_rx.record(&frame);
             // Crypto, Stream and Datagram frames are special cased in order no pollute
@@ -2790,9 +4048,11 @@ impl Connection {
                 }
                 Frame::Stream(f) => {
                     trace!(id = %f.id, offset = f.offset, len = f.data.len(), fin = f.fin, "got stream frame");
+                    self.stats.udp_rx.payload_bytes += f.data.len() as u64;
                 }
                 Frame::Datagram(f) => {
                     trace!(len = f.data.len(), "got datagram frame");
+                    self.stats.udp_rx.payload_bytes += f.data.len() as u64;
                 }
                 f => {
                     trace!("got frame {:?}", f);
@@ -2840,6 +4100,9 @@ impl Connection {
                 }
                 Frame::PathChallenge(token) => {
                     self.path_responses.push(number, token, remote);
+                    // The peer is validating a new address of ours, so a NAT binding may have
+                    // expired
+                    self.on_rebind(now);
                     if remote == self.path.remote {
                         // PATH_CHALLENGE on active path, possible off-path packet forwarding
                         // attack. Send a non-probing packet to recover the active path.
@@ -2850,7 +4113,22 @@ impl Connection {
                     }
                 }
                 Frame::PathResponse(token) => {
-                    if self.path.challenge == Some(token) && remote == self.path.remote {
+                    if let Some(i) = self
+                        .path_probes
+                        .iter()
+                        .position(|x| x.token == token && x.remote == remote)
+                    {
+                        trace!(%remote, "path validated on request");
+                        self.path_probes.swap_remove(i);
+                        self.set_path_probe_timer();
+                        if remote == self.path.remote {
+                            self.path.validated = true;
+                        }
+                        self.events.push_back(Event::PathValidation {
+                            remote,
+                            validated: true,
+                        });
+                    } else if self.path.challenge == Some(token) && remote == self.path.remote {
                         trace!("new path validated");
                         self.timers.stop(Timer::PathValidation);
                         self.path.challenge = None;
@@ -2873,9 +4151,13 @@ impl Connection {
                     self.streams.received_max_streams(dir, count)?;
                 }
                 Frame::ResetStream(frame) => {
+                    let resets = self.streams.peer_resets();
                     if self.streams.received_reset(frame)?.should_transmit() {
                         self.spaces[SpaceId::Data].pending.max_data = true;
                     }
+                    if self.streams.peer_resets() > resets {
+                        self.peer_limits.on_stream_reset(now)?;
+                    }
                 }
                 Frame::DataBlocked { offset } => {
                     debug!(offset, "peer claims to be blocked at connection level");
@@ -2916,12 +4198,22 @@ impl Connection {
                             "STOP_SENDING on unopened stream",
                         ));
                     }
+                    let resets = self.streams.peer_resets();
                     self.streams.received_stop_sending(id, error_code);
+                    if self.streams.peer_resets() > resets {
+                        self.peer_limits.on_stream_reset(now)?;
+                    }
                 }
                 Frame::RetireConnectionId { sequence } => {
-                    let allow_more_cids = self
-                        .local_cid_state
-                        .on_cid_retirement(sequence, self.peer_params.issue_cids_limit())?;
+                    let retired = self.local_cid_state.retired();
+                    let allow_more_cids = self.local_cid_state.on_cid_retirement(
+                        sequence,
+                        self.peer_params
+                            .issue_cids_limit(self.config.max_issued_cids),
+                    )?;
+                    if self.local_cid_state.retired() > retired {
+                        self.peer_limits.on_cid_retirement(now)?;
+                    }
                     self.endpoint_events
                         .push_back(EndpointEventInner::RetireConnectionId(
                             now,
@@ -2965,8 +4257,12 @@ impl Connection {
                                     "queued too many retired CIDs",
                                 ));
                             }
-                            pending_retired.extend(retired);
+                            pending_retired.extend(retired.clone());
+                            self.transport_event(TransportEvent::PeerCidsRetired {
+                                sequences: retired,
+                            });
                             self.set_reset_token(reset_token);
+                            self.path.flow_label = self.new_flow_label();
                         }
                         Err(InsertError::ExceedsLimit) => {
                             return Err(TransportError::CONNECTION_ID_LIMIT_ERROR(""));
@@ -3006,10 +4302,7 @@ impl Connection {
                     token_store.insert(server_name, token);
                 }
                 Frame::Datagram(datagram) => {
-                    if self
-                        .datagrams
-                        .received(datagram, &self.config.datagram_receive_buffer_size)?
-                    {
+                    if self.datagrams.received(datagram, &self.config)? {
                         self.events.push_back(Event::DatagramReceived);
                     }
                 }
@@ -3050,8 +4343,32 @@ impl Connection {
                         self.discard_space(now, SpaceId::Handshake);
                     }
                     self.events.push_back(Event::HandshakeConfirmed);
+                    self.transport_event(TransportEvent::HandshakeConfirmed);
+                    self.stats.handshake.confirmed = Some(now);
                     trace!("handshake confirmed");
                 }
+                Frame::ObservedAddr(observed) => {
+                    if !self.config.receive_observed_address_reports {
+                        return Err(TransportError::PROTOCOL_VIOLATION(
+                            "unexpected OBSERVED_ADDRESS frame",
+                        ));
+                    }
+                    // Reports may be reordered, only the one with the highest sequence number is
+                    // current
+                    if self
+                        .observed_addr
+                        .is_some_and(|(seq_no, _)| seq_no >= observed.seq_no)
+                    {
+                        continue;
+                    }
+                    let address = observed.address();
+                    let changed = self.observed_addr.is_none_or(|(_, x)| x != address);
+                    self.observed_addr = Some((observed.seq_no, address));
+                    if changed {
+                        trace!(%address, "observed address changed");
+                        self.transport_event(TransportEvent::ObservedAddr { address });
+                    }
+                }
             }
         }
 
@@ -3082,12 +4399,18 @@ impl Connection {
             && number == self.spaces[SpaceId::Data].rx_packet
         {
             let ConnectionSide::Server { ref server_config } = self.side else {
-                panic!("packets from unknown remote should be dropped by clients");
+                // Clients only accept packets from other ports of the server when port hopping,
+                // or from addresses being validated, neither of which moves the path
+                debug_assert!(self.is_hopping_port(remote) || self.is_probed_remote(remote));
+                return Ok(());
             };
-            debug_assert!(
-                server_config.migration,
-                "migration-initiating packets should have been dropped immediately"
-            );
+            if !server_config.migration {
+                debug_assert!(
+                    self.is_probed_remote(remote),
+                    "migration-initiating packets should have been dropped immediately"
+                );
+                return Ok(());
+            }
             self.migrate(now, remote);
             // Break linkability, if possible
             self.update_rem_cid();
@@ -3099,6 +4422,7 @@ impl Connection {
 
     fn migrate(&mut self, now: Instant, remote: SocketAddr) {
         trace!(%remote, "migration initiated");
+        self.on_rebind(now);
         self.path_counter = self.path_counter.wrapping_add(1);
         // Reset rtt/congestion state for new path unless it looks like a NAT rebinding.
         // Note that the congestion window will not grow until validation terminates. Helps mitigate
@@ -3111,15 +4435,19 @@ impl Connection {
                     .unwrap_or(u16::MAX);
             PathData::new(
                 remote,
-                self.allow_mtud,
+                self.mtud_config.as_ref().filter(|_| self.allow_mtud),
                 Some(peer_max_udp_payload_size),
                 self.path_counter,
                 now,
                 &self.config,
             )
         };
+        new_path
+            .pacing
+            .set_max_bytes_per_second(self.path.pacing.max_bytes_per_second());
         new_path.challenge = Some(self.rng.random());
         new_path.challenge_pending = true;
+        new_path.flow_label = self.new_flow_label();
         let prev_pto = self.pto(SpaceId::Data);
 
         let mut prev = mem::replace(&mut self.path, new_path);
@@ -3136,6 +4464,22 @@ impl Connection {
             Timer::PathValidation,
             now + 3 * cmp::max(self.pto(SpaceId::Data), prev_pto),
         );
+        self.transport_event(TransportEvent::PathMigrated { remote });
+        self.queue_observed_addr();
+    }
+
+    /// Report the peer's current address to it, if it asked for such reports
+    fn queue_observed_addr(&mut self) {
+        if !self.config.send_observed_address_reports
+            || !self
+                .peer_params
+                .address_discovery
+                .is_some_and(|x| x.receives())
+        {
+            return;
+        }
+        self.observed_addr_reports += 1;
+        self.spaces[SpaceId::Data].pending.observed_addr = true;
     }
 
     /// Handle a change in the local address, i.e. an active migration
@@ -3154,8 +4498,117 @@ impl Connection {
         self.spaces[SpaceId::Data]
             .pending
             .retire_cids
-            .extend(retired);
+            .extend(retired.clone());
+        self.transport_event(TransportEvent::PeerCidsRetired { sequences: retired });
         self.set_reset_token(reset_token);
+        // Don't let the flow label link the old and new connection IDs
+        self.path.flow_label = self.new_flow_label();
+    }
+
+    /// Switch to a fresh remote CID if required by [`TransportConfig::cid_rotation`]
+    fn maybe_rotate_rem_cid(&mut self, now: Instant) {
+        let Some(config) = &self.config.cid_rotation else {
+            return;
+        };
+        let bytes = self.stats.udp_tx.bytes;
+        if self.rem_cids.active_seq() != self.rem_cid_adopted.sequence {
+            // Replaced for another reason, e.g. migration
+            self.rem_cid_adopted = RemCidAdopted {
+                sequence: self.rem_cids.active_seq(),
+                time: now,
+                bytes,
+            };
+            return;
+        }
+        if !self.state.is_established() {
+            return;
+        }
+        let adopted = &self.rem_cid_adopted;
+        let due = config
+            .interval
+            .is_some_and(|x| now.saturating_duration_since(adopted.time) >= x)
+            || config.bytes.is_some_and(|x| bytes - adopted.bytes >= x);
+        if !due {
+            return;
+        }
+        self.update_rem_cid();
+        if self.rem_cids.active_seq() != self.rem_cid_adopted.sequence {
+            trace!(sequence = self.rem_cids.active_seq(), "rotated remote CID");
+            self.rem_cid_adopted = RemCidAdopted {
+                sequence: self.rem_cids.active_seq(),
+                time: now,
+                bytes,
+            };
+        }
+    }
+
+    /// Move to another remote port if required by [`TransportConfig::port_hopping`]
+    fn maybe_hop_port(&mut self, now: Instant) {
+        let Some(config) = &self.config.port_hopping else {
+            return;
+        };
+        if !self.side.is_client()
+            || !self.state.is_established()
+            || self.local_cid_state.cid_len() == 0
+            // Let the previous hop, or a migration, be validated first
+            || self.path.challenge.is_some()
+            || now.saturating_duration_since(self.port_hopped) < config.interval
+        {
+            return;
+        }
+        if !self.rem_cids.active().is_empty() && self.rem_cids.available().next().is_none() {
+            // Hopping on the current CID would let observers link the two ports
+            trace!("no spare remote CID to hop ports with");
+            return;
+        }
+        self.port_hopped = now;
+        let current = self.path.remote.port();
+        let mut candidates = config.ports.iter().filter(|&&port| port != current);
+        let count = candidates.clone().count();
+        if count == 0 {
+            return;
+        }
+        let port = *candidates.nth(self.rng.random_range(0..count)).unwrap();
+
+        let remote = SocketAddr::new(self.path.remote.ip(), port);
+        trace!(%remote, "hopping to new remote port");
+        self.path_counter = self.path_counter.wrapping_add(1);
+        let mut new_path = PathData::from_previous(remote, &self.path, self.path_counter, now);
+        // Anti-amplification doesn't apply to clients
+        new_path.validated = true;
+        new_path.challenge = Some(self.rng.random());
+        new_path.challenge_pending = true;
+        let prev = mem::replace(&mut self.path, new_path);
+        // Falling back to the previous port doesn't require validating it again
+        self.prev_path = Some((self.rem_cids.active(), prev));
+        self.update_rem_cid();
+        self.timers
+            .set(Timer::PathValidation, now + 3 * self.pto(SpaceId::Data));
+        self.queue_observed_addr();
+    }
+
+    /// Whether `remote` is a port of the server that packets are accepted from when port hopping
+    fn is_hopping_port(&self, remote: SocketAddr) -> bool {
+        let Some(config) = &self.config.port_hopping else {
+            return false;
+        };
+        self.side.is_client()
+            && remote.ip() == self.path.remote.ip()
+            && (remote.port() == self.original_remote_port
+                || config.ports.binary_search(&remote.port()).is_ok())
+    }
+
+    /// Whether an application-requested path validation is waiting for a response from `remote`
+    fn is_probed_remote(&self, remote: SocketAddr) -> bool {
+        self.path_probes.iter().any(|x| x.remote == remote)
+    }
+
+    /// Pick a fresh IPv6 flow label, if enabled
+    fn new_flow_label(&mut self) -> Option<u32> {
+        // Zero means "unlabeled" and is left to the OS
+        self.config
+            .flow_label
+            .then(|| self.rng.random_range(1..=0xf_ffff))
     }
 
     fn set_reset_token(&mut self, reset_token: ResetToken) {
@@ -3174,11 +4627,14 @@ impl Connection {
         }
 
         // Subtract 1 to account for the CID we supplied while handshaking
-        let mut n = self.peer_params.issue_cids_limit() - 1;
+        let mut n = self
+            .peer_params
+            .issue_cids_limit(self.config.max_issued_cids)
+            .saturating_sub(1);
         if let ConnectionSide::Server { server_config } = &self.side {
             if server_config.has_preferred_address() {
                 // We also sent a CID in the transport parameters
-                n -= 1;
+                n = n.saturating_sub(1);
             }
         }
         self.endpoint_events
@@ -3194,6 +4650,7 @@ impl Connection {
         pn: u64,
     ) -> SentFrames {
         let mut sent = SentFrames::default();
+        let frames_start = buf.len();
         let space = &mut self.spaces[space_id];
         let is_0rtt = space_id == SpaceId::Data && space.crypto.is_none();
         space.pending_acks.maybe_ack_non_eliciting();
@@ -3213,6 +4670,12 @@ impl Connection {
             buf.write(frame::FrameType::PING);
             sent.non_retransmits = true;
             self.stats.frame_tx.ping += 1;
+            if self.rtt_probe == Some(RttProbe::Queued) {
+                self.rtt_probe = Some(RttProbe::Sent {
+                    space: space_id,
+                    number: pn,
+                });
+            }
         }
 
         // IMMEDIATE_ACK
@@ -3231,6 +4694,7 @@ impl Connection {
                 &mut sent,
                 space,
                 buf,
+                max_size,
                 &mut self.stats,
             );
         }
@@ -3292,6 +4756,21 @@ impl Connection {
             }
         }
 
+        // OBSERVED_ADDRESS
+        if space_id == SpaceId::Data && !is_0rtt && space.pending.observed_addr {
+            let frame = frame::ObservedAddr::new(
+                VarInt::from_u64(self.observed_addr_reports - 1).unwrap(),
+                self.path.remote,
+            );
+            if buf.len() + frame.size() < max_size {
+                space.pending.observed_addr = false;
+                trace!(seq = %frame.seq_no, address = %frame.address(), "OBSERVED_ADDRESS");
+                frame.encode(buf);
+                sent.retransmits.get_or_create().observed_addr = true;
+                self.stats.frame_tx.observed_addr += 1;
+            }
+        }
+
         // CRYPTO
         while buf.len() + frame::Crypto::SIZE_BOUND < max_size && !is_0rtt {
             let Some(mut frame) = space.pending.crypto.pop_front() else {
@@ -3377,20 +4856,19 @@ impl Connection {
         }
 
         // DATAGRAM
+        // Same as `may_send_app_data()`, which can't be called while `space` is borrowed
+        let send_app_data =
+            space_id == SpaceId::Data && !self.sending_paused && self.deferred_close.is_none();
         let mut sent_datagrams = false;
-        while buf.len() + Datagram::SIZE_BOUND < max_size && space_id == SpaceId::Data {
-            match self.datagrams.write(buf, max_size) {
-                true => {
-                    sent_datagrams = true;
-                    sent.non_retransmits = true;
-                    self.stats.frame_tx.datagram += 1;
-                }
-                false => break,
-            }
-        }
-        if self.datagrams.send_blocked && sent_datagrams {
-            self.events.push_back(Event::DatagramsUnblocked);
-            self.datagrams.send_blocked = false;
+        if send_app_data {
+            sent_datagrams = Self::populate_datagrams(
+                &mut self.datagrams,
+                &self.streams,
+                &mut sent,
+                buf,
+                max_size,
+                &mut self.stats,
+            );
         }
 
         // NEW_TOKEN
@@ -3408,10 +4886,22 @@ impl Connection {
                 continue;
             }
 
+            let hint = match server_config.validation_token.resumption_hints {
+                true => self
+                    .path
+                    .delivery_rate
+                    .estimate()
+                    .map(|estimate| ResumptionHint {
+                        rtt: self.path.rtt.get(),
+                        bandwidth: estimate.bytes_per_second,
+                    }),
+                false => None,
+            };
             let token = Token::new(
                 TokenPayload::Validation {
                     ip: remote_addr.ip(),
                     issued: server_config.time_source.now(),
+                    hint,
                 },
                 &mut self.rng,
             );
@@ -3433,16 +4923,114 @@ impl Connection {
         }
 
         // STREAM
-        if space_id == SpaceId::Data {
+        if send_app_data {
             sent.stream_frames =
                 self.streams
-                    .write_stream_frames(buf, max_size, self.config.send_fairness);
+                    .write_stream_frames(now, buf, max_size, self.config.send_fairness);
             self.stats.frame_tx.stream += sent.stream_frames.len() as u64;
+            self.stats.udp_tx.payload_bytes += sent
+                .stream_frames
+                .iter()
+                .map(|frame| frame.offsets.end - frame.offsets.start)
+                .sum::<u64>();
+
+            // Datagrams waiting for the stream data just written may follow it in this packet
+            sent_datagrams |= Self::populate_datagrams(
+                &mut self.datagrams,
+                &self.streams,
+                &mut sent,
+                buf,
+                max_size,
+                &mut self.stats,
+            );
+        }
+        if self.datagrams.send_blocked && sent_datagrams {
+            self.events.push_back(Event::DatagramsUnblocked);
+            self.datagrams.send_blocked = false;
+        }
+
+        // PADDING, to grease packet sizes
+        let grease = &self.config.grease;
+        if space_id == SpaceId::Data
+            && !is_0rtt
+            && buf.len() > frames_start
+            && !sent.is_ack_only(&self.streams)
+            && grease.max_padding > 0
+            && self.rng.random_bool(grease.padding_frequency)
+        {
+            let len = self.rng.random_range(1..=usize::from(grease.max_padding));
+            let len = len.min(max_size.saturating_sub(buf.len()));
+            trace!("PADDING * {} (grease)", len);
+            buf.resize(buf.len() + len, 0);
         }
 
         sent
     }
 
+    /// Pad the datagram ending with `builder` as required by [`TransportConfig::padding_policy`]
+    ///
+    /// `buf_len` is the length of the buffer so far, and `buf_capacity` the offset the datagram
+    /// must not extend beyond.
+    fn apply_padding_policy(
+        &mut self,
+        builder: &mut PacketBuilder,
+        buf_len: usize,
+        buf_capacity: usize,
+    ) {
+        let len = cmp::max(builder.min_size, buf_len) - builder.datagram_start + builder.tag_len;
+        let capacity = buf_capacity - builder.datagram_start;
+        let target = match &self.config.padding_policy {
+            PaddingPolicy::None => return,
+            PaddingPolicy::Fixed(size) => usize::from(*size),
+            PaddingPolicy::Buckets(sizes) => sizes
+                .iter()
+                .map(|&size| usize::from(size))
+                .filter(|&size| size >= len)
+                .min()
+                .unwrap_or(capacity),
+            PaddingPolicy::Random { max, budget } => {
+                let padding = self.rng.random_range(0..=usize::from(*max));
+                let allowed = (self.stats.udp_tx.bytes as f64 * f64::from(*budget)) as u64;
+                if self.stats.padding_bytes + padding as u64 > allowed {
+                    return;
+                }
+                len + padding
+            }
+        };
+        let target = target.min(capacity);
+        if target <= len {
+            return;
+        }
+        builder.pad_to(target as u16);
+        self.stats.padding_bytes += (target - len) as u64;
+    }
+
+    /// Write queued application datagrams into a buffer
+    ///
+    /// Returns whether any datagrams were written.
+    fn populate_datagrams(
+        datagrams: &mut DatagramState,
+        streams: &StreamsState,
+        sent: &mut SentFrames,
+        buf: &mut Vec<u8>,
+        max_size: usize,
+        stats: &mut ConnectionStats,
+    ) -> bool {
+        let mut sent_datagrams = false;
+        while buf.len() + Datagram::SIZE_BOUND < max_size {
+            match datagrams.write(buf, max_size, streams) {
+                Some(len) => {
+                    sent_datagrams = true;
+                    sent.non_retransmits = true;
+                    stats.frame_tx.datagram += 1;
+                    stats.udp_tx.payload_bytes += len as u64;
+                }
+                None => break,
+            }
+        }
+        sent_datagrams
+    }
+
     /// Write pending ACKs into a buffer
     ///
     /// This method assumes ACKs are pending, and should only be called if
@@ -3453,6 +5041,7 @@ impl Connection {
         sent: &mut SentFrames,
         space: &mut PacketSpace,
         buf: &mut Vec<u8>,
+        max_size: usize,
         stats: &mut ConnectionStats,
     ) {
         debug_assert!(!space.pending_acks.ranges().is_empty());
@@ -3464,7 +5053,7 @@ impl Connection {
         } else {
             None
         };
-        sent.largest_acked = space.pending_acks.ranges().max();
+        let largest = space.pending_acks.ranges().max().unwrap();
 
         let delay_micros = space.pending_acks.ack_delay(now).as_micros() as u64;
 
@@ -3478,7 +5067,14 @@ impl Connection {
             delay_micros
         );
 
-        frame::Ack::encode(delay as _, space.pending_acks.ranges(), ecn, buf);
+        let smallest = frame::Ack::encode(
+            delay as _,
+            space.pending_acks.ranges(),
+            ecn,
+            max_size.saturating_sub(buf.len()),
+            buf,
+        );
+        sent.acked = Some(smallest..largest + 1);
         stats.frame_tx.acks += 1;
     }
 
@@ -3487,6 +5083,7 @@ impl Connection {
         for &timer in &Timer::VALUES {
             self.timers.stop(timer);
         }
+        self.path_probes.clear();
     }
 
     fn set_close_timer(&mut self, now: Instant) {
@@ -3513,9 +5110,13 @@ impl Connection {
 
     fn set_peer_params(&mut self, params: TransportParameters) {
         self.streams.set_params(&params);
-        self.idle_timeout =
+        self.negotiated_idle_timeout =
             negotiate_max_idle_timeout(self.config.max_idle_timeout, Some(params.max_idle_timeout));
-        trace!("negotiated max idle timeout {:?}", self.idle_timeout);
+        trace!(
+            "negotiated max idle timeout {:?}",
+            self.negotiated_idle_timeout
+        );
+        self.update_idle_timeout();
         if let Some(ref info) = params.preferred_address {
             self.rem_cids.insert(NewConnectionId {
                 sequence: 1,
@@ -3529,6 +5130,7 @@ impl Connection {
         self.path.mtud.on_peer_max_udp_payload_size_received(
             u16::try_from(self.peer_params.max_udp_payload_size.into_inner()).unwrap_or(u16::MAX),
         );
+        self.queue_observed_addr();
     }
 
     fn decrypt_packet(
@@ -3593,6 +5195,7 @@ impl Connection {
             update_unacked: remote,
         });
         self.key_phase = !self.key_phase;
+        self.transport_event(TransportEvent::KeysUpdated { remote });
     }
 
     fn peer_supports_ack_frequency(&self) -> bool {
@@ -3713,18 +5316,25 @@ impl Connection {
     ///
     /// See also `self.space(SpaceId::Data).can_send()`
     fn can_send_1rtt(&self, max_size: usize) -> bool {
-        self.streams.can_send_stream_data()
+        (self.may_send_app_data() && self.streams.can_send_stream_data())
             || self.path.challenge_pending
             || self
                 .prev_path
                 .as_ref()
                 .is_some_and(|(_, x)| x.challenge_pending)
             || !self.path_responses.is_empty()
-            || self
-                .datagrams
-                .outgoing
-                .front()
-                .is_some_and(|x| x.size(true) <= max_size)
+            || (self.may_send_app_data()
+                && self.datagrams.outgoing.front().is_some_and(|x| {
+                    x.datagram.size(true) <= max_size && x.is_ready(&self.streams)
+                }))
+    }
+
+    /// Whether STREAM and DATAGRAM frames may be sent
+    ///
+    /// Not while paused by the application, nor while a close is deferred, as application data
+    /// would have to be discarded anyway.
+    fn may_send_app_data(&self) -> bool {
+        !self.sending_paused && self.deferred_close.is_none()
     }
 
     /// Update counters to account for a packet becoming acknowledged, lost, or abandoned
@@ -3755,6 +5365,74 @@ impl Connection {
         self.path.current_mtu()
     }
 
+    /// Replace the MTU discovery configuration of this connection
+    ///
+    /// Applies to the current path immediately, where a search in progress continues with the new
+    /// bounds, and to paths the connection migrates to. `None` disables MTU discovery, retaining
+    /// the current MTU. Has no effect on endpoints that don't allow MTU discovery, e.g. because
+    /// their socket may fragment packets.
+    ///
+    /// See [`TransportConfig::mtu_discovery_config()`].
+    pub fn set_mtu_discovery_config(&mut self, config: Option<MtuDiscoveryConfig>) {
+        Arc::make_mut(&mut self.config).mtu_discovery_config = config.clone();
+        if !self.allow_mtud {
+            return;
+        }
+        let peer_max_udp_payload_size =
+            u16::try_from(self.peer_params.max_udp_payload_size.into_inner()).unwrap_or(u16::MAX);
+        self.path
+            .mtud
+            .set_config(config.clone(), peer_max_udp_payload_size);
+        self.mtud_config = config;
+    }
+
+    /// Attach an application-supplied trace context, such as a distributed tracing ID
+    ///
+    /// qlog events of this connection are tagged with the context in a `trace_context` field, to
+    /// correlate them with the application's own records. Replaces any previous context.
+    pub fn set_trace_context(&mut self, context: Option<String>) {
+        self.trace_context = context;
+    }
+
+    /// The trace context attached with [`set_trace_context()`](Self::set_trace_context)
+    pub fn trace_context(&self) -> Option<&str> {
+        self.trace_context.as_deref()
+    }
+
+    /// Lower the idle timeout of this connection, or restore the negotiated one with `None`
+    ///
+    /// Useful to apply a short timeout to connections until they are authenticated, for example.
+    /// The timeout can't exceed the one negotiated during the handshake, since the peer would
+    /// close the connection after that anyway; longer values are truncated. Takes effect
+    /// immediately, counting from `now`.
+    ///
+    /// See [`TransportConfig::max_idle_timeout()`].
+    pub fn set_idle_timeout(&mut self, now: Instant, timeout: Option<Duration>) {
+        self.local_idle_timeout = timeout;
+        self.update_idle_timeout();
+        self.reset_idle_timeout(now, self.highest_space);
+    }
+
+    /// The effective idle timeout, if any
+    ///
+    /// Reflects [`TransportConfig::max_idle_timeout()`] until the timeout is negotiated during the
+    /// handshake.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    fn update_idle_timeout(&mut self) {
+        self.idle_timeout = match (self.negotiated_idle_timeout, self.local_idle_timeout) {
+            (Some(negotiated), Some(local)) => Some(negotiated.min(local)),
+            (negotiated, local) => negotiated.or(local),
+        };
+    }
+
+    /// The most recent MTU probes sent on the current path, oldest first
+    pub fn mtu_probes(&self) -> impl ExactSizeIterator<Item = MtuProbe> + '_ {
+        self.path.mtud.probes()
+    }
+
     /// Size of non-frame data for a 1-RTT packet
     ///
     /// Quantifies space consumed by the QUIC header and AEAD tag. All other bytes in a packet are
@@ -3790,6 +5468,10 @@ impl Connection {
     /// Mark the path as validated, and enqueue NEW_TOKEN frames to be sent as appropriate
     fn on_path_validated(&mut self) {
         self.path.validated = true;
+        self.queue_new_tokens();
+    }
+
+    fn queue_new_tokens(&mut self) {
         let ConnectionSide::Server { server_config } = &self.side else {
             return;
         };
@@ -3799,6 +5481,29 @@ impl Connection {
             new_tokens.push(self.path.remote);
         }
     }
+
+    /// Restore the congestion window of a previous connection, if its path looks the same
+    ///
+    /// Follows the reconnaissance phase of draft-ietf-tsvwg-careful-resume: the hint is only used
+    /// if the RTT measured during the handshake is comparable to the one it was taken at. The
+    /// congestion controller is responsible for validating the resulting window.
+    fn apply_resumption_hint(&mut self, now: Instant) {
+        let Some(hint) = self.resumption_hint.take() else {
+            return;
+        };
+        let rtt = self.path.rtt.get();
+        if rtt < hint.rtt / 2 || rtt > hint.rtt * 10 {
+            debug!(?rtt, hint = ?hint.rtt, "ignoring resumption hint for a different path");
+            return;
+        }
+        // Jump to half of the previous bandwidth-delay product
+        let window = (hint.bandwidth as u128 * hint.rtt.as_micros() / 2_000_000) as u64;
+        if window <= self.path.congestion.window() {
+            return;
+        }
+        trace!(window, "resuming congestion window");
+        self.path.congestion.on_resume_hint(now, window);
+    }
 }
 
 impl fmt::Debug for Connection {
@@ -3861,6 +5566,7 @@ impl From<SideArgs> for ConnectionSide {
                 server_config,
                 pref_addr_cid: _,
                 path_validated: _,
+                resumption_hint: _,
             } => Self::Server { server_config },
         }
     }
@@ -3876,6 +5582,7 @@ pub(crate) enum SideArgs {
         server_config: Arc<ServerConfig>,
         pref_addr_cid: Option<ConnectionId>,
         path_validated: bool,
+        resumption_hint: Option<ResumptionHint>,
     },
 }
 
@@ -3894,6 +5601,15 @@ impl SideArgs {
         }
     }
 
+    pub(crate) fn resumption_hint(&self) -> Option<ResumptionHint> {
+        match *self {
+            Self::Client { .. } => None,
+            Self::Server {
+                resumption_hint, ..
+            } => resumption_hint,
+        }
+    }
+
     pub(crate) fn side(&self) -> Side {
         match *self {
             Self::Client { .. } => Side::Client,
@@ -3902,6 +5618,22 @@ impl SideArgs {
     }
 }
 
+/// Fields of [`TransportConfig`] that [`Connection::tune()`] may change
+const RUNTIME_MUTABLE_FIELDS: &[&str] = &[
+    "max_outgoing_bytes_per_second",
+    "ack_policy_config",
+    "keep_alive_interval",
+    "keep_alive_while_active",
+    "adaptive_keep_alive",
+    "mtu_discovery_config",
+    "send_window",
+    "receive_window",
+    "max_concurrent_bidi_streams",
+    "max_concurrent_uni_streams",
+    "dscp",
+    "transmit_marker",
+];
+
 /// Reasons why a connection might be lost
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum ConnectionError {
@@ -3925,8 +5657,8 @@ pub enum ConnectionError {
     /// If neither side is sending keep-alives, a connection will time out after a long enough idle
     /// period even if the peer is still reachable. See also [`TransportConfig::max_idle_timeout()`]
     /// and [`TransportConfig::keep_alive_interval()`].
-    #[error("timed out")]
-    TimedOut,
+    #[error("timed out: {0}")]
+    TimedOut(TimeoutCause),
     /// The local application closed the connection
     #[error("closed")]
     LocallyClosed,
@@ -3937,6 +5669,80 @@ pub enum ConnectionError {
     CidsExhausted,
 }
 
+/// Circumstances of a [`ConnectionError::TimedOut`]
+///
+/// Durations are measured from the moment the timer fired. Connections that are draining after
+/// being closed end silently once the draining period elapses, so never report a timeout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TimeoutCause {
+    /// Which timer fired
+    pub timer: TimeoutTimer,
+    /// The idle timeout in effect, which may be the locally configured one if the handshake
+    /// didn't complete
+    ///
+    /// The timer fires after at least three probe timeouts, so may exceed this on slow paths. For
+    /// [`TimeoutTimer::Retransmission`], the time since the first of the consecutive probe
+    /// timeouts instead.
+    pub timeout: Duration,
+    /// Time elapsed since a packet was last sent, if any was
+    pub since_last_sent: Option<Duration>,
+    /// Time elapsed since an authenticated packet was last received, if any was
+    pub since_last_received: Option<Duration>,
+    /// Whether keep-alive packets were being sent to prevent the connection from idling
+    pub keep_alive: bool,
+}
+
+impl fmt::Display for TimeoutCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} timeout of {:?}", self.timer, self.timeout)?;
+        match self.since_last_received {
+            Some(x) => write!(f, ", last received {x:?} ago")?,
+            None => f.write_str(", nothing received")?,
+        }
+        match self.since_last_sent {
+            Some(x) => write!(f, ", last sent {x:?} ago")?,
+            None => f.write_str(", nothing sent")?,
+        }
+        if self.keep_alive {
+            f.write_str(", sending keep-alives")?;
+        }
+        Ok(())
+    }
+}
+
+/// The remote CID in use and the circumstances of its adoption
+#[derive(Debug, Copy, Clone)]
+struct RemCidAdopted {
+    sequence: u64,
+    time: Instant,
+    /// UDP payload bytes sent before adoption
+    bytes: u64,
+}
+
+/// Timer whose expiry caused a [`ConnectionError::TimedOut`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimeoutTimer {
+    /// The handshake didn't complete in time
+    Handshake,
+    /// The established connection saw no activity for the idle timeout
+    Idle,
+    /// The peer didn't acknowledge anything within the limits of
+    /// [`TransportConfig::max_pto_count()`] or [`TransportConfig::max_retransmission_time()`]
+    Retransmission,
+}
+
+impl fmt::Display for TimeoutTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Handshake => "handshake",
+            Self::Idle => "idle",
+            Self::Retransmission => "retransmission",
+        })
+    }
+}
+
 impl From<Close> for ConnectionError {
     fn from(x: Close) -> Self {
         match x {
@@ -3951,7 +5757,7 @@ impl From<ConnectionError> for io::Error {
     fn from(x: ConnectionError) -> Self {
         use ConnectionError::*;
         let kind = match x {
-            TimedOut => io::ErrorKind::TimedOut,
+            TimedOut(_) => io::ErrorKind::TimedOut,
             Reset => io::ErrorKind::ConnectionReset,
             ApplicationClosed(_) | ConnectionClosed(_) => io::ErrorKind::ConnectionAborted,
             TransportError(_) | VersionMismatch | LocallyClosed | CidsExhausted => {
@@ -3995,6 +5801,16 @@ impl State {
     fn is_drained(&self) -> bool {
         matches!(*self, Self::Drained)
     }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Self::Handshake(_) => "handshake",
+            Self::Established => "established",
+            Self::Closed(_) => "closed",
+            Self::Draining => "draining",
+            Self::Drained => "drained",
+        }
+    }
 }
 
 mod state {
@@ -4049,15 +5865,169 @@ pub enum Event {
     DatagramReceived,
     /// One or more application datagrams have been sent after blocking
     DatagramsUnblocked,
+    /// A path validation requested through [`Connection::validate_path_to()`] finished
+    PathValidation {
+        /// The remote address that was validated
+        remote: SocketAddr,
+        /// Whether the peer responded in time
+        validated: bool,
+    },
+}
+
+/// Error returned by [`Connection::validate_path_to()`]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum ValidatePathError {
+    /// The peer hasn't issued a connection ID that isn't in use yet
+    ///
+    /// Probing another address with the connection ID of the current path would let observers
+    /// link the two paths, see RFC 9000 §9.5.
+    #[error("no spare connection ID to probe the address with")]
+    NoConnectionId,
+}
+
+/// A path validation requested through [`Connection::validate_path_to`]
+#[derive(Debug, Copy, Clone)]
+struct PathProbe {
+    remote: SocketAddr,
+    /// Remote CID reserved for challenges to an address other than the current one
+    cid: Option<ConnectionId>,
+    token: u64,
+    /// When to give up, reporting the path as unreachable
+    deadline: Instant,
+    /// When to repeat the PATH_CHALLENGE, or `None` if it is waiting to be sent
+    retry_at: Option<Instant>,
+}
+
+/// State of an RTT measurement requested through [`Connection::probe_rtt`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RttProbe {
+    /// Waiting for a packet to carry the probe
+    Queued,
+    /// Carried by the packet `number` in `space`
+    Sent { space: SpaceId, number: u64 },
+}
+
+/// Observations about the internal behavior of a connection
+///
+/// Returned by [`Connection::poll_transport_event`]. These don't require any action, but help
+/// explain the behavior of a connection beyond what [`ConnectionStats`] reveal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransportEvent {
+    /// The TLS handshake was confirmed
+    HandshakeConfirmed,
+    /// The 1-RTT packet protection keys were updated
+    KeysUpdated {
+        /// Whether the update was initiated by the peer
+        remote: bool,
+    },
+    /// Connection IDs issued by the peer were retired, and will no longer be used
+    PeerCidsRetired {
+        /// Sequence numbers of the retired connection IDs
+        sequences: Range<u64>,
+    },
+    /// The peer's address changed, and the connection started migrating to the new path
+    PathMigrated {
+        /// The new address of the peer
+        remote: SocketAddr,
+    },
+    /// The maximum size of UDP payloads on the current path changed
+    MtuChanged {
+        /// The new MTU, see [`Connection::current_mtu`]
+        mtu: u16,
+    },
+    /// Packet loss suggested that the path no longer carries packets of the current MTU
+    ///
+    /// The MTU is reset to the minimum, and MTU discovery resumes after
+    /// [`MtuDiscoveryConfig::black_hole_cooldown()`].
+    MtuBlackHoleDetected {
+        /// The MTU before the black hole was detected
+        previous_mtu: u16,
+        /// The new MTU
+        mtu: u16,
+    },
+    /// An RTT measurement requested through [`Connection::probe_rtt()`] completed
+    ///
+    /// Unlike other transport events, this is retained until polled.
+    RttProbed {
+        /// Time from sending the probe until receiving its acknowledgement
+        rtt: Duration,
+    },
+    /// Discovery of the keep-alive interval finished
+    ///
+    /// See [`AdaptiveKeepAliveConfig`](crate::AdaptiveKeepAliveConfig).
+    KeepAliveSettled {
+        /// The interval used from now on
+        interval: Duration,
+    },
+    /// A stream could not be opened because the limit set by the peer was reached
+    StreamsBlocked {
+        /// Directionality of the stream
+        dir: Dir,
+    },
+    /// The peer opened a stream
+    ///
+    /// Announced as soon as the stream is opened, independently of [`Streams::accept()`], so the
+    /// application can inspect incoming streams before accepting them, or turn them down by
+    /// stopping them with [`RecvStream::stop()`]. Streams opened implicitly, by the peer opening a
+    /// stream of the same directionality with a higher ID, are announced too.
+    StreamOpened {
+        /// Identity of the stream, which also determines its directionality
+        id: StreamId,
+    },
+    /// Application datagrams were discarded
+    DatagramsDropped {
+        /// Number of received datagrams discarded before the application read them, to make
+        /// space for newer ones
+        incoming: u64,
+        /// Number of queued datagrams discarded before they were sent, to make space for newer
+        /// ones or because they no longer fit the path MTU
+        outgoing: u64,
+    },
+    /// Packets were declared lost
+    ///
+    /// A single round of loss detection may yield one event per [`LossTrigger`].
+    PacketsLost {
+        /// Packet number space of the lost packets
+        space: SpaceId,
+        /// Reason the packets were declared lost
+        trigger: LossTrigger,
+        /// Numbers of the lost packets, in ascending order
+        packets: Vec<u64>,
+        /// Total size of the lost packets
+        bytes: u64,
+    },
+    /// The peer reported the address it sees our packets coming from
+    ///
+    /// Only emitted if enabled through [`TransportConfig::receive_observed_address_reports`], and
+    /// again whenever the reported address changes.
+    ObservedAddr {
+        /// Our reflexive transport address, as observed by the peer
+        address: SocketAddr,
+    },
+}
+
+/// Reason packets were declared lost, see [`TransportEvent::PacketsLost`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LossTrigger {
+    /// Sufficiently many later packets were acknowledged
+    ///
+    /// Typical of loss within a burst of packets.
+    PacketThreshold,
+    /// The packets weren't acknowledged in time, though later packets were
+    TimeThreshold,
+    /// Loss was only detected after the probe timeout expired, since no later packets were
+    /// acknowledged before
+    ///
+    /// Typical of loss at the tail of a flight of packets.
+    ProbeTimeout,
 }
 
 fn get_max_ack_delay(params: &TransportParameters) -> Duration {
     Duration::from_micros(params.max_ack_delay.0 * 1000)
 }
 
-// Prevents overflow and improves behavior in extreme circumstances
-const MAX_BACKOFF_EXPONENT: u32 = 16;
-
 /// Minimal remaining size to allow packet coalescing, excluding cryptographic tag
 ///
 /// This must be at least as large as the header for a well-formed empty packet to be coalesced,
@@ -4080,10 +6050,13 @@ const MAX_HANDSHAKE_OR_0RTT_HEADER_SIZE: usize =
 /// Chosen arbitrarily, intended to be large enough to prevent spurious connection loss.
 const KEY_UPDATE_MARGIN: u64 = 10_000;
 
+/// Maximum number of [`TransportEvent`]s retained until polled
+const MAX_TRANSPORT_EVENTS: usize = 32;
+
 #[derive(Default)]
 struct SentFrames {
     retransmits: ThinRetransmits,
-    largest_acked: Option<u64>,
+    acked: Option<Range<u64>>,
     stream_frames: StreamMetaVec,
     /// Whether the packet contains non-retransmittable frames (like datagrams)
     non_retransmits: bool,
@@ -4093,7 +6066,7 @@ struct SentFrames {
 impl SentFrames {
     /// Returns whether the packet contains only ACKs
     fn is_ack_only(&self, streams: &StreamsState) -> bool {
-        self.largest_acked.is_some()
+        self.acked.is_some()
             && !self.non_retransmits
             && self.stream_frames.is_empty()
             && self.retransmits.is_empty(streams)
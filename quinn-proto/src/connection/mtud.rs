@@ -1,5 +1,5 @@
 use crate::{Instant, MAX_UDP_PAYLOAD, MtuDiscoveryConfig, packet::SpaceId};
-use std::cmp;
+use std::{cmp, collections::VecDeque};
 use tracing::trace;
 
 /// Implements Datagram Packetization Layer Path Maximum Transmission Unit Discovery
@@ -13,6 +13,8 @@ pub(crate) struct MtuDiscovery {
     state: Option<EnabledMtuDiscovery>,
     /// The state of the black hole detector
     black_hole_detector: BlackHoleDetector,
+    /// The most recent probes, bounded by [`MAX_PROBE_HISTORY`]
+    probes: VecDeque<MtuProbe>,
 }
 
 impl MtuDiscovery {
@@ -53,6 +55,51 @@ impl MtuDiscovery {
             current_mtu,
             state,
             black_hole_detector: BlackHoleDetector::new(min_mtu),
+            probes: VecDeque::new(),
+        }
+    }
+
+    /// Replace the configuration, enabling or disabling MTU discovery
+    ///
+    /// A search in progress continues with the new bounds. Disabling retains the current MTU.
+    pub(super) fn set_config(
+        &mut self,
+        config: Option<MtuDiscoveryConfig>,
+        peer_max_udp_payload_size: u16,
+    ) {
+        let Some(config) = config else {
+            self.state = None;
+            return;
+        };
+        let state = self.state.get_or_insert_with(|| {
+            let mut state = EnabledMtuDiscovery::new(config.clone());
+            state.peer_max_udp_payload_size = peer_max_udp_payload_size;
+            state
+        });
+        if let Phase::Searching(search) = &mut state.phase {
+            let mut new =
+                SearchState::new(search.lower_bound, state.peer_max_udp_payload_size, &config);
+            new.last_probed_mtu = search.last_probed_mtu;
+            new.in_flight_probe = search.in_flight_probe;
+            new.lost_probe_count = search.lost_probe_count;
+            *search = new;
+        }
+        state.config = config;
+    }
+
+    /// The most recent MTU probes, oldest first
+    pub(super) fn probes(&self) -> impl ExactSizeIterator<Item = MtuProbe> + '_ {
+        self.probes.iter().copied()
+    }
+
+    /// Update the outcome of the most recent probe, if it is still in flight
+    fn on_probe_outcome(&mut self, outcome: MtuProbeOutcome) {
+        if let Some(probe) = self
+            .probes
+            .back_mut()
+            .filter(|probe| probe.outcome == MtuProbeOutcome::InFlight)
+        {
+            probe.outcome = outcome;
         }
     }
 
@@ -72,9 +119,19 @@ impl MtuDiscovery {
 
     /// Returns the amount of bytes that should be sent as an MTU probe, if any
     pub(crate) fn poll_transmit(&mut self, now: Instant, next_pn: u64) -> Option<u16> {
-        self.state
+        let size = self
+            .state
             .as_mut()
-            .and_then(|state| state.poll_transmit(now, self.current_mtu, next_pn))
+            .and_then(|state| state.poll_transmit(now, self.current_mtu, next_pn))?;
+        if self.probes.len() >= MAX_PROBE_HISTORY {
+            self.probes.pop_front();
+        }
+        self.probes.push_back(MtuProbe {
+            size,
+            sent: now,
+            outcome: MtuProbeOutcome::InFlight,
+        });
+        Some(size)
     }
 
     /// Notifies the [`MtuDiscovery`] that the peer's `max_udp_payload_size` transport parameter has
@@ -112,6 +169,7 @@ impl MtuDiscovery {
         {
             self.current_mtu = new_mtu;
             trace!(current_mtu = self.current_mtu, "new MTU detected");
+            self.on_probe_outcome(MtuProbeOutcome::Acked);
 
             self.black_hole_detector.on_probe_acked(pn, len);
             true
@@ -134,11 +192,50 @@ impl MtuDiscovery {
 
     /// Notifies the [`MtuDiscovery`] that the in-flight MTU probe was lost
     pub(crate) fn on_probe_lost(&mut self) {
+        self.on_probe_outcome(MtuProbeOutcome::Lost);
         if let Some(state) = &mut self.state {
             state.on_probe_lost();
         }
     }
 
+    /// Notifies the [`MtuDiscovery`] that a datagram of `len` bytes could not be sent because it
+    /// exceeds the path MTU known to the operating system
+    ///
+    /// If the datagram was an MTU probe, the caller must first declare it lost through
+    /// [`MtuDiscovery::on_probe_lost`]. Returns true if the current MTU was lowered.
+    pub(crate) fn on_too_big(&mut self, len: u16) -> bool {
+        if len > self.current_mtu {
+            // Only probes exceed the current MTU. There's no point in retransmitting this one.
+            if let Some(EnabledMtuDiscovery {
+                phase: Phase::Searching(state),
+                ..
+            }) = &mut self.state
+            {
+                if len >= state.last_probed_mtu {
+                    state.lost_probe_count = MAX_PROBE_RETRANSMITS;
+                }
+            }
+            return false;
+        }
+
+        // The path MTU shrank, e.g. because the route changed. Fall back to the minimum MTU and
+        // search again below the new ceiling.
+        let min_mtu = self.black_hole_detector.min_mtu;
+        if self.current_mtu == min_mtu {
+            return false;
+        }
+        self.current_mtu = min_mtu;
+        self.black_hole_detector = BlackHoleDetector::new(min_mtu);
+        if let Some(state) = &mut self.state {
+            state.on_ceiling_lowered(min_mtu, len.saturating_sub(1));
+        }
+        trace!(
+            current_mtu = self.current_mtu,
+            "datagram too big, MTU lowered"
+        );
+        true
+    }
+
     /// Notifies the [`MtuDiscovery`] that a non-probe packet was lost
     ///
     /// When done notifying of lost packets, [`MtuDiscovery::black_hole_detected`] must be called, to
@@ -226,7 +323,9 @@ impl EnabledMtuDiscovery {
                 state.in_flight_probe = None;
             }
 
-            if let Some(probe_udp_payload_size) = state.next_mtu_to_probe(last_probe_succeeded) {
+            if let Some(probe_udp_payload_size) =
+                state.next_mtu_to_probe(last_probe_succeeded, &self.config.probe_sizes)
+            {
                 state.in_flight_probe = Some(next_pn);
                 state.last_probed_mtu = probe_udp_payload_size;
                 return Some(probe_udp_payload_size);
@@ -263,6 +362,18 @@ impl EnabledMtuDiscovery {
         }
     }
 
+    /// Called when the path can't carry datagrams larger than `ceiling`
+    fn on_ceiling_lowered(&mut self, current_mtu: u16, ceiling: u16) {
+        // Searching only starts once the peer's transport parameters are known
+        if let Phase::Initial = self.phase {
+            return;
+        }
+        let mut search =
+            SearchState::new(current_mtu, self.peer_max_udp_payload_size, &self.config);
+        search.upper_bound = search.upper_bound.min(ceiling).max(search.lower_bound);
+        self.phase = Phase::Searching(search);
+    }
+
     /// Called when a black hole is detected
     fn on_black_hole_detected(&mut self, now: Instant) {
         // Stop searching, if applicable, and reset the timer
@@ -322,8 +433,8 @@ impl SearchState {
         }
     }
 
-    /// Determines the next MTU to probe using binary search
-    fn next_mtu_to_probe(&mut self, last_probe_succeeded: bool) -> Option<u16> {
+    /// Determines the next MTU to probe from `schedule`, or using binary search if it is empty
+    fn next_mtu_to_probe(&mut self, last_probe_succeeded: bool, schedule: &[u16]) -> Option<u16> {
         debug_assert_eq!(self.in_flight_probe, None);
 
         if last_probe_succeeded {
@@ -332,6 +443,14 @@ impl SearchState {
             self.upper_bound = self.last_probed_mtu - 1;
         }
 
+        if !schedule.is_empty() {
+            return schedule
+                .iter()
+                .copied()
+                .filter(|&size| size > self.lower_bound && size <= self.upper_bound)
+                .min();
+        }
+
         let next_mtu = (self.lower_bound as i32 + self.upper_bound as i32) / 2;
 
         // Binary search stopping condition
@@ -519,6 +638,31 @@ struct CurrentLossBurst {
 const MAX_PROBE_RETRANSMITS: usize = 3;
 /// Maximum number of suspicious loss bursts that will not trigger black hole detection
 const BLACK_HOLE_THRESHOLD: usize = 3;
+/// Number of probes retained for [`Connection::mtu_probes`](super::Connection::mtu_probes)
+const MAX_PROBE_HISTORY: usize = 16;
+
+/// An MTU probe sent on the current path
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MtuProbe {
+    /// UDP payload size probed
+    pub size: u16,
+    /// When the probe was sent
+    pub sent: Instant,
+    /// What became of the probe
+    pub outcome: MtuProbeOutcome,
+}
+
+/// Outcome of an [`MtuProbe`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MtuProbeOutcome {
+    /// Neither acknowledged nor deemed lost yet
+    InFlight,
+    /// The probe was acknowledged, raising the MTU to its size
+    Acked,
+    /// The probe was deemed lost or rejected by the operating system
+    Lost,
+}
 
 #[cfg(test)]
 mod tests {
@@ -599,6 +743,40 @@ mod tests {
         assert_eq!(mtud.black_hole_detector.suspicious_loss_burst_count(), 0);
     }
 
+    #[test]
+    fn mtu_discovery_probe_schedule() {
+        let mut config = MtuDiscoveryConfig::default();
+        config
+            .upper_bound(9_000)
+            .probe_sizes(vec![9_000, 1_400, 1_300, 1_500]);
+        let mut mtud = MtuDiscovery::new(1_200, 1_200, None, config);
+        let probed_sizes = drive_to_completion(&mut mtud, Instant::now(), 1_450);
+        assert_eq!(probed_sizes, [1_300, 1_400, 1_500, 1_500, 1_500]);
+        assert_eq!(mtud.current_mtu, 1_400);
+
+        let outcomes = mtud.probes().map(|p| p.outcome).collect::<Vec<_>>();
+        use MtuProbeOutcome::*;
+        assert_eq!(outcomes, [Acked, Acked, Lost, Lost, Lost]);
+    }
+
+    #[test]
+    fn mtu_discovery_set_config() {
+        let now = Instant::now();
+        let mut mtud = MtuDiscovery::disabled(1_200, 1_200);
+        assert_eq!(mtud.poll_transmit(now, 0), None);
+
+        mtud.set_config(Some(MtuDiscoveryConfig::default()), 1_300);
+        drive_to_completion(&mut mtud, now, 1_500);
+        assert_eq!(mtud.current_mtu, 1_300);
+
+        mtud.set_config(None, 1_300);
+        assert_eq!(mtud.current_mtu, 1_300);
+        assert_eq!(
+            mtud.poll_transmit(now + Duration::from_secs(3_600), 100),
+            None
+        );
+    }
+
     #[test]
     fn mtu_discovery_disabled_does_nothing() {
         let mut mtud = MtuDiscovery::disabled(1_200, 1_200);
@@ -708,6 +886,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn mtu_discovery_too_big_probe_is_not_retransmitted() {
+        let mut mtud = default_mtud();
+        let now = Instant::now();
+
+        let first_probe_size = mtud.poll_transmit(now, 0).unwrap();
+        mtud.on_probe_lost();
+        assert!(!mtud.on_too_big(first_probe_size));
+
+        // The next probe is smaller right away
+        let second_probe_size = mtud.poll_transmit(now, 1).unwrap();
+        assert_eq!(
+            second_probe_size,
+            first_probe_size - (first_probe_size - 1_200) / 2 - 1
+        );
+        assert_eq!(mtud.current_mtu, 1_200);
+    }
+
+    #[test]
+    fn mtu_discovery_too_big_lowers_current_mtu() {
+        let mut config = MtuDiscoveryConfig::default();
+        config.upper_bound(9_000);
+        let mut mtud = MtuDiscovery::new(1_200, 1_200, None, config);
+        let now = Instant::now();
+        drive_to_completion(&mut mtud, now, 1_500);
+        assert_eq!(mtud.current_mtu, 1_471);
+
+        assert!(mtud.on_too_big(1_471));
+        assert_eq!(mtud.current_mtu, 1_200);
+        // Already at the minimum
+        assert!(!mtud.on_too_big(1_200));
+
+        // Searching restarts immediately, below the datagram that was too big
+        let probed_sizes = drive_to_completion(&mut mtud, now, 1_400);
+        assert!(probed_sizes.iter().all(|&size| size < 1_471));
+        assert!((1_201..=1_400).contains(&mtud.current_mtu));
+    }
+
     #[test]
     fn mtu_discovery_with_peer_max_udp_payload_size_clamps_upper_bound() {
         let mut mtud = default_mtud();
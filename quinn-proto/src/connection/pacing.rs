@@ -42,11 +42,16 @@ impl Pacer {
         }
     }
 
-    /// Obtains the `max_bytes_per_second` used when this [`Pacer`] was constructed.
+    /// Obtains the current limit on the sending rate.
     pub(crate) fn max_bytes_per_second(&self) -> Option<u64> {
         self.max_bytes_per_second
     }
 
+    /// Changes the limit on the sending rate, taking effect with the next call to [`Self::delay`].
+    pub(crate) fn set_max_bytes_per_second(&mut self, value: Option<u64>) {
+        self.max_bytes_per_second = value;
+    }
+
     /// Record that a packet has been transmitted.
     pub(super) fn on_transmit(&mut self, packet_length: u16) {
         self.tokens = self.tokens.saturating_sub(packet_length.into())
@@ -391,4 +396,28 @@ mod tests {
         let now = old_instant + expected_delay / 2;
         assert_eq!(pacer.delay(rtt, 500, mtu, window, now), None);
     }
+
+    #[test]
+    fn rate_limit_changes() {
+        let window = 2_000_000u64;
+        let mtu = 1000;
+        let rtt = Duration::from_millis(50);
+        let now = Instant::now();
+
+        let mut pacer = Pacer::new(rtt, window, mtu, None, now);
+        assert_eq!(pacer.delay(rtt, 1_000, mtu, window, now), None);
+
+        // Tokens beyond the capacity for the new rate are discarded
+        pacer.set_max_bytes_per_second(Some(2_000));
+        assert_eq!(pacer.delay(rtt, 1_000, mtu, window, now), None);
+        pacer.on_transmit(mtu);
+        assert!(pacer.delay(rtt, 1_000, mtu, window, now).is_some());
+
+        // Lifting the limit restores the congestion window's rate
+        pacer.set_max_bytes_per_second(None);
+        assert_eq!(
+            pacer.delay(rtt, 1_000, mtu, window, now + Duration::from_millis(1)),
+            None
+        );
+    }
 }
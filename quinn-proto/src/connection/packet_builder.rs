@@ -3,6 +3,8 @@ use rand::RngExt;
 use tracing::{debug, trace, trace_span};
 
 use super::{Connection, SentFrames, spaces::SentPacket};
+#[cfg(feature = "packet-trace")]
+use super::{FrameStats, packet_trace::PacketTrace};
 use crate::{
     ConnectionId, Instant, TransportError, TransportErrorCode,
     connection::ConnectionSide,
@@ -25,6 +27,9 @@ pub(super) struct PacketBuilder {
     pub(super) max_size: usize,
     pub(super) tag_len: usize,
     pub(super) _span: tracing::span::EnteredSpan,
+    /// Frames sent before this packet, to identify the frames it contains
+    #[cfg(feature = "packet-trace")]
+    frames_before: FrameStats,
 }
 
 impl PacketBuilder {
@@ -166,6 +171,8 @@ impl PacketBuilder {
             tag_len,
             ack_eliciting,
             _span: span,
+            #[cfg(feature = "packet-trace")]
+            frames_before: conn.stats.frame_tx,
         })
     }
 
@@ -201,9 +208,16 @@ impl PacketBuilder {
             false => 0,
         };
 
+        let app_limited = conn.is_app_limited();
+        let delivery = (size != 0).then(|| {
+            conn.path
+                .delivery_rate
+                .on_sent(now, conn.path.in_flight.bytes, app_limited)
+        });
         let packet = SentPacket {
             path_generation: conn.path.generation(),
-            largest_acked: sent.largest_acked,
+            delivery,
+            acked: sent.acked,
             time_sent: now,
             size,
             ack_eliciting,
@@ -214,6 +228,8 @@ impl PacketBuilder {
         conn.path
             .sent(exact_number, packet, &mut conn.spaces[space_id]);
         conn.stats.path.sent_packets += 1;
+        let is_1rtt = space_id == SpaceId::Data && conn.spaces[space_id].crypto.is_some();
+        conn.stats.handshake.on_sent(now, space_id, is_1rtt);
         conn.reset_keep_alive(now);
         if size != 0 {
             if ack_eliciting {
@@ -267,6 +283,17 @@ impl PacketBuilder {
         );
 
         let len = buffer.len() - encode_start;
+        conn.last_sent = Some(now);
+        #[cfg(feature = "packet-trace")]
+        PacketTrace {
+            space: self.space,
+            number: Some(self.exact_number),
+            size: len,
+            ecn: conn.path.sending_ecn.then_some(crate::EcnCodepoint::Ect0),
+            remote: conn.path.remote,
+            frames_before: self.frames_before,
+        }
+        .sent(&conn.stats.frame_tx);
         conn.config.qlog_sink.emit_packet_sent(
             self.exact_number,
             len,
@@ -274,6 +301,7 @@ impl PacketBuilder {
             self.space == SpaceId::Data && conn.spaces[SpaceId::Data].crypto.is_none(),
             now,
             conn.orig_rem_cid,
+            conn.trace_context.as_deref(),
         );
 
         (len, pad)
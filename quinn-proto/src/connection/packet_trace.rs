@@ -0,0 +1,90 @@
+//! One structured event per packet, for deep debugging
+//!
+//! Events are emitted at the trace level with the target `quinn_proto::packet_trace`. Their fields
+//! are only computed if a subscriber is interested, and the whole module is compiled out unless the
+//! `packet-trace` feature is enabled.
+
+use std::{fmt, net::SocketAddr};
+
+use tracing::trace;
+
+use super::FrameStats;
+use crate::{EcnCodepoint, packet::SpaceId};
+
+/// Properties of a packet that are known before its frames are written or processed
+pub(super) struct PacketTrace {
+    pub(super) space: SpaceId,
+    pub(super) number: Option<u64>,
+    pub(super) size: usize,
+    pub(super) ecn: Option<EcnCodepoint>,
+    pub(super) remote: SocketAddr,
+    /// Frame counts before the packet, to identify the frames it contains
+    pub(super) frames_before: FrameStats,
+}
+
+impl PacketTrace {
+    pub(super) fn sent(self, frames_after: &FrameStats) {
+        trace!(
+            target: "quinn_proto::packet_trace",
+            direction = "sent",
+            space = ?self.space,
+            number = self.number,
+            size = self.size,
+            ecn = ?self.ecn,
+            remote = %self.remote,
+            frames = %Frames(&self.frames_before, frames_after),
+        );
+    }
+
+    pub(super) fn received(self, frames_after: &FrameStats) {
+        trace!(
+            target: "quinn_proto::packet_trace",
+            direction = "received",
+            space = ?self.space,
+            number = self.number,
+            size = self.size,
+            ecn = ?self.ecn,
+            remote = %self.remote,
+            frames = %Frames(&self.frames_before, frames_after),
+        );
+    }
+}
+
+/// Frames counted between two snapshots of [`FrameStats`]
+struct Frames<'a>(&'a FrameStats, &'a FrameStats);
+
+impl fmt::Display for Frames<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for ((name, before), (_, after)) in self.0.counts().into_iter().zip(self.1.counts()) {
+            let count = after.saturating_sub(before);
+            if count == 0 {
+                continue;
+            }
+            if !first {
+                f.write_str(",")?;
+            }
+            first = false;
+            f.write_str(name)?;
+            if count > 1 {
+                write!(f, "*{count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let before = FrameStats::default();
+        let mut after = before;
+        assert_eq!(Frames(&before, &after).to_string(), "");
+        after.acks += 1;
+        after.stream += 3;
+        assert_eq!(Frames(&before, &after).to_string(), "ACK,STREAM*3");
+    }
+}
@@ -3,11 +3,15 @@ use std::{cmp, net::SocketAddr};
 use tracing::trace;
 
 use super::{
+    delivery_rate::DeliveryRateEstimator,
     mtud::MtuDiscovery,
     pacing::Pacer,
     spaces::{PacketSpace, SentPacket},
 };
-use crate::{Duration, Instant, TIMER_GRANULARITY, TransportConfig, congestion, packet::SpaceId};
+use crate::{
+    Duration, Instant, MtuDiscoveryConfig, RttEstimatorConfig, TIMER_GRANULARITY, TransportConfig,
+    congestion, packet::SpaceId,
+};
 
 #[cfg(feature = "qlog")]
 use qlog::events::{ExData, quic::RecoveryMetricsUpdated};
@@ -18,6 +22,8 @@ pub(super) struct PathData {
     pub(super) rtt: RttEstimator,
     /// Whether we're enabling ECN on outgoing packets
     pub(super) sending_ecn: bool,
+    /// IPv6 flow label to set on outgoing packets, if any
+    pub(super) flow_label: Option<u32>,
     /// Congestion controller state
     pub(super) congestion: Box<dyn congestion::Controller>,
     /// Pacing state
@@ -33,6 +39,8 @@ pub(super) struct PathData {
     pub(super) total_sent: u64,
     /// Total size of all UDP datagrams received on this path
     pub(super) total_recvd: u64,
+    /// Multiple of `total_recvd` that may be sent before the path is validated
+    anti_amplification_factor: u64,
     /// The state of the MTU discovery process
     pub(super) mtud: MtuDiscovery,
     /// Packet number of the first packet sent after an RTT sample was collected on this path
@@ -40,6 +48,7 @@ pub(super) struct PathData {
     /// Used in persistent congestion determination.
     pub(super) first_packet_after_rtt_sample: Option<(SpaceId, u64)>,
     pub(super) in_flight: InFlight,
+    pub(super) delivery_rate: DeliveryRateEstimator,
     /// Number of the first packet sent on this path
     ///
     /// Used to determine whether a packet was sent on an earlier path. Insufficient to determine if
@@ -57,7 +66,7 @@ pub(super) struct PathData {
 impl PathData {
     pub(super) fn new(
         remote: SocketAddr,
-        allow_mtud: bool,
+        mtud_config: Option<&MtuDiscoveryConfig>,
         peer_max_udp_payload_size: Option<u16>,
         generation: u64,
         now: Instant,
@@ -69,8 +78,9 @@ impl PathData {
             .build(now, config.get_initial_mtu());
         Self {
             remote,
-            rtt: RttEstimator::new(config.initial_rtt),
+            rtt: RttEstimator::new(config.initial_rtt, &config.rtt_estimator),
             sending_ecn: true,
+            flow_label: None,
             pacing: Pacer::new(
                 config.initial_rtt,
                 congestion.initial_window(),
@@ -84,23 +94,21 @@ impl PathData {
             validated: false,
             total_sent: 0,
             total_recvd: 0,
-            mtud: config
-                .mtu_discovery_config
-                .as_ref()
-                .filter(|_| allow_mtud)
-                .map_or_else(
-                    || MtuDiscovery::disabled(config.get_initial_mtu(), config.min_mtu),
-                    |mtud_config| {
-                        MtuDiscovery::new(
-                            config.get_initial_mtu(),
-                            config.min_mtu,
-                            peer_max_udp_payload_size,
-                            mtud_config.clone(),
-                        )
-                    },
-                ),
+            anti_amplification_factor: config.anti_amplification_factor.into(),
+            mtud: mtud_config.map_or_else(
+                || MtuDiscovery::disabled(config.get_initial_mtu(), config.min_mtu),
+                |mtud_config| {
+                    MtuDiscovery::new(
+                        config.get_initial_mtu(),
+                        config.min_mtu,
+                        peer_max_udp_payload_size,
+                        mtud_config.clone(),
+                    )
+                },
+            ),
             first_packet_after_rtt_sample: None,
             in_flight: InFlight::new(),
+            delivery_rate: DeliveryRateEstimator::default(),
             first_packet: None,
             #[cfg(feature = "qlog")]
             recovery_metrics: RecoveryMetrics::default(),
@@ -127,15 +135,18 @@ impl PathData {
                 now,
             ),
             sending_ecn: true,
+            flow_label: None,
             congestion,
             challenge: None,
             challenge_pending: false,
             validated: false,
             total_sent: 0,
             total_recvd: 0,
+            anti_amplification_factor: prev.anti_amplification_factor,
             mtud: prev.mtud.clone(),
             first_packet_after_rtt_sample: prev.first_packet_after_rtt_sample,
             in_flight: InFlight::new(),
+            delivery_rate: DeliveryRateEstimator::default(),
             first_packet: None,
             #[cfg(feature = "qlog")]
             recovery_metrics: prev.recovery_metrics.clone(),
@@ -147,18 +158,30 @@ impl PathData {
     ///
     /// This is useful when it is known the underlying path has changed.
     pub(super) fn reset(&mut self, now: Instant, config: &TransportConfig) {
-        self.rtt = RttEstimator::new(config.initial_rtt);
+        self.rtt = RttEstimator::new(config.initial_rtt, &config.rtt_estimator);
         self.congestion = config
             .congestion_controller_factory
             .clone()
             .build(now, config.get_initial_mtu());
         self.mtud.reset(config.get_initial_mtu(), config.min_mtu);
+        self.delivery_rate = DeliveryRateEstimator::default();
     }
 
     /// Indicates whether we're a server that hasn't validated the peer's address and hasn't
     /// received enough data from the peer to permit sending `bytes_to_send` additional bytes
     pub(super) fn anti_amplification_blocked(&self, bytes_to_send: u64) -> bool {
-        !self.validated && self.total_recvd * 3 < self.total_sent + bytes_to_send
+        !self.validated
+            && self.total_recvd * self.anti_amplification_factor < self.total_sent + bytes_to_send
+    }
+
+    /// Number of bytes anti-amplification permits sending, or `None` if unrestricted
+    pub(super) fn anti_amplification_budget(&self) -> Option<u64> {
+        match self.validated {
+            true => None,
+            false => Some(
+                (self.total_recvd * self.anti_amplification_factor).saturating_sub(self.total_sent),
+            ),
+        }
     }
 
     /// Returns the path's current MTU
@@ -308,15 +331,22 @@ pub struct RttEstimator {
     var: Duration,
     /// The minimum RTT seen in the connection, ignoring ack delay.
     min: Duration,
+    /// Tracks the minimum over recent samples, if limited to a window
+    min_filter: Option<WindowedMin>,
+    smoothing_gain: f64,
+    variance_gain: f64,
 }
 
 impl RttEstimator {
-    pub(crate) fn new(initial_rtt: Duration) -> Self {
+    pub(crate) fn new(initial_rtt: Duration, config: &RttEstimatorConfig) -> Self {
         Self {
             latest: initial_rtt,
             smoothed: None,
             var: initial_rtt / 2,
             min: initial_rtt,
+            min_filter: config.min_rtt_window.map(WindowedMin::new),
+            smoothing_gain: config.smoothing_gain,
+            variance_gain: config.variance_gain,
         }
     }
 
@@ -334,19 +364,34 @@ impl RttEstimator {
     }
 
     /// Minimum RTT registered so far for this estimator.
+    ///
+    /// Limited to recent samples if [`RttEstimatorConfig::min_rtt_window`] is set.
     pub fn min(&self) -> Duration {
         self.min
     }
 
+    /// The most recent RTT sample
+    pub fn latest(&self) -> Duration {
+        self.latest
+    }
+
+    /// The RTT variation, as per RFC 9002 §5.3
+    pub fn var(&self) -> Duration {
+        self.var
+    }
+
     // PTO computed as described in RFC9002#6.2.1
     pub(crate) fn pto_base(&self) -> Duration {
         self.get() + cmp::max(4 * self.var, TIMER_GRANULARITY)
     }
 
-    pub(crate) fn update(&mut self, ack_delay: Duration, rtt: Duration) {
+    pub(crate) fn update(&mut self, now: Instant, ack_delay: Duration, rtt: Duration) {
         self.latest = rtt;
         // min_rtt ignores ack delay.
-        self.min = cmp::min(self.min, self.latest);
+        self.min = match self.min_filter {
+            Some(ref mut filter) => filter.update(now, self.latest),
+            None => cmp::min(self.min, self.latest),
+        };
         // Based on RFC6298.
         if let Some(smoothed) = self.smoothed {
             let adjusted_rtt = if self.min + ack_delay <= self.latest {
@@ -355,8 +400,8 @@ impl RttEstimator {
                 self.latest
             };
             let var_sample = smoothed.abs_diff(adjusted_rtt);
-            self.var = (3 * self.var + var_sample) / 4;
-            self.smoothed = Some((7 * smoothed + adjusted_rtt) / 8);
+            self.var = ewma(self.var, var_sample, self.variance_gain);
+            self.smoothed = Some(ewma(smoothed, adjusted_rtt, self.smoothing_gain));
         } else {
             self.smoothed = Some(self.latest);
             self.var = self.latest / 2;
@@ -365,6 +410,71 @@ impl RttEstimator {
     }
 }
 
+/// Minimum of the samples in a sliding time window
+///
+/// Uses Kathleen Nichols' algorithm, as in BBR: keeps the best, second and third best samples
+/// from successively later parts of the window, so that when the best one expires, the next best
+/// one of the remaining window takes its place rather than whatever the latest sample is.
+#[derive(Copy, Clone)]
+struct WindowedMin {
+    window: Duration,
+    /// Ordered by value and time, in (time, value) pairs; `None` until the first sample
+    samples: Option<[(Instant, Duration); 3]>,
+}
+
+impl WindowedMin {
+    fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: None,
+        }
+    }
+
+    /// Record a sample taken at `now`, returning the minimum of the window
+    fn update(&mut self, now: Instant, value: Duration) -> Duration {
+        let sample = (now, value);
+        let s = match self.samples {
+            Some(ref mut s)
+                if value > s[0].1 && now.saturating_duration_since(s[2].0) <= self.window =>
+            {
+                s
+            }
+            // New minimum, or nothing left in the window: forget earlier samples
+            _ => return self.samples.insert([sample; 3])[0].1,
+        };
+
+        if value <= s[1].1 {
+            s[1] = sample;
+            s[2] = sample;
+        } else if value <= s[2].1 {
+            s[2] = sample;
+        }
+
+        // Expire the best samples as time advances, and keep the others spread over the window
+        let age = now.saturating_duration_since(s[0].0);
+        if age > self.window {
+            s[0] = s[1];
+            s[1] = s[2];
+            s[2] = sample;
+            if now.saturating_duration_since(s[0].0) > self.window {
+                s[0] = s[1];
+                s[1] = s[2];
+            }
+        } else if s[1].0 == s[0].0 && age > self.window / 4 {
+            s[1] = sample;
+            s[2] = sample;
+        } else if s[2].0 == s[1].0 && age > self.window / 2 {
+            s[2] = sample;
+        }
+        s[0].1
+    }
+}
+
+/// Exponentially weighted moving average of `average` and `sample`
+fn ewma(average: Duration, sample: Duration, gain: f64) -> Duration {
+    average.mul_f64(1.0 - gain) + sample.mul_f64(gain)
+}
+
 #[derive(Default)]
 pub(crate) struct PathResponses {
     pending: Vec<PathResponse>,
@@ -467,3 +577,36 @@ impl InFlight {
         self.ack_eliciting -= u64::from(packet.ack_eliciting);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nan_gains() {
+        let mut config = RttEstimatorConfig::default();
+        config.smoothing_gain(f64::NAN).variance_gain(f64::NAN);
+        assert_eq!(config, RttEstimatorConfig::default());
+
+        let mut rtt = RttEstimator::new(Duration::from_millis(100), &config);
+        let now = Instant::now();
+        rtt.update(now, Duration::ZERO, Duration::from_millis(80));
+        rtt.update(now, Duration::ZERO, Duration::from_millis(160));
+        assert_eq!(rtt.get(), Duration::from_millis(90));
+    }
+
+    #[test]
+    fn windowed_min() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut filter = WindowedMin::new(ms(100));
+        assert_eq!(filter.update(start, ms(10)), ms(10));
+        assert_eq!(filter.update(start + ms(30), ms(30)), ms(10));
+        assert_eq!(filter.update(start + ms(60), ms(20)), ms(10));
+        // The best sample expires, and the best of the rest of the window takes its place, rather
+        // than the larger latest sample
+        assert_eq!(filter.update(start + ms(110), ms(50)), ms(20));
+        assert_eq!(filter.update(start + ms(170), ms(40)), ms(40));
+        assert_eq!(filter.update(start + ms(180), ms(15)), ms(15));
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{Duration, Instant, PeerLimitsConfig, TransportError};
+
+/// Enforces [`PeerLimitsConfig`]
+#[derive(Debug)]
+pub(super) struct PeerLimits {
+    datagrams: Option<RateLimit>,
+    cid_retirements: Option<RateLimit>,
+    stream_resets: Option<RateLimit>,
+    max_frames_per_packet: Option<u32>,
+}
+
+impl PeerLimits {
+    pub(super) fn new(config: &PeerLimitsConfig, now: Instant) -> Self {
+        let limit = |x: Option<u32>| x.map(|limit| RateLimit::new(limit, now));
+        Self {
+            datagrams: limit(config.max_datagrams_per_second),
+            cid_retirements: limit(config.max_cid_retirements_per_second),
+            stream_resets: limit(config.max_stream_resets_per_second),
+            max_frames_per_packet: config.max_frames_per_packet,
+        }
+    }
+
+    /// Whether a datagram received at `now` should be processed
+    pub(super) fn on_datagram(&mut self, now: Instant) -> bool {
+        self.datagrams.as_mut().is_none_or(|x| x.allow(now))
+    }
+
+    pub(super) fn on_cid_retirement(&mut self, now: Instant) -> Result<(), TransportError> {
+        match self.cid_retirements.as_mut().is_none_or(|x| x.allow(now)) {
+            true => Ok(()),
+            false => Err(excessive_load("too many connection IDs retired")),
+        }
+    }
+
+    pub(super) fn on_stream_reset(&mut self, now: Instant) -> Result<(), TransportError> {
+        match self.stream_resets.as_mut().is_none_or(|x| x.allow(now)) {
+            true => Ok(()),
+            false => Err(excessive_load("too many streams reset")),
+        }
+    }
+
+    /// Check the number of frames in a packet processed so far
+    pub(super) fn on_frame(&self, frames: u32) -> Result<(), TransportError> {
+        match self.max_frames_per_packet.is_none_or(|max| frames <= max) {
+            true => Ok(()),
+            false => Err(excessive_load("too many frames in packet")),
+        }
+    }
+}
+
+/// The error closing a connection that exceeded a limit
+///
+/// The peer's behavior is legal, so this isn't a protocol violation. QUIC has no code for excessive
+/// load, so this is reported as an internal error: the endpoint declines to spend more resources
+/// on the connection.
+fn excessive_load(reason: &'static str) -> TransportError {
+    TransportError::INTERNAL_ERROR(reason)
+}
+
+/// Counts events in fixed windows of one second
+#[derive(Debug)]
+struct RateLimit {
+    limit: u32,
+    window_start: Instant,
+    count: u32,
+}
+
+impl RateLimit {
+    fn new(limit: u32, now: Instant) -> Self {
+        Self {
+            limit,
+            window_start: now,
+            count: 0,
+        }
+    }
+
+    /// Count an event, returning whether it's within the limit
+    fn allow(&mut self, now: Instant) -> bool {
+        if now.saturating_duration_since(self.window_start) >= WINDOW {
+            self.window_start = now;
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(1);
+        self.count <= self.limit
+    }
+}
+
+const WINDOW: Duration = Duration::from_secs(1);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit() {
+        let mut now = Instant::now();
+        let mut limit = RateLimit::new(2, now);
+        assert!(limit.allow(now));
+        assert!(limit.allow(now));
+        assert!(!limit.allow(now));
+        now += WINDOW / 2;
+        assert!(!limit.allow(now));
+        now += WINDOW / 2;
+        assert!(limit.allow(now));
+        assert!(limit.allow(now));
+        assert!(!limit.allow(now));
+    }
+}
@@ -31,10 +31,21 @@ pub struct QlogStream(pub(crate) Arc<Mutex<QlogStreamer>>);
 
 #[cfg(feature = "qlog")]
 impl QlogStream {
-    fn emit_event(&self, orig_rem_cid: ConnectionId, event: EventData, now: Instant) {
+    fn emit_event(
+        &self,
+        orig_rem_cid: ConnectionId,
+        trace_context: Option<&str>,
+        event: EventData,
+        now: Instant,
+    ) {
         // Time will be overwritten by `add_event_with_instant`
         let mut event = Event::with_time(0.0, event);
         event.group_id = Some(Box::new(orig_rem_cid.to_string()));
+        if let Some(context) = trace_context {
+            event
+                .ex_data
+                .insert("trace_context".into(), context.to_owned().into());
+        }
 
         let mut qlog_streamer = self.0.lock().unwrap();
         if let Err(e) = qlog_streamer.add_event_with_instant(event, now) {
@@ -51,6 +62,22 @@ pub(crate) struct QlogSink {
 }
 
 impl QlogSink {
+    /// Whether both sinks write to the same stream, or are both disabled
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        #[cfg(feature = "qlog")]
+        {
+            match (&self.stream, &other.stream) {
+                (Some(a), Some(b)) => Arc::ptr_eq(&a.0, &b.0),
+                (None, None) => true,
+                _ => false,
+            }
+        }
+        #[cfg(not(feature = "qlog"))]
+        {
+            true
+        }
+    }
+
     pub(crate) fn is_enabled(&self) -> bool {
         #[cfg(feature = "qlog")]
         {
@@ -68,6 +95,7 @@ impl QlogSink {
         path: &mut PathData,
         now: Instant,
         orig_rem_cid: ConnectionId,
+        trace_context: Option<&str>,
     ) {
         #[cfg(feature = "qlog")]
         {
@@ -79,7 +107,12 @@ impl QlogSink {
                 return;
             };
 
-            stream.emit_event(orig_rem_cid, EventData::QuicMetricsUpdated(metrics), now);
+            stream.emit_event(
+                orig_rem_cid,
+                trace_context,
+                EventData::QuicMetricsUpdated(metrics),
+                now,
+            );
         }
     }
 
@@ -91,6 +124,7 @@ impl QlogSink {
         space: SpaceId,
         now: Instant,
         orig_rem_cid: ConnectionId,
+        trace_context: Option<&str>,
     ) {
         #[cfg(feature = "qlog")]
         {
@@ -115,7 +149,12 @@ impl QlogSink {
                 ),
             };
 
-            stream.emit_event(orig_rem_cid, EventData::QuicPacketLost(event), now);
+            stream.emit_event(
+                orig_rem_cid,
+                trace_context,
+                EventData::QuicPacketLost(event),
+                now,
+            );
         }
     }
 
@@ -127,6 +166,7 @@ impl QlogSink {
         is_0rtt: bool,
         now: Instant,
         orig_rem_cid: ConnectionId,
+        trace_context: Option<&str>,
     ) {
         #[cfg(feature = "qlog")]
         {
@@ -144,7 +184,12 @@ impl QlogSink {
                 ..Default::default()
             };
 
-            stream.emit_event(orig_rem_cid, EventData::QuicPacketSent(event), now);
+            stream.emit_event(
+                orig_rem_cid,
+                trace_context,
+                EventData::QuicPacketSent(event),
+                now,
+            );
         }
     }
 
@@ -155,6 +200,7 @@ impl QlogSink {
         is_0rtt: bool,
         now: Instant,
         orig_rem_cid: ConnectionId,
+        trace_context: Option<&str>,
     ) {
         #[cfg(feature = "qlog")]
         {
@@ -171,7 +217,12 @@ impl QlogSink {
                 ..Default::default()
             };
 
-            stream.emit_event(orig_rem_cid, EventData::QuicPacketReceived(event), now);
+            stream.emit_event(
+                orig_rem_cid,
+                trace_context,
+                EventData::QuicPacketReceived(event),
+                now,
+            );
         }
     }
 }
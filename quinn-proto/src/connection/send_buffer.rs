@@ -161,6 +161,11 @@ impl SendBuffer {
         self.retransmits.insert(range);
     }
 
+    /// Whether lost data is queued for retransmission, which [`Self::poll_transmit`] yields first
+    pub(super) fn has_retransmits(&self) -> bool {
+        !self.retransmits.is_empty()
+    }
+
     pub(super) fn retransmit_all_for_0rtt(&mut self) {
         debug_assert_eq!(self.offset, self.unacked_len as u64);
         self.unsent = 0;
@@ -172,6 +177,11 @@ impl SendBuffer {
         self.offset
     }
 
+    /// First stream offset that hasn't been transmitted yet
+    pub(super) fn sent_offset(&self) -> u64 {
+        self.unsent
+    }
+
     /// Whether all sent data has been acknowledged
     pub(super) fn is_fully_acked(&self) -> bool {
         self.unacked_len == 0
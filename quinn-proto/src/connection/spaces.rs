@@ -2,17 +2,22 @@ use std::{
     cmp,
     collections::{BTreeMap, VecDeque},
     mem,
-    ops::{Bound, Index, IndexMut},
+    ops::{Bound, Index, IndexMut, Range},
 };
 
 use rand::{Rng, RngExt};
 use rustc_hash::FxHashSet;
 use tracing::trace;
 
-use super::assembler::Assembler;
+use super::{assembler::Assembler, delivery_rate::DeliverySnapshot};
 use crate::{
-    Dir, Duration, Instant, SocketAddr, StreamId, TransportError, VarInt, connection::StreamsState,
-    crypto::Keys, frame, packet::SpaceId, range_set::ArrayRangeSet, shared::IssuedCid,
+    Dir, Duration, Instant, SocketAddr, StreamId, TransportError, VarInt,
+    connection::{SpaceDebugState, StreamsState},
+    crypto::Keys,
+    frame,
+    packet::SpaceId,
+    range_set::ArrayRangeSet,
+    shared::IssuedCid,
 };
 
 pub(super) struct PacketSpace {
@@ -266,6 +271,30 @@ impl PacketSpace {
         // this shouldn't need to visit many packets before finishing one way or another.
         self.sent_packets.values().any(|x| x.size != 0)
     }
+
+    pub(super) fn debug_state(&self, space: SpaceId, streams: &StreamsState) -> SpaceDebugState {
+        let mut in_flight = Vec::<Range<u64>>::new();
+        for &number in self.sent_packets.keys() {
+            match in_flight.last_mut() {
+                Some(range) if range.end == number => range.end += 1,
+                _ => in_flight.push(number..number + 1),
+            }
+        }
+        SpaceDebugState {
+            space,
+            has_keys: self.crypto.is_some(),
+            next_packet_number: self.next_packet_number,
+            largest_acked_packet: self.largest_acked_packet,
+            largest_received_packet: self.rx_packet,
+            in_flight,
+            in_flight_bytes: self.sent_packets.values().map(|x| u64::from(x.size)).sum(),
+            pending_acks: self.pending_acks.ranges().iter().collect(),
+            lost_packets: self.lost_packets.len(),
+            retransmits_pending: !self.pending.is_empty(streams),
+            loss_probes: self.loss_probes,
+            crypto_offset: self.crypto_offset,
+        }
+    }
 }
 
 impl Index<SpaceId> for [PacketSpace; 3] {
@@ -294,8 +323,10 @@ pub(super) struct SentPacket {
     pub(super) size: u16,
     /// Whether an acknowledgement is expected directly in response to this packet.
     pub(super) ack_eliciting: bool,
-    /// The largest packet number acknowledged by this packet
-    pub(super) largest_acked: Option<u64>,
+    /// The packet numbers covered by the ACK frame in this packet
+    ///
+    /// Ranges omitted from a truncated frame lie below it.
+    pub(super) acked: Option<Range<u64>>,
     /// Data which needs to be retransmitted in case the packet is lost.
     /// The data is boxed to minimize `SentPacket` size for the typical case of
     /// packets only containing ACKs and STREAM frames.
@@ -304,6 +335,8 @@ pub(super) struct SentPacket {
     ///
     /// The actual application data is stored with the stream state.
     pub(super) stream_frames: frame::StreamMetaVec,
+    /// Delivery state when the packet was sent, if it counts towards bytes in flight
+    pub(super) delivery: Option<DeliverySnapshot>,
 }
 
 /// Represents one or more packets that are deemed lost.
@@ -328,6 +361,8 @@ pub struct Retransmits {
     pub(super) retire_cids: Vec<u64>,
     pub(super) ack_frequency: bool,
     pub(super) handshake_done: bool,
+    /// Report the peer's current address to it
+    pub(super) observed_addr: bool,
     /// For each enqueued NEW_TOKEN frame, a copy of the path's remote address
     ///
     /// There are 2 reasons this is unusual:
@@ -363,6 +398,7 @@ impl Retransmits {
             && self.retire_cids.is_empty()
             && !self.ack_frequency
             && !self.handshake_done
+            && !self.observed_addr
             && self.new_tokens.is_empty()
     }
 }
@@ -386,6 +422,7 @@ impl ::std::ops::BitOrAssign for Retransmits {
         self.retire_cids.extend(rhs.retire_cids);
         self.ack_frequency |= rhs.ack_frequency;
         self.handshake_done |= rhs.handshake_done;
+        self.observed_addr |= rhs.observed_addr;
         self.new_tokens.extend_from_slice(&rhs.new_tokens);
     }
 }
@@ -626,6 +663,10 @@ pub(super) struct PendingAcks {
     largest_ack_eliciting_packet: Option<u64>,
     /// The largest acknowledged packet number sent in an ACK frame
     largest_acked: Option<u64>,
+    /// Number of further packets each ACK frame is repeated in
+    redundancy: u32,
+    /// Number of packets the most recent ACK frame remains to be repeated in
+    repetitions_pending: u32,
 }
 
 impl PendingAcks {
@@ -641,12 +682,26 @@ impl PendingAcks {
             largest_packet: None,
             largest_ack_eliciting_packet: None,
             largest_acked: None,
+            redundancy: 0,
+            repetitions_pending: 0,
         }
     }
 
     pub(super) fn set_ack_frequency_params(&mut self, frame: &frame::AckFrequency) {
-        self.ack_eliciting_threshold = frame.ack_eliciting_threshold.into_inner();
-        self.reordering_threshold = frame.reordering_threshold.into_inner();
+        self.set_thresholds(
+            frame.ack_eliciting_threshold.into_inner(),
+            frame.reordering_threshold.into_inner(),
+        );
+    }
+
+    pub(super) fn set_thresholds(&mut self, ack_eliciting: u64, reordering: u64) {
+        self.ack_eliciting_threshold = ack_eliciting;
+        self.reordering_threshold = reordering;
+    }
+
+    pub(super) fn set_redundancy(&mut self, redundancy: u32) {
+        self.redundancy = redundancy;
+        self.repetitions_pending = self.repetitions_pending.min(redundancy);
     }
 
     pub(super) fn set_immediate_ack_required(&mut self) {
@@ -664,7 +719,12 @@ impl PendingAcks {
 
     /// Whether any ACK frames can be sent
     pub(super) fn can_send(&self) -> bool {
-        self.immediate_ack_required && !self.ranges.is_empty()
+        (self.immediate_ack_required || self.repetitions_pending > 0) && !self.ranges.is_empty()
+    }
+
+    /// Whether the only ACK frames to send are repetitions of one already sent
+    pub(super) fn only_repetitions(&self) -> bool {
+        !self.immediate_ack_required && self.repetitions_pending > 0
     }
 
     /// Returns the delay since the packet with the largest packet number was received
@@ -764,6 +824,10 @@ impl PendingAcks {
         // new packet, which is suboptimal, because we already received them. Our assumption here is
         // that simplicity results in code that is more performant, even in the presence of
         // occasional redundant retransmits.
+        self.repetitions_pending = match self.immediate_ack_required {
+            true => self.redundancy,
+            false => self.repetitions_pending.saturating_sub(1),
+        };
         self.immediate_ack_required = false;
         self.ack_eliciting_since_last_ack_sent = 0;
         self.non_ack_eliciting_since_last_ack_sent = 0;
@@ -771,22 +835,37 @@ impl PendingAcks {
         self.largest_acked = self.largest_ack_eliciting_packet;
     }
 
-    /// Insert one packet that needs to be acknowledged
-    pub(super) fn insert_one(&mut self, packet: u64, now: Instant) {
-        self.ranges.insert_one(packet);
-
+    /// Insert one packet that needs to be acknowledged, tracking at most `max_ranges` ranges
+    ///
+    /// Returns whether a range was evicted to stay within the limit.
+    pub(super) fn insert_one(&mut self, packet: u64, now: Instant, max_ranges: usize) -> bool {
         if self.largest_packet.is_none_or(|(pn, _)| packet > pn) {
             self.largest_packet = Some((packet, now));
         }
 
-        if self.ranges.len() > MAX_ACK_BLOCKS {
+        // A packet below every tracked range would be evicted right away, so don't insert it
+        if self.ranges.len() >= max_ranges
+            && self
+                .ranges
+                .iter()
+                .next()
+                .is_some_and(|x| packet + 1 < x.start)
+        {
+            return true;
+        }
+
+        self.ranges.insert_one(packet);
+        let mut evicted = false;
+        while self.ranges.len() > max_ranges {
             self.ranges.pop_min();
+            evicted = true;
         }
+        evicted
     }
 
-    /// Remove ACKs of packets numbered at or below `max` from the set of pending ACKs
-    pub(super) fn subtract_below(&mut self, max: u64) {
-        self.ranges.remove(0..(max + 1));
+    /// Remove ACKs of packets numbered within `range` from the set of pending ACKs
+    pub(super) fn subtract(&mut self, range: Range<u64>) {
+        self.ranges.remove(range);
     }
 
     /// Returns the set of currently pending ACK ranges
@@ -901,9 +980,6 @@ impl PacketNumberFilter {
     }
 }
 
-/// Ensures we can always fit all our ACKs in a single minimum-MTU packet with room to spare
-const MAX_ACK_BLOCKS: usize = 64;
-
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1047,7 +1123,7 @@ mod test {
         // Receive ack-eliciting packet
         dedup.insert(0);
         let now = Instant::now();
-        acks.insert_one(0, now);
+        acks.insert_one(0, now, 64);
         acks.packet_received(now, 0, true, &dedup);
 
         // Sanity check
@@ -1059,6 +1135,22 @@ mod test {
         assert!(acks.can_send());
     }
 
+    #[test]
+    fn pending_acks_truncated_frame_keeps_omitted_ranges() {
+        let mut acks = PendingAcks::new();
+        let now = Instant::now();
+        for packet in [1, 2, 3, 5, 10, 11, 14] {
+            acks.insert_one(packet, now, 64);
+        }
+        // Room for the largest range and one more
+        let smallest = frame::Ack::encode(0, &acks.ranges, None, 7, &mut Vec::new());
+        assert_eq!(smallest, 10);
+
+        // Acknowledgement of the frame only retires the ranges it carried
+        acks.subtract(smallest..15);
+        assert_eq!(acks.ranges.iter().collect::<Vec<_>>(), [1..4, 5..6]);
+    }
+
     #[test]
     fn pending_acks_ack_delay() {
         let mut acks = PendingAcks::new();
@@ -1073,7 +1165,7 @@ mod test {
 
         // In-order packet
         dedup.insert(0);
-        acks.insert_one(0, t1);
+        acks.insert_one(0, t1, 64);
         acks.packet_received(t1, 0, true, &dedup);
         assert_eq!(acks.ack_delay(t1), Duration::from_millis(0));
         assert_eq!(acks.ack_delay(t2), Duration::from_millis(2));
@@ -1081,22 +1173,74 @@ mod test {
 
         // Out of order (higher than expected)
         dedup.insert(3);
-        acks.insert_one(3, t2);
+        acks.insert_one(3, t2, 64);
         acks.packet_received(t2, 3, true, &dedup);
         assert_eq!(acks.ack_delay(t2), Duration::from_millis(0));
         assert_eq!(acks.ack_delay(t3), Duration::from_millis(5));
 
         // Out of order (lower than expected, so previous instant is kept)
         dedup.insert(2);
-        acks.insert_one(2, t3);
+        acks.insert_one(2, t3, 64);
         acks.packet_received(t3, 2, true, &dedup);
         assert_eq!(acks.ack_delay(t3), Duration::from_millis(5));
     }
 
+    #[test]
+    fn pending_acks_range_limit() {
+        let mut acks = PendingAcks::new();
+        let now = Instant::now();
+        for packet in [10, 12, 14] {
+            assert!(!acks.insert_one(packet, now, 3));
+        }
+        // Adjacent packets merge into existing ranges
+        assert!(!acks.insert_one(11, now, 3));
+        assert_eq!(acks.ranges().len(), 2);
+
+        // The lowest range is evicted, and packets below it are ignored
+        assert!(!acks.insert_one(16, now, 3));
+        assert!(acks.insert_one(18, now, 3));
+        assert!(acks.insert_one(0, now, 3));
+        assert_eq!(
+            acks.ranges().iter().collect::<Vec<_>>(),
+            [14..15, 16..17, 18..19]
+        );
+    }
+
     #[test]
     fn sent_packet_size() {
         // The tracking state of sent packets should be minimal, and not grow
         // over time.
         assert!(size_of::<SentPacket>() <= 128);
     }
+
+    #[test]
+    fn packet_number_filter() {
+        let mut rng = rand::rng();
+        let mut filter = PacketNumberFilter::new(&mut rng);
+        let mut space = PacketSpace::new(Instant::now());
+        // Allocate until a packet number is skipped, which happens below 64
+        let mut expected = 0;
+        loop {
+            let n = filter.allocate(&mut rng, &mut space);
+            if n != expected {
+                break;
+            }
+            expected += 1;
+        }
+        let skipped = expected;
+        assert!(skipped < 64);
+
+        assert!(filter.check_ack(SpaceId::Data, 0..=skipped).is_err());
+        assert!(filter.check_ack(SpaceId::Data, skipped..=skipped).is_err());
+        assert!(
+            filter
+                .check_ack(SpaceId::Data, skipped + 1..=skipped + 1)
+                .is_ok()
+        );
+        if skipped > 0 {
+            assert!(filter.check_ack(SpaceId::Data, 0..=skipped - 1).is_ok());
+        }
+        // Other spaces don't skip packet numbers
+        assert!(filter.check_ack(SpaceId::Initial, 0..=skipped).is_ok());
+    }
 }
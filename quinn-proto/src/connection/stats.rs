@@ -1,6 +1,8 @@
 //! Connection statistics
 
-use crate::{Dir, Duration, frame::Frame};
+use std::sync::Arc;
+
+use crate::{Dir, Duration, Instant, SpaceId, frame::Frame};
 
 /// Statistics about UDP datagrams transmitted or received on a connection
 ///
@@ -16,13 +18,79 @@ pub struct UdpStats {
     ///
     /// Can be less than `datagrams` when GSO, GRO, and/or batched system calls are in use.
     pub ios: u64,
+    /// Bytes of application data carried in STREAM and DATAGRAM frames, including retransmissions
+    pub payload_bytes: u64,
+    /// Distribution of the sizes of the UDP datagrams
+    pub sizes: DatagramSizeHistogram,
 }
 
 impl UdpStats {
-    pub(crate) fn on_sent(&mut self, datagrams: u64, bytes: usize) {
+    /// Record an I/O operation carrying `datagrams` datagrams of `segment_size` bytes each, except
+    /// for the last one which may be shorter
+    pub(crate) fn on_sent(&mut self, datagrams: u64, bytes: usize, segment_size: usize) {
         self.datagrams += datagrams;
         self.bytes += bytes as u64;
         self.ios += 1;
+        let full = datagrams.saturating_sub(1);
+        self.sizes.record_n(segment_size, full);
+        self.sizes
+            .record(bytes.saturating_sub(full as usize * segment_size));
+    }
+
+    pub(crate) fn on_received(&mut self, bytes: usize) {
+        self.datagrams += 1;
+        self.bytes += bytes as u64;
+        self.sizes.record(bytes);
+    }
+
+    /// Fraction of the bytes in UDP datagrams that isn't application data
+    ///
+    /// Covers packet headers, encryption overhead, padding, acknowledgements and other control
+    /// frames, as well as the framing of the application data itself. Retransmitted application
+    /// data counts as payload. Returns `None` if no bytes were transferred.
+    pub fn overhead(&self) -> Option<f64> {
+        if self.bytes == 0 {
+            return None;
+        }
+        let overhead = self.bytes.saturating_sub(self.payload_bytes);
+        Some(overhead as f64 / self.bytes as f64)
+    }
+}
+
+/// Distribution of the sizes of UDP datagrams
+///
+/// Datagrams are counted in fixed buckets, bounded by powers of two and the sizes common on the
+/// Internet, as listed by [`BOUNDS`](Self::BOUNDS).
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DatagramSizeHistogram {
+    /// Number of datagrams per bucket, followed by the number of datagrams exceeding all bounds
+    counts: [u64; Self::BOUNDS.len() + 1],
+}
+
+impl DatagramSizeHistogram {
+    /// Inclusive upper bounds of the buckets, in bytes
+    pub const BOUNDS: [u16; 7] = [64, 128, 256, 512, 1024, 1200, 1500];
+
+    fn record(&mut self, size: usize) {
+        self.record_n(size, 1);
+    }
+
+    fn record_n(&mut self, size: usize, n: u64) {
+        let bucket = Self::BOUNDS.partition_point(|&bound| usize::from(bound) < size);
+        self.counts[bucket] += n;
+    }
+
+    /// Total number of datagrams
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Inclusive upper bound and number of datagrams of each bucket, in ascending order
+    ///
+    /// The final bucket, without a bound, counts datagrams exceeding all bounds.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u16>, u64)> + '_ {
+        let bounds = Self::BOUNDS.iter().copied().map(Some).chain([None]);
+        bounds.zip(self.counts.iter().copied())
     }
 }
 
@@ -45,6 +113,7 @@ pub struct FrameStats {
     pub max_streams_uni: u64,
     pub new_connection_id: u64,
     pub new_token: u64,
+    pub observed_addr: u64,
     pub path_challenge: u64,
     pub path_response: u64,
     pub ping: u64,
@@ -95,38 +164,49 @@ impl FrameStats {
             Frame::AckFrequency(_) => self.ack_frequency += 1,
             Frame::ImmediateAck => self.immediate_ack += 1,
             Frame::HandshakeDone => self.handshake_done = self.handshake_done.saturating_add(1),
+            Frame::ObservedAddr(_) => self.observed_addr += 1,
         }
     }
+
+    /// Frame type names with the corresponding counts
+    pub(crate) fn counts(&self) -> [(&'static str, u64); 25] {
+        [
+            ("ACK", self.acks),
+            ("ACK_FREQUENCY", self.ack_frequency),
+            ("CONNECTION_CLOSE", self.connection_close),
+            ("CRYPTO", self.crypto),
+            ("DATA_BLOCKED", self.data_blocked),
+            ("DATAGRAM", self.datagram),
+            ("HANDSHAKE_DONE", self.handshake_done.into()),
+            ("IMMEDIATE_ACK", self.immediate_ack),
+            ("MAX_DATA", self.max_data),
+            ("MAX_STREAM_DATA", self.max_stream_data),
+            ("MAX_STREAMS_BIDI", self.max_streams_bidi),
+            ("MAX_STREAMS_UNI", self.max_streams_uni),
+            ("NEW_CONNECTION_ID", self.new_connection_id),
+            ("NEW_TOKEN", self.new_token),
+            ("OBSERVED_ADDRESS", self.observed_addr),
+            ("PATH_CHALLENGE", self.path_challenge),
+            ("PATH_RESPONSE", self.path_response),
+            ("PING", self.ping),
+            ("RESET_STREAM", self.reset_stream),
+            ("RETIRE_CONNECTION_ID", self.retire_connection_id),
+            ("STREAM_DATA_BLOCKED", self.stream_data_blocked),
+            ("STREAMS_BLOCKED_BIDI", self.streams_blocked_bidi),
+            ("STREAMS_BLOCKED_UNI", self.streams_blocked_uni),
+            ("STOP_SENDING", self.stop_sending),
+            ("STREAM", self.stream),
+        ]
+    }
 }
 
 impl std::fmt::Debug for FrameStats {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("FrameStats")
-            .field("ACK", &self.acks)
-            .field("ACK_FREQUENCY", &self.ack_frequency)
-            .field("CONNECTION_CLOSE", &self.connection_close)
-            .field("CRYPTO", &self.crypto)
-            .field("DATA_BLOCKED", &self.data_blocked)
-            .field("DATAGRAM", &self.datagram)
-            .field("HANDSHAKE_DONE", &self.handshake_done)
-            .field("IMMEDIATE_ACK", &self.immediate_ack)
-            .field("MAX_DATA", &self.max_data)
-            .field("MAX_STREAM_DATA", &self.max_stream_data)
-            .field("MAX_STREAMS_BIDI", &self.max_streams_bidi)
-            .field("MAX_STREAMS_UNI", &self.max_streams_uni)
-            .field("NEW_CONNECTION_ID", &self.new_connection_id)
-            .field("NEW_TOKEN", &self.new_token)
-            .field("PATH_CHALLENGE", &self.path_challenge)
-            .field("PATH_RESPONSE", &self.path_response)
-            .field("PING", &self.ping)
-            .field("RESET_STREAM", &self.reset_stream)
-            .field("RETIRE_CONNECTION_ID", &self.retire_connection_id)
-            .field("STREAM_DATA_BLOCKED", &self.stream_data_blocked)
-            .field("STREAMS_BLOCKED_BIDI", &self.streams_blocked_bidi)
-            .field("STREAMS_BLOCKED_UNI", &self.streams_blocked_uni)
-            .field("STOP_SENDING", &self.stop_sending)
-            .field("STREAM", &self.stream)
-            .finish()
+        let mut s = f.debug_struct("FrameStats");
+        for (name, count) in self.counts() {
+            s.field(name, &count);
+        }
+        s.finish()
     }
 }
 
@@ -136,16 +216,46 @@ impl std::fmt::Debug for FrameStats {
 pub struct PathStats {
     /// Current best estimate of this connection's latency (round-trip-time)
     pub rtt: Duration,
+    /// The most recent RTT sample
+    pub latest_rtt: Duration,
+    /// Variation of the RTT samples, as per RFC 9002 §5.3
+    pub rtt_var: Duration,
+    /// Minimum RTT, see [`RttEstimatorConfig::min_rtt_window`](crate::RttEstimatorConfig::min_rtt_window)
+    pub min_rtt: Duration,
+    /// Median of the RTT samples taken on the connection, see [`RttHistogram::quantile`]
+    ///
+    /// Zero until the first sample is taken.
+    pub rtt_p50: Duration,
+    /// 95th percentile of the RTT samples taken on the connection
+    pub rtt_p95: Duration,
+    /// 99th percentile of the RTT samples taken on the connection
+    pub rtt_p99: Duration,
     /// Current congestion window of the connection
     pub cwnd: u64,
     /// Congestion events on the connection
     pub congestion_events: u64,
     /// Spurious congestion events on the connection
     pub spurious_congestion_events: u64,
+    /// Time during which data was held back because the congestion window was full
+    ///
+    /// Stalls that are still ongoing aren't included.
+    pub congestion_blocked_time: Duration,
     /// The amount of packets lost on this path
     pub lost_packets: u64,
     /// The amount of bytes lost on this path
     pub lost_bytes: u64,
+    /// The amount of packets deemed lost on this path which were acknowledged later on
+    ///
+    /// Data carried by such packets was retransmitted needlessly. Only acknowledgments arriving
+    /// within two probe timeouts of a packet being sent are detected.
+    pub spurious_lost_packets: u64,
+    /// The amount of stream data bytes sent again after being deemed lost
+    pub retransmitted_bytes: u64,
+    /// The number of times the probe timeout expired, i.e. tail losses were probed for
+    pub pto_count: u64,
+    /// The number of times sending was held back by the anti-amplification limit, i.e. until
+    /// more data is received from an unvalidated peer address
+    pub anti_amplification_blocked: u64,
     /// The amount of packets sent on this path
     pub sent_packets: u64,
     /// The amount of PLPMTUD probe packets sent on this path (also counted by `sent_packets`)
@@ -159,6 +269,46 @@ pub struct PathStats {
     pub current_mtu: u16,
 }
 
+/// Points in time at which a connection's handshake made progress
+///
+/// Allows decomposing connection setup latency. Each field is `None` until the respective event
+/// has occurred.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct HandshakeTimings {
+    /// When the first Initial packet was sent
+    pub initial_sent: Option<Instant>,
+    /// When the first Initial packet was received
+    pub initial_received: Option<Instant>,
+    /// When the keys protecting Handshake packets became available
+    pub handshake_keys: Option<Instant>,
+    /// When the handshake was confirmed
+    pub confirmed: Option<Instant>,
+    /// When the first 1-RTT packet was sent, i.e. the first packet that may carry application
+    /// data with full security guarantees
+    pub one_rtt_sent: Option<Instant>,
+    /// When the first 1-RTT packet was received
+    pub one_rtt_received: Option<Instant>,
+}
+
+impl HandshakeTimings {
+    pub(crate) fn on_sent(&mut self, now: Instant, space: SpaceId, is_1rtt: bool) {
+        match space {
+            SpaceId::Initial => self.initial_sent.get_or_insert(now),
+            SpaceId::Data if is_1rtt => self.one_rtt_sent.get_or_insert(now),
+            _ => return,
+        };
+    }
+
+    pub(crate) fn on_received(&mut self, now: Instant, space: SpaceId, is_1rtt: bool) {
+        match space {
+            SpaceId::Initial => self.initial_received.get_or_insert(now),
+            SpaceId::Data if is_1rtt => self.one_rtt_received.get_or_insert(now),
+            _ => return,
+        };
+    }
+}
+
 /// Connection statistics
 #[derive(Debug, Default, Copy, Clone)]
 #[non_exhaustive]
@@ -173,4 +323,161 @@ pub struct ConnectionStats {
     pub frame_rx: FrameStats,
     /// Statistics related to the current transmission path
     pub path: PathStats,
+    /// Progress of the handshake
+    pub handshake: HandshakeTimings,
+    /// Time during which writes were blocked by the connection-level flow control limit of the
+    /// peer
+    ///
+    /// Stalls that are still ongoing aren't included.
+    pub flow_control_blocked_time: Duration,
+    /// Datagrams dropped for exceeding
+    /// [`PeerLimitsConfig::max_datagrams_per_second`](crate::PeerLimitsConfig::max_datagrams_per_second)
+    pub rate_limited_datagrams: u64,
+    /// Ranges of received packets currently awaiting acknowledgement, across all packet number
+    /// spaces
+    pub ack_ranges: u64,
+    /// Ranges of received packets that were dropped from acknowledgement to stay within
+    /// [`TransportConfig::max_ack_ranges`](crate::TransportConfig::max_ack_ranges)
+    pub ack_ranges_evicted: u64,
+    /// Packets that arrived before they could be processed, and were held back until the
+    /// handshake progressed
+    pub deferred_packets: u64,
+    /// Packets that arrived before they could be processed, and were dropped for exceeding
+    /// [`TransportConfig::max_deferred_packets`](crate::TransportConfig::max_deferred_packets)
+    /// or [`TransportConfig::max_deferred_bytes`](crate::TransportConfig::max_deferred_bytes)
+    pub deferred_packets_dropped: u64,
+    /// Bytes of padding added to UDP datagrams by
+    /// [`TransportConfig::padding_policy`](crate::TransportConfig::padding_policy)
+    pub padding_bytes: u64,
+}
+
+/// Distribution of the RTT samples taken on a connection
+///
+/// Samples are counted in buckets, as configured by
+/// [`TransportConfig::rtt_histogram_buckets`](crate::TransportConfig::rtt_histogram_buckets).
+/// Unlike the smoothed RTT, this reveals paths alternating between distinct latencies, as is
+/// common on wireless links.
+#[derive(Debug, Clone)]
+pub struct RttHistogram {
+    bounds: Arc<[Duration]>,
+    /// Number of samples per bucket, followed by the number of samples exceeding all bounds
+    counts: Vec<u64>,
+    max: Duration,
+}
+
+impl RttHistogram {
+    pub(crate) fn new(bounds: Arc<[Duration]>) -> Self {
+        Self {
+            counts: vec![0; bounds.len() + 1],
+            bounds,
+            max: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn record(&mut self, sample: Duration) {
+        let bucket = self.bounds.partition_point(|&bound| bound < sample);
+        self.counts[bucket] += 1;
+        self.max = self.max.max(sample);
+    }
+
+    /// Total number of samples taken
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Inclusive upper bound and number of samples of each bucket, in ascending order
+    ///
+    /// The final bucket, without a bound, counts samples exceeding all bounds.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<Duration>, u64)> + '_ {
+        let bounds = self.bounds.iter().copied().map(Some).chain([None]);
+        bounds.zip(self.counts.iter().copied())
+    }
+
+    /// Estimate the `q`-quantile of the samples, with `q` ranging from 0 to 1
+    ///
+    /// Yields the upper bound of the bucket the quantile falls into, or the largest sample if
+    /// that is smaller. Returns `None` if no samples were taken.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        let bound = self.buckets().find_map(|(bound, n)| {
+            seen += n;
+            (seen >= rank).then_some(bound)
+        })?;
+        Some(bound.map_or(self.max, |bound| bound.min(self.max)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datagram_sizes() {
+        let mut stats = UdpStats::default();
+        assert_eq!(stats.overhead(), None);
+        // A GSO batch of three full-sized datagrams and a short one
+        stats.on_sent(4, 3 * 1200 + 100, 1200);
+        stats.on_sent(1, 1500, 1500);
+        stats.on_sent(1, 1501, 1501);
+        assert_eq!(stats.datagrams, 6);
+        assert_eq!(stats.sizes.count(), 6);
+        assert_eq!(
+            stats.sizes.buckets().collect::<Vec<_>>(),
+            [
+                (Some(64), 0),
+                (Some(128), 1),
+                (Some(256), 0),
+                (Some(512), 0),
+                (Some(1024), 0),
+                (Some(1200), 3),
+                (Some(1500), 1),
+                (None, 1)
+            ]
+        );
+
+        let stats = UdpStats {
+            bytes: 1000,
+            payload_bytes: 250,
+            ..UdpStats::default()
+        };
+        assert_eq!(stats.overhead(), Some(0.75));
+    }
+
+    #[test]
+    fn rtt_quantiles() {
+        let ms = Duration::from_millis;
+        let mut histogram = RttHistogram::new([ms(10), ms(20), ms(50), ms(100)].into());
+        assert_eq!(histogram.quantile(0.5), None);
+
+        // Bimodal distribution
+        for _ in 0..90 {
+            histogram.record(ms(15));
+        }
+        for _ in 0..10 {
+            histogram.record(ms(80));
+        }
+        assert_eq!(histogram.count(), 100);
+        assert_eq!(histogram.quantile(0.5), Some(ms(20)));
+        assert_eq!(histogram.quantile(0.9), Some(ms(20)));
+        assert_eq!(histogram.quantile(0.95), Some(ms(80)));
+        assert_eq!(histogram.quantile(0.0), Some(ms(20)));
+
+        histogram.record(ms(300));
+        assert_eq!(histogram.quantile(1.0), Some(ms(300)));
+        assert_eq!(
+            histogram.buckets().collect::<Vec<_>>(),
+            [
+                (Some(ms(10)), 0),
+                (Some(ms(20)), 90),
+                (Some(ms(50)), 0),
+                (Some(ms(100)), 10),
+                (None, 1)
+            ]
+        );
+    }
 }
@@ -0,0 +1,80 @@
+use rustc_hash::FxHashMap;
+
+use crate::StreamId;
+
+/// Statistics of a group of streams, see [`Streams::set_group`](super::Streams::set_group)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StreamGroupStats {
+    /// Streams in the group which haven't been fully closed
+    pub open_streams: u64,
+    /// Bytes written by the application to streams of the group
+    pub bytes_written: u64,
+    /// Bytes read by the application from streams of the group
+    pub bytes_read: u64,
+}
+
+/// Application-defined groups of streams, such as the streams of a WebTransport session
+#[derive(Debug, Default)]
+pub(super) struct StreamGroups {
+    members: FxHashMap<StreamId, u64>,
+    stats: FxHashMap<u64, StreamGroupStats>,
+}
+
+impl StreamGroups {
+    pub(super) fn get(&self, id: StreamId) -> Option<u64> {
+        self.members.get(&id).copied()
+    }
+
+    pub(super) fn stats(&self, group: u64) -> Option<StreamGroupStats> {
+        self.stats.get(&group).copied()
+    }
+
+    /// Move an open stream into `group`, or out of any group if `None`
+    pub(super) fn set(&mut self, id: StreamId, group: Option<u64>) {
+        self.remove_stream(id);
+        if let Some(group) = group {
+            self.members.insert(id, group);
+            self.stats.entry(group).or_default().open_streams += 1;
+        }
+    }
+
+    /// A stream was fully closed
+    pub(super) fn remove_stream(&mut self, id: StreamId) {
+        if let Some(group) = self.members.remove(&id) {
+            let stats = self.stats.get_mut(&group).unwrap();
+            stats.open_streams -= 1;
+        }
+    }
+
+    pub(super) fn on_written(&mut self, id: StreamId, bytes: u64) {
+        if let Some(stats) = self.stats_mut(id) {
+            stats.bytes_written += bytes;
+        }
+    }
+
+    pub(super) fn on_read(&mut self, id: StreamId, bytes: u64) {
+        if let Some(stats) = self.stats_mut(id) {
+            stats.bytes_read += bytes;
+        }
+    }
+
+    /// Forget `group`, returning its open streams and final statistics
+    pub(super) fn remove(&mut self, group: u64) -> Option<(Vec<StreamId>, StreamGroupStats)> {
+        let stats = self.stats.remove(&group)?;
+        let mut streams = Vec::new();
+        self.members.retain(|&id, &mut x| {
+            if x == group {
+                streams.push(id);
+            }
+            x != group
+        });
+        streams.sort_unstable();
+        Some((streams, stats))
+    }
+
+    fn stats_mut(&mut self, id: StreamId) -> Option<&mut StreamGroupStats> {
+        let group = self.members.get(&id)?;
+        self.stats.get_mut(group)
+    }
+}
@@ -14,14 +14,22 @@ use crate::{
     frame,
 };
 
+mod group;
+pub use group::StreamGroupStats;
+use group::StreamGroups;
+
 mod recv;
 use recv::Recv;
 pub use recv::{Chunks, ReadError, ReadableError};
 
+mod scheduler;
+use scheduler::SchedulerMetrics;
+pub use scheduler::StreamSchedulerStats;
+
 mod send;
 pub(crate) use send::{ByteSlice, BytesArray};
 use send::{BytesSource, Send, SendState};
-pub use send::{FinishError, WriteError, Written};
+pub use send::{FinishError, SendBlockReason, SendFlowControl, WriteError, Written};
 
 mod state;
 #[allow(unreachable_pub)] // fuzzing only
@@ -49,6 +57,9 @@ impl<'a> Streams<'a> {
         }
 
         if self.state.next[dir as usize] >= self.state.max[dir as usize] {
+            if !self.state.streams_blocked[dir as usize] {
+                self.state.blocked_unreported[dir as usize] = true;
+            }
             self.state.streams_blocked[dir as usize] = true;
             return None;
         }
@@ -88,6 +99,35 @@ impl<'a> Streams<'a> {
         self.state.send_streams
     }
 
+    /// Assign an open stream to an application-defined group, or remove it from its group
+    ///
+    /// Groups let protocols that multiplex sessions over a connection, like WebTransport, track
+    /// the streams of each session through [`group_stats`](Self::group_stats), and close them
+    /// together through [`Connection::close_stream_group`](super::Connection::close_stream_group).
+    /// A stream leaves its group once fully closed.
+    pub fn set_group(&mut self, id: StreamId, group: Option<u64>) -> Result<(), ClosedStream> {
+        if !self.state.send.contains_key(&id) && !self.state.recv.contains_key(&id) {
+            return Err(ClosedStream { _private: () });
+        }
+        self.state.groups.set(id, group);
+        Ok(())
+    }
+
+    /// The group a stream was assigned to with [`set_group`](Self::set_group)
+    pub fn group(&self, id: StreamId) -> Option<u64> {
+        self.state.groups.get(id)
+    }
+
+    /// Statistics of a group, if any stream was assigned to it since it was last closed
+    pub fn group_stats(&self, group: u64) -> Option<StreamGroupStats> {
+        self.state.groups.stats(group)
+    }
+
+    /// Statistics of how stream data was scheduled for transmission
+    pub fn scheduler_stats(&self) -> StreamSchedulerStats {
+        self.state.scheduler.stats()
+    }
+
     /// The number of remotely initiated open streams of a certain directionality.
     ///
     /// Includes remotely initiated streams, which have not been accepted via [`accept`](Self::accept).
@@ -129,6 +169,31 @@ impl RecvStream<'_> {
         Chunks::new(self.id, ordered, self.state, self.pending)
     }
 
+    /// Reassemble incoming data directly into `buffer`
+    ///
+    /// Data is copied into `buffer` as it arrives, at the position of its stream offset modulo the
+    /// length of `buffer`, and can be accessed in place with [`Chunks::ring_readable()`]. This
+    /// avoids buffering received data in intermediate allocations, as long as `buffer` is at least
+    /// as long as the stream receive window. Data beyond the end of `buffer` is buffered as usual
+    /// until room is made with [`Chunks::consume()`]. [`read()`](Self::read) remains usable, but
+    /// copies data out of `buffer`.
+    ///
+    /// Data buffered so far, including that in a previously set ring buffer, is moved into
+    /// `buffer`. Only ordered reads are supported while a ring buffer is set. Panics if `buffer`
+    /// is empty.
+    pub fn set_ring_buffer(&mut self, buffer: Box<[u8]>) -> Result<(), ReadableError> {
+        assert!(!buffer.is_empty(), "ring buffer must not be empty");
+        let Some(stream) = self.state.recv.get_mut(&self.id) else {
+            return Err(ReadableError::ClosedStream);
+        };
+        let stream = get_or_insert_recv(self.state.stream_receive_window)(stream);
+        if stream.stopped {
+            return Err(ReadableError::ClosedStream);
+        }
+        stream.assembler.set_ring(buffer)?;
+        Ok(())
+    }
+
     /// Stop accepting data on the given receive stream
     ///
     /// Discards unread data and notifies the peer to stop transmitting. Once stopped, further
@@ -267,6 +332,7 @@ impl<'a> SendStream<'a> {
         let written = stream.write(source, limit)?;
         self.state.data_sent += written.bytes as u64;
         self.state.unacked_data += written.bytes as u64;
+        self.state.groups.on_written(self.id, written.bytes as u64);
         trace!(stream = %self.id, "wrote {} bytes", written.bytes);
         if !was_pending {
             self.state.pending.push_pending(self.id, stream.priority);
@@ -376,6 +442,8 @@ struct PendingStreamsQueue {
     /// A monotonically decreasing counter, used to implement round-robin scheduling for streams of the same priority.
     /// Underflowing is not a practical concern, as it is initialized to u64::MAX and only decremented by 1 in `push_pending`
     recency: u64,
+    /// Streams pushed since the last transmission opportunity, for measuring how long they wait
+    newly_queued: Vec<StreamId>,
 }
 
 impl PendingStreamsQueue {
@@ -384,6 +452,7 @@ impl PendingStreamsQueue {
             streams: BinaryHeap::new(),
             next: None,
             recency: u64::MAX,
+            newly_queued: Vec::new(),
         }
     }
 
@@ -409,6 +478,7 @@ impl PendingStreamsQueue {
         // This is enough to implement round-robin scheduling for streams that are still pending even after being handled,
         // as in that case they are removed from the `BinaryHeap`, handled, and then immediately reinserted.
         self.recency -= 1;
+        self.newly_queued.push(id);
         self.streams.push(PendingStream {
             priority,
             recency: self.recency,
@@ -416,6 +486,30 @@ impl PendingStreamsQueue {
         });
     }
 
+    /// Move a queued stream to where `priority` places it, if that differs from its current place
+    ///
+    /// A stream reinserted by `reinsert_pending()` loses its precedence if a higher priority stream
+    /// is queued.
+    fn requeue(&mut self, id: StreamId, priority: i32) {
+        if let Some(next) = self.next.as_ref().filter(|x| x.id == id) {
+            if next.priority == priority
+                && self.streams.peek().is_none_or(|x| x.priority <= priority)
+            {
+                return;
+            }
+            self.next = None;
+        } else if self
+            .streams
+            .iter()
+            .any(|x| x.id == id && x.priority != priority)
+        {
+            self.streams.retain(|x| x.id != id);
+        } else {
+            return;
+        }
+        self.push_pending(id, priority);
+    }
+
     fn pop(&mut self) -> Option<PendingStream> {
         self.next.take().or_else(|| self.streams.pop())
     }
@@ -423,6 +517,7 @@ impl PendingStreamsQueue {
     fn clear(&mut self) {
         self.next = None;
         self.streams.clear();
+        self.newly_queued.clear();
     }
 
     fn iter(&self) -> impl Iterator<Item = &PendingStream> {
@@ -6,6 +6,7 @@ use tracing::debug;
 
 use super::state::get_or_insert_recv;
 use super::{ClosedStream, Retransmits, ShouldTransmit, StreamId, StreamsState};
+use crate::connection::RecvStreamDebugState;
 use crate::connection::assembler::{Assembler, Chunk, IllegalOrderedRead};
 use crate::connection::streams::state::StreamRecv;
 use crate::{TransportError, VarInt, frame};
@@ -158,6 +159,24 @@ impl Recv {
         matches!(self.state, RecvState::Recv { .. })
     }
 
+    pub(super) fn debug_state(&self) -> RecvStreamDebugState {
+        RecvStreamDebugState {
+            state: match self.state {
+                RecvState::Recv { .. } => "recv",
+                RecvState::ResetRecvd { .. } => "reset_recvd",
+            },
+            bytes_read: self.assembler.bytes_read(),
+            end: self.end,
+            max_data: self.sent_max_stream_data,
+            final_size: self.final_offset(),
+            reset_code: match self.state {
+                RecvState::ResetRecvd { error_code, .. } => Some(error_code),
+                RecvState::Recv { .. } => None,
+            },
+            stopped: self.stopped,
+        }
+    }
+
     fn final_offset(&self) -> Option<u64> {
         match self.state {
             RecvState::Recv { size } => size,
@@ -299,9 +318,63 @@ impl<'a> Chunks<'a> {
 
         if let Some(chunk) = rs.assembler.read(max_length, self.ordered) {
             self.read += chunk.bytes.len() as u64;
+            self.streams
+                .groups
+                .on_read(self.id, chunk.bytes.len() as u64);
             return Ok(Some(chunk));
         }
 
+        self.drained().map(|()| None)
+    }
+
+    /// Data reassembled into the ring buffer of the stream, without consuming it
+    ///
+    /// Yields `Ok(None)` if the stream was finished. Otherwise, yields the contiguous data
+    /// following the read offset as two slices, the second of which is non-empty if the data wraps
+    /// around the end of the ring buffer. Release data with [`consume()`](Self::consume) to make
+    /// room for more.
+    ///
+    /// See [`RecvStream::set_ring_buffer()`](super::RecvStream::set_ring_buffer).
+    pub fn ring_readable(&mut self) -> Result<Option<[&[u8]; 2]>, ReadError> {
+        let readable = matches!(
+            self.state,
+            ChunksState::Readable(ref rs) if !rs.assembler.ring_readable().0.is_empty()
+        );
+        if !readable {
+            return self.drained().map(|()| None);
+        }
+        let ChunksState::Readable(ref rs) = self.state else {
+            unreachable!("state must be ChunkState::Readable");
+        };
+        let (first, second) = rs.assembler.ring_readable();
+        Ok(Some([first, second]))
+    }
+
+    /// Release the first `len` bytes of [`ring_readable()`](Self::ring_readable)
+    ///
+    /// Panics if fewer bytes are readable.
+    pub fn consume(&mut self, len: usize) {
+        let ChunksState::Readable(ref mut rs) = self.state else {
+            panic!("consumed more than readable");
+        };
+        rs.assembler.consume(len);
+        self.read += len as u64;
+        self.streams.groups.on_read(self.id, len as u64);
+    }
+
+    /// Handle a read finding no data, returning `Ok` if the stream is finished
+    fn drained(&mut self) -> Result<(), ReadError> {
+        let rs = match self.state {
+            ChunksState::Readable(ref mut rs) => rs,
+            ChunksState::Reset(error_code) => {
+                return Err(ReadError::Reset(error_code));
+            }
+            ChunksState::Finished => {
+                return Ok(());
+            }
+            ChunksState::Finalized => panic!("must not read after finalize()"),
+        };
+
         match rs.state {
             RecvState::ResetRecvd { error_code, .. } => {
                 debug_assert_eq!(self.read, 0, "reset streams have empty buffers");
@@ -323,7 +396,7 @@ impl<'a> Chunks<'a> {
                         _ => unreachable!("state must be ChunkState::Readable"),
                     };
                     self.streams.stream_recv_freed(self.id, recv);
-                    Ok(None)
+                    Ok(())
                 } else {
                     // We don't need a distinct `ChunksState` variant for a blocked stream because
                     // retrying a read harmlessly re-traces our steps back to returning
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use rustc_hash::FxHashMap;
+
+use crate::{Duration, Instant, StreamId};
+
+/// Statistics of the scheduling of stream data for transmission, see
+/// [`Streams::scheduler_stats`](super::Streams::scheduler_stats)
+///
+/// Intended to help tune stream priorities.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StreamSchedulerStats {
+    /// Longest time a stream with data ready to send waited to be serviced
+    ///
+    /// Measured from the first transmission opportunity after the stream became ready, or from the
+    /// last time it was serviced if it remained ready. Waits that are still ongoing aren't included.
+    pub max_wait: Duration,
+    /// Bytes of stream data sent, including retransmissions, by the priority of their stream
+    pub bytes_by_priority: BTreeMap<i32, u64>,
+    /// Transmission attempts in which queued stream data was held back by a full congestion window
+    pub congestion_limited: u64,
+    /// Transmission attempts in which no stream data could be sent because the peer's
+    /// connection-level flow control limit was reached
+    pub flow_control_limited: u64,
+}
+
+/// Tracks how streams with pending data are serviced
+#[derive(Debug, Default)]
+pub(super) struct SchedulerMetrics {
+    stats: StreamSchedulerStats,
+    /// When each stream with pending data started waiting to be serviced
+    waiting_since: FxHashMap<StreamId, Instant>,
+}
+
+impl SchedulerMetrics {
+    pub(super) fn stats(&self) -> StreamSchedulerStats {
+        self.stats.clone()
+    }
+
+    /// A stream that became pending was offered a transmission opportunity
+    pub(super) fn on_ready(&mut self, id: StreamId, now: Instant) {
+        self.waiting_since.entry(id).or_insert(now);
+    }
+
+    /// `bytes` of a stream's data were written out
+    pub(super) fn on_serviced(
+        &mut self,
+        id: StreamId,
+        now: Instant,
+        priority: i32,
+        bytes: u64,
+        still_pending: bool,
+    ) {
+        if let Some(since) = self.waiting_since.remove(&id) {
+            self.stats.max_wait = self
+                .stats
+                .max_wait
+                .max(now.saturating_duration_since(since));
+        }
+        if still_pending {
+            self.waiting_since.insert(id, now);
+        }
+        *self.stats.bytes_by_priority.entry(priority).or_default() += bytes;
+    }
+
+    /// A stream left the queue without being serviced, e.g. because it was reset
+    pub(super) fn on_dropped(&mut self, id: StreamId) {
+        self.waiting_since.remove(&id);
+    }
+
+    pub(super) fn on_transmit_attempt(
+        &mut self,
+        congestion_limited: bool,
+        flow_control_limited: bool,
+    ) {
+        if congestion_limited {
+            self.stats.congestion_limited += 1;
+        } else if flow_control_limited {
+            self.stats.flow_control_limited += 1;
+        }
+    }
+
+    /// Forget all waiting streams, keeping statistics
+    pub(super) fn clear(&mut self) {
+        self.waiting_since.clear();
+    }
+}
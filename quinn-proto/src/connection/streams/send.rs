@@ -1,7 +1,11 @@
 use bytes::Bytes;
 use thiserror::Error;
 
-use crate::{VarInt, connection::send_buffer::SendBuffer, frame};
+use crate::{
+    VarInt,
+    connection::{SendStreamDebugState, send_buffer::SendBuffer},
+    frame,
+};
 
 #[derive(Debug)]
 pub(super) struct Send {
@@ -30,6 +34,22 @@ impl Send {
         })
     }
 
+    pub(super) fn debug_state(&self) -> SendStreamDebugState {
+        SendStreamDebugState {
+            state: match self.state {
+                SendState::Ready => "ready",
+                SendState::DataSent { .. } => "data_sent",
+                SendState::ResetSent => "reset_sent",
+            },
+            offset: self.pending.offset(),
+            unacked: self.pending.unacked(),
+            max_data: self.max_data,
+            priority: self.priority,
+            retransmits_pending: self.pending.has_retransmits(),
+            stop_reason: self.stop_reason,
+        }
+    }
+
     /// Whether the stream has been reset
     pub(super) fn is_reset(&self) -> bool {
         matches!(self.state, SendState::ResetSent)
@@ -254,6 +274,47 @@ pub struct Written {
     pub chunks: usize,
 }
 
+/// Send-side flow control state of a stream, see [`Connection::send_flow_control()`]
+///
+/// [`Connection::send_flow_control()`]: crate::Connection::send_flow_control
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub struct SendFlowControl {
+    /// Limit on the stream's data, granted by the peer
+    pub stream_max_data: u64,
+    /// Bytes written to the stream
+    pub stream_data_sent: u64,
+    /// Limit on stream data across the connection, granted by the peer
+    pub connection_max_data: u64,
+    /// Bytes written to all streams of the connection
+    pub connection_data_sent: u64,
+    /// Bytes written to all streams of the connection and not yet acknowledged
+    pub unacked_data: u64,
+    /// Limit on `unacked_data`, see [`TransportConfig::send_window()`]
+    ///
+    /// [`TransportConfig::send_window()`]: crate::TransportConfig::send_window
+    pub send_window: u64,
+    /// The limit currently holding the stream back, if any
+    pub blocked: Option<SendBlockReason>,
+}
+
+/// Limit preventing progress on a send stream, see [`SendFlowControl::blocked`]
+///
+/// Flow control and the send window cause writes to fail with [`WriteError::Blocked`], whereas the
+/// congestion controller delays the transmission of data that was already written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[non_exhaustive]
+pub enum SendBlockReason {
+    /// The peer's limit on the stream's data was reached
+    Stream,
+    /// The peer's limit on stream data across the connection was reached
+    Connection,
+    /// The local limit on unacknowledged data was reached
+    SendWindow,
+    /// The congestion window is full
+    Congestion,
+}
+
 /// Errors triggered while writing to a send stream
 #[derive(Debug, Error, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub enum WriteError {
@@ -9,13 +9,14 @@ use rustc_hash::FxHashMap;
 use tracing::{debug, trace};
 
 use super::{
-    PendingStreamsQueue, Recv, Retransmits, Send, SendState, ShouldTransmit, StreamEvent,
+    ClosedStream, PendingStreamsQueue, Recv, Retransmits, SchedulerMetrics, Send, SendBlockReason,
+    SendFlowControl, SendState, ShouldTransmit, StreamEvent, StreamGroupStats, StreamGroups,
     StreamHalf, ThinRetransmits,
 };
 use crate::{
-    Dir, MAX_STREAM_COUNT, Side, StreamId, TransportError, VarInt,
+    Dir, Instant, MAX_STREAM_COUNT, Side, StreamId, TransportError, VarInt,
     coding::BufMutExt,
-    connection::stats::FrameStats,
+    connection::{FlowControlDebugState, StreamDebugState, stats::FrameStats},
     frame::{self, FrameStruct, StreamMetaVec},
     transport_parameters::TransportParameters,
 };
@@ -95,6 +96,10 @@ pub struct StreamsState {
     opened: [bool; 2],
     // Next to report to the application, once opened
     pub(super) next_reported_remote: [u64; 2],
+    /// Next remotely initiated stream index to announce through [`TransportEvent::StreamOpened`]
+    ///
+    /// [`TransportEvent::StreamOpened`]: crate::TransportEvent::StreamOpened
+    next_announced_remote: [u64; 2],
     /// Number of outbound streams
     ///
     /// This differs from `self.send.len()` in that it does not include streams that the peer is
@@ -123,6 +128,10 @@ pub struct StreamsState {
     data_recvd: u64,
     /// Total quantity of unacknowledged outgoing data
     pub(super) unacked_data: u64,
+    /// Total quantity of stream data sent again after being deemed lost
+    retransmitted_bytes: u64,
+    /// Number of streams the peer reset or stopped, ignoring redundant frames
+    peer_resets: u64,
     /// Configured upper bound for `unacked_data`.
     ///
     /// Note this may be less than `unacked_data` if the user has set a new value.
@@ -139,6 +148,11 @@ pub struct StreamsState {
     receive_window_shrink_debt: u64,
     /// Whether the locally-initiated stream limit has been hit, per direction
     pub(super) streams_blocked: [bool; 2],
+    /// Whether the stream limit was hit since the last call to `poll_blocked`, per direction
+    pub(super) blocked_unreported: [bool; 2],
+    /// Application-defined groups of streams
+    pub(super) groups: StreamGroups,
+    pub(super) scheduler: SchedulerMetrics,
 }
 
 impl StreamsState {
@@ -166,6 +180,7 @@ impl StreamsState {
             next_remote: [0, 0],
             opened: [false, false],
             next_reported_remote: [0, 0],
+            next_announced_remote: [0, 0],
             send_streams: 0,
             pending: PendingStreamsQueue::new(),
             events: VecDeque::new(),
@@ -177,6 +192,8 @@ impl StreamsState {
             data_sent: 0,
             data_recvd: 0,
             unacked_data: 0,
+            retransmitted_bytes: 0,
+            peer_resets: 0,
             send_window,
             stream_receive_window: stream_receive_window.into(),
             initial_max_stream_data_uni: 0u32.into(),
@@ -184,6 +201,9 @@ impl StreamsState {
             initial_max_stream_data_bidi_remote: 0u32.into(),
             receive_window_shrink_debt: 0,
             streams_blocked: [false, false],
+            blocked_unreported: [false, false],
+            groups: StreamGroups::default(),
+            scheduler: SchedulerMetrics::default(),
         };
 
         for dir in Dir::iter() {
@@ -245,6 +265,7 @@ impl StreamsState {
         }
 
         self.pending.clear();
+        self.scheduler.clear();
         self.send_streams = 0;
         self.data_sent = 0;
         self.connection_blocked.clear();
@@ -332,6 +353,7 @@ impl StreamsState {
             // Redundant reset
             return Ok(ShouldTransmit(false));
         }
+        self.peer_resets += 1;
         let bytes_read = rs.assembler.bytes_read();
         let stopped = rs.stopped;
         let end = rs.end;
@@ -367,6 +389,7 @@ impl StreamsState {
         };
 
         if stream.try_stop(error_code) {
+            self.peer_resets += 1;
             self.events
                 .push_back(StreamEvent::Stopped { id, error_code });
             self.on_stream_frame(false, id);
@@ -396,6 +419,26 @@ impl StreamsState {
         })
     }
 
+    /// Whether any stream is open in either direction
+    pub(crate) fn has_open_streams(&self) -> bool {
+        self.send.values().any(|s| s.is_some())
+            || self
+                .recv
+                .values()
+                .any(|s| s.as_ref().is_some_and(|s| s.as_open_recv().is_some()))
+    }
+
+    /// Whether stream `id`'s data below `offset` has been transmitted at least once
+    ///
+    /// Also true if the stream has been reset or is no longer tracked, since then no more of its
+    /// data will be sent.
+    pub(crate) fn is_sent_up_to(&self, id: StreamId, offset: u64) -> bool {
+        self.send
+            .get(&id)
+            .and_then(|s| s.as_ref())
+            .is_none_or(|s| s.is_reset() || s.pending.sent_offset() >= offset)
+    }
+
     /// Whether MAX_STREAM_DATA frames could be sent for stream `id`
     pub(crate) fn can_send_flow_control(&self, id: StreamId) -> bool {
         self.recv
@@ -405,6 +448,21 @@ impl StreamsState {
             .is_some_and(|s| s.can_send_flow_control())
     }
 
+    /// Directionality of locally-initiated streams whose limit was newly hit, if any
+    pub(in crate::connection) fn poll_blocked(&mut self) -> Option<Dir> {
+        Dir::iter().find(|&dir| mem::take(&mut self.blocked_unreported[dir as usize]))
+    }
+
+    /// Remotely initiated stream opened since the last call, if any
+    pub(in crate::connection) fn poll_opened(&mut self) -> Option<StreamId> {
+        let dir = Dir::iter().find(|&dir| {
+            self.next_announced_remote[dir as usize] < self.next_remote[dir as usize]
+        })?;
+        let index = self.next_announced_remote[dir as usize];
+        self.next_announced_remote[dir as usize] += 1;
+        Some(StreamId::new(!self.side, dir, index))
+    }
+
     pub(in crate::connection) fn write_control_frames(
         &mut self,
         buf: &mut Vec<u8>,
@@ -558,10 +616,14 @@ impl StreamsState {
 
     pub(crate) fn write_stream_frames(
         &mut self,
+        now: Instant,
         buf: &mut Vec<u8>,
         max_buf_size: usize,
         fair: bool,
     ) -> StreamMetaVec {
+        for id in self.pending.newly_queued.drain(..) {
+            self.scheduler.on_ready(id, now);
+        }
         let mut stream_frames = StreamMetaVec::new();
         while buf.len() + frame::Stream::SIZE_BOUND < max_buf_size {
             if max_buf_size
@@ -581,6 +643,7 @@ impl StreamsState {
 
             let Some(stream) = self.send.get_mut(&id).and_then(|s| s.as_mut()) else {
                 // Stream was reset with pending data and the reset was acknowledged
+                self.scheduler.on_dropped(id);
                 continue;
             };
 
@@ -588,19 +651,31 @@ impl StreamsState {
             // hasn't acknowledged the reset, but should not generate STREAM frames, so we need to
             // check for them explicitly.
             if stream.is_reset() {
+                self.scheduler.on_dropped(id);
                 continue;
             }
 
             // Now that we know the `StreamId`, we can better account for how many bytes
             // are required to encode it.
             let max_buf_size = max_buf_size - buf.len() - 1 - VarInt::size(id.into());
+            let retransmit = stream.pending.has_retransmits();
             let (offsets, encode_length) = stream.pending.poll_transmit(max_buf_size);
+            if retransmit {
+                self.retransmitted_bytes += offsets.end - offsets.start;
+            }
             let fin = offsets.end == stream.pending.offset()
                 && matches!(stream.state, SendState::DataSent { .. });
             if fin {
                 stream.fin_pending = false;
             }
 
+            self.scheduler.on_serviced(
+                id,
+                now,
+                stream.priority,
+                offsets.end - offsets.start,
+                stream.is_pending(),
+            );
             if stream.is_pending() {
                 // If the stream still has pending data, reinsert it, possibly with an updated priority value
                 // Fairness with other streams is achieved by implementing round-robin scheduling,
@@ -680,13 +755,19 @@ impl StreamsState {
         self.events.push_back(StreamEvent::Finished { id });
     }
 
-    pub(crate) fn retransmit(&mut self, frame: frame::StreamMeta) {
+    /// Queue lost data for retransmission
+    ///
+    /// If `reprioritize` is set, a stream that's already queued is moved according to its current
+    /// priority.
+    pub(crate) fn retransmit(&mut self, frame: frame::StreamMeta, reprioritize: bool) {
         let Some(stream) = self.send.get_mut(&frame.id).and_then(|s| s.as_mut()) else {
             // Loss of data on a closed stream is a noop
             return;
         };
         if !stream.is_pending() {
             self.pending.push_pending(frame.id, stream.priority);
+        } else if reprioritize {
+            self.pending.requeue(frame.id, stream.priority);
         }
         stream.fin_pending |= frame.fin;
         stream.pending.retransmit(frame.offsets);
@@ -786,6 +867,105 @@ impl StreamsState {
             .min(self.send_window.saturating_sub(self.unacked_data))
     }
 
+    /// Whether writes are blocked by the connection-level flow control limit set by the peer
+    pub(crate) fn flow_control_blocked(&self) -> bool {
+        !self.connection_blocked.is_empty() && self.data_sent >= self.max_data
+    }
+
+    /// Account for a transmission attempt, which found the congestion window full if `cwnd_full`
+    pub(crate) fn on_transmit_attempt(&mut self, cwnd_full: bool) {
+        let queued = self.can_send_stream_data();
+        self.scheduler
+            .on_transmit_attempt(cwnd_full && queued, !queued && self.flow_control_blocked());
+    }
+
+    /// Total quantity of stream data sent again after being deemed lost
+    pub(crate) fn retransmitted_bytes(&self) -> u64 {
+        self.retransmitted_bytes
+    }
+
+    /// Number of streams the peer reset or stopped, ignoring redundant frames
+    pub(crate) fn peer_resets(&self) -> u64 {
+        self.peer_resets
+    }
+
+    /// Send-side flow control state of stream `id`, given whether the congestion window is full
+    pub(crate) fn send_flow_control(
+        &self,
+        id: StreamId,
+        cwnd_full: bool,
+    ) -> Result<SendFlowControl, ClosedStream> {
+        let (stream_max_data, stream_data_sent) = match self.send.get(&id) {
+            Some(Some(s)) => (s.max_data, s.pending.offset()),
+            Some(None) => (self.max_send_data(id).into(), 0),
+            None => return Err(ClosedStream { _private: () }),
+        };
+        let blocked = if stream_data_sent >= stream_max_data {
+            Some(SendBlockReason::Stream)
+        } else if self.data_sent >= self.max_data {
+            Some(SendBlockReason::Connection)
+        } else if self.unacked_data >= self.send_window {
+            Some(SendBlockReason::SendWindow)
+        } else if cwnd_full {
+            Some(SendBlockReason::Congestion)
+        } else {
+            None
+        };
+        Ok(SendFlowControl {
+            stream_max_data,
+            stream_data_sent,
+            connection_max_data: self.max_data,
+            connection_data_sent: self.data_sent,
+            unacked_data: self.unacked_data,
+            send_window: self.send_window,
+            blocked,
+        })
+    }
+
+    pub(crate) fn flow_control_debug_state(&self) -> FlowControlDebugState {
+        FlowControlDebugState {
+            max_data: self.max_data,
+            data_sent: self.data_sent,
+            local_max_data: self.local_max_data,
+            data_received: self.data_recvd,
+            unacked_data: self.unacked_data,
+            send_window: self.send_window,
+            max_streams: self.max,
+            max_remote_streams: self.max_remote,
+        }
+    }
+
+    /// State of the streams that have an open half, ordered by ID
+    pub(crate) fn streams_debug_state(&self) -> Vec<StreamDebugState> {
+        let mut ids = self
+            .send
+            .iter()
+            .filter(|(_, s)| s.is_some())
+            .map(|(&id, _)| id)
+            .chain(
+                self.recv
+                    .iter()
+                    .filter(|(_, r)| r.as_ref().is_some_and(|r| r.as_open_recv().is_some()))
+                    .map(|(&id, _)| id),
+            )
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.into_iter()
+            .map(|id| StreamDebugState {
+                id,
+                send: self
+                    .send
+                    .get(&id)
+                    .and_then(|s| Some(s.as_ref()?.debug_state())),
+                recv: self
+                    .recv
+                    .get(&id)
+                    .and_then(|r| Some(r.as_ref()?.as_open_recv()?.debug_state())),
+            })
+            .collect()
+    }
+
     /// Yield stream events
     pub(crate) fn poll(&mut self) -> Option<StreamEvent> {
         if let Some(dir) = Dir::iter().find(|&i| mem::replace(&mut self.opened[i as usize], false))
@@ -951,6 +1131,17 @@ impl StreamsState {
         if half == StreamHalf::Send {
             self.send_streams -= 1;
         }
+        if !self.send.contains_key(&id) && !self.recv.contains_key(&id) {
+            self.groups.remove_stream(id);
+        }
+    }
+
+    /// Forget a group of streams, returning its open streams and final statistics
+    pub(in crate::connection) fn remove_group(
+        &mut self,
+        group: u64,
+    ) -> Option<(Vec<StreamId>, StreamGroupStats)> {
+        self.groups.remove(group)
     }
 
     pub(super) fn stream_recv_freed(&mut self, id: StreamId, recv: StreamRecv) {
@@ -996,7 +1187,7 @@ pub(super) fn get_or_insert_recv(
 mod tests {
     use super::*;
     use crate::{
-        ReadableError, RecvStream, SendStream, TransportErrorCode, WriteError,
+        Duration, ReadableError, RecvStream, SendStream, TransportErrorCode, WriteError,
         connection::State as ConnState, connection::Streams,
     };
     use bytes::Bytes;
@@ -1397,7 +1588,7 @@ mod tests {
         high.write(b"high").unwrap();
 
         let mut buf = Vec::with_capacity(40);
-        let meta = server.write_stream_frames(&mut buf, 40, true);
+        let meta = server.write_stream_frames(Instant::now(), &mut buf, 40, true);
         assert_eq!(meta[0].id, id_high);
         assert_eq!(meta[1].id, id_mid);
         assert_eq!(meta[2].id, id_low);
@@ -1456,7 +1647,7 @@ mod tests {
         high.set_priority(-1).unwrap();
 
         let mut buf = Vec::with_capacity(1000);
-        let meta = server.write_stream_frames(&mut buf, 40, true);
+        let meta = server.write_stream_frames(Instant::now(), &mut buf, 40, true);
         assert_eq!(meta.len(), 1);
         assert_eq!(meta[0].id, id_high);
 
@@ -1464,7 +1655,7 @@ mod tests {
         assert_eq!(server.pending.len(), 2);
 
         // Send the remaining data. The initial mid priority one should go first now
-        let meta = server.write_stream_frames(&mut buf, 1000, true);
+        let meta = server.write_stream_frames(Instant::now(), &mut buf, 1000, true);
         assert_eq!(meta.len(), 2);
         assert_eq!(meta[0].id, id_mid);
         assert_eq!(meta[1].id, id_high);
@@ -1525,7 +1716,7 @@ mod tests {
             // loop until all the streams are written
             loop {
                 let buf_len = buf.len();
-                let meta = server.write_stream_frames(&mut buf, buf_len + 40, fair);
+                let meta = server.write_stream_frames(Instant::now(), &mut buf, buf_len + 40, fair);
                 if meta.is_empty() {
                     break;
                 }
@@ -1596,7 +1787,7 @@ mod tests {
 
         // Write the first chunk of stream_a
         let buf_len = buf.len();
-        let meta = server.write_stream_frames(&mut buf, buf_len + 40, false);
+        let meta = server.write_stream_frames(Instant::now(), &mut buf, buf_len + 40, false);
         assert!(!meta.is_empty());
         metas.extend(meta);
 
@@ -1613,7 +1804,7 @@ mod tests {
         // loop until all the streams are written
         loop {
             let buf_len = buf.len();
-            let meta = server.write_stream_frames(&mut buf, buf_len + 40, false);
+            let meta = server.write_stream_frames(Instant::now(), &mut buf, buf_len + 40, false);
             if meta.is_empty() {
                 break;
             }
@@ -1632,6 +1823,130 @@ mod tests {
         );
     }
 
+    #[test]
+    fn retransmit_at_stream_priority() {
+        for reprioritize in [true, false] {
+            let mut server = make(Side::Server);
+            server.set_params(&TransportParameters {
+                initial_max_streams_bidi: 3u32.into(),
+                initial_max_data: 300u32.into(),
+                initial_max_stream_data_bidi_remote: 300u32.into(),
+                ..TransportParameters::default()
+            });
+
+            let (mut pending, state) = (Retransmits::default(), ConnState::Established);
+            let mut streams = Streams {
+                state: &mut server,
+                conn_state: &state,
+            };
+
+            let id_low = streams.open(Dir::Bi).unwrap();
+            let id_high = streams.open(Dir::Bi).unwrap();
+
+            let mut low = SendStream {
+                id: id_low,
+                state: &mut server,
+                pending: &mut pending,
+                conn_state: &state,
+            };
+            low.set_priority(-1).unwrap();
+            low.write(&[b'l'; 100]).unwrap();
+
+            // Partially write out the low priority stream, which is then reinserted ahead of
+            // other streams
+            let mut buf = Vec::with_capacity(1024);
+            let buf_len = buf.len();
+            let lost = server.write_stream_frames(Instant::now(), &mut buf, buf_len + 40, false);
+            assert_eq!(lost[0].id, id_low);
+
+            let mut high = SendStream {
+                id: id_high,
+                state: &mut server,
+                pending: &mut pending,
+                conn_state: &state,
+            };
+            high.set_priority(1).unwrap();
+            high.write(&[b'h'; 10]).unwrap();
+
+            server.retransmit(lost[0].clone(), reprioritize);
+
+            let buf_len = buf.len();
+            let meta = server.write_stream_frames(Instant::now(), &mut buf, buf_len + 40, false);
+            if reprioritize {
+                // The lost data waits for the higher priority stream
+                assert_eq!(meta[0].id, id_high);
+            } else {
+                assert_eq!(meta[0].id, id_low);
+            }
+        }
+    }
+
+    #[test]
+    fn scheduler_stats() {
+        let mut server = make(Side::Server);
+        server.set_params(&TransportParameters {
+            initial_max_streams_bidi: 2u32.into(),
+            initial_max_data: 20u32.into(),
+            initial_max_stream_data_bidi_remote: 20u32.into(),
+            ..TransportParameters::default()
+        });
+
+        let (mut pending, state) = (Retransmits::default(), ConnState::Established);
+        let mut streams = Streams {
+            state: &mut server,
+            conn_state: &state,
+        };
+        let id_high = streams.open(Dir::Bi).unwrap();
+        let id_low = streams.open(Dir::Bi).unwrap();
+
+        let mut high = SendStream {
+            id: id_high,
+            state: &mut server,
+            pending: &mut pending,
+            conn_state: &state,
+        };
+        high.set_priority(1).unwrap();
+        high.write(&[0; 10]).unwrap();
+        let mut low = SendStream {
+            id: id_low,
+            state: &mut server,
+            pending: &mut pending,
+            conn_state: &state,
+        };
+        low.write(&[0; 10]).unwrap();
+
+        // Room for a single frame per call
+        let now = Instant::now();
+        let mut buf = Vec::with_capacity(100);
+        let meta = server.write_stream_frames(now, &mut buf, 30, true);
+        assert_eq!(meta.len(), 1);
+        assert_eq!(meta[0].id, id_high);
+        server.on_transmit_attempt(true);
+        let later = now + Duration::from_millis(10);
+        let buf_len = buf.len();
+        let meta = server.write_stream_frames(later, &mut buf, buf_len + 30, true);
+        assert_eq!(meta[0].id, id_low);
+
+        // The connection-level flow control limit is exhausted
+        let mut low = SendStream {
+            id: id_low,
+            state: &mut server,
+            pending: &mut pending,
+            conn_state: &state,
+        };
+        assert_eq!(low.write(&[0; 10]), Err(WriteError::Blocked));
+        server.on_transmit_attempt(false);
+
+        let stats = server.scheduler.stats();
+        assert_eq!(stats.max_wait, Duration::from_millis(10));
+        assert_eq!(
+            stats.bytes_by_priority.into_iter().collect::<Vec<_>>(),
+            [(0, 10), (1, 10)]
+        );
+        assert_eq!(stats.congestion_limited, 1);
+        assert_eq!(stats.flow_control_limited, 1);
+    }
+
     #[test]
     fn stop_finished() {
         let mut client = make(Side::Client);
@@ -1,4 +1,4 @@
-use crate::Instant;
+use crate::{Duration, Instant};
 
 #[derive(Debug, Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub(crate) enum Timer {
@@ -20,10 +20,14 @@ pub(crate) enum Timer {
     PushNewCid = 7,
     /// When to send an immediate ACK if there are unacked ack-eliciting packets of the peer
     MaxAckDelay = 8,
+    /// When to retransmit CONNECTION_CLOSE while waiting for the peer to respond
+    CloseRetransmit = 9,
+    /// When to retransmit or give up on a path validation requested by the application
+    PathProbe = 10,
 }
 
 impl Timer {
-    pub(crate) const VALUES: [Self; 9] = [
+    pub(crate) const VALUES: [Self; 11] = [
         Self::LossDetection,
         Self::Idle,
         Self::Close,
@@ -33,13 +37,15 @@ impl Timer {
         Self::Pacing,
         Self::PushNewCid,
         Self::MaxAckDelay,
+        Self::CloseRetransmit,
+        Self::PathProbe,
     ];
 }
 
 /// A table of data associated with each distinct kind of `Timer`
 #[derive(Debug, Copy, Clone, Default)]
 pub(crate) struct TimerTable {
-    data: [Option<Instant>; 10],
+    data: [Option<Instant>; 11],
 }
 
 impl TimerTable {
@@ -63,3 +69,39 @@ impl TimerTable {
         self.data[timer as usize].is_some_and(|x| x <= after)
     }
 }
+
+/// Round `time` up to the next multiple of `granularity` after `epoch`
+///
+/// Times before `epoch` and zero granularities are left unchanged.
+pub(super) fn round_up(time: Instant, epoch: Instant, granularity: Duration) -> Instant {
+    let granularity = granularity.as_nanos();
+    if granularity == 0 {
+        return time;
+    }
+    let since_epoch = time.saturating_duration_since(epoch).as_nanos();
+    let excess = since_epoch % granularity;
+    match excess {
+        0 => time,
+        _ => time + Duration::from_nanos((granularity - excess) as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_up_to_grid() {
+        let epoch = Instant::now();
+        let granularity = Duration::from_secs(1);
+        let round = |t: Duration| round_up(epoch + t, epoch, granularity) - epoch;
+        assert_eq!(round(Duration::ZERO), Duration::ZERO);
+        assert_eq!(round(Duration::from_millis(1)), Duration::from_secs(1));
+        assert_eq!(round(Duration::from_millis(999)), Duration::from_secs(1));
+        assert_eq!(round(Duration::from_secs(1)), Duration::from_secs(1));
+        assert_eq!(round(Duration::from_millis(1250)), Duration::from_secs(2));
+        // Unaffected without a granularity
+        let t = epoch + Duration::from_millis(1250);
+        assert_eq!(round_up(t, epoch, Duration::ZERO), t);
+    }
+}
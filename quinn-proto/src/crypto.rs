@@ -17,6 +17,9 @@ use crate::{
     transport_parameters::TransportParameters,
 };
 
+mod credentials;
+pub use credentials::PeerCredentials;
+
 /// Cryptography interface based on *ring*
 #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
 pub(crate) mod ring_like;
@@ -37,6 +40,14 @@ pub trait Session: Send + Sync + 'static {
     /// Get the peer's identity, if available
     fn peer_identity(&self) -> Option<Box<dyn Any>>;
 
+    /// Get the credentials the peer authenticated with, if available
+    ///
+    /// Unlike [`peer_identity()`](Self::peer_identity), doesn't depend on the session type.
+    /// Returns `None` by default, for sessions that don't support it.
+    fn peer_credentials(&self) -> Option<PeerCredentials> {
+        None
+    }
+
     /// Get the 0-RTT keys if available (clients only)
     ///
     /// On the client side, this method can be used to see if 0-RTT key material is available
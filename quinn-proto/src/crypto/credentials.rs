@@ -0,0 +1,134 @@
+use bytes::Bytes;
+
+/// Credentials the peer authenticated with during the handshake
+///
+/// Obtained through [`Session::peer_credentials()`](super::Session::peer_credentials), without
+/// having to downcast the session-specific [`peer_identity()`](super::Session::peer_identity).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PeerCredentials {
+    /// DER-encoded X.509 certificates, starting with the end-entity certificate
+    Certificates(Vec<Bytes>),
+    /// DER-encoded `SubjectPublicKeyInfo` of a raw public key, as per RFC 7250
+    RawPublicKey(Bytes),
+}
+
+impl PeerCredentials {
+    /// Classify the DER-encoded entries presented by the peer, end-entity first
+    ///
+    /// A single entry that is a `SubjectPublicKeyInfo` rather than a certificate is taken to be a
+    /// raw public key.
+    pub fn from_der(mut chain: Vec<Bytes>) -> Self {
+        match &chain[..] {
+            [key] if is_spki(key) => Self::RawPublicKey(chain.pop().unwrap()),
+            _ => Self::Certificates(chain),
+        }
+    }
+
+    /// The DER-encoded end-entity certificate, if the peer presented certificates
+    pub fn end_entity(&self) -> Option<&[u8]> {
+        match self {
+            Self::Certificates(chain) => chain.first().map(|cert| &cert[..]),
+            Self::RawPublicKey(_) => None,
+        }
+    }
+
+    /// The DER-encoded `SubjectPublicKeyInfo` of the peer's key
+    ///
+    /// Extracted from the end-entity certificate, if any. `None` if the certificate is malformed.
+    pub fn subject_public_key_info(&self) -> Option<&[u8]> {
+        match self {
+            Self::Certificates(chain) => certificate_spki(chain.first()?),
+            Self::RawPublicKey(key) => Some(key),
+        }
+    }
+
+    /// SHA-256 digest of the [`subject_public_key_info()`](Self::subject_public_key_info)
+    ///
+    /// Suitable for public key pinning, which unlike pinning certificates survives their renewal
+    /// with the same key. Comparable to the pins of RFC 7469 once base64-encoded.
+    #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+    pub fn spki_sha256(&self) -> Option<[u8; 32]> {
+        #[cfg(all(feature = "aws-lc-rs", not(feature = "ring")))]
+        use aws_lc_rs::digest;
+        #[cfg(feature = "ring")]
+        use ring::digest;
+
+        let digest = digest::digest(&digest::SHA256, self.subject_public_key_info()?);
+        digest.as_ref().try_into().ok()
+    }
+}
+
+/// Whether `der` consists of a `SubjectPublicKeyInfo`
+///
+/// That is a `SEQUENCE` of an `AlgorithmIdentifier` and a `BIT STRING`, whereas a certificate
+/// additionally holds a signature.
+fn is_spki(der: &[u8]) -> bool {
+    let Some(spki) = Element::parse(der).filter(|x| x.tag == SEQUENCE && x.rest.is_empty()) else {
+        return false;
+    };
+    let Some(algorithm) = Element::parse(spki.contents).filter(|x| x.tag == SEQUENCE) else {
+        return false;
+    };
+    Element::parse(algorithm.rest).is_some_and(|x| x.tag == BIT_STRING && x.rest.is_empty())
+}
+
+/// The encoded `subjectPublicKeyInfo` of an X.509 certificate, as per RFC 5280 §4.1
+fn certificate_spki(der: &[u8]) -> Option<&[u8]> {
+    let certificate = Element::parse(der).filter(|x| x.tag == SEQUENCE)?;
+    let mut tbs = Element::parse(certificate.contents)
+        .filter(|x| x.tag == SEQUENCE)?
+        .contents;
+    // Skip the optional version, serial number, signature algorithm, issuer, validity and subject
+    if tbs.first() == Some(&VERSION) {
+        tbs = Element::parse(tbs)?.rest;
+    }
+    for _ in 0..5 {
+        tbs = Element::parse(tbs)?.rest;
+    }
+    let spki = Element::parse(tbs).filter(|x| x.tag == SEQUENCE)?;
+    Some(spki.encoded)
+}
+
+/// A DER element split off the front of some input
+struct Element<'a> {
+    tag: u8,
+    contents: &'a [u8],
+    /// The complete encoding, including tag and length
+    encoded: &'a [u8],
+    /// Input following the element
+    rest: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    /// Only supports single-byte tags and lengths that fit in four bytes, which suffices for
+    /// certificates
+    fn parse(input: &'a [u8]) -> Option<Self> {
+        let (&tag, rest) = input.split_first()?;
+        let (&first, mut rest) = rest.split_first()?;
+        let len = match first {
+            0..=0x7f => usize::from(first),
+            0x81..=0x84 => {
+                let (bytes, remainder) = rest.split_at_checked(usize::from(first & 0x7f))?;
+                rest = remainder;
+                bytes
+                    .iter()
+                    .fold(0usize, |len, &byte| (len << 8) | usize::from(byte))
+            }
+            _ => return None,
+        };
+        let header = input.len() - rest.len();
+        let (contents, rest) = rest.split_at_checked(len)?;
+        Some(Self {
+            tag,
+            contents,
+            encoded: &input[..header + len],
+            rest,
+        })
+    }
+}
+
+const SEQUENCE: u8 = 0x30;
+const BIT_STRING: u8 = 0x03;
+/// Context-specific, constructed tag `[0]` of the certificate version
+const VERSION: u8 = 0xa0;
@@ -1,8 +1,13 @@
-use std::{any::Any, io, str, sync::Arc};
+use std::{
+    any::Any,
+    io::{self, Write},
+    str,
+    sync::{Arc, Mutex},
+};
 
 #[cfg(all(feature = "aws-lc-rs", not(feature = "ring")))]
 use aws_lc_rs::aead;
-use bytes::BytesMut;
+use bytes::{Bytes, BytesMut};
 #[cfg(feature = "ring")]
 use ring::aead;
 pub use rustls::Error;
@@ -97,6 +102,16 @@ impl crypto::Session for TlsSession {
         })
     }
 
+    fn peer_credentials(&self) -> Option<crypto::PeerCredentials> {
+        let chain = self.inner.peer_certificates()?;
+        Some(crypto::PeerCredentials::from_der(
+            chain
+                .iter()
+                .map(|cert| Bytes::copy_from_slice(cert))
+                .collect(),
+        ))
+    }
+
     fn early_crypto(&self) -> Option<(Box<dyn HeaderKey>, Box<dyn crypto::PacketKey>)> {
         let keys = self.inner.zero_rtt_keys()?;
         Some((Box::new(keys.header), Box::new(keys.packet)))
@@ -366,6 +381,21 @@ impl QuicClientConfig {
         config.enable_early_data = true;
         config
     }
+
+    /// Copy of this configuration that logs TLS secrets to `key_log`
+    ///
+    /// Unlike [`rustls::KeyLogFile`], which is controlled by the `SSLKEYLOGFILE` environment
+    /// variable for the whole process, this allows capturing secrets of select connections only,
+    /// e.g. by passing the result to `Endpoint::connect_with` or setting it as the default client
+    /// configuration of one endpoint while debugging. See [`KeyLogWriter`].
+    pub fn with_key_log(&self, key_log: Arc<dyn rustls::KeyLog>) -> Self {
+        let mut inner = (*self.inner).clone();
+        inner.key_log = key_log;
+        Self {
+            inner: Arc::new(inner),
+            initial: self.initial,
+        }
+    }
 }
 
 impl crypto::ClientConfig for QuicClientConfig {
@@ -502,6 +532,21 @@ impl QuicServerConfig {
         inner.max_early_data_size = u32::MAX;
         Ok(inner)
     }
+
+    /// Copy of this configuration that logs TLS secrets to `key_log`
+    ///
+    /// Unlike [`rustls::KeyLogFile`], which is controlled by the `SSLKEYLOGFILE` environment
+    /// variable for the whole process, this allows capturing secrets of a single endpoint, by
+    /// installing the result with `Endpoint::set_server_config`, or of a single connection,
+    /// through `Incoming::accept_with`. See [`KeyLogWriter`].
+    pub fn with_key_log(&self, key_log: Arc<dyn rustls::KeyLog>) -> Self {
+        let mut inner = (*self.inner).clone();
+        inner.key_log = key_log;
+        Self {
+            inner: Arc::new(inner),
+            initial: self.initial,
+        }
+    }
 }
 
 impl TryFrom<rustls::ServerConfig> for QuicServerConfig {
@@ -579,6 +624,58 @@ impl crypto::ServerConfig for QuicServerConfig {
     }
 }
 
+/// A [`rustls::KeyLog`] writing secrets to an arbitrary writer
+///
+/// Secrets are written in the NSS key log format understood by e.g. Wireshark, as with the
+/// `SSLKEYLOGFILE` environment variable. Failures to write are logged and otherwise ignored.
+pub struct KeyLogWriter {
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl KeyLogWriter {
+    /// Log secrets to `output`
+    ///
+    /// Each secret is written with a single call to [`Write::write_all`], so `output` should be
+    /// opened in append mode when shared with other processes.
+    pub fn new(output: impl Write + Send + 'static) -> Self {
+        Self {
+            output: Mutex::new(Box::new(output)),
+        }
+    }
+}
+
+impl rustls::KeyLog for KeyLogWriter {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        use std::fmt::Write as _;
+
+        let mut line =
+            String::with_capacity(label.len() + 2 * (client_random.len() + secret.len()) + 3);
+        line.push_str(label);
+        line.push(' ');
+        for byte in client_random {
+            let _ = write!(line, "{byte:02x}");
+        }
+        line.push(' ');
+        for byte in secret {
+            let _ = write!(line, "{byte:02x}");
+        }
+        line.push('\n');
+        let mut output = self.output.lock().unwrap();
+        if let Err(e) = output
+            .write_all(line.as_bytes())
+            .and_then(|()| output.flush())
+        {
+            tracing::warn!("failed to write key log: {e}");
+        }
+    }
+}
+
+impl std::fmt::Debug for KeyLogWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KeyLogWriter").finish_non_exhaustive()
+    }
+}
+
 pub(crate) fn initial_suite_from_provider(
     provider: &Arc<rustls::crypto::CryptoProvider>,
 ) -> Option<Suite> {
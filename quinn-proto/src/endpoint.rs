@@ -20,10 +20,10 @@ use tracing::{debug, error, trace, warn};
 use crate::{
     Duration, INITIAL_MTU, Instant, MAX_CID_SIZE, MIN_INITIAL_SIZE, RESET_TOKEN_SIZE, ResetToken,
     Side, Transmit, TransportConfig, TransportError,
-    cid_generator::ConnectionIdGenerator,
+    cid_generator::{ConnectionIdGenerator, RandomConnectionIdGenerator},
     coding::BufMutExt,
     config::{ClientConfig, EndpointConfig, ServerConfig},
-    connection::{Connection, ConnectionError, SideArgs},
+    connection::{Connection, ConnectionError, SideArgs, TimeoutCause, TimeoutTimer},
     crypto::{self, Keys, UnsupportedVersion},
     frame,
     packet::{
@@ -47,6 +47,9 @@ pub struct Endpoint {
     index: ConnectionIndex,
     connections: Slab<ConnectionMeta>,
     local_cid_generator: Box<dyn ConnectionIdGenerator>,
+    /// Describes the local CIDs of outgoing connections if
+    /// [`EndpointConfig::zero_length_client_cids`] is set
+    zero_length_cid_generator: RandomConnectionIdGenerator,
     config: Arc<EndpointConfig>,
     server_config: Option<Arc<ServerConfig>>,
     /// Whether the underlying UDP socket promises not to fragment packets
@@ -56,6 +59,10 @@ pub struct Endpoint {
     /// Buffered Initial and 0-RTT messages for pending incoming connections
     incoming_buffers: Slab<IncomingBuffer>,
     all_incoming_buffers_total_bytes: u64,
+    /// Origin of the grid that coalesced timers of connections are rounded to
+    ///
+    /// See [`TransportConfig::timer_coalescing`].
+    timer_epoch: Option<Instant>,
 }
 
 impl Endpoint {
@@ -80,12 +87,14 @@ impl Endpoint {
             index: ConnectionIndex::default(),
             connections: Slab::new(),
             local_cid_generator: (config.connection_id_generator_factory.as_ref())(),
+            zero_length_cid_generator: RandomConnectionIdGenerator::new(0),
             config,
             server_config,
             allow_mtud,
             last_stateless_reset: None,
             incoming_buffers: Slab::new(),
             all_incoming_buffers_total_bytes: 0,
+            timer_epoch: None,
         }
     }
 
@@ -150,9 +159,11 @@ impl Endpoint {
     ) -> Option<DatagramEvent> {
         // Partially decode packet or short-circuit if unable
         let datagram_len = data.len();
+        let cid_parser =
+            FixedLengthConnectionIdParser::new(self.short_header_cid_len(remote, &data));
         let event = match PartialDecode::new(
             data,
-            &FixedLengthConnectionIdParser::new(self.local_cid_generator.cid_len()),
+            &cid_parser,
             &self.config.supported_versions,
             self.config.grease_quic_bit,
         ) {
@@ -191,6 +202,8 @@ impl Endpoint {
                 return Some(DatagramEvent::Response(Transmit {
                     destination: remote,
                     ecn: None,
+                    dscp: None,
+                    flow_label: None,
                     size: buf.len(),
                     segment_size: None,
                     src_ip: local_ip,
@@ -315,12 +328,38 @@ impl Endpoint {
         Some(Transmit {
             destination: addresses.remote,
             ecn: None,
+            dscp: None,
+            flow_label: None,
             size: buf.len(),
             segment_size: None,
             src_ip: addresses.local_ip,
         })
     }
 
+    /// Length of the destination CID of a short-header packet received from `remote`
+    ///
+    /// Peers of outgoing connections with zero-length CIDs send packets without any, unless they
+    /// address another connection to the same peer by one of its CIDs.
+    fn short_header_cid_len(&self, remote: SocketAddr, data: &[u8]) -> usize {
+        let cid_len = self.local_cid_generator.cid_len();
+        if !self.config.zero_length_client_cids
+            || !self.index.outgoing_connection_remotes.contains_key(&remote)
+        {
+            return cid_len;
+        }
+        match data.get(1..1 + cid_len) {
+            Some(cid)
+                if self
+                    .index
+                    .connection_ids
+                    .contains_key(&ConnectionId::new(cid)) =>
+            {
+                cid_len
+            }
+            _ => 0,
+        }
+    }
+
     /// Initiate a connection
     pub fn connect(
         &mut self,
@@ -343,11 +382,20 @@ impl Endpoint {
         trace!(initial_dcid = %remote_id);
 
         let ch = ConnectionHandle(self.connections.vacant_key());
-        let loc_cid = self.new_cid(ch);
+        let zero_length_cids = self.config.zero_length_client_cids
+            && !self.index.outgoing_connection_remotes.contains_key(&remote);
+        let loc_cid = match zero_length_cids {
+            true => ConnectionId::new(&[]),
+            false => self.new_cid(ch),
+        };
+        let cid_gen: &dyn ConnectionIdGenerator = match zero_length_cids {
+            true => &self.zero_length_cid_generator,
+            false => self.local_cid_generator.as_ref(),
+        };
         let params = TransportParameters::new(
             &config.transport,
             &self.config,
-            self.local_cid_generator.as_ref(),
+            cid_gen,
             loc_cid,
             None,
             &mut self.rng,
@@ -550,17 +598,22 @@ impl Endpoint {
         let server_config =
             server_config.unwrap_or_else(|| self.server_config.as_ref().unwrap().clone());
 
-        if server_config
+        if let Some(timeout) = server_config
             .transport
             .max_idle_timeout
-            .is_some_and(|timeout| {
-                incoming.received_at + Duration::from_millis(timeout.into()) <= now
-            })
+            .map(|timeout| Duration::from_millis(timeout.into()))
+            .filter(|&timeout| incoming.received_at + timeout <= now)
         {
             debug!("abandoning accept of stale initial");
             self.index.remove_initial(dst_cid);
             return Err(Box::new(AcceptError {
-                cause: ConnectionError::TimedOut,
+                cause: ConnectionError::TimedOut(TimeoutCause {
+                    timer: TimeoutTimer::Handshake,
+                    timeout,
+                    since_last_sent: None,
+                    since_last_received: Some(now.saturating_duration_since(incoming.received_at)),
+                    keep_alive: false,
+                }),
                 response: None,
             }));
         }
@@ -641,6 +694,7 @@ impl Endpoint {
                 server_config,
                 pref_addr_cid,
                 path_validated: remote_address_validated,
+                resumption_hint: incoming.token.resumption_hint,
             },
         );
         self.index.insert_initial(dst_cid, ch);
@@ -767,6 +821,8 @@ impl Endpoint {
         Ok(Transmit {
             destination: incoming.addresses.remote,
             ecn: None,
+            dscp: None,
+            flow_label: None,
             size: buf.len(),
             segment_size: None,
             src_ip: incoming.addresses.local_ip,
@@ -806,6 +862,10 @@ impl Endpoint {
         self.rng.fill_bytes(&mut rng_seed);
         let side = side_args.side();
         let pref_addr_cid = side_args.pref_addr_cid();
+        let cid_gen: &dyn ConnectionIdGenerator = match loc_cid.is_empty() && side.is_client() {
+            true => &self.zero_length_cid_generator,
+            false => self.local_cid_generator.as_ref(),
+        };
         let conn = Connection::new(
             self.config.clone(),
             transport_config,
@@ -815,8 +875,9 @@ impl Endpoint {
             addresses.remote,
             addresses.local_ip,
             tls,
-            self.local_cid_generator.as_ref(),
+            cid_gen,
             now,
+            *self.timer_epoch.get_or_insert(now),
             version,
             self.allow_mtud,
             rng_seed,
@@ -881,6 +942,8 @@ impl Endpoint {
         Transmit {
             destination: addresses.remote,
             ecn: None,
+            dscp: None,
+            flow_label: None,
             size: buf.len(),
             segment_size: None,
             src_ip: addresses.local_ip,
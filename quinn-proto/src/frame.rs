@@ -1,6 +1,7 @@
 use std::{
     fmt::{self, Write},
     mem,
+    net::{IpAddr, SocketAddr},
     ops::{Range, RangeInclusive},
 };
 
@@ -133,6 +134,9 @@ frame_types! {
     // ACK Frequency
     ACK_FREQUENCY = 0xaf,
     IMMEDIATE_ACK = 0x1f,
+    // Address Discovery
+    OBSERVED_IPV4_ADDR = 0x9f81a6,
+    OBSERVED_IPV6_ADDR = 0x9f81a7,
     // DATAGRAM
 }
 
@@ -164,6 +168,7 @@ pub(crate) enum Frame {
     AckFrequency(AckFrequency),
     ImmediateAck,
     HandshakeDone,
+    ObservedAddr(ObservedAddr),
 }
 
 impl Frame {
@@ -205,6 +210,7 @@ impl Frame {
             AckFrequency(_) => FrameType::ACK_FREQUENCY,
             ImmediateAck => FrameType::IMMEDIATE_ACK,
             HandshakeDone => FrameType::HANDSHAKE_DONE,
+            ObservedAddr(ref x) => x.ty(),
         }
     }
 
@@ -285,6 +291,13 @@ impl FrameStruct for ConnectionClose {
 }
 
 impl ConnectionClose {
+    /// The reason for the close decoded as UTF-8, or `None` if it isn't valid UTF-8
+    ///
+    /// The specification recommends, but doesn't require, UTF-8 reasons.
+    pub fn reason_str(&self) -> Option<&str> {
+        str::from_utf8(&self.reason).ok()
+    }
+
     pub(crate) fn encode<W: BufMut>(&self, out: &mut W, max_len: usize) {
         out.write(FrameType::CONNECTION_CLOSE); // 1 byte
         out.write(self.error_code); // <= 8 bytes
@@ -328,6 +341,13 @@ impl FrameStruct for ApplicationClose {
 }
 
 impl ApplicationClose {
+    /// The reason for the close decoded as UTF-8, or `None` if it isn't valid UTF-8
+    ///
+    /// The specification recommends, but doesn't require, UTF-8 reasons.
+    pub fn reason_str(&self) -> Option<&str> {
+        str::from_utf8(&self.reason).ok()
+    }
+
     pub(crate) fn encode<W: BufMut>(&self, out: &mut W, max_len: usize) {
         out.write(FrameType::APPLICATION_CLOSE); // 1 byte
         out.write(self.error_code); // <= 8 bytes
@@ -378,16 +398,41 @@ impl<'a> IntoIterator for &'a Ack {
 }
 
 impl Ack {
+    /// Encode an ACK frame of at most `max_size` bytes
+    ///
+    /// The lowest ranges are omitted if they don't fit, but the largest range is always included.
+    /// Returns the smallest packet number acknowledged by the encoded frame.
     pub(crate) fn encode<W: BufMut>(
         delay: u64,
         ranges: &ArrayRangeSet,
         ecn: Option<&EcnCounts>,
+        max_size: usize,
         buf: &mut W,
-    ) {
+    ) -> u64 {
+        let var_size = |x: u64| VarInt::from_u64(x).unwrap().size();
         let mut rest = ranges.iter().rev();
         let first = rest.next().unwrap();
         let largest = first.end - 1;
         let first_size = first.end - first.start;
+
+        // The number of ranges can only shrink, so its encoding doesn't grow
+        let mut size = 1
+            + var_size(largest)
+            + var_size(delay)
+            + var_size(ranges.len() as u64 - 1)
+            + var_size(first_size - 1)
+            + ecn.map_or(0, |x| var_size(x.ect0) + var_size(x.ect1) + var_size(x.ce));
+        let mut count = 0;
+        let mut prev = first.start;
+        for block in ranges.iter().rev().skip(1) {
+            size += var_size(prev - block.end - 1) + var_size(block.end - block.start - 1);
+            if size > max_size {
+                break;
+            }
+            count += 1;
+            prev = block.start;
+        }
+
         buf.write(if ecn.is_some() {
             FrameType::ACK_ECN
         } else {
@@ -395,10 +440,10 @@ impl Ack {
         });
         buf.write_var(largest);
         buf.write_var(delay);
-        buf.write_var(ranges.len() as u64 - 1);
+        buf.write_var(count);
         buf.write_var(first_size - 1);
         let mut prev = first.start;
-        for block in rest {
+        for block in rest.take(count as usize) {
             let size = block.end - block.start;
             buf.write_var(prev - block.end - 1);
             buf.write_var(size - 1);
@@ -407,6 +452,7 @@ impl Ack {
         if let Some(x) = ecn {
             x.encode(buf)
         }
+        prev
     }
 
     pub(crate) fn iter(&self) -> AckIter<'_> {
@@ -695,6 +741,16 @@ impl Iter {
                 reordering_threshold: self.bytes.get()?,
             }),
             FrameType::IMMEDIATE_ACK => Frame::ImmediateAck,
+            FrameType::OBSERVED_IPV4_ADDR => Frame::ObservedAddr(ObservedAddr {
+                seq_no: self.bytes.get()?,
+                ip: IpAddr::V4(self.bytes.get()?),
+                port: self.bytes.get()?,
+            }),
+            FrameType::OBSERVED_IPV6_ADDR => Frame::ObservedAddr(ObservedAddr {
+                seq_no: self.bytes.get()?,
+                ip: IpAddr::V6(self.bytes.get()?),
+                port: self.bytes.get()?,
+            }),
             _ => {
                 if let Some(s) = ty.stream() {
                     Frame::Stream(Stream {
@@ -939,6 +995,55 @@ impl AckFrequency {
     }
 }
 
+/// The address from which a peer's packets are seen, per draft-ietf-quic-address-discovery
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct ObservedAddr {
+    /// Increases whenever the observed address changes, so stale reports can be ignored
+    pub(crate) seq_no: VarInt,
+    pub(crate) ip: IpAddr,
+    pub(crate) port: u16,
+}
+
+impl ObservedAddr {
+    pub(crate) fn new(seq_no: VarInt, address: SocketAddr) -> Self {
+        Self {
+            seq_no,
+            // IPv4-mapped addresses of dual-stack sockets are reported as IPv4
+            ip: address.ip().to_canonical(),
+            port: address.port(),
+        }
+    }
+
+    pub(crate) fn address(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    fn ty(&self) -> FrameType {
+        match self.ip {
+            IpAddr::V4(_) => FrameType::OBSERVED_IPV4_ADDR,
+            IpAddr::V6(_) => FrameType::OBSERVED_IPV6_ADDR,
+        }
+    }
+
+    pub(crate) fn encode<W: BufMut>(&self, buf: &mut W) {
+        buf.write(self.ty());
+        buf.write(self.seq_no);
+        match self.ip {
+            IpAddr::V4(ip) => buf.write(ip),
+            IpAddr::V6(ip) => buf.write(ip),
+        }
+        buf.write(self.port);
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        let ip = match self.ip {
+            IpAddr::V4(_) => 4,
+            IpAddr::V6(_) => 16,
+        };
+        VarInt(self.ty().0).size() + self.seq_no.size() + ip + 2
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -965,7 +1070,7 @@ mod test {
             ect1: 24,
             ce: 12,
         };
-        Ack::encode(42, &ranges, Some(&ECN), &mut buf);
+        Ack::encode(42, &ranges, Some(&ECN), usize::MAX, &mut buf);
         let frames = frames(buf);
         assert_eq!(frames.len(), 1);
         match frames[0] {
@@ -979,6 +1084,27 @@ mod test {
         }
     }
 
+    #[test]
+    fn ack_truncation() {
+        let mut ranges = ArrayRangeSet::new();
+        for packet in [1, 2, 3, 5, 10, 11, 14] {
+            ranges.insert(packet..packet + 1);
+        }
+        // Room for the largest range and one more
+        let mut buf = Vec::new();
+        assert_eq!(Ack::encode(42, &ranges, None, 7, &mut buf), 10);
+        assert_eq!(buf.len(), 7);
+        let frames = frames(buf);
+        match frames[0] {
+            Frame::Ack(ref ack) => {
+                let mut packets = ack.iter().flatten().collect::<Vec<_>>();
+                packets.sort_unstable();
+                assert_eq!(&packets[..], [10, 11, 14]);
+            }
+            ref x => panic!("incorrect frame {x:?}"),
+        }
+    }
+
     #[test]
     fn ack_frequency_coding() {
         let mut buf = Vec::new();
@@ -1005,4 +1131,28 @@ mod test {
         assert_eq!(frames.len(), 1);
         assert_matches!(&frames[0], Frame::ImmediateAck);
     }
+
+    #[test]
+    fn observed_addr_coding() {
+        for address in [
+            "192.0.2.1:4433".parse().unwrap(),
+            "[::ffff:192.0.2.1]:4433".parse().unwrap(),
+            "[2001:db8::1]:443".parse().unwrap(),
+        ] {
+            let original = ObservedAddr::new(VarInt(7), address);
+            let mut buf = Vec::new();
+            original.encode(&mut buf);
+            assert_eq!(buf.len(), original.size());
+            let frames = frames(buf);
+            assert_eq!(frames.len(), 1);
+            match &frames[0] {
+                Frame::ObservedAddr(decoded) => {
+                    assert_eq!(decoded, &original);
+                    assert_eq!(decoded.address().port(), address.port());
+                    assert_eq!(decoded.address().ip(), address.ip().to_canonical());
+                }
+                x => panic!("incorrect frame {x:?}"),
+            }
+        }
+    }
 }
@@ -0,0 +1,150 @@
+//! Entry points for fuzzing and property testing the parsers of untrusted input
+//!
+//! Each function feeds arbitrary bytes through the same code that processes data received from the
+//! network, and panics only if an internal invariant is violated. Unlike the types they exercise,
+//! these signatures are kept stable, so that fuzz targets and property tests outside this crate
+//! keep working across releases.
+
+use std::{
+    net::{Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    ConnectionIdParser, DatagramEvent, Endpoint, EndpointConfig, Instant, ServerConfig, Side,
+    frame::{self, Frame},
+    packet::PartialDecode,
+    transport_parameters::TransportParameters,
+};
+
+/// Decode the unprotected parts of the first QUIC packet in `data`
+///
+/// `cid_parser` determines the length of short header destination CIDs, as it would for an
+/// endpoint. Returns whether a packet header was decoded.
+pub fn packet_header(
+    data: &[u8],
+    cid_parser: &(impl ConnectionIdParser + ?Sized),
+    supported_versions: &[u32],
+    grease_quic_bit: bool,
+) -> bool {
+    let Ok((packet, rest)) = PartialDecode::new(
+        BytesMut::from(data),
+        cid_parser,
+        supported_versions,
+        grease_quic_bit,
+    ) else {
+        return false;
+    };
+    assert_eq!(packet.len() + rest.map_or(0, |x| x.len()), data.len());
+    true
+}
+
+/// Decode the frames of a decrypted packet payload
+///
+/// Returns the number of frames decoded before the end of `data` or the first invalid frame.
+pub fn frames(data: &[u8]) -> usize {
+    let Ok(iter) = frame::Iter::new(Bytes::copy_from_slice(data)) else {
+        return 0;
+    };
+    let mut count = 0;
+    for frame in iter {
+        let Ok(frame) = frame else {
+            break;
+        };
+        if let Frame::Ack(ack) = &frame {
+            for range in ack {
+                assert!(range.start() <= range.end());
+            }
+        }
+        count += 1;
+    }
+    count
+}
+
+/// Decode transport parameters sent by the peer of `side`
+///
+/// Valid parameters are re-encoded, and must decode to the same value, except for unknown
+/// parameters which aren't re-encoded. Returns whether `data` was valid.
+pub fn transport_parameters(side: Side, data: &[u8]) -> bool {
+    let Ok(mut params) = TransportParameters::read(side, &mut &*data) else {
+        return false;
+    };
+    params.unknown.clear();
+    let mut buf = Vec::new();
+    params.write(&mut buf);
+    let decoded = TransportParameters::read(side, &mut &buf[..])
+        .expect("re-encoded transport parameters are valid");
+    assert_eq!(decoded, params);
+    true
+}
+
+/// Route `datagrams` through a fresh endpoint, as if received from a single peer
+///
+/// Exercises the lookup of connections by connection ID using the generator of `config`, and the
+/// stateless responses that unroutable datagrams may trigger. If `server_config` is set, datagrams
+/// that would initiate a connection are decoded as far as the handshake, then ignored.
+pub fn cid_routing<'a>(
+    config: Arc<EndpointConfig>,
+    server_config: Option<Arc<ServerConfig>>,
+    datagrams: impl IntoIterator<Item = &'a [u8]>,
+) {
+    let mut endpoint = Endpoint::new(config, server_config, true);
+    let remote = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 4433);
+    let now = Instant::now();
+    let mut buf = Vec::new();
+    for datagram in datagrams {
+        buf.clear();
+        let event = endpoint.handle(now, remote, None, None, BytesMut::from(datagram), &mut buf);
+        match event {
+            Some(DatagramEvent::NewConnection(incoming)) => endpoint.ignore(incoming),
+            Some(DatagramEvent::Response(transmit)) => {
+                assert_eq!(transmit.destination, remote);
+                assert!(transmit.size <= buf.len());
+            }
+            Some(DatagramEvent::ConnectionEvent(..)) => {
+                unreachable!("no connections were established")
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DEFAULT_SUPPORTED_VERSIONS, FixedLengthConnectionIdParser};
+
+    #[test]
+    fn smoke() {
+        // A long header without payload length, and a short header
+        let parser = FixedLengthConnectionIdParser::new(8);
+        assert!(!packet_header(
+            &[],
+            &parser,
+            DEFAULT_SUPPORTED_VERSIONS,
+            false
+        ));
+        assert!(packet_header(
+            &[0x40; 32],
+            &parser,
+            DEFAULT_SUPPORTED_VERSIONS,
+            false
+        ));
+
+        // PING, two PADDING frames, then an unknown frame type
+        assert_eq!(frames(&[0x01, 0x00, 0x00, 0x40, 0x80]), 3);
+
+        let mut params = Vec::new();
+        TransportParameters::default().write(&mut params);
+        assert!(transport_parameters(Side::Client, &params));
+        assert!(!transport_parameters(Side::Client, &[0xff]));
+
+        cid_routing(
+            Arc::new(EndpointConfig::default()),
+            None,
+            [&[0x40; 32][..], &[0xc0; 1200][..]],
+        );
+    }
+}
@@ -4,6 +4,11 @@
 //! no networking code and does not get any relevant timestamps from the operating system. Most
 //! users may want to use the futures-based quinn API instead.
 //!
+//! Instead, the current time is passed as `now` to every method that depends on it, so callers
+//! such as test harnesses and network simulators are free to supply virtual time. The only
+//! wall-clock time used, to check the age of address validation tokens, is read through a
+//! configurable `TimeSource`.
+//!
 //! The quinn-proto API might be of interest if you want to use it from a C or C++ project
 //! through C bindings or if you want to use a different event loop than the one tokio provides.
 //!
@@ -33,6 +38,7 @@ mod range_set;
 #[cfg(all(test, any(feature = "rustls-aws-lc-rs", feature = "rustls-ring")))]
 mod tests;
 pub mod transport_parameters;
+pub use crate::transport_parameters::{Extension, PeerTransportParameters};
 mod varint;
 
 pub use varint::{VarInt, VarIntBoundsExceeded};
@@ -44,10 +50,15 @@ pub use bloom_token_log::BloomTokenLog;
 
 mod connection;
 pub use crate::connection::{
-    Chunk, Chunks, ClosedStream, Connection, ConnectionError, ConnectionStats, Datagrams, Event,
-    FinishError, FrameStats, PathStats, ReadError, ReadableError, RecvStream, RttEstimator,
-    SendDatagramError, SendStream, ShouldTransmit, StreamEvent, Streams, UdpStats, WriteError,
-    Written,
+    BandwidthEstimate, Chunk, Chunks, ClosedStream, CongestionDebugState, Connection,
+    ConnectionDebugState, ConnectionError, ConnectionIdState, ConnectionStats, DatagramClassifier,
+    DatagramFlowStats, DatagramSizeHistogram, Datagrams, Event, FinishError, FlowControlDebugState,
+    FrameStats, HandshakeTimings, LossTrigger, MtuProbe, MtuProbeOutcome, PathStats, ReadError,
+    ReadableError, RecvFlowError, RecvStream, RecvStreamDebugState, RttEstimator, RttHistogram,
+    SendBlockReason, SendDatagramError, SendFlowControl, SendStream, SendStreamDebugState,
+    ShouldTransmit, SpaceDebugState, StreamDebugState, StreamEvent, StreamGroupStats,
+    StreamSchedulerStats, Streams, TimeoutCause, TimeoutTimer, TransportEvent, UdpStats,
+    ValidatePathError, WriteError, Written,
 };
 #[cfg(feature = "qlog")]
 pub use connection::qlog::QlogStream;
@@ -59,8 +70,10 @@ mod config;
 #[cfg(feature = "qlog")]
 pub use config::QlogConfig;
 pub use config::{
-    AckFrequencyConfig, ClientConfig, ConfigError, EndpointConfig, IdleTimeout, MtuDiscoveryConfig,
-    ServerConfig, StdSystemTime, TimeSource, TransportConfig, ValidationTokenConfig,
+    AckFrequencyConfig, AckPolicyConfig, AdaptiveKeepAliveConfig, CidRotationConfig, ClientConfig,
+    ConfigError, EndpointConfig, GreaseConfig, IdleTimeout, MtuDiscoveryConfig, PaddingPolicy,
+    PeerLimitsConfig, PortHoppingConfig, RecvBufferPoolConfig, RttEstimatorConfig, ServerConfig,
+    SocketConfig, StdSystemTime, TimeSource, TransportConfig, TuneError, ValidationTokenConfig,
 };
 
 pub mod crypto;
@@ -77,7 +90,7 @@ pub use crate::endpoint::{
 mod packet;
 pub use packet::{
     ConnectionIdParser, FixedLengthConnectionIdParser, LongType, PacketDecodeError, PartialDecode,
-    ProtectedHeader, ProtectedInitialHeader,
+    ProtectedHeader, ProtectedInitialHeader, SpaceId,
 };
 
 mod shared;
@@ -88,6 +101,8 @@ pub use crate::transport_error::{Code as TransportErrorCode, Error as TransportE
 
 pub mod congestion;
 
+pub mod loss;
+
 mod cid_generator;
 pub use crate::cid_generator::{
     ConnectionIdGenerator, HashedConnectionIdGenerator, InvalidCid, RandomConnectionIdGenerator,
@@ -109,6 +124,12 @@ pub(crate) use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 #[cfg(all(target_family = "wasm", target_os = "unknown"))]
 pub(crate) use web_time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+
 #[cfg(fuzzing)]
 pub mod fuzzing {
     pub use crate::connection::{Retransmits, State as ConnectionState, StreamsState};
@@ -170,6 +191,7 @@ pub const DEFAULT_SUPPORTED_VERSIONS: &[u32] = &[
 /// Whether an endpoint was the initiator of a connection
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Side {
     /// The initiator of a connection
     Client = 0,
@@ -230,6 +252,7 @@ impl fmt::Display for Dir {
 /// Identifier for a stream within a particular connection
 #[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StreamId(u64);
 
 impl fmt::Display for StreamId {
@@ -310,6 +333,10 @@ pub struct Transmit {
     pub destination: SocketAddr,
     /// Explicit congestion notification bits to set on the packet
     pub ecn: Option<EcnCodepoint>,
+    /// Differentiated Services Code Point to set on the packet, if any
+    pub dscp: Option<u8>,
+    /// IPv6 flow label to set on the packet, if any
+    pub flow_label: Option<u32>,
     /// Amount of data written to the caller-supplied buffer
     pub size: usize,
     /// The segment size if this transmission contains multiple datagrams.
@@ -319,6 +346,62 @@ pub struct Transmit {
     pub src_ip: Option<IpAddr>,
 }
 
+/// Adjusts the IP header marking of individual outgoing transmits, see
+/// [`TransportConfig::transmit_marker`]
+///
+/// Implemented for closures of the appropriate signature.
+pub trait TransmitMarker: Send + Sync {
+    /// Adjust `marking`, initialized to what the connection would otherwise use, for a transmit
+    /// described by `info`
+    fn mark(&self, info: &TransmitInfo, marking: &mut TransmitMarking);
+}
+
+impl<F: Fn(&TransmitInfo, &mut TransmitMarking) + Send + Sync> TransmitMarker for F {
+    fn mark(&self, info: &TransmitInfo, marking: &mut TransmitMarking) {
+        self(info, marking)
+    }
+}
+
+/// Description of an outgoing transmit passed to a [`TransmitMarker`]
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub struct TransmitInfo {
+    /// Why the transmit is being sent
+    pub kind: TransmitKind,
+    /// The socket the transmit will be sent to
+    pub destination: SocketAddr,
+    /// Total size of the transmit's datagrams
+    pub size: usize,
+}
+
+/// Reason an outgoing transmit is being sent
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TransmitKind {
+    /// Regular handshake, application or acknowledgement data
+    Data,
+    /// A probe sent on expiry of the probe timeout (PTO), or retransmissions following one
+    LossProbe,
+    /// A padded probe for path MTU discovery
+    MtuProbe,
+    /// A PATH_CHALLENGE or PATH_RESPONSE validating a path
+    PathValidation,
+}
+
+/// IP header marking of an outgoing transmit, as adjusted by a [`TransmitMarker`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TransmitMarking {
+    /// Differentiated Services Code Point to set on the transmit's packets, if any
+    pub dscp: Option<u8>,
+    /// Explicit congestion notification bits to set on the transmit's packets
+    ///
+    /// ECN validation expects packets to be marked ECT(0) while it is in use. Changing the
+    /// codepoint of such packets will usually cause validation to fail and ECN to be disabled for
+    /// the path.
+    pub ecn: Option<EcnCodepoint>,
+}
+
 //
 // Useful internal constants
 //
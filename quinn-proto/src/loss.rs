@@ -0,0 +1,135 @@
+//! Logic for deciding when sent packets are lost
+//!
+//! This interface is experimental and may change in any release. It allows alternative loss
+//! detection algorithms, such as RACK variants with adaptive reordering windows, to be evaluated
+//! without modifying the connection state machine.
+
+use std::{cmp, sync::Arc};
+
+use crate::{Instant, LossTrigger, SpaceId, TIMER_GRANULARITY, connection::RttEstimator};
+
+/// Decides which unacknowledged packets are lost
+///
+/// Consulted whenever loss detection runs, i.e. on receipt of an ACK frame and when the loss
+/// detection timer expires. Persistent congestion, probe timeouts and the congestion controller's
+/// response are handled by the connection.
+pub trait LossDetector: Send + Sync {
+    /// Judge whether `packet`, sent before the largest acknowledged packet of its space, is lost
+    ///
+    /// `largest_acked` is the largest packet number acknowledged in `packet.space`. Packets
+    /// deemed lost after a probe timeout are reported with [`LossTrigger::ProbeTimeout`]
+    /// regardless of the returned trigger.
+    fn check(
+        &self,
+        now: Instant,
+        rtt: &RttEstimator,
+        largest_acked: u64,
+        packet: &UnackedPacket,
+    ) -> LossVerdict;
+
+    /// A packet that was deemed lost has been acknowledged
+    ///
+    /// `largest_acked` is the largest packet number acknowledged by the ACK frame that
+    /// acknowledged `packet`, from which the extent of reordering can be estimated.
+    #[allow(unused_variables)]
+    fn on_spurious_loss(&mut self, now: Instant, space: SpaceId, packet: u64, largest_acked: u64) {}
+}
+
+/// Constructs loss detectors on demand
+pub trait LossDetectorFactory {
+    /// Construct a fresh `LossDetector`
+    ///
+    /// `thresholds` reflects the connection's `TransportConfig`.
+    fn build(self: Arc<Self>, thresholds: LossThresholds) -> Box<dyn LossDetector>;
+}
+
+/// Reordering thresholds configured through the `TransportConfig`
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub struct LossThresholds {
+    /// Reordering in packet number space tolerated before a packet is deemed lost
+    pub packet: u32,
+    /// Reordering in time tolerated before a packet is deemed lost, as a factor of RTT
+    pub time: f32,
+}
+
+/// A packet that is neither acknowledged nor deemed lost, see [`LossDetector::check`]
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub struct UnackedPacket {
+    /// Packet number space of the packet
+    pub space: SpaceId,
+    /// Number of the packet
+    pub number: u64,
+    /// When the packet was sent
+    pub time_sent: Instant,
+    /// Size of the packet, in bytes
+    pub size: u16,
+    /// Whether the packet requires the peer to send an acknowledgement
+    pub ack_eliciting: bool,
+}
+
+/// Outcome of [`LossDetector::check`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LossVerdict {
+    /// The packet is lost
+    Lost(LossTrigger),
+    /// The packet is not lost yet
+    ///
+    /// Loss detection runs again at `deadline`, if set, unless an ACK frame is received earlier.
+    Pending {
+        /// When the packet would be deemed lost if nothing else happens
+        deadline: Option<Instant>,
+    },
+}
+
+/// Configuration for the [`ThresholdLossDetector`]
+///
+/// The detector uses the thresholds of the `TransportConfig`.
+#[derive(Debug, Default, Clone)]
+pub struct ThresholdLossDetectorConfig {
+    _private: (),
+}
+
+impl LossDetectorFactory for ThresholdLossDetectorConfig {
+    fn build(self: Arc<Self>, thresholds: LossThresholds) -> Box<dyn LossDetector> {
+        Box::new(ThresholdLossDetector {
+            packet_threshold: thresholds.packet.into(),
+            time_threshold: thresholds.time,
+        })
+    }
+}
+
+/// Loss detection with fixed packet and time thresholds, as specified by RFC 9002 §6.1
+#[derive(Debug, Clone)]
+pub struct ThresholdLossDetector {
+    packet_threshold: u64,
+    time_threshold: f32,
+}
+
+impl LossDetector for ThresholdLossDetector {
+    fn check(
+        &self,
+        now: Instant,
+        rtt: &RttEstimator,
+        largest_acked: u64,
+        packet: &UnackedPacket,
+    ) -> LossVerdict {
+        if largest_acked >= packet.number + self.packet_threshold {
+            return LossVerdict::Lost(LossTrigger::PacketThreshold);
+        }
+
+        let loss_delay = cmp::max(
+            rtt.conservative().mul_f32(self.time_threshold),
+            TIMER_GRANULARITY,
+        );
+        // Packets sent before now - loss_delay are deemed lost. The subtraction is avoided, as it
+        // can panic and Instant has no saturating equivalent.
+        if now.saturating_duration_since(packet.time_sent) >= loss_delay {
+            return LossVerdict::Lost(LossTrigger::TimeThreshold);
+        }
+        LossVerdict::Pending {
+            deadline: Some(packet.time_sent + loss_delay),
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt, net::SocketAddr};
+use std::{fmt, mem, net::SocketAddr};
 
 use bytes::{Buf, BufMut, BytesMut};
 
@@ -8,10 +8,41 @@ use crate::{Instant, MAX_CID_SIZE, ResetToken, coding::BufExt, packet::PartialDe
 #[derive(Debug)]
 pub struct ConnectionEvent(pub(crate) ConnectionEventInner);
 
+impl ConnectionEvent {
+    /// Append the datagrams carried by `other` to this event
+    ///
+    /// Datagrams received together, such as the segments of a GRO batch destined for the same
+    /// connection, are then processed as a unit by
+    /// [`Connection::handle_event`](crate::Connection::handle_event), which performs loss detection
+    /// and related bookkeeping once for the whole batch. Returns `other` if either event doesn't
+    /// carry datagrams.
+    // box err to avoid clippy::result_large_err
+    pub fn merge(&mut self, other: Self) -> Result<(), Box<Self>> {
+        use ConnectionEventInner::*;
+        let datagram = match other.0 {
+            Datagram(x) => x,
+            inner => return Err(Box::new(Self(inner))),
+        };
+        match &mut self.0 {
+            Datagrams(batch) => batch.push(datagram),
+            Datagram(_) => {
+                let Datagram(first) = mem::replace(&mut self.0, Datagrams(Vec::new())) else {
+                    unreachable!()
+                };
+                self.0 = Datagrams(vec![first, datagram]);
+            }
+            NewIdentifiers(..) => return Err(Box::new(Self(Datagram(datagram)))),
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum ConnectionEventInner {
     /// A datagram has been received for the Connection
     Datagram(DatagramConnectionEvent),
+    /// Datagrams received in the same batch, to be processed as a unit
+    Datagrams(Vec<DatagramConnectionEvent>),
     /// New connection identifiers have been issued for the Connection
     NewIdentifiers(Vec<IssuedCid>, Instant),
 }
@@ -0,0 +1,242 @@
+use rand::RngExt;
+use rand_pcg::Pcg32;
+
+use crate::{Duration, EcnCodepoint, Instant};
+
+/// Behavior of a unidirectional link between two endpoints
+///
+/// The default link delivers every datagram instantly and in order.
+#[derive(Debug, Clone)]
+pub struct LinkConfig {
+    pub(crate) bandwidth: Option<u64>,
+    pub(crate) queue_capacity: Option<usize>,
+    pub(crate) delay: Duration,
+    pub(crate) jitter: Duration,
+    pub(crate) loss: f64,
+    pub(crate) reorder: f64,
+    pub(crate) congestion_experienced: f64,
+    pub(crate) mtu: usize,
+}
+
+impl LinkConfig {
+    /// Rate at which datagrams are serialized onto the link, in bytes per second
+    ///
+    /// Datagrams queue behind each other at the sender. `None` (the default) is unlimited.
+    pub fn bandwidth(&mut self, value: Option<u64>) -> &mut Self {
+        self.bandwidth = value;
+        self
+    }
+
+    /// Number of bytes that may be queued at the sender before datagrams are dropped
+    ///
+    /// Only meaningful with a limited [`bandwidth`](Self::bandwidth). `None` (the default) is
+    /// unlimited.
+    pub fn queue_capacity(&mut self, value: Option<usize>) -> &mut Self {
+        self.queue_capacity = value;
+        self
+    }
+
+    /// One-way propagation delay
+    pub fn delay(&mut self, value: Duration) -> &mut Self {
+        self.delay = value;
+        self
+    }
+
+    /// Upper bound of a uniformly distributed delay added to each datagram
+    ///
+    /// Jitter alone doesn't reorder datagrams; see [`reorder`](Self::reorder).
+    pub fn jitter(&mut self, value: Duration) -> &mut Self {
+        self.jitter = value;
+        self
+    }
+
+    /// Probability of a datagram being dropped
+    ///
+    /// Clamped to the range 0 to 1, with NaN treated as 0.
+    pub fn loss(&mut self, value: f64) -> &mut Self {
+        self.loss = probability(value);
+        self
+    }
+
+    /// Probability of a datagram being held back by an additional [`delay`](Self::delay), letting
+    /// later datagrams overtake it
+    ///
+    /// Clamped to the range 0 to 1, with NaN treated as 0.
+    pub fn reorder(&mut self, value: f64) -> &mut Self {
+        self.reorder = probability(value);
+        self
+    }
+
+    /// Probability of an ECN-capable datagram being marked as having experienced congestion
+    ///
+    /// Clamped to the range 0 to 1, with NaN treated as 0.
+    pub fn congestion_experienced(&mut self, value: f64) -> &mut Self {
+        self.congestion_experienced = probability(value);
+        self
+    }
+
+    /// Largest UDP payload carried by the link; larger datagrams are dropped
+    pub fn mtu(&mut self, value: usize) -> &mut Self {
+        self.mtu = value;
+        self
+    }
+}
+
+fn probability(value: f64) -> f64 {
+    match value.is_nan() {
+        true => 0.0,
+        false => value.clamp(0.0, 1.0),
+    }
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            bandwidth: None,
+            queue_capacity: None,
+            delay: Duration::ZERO,
+            jitter: Duration::ZERO,
+            loss: 0.0,
+            reorder: 0.0,
+            congestion_experienced: 0.0,
+            mtu: 1500,
+        }
+    }
+}
+
+/// Statistics of a unidirectional link
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LinkStats {
+    /// Datagrams handed to the link
+    pub sent: u64,
+    /// Datagrams dropped at random, for exceeding the MTU, or because the queue was full
+    pub lost: u64,
+    /// Datagrams held back, letting later ones overtake them
+    pub reordered: u64,
+    /// Datagrams marked as having experienced congestion
+    pub congestion_experienced: u64,
+}
+
+/// State of a unidirectional link
+#[derive(Debug)]
+pub(crate) struct Link {
+    pub(crate) config: LinkConfig,
+    pub(crate) stats: LinkStats,
+    /// When the sender finishes serializing the datagrams queued so far
+    busy_until: Option<Instant>,
+    /// Arrival time of the latest datagram that wasn't reordered
+    last_arrival: Option<Instant>,
+}
+
+impl Link {
+    pub(crate) fn new(config: LinkConfig) -> Self {
+        Self {
+            config,
+            stats: LinkStats::default(),
+            busy_until: None,
+            last_arrival: None,
+        }
+    }
+
+    /// Decide the fate of a datagram of `len` bytes sent at `now`
+    ///
+    /// Returns when it arrives and with which ECN codepoint, or `None` if it's dropped.
+    pub(crate) fn send(
+        &mut self,
+        now: Instant,
+        len: usize,
+        ecn: Option<EcnCodepoint>,
+        rng: &mut Pcg32,
+    ) -> Option<(Instant, Option<EcnCodepoint>)> {
+        let config = &self.config;
+        self.stats.sent += 1;
+        // Draw every random decision, so that the outcome of one doesn't shift the others
+        let lost = rng.random_bool(config.loss);
+        let reordered = rng.random_bool(config.reorder);
+        let marked = rng.random_bool(config.congestion_experienced);
+        let jitter = config.jitter.mul_f64(rng.random::<f64>());
+
+        let start = self.busy_until.map_or(now, |x| x.max(now));
+        let serialization = match config.bandwidth {
+            Some(bandwidth) => {
+                let backlog = start - now;
+                if config.queue_capacity.is_some_and(|cap| {
+                    backlog.as_secs_f64() * bandwidth as f64 + len as f64 > cap as f64
+                }) {
+                    self.stats.lost += 1;
+                    return None;
+                }
+                Duration::from_secs_f64(len as f64 / bandwidth as f64)
+            }
+            None => Duration::ZERO,
+        };
+        self.busy_until = Some(start + serialization);
+
+        if lost || len > config.mtu {
+            self.stats.lost += 1;
+            return None;
+        }
+
+        let mut arrival = start + serialization + config.delay + jitter;
+        if reordered {
+            self.stats.reordered += 1;
+            arrival += config.delay;
+        } else {
+            arrival = self.last_arrival.map_or(arrival, |x| x.max(arrival));
+            self.last_arrival = Some(arrival);
+        }
+
+        let ecn = match ecn {
+            Some(_) if marked => {
+                self.stats.congestion_experienced += 1;
+                Some(EcnCodepoint::Ce)
+            }
+            x => x,
+        };
+        Some((arrival, ecn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn bandwidth_and_queue() {
+        let mut config = LinkConfig::default();
+        config
+            .bandwidth(Some(1000))
+            .queue_capacity(Some(1000))
+            .delay(Duration::from_millis(10));
+        let mut link = Link::new(config);
+        let mut rng = Pcg32::seed_from_u64(0);
+        let now = Instant::now();
+
+        let (arrival, _) = link.send(now, 500, None, &mut rng).unwrap();
+        assert_eq!(arrival - now, Duration::from_millis(510));
+        let (arrival, _) = link.send(now, 500, None, &mut rng).unwrap();
+        assert_eq!(arrival - now, Duration::from_millis(1010));
+        // 1000 bytes are queued
+        assert!(link.send(now, 1, None, &mut rng).is_none());
+        assert_eq!(link.stats.lost, 1);
+    }
+
+    #[test]
+    fn probabilities_clamped() {
+        let mut config = LinkConfig::default();
+        config
+            .loss(f64::NAN)
+            .reorder(2.0)
+            .congestion_experienced(-1.0);
+        assert_eq!(config.loss, 0.0);
+        assert_eq!(config.reorder, 1.0);
+        assert_eq!(config.congestion_experienced, 0.0);
+        let mut link = Link::new(config);
+        let mut rng = Pcg32::seed_from_u64(0);
+        link.send(Instant::now(), 100, None, &mut rng).unwrap();
+        assert_eq!(link.stats.reordered, 1);
+    }
+}
@@ -0,0 +1,272 @@
+//! Deterministic network simulation
+//!
+//! A [`Network`] connects any number of [`Node`]s, each wrapping an [`Endpoint`] bound to a socket
+//! address, through simulated links. Time is virtual: [`Network::step`] jumps straight to the next
+//! timer expiry or datagram arrival, so simulating minutes of traffic takes milliseconds, and runs
+//! aren't affected by the load of the machine running them.
+//!
+//! Each direction of each pair of nodes is a separate link, modelled by a [`LinkConfig`] with
+//! limited bandwidth, propagation delay, jitter, random loss, reordering and ECN marking. All
+//! randomness of the links is drawn from a generator seeded by the caller, so a failing scenario
+//! can be replayed by reusing its seed. For the endpoints' own random choices to repeat across runs
+//! too, configure them with [`EndpointConfig::rng_seed`].
+//!
+//! The same nodes drive the endpoints in this crate's own tests.
+
+use std::{
+    cmp::{self, Reverse},
+    collections::{BTreeMap, BinaryHeap, HashMap},
+    net::SocketAddr,
+    sync::Arc,
+};
+
+use bytes::{Bytes, BytesMut};
+use rand::SeedableRng;
+use rand_pcg::Pcg32;
+use tracing::trace;
+
+use crate::{
+    ClientConfig, ConnectError, ConnectionHandle, Duration, EcnCodepoint, Endpoint, EndpointConfig,
+    Instant, ServerConfig, Transmit,
+};
+
+mod link;
+use link::Link;
+pub use link::{LinkConfig, LinkStats};
+
+mod node;
+#[cfg(test)]
+pub(crate) use node::IncomingConnectionBehavior;
+pub use node::Node;
+
+/// A set of endpoints connected by simulated links, with a virtual clock
+#[derive(Debug)]
+pub struct Network {
+    epoch: Instant,
+    now: Instant,
+    rng: Pcg32,
+    nodes: BTreeMap<SocketAddr, Node>,
+    links: HashMap<(SocketAddr, SocketAddr), Link>,
+    default_link: LinkConfig,
+    in_flight: BinaryHeap<Reverse<InFlight>>,
+    /// Breaks ties between datagrams arriving at the same time, preserving the order of sending
+    next_sequence: u64,
+}
+
+impl Network {
+    /// Create an empty network whose links draw randomness from `seed`
+    pub fn new(seed: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            epoch: now,
+            now,
+            rng: Pcg32::seed_from_u64(seed),
+            nodes: BTreeMap::new(),
+            links: HashMap::new(),
+            default_link: LinkConfig::default(),
+            in_flight: BinaryHeap::new(),
+            next_sequence: 0,
+        }
+    }
+
+    /// Add an endpoint bound to `addr`
+    ///
+    /// If `server_config` is set, connections initiated by other nodes are accepted automatically,
+    /// see [`Node::accept`].
+    pub fn add_node(
+        &mut self,
+        addr: SocketAddr,
+        config: Arc<EndpointConfig>,
+        server_config: Option<Arc<ServerConfig>>,
+    ) -> &mut Node {
+        let endpoint = Endpoint::new(config, server_config, true);
+        self.nodes.insert(addr, Node::new(endpoint, addr));
+        self.nodes.get_mut(&addr).unwrap()
+    }
+
+    /// Access the node bound to `addr`
+    ///
+    /// Panics if there is none.
+    pub fn node_mut(&mut self, addr: SocketAddr) -> &mut Node {
+        self.nodes.get_mut(&addr).expect("unknown node")
+    }
+
+    /// Configure the link carrying datagrams from `from` to `to`
+    ///
+    /// Resets the statistics of the link.
+    pub fn set_link(&mut self, from: SocketAddr, to: SocketAddr, config: LinkConfig) {
+        self.links.insert((from, to), Link::new(config));
+    }
+
+    /// Configure links not configured by [`set_link`](Self::set_link)
+    ///
+    /// Only affects links that haven't carried any datagrams yet.
+    pub fn set_default_link(&mut self, config: LinkConfig) {
+        self.default_link = config;
+    }
+
+    /// Statistics of the link carrying datagrams from `from` to `to`
+    pub fn link_stats(&self, from: SocketAddr, to: SocketAddr) -> LinkStats {
+        self.links
+            .get(&(from, to))
+            .map(|link| link.stats)
+            .unwrap_or_default()
+    }
+
+    /// Initiate a connection from the node bound to `from` to `to`
+    pub fn connect(
+        &mut self,
+        from: SocketAddr,
+        to: SocketAddr,
+        config: ClientConfig,
+        server_name: &str,
+    ) -> Result<ConnectionHandle, ConnectError> {
+        let now = self.now;
+        self.node_mut(from).connect(now, config, to, server_name)
+    }
+
+    /// The current virtual time
+    pub fn now(&self) -> Instant {
+        self.now
+    }
+
+    /// Virtual time passed since the network was created
+    pub fn elapsed(&self) -> Duration {
+        self.now - self.epoch
+    }
+
+    /// Let nodes react to the current time, then advance to the next event and deliver the
+    /// datagrams arriving at that time
+    ///
+    /// Returns false, without advancing, if no datagrams are in flight and no timers are armed.
+    /// Connections keep timers armed until they are closed, so this is mostly useful to execute
+    /// the simulation event by event.
+    pub fn step(&mut self) -> bool {
+        self.flush();
+        let Some(next) = self.next_event() else {
+            return false;
+        };
+        self.advance(next);
+        true
+    }
+
+    /// Step until `deadline`, leaving the clock at `deadline`
+    pub fn run_until(&mut self, deadline: Instant) {
+        loop {
+            self.flush();
+            match self.next_event() {
+                Some(next) if next <= deadline => self.advance(next),
+                _ => break,
+            }
+        }
+        self.advance(deadline);
+        self.flush();
+    }
+
+    fn next_event(&self) -> Option<Instant> {
+        let arrival = self.in_flight.peek().map(|x| x.0.arrival);
+        let timeout = self.nodes.values().filter_map(Node::next_wakeup).min();
+        match (arrival, timeout) {
+            (Some(x), Some(y)) => Some(x.min(y)),
+            (x, y) => x.or(y),
+        }
+    }
+
+    /// Move the clock to `time` and deliver the datagrams arriving by then
+    fn advance(&mut self, time: Instant) {
+        if time > self.now {
+            self.now = time;
+            trace!("advancing to {:?}", self.elapsed());
+        }
+        while self
+            .in_flight
+            .peek()
+            .is_some_and(|x| x.0.arrival <= self.now)
+        {
+            let Reverse(datagram) = self.in_flight.pop().unwrap();
+            let Some(node) = self.nodes.get_mut(&datagram.destination) else {
+                continue;
+            };
+            node.handle(
+                datagram.arrival,
+                datagram.source,
+                datagram.ecn,
+                BytesMut::from(datagram.contents),
+            );
+        }
+    }
+
+    /// Process timeouts and events of all connections, and send whatever they produce
+    fn flush(&mut self) {
+        let mut outgoing = Vec::new();
+        for (&addr, node) in self.nodes.iter_mut() {
+            node.drive_outgoing(self.now);
+            outgoing.extend(
+                node.outbound
+                    .drain(..)
+                    .map(|(transmit, contents)| (addr, transmit, contents)),
+            );
+        }
+        for (source, transmit, contents) in outgoing {
+            self.send(source, transmit, contents);
+        }
+    }
+
+    fn send(&mut self, source: SocketAddr, transmit: Transmit, mut contents: Bytes) {
+        let destination = transmit.destination;
+        let link = self
+            .links
+            .entry((source, destination))
+            .or_insert_with(|| Link::new(self.default_link.clone()));
+        let segment_size = transmit.segment_size.unwrap_or(contents.len());
+        while !contents.is_empty() {
+            let segment = contents.split_to(segment_size.min(contents.len()));
+            let Some((arrival, ecn)) =
+                link.send(self.now, segment.len(), transmit.ecn, &mut self.rng)
+            else {
+                trace!(%source, %destination, "datagram dropped");
+                continue;
+            };
+            self.in_flight.push(Reverse(InFlight {
+                arrival,
+                sequence: self.next_sequence,
+                source,
+                destination,
+                ecn,
+                contents: segment,
+            }));
+            self.next_sequence += 1;
+        }
+    }
+}
+
+/// A datagram traversing a link
+#[derive(Debug)]
+struct InFlight {
+    arrival: Instant,
+    sequence: u64,
+    source: SocketAddr,
+    destination: SocketAddr,
+    ecn: Option<EcnCodepoint>,
+    contents: Bytes,
+}
+
+impl PartialEq for InFlight {
+    fn eq(&self, other: &Self) -> bool {
+        self.sequence == other.sequence
+    }
+}
+
+impl Eq for InFlight {}
+
+impl PartialOrd for InFlight {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InFlight {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        (self.arrival, self.sequence).cmp(&(other.arrival, other.sequence))
+    }
+}
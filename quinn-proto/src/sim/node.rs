@@ -0,0 +1,333 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt,
+    net::SocketAddr,
+};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    ClientConfig, ConnectError, Connection, ConnectionError, ConnectionEvent, ConnectionHandle,
+    DatagramEvent, EcnCodepoint, Endpoint, EndpointEvent, Incoming, Instant, Transmit,
+};
+
+/// An endpoint attached to a [`Network`](super::Network)
+pub struct Node {
+    pub(crate) endpoint: Endpoint,
+    pub(crate) addr: SocketAddr,
+    /// Datagrams received, with their time of arrival, not yet handled by the endpoint
+    pub(crate) inbound: VecDeque<(Instant, Option<EcnCodepoint>, BytesMut)>,
+    /// Datagrams produced by the node, one per segment, not yet sent
+    pub(crate) outbound: VecDeque<(Transmit, Bytes)>,
+    pub(crate) connections: BTreeMap<ConnectionHandle, Connection>,
+    /// Events for connections, applied by the next [`drive_outgoing`](Self::drive_outgoing)
+    conn_events: HashMap<ConnectionHandle, VecDeque<ConnectionEvent>>,
+    /// Outcomes of connections initiated by peers, not yet taken
+    pub(crate) accepted: VecDeque<Result<ConnectionHandle, ConnectionError>>,
+    /// Whether datagrams for the same connection are handled as a batch
+    pub(crate) batch_inbound: bool,
+    #[cfg(test)]
+    pub(crate) handle_incoming: Box<dyn FnMut(&Incoming) -> IncomingConnectionBehavior>,
+    #[cfg(test)]
+    pub(crate) waiting_incoming: Vec<Incoming>,
+    #[cfg(test)]
+    pub(crate) captured_packets: Vec<Vec<u8>>,
+    #[cfg(test)]
+    pub(crate) capture_inbound_packets: bool,
+    /// Whether drained connections are kept for inspection, rather than forgotten
+    #[cfg(test)]
+    pub(crate) retain_drained: bool,
+}
+
+/// How a [`Node`] responds to a connection initiated by a peer
+#[cfg(test)]
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum IncomingConnectionBehavior {
+    Accept,
+    Reject,
+    Retry,
+    Wait,
+}
+
+impl Node {
+    pub(crate) fn new(endpoint: Endpoint, addr: SocketAddr) -> Self {
+        Self {
+            endpoint,
+            addr,
+            inbound: VecDeque::new(),
+            outbound: VecDeque::new(),
+            connections: BTreeMap::new(),
+            conn_events: HashMap::default(),
+            accepted: VecDeque::new(),
+            batch_inbound: false,
+            #[cfg(test)]
+            handle_incoming: Box::new(|_| IncomingConnectionBehavior::Accept),
+            #[cfg(test)]
+            waiting_incoming: Vec::new(),
+            #[cfg(test)]
+            captured_packets: Vec::new(),
+            #[cfg(test)]
+            capture_inbound_packets: false,
+            #[cfg(test)]
+            retain_drained: false,
+        }
+    }
+
+    /// The underlying endpoint
+    pub fn endpoint(&self) -> &Endpoint {
+        &self.endpoint
+    }
+
+    /// The address the node is bound to
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Access a connection of this node
+    ///
+    /// Datagrams and events resulting from calls on the connection are processed by the next
+    /// [`Network::step`](super::Network::step). Returns `None` once the connection is drained,
+    /// after which its handle may be reused for a new connection.
+    pub fn connection(&mut self, ch: ConnectionHandle) -> Option<&mut Connection> {
+        self.connections.get_mut(&ch)
+    }
+
+    /// Take the next connection initiated by a peer
+    ///
+    /// Connections are accepted automatically, as long as the endpoint has a server
+    /// configuration.
+    pub fn accept(&mut self) -> Option<ConnectionHandle> {
+        while let Some(result) = self.accepted.pop_front() {
+            if let Ok(ch) = result {
+                return Some(ch);
+            }
+        }
+        None
+    }
+
+    pub(crate) fn connect(
+        &mut self,
+        now: Instant,
+        config: ClientConfig,
+        remote: SocketAddr,
+        server_name: &str,
+    ) -> Result<ConnectionHandle, ConnectError> {
+        let (ch, conn) = self.endpoint.connect(now, config, remote, server_name)?;
+        self.connections.insert(ch, conn);
+        Ok(ch)
+    }
+
+    /// Handle the queued datagrams that arrived by `now` from `remote`
+    #[cfg(test)]
+    pub(crate) fn drive_incoming(&mut self, now: Instant, remote: SocketAddr) {
+        while self.inbound.front().is_some_and(|x| x.0 <= now) {
+            let (recv_time, ecn, packet) = self.inbound.pop_front().unwrap();
+            self.handle(recv_time, remote, ecn, packet);
+        }
+    }
+
+    /// Pass a datagram from `remote` to the endpoint
+    ///
+    /// Resulting connection events are applied by the next [`drive_outgoing`](Self::drive_outgoing).
+    pub(crate) fn handle(
+        &mut self,
+        now: Instant,
+        remote: SocketAddr,
+        ecn: Option<EcnCodepoint>,
+        packet: BytesMut,
+    ) {
+        let mut buf = Vec::new();
+        let Some(event) = self
+            .endpoint
+            .handle(now, remote, None, ecn, packet, &mut buf)
+        else {
+            return;
+        };
+        match event {
+            DatagramEvent::NewConnection(incoming) => self.incoming(incoming, now),
+            DatagramEvent::ConnectionEvent(ch, event) => {
+                #[cfg(test)]
+                if self.capture_inbound_packets {
+                    let packet = self.connections[&ch].decode_packet(&event);
+                    self.captured_packets.extend(packet);
+                }
+
+                let events = self.conn_events.entry(ch).or_default();
+                let event = match events.back_mut() {
+                    Some(last) if self.batch_inbound => match last.merge(event) {
+                        Ok(()) => return,
+                        Err(event) => *event,
+                    },
+                    _ => event,
+                };
+                events.push_back(event);
+            }
+            DatagramEvent::Response(transmit) => {
+                let size = transmit.size;
+                self.outbound.extend(split_transmit(transmit, &buf[..size]));
+            }
+        }
+    }
+
+    #[cfg(not(test))]
+    fn incoming(&mut self, incoming: Incoming, now: Instant) {
+        let _ = self.try_accept(incoming, now);
+    }
+
+    #[cfg(test)]
+    fn incoming(&mut self, incoming: Incoming, now: Instant) {
+        match (self.handle_incoming)(&incoming) {
+            IncomingConnectionBehavior::Accept => {
+                let _ = self.try_accept(incoming, now);
+            }
+            IncomingConnectionBehavior::Reject => self.reject(incoming),
+            IncomingConnectionBehavior::Retry => self.retry(incoming),
+            IncomingConnectionBehavior::Wait => self.waiting_incoming.push(incoming),
+        }
+    }
+
+    /// Let every connection react to its events and timers, and queue what it sends
+    ///
+    /// Drained connections are forgotten, as the endpoint may reuse their handles.
+    pub(crate) fn drive_outgoing(&mut self, now: Instant) {
+        let buffer_size = self.endpoint.config().get_max_udp_payload_size() as usize;
+        let mut buf = Vec::with_capacity(buffer_size);
+
+        loop {
+            let mut endpoint_events: Vec<(ConnectionHandle, EndpointEvent)> = vec![];
+            for (&ch, conn) in self.connections.iter_mut() {
+                if conn.poll_timeout().is_some_and(|x| x <= now) {
+                    conn.handle_timeout(now);
+                }
+
+                for event in self.conn_events.remove(&ch).into_iter().flatten() {
+                    conn.handle_event(event);
+                }
+
+                while let Some(event) = conn.poll_endpoint_events() {
+                    endpoint_events.push((ch, event));
+                }
+                while let Some(transmit) = conn.poll_transmit(now, MAX_DATAGRAMS, &mut buf) {
+                    let size = transmit.size;
+                    self.outbound.extend(split_transmit(transmit, &buf[..size]));
+                    buf.clear();
+                }
+            }
+
+            if endpoint_events.is_empty() {
+                break;
+            }
+
+            for (ch, event) in endpoint_events {
+                #[cfg(test)]
+                let drained = event.is_drained() && !self.retain_drained;
+                #[cfg(not(test))]
+                let drained = event.is_drained();
+                if let Some(event) = self.endpoint.handle_event(ch, event) {
+                    if let Some(conn) = self.connections.get_mut(&ch) {
+                        conn.handle_event(event);
+                    }
+                }
+                if drained {
+                    self.connections.remove(&ch);
+                }
+            }
+        }
+    }
+
+    /// The earliest time at which a connection timer expires or a queued datagram arrives
+    pub(crate) fn next_wakeup(&self) -> Option<Instant> {
+        let next_inbound = self.inbound.front().map(|x| x.0);
+        self.connections
+            .values()
+            .filter_map(|conn| conn.poll_timeout())
+            .chain(next_inbound)
+            .min()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn is_idle(&self) -> bool {
+        self.connections.values().all(|x| x.is_idle())
+    }
+
+    pub(crate) fn try_accept(
+        &mut self,
+        incoming: Incoming,
+        now: Instant,
+    ) -> Result<ConnectionHandle, ConnectionError> {
+        let mut buf = Vec::new();
+        match self.endpoint.accept(incoming, now, &mut buf, None) {
+            Ok((ch, conn)) => {
+                self.connections.insert(ch, conn);
+                self.accepted.push_back(Ok(ch));
+                Ok(ch)
+            }
+            Err(error) => {
+                if let Some(transmit) = error.response {
+                    let size = transmit.size;
+                    self.outbound.extend(split_transmit(transmit, &buf[..size]));
+                }
+                self.accepted.push_back(Err(error.cause.clone()));
+                Err(error.cause)
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn retry(&mut self, incoming: Incoming) {
+        let mut buf = Vec::new();
+        let transmit = self.endpoint.retry(incoming, &mut buf).unwrap();
+        let size = transmit.size;
+        self.outbound.extend(split_transmit(transmit, &buf[..size]));
+    }
+
+    #[cfg(test)]
+    pub(crate) fn reject(&mut self, incoming: Incoming) {
+        let mut buf = Vec::new();
+        let transmit = self.endpoint.refuse(incoming, &mut buf);
+        let size = transmit.size;
+        self.outbound.extend(split_transmit(transmit, &buf[..size]));
+    }
+}
+
+impl fmt::Debug for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Node")
+            .field("endpoint", &self.endpoint)
+            .field("addr", &self.addr)
+            .field("connections", &self.connections)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Split a GSO batch into one transmit per datagram
+fn split_transmit(transmit: Transmit, buffer: &[u8]) -> Vec<(Transmit, Bytes)> {
+    let mut buffer = Bytes::copy_from_slice(buffer);
+    let Some(segment_size) = transmit.segment_size else {
+        return vec![(transmit, buffer)];
+    };
+
+    let mut transmits = Vec::new();
+    while !buffer.is_empty() {
+        let end = segment_size.min(buffer.len());
+
+        let contents = buffer.split_to(end);
+        transmits.push((
+            Transmit {
+                destination: transmit.destination,
+                size: contents.len(),
+                ecn: transmit.ecn,
+                dscp: transmit.dscp,
+                flow_label: transmit.flow_label,
+                segment_size: None,
+                src_ip: transmit.src_ip,
+            },
+            contents,
+        ));
+    }
+
+    transmits
+}
+
+/// The maximum of datagrams a [`Node`] produces per `poll_transmit`
+pub(crate) const MAX_DATAGRAMS: usize = 10;
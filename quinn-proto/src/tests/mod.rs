@@ -1,7 +1,8 @@
 use std::{
     convert::TryInto,
-    mem,
+    iter, mem,
     net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    num::NonZeroU32,
     sync::{Arc, Mutex},
 };
 
@@ -139,10 +140,10 @@ fn lifecycle() {
                         ApplicationClose { error_code: VarInt(42), ref reason }
                     )}) if reason == REASON);
     assert_matches!(pair.client_conn_mut(client_ch).poll(), None);
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -172,10 +173,10 @@ fn draft_version_compat() {
                         ApplicationClose { error_code: VarInt(42), ref reason }
                     )}) if reason == REASON);
     assert_matches!(pair.client_conn_mut(client_ch).poll(), None);
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -439,6 +440,38 @@ fn reject_self_signed_server_cert() {
                     if error.code == TransportErrorCode::crypto(AlertDescription::UnknownCA.into()));
 }
 
+#[test]
+fn peer_credentials() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+
+    let credentials = pair
+        .client_conn_mut(client_ch)
+        .crypto_session()
+        .peer_credentials()
+        .unwrap();
+    assert_eq!(
+        credentials.end_entity(),
+        Some(&CERTIFIED_KEY.cert.der()[..])
+    );
+    let spki = rcgen::PublicKeyData::subject_public_key_info(&CERTIFIED_KEY.signing_key);
+    assert_eq!(credentials.subject_public_key_info(), Some(&spki[..]));
+    #[cfg(feature = "ring")]
+    assert_eq!(
+        credentials.spki_sha256().unwrap()[..],
+        *ring::digest::digest(&ring::digest::SHA256, &spki).as_ref()
+    );
+
+    let raw = crypto::PeerCredentials::from_der(vec![Bytes::from(spki.clone())]);
+    assert_eq!(
+        raw,
+        crypto::PeerCredentials::RawPublicKey(spki.clone().into())
+    );
+    assert_eq!(raw.end_entity(), None);
+    assert_eq!(raw.subject_public_key_info(), Some(&spki[..]));
+}
+
 #[test]
 fn reject_missing_client_cert() {
     let _guard = subscribe();
@@ -518,6 +551,24 @@ fn congestion() {
     pair.client_send(client_ch, s).write(&[42; 1024]).unwrap();
 }
 
+#[test]
+fn send_budget() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+
+    let initial = pair.client_conn_mut(client_ch).send_budget();
+    assert_eq!(initial, pair.client_conn_mut(client_ch).congestion_window());
+    // Unacknowledged data consumes the budget
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[42; 4096]).unwrap();
+    pair.drive_client();
+    assert!(pair.client_conn_mut(client_ch).send_budget() <= initial - 4096);
+    // Acknowledgments restore it
+    pair.drive();
+    assert!(pair.client_conn_mut(client_ch).send_budget() >= initial);
+}
+
 #[test]
 fn high_latency_handshake() {
     let _guard = subscribe();
@@ -1061,6 +1112,267 @@ fn streams_blocked_not_sent_under_limit() {
     );
 }
 
+#[test]
+fn transport_events() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    let drain = |conn: &mut Connection| {
+        let mut events = Vec::new();
+        while let Some(event) = conn.poll_transport_event() {
+            events.push(event);
+        }
+        events
+    };
+    assert!(drain(pair.client_conn_mut(client_ch)).contains(&TransportEvent::HandshakeConfirmed));
+
+    info!("initiating key update");
+    pair.client_conn_mut(client_ch).force_key_update();
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive();
+    assert!(
+        drain(pair.client_conn_mut(client_ch))
+            .contains(&TransportEvent::KeysUpdated { remote: false })
+    );
+    assert!(
+        drain(pair.server_conn_mut(server_ch))
+            .contains(&TransportEvent::KeysUpdated { remote: true })
+    );
+
+    info!("exhausting stream limit");
+    while pair.client_streams(client_ch).open(Dir::Uni).is_some() {}
+    // Reported once, however often opening fails
+    assert!(pair.client_streams(client_ch).open(Dir::Uni).is_none());
+    assert_eq!(
+        drain(pair.client_conn_mut(client_ch)),
+        [TransportEvent::StreamsBlocked { dir: Dir::Uni }]
+    );
+}
+
+#[test]
+fn stream_opened_events() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    while pair
+        .server_conn_mut(server_ch)
+        .poll_transport_event()
+        .is_some()
+    {}
+
+    let streams = (0..3)
+        .map(|_| pair.client_streams(client_ch).open(Dir::Uni).unwrap())
+        .collect::<Vec<_>>();
+    // Only the last stream carries data, implicitly opening the others
+    pair.client_send(client_ch, streams[2])
+        .write(b"hi")
+        .unwrap();
+    pair.drive();
+
+    let mut opened = Vec::new();
+    while let Some(event) = pair.server_conn_mut(server_ch).poll_transport_event() {
+        if let TransportEvent::StreamOpened { id } = event {
+            opened.push(id);
+        }
+    }
+    assert_eq!(opened, streams);
+
+    // Turn down a stream before accepting it
+    const ERROR: VarInt = VarInt(42);
+    pair.server_recv(server_ch, streams[2]).stop(ERROR).unwrap();
+    pair.drive();
+    assert_eq!(
+        pair.client_send(client_ch, streams[2]).write(b"more"),
+        Err(WriteError::Stopped(ERROR))
+    );
+
+    // Announcements don't consume streams
+    for &id in &streams {
+        assert_eq!(pair.server_streams(server_ch).accept(Dir::Uni), Some(id));
+    }
+}
+
+#[test]
+fn handshake_timings() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    pair.latency = Duration::from_millis(10);
+    let start = pair.time;
+    let (client_ch, server_ch) = pair.connect();
+
+    let rtt = 2 * pair.latency;
+    let client = pair.client_conn_mut(client_ch).stats().handshake;
+    assert_eq!(client.initial_sent, Some(start));
+    assert_eq!(client.initial_received, Some(start + rtt));
+    assert_eq!(client.handshake_keys, client.initial_received);
+    assert!(client.one_rtt_sent >= client.handshake_keys);
+    // Confirmation requires the server's HANDSHAKE_DONE frame
+    assert_eq!(client.confirmed, Some(start + 2 * rtt));
+
+    let server = pair.server_conn_mut(server_ch).stats().handshake;
+    assert_eq!(server.initial_received, Some(start + pair.latency));
+    assert_eq!(server.confirmed, Some(start + rtt + pair.latency));
+    assert!(server.one_rtt_received.is_some());
+}
+
+#[test]
+fn rtt_estimator_config() {
+    let _guard = subscribe();
+    let mut rtt_estimator = RttEstimatorConfig::default();
+    rtt_estimator.min_rtt_window(Some(Duration::from_millis(100)));
+    let client_config = ClientConfig {
+        transport: Arc::new(TransportConfig {
+            rtt_estimator,
+            ..TransportConfig::default()
+        }),
+        ..client_config()
+    };
+    let mut pair = Pair::default();
+    pair.latency = Duration::from_millis(10);
+    let (client_ch, _) = pair.connect_with(client_config);
+    let stats = pair.client_conn_mut(client_ch).stats().path;
+    assert_eq!(stats.min_rtt, 2 * pair.latency);
+    assert!(stats.latest_rtt >= stats.min_rtt);
+    assert!(stats.rtt_var > Duration::ZERO);
+
+    // Once the window elapses, the minimum follows a lasting rise in latency
+    pair.latency = Duration::from_millis(50);
+    pair.time += Duration::from_millis(200);
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(b"hello").unwrap();
+    pair.drive();
+    let stats = pair.client_conn_mut(client_ch).stats().path;
+    assert!(stats.min_rtt >= 2 * pair.latency);
+}
+
+#[test]
+fn peer_transport_parameters() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    assert_eq!(
+        pair.client_conn_mut(client_ch).peer_transport_parameters(),
+        None
+    );
+    pair.drive();
+    let server_ch = pair.server.assert_accept();
+
+    let params = pair
+        .client_conn_mut(client_ch)
+        .peer_transport_parameters()
+        .unwrap();
+    let server_config = TransportConfig::default();
+    assert_eq!(
+        params.initial_max_streams_bidi,
+        server_config.max_concurrent_bidi_streams.into_inner()
+    );
+    assert_eq!(params.max_idle_timeout, Some(Duration::from_secs(30)));
+    assert!(params.original_dst_cid.is_some());
+    // Every endpoint sends a reserved parameter
+    assert_eq!(params.unknown.len(), 1);
+
+    let extensions = [
+        Extension::Datagram,
+        Extension::AckFrequency,
+        Extension::GreaseQuicBit,
+    ];
+    assert_eq!(
+        pair.client_conn_mut(client_ch).negotiated_extensions(),
+        extensions
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).negotiated_extensions(),
+        extensions
+    );
+}
+
+#[test]
+fn grease() {
+    let _guard = subscribe();
+    let mut grease = GreaseConfig::default();
+    grease.padding_frequency(f64::NAN);
+    assert_eq!(grease, GreaseConfig::default());
+    grease
+        .transport_parameters(4)
+        .padding_frequency(1.0)
+        .max_padding(200);
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .grease(grease);
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect_with(client_config);
+
+    let params = pair
+        .server_conn_mut(server_ch)
+        .peer_transport_parameters()
+        .unwrap();
+    assert_eq!(params.unknown.len(), 4);
+    assert!(params.unknown.iter().all(|(id, _)| id % 31 == 27));
+
+    // The peer accepts padded packets carrying all kinds of frames
+    const MSG: &[u8] = &[0xab; 20_000];
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    assert_eq!(
+        pair.client_send(client_ch, s).write(MSG).unwrap(),
+        MSG.len()
+    );
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.client_datagrams(client_ch)
+        .send(Bytes::from_static(b"datagram"), true)
+        .unwrap();
+    pair.drive();
+
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(id) if id == s);
+    let mut recv = pair.server_recv(server_ch, s);
+    let mut chunks = recv.read(true).unwrap();
+    let mut received = Vec::new();
+    while let Ok(Some(chunk)) = chunks.next(usize::MAX) {
+        received.extend_from_slice(&chunk.bytes);
+    }
+    let _ = chunks.finalize();
+    assert_eq!(received, MSG);
+    assert_eq!(
+        pair.server_datagrams(server_ch).recv(),
+        Some(Bytes::from_static(b"datagram"))
+    );
+    assert!(!pair.client_conn_mut(client_ch).is_closed());
+}
+
+#[test]
+fn debug_state() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello";
+    pair.client_send(client_ch, s).write(MSG).unwrap();
+    pair.client.drive(pair.time, pair.server.addr);
+
+    let state = pair.client_conn_mut(client_ch).debug_state();
+    assert_eq!(state.side, Side::Client);
+    assert_eq!(state.state, "established");
+    assert_eq!(state.spaces.len(), 3);
+    let data = &state.spaces[SpaceId::Data as usize];
+    assert_eq!(data.space, SpaceId::Data);
+    assert!(data.has_keys);
+    assert!(!state.spaces[SpaceId::Initial as usize].has_keys);
+    assert_eq!(data.in_flight.last().unwrap().end, data.next_packet_number);
+    assert!(data.in_flight_bytes > 0);
+    assert_eq!(state.flow_control.data_sent, MSG.len() as u64);
+    assert_eq!(state.flow_control.unacked_data, MSG.len() as u64);
+    assert_eq!(state.congestion.bytes_in_flight, data.in_flight_bytes);
+
+    assert_eq!(state.streams.len(), 1);
+    let stream = &state.streams[0];
+    assert_eq!(stream.id, s);
+    assert!(stream.recv.is_none());
+    let send = stream.send.as_ref().unwrap();
+    assert_eq!(send.state, "ready");
+    assert_eq!(send.offset, MSG.len() as u64);
+    assert_eq!(send.unacked, MSG.len() as u64);
+}
+
 #[test]
 fn key_update_simple() {
     let _guard = subscribe();
@@ -1241,19 +1553,65 @@ fn instant_server_close() {
     let _guard = subscribe();
     let mut pair = Pair::default();
     info!("connecting");
-    pair.begin_connect(client_config());
+    let client_ch = pair.begin_connect(client_config());
     pair.drive_client();
     pair.server.drive_incoming(pair.time, pair.client.addr);
     let server_ch = pair.server.assert_accept();
     info!("closing");
-    pair.server
-        .connections
-        .get_mut(&server_ch)
-        .unwrap()
-        .close(pair.time, VarInt(42), Bytes::new());
+    pair.server.connections.get_mut(&server_ch).unwrap().close(
+        pair.time,
+        VarInt(42),
+        Bytes::from_static(b"over quota"),
+    );
+    // Holding back application data until the close is sent doesn't pause sending on behalf of
+    // the application
+    assert!(!pair.server_conn_mut(server_ch).is_sending_paused());
     pair.drive();
+    // The close is sent after the server's handshake messages, so the client can decrypt it
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Connected)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::ConnectionLost {
+            reason: ConnectionError::ApplicationClosed(ApplicationClose {
+                error_code: VarInt(42),
+                ref reason,
+            }),
+        }) if reason == "over quota"
+    );
+}
+
+#[test]
+fn instant_server_close_lost_handshake() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    pair.drive_client();
+    pair.server.drive_incoming(pair.time, pair.client.addr);
+    let server_ch = pair.server.assert_accept();
+    let now = pair.time;
+    pair.server_conn_mut(server_ch)
+        .close(now, VarInt(42), Bytes::from_static(b"over quota"));
+    pair.drive_server();
+    // Lose the server's handshake messages, so the client can't decrypt a 1-RTT close
+    pair.client.inbound.clear();
+
+    // The client's retransmitted Initial prompts a close it can decrypt
+    while !pair.client_conn_mut(client_ch).is_closed() {
+        if !pair.step() {
+            if let Some(time) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = time;
+            }
+        }
+    }
     assert_matches!(
-        pair.client_conn_mut(server_ch).poll(),
+        pair.client_conn_mut(client_ch).poll(),
         Some(Event::ConnectionLost {
             reason: ConnectionError::ConnectionClosed(ConnectionClose {
                 error_code: TransportErrorCode::APPLICATION_ERROR,
@@ -1291,76 +1649,671 @@ fn idle_timeout() {
     }
 
     assert!(pair.time - start < Duration::from_millis(2 * IDLE_TIMEOUT));
-    assert_matches!(
+    let cause = assert_matches!(
         pair.client_conn_mut(client_ch).poll(),
         Some(Event::ConnectionLost {
-            reason: ConnectionError::TimedOut,
-        })
-    );
+            reason: ConnectionError::TimedOut(cause),
+        }) => cause
+    );
+    assert_eq!(cause.timer, TimeoutTimer::Idle);
+    assert_eq!(cause.timeout, Duration::from_millis(IDLE_TIMEOUT));
+    assert!(!cause.keep_alive);
+    assert!(cause.since_last_received.unwrap() >= cause.timeout);
+    assert!(cause.since_last_sent.unwrap() < cause.since_last_received.unwrap());
     assert_matches!(
         pair.server_conn_mut(server_ch).poll(),
         Some(Event::ConnectionLost {
-            reason: ConnectionError::TimedOut,
+            reason: ConnectionError::TimedOut(TimeoutCause {
+                timer: TimeoutTimer::Idle,
+                ..
+            }),
         })
     );
 }
 
 #[test]
-fn connection_close_sends_acks() {
+fn set_idle_timeout() {
     let _guard = subscribe();
     let mut pair = Pair::default();
-    let (client_ch, _server_ch) = pair.connect();
-
-    let client_acks = pair.client_conn_mut(client_ch).stats().frame_rx.acks;
-
-    pair.client_conn_mut(client_ch).ping();
-    pair.drive_client();
-
-    let time = pair.time;
-    pair.server_conn_mut(client_ch)
-        .close(time, VarInt(42), Bytes::new());
-
-    pair.drive();
+    let (client_ch, _) = pair.connect();
+    let negotiated = pair.client_conn_mut(client_ch).idle_timeout().unwrap();
 
-    let client_acks_2 = pair.client_conn_mut(client_ch).stats().frame_rx.acks;
-    assert!(
-        client_acks_2 > client_acks,
-        "Connection close should send pending ACKs"
+    // Can't exceed the negotiated timeout
+    let now = pair.time;
+    pair.client_conn_mut(client_ch)
+        .set_idle_timeout(now, Some(negotiated * 2));
+    assert_eq!(
+        pair.client_conn_mut(client_ch).idle_timeout(),
+        Some(negotiated)
     );
-}
 
-#[test]
-fn server_hs_retransmit() {
-    let _guard = subscribe();
-    let mut pair = Pair::default();
-    let client_ch = pair.begin_connect(client_config());
-    pair.step();
-    assert!(!pair.client.inbound.is_empty()); // Initial + Handshakes
-    pair.client.inbound.clear();
-    pair.drive();
-    assert_matches!(
-        pair.client_conn_mut(client_ch).poll(),
-        Some(Event::HandshakeDataReady)
+    const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+    pair.client_conn_mut(client_ch)
+        .set_idle_timeout(now, Some(IDLE_TIMEOUT));
+    assert_eq!(
+        pair.client_conn_mut(client_ch).idle_timeout(),
+        Some(IDLE_TIMEOUT)
     );
-    assert_matches!(
+    while !pair.client_conn_mut(client_ch).is_closed() {
+        if !pair.step() {
+            if let Some(t) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = t;
+            }
+        }
+    }
+
+    let cause = assert_matches!(
         pair.client_conn_mut(client_ch).poll(),
-        Some(Event::Connected)
+        Some(Event::ConnectionLost {
+            reason: ConnectionError::TimedOut(cause),
+        }) => cause
     );
+    assert_eq!(cause.timer, TimeoutTimer::Idle);
+    assert_eq!(cause.timeout, IDLE_TIMEOUT);
+    assert!(cause.since_last_received.unwrap() < 2 * IDLE_TIMEOUT);
 }
 
 #[test]
-fn migration() {
+fn tune() {
     let _guard = subscribe();
-    let mut pair = Pair::default();
+    const IDLE_TIMEOUT: u64 = 10;
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            max_idle_timeout: Some(VarInt(IDLE_TIMEOUT)),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
     let (client_ch, server_ch) = pair.connect();
-    pair.drive();
-
-    let client_stats_after_connect = pair.client_conn_mut(client_ch).stats();
-
-    pair.client.addr = SocketAddr::new(
-        Ipv4Addr::new(127, 0, 0, 1).into(),
-        CLIENT_PORTS.lock().unwrap().next().unwrap(),
-    );
+    let now = pair.time;
+
+    // Fields fixed for the lifetime of the connection are rejected
+    let mut config = (**pair.client_conn_mut(client_ch).config()).clone();
+    let mut ack_policy = AckPolicyConfig::default();
+    ack_policy.max_ack_delay(Duration::from_millis(100));
+    config
+        .initial_rtt(Duration::from_millis(10))
+        .ack_policy_config(ack_policy)
+        .keep_alive_interval(Some(Duration::from_millis(IDLE_TIMEOUT / 2)));
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .tune(now, Arc::new(config))
+            .unwrap_err()
+            .fields,
+        ["initial_rtt", "ack_policy_config"]
+    );
+    assert_eq!(pair.client_conn_mut(client_ch).keep_alive_interval(), None);
+
+    let mut config = (**pair.client_conn_mut(client_ch).config()).clone();
+    let mut ack_policy = AckPolicyConfig::default();
+    ack_policy.ack_eliciting_threshold(VarInt(4));
+    config
+        .ack_policy_config(ack_policy)
+        .keep_alive_interval(Some(Duration::from_millis(IDLE_TIMEOUT / 2)))
+        .max_outgoing_bytes_per_second(Some(1_000_000));
+    let config = Arc::new(config);
+    pair.client_conn_mut(client_ch)
+        .tune(now, config.clone())
+        .unwrap();
+    assert!(
+        pair.client_conn_mut(client_ch)
+            .config()
+            .diff(&config)
+            .is_empty()
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch).keep_alive_interval(),
+        Some(Duration::from_millis(IDLE_TIMEOUT / 2))
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .max_outgoing_bytes_per_second(),
+        Some(1_000_000)
+    );
+
+    // Keep-alives now keep the connection open
+    let end = pair.time + Duration::from_millis(20 * IDLE_TIMEOUT);
+    while pair.time < end {
+        if !pair.step() {
+            if let Some(time) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = time;
+            }
+        }
+        assert!(!pair.client_conn_mut(client_ch).is_closed());
+        assert!(!pair.server_conn_mut(server_ch).is_closed());
+    }
+}
+
+#[test]
+fn tune_after_setters() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let now = pair.time;
+    let conn = pair.client_conn_mut(client_ch);
+    let original = conn.config().clone();
+
+    conn.set_receive_window(VarInt(1000));
+    conn.set_send_window(1000);
+    conn.set_max_concurrent_streams(Dir::Uni, VarInt(1));
+    conn.set_max_outgoing_bytes_per_second(Some(1000));
+    conn.set_dscp(Some(1));
+    let config = conn.config();
+    assert_eq!(config.receive_window, VarInt(1000));
+    assert_eq!(config.send_window, 1000);
+    assert_eq!(config.max_concurrent_uni_streams, VarInt(1));
+    assert_eq!(config.max_outgoing_bytes_per_second, Some(1000));
+    assert_eq!(config.dscp, Some(1));
+
+    // Restoring the original configuration undoes the changes
+    conn.tune(now, original.clone()).unwrap();
+    assert!(conn.config().diff(&original).is_empty());
+    assert_eq!(conn.max_outgoing_bytes_per_second(), None);
+}
+
+#[test]
+fn max_pto_count() {
+    let _guard = subscribe();
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .max_pto_count(NonZeroU32::new(3));
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect_with(client_config);
+    pair.client_conn_mut(client_ch).ping();
+
+    while !pair.client_conn_mut(client_ch).is_closed() {
+        if !pair.step() {
+            if let Some(t) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = t;
+            }
+        }
+        pair.client.inbound.clear(); // Simulate total S->C packet loss
+    }
+
+    let cause = assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::ConnectionLost {
+            reason: ConnectionError::TimedOut(cause),
+        }) => cause
+    );
+    assert_eq!(cause.timer, TimeoutTimer::Retransmission);
+    assert_eq!(pair.client_conn_mut(client_ch).stats().path.pto_count, 3);
+    assert!(cause.since_last_received.unwrap() < Duration::from_secs(30));
+}
+
+#[test]
+fn stream_reset_rate_limit() {
+    let _guard = subscribe();
+    let mut limits = PeerLimitsConfig::default();
+    limits.max_stream_resets_per_second(Some(2));
+    let mut server_config = server_config();
+    Arc::get_mut(&mut server_config.transport)
+        .unwrap()
+        .peer_limits(limits);
+    let mut pair = Pair::new(Default::default(), server_config);
+    let (client_ch, server_ch) = pair.connect();
+
+    for _ in 0..3 {
+        let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+        pair.client_send(client_ch, s).write(b"hello").unwrap();
+        pair.client_send(client_ch, s).reset(VarInt(42)).unwrap();
+    }
+    pair.drive();
+
+    let reason =
+        iter::from_fn(|| pair.server_conn_mut(server_ch).poll()).find_map(|event| match event {
+            Event::ConnectionLost { reason } => Some(reason),
+            _ => None,
+        });
+    assert_matches!(
+        reason,
+        Some(ConnectionError::TransportError(TransportError {
+            code: TransportErrorCode::INTERNAL_ERROR,
+            ..
+        }))
+    );
+}
+
+#[test]
+fn stream_reset_rate_limit_retransmission() {
+    let _guard = subscribe();
+    let mut limits = PeerLimitsConfig::default();
+    limits.max_stream_resets_per_second(Some(2));
+    let mut server_config = server_config();
+    Arc::get_mut(&mut server_config.transport)
+        .unwrap()
+        .peer_limits(limits);
+    let mut pair = Pair::new(Default::default(), server_config);
+    let (client_ch, server_ch) = pair.connect();
+
+    for _ in 0..2 {
+        let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+        pair.client_send(client_ch, s).write(b"hello").unwrap();
+        pair.client_send(client_ch, s).reset(VarInt(42)).unwrap();
+    }
+    // Drop the acknowledgements, so that the server receives the resets again
+    while pair
+        .client_conn_mut(client_ch)
+        .stats()
+        .frame_tx
+        .reset_stream
+        <= 2
+    {
+        pair.step();
+        pair.client.inbound.clear();
+    }
+    pair.drive();
+
+    assert!(!pair.server_conn_mut(server_ch).is_closed());
+}
+
+#[test]
+fn connection_close_sends_acks() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _server_ch) = pair.connect();
+
+    let client_acks = pair.client_conn_mut(client_ch).stats().frame_rx.acks;
+
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+
+    let time = pair.time;
+    pair.server_conn_mut(client_ch)
+        .close(time, VarInt(42), Bytes::new());
+
+    pair.drive();
+
+    let client_acks_2 = pair.client_conn_mut(client_ch).stats().frame_rx.acks;
+    assert!(
+        client_acks_2 > client_acks,
+        "Connection close should send pending ACKs"
+    );
+}
+
+#[test]
+fn retransmit_stats() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    let before = pair.client_conn_mut(client_ch).stats();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[42; 500]).unwrap();
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.outbound.clear(); // Drop the stream data
+    pair.drive();
+    assert_eq!(stream_chunks(pair.server_recv(server_ch, s)).len(), 500);
+
+    let lost = iter::from_fn(|| pair.client_conn_mut(client_ch).poll_transport_event())
+        .filter_map(|event| match event {
+            TransportEvent::PacketsLost {
+                space: SpaceId::Data,
+                trigger,
+                packets,
+                ..
+            } => Some((trigger, packets.len())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(lost, [(LossTrigger::ProbeTimeout, 1)]);
+
+    let stats = pair.client_conn_mut(client_ch).stats();
+    assert!(stats.path.pto_count > before.path.pto_count);
+    assert_eq!(
+        stats.path.retransmitted_bytes - before.path.retransmitted_bytes,
+        500
+    );
+    assert_eq!(stats.path.spurious_lost_packets, 0);
+}
+
+#[test]
+fn payload_stats() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[42; 5000]).unwrap();
+    pair.client_conn_mut(client_ch)
+        .datagrams()
+        .send(vec![7; 100].into(), true)
+        .unwrap();
+    pair.drive();
+    assert_eq!(stream_chunks(pair.server_recv(server_ch, s)).len(), 5000);
+
+    let client = pair.client_conn_mut(client_ch).stats().udp_tx;
+    let server = pair.server_conn_mut(server_ch).stats().udp_rx;
+    assert_eq!(client.payload_bytes, 5100);
+    assert_eq!(server.payload_bytes, 5100);
+    assert_eq!(client.sizes.count(), client.datagrams);
+    assert_eq!(server.sizes.count(), server.datagrams);
+    let overhead = client.overhead().unwrap();
+    assert!(overhead > 0.0 && overhead < 1.0);
+}
+
+#[test]
+fn custom_loss_detector() {
+    /// Deems every packet lost once a later packet is acknowledged
+    struct Eager;
+
+    impl loss::LossDetectorFactory for Eager {
+        fn build(self: Arc<Self>, _: loss::LossThresholds) -> Box<dyn loss::LossDetector> {
+            Box::new(Self)
+        }
+    }
+
+    impl loss::LossDetector for Eager {
+        fn check(
+            &self,
+            _: Instant,
+            _: &RttEstimator,
+            _: u64,
+            _: &loss::UnackedPacket,
+        ) -> loss::LossVerdict {
+            loss::LossVerdict::Lost(LossTrigger::PacketThreshold)
+        }
+    }
+
+    let _guard = subscribe();
+    let mut transport = TransportConfig::default();
+    transport.loss_detector_factory(Arc::new(Eager));
+    let mut config = client_config();
+    config.transport_config(Arc::new(transport));
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect_with(config);
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.outbound.clear(); // Drop the first packet
+    pair.client_send(client_ch, s).write(&[43; 100]).unwrap();
+    pair.drive();
+    assert_eq!(stream_chunks(pair.server_recv(server_ch, s)).len(), 200);
+
+    // The default detector would only deem the packet lost after the time threshold, as fewer than
+    // three later packets were acknowledged
+    let lost = iter::from_fn(|| pair.client_conn_mut(client_ch).poll_transport_event())
+        .filter_map(|event| match event {
+            TransportEvent::PacketsLost {
+                space: SpaceId::Data,
+                trigger,
+                packets,
+                ..
+            } => Some((trigger, packets.len())),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(lost, [(LossTrigger::PacketThreshold, 1)]);
+}
+
+#[test]
+fn ack_policy() {
+    let _guard = subscribe();
+    let mut server_config = server_config();
+    let mut ack_policy = AckPolicyConfig::default();
+    ack_policy
+        .ack_eliciting_threshold(VarInt(0))
+        .max_ack_delay(Duration::from_millis(5));
+    Arc::get_mut(&mut server_config.transport)
+        .unwrap()
+        .ack_policy_config(ack_policy);
+    let mut pair = Pair::new(Default::default(), server_config);
+    let (client_ch, server_ch) = pair.connect();
+    let peer_params = pair
+        .client_conn_mut(client_ch)
+        .peer_transport_parameters()
+        .unwrap();
+    assert_eq!(peer_params.max_ack_delay, Duration::from_millis(5));
+
+    let acks = pair.server_conn_mut(server_ch).stats().frame_tx.acks;
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    for _ in 0..4 {
+        pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+        pair.drive_client();
+        pair.drive_server();
+    }
+    // Every packet is acknowledged immediately, rather than every other packet
+    let acks = pair.server_conn_mut(server_ch).stats().frame_tx.acks - acks;
+    assert_eq!(acks, 4);
+}
+
+#[test]
+fn ack_redundancy() {
+    let _guard = subscribe();
+    let mut server_config = server_config();
+    let mut ack_policy = AckPolicyConfig::default();
+    ack_policy.ack_eliciting_threshold(VarInt(0)).redundancy(2);
+    Arc::get_mut(&mut server_config.transport)
+        .unwrap()
+        .ack_policy_config(ack_policy);
+    let mut pair = Pair::new(Default::default(), server_config);
+    let (client_ch, server_ch) = pair.connect();
+    pair.drive();
+    let acks = pair.server_conn_mut(server_ch).stats().frame_tx.acks;
+    let before = pair.client_conn_mut(client_ch).stats().path;
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&[42; 100]).unwrap();
+    pair.drive_client();
+    pair.server.drive(pair.time, pair.client.addr);
+    pair.server.outbound.pop_front(); // Drop the first ACK
+    pair.drive();
+
+    // Each ACK is sent three times, so losing one doesn't cause the data to be probed for
+    let acks = pair.server_conn_mut(server_ch).stats().frame_tx.acks - acks;
+    assert_eq!(acks, 3);
+    let stats = pair.client_conn_mut(client_ch).stats().path;
+    assert_eq!(stats.pto_count, before.pto_count);
+    assert_eq!(stats.lost_packets, before.lost_packets);
+    assert_eq!(pair.client_conn_mut(client_ch).bytes_in_flight(), 0);
+}
+
+#[test]
+fn probe_rtt() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    pair.latency = Duration::from_millis(10);
+    let pings = pair.client_conn_mut(client_ch).stats().frame_tx.ping;
+
+    pair.client_conn_mut(client_ch).probe_rtt();
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.outbound.clear(); // Drop the probe, so it's repeated
+    pair.drive();
+    let probed = iter::from_fn(|| pair.client_conn_mut(client_ch).poll_transport_event())
+        .filter_map(|event| match event {
+            TransportEvent::RttProbed { rtt } => Some(rtt),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(probed, [Duration::from_millis(20)]);
+    let pings = pair.client_conn_mut(client_ch).stats().frame_tx.ping - pings;
+    assert_eq!(pings, 2);
+}
+
+#[test]
+fn validate_path() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect();
+    let validations = |pair: &mut Pair| {
+        iter::from_fn(|| pair.client_conn_mut(client_ch).poll())
+            .filter_map(|event| match event {
+                Event::PathValidation { remote, validated } => Some((remote, validated)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // The first challenge is lost, so it's repeated
+    let challenges = pair
+        .client_conn_mut(client_ch)
+        .stats()
+        .frame_tx
+        .path_challenge;
+    let now = pair.time;
+    pair.client_conn_mut(client_ch).validate_path(now);
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.outbound.clear();
+    pair.drive();
+    assert_eq!(validations(&mut pair), [(pair.server.addr, true)]);
+    let challenges = pair
+        .client_conn_mut(client_ch)
+        .stats()
+        .frame_tx
+        .path_challenge
+        - challenges;
+    assert_eq!(challenges, 2);
+
+    // Nobody answers at the alternative address, which is probed with a different CID
+    let alternative = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 1);
+    let now = pair.time;
+    pair.client_conn_mut(client_ch)
+        .validate_path_to(now, alternative)
+        .unwrap();
+    pair.client_conn_mut(client_ch)
+        .validate_path_to(now, alternative)
+        .unwrap();
+    pair.client_conn_mut(client_ch).ping();
+    pair.client.drive(pair.time, pair.server.addr);
+    let dcid = |addr| {
+        let (_, buf) = pair
+            .client
+            .outbound
+            .iter()
+            .find(|(transmit, _)| transmit.destination == addr)
+            .unwrap();
+        buf[1..9].to_vec()
+    };
+    assert_ne!(dcid(alternative), dcid(pair.server.addr));
+    pair.drive();
+    assert_eq!(validations(&mut pair), [(alternative, false)]);
+    assert_eq!(
+        pair.client_conn_mut(client_ch).remote_address(),
+        pair.server.addr
+    );
+}
+
+#[test]
+fn validate_path_without_spare_cid() {
+    let _guard = subscribe();
+    let mut server_config = server_config();
+    let mut transport = TransportConfig::default();
+    transport.max_issued_cids(1);
+    server_config.transport_config(Arc::new(transport));
+    let mut pair = Pair::new(Arc::new(EndpointConfig::default()), server_config);
+    let (client_ch, _) = pair.connect();
+
+    let alternative = SocketAddr::new(Ipv6Addr::LOCALHOST.into(), 1);
+    let now = pair.time;
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .validate_path_to(now, alternative),
+        Err(ValidatePathError::NoConnectionId)
+    );
+    // The current path can still be validated
+    pair.client_conn_mut(client_ch).validate_path(now);
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::PathValidation {
+            validated: true,
+            ..
+        })
+    );
+}
+
+#[test]
+fn server_hs_retransmit() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    pair.step();
+    assert!(!pair.client.inbound.is_empty()); // Initial + Handshakes
+    pair.client.inbound.clear();
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Connected)
+    );
+}
+
+#[test]
+fn observed_address() {
+    let _guard = subscribe();
+    let mut server_config = server_config();
+    Arc::get_mut(&mut server_config.transport)
+        .unwrap()
+        .send_observed_address_reports(true);
+    let mut pair = Pair::new(Default::default(), server_config);
+    let mut transport = TransportConfig::default();
+    transport.receive_observed_address_reports(true);
+    let (client_ch, server_ch) = pair.connect_with(ClientConfig {
+        transport: Arc::new(transport),
+        ..client_config()
+    });
+    pair.drive();
+    let observed = |pair: &mut Pair| {
+        iter::from_fn(|| pair.client_conn_mut(client_ch).poll_transport_event())
+            .filter_map(|event| match event {
+                TransportEvent::ObservedAddr { address } => Some(address),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(observed(&mut pair), [pair.client.addr]);
+    assert_eq!(
+        pair.client_conn_mut(client_ch).observed_address(),
+        Some(pair.client.addr)
+    );
+    assert!(
+        pair.client_conn_mut(client_ch)
+            .negotiated_extensions()
+            .contains(&Extension::AddressDiscovery)
+    );
+    // The server didn't ask for reports
+    assert_eq!(pair.server_conn_mut(server_ch).observed_address(), None);
+
+    // A new address is reported after migrating
+    pair.client.addr = SocketAddr::new(
+        Ipv4Addr::new(127, 0, 0, 1).into(),
+        CLIENT_PORTS.lock().unwrap().next().unwrap(),
+    );
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive();
+    assert_eq!(observed(&mut pair), [pair.client.addr]);
+    assert_eq!(
+        pair.client_conn_mut(client_ch).observed_address(),
+        Some(pair.client.addr)
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .stats()
+            .frame_rx
+            .observed_addr,
+        2
+    );
+}
+
+#[test]
+fn migration() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    pair.drive();
+
+    let client_stats_after_connect = pair.client_conn_mut(client_ch).stats();
+
+    pair.client.addr = SocketAddr::new(
+        Ipv4Addr::new(127, 0, 0, 1).into(),
+        CLIENT_PORTS.lock().unwrap().next().unwrap(),
+    );
     pair.client_conn_mut(client_ch).ping();
 
     // Assert that just receiving the ping message is accounted into the servers
@@ -1511,6 +2464,79 @@ fn conn_flow_control() {
     );
 }
 
+#[test]
+fn send_flow_control() {
+    let _guard = subscribe();
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            stream_receive_window: 2000u32.into(),
+            receive_window: 3000u32.into(),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, _) = pair.connect();
+    let msg = vec![0xAB; 3000];
+
+    let s1 = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    let state = pair
+        .client_conn_mut(client_ch)
+        .send_flow_control(s1)
+        .unwrap();
+    assert_eq!(state.stream_max_data, 2000);
+    assert_eq!(state.stream_data_sent, 0);
+    assert_eq!(state.connection_max_data, 3000);
+    assert_eq!(state.blocked, None);
+
+    assert_eq!(pair.client_send(client_ch, s1).write(&msg), Ok(2000));
+    let state = pair
+        .client_conn_mut(client_ch)
+        .send_flow_control(s1)
+        .unwrap();
+    assert_eq!(state.stream_data_sent, 2000);
+    assert_eq!(state.connection_data_sent, 2000);
+    assert_eq!(state.blocked, Some(SendBlockReason::Stream));
+
+    let s2 = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    assert_eq!(pair.client_send(client_ch, s2).write(&msg), Ok(1000));
+    let state = pair
+        .client_conn_mut(client_ch)
+        .send_flow_control(s2)
+        .unwrap();
+    assert_eq!(state.stream_data_sent, 1000);
+    assert_eq!(state.connection_data_sent, 3000);
+    assert_eq!(state.unacked_data, 3000);
+    assert_eq!(state.blocked, Some(SendBlockReason::Connection));
+
+    pair.drive();
+    let state = pair
+        .client_conn_mut(client_ch)
+        .send_flow_control(s2)
+        .unwrap();
+    assert_eq!(state.unacked_data, 0);
+    assert_eq!(state.blocked, Some(SendBlockReason::Connection));
+
+    // The local send window applies independently of the peer's limits
+    let client = ClientConfig {
+        transport: Arc::new(TransportConfig {
+            send_window: 500,
+            ..TransportConfig::default()
+        }),
+        ..client_config()
+    };
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect_with(client);
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    assert_eq!(pair.client_send(client_ch, s).write(&msg), Ok(500));
+    let state = pair
+        .client_conn_mut(client_ch)
+        .send_flow_control(s)
+        .unwrap();
+    assert_eq!(state.send_window, 500);
+    assert_eq!(state.blocked, Some(SendBlockReason::SendWindow));
+}
+
 #[test]
 fn stop_opens_bidi() {
     let _guard = subscribe();
@@ -1604,30 +2630,319 @@ fn zero_length_cid() {
 }
 
 #[test]
-fn keep_alive() {
+fn zero_length_client_cids() {
+    let _guard = subscribe();
+    let mut endpoint_config = EndpointConfig::default();
+    endpoint_config.zero_length_client_cids(true);
+    let client = Endpoint::new(Arc::new(endpoint_config), None, true);
+    let server = Endpoint::new(
+        Arc::new(EndpointConfig::default()),
+        Some(Arc::new(server_config())),
+        true,
+    );
+    let mut pair = Pair::new_from_endpoint(client, server);
+    let (client_ch, server_ch) = pair.connect();
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .connection_ids()
+            .local_active,
+        [(0, ConnectionId::new(&[]))]
+    );
+    assert!(
+        pair.server_conn_mut(server_ch)
+            .connection_ids()
+            .remote_active
+            .1
+            .is_empty()
+    );
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(b"hello").unwrap();
+    pair.drive();
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(stream) if stream == s);
+    let mut recv = pair.server_recv(server_ch, s);
+    let mut chunks = recv.read(false).unwrap();
+    assert_matches!(chunks.next(usize::MAX), Ok(Some(chunk)) if chunk.bytes[..] == b"hello"[..]);
+    let _ = chunks.finalize();
+
+    // Only one connection per peer address can be told apart without connection IDs
+    let (_, conn) = pair
+        .client
+        .endpoint
+        .connect(pair.time, client_config(), pair.server.addr, "localhost")
+        .unwrap();
+    assert!(
+        conn.connection_ids()
+            .local_active
+            .iter()
+            .all(|(_, cid)| !cid.is_empty())
+    );
+}
+
+#[test]
+fn keep_alive() {
+    let _guard = subscribe();
+    const IDLE_TIMEOUT: u64 = 10;
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            keep_alive_interval: Some(Duration::from_millis(IDLE_TIMEOUT / 2)),
+            max_idle_timeout: Some(VarInt(IDLE_TIMEOUT)),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+    // Run a good while longer than the idle timeout
+    let end = pair.time + Duration::from_millis(20 * IDLE_TIMEOUT);
+    while pair.time < end {
+        if !pair.step() {
+            if let Some(time) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = time;
+            }
+        }
+        assert!(!pair.client_conn_mut(client_ch).is_closed());
+        assert!(!pair.server_conn_mut(server_ch).is_closed());
+    }
+}
+
+#[test]
+fn keep_alive_while_active() {
+    let _guard = subscribe();
+    const IDLE_TIMEOUT: u64 = 10;
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            keep_alive_interval: Some(Duration::from_millis(IDLE_TIMEOUT / 2)),
+            keep_alive_while_active: true,
+            max_idle_timeout: Some(VarInt(IDLE_TIMEOUT)),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    // Kept alive while a stream is open
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(b"hello").unwrap();
+    let end = pair.time + Duration::from_millis(20 * IDLE_TIMEOUT);
+    while pair.time < end {
+        if !pair.step() {
+            if let Some(time) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = time;
+            }
+        }
+        assert!(!pair.client_conn_mut(client_ch).is_closed());
+        assert!(!pair.server_conn_mut(server_ch).is_closed());
+    }
+
+    // Once the stream is closed, the connection idles out
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.drive();
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(id) if id == s);
+    let mut recv = pair.server_recv(server_ch, s);
+    let mut chunks = recv.read(false).unwrap();
+    assert_matches!(chunks.next(usize::MAX), Ok(Some(_)));
+    assert_matches!(chunks.next(usize::MAX), Ok(None));
+    let _ = chunks.finalize();
+    let start = pair.time;
+    while !pair.server_conn_mut(server_ch).is_closed() {
+        if !pair.step() {
+            if let Some(time) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = time;
+            }
+        }
+    }
+    assert!(pair.time - start < Duration::from_millis(20 * IDLE_TIMEOUT));
+    let reason = loop {
+        match pair.server_conn_mut(server_ch).poll() {
+            Some(Event::ConnectionLost { reason }) => break reason,
+            Some(_) => {}
+            None => panic!("connection wasn't lost"),
+        }
+    };
+    assert_matches!(
+        reason,
+        ConnectionError::TimedOut(TimeoutCause {
+            keep_alive: false,
+            ..
+        })
+    );
+}
+
+#[test]
+fn adaptive_keep_alive() {
+    let _guard = subscribe();
+    // Inactivity after which the client's NAT assigns it a new port
+    const NAT_TIMEOUT: Duration = Duration::from_secs(30);
+    let mut keep_alive = AdaptiveKeepAliveConfig::default();
+    keep_alive.initial_interval(Duration::from_secs(10));
+    let client_config = {
+        let mut transport = TransportConfig::default();
+        transport
+            .adaptive_keep_alive(Some(keep_alive))
+            .max_idle_timeout(Some(VarInt(600_000).into()));
+        let mut config = client_config();
+        config.transport_config(Arc::new(transport));
+        config
+    };
+    let mut server_config = server_config();
+    Arc::get_mut(&mut server_config.transport)
+        .unwrap()
+        .max_idle_timeout(Some(VarInt(600_000).into()));
+    let mut pair = Pair::new(Default::default(), server_config);
+    let (client_ch, server_ch) = pair.connect_with(client_config);
+    pair.drive();
+
+    let mut last_sent = pair.time;
+    let end = pair.time + Duration::from_secs(600);
+    let mut settled = None;
+    while pair.time < end && settled.is_none() {
+        if pair.time - last_sent > NAT_TIMEOUT {
+            pair.client.addr = SocketAddr::new(
+                Ipv4Addr::new(127, 0, 0, 1).into(),
+                CLIENT_PORTS.lock().unwrap().next().unwrap(),
+            );
+        }
+        let (time, sent) = (pair.time, pair.client_conn_mut(client_ch).stats().udp_tx);
+        if !pair.step() {
+            if let Some(time) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
+                pair.time = time;
+            }
+        }
+        if pair.client_conn_mut(client_ch).stats().udp_tx.datagrams != sent.datagrams {
+            last_sent = time;
+        }
+        settled = iter::from_fn(|| pair.client_conn_mut(client_ch).poll_transport_event())
+            .find_map(|event| match event {
+                TransportEvent::KeepAliveSettled { interval } => Some(interval),
+                _ => None,
+            });
+        assert!(!pair.server_conn_mut(server_ch).is_closed());
+    }
+
+    // Keep-alives after 10s, 15s and 22.5s were answered, whereas 33.75s exceeded the NAT timeout
+    let interval = Duration::from_millis(22_500);
+    assert_eq!(settled, Some(interval));
+    assert_eq!(
+        pair.client_conn_mut(client_ch).keep_alive_interval(),
+        Some(interval)
+    );
+}
+
+#[test]
+fn dscp() {
+    let _guard = subscribe();
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .dscp(Some(46));
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect_with(client_config);
+    pair.drive();
+
+    pair.client_conn_mut(client_ch).ping();
+    pair.client.drive(pair.time, pair.server.addr);
+    assert!(!pair.client.outbound.is_empty());
+    assert!(pair.client.outbound.iter().all(|(t, _)| t.dscp == Some(46)));
+    pair.drive();
+
+    pair.client_conn_mut(client_ch).set_dscp(Some(10));
+    pair.client_conn_mut(client_ch).ping();
+    pair.client.drive(pair.time, pair.server.addr);
+    assert!(!pair.client.outbound.is_empty());
+    assert!(pair.client.outbound.iter().all(|(t, _)| t.dscp == Some(10)));
+}
+
+#[test]
+fn transmit_marker() {
+    let _guard = subscribe();
+    let kinds = Arc::new(Mutex::new(Vec::new()));
+    let marker = {
+        let kinds = kinds.clone();
+        move |info: &TransmitInfo, marking: &mut TransmitMarking| {
+            kinds.lock().unwrap().push(info.kind);
+            marking.dscp = match info.kind {
+                TransmitKind::LossProbe => Some(8),
+                _ => marking.dscp.map(|x| x + 1),
+            };
+        }
+    };
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .dscp(Some(45))
+        .transmit_marker(Some(Arc::new(marker)));
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect_with(client_config);
+    pair.drive();
+    assert!(kinds.lock().unwrap().contains(&TransmitKind::MtuProbe));
+
+    pair.client_conn_mut(client_ch).ping();
+    pair.client.drive(pair.time, pair.server.addr);
+    assert!(!pair.client.outbound.is_empty());
+    assert!(pair.client.outbound.iter().all(|(t, _)| t.dscp == Some(46)));
+    pair.drive();
+
+    // Lose a ping so that a loss probe is sent
+    kinds.lock().unwrap().clear();
+    let default_mtu = mem::replace(&mut pair.mtu, 0);
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+    pair.mtu = default_mtu;
+    pair.time = pair.client.next_wakeup().unwrap();
+    pair.client.drive(pair.time, pair.server.addr);
+    assert!(kinds.lock().unwrap().contains(&TransmitKind::LossProbe));
+    assert!(pair.client.outbound.iter().any(|(t, _)| t.dscp == Some(8)));
+
+    pair.client_conn_mut(client_ch).set_transmit_marker(None);
+    pair.drive();
+    pair.client_conn_mut(client_ch).ping();
+    pair.client.drive(pair.time, pair.server.addr);
+    assert!(pair.client.outbound.iter().all(|(t, _)| t.dscp == Some(45)));
+}
+
+#[test]
+fn flow_label() {
     let _guard = subscribe();
-    const IDLE_TIMEOUT: u64 = 10;
-    let server = ServerConfig {
-        transport: Arc::new(TransportConfig {
-            keep_alive_interval: Some(Duration::from_millis(IDLE_TIMEOUT / 2)),
-            max_idle_timeout: Some(VarInt(IDLE_TIMEOUT)),
-            ..TransportConfig::default()
-        }),
-        ..server_config()
-    };
-    let mut pair = Pair::new(Default::default(), server);
-    let (client_ch, server_ch) = pair.connect();
-    // Run a good while longer than the idle timeout
-    let end = pair.time + Duration::from_millis(20 * IDLE_TIMEOUT);
-    while pair.time < end {
-        if !pair.step() {
-            if let Some(time) = min_opt(pair.client.next_wakeup(), pair.server.next_wakeup()) {
-                pair.time = time;
-            }
-        }
-        assert!(!pair.client_conn_mut(client_ch).is_closed());
-        assert!(!pair.server_conn_mut(server_ch).is_closed());
-    }
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .flow_label(true);
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect_with(client_config);
+    pair.drive();
+
+    pair.client_conn_mut(client_ch).ping();
+    pair.client.drive(pair.time, pair.server.addr);
+    let label = pair.client.outbound[0].0.flow_label;
+    assert!(label.is_some());
+    assert!(
+        pair.client
+            .outbound
+            .iter()
+            .all(|(t, _)| t.flow_label == label)
+    );
+    pair.drive();
+
+    // Switching to a new remote CID picks a new label
+    pair.client_conn_mut(client_ch).local_address_changed();
+    pair.client.drive(pair.time, pair.server.addr);
+    let new_label = pair.client.outbound[0].0.flow_label;
+    assert!(new_label.is_some());
+    assert_ne!(new_label, label);
+    pair.drive();
+
+    // Server doesn't set flow labels by default
+    pair.server_conn_mut(server_ch).ping();
+    pair.server.drive(pair.time, pair.client.addr);
+    assert!(!pair.server.outbound.is_empty());
+    assert!(
+        pair.server
+            .outbound
+            .iter()
+            .all(|(t, _)| t.flow_label.is_none())
+    );
 }
 
 #[test]
@@ -1689,6 +3004,118 @@ fn cid_rotation() {
     }
 }
 
+#[test]
+fn rem_cid_rotation() {
+    let _guard = subscribe();
+    const INTERVAL: Duration = Duration::from_secs(1);
+    let mut rotation = CidRotationConfig::default();
+    rotation.interval(Some(INTERVAL));
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .cid_rotation(Some(rotation));
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect_with(client_config);
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).active_rem_cid_seq(), 0);
+
+    pair.time += INTERVAL / 2;
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).active_rem_cid_seq(), 0);
+
+    pair.time += INTERVAL;
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).active_rem_cid_seq(), 1);
+    assert!(!pair.client_conn_mut(client_ch).is_closed());
+    assert!(!pair.server_conn_mut(server_ch).is_closed());
+}
+
+#[test]
+fn port_hopping() {
+    let _guard = subscribe();
+    const INTERVAL: Duration = Duration::from_secs(10);
+    let mut pair = Pair::default();
+    let original = pair.server.addr;
+    let hop = SocketAddr::new(original.ip(), original.port().wrapping_add(1));
+    let mut hopping = PortHoppingConfig::default();
+    hopping.ports([hop.port()]).interval(INTERVAL);
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .port_hopping(Some(hopping));
+    let (client_ch, server_ch) = pair.connect_with(client_config);
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).remote_address(), original);
+
+    // The server doesn't receive on the new port, so the client falls back
+    pair.time += INTERVAL;
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+    assert_eq!(pair.client_conn_mut(client_ch).remote_address(), hop);
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).remote_address(), original);
+    assert!(!pair.client_conn_mut(client_ch).is_closed());
+
+    // Once the server receives on the new port, the hop succeeds
+    pair.time += INTERVAL;
+    pair.client_conn_mut(client_ch).ping();
+    pair.server.addr = hop;
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).remote_address(), hop);
+    assert_eq!(pair.client_conn_mut(client_ch).active_rem_cid_seq(), 2);
+    assert!(!pair.client_conn_mut(client_ch).is_closed());
+    assert!(!pair.server_conn_mut(server_ch).is_closed());
+
+    // Packets from the original port are still accepted
+    pair.server.addr = original;
+    let s = pair
+        .server_conn_mut(server_ch)
+        .streams()
+        .open(Dir::Uni)
+        .unwrap();
+    pair.server_conn_mut(server_ch)
+        .send_stream(s)
+        .write(b"hello")
+        .unwrap();
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Stream(StreamEvent::Opened { dir: Dir::Uni }))
+    );
+}
+
+#[test]
+fn port_hopping_without_spare_cid() {
+    let _guard = subscribe();
+    const INTERVAL: Duration = Duration::from_secs(10);
+    let mut server_config = server_config();
+    let mut transport = TransportConfig::default();
+    transport.max_issued_cids(1);
+    server_config.transport_config(Arc::new(transport));
+    let mut pair = Pair::new(Arc::new(EndpointConfig::default()), server_config);
+    let original = pair.server.addr;
+    let hop = SocketAddr::new(original.ip(), original.port().wrapping_add(1));
+    let mut hopping = PortHoppingConfig::default();
+    hopping.ports([hop.port()]).interval(INTERVAL);
+    let mut client_config = client_config();
+    Arc::get_mut(&mut client_config.transport)
+        .unwrap()
+        .port_hopping(Some(hopping));
+    let (client_ch, _) = pair.connect_with(client_config);
+    pair.drive();
+
+    // Hopping would reuse the only CID, linking the two ports
+    pair.time += INTERVAL;
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive_client();
+    assert_eq!(pair.client_conn_mut(client_ch).remote_address(), original);
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).active_rem_cid_seq(), 0);
+    assert!(!pair.client_conn_mut(client_ch).is_closed());
+}
+
 #[test]
 fn cid_retirement() {
     let _guard = subscribe();
@@ -1724,6 +3151,91 @@ fn cid_retirement() {
     );
 }
 
+#[test]
+fn connection_ids() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    pair.drive();
+
+    use crate::cid_queue::CidQueue;
+    let client_ids = pair.client_conn_mut(client_ch).connection_ids();
+    let server_ids = pair.server_conn_mut(server_ch).connection_ids();
+    // Each side issues as many CIDs as the other is willing to store
+    assert_eq!(client_ids.local_active.len(), CidQueue::LEN);
+    assert_eq!(server_ids.local_active.len(), CidQueue::LEN);
+    assert_eq!(client_ids.remote_active, server_ids.local_active[0]);
+    assert_eq!(client_ids.remote_available, server_ids.local_active[1..]);
+    assert_eq!(client_ids.remote_retired, 0);
+    assert_eq!(server_ids.local_retired, 0);
+
+    // Migrating switches to an unused CID, so packets on the new path can't be linked to the old
+    let (_, old_cid) = client_ids.remote_active;
+    pair.client_conn_mut(client_ch).local_address_changed();
+    pair.client.drive(pair.time, pair.server.addr);
+    let (new_seq, new_cid) = pair
+        .client_conn_mut(client_ch)
+        .connection_ids()
+        .remote_active;
+    assert_eq!(new_seq, 1);
+    assert_ne!(new_cid, old_cid);
+    assert!(!pair.client.outbound.is_empty());
+    for (_, datagram) in &pair.client.outbound {
+        assert_eq!(&datagram[1..1 + new_cid.len()], &new_cid[..]);
+        assert!(!datagram.windows(old_cid.len()).any(|x| x == &old_cid[..]));
+    }
+    pair.drive();
+
+    // The server learns of the retirement and issues a replacement
+    let client_ids = pair.client_conn_mut(client_ch).connection_ids();
+    let server_ids = pair.server_conn_mut(server_ch).connection_ids();
+    assert_eq!(client_ids.remote_retired, 1);
+    assert_eq!(server_ids.local_retired, 1);
+    assert_eq!(server_ids.local_active.len(), CidQueue::LEN);
+    assert!(
+        server_ids
+            .local_active
+            .iter()
+            .all(|&(_, cid)| cid != old_cid)
+    );
+    assert_eq!(client_ids.remote_active, server_ids.local_active[0]);
+    assert_eq!(client_ids.remote_available, server_ids.local_active[1..]);
+}
+
+#[test]
+fn max_issued_cids() {
+    let _guard = subscribe();
+    let mut server_config = server_config();
+    let mut transport = TransportConfig::default();
+    transport.max_issued_cids(2);
+    server_config.transport_config(Arc::new(transport));
+    let mut pair = Pair::new(Default::default(), server_config);
+    let (client_ch, server_ch) = pair.connect();
+    pair.drive();
+    assert_eq!(
+        pair.server_conn_mut(server_ch)
+            .connection_ids()
+            .local_active
+            .len(),
+        2
+    );
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .connection_ids()
+            .remote_available
+            .len(),
+        1
+    );
+
+    // A retired CID is replaced without exceeding the limit
+    pair.client_conn_mut(client_ch).local_address_changed();
+    pair.drive();
+    let client_ids = pair.client_conn_mut(client_ch).connection_ids();
+    assert_eq!(client_ids.remote_active.0, 1);
+    assert_eq!(client_ids.remote_available.len(), 1);
+    assert!(!pair.client_conn_mut(client_ch).is_closed());
+}
+
 #[test]
 fn finish_stream_flow_control_reordered() {
     let _guard = subscribe();
@@ -1797,7 +3309,82 @@ fn handshake_1rtt_handling() {
 
     pair.drive();
 
+    // The 1-RTT data is processed once the handshake completes, rather than retransmitted
+    assert_eq!(pair.client_conn_mut(client_ch).stats().path.lost_packets, 0);
+    let stats = pair.server_conn_mut(server_ch).stats();
+    assert!(stats.deferred_packets != 0);
+    assert_eq!(stats.deferred_packets_dropped, 0);
+    let mut recv = pair.server_recv(server_ch, s);
+    let mut chunks = recv.read(false).unwrap();
+    assert_matches!(
+        chunks.next(usize::MAX),
+        Ok(Some(chunk)) if chunk.offset == 0 && chunk.bytes == MSG
+    );
+    let _ = chunks.finalize();
+}
+
+#[test]
+fn half_rtt_data_reordered() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let client_ch = pair.begin_connect(client_config());
+    pair.drive_client();
+    pair.server.drive(pair.time, pair.client.addr);
+    let server_ch = pair.server.assert_accept();
+    // Deliver 0.5-RTT data ahead of the server's handshake flight, so the client doesn't have
+    // 1-RTT keys yet when it arrives
+    pair.server.delay_outbound();
+    let s = pair.server_streams(server_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello";
+    pair.server_send(server_ch, s).write(MSG).unwrap();
+    // Let pacing release the data
+    pair.time = pair.server.next_wakeup().unwrap();
+    pair.server.drive(pair.time, pair.client.addr);
+    assert!(!pair.server.outbound.is_empty());
+    pair.server.finish_delay();
+
+    pair.drive();
+
+    assert_eq!(pair.server_conn_mut(server_ch).stats().path.lost_packets, 0);
+    assert!(pair.client_conn_mut(client_ch).stats().deferred_packets != 0);
+    let mut recv = pair.client_recv(client_ch, s);
+    let mut chunks = recv.read(false).unwrap();
+    assert_matches!(
+        chunks.next(usize::MAX),
+        Ok(Some(chunk)) if chunk.offset == 0 && chunk.bytes == MSG
+    );
+    let _ = chunks.finalize();
+}
+
+#[test]
+fn handshake_1rtt_handling_without_deferral() {
+    let _guard = subscribe();
+    let mut server_config = server_config();
+    let mut transport = TransportConfig::default();
+    transport.max_deferred_packets(0);
+    server_config.transport_config(Arc::new(transport));
+    let mut pair = Pair::new(Default::default(), server_config);
+    let client_ch = pair.begin_connect(client_config());
+    pair.drive_client();
+    pair.drive_server();
+    let server_ch = pair.server.assert_accept();
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.delay_outbound();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello";
+    pair.client_send(client_ch, s).write(MSG).unwrap();
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.finish_delay();
+
+    pair.drive();
+
+    // 1-RTT data received before the handshake completes is dropped and must be retransmitted
     assert!(pair.client_conn_mut(client_ch).stats().path.lost_packets != 0);
+    let stats = pair.server_conn_mut(server_ch).stats();
+    assert_eq!(stats.deferred_packets, 0);
+    assert!(stats.deferred_packets_dropped != 0);
     let mut recv = pair.server_recv(server_ch, s);
     let mut chunks = recv.read(false).unwrap();
     assert_matches!(
@@ -1993,6 +3580,39 @@ fn datagram_send_recv() {
     assert_matches!(pair.server_datagrams(server_ch).recv(), None);
 }
 
+#[test]
+fn datagram_after_stream_offset() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(b"hello").unwrap();
+    pair.client_datagrams(client_ch)
+        .send_after(b"first".as_slice().into(), true, s, 10)
+        .unwrap();
+    pair.client_datagrams(client_ch)
+        .send(b"second".as_slice().into(), true)
+        .unwrap();
+    pair.drive();
+    // Only the stream data written so far arrives, and both datagrams are held back
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::Stream(StreamEvent::Opened { dir: Dir::Uni }))
+    );
+    assert_matches!(pair.server_conn_mut(server_ch).poll(), None);
+    assert_eq!(pair.client_conn_mut(client_ch).stats().frame_tx.datagram, 0);
+
+    pair.client_send(client_ch, s).write(b"world").unwrap();
+    pair.drive();
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::DatagramReceived)
+    );
+    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), "first");
+    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), "second");
+}
+
 #[test]
 fn datagram_recv_buffer_overflow() {
     let _guard = subscribe();
@@ -2034,11 +3654,67 @@ fn datagram_recv_buffer_overflow() {
     assert_matches!(pair.server_datagrams(server_ch).recv(), None);
 
     pair.client_datagrams(client_ch)
-        .send(DATA1.into(), true)
+        .send(DATA1.into(), true)
+        .unwrap();
+    pair.drive();
+    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), DATA1);
+    assert_matches!(pair.server_datagrams(server_ch).recv(), None);
+}
+
+#[test]
+fn datagram_flows() {
+    let _guard = subscribe();
+    const WINDOW: usize = 100;
+    let server = ServerConfig {
+        transport: Arc::new(TransportConfig {
+            datagram_receive_buffer_size: Some(WINDOW),
+            datagram_classifier: Some(Arc::new(|data: &[u8]| u64::from(data[0]))),
+            ..TransportConfig::default()
+        }),
+        ..server_config()
+    };
+    let mut pair = Pair::new(Default::default(), server);
+    let (client_ch, server_ch) = pair.connect();
+
+    const A: &[u8] = &[0xA; 30];
+    const B1: &[u8] = &[0xB; 30];
+    const B2: &[u8] = &[0xB; 31];
+    const B3: &[u8] = &[0xB; 32];
+    for data in [A, B1, B2, B3] {
+        pair.client_datagrams(client_ch)
+            .send(data.into(), true)
+            .unwrap();
+    }
+    pair.drive();
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::DatagramReceived)
+    );
+
+    // The busiest flow's oldest datagram was dropped to make room
+    let mut datagrams = pair.server_datagrams(server_ch);
+    assert_eq!(datagrams.flow_stats(0xA).unwrap().dropped, 0);
+    assert_eq!(datagrams.flow_stats(0xB).unwrap().dropped, 1);
+    assert_eq!(datagrams.recv_flow(0xB).unwrap().unwrap(), B2);
+    assert_eq!(datagrams.recv().unwrap(), A);
+    assert_eq!(datagrams.recv().unwrap(), B3);
+    assert_matches!(datagrams.recv_flow(0xA), Ok(None));
+    assert_eq!(datagrams.flow_stats(0xB).unwrap().buffered_bytes, 0);
+}
+
+#[test]
+fn datagram_flows_without_classifier() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+
+    pair.client_datagrams(client_ch)
+        .send(Bytes::from_static(&[0xA]), true)
         .unwrap();
     pair.drive();
-    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), DATA1);
-    assert_matches!(pair.server_datagrams(server_ch).recv(), None);
+    let mut datagrams = pair.server_datagrams(server_ch);
+    assert_matches!(datagrams.recv_flow(0xA), Err(RecvFlowError::NoClassifier));
+    assert_eq!(datagrams.recv().unwrap(), [0xA][..]);
 }
 
 #[test]
@@ -2339,6 +4015,51 @@ fn server_can_send_3_inital_packets() {
     );
 }
 
+#[test]
+fn anti_amplification_factor() {
+    let _guard = subscribe();
+    let mut transport = TransportConfig::default();
+    transport
+        .initial_rtt(Duration::from_millis(10))
+        .anti_amplification_factor(1);
+    let (cert, key) = big_cert_and_key();
+    let mut server = server_config_with_cert(cert.clone(), key);
+    server.transport_config(Arc::new(transport));
+    let client = client_config_with_certs(vec![cert]);
+    let mut pair = Pair::new(Default::default(), server);
+
+    let client_ch = pair.begin_connect(client);
+    pair.drive_client();
+    pair.drive_server();
+    // Only as much as was received may be sent
+    assert_eq!(pair.client.inbound.len(), 1);
+    let server_ch = pair.server.assert_accept();
+    assert_eq!(
+        pair.server_conn_mut(server_ch).anti_amplification_budget(),
+        Some(0)
+    );
+    let blocked = pair
+        .server_conn_mut(server_ch)
+        .stats()
+        .path
+        .anti_amplification_blocked;
+    assert!(blocked > 0);
+
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Connected)
+    );
+    assert_eq!(
+        pair.server_conn_mut(server_ch).anti_amplification_budget(),
+        None
+    );
+}
+
 /// Generate a big fat certificate that can't fit inside the initial anti-amplification limit
 fn big_cert_and_key() -> (CertificateDer<'static>, PrivateKeyDer<'static>) {
     let cert = rcgen::generate_simple_self_signed(
@@ -2564,6 +4285,9 @@ fn blackhole_after_mtu_change_repairs_itself() {
     // Sanity check
     assert_eq!(pair.client_conn_mut(client_ch).path_mtu(), 1452);
     assert_eq!(pair.server_conn_mut(server_ch).path_mtu(), 1452);
+    let probe = pair.client_conn_mut(client_ch).mtu_probes().last().unwrap();
+    assert_eq!(probe.size, 1452);
+    assert_eq!(probe.outcome, MtuProbeOutcome::Acked);
 
     // Back to the base MTU
     pair.mtu = 1200;
@@ -2590,6 +4314,39 @@ fn blackhole_after_mtu_change_repairs_itself() {
     assert!(client_stats.path.lost_packets >= 3);
     assert!(client_stats.path.congestion_events >= 3);
     assert_eq!(client_stats.path.black_holes_detected, 1);
+    let client = pair.client_conn_mut(client_ch);
+    assert!(iter::from_fn(|| client.poll_transport_event()).any(|e| e
+        == TransportEvent::MtuBlackHoleDetected {
+            previous_mtu: 1452,
+            mtu: 1200,
+        }));
+}
+
+#[test]
+fn too_big_lowers_mtu_immediately() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    pair.mtu = 1500;
+    let (client_ch, server_ch) = pair.connect();
+    pair.drive();
+    assert_eq!(pair.client_conn_mut(client_ch).path_mtu(), 1452);
+
+    // The operating system rejects a full-sized packet after the link shrank
+    pair.mtu = 1300;
+    let now = pair.time;
+    pair.client_conn_mut(client_ch).handle_too_big(now, 1452);
+    assert_eq!(pair.client_conn_mut(client_ch).path_mtu(), 1200);
+
+    let payload = vec![42; 1300];
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    pair.client_send(client_ch, s).write(&payload).unwrap();
+    pair.drive();
+    assert_eq!(stream_chunks(pair.server_recv(server_ch, s)).len(), 1300);
+
+    // Discovery resumed below the rejected size, without waiting for black hole detection
+    let client_stats = pair.client_conn_mut(client_ch).stats();
+    assert_eq!(client_stats.path.black_holes_detected, 0);
+    assert!((1201..=1300).contains(&client_stats.path.current_mtu));
 }
 
 #[test]
@@ -3210,7 +4967,12 @@ fn silently_drop_rejected_initials() {
     assert_matches!(
         pair.client_conn_mut(client_ch).poll(),
         Some(Event::ConnectionLost {
-            reason: ConnectionError::TimedOut,
+            reason: ConnectionError::TimedOut(TimeoutCause {
+                timer: TimeoutTimer::Handshake,
+                since_last_received: None,
+                since_last_sent: Some(_),
+                ..
+            }),
         })
     );
 }
@@ -3268,10 +5030,10 @@ fn validate_then_reject_manually() {
     ));
     pair.drive();
     assert_matches!(pair.client_conn_mut(client_ch).poll(), None);
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -3431,6 +5193,42 @@ fn pad_to_mtu() {
     );
 }
 
+/// Verify that UDP datagrams carrying application data are padded according to the padding policy
+#[test]
+fn padding_policy() {
+    let _guard = subscribe();
+    const LEN: usize = 300;
+    for (policy, expected) in [
+        (PaddingPolicy::Fixed(1000), 1000),
+        (PaddingPolicy::Buckets([200, 500, 1000].into()), 500),
+    ] {
+        let mut client_config = client_config();
+        let mut transport = TransportConfig::default();
+        transport.padding_policy(policy);
+        client_config.transport_config(transport.into());
+        let mut pair = Pair::default();
+        let (client_ch, server_ch) = pair.connect_with(client_config);
+        let padding_before = pair.client_conn_mut(client_ch).stats().padding_bytes;
+
+        pair.client_datagrams(client_ch)
+            .send(vec![0; LEN].into(), false)
+            .unwrap();
+        pair.client.drive(pair.time, pair.server.addr);
+        assert_eq!(pair.client.outbound.len(), 1);
+        assert_eq!(pair.client.outbound[0].1.len(), expected);
+        assert!(pair.client_conn_mut(client_ch).stats().padding_bytes > padding_before);
+        pair.drive();
+
+        assert_eq!(
+            pair.server_datagrams(server_ch)
+                .recv()
+                .expect("datagram lost")
+                .len(),
+            LEN
+        );
+    }
+}
+
 /// Verify that a large application datagram is sent successfully when an ACK frame too large to fit
 /// alongside it is also queued, in exactly 2 UDP datagrams.
 #[test]
@@ -3687,3 +5485,335 @@ fn handshake_confirmation_no_resumption_shortcut() {
     );
     assert_matches!(pair.client_conn_mut(ch).poll(), None);
 }
+
+#[test]
+fn pause_sending() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+    pair.client_conn_mut(client_ch).pause_sending();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello";
+    pair.client_send(client_ch, s).write(MSG).unwrap();
+    pair.client_datagrams(client_ch)
+        .send(MSG.into(), true)
+        .unwrap();
+    // Other frames are still sent
+    pair.client_conn_mut(client_ch).ping();
+    let pings = pair.client_conn_mut(client_ch).stats().frame_tx.ping;
+    pair.drive();
+    assert_eq!(
+        pair.client_conn_mut(client_ch).stats().frame_tx.ping,
+        pings + 1
+    );
+    assert_eq!(pair.client_conn_mut(client_ch).stats().frame_tx.stream, 0);
+    assert_matches!(pair.server_conn_mut(server_ch).poll(), None);
+
+    pair.client_conn_mut(client_ch).resume_sending();
+    pair.drive();
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::DatagramReceived)
+    );
+    assert_matches!(
+        pair.server_conn_mut(server_ch).poll(),
+        Some(Event::Stream(StreamEvent::Opened { dir: Dir::Uni }))
+    );
+    assert_eq!(pair.server_datagrams(server_ch).recv().unwrap(), MSG);
+}
+
+#[test]
+fn bandwidth_estimate() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    pair.latency = Duration::from_millis(10);
+    let (client_ch, server_ch) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const LEN: usize = 256 * 1024;
+    let mut written = 0;
+    while written < LEN {
+        written += pair
+            .client_send(client_ch, s)
+            .write(&[0; 1024])
+            .unwrap_or(0);
+        pair.step();
+    }
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.drive();
+
+    let estimate = pair
+        .client_conn_mut(client_ch)
+        .bandwidth_estimate()
+        .unwrap();
+    assert!(estimate.bytes_per_second > 0);
+    // The server sent little more than the handshake
+    let server_estimate = pair.server_conn_mut(server_ch).bandwidth_estimate();
+    assert!(server_estimate.is_none_or(|x| x.app_limited));
+}
+
+#[test]
+fn batched_datagrams() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    pair.client.batch_inbound = true;
+    pair.server.batch_inbound = true;
+    let (client_ch, server_ch) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = &[0xab; 8 * 1024];
+    assert_eq!(pair.client_send(client_ch, s).write(MSG), Ok(MSG.len()));
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.drive_client();
+    // The server handles several datagrams at once
+    assert!(pair.server.inbound.len() > 1);
+    pair.drive();
+
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Stream(StreamEvent::Finished { id })) if id == s
+    );
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(stream) if stream == s);
+    let mut recv = pair.server_recv(server_ch, s);
+    let mut chunks = recv.read(true).unwrap();
+    let mut received = Vec::new();
+    while let Some(chunk) = chunks.next(usize::MAX).unwrap() {
+        received.extend_from_slice(&chunk.bytes);
+    }
+    let _ = chunks.finalize();
+    assert_eq!(received, MSG);
+}
+
+#[test]
+fn transport_config_presets() {
+    let _guard = subscribe();
+    for preset in [
+        TransportConfig::datacenter,
+        TransportConfig::satellite,
+        TransportConfig::mobile,
+        TransportConfig::bulk_transfer,
+    ] {
+        let mut server_config = server_config();
+        server_config.transport_config(Arc::new(preset()));
+        let mut client_config = client_config();
+        client_config.transport_config(Arc::new(preset()));
+        let mut pair = Pair::new(Default::default(), server_config);
+        let (client_ch, server_ch) = pair.connect_with(client_config);
+
+        let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+        const MSG: &[u8] = &[0xab; 64 * 1024];
+        assert_eq!(pair.client_send(client_ch, s).write(MSG), Ok(MSG.len()));
+        pair.client_send(client_ch, s).finish().unwrap();
+        pair.drive();
+
+        assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(stream) if stream == s);
+        let mut recv = pair.server_recv(server_ch, s);
+        let mut chunks = recv.read(true).unwrap();
+        let mut received = 0;
+        while let Some(chunk) = chunks.next(usize::MAX).unwrap() {
+            received += chunk.bytes.len();
+        }
+        let _ = chunks.finalize();
+        assert_eq!(received, MSG.len());
+    }
+}
+
+#[test]
+fn send_rate_limit() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    // Pacing requires a nonzero RTT
+    pair.latency = Duration::from_millis(10);
+    let (client_ch, _) = pair.connect();
+    pair.client_conn_mut(client_ch)
+        .set_max_outgoing_bytes_per_second(Some(50_000));
+    assert_eq!(
+        pair.client_conn_mut(client_ch)
+            .max_outgoing_bytes_per_second(),
+        Some(50_000)
+    );
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const LEN: usize = 100_000;
+    assert_eq!(pair.client_send(client_ch, s).write(&[0; LEN]), Ok(LEN));
+    pair.client_send(client_ch, s).finish().unwrap();
+    let start = pair.time;
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Stream(StreamEvent::Finished { id })) if id == s
+    );
+    // Sending at the limit takes two seconds, less the initial burst
+    assert!(pair.time - start > Duration::from_millis(1500));
+}
+
+#[test]
+fn stream_groups() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+
+    const SESSION: u64 = 4;
+    let a = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    let b = pair.client_streams(client_ch).open(Dir::Bi).unwrap();
+    let other = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    for s in [a, b] {
+        pair.client_streams(client_ch)
+            .set_group(s, Some(SESSION))
+            .unwrap();
+    }
+    assert_eq!(pair.client_streams(client_ch).group(b), Some(SESSION));
+    assert_eq!(pair.client_streams(client_ch).group(other), None);
+    for s in [a, b, other] {
+        pair.client_send(client_ch, s).write(b"hello").unwrap();
+    }
+    pair.drive();
+
+    let stats = pair.client_streams(client_ch).group_stats(SESSION).unwrap();
+    assert_eq!(stats.open_streams, 2);
+    assert_eq!(stats.bytes_written, 10);
+
+    // The server groups the streams of the session as they arrive
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(stream) if stream == a);
+    pair.server_streams(server_ch)
+        .set_group(a, Some(SESSION))
+        .unwrap();
+    let mut recv = pair.server_recv(server_ch, a);
+    let mut chunks = recv.read(true).unwrap();
+    assert_matches!(chunks.next(usize::MAX), Ok(Some(_)));
+    let _ = chunks.finalize();
+    let stats = pair.server_streams(server_ch).group_stats(SESSION).unwrap();
+    assert_eq!(stats.bytes_read, 5);
+
+    const ERROR: VarInt = VarInt(42);
+    let stats = pair
+        .client_conn_mut(client_ch)
+        .close_stream_group(SESSION, ERROR)
+        .unwrap();
+    assert_eq!(stats.bytes_written, 10);
+    assert_eq!(pair.client_streams(client_ch).group_stats(SESSION), None);
+    pair.drive();
+
+    let mut recv = pair.server_recv(server_ch, a);
+    let mut chunks = recv.read(true).unwrap();
+    assert_matches!(chunks.next(usize::MAX), Err(ReadError::Reset(ERROR)));
+    let _ = chunks.finalize();
+    // The stream outside the group is unaffected
+    assert_eq!(pair.client_send(client_ch, other).write(b"world"), Ok(5));
+}
+
+#[cfg(feature = "qlog")]
+#[test]
+fn qlog_trace_context() {
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let _guard = subscribe();
+    let output = Output::default();
+    let mut qlog = QlogConfig::default();
+    qlog.writer(Box::new(output.clone()));
+    let mut transport = TransportConfig::default();
+    transport.qlog_stream(qlog.into_stream());
+    let mut config = client_config();
+    config.transport_config(Arc::new(transport));
+
+    let mut pair = Pair::default();
+    let (client_ch, _) = pair.connect_with(config);
+    assert_eq!(pair.client_conn_mut(client_ch).trace_context(), None);
+    assert!(!String::from_utf8_lossy(&output.0.lock().unwrap()).contains("trace_context"));
+
+    pair.client_conn_mut(client_ch)
+        .set_trace_context(Some("4bf92f3577b34da6".into()));
+    assert_eq!(
+        pair.client_conn_mut(client_ch).trace_context(),
+        Some("4bf92f3577b34da6")
+    );
+    pair.client_conn_mut(client_ch).ping();
+    pair.drive();
+    assert!(
+        String::from_utf8_lossy(&output.0.lock().unwrap())
+            .contains(r#""trace_context":"4bf92f3577b34da6""#)
+    );
+}
+
+#[test]
+fn ring_buffer_read() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+
+    let s = pair.client_streams(client_ch).open(Dir::Uni).unwrap();
+    const MSG: &[u8] = b"hello, ring buffer";
+    pair.client_send(client_ch, s).write(&MSG[..5]).unwrap();
+    pair.drive();
+
+    assert_matches!(pair.server_streams(server_ch).accept(Dir::Uni), Some(stream) if stream == s);
+    // Data received before the ring buffer was set is moved into it
+    pair.server_recv(server_ch, s)
+        .set_ring_buffer(vec![0; 8].into())
+        .unwrap();
+    pair.client_send(client_ch, s).write(&MSG[5..]).unwrap();
+    pair.client_send(client_ch, s).finish().unwrap();
+    pair.drive();
+
+    let mut received = Vec::new();
+    loop {
+        let mut recv = pair.server_recv(server_ch, s);
+        let mut chunks = recv.read(true).unwrap();
+        let Some([first, second]) = chunks.ring_readable().unwrap() else {
+            break;
+        };
+        assert!(first.len() + second.len() <= 8);
+        received.extend_from_slice(first);
+        received.extend_from_slice(second);
+        let len = first.len() + second.len();
+        chunks.consume(len);
+        let _ = chunks.finalize();
+        pair.drive();
+    }
+    assert_eq!(received, MSG);
+    assert!(pair.server_recv(server_ch, s).read(true).is_err());
+}
+
+#[test]
+fn close_and_await_peer() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    let (client_ch, server_ch) = pair.connect();
+
+    const REASON: &[u8] = b"whee";
+    let now = pair.time;
+    pair.client_conn_mut(client_ch).close_and_await_peer(
+        now,
+        VarInt(42),
+        REASON.into(),
+        Duration::from_secs(10),
+    );
+    pair.client.drive(pair.time, pair.server.addr);
+    pair.client.outbound.clear(); // Drop the first CONNECTION_CLOSE
+    assert!(!pair.client_conn_mut(client_ch).is_close_answered());
+
+    // The retransmitted CONNECTION_CLOSE reaches the server, which responds in kind
+    while !pair.client_conn_mut(client_ch).is_close_answered() {
+        assert!(pair.step());
+    }
+    assert_matches!(pair.server_conn_mut(server_ch).poll(),
+                    Some(Event::ConnectionLost { reason: ConnectionError::ApplicationClosed(
+                        ApplicationClose { error_code: VarInt(42), ref reason }
+                    )}) if reason == REASON);
+    pair.drive();
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+}
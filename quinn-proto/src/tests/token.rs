@@ -17,10 +17,10 @@ fn stateless_retry() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -37,7 +37,9 @@ fn retry_token_expired() {
     config
         .time_source(Arc::clone(&fake_time) as _)
         .retry_token_lifetime(retry_token_lifetime);
-    pair.server.set_server_config(Some(Arc::new(config)));
+    pair.server
+        .endpoint
+        .set_server_config(Some(Arc::new(config)));
 
     let client_ch = pair.begin_connect(client_config());
     pair.drive_client();
@@ -54,10 +56,65 @@ fn retry_token_expired() {
         if err.error_code == TransportErrorCode::INVALID_TOKEN
     );
 
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
+}
+
+#[test]
+fn retry_token_replay() {
+    let _guard = subscribe();
+    let mut pair = Pair::default();
+    pair.server.handle_incoming = Box::new(validate_incoming);
+    let mut config = server_config();
+    config.retry_token_log(Some(Arc::new(SimpleTokenLog::default())));
+    pair.server
+        .endpoint
+        .set_server_config(Some(Arc::new(config)));
+
+    let client_ch = pair.begin_connect(client_config());
+    pair.drive_client();
+    pair.drive_server();
+    // Capture the client's Initial carrying the retry token
+    pair.client.drive(pair.time, pair.server.addr);
+    while pair.client.outbound.is_empty() {
+        pair.time = pair.client.next_wakeup().unwrap();
+        pair.client.drive(pair.time, pair.server.addr);
+    }
+    let replay = pair
+        .client
+        .outbound
+        .iter()
+        .map(|(_, buffer)| buffer.clone())
+        .collect::<Vec<_>>();
+    pair.drive();
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::HandshakeDataReady)
+    );
+    assert_matches!(
+        pair.client_conn_mut(client_ch).poll(),
+        Some(Event::Connected)
+    );
+    pair.server.assert_accept();
+    pair.client
+        .connections
+        .get_mut(&client_ch)
+        .unwrap()
+        .close(pair.time, VarInt(42), Bytes::new());
+    pair.drive();
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+
+    // A replay of the token is refused
+    for buffer in replay {
+        pair.server
+            .inbound
+            .push_back((pair.time, None, buffer.as_ref().into()));
+    }
+    pair.drive();
+    pair.server.assert_no_accept();
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
 }
 
 #[test]
@@ -72,10 +129,10 @@ fn use_token() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 
     pair.server.handle_incoming = Box::new(|incoming| {
         assert!(incoming.remote_address_validated());
@@ -89,10 +146,10 @@ fn use_token() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -108,10 +165,10 @@ fn retry_then_use_token() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 
     pair.server.handle_incoming = Box::new(|incoming| {
         assert!(incoming.remote_address_validated());
@@ -125,10 +182,10 @@ fn retry_then_use_token() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -143,10 +200,10 @@ fn use_token_then_retry() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 
     pair.server.handle_incoming = Box::new({
         let mut i = 0;
@@ -173,10 +230,10 @@ fn use_token_then_retry() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -213,10 +270,10 @@ fn use_same_token_twice() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 
     pair.server.handle_incoming = Box::new(|incoming| {
         assert!(incoming.remote_address_validated());
@@ -230,10 +287,10 @@ fn use_same_token_twice() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 
     pair.server.handle_incoming = Box::new(|incoming| {
         assert!(!incoming.remote_address_validated());
@@ -247,10 +304,10 @@ fn use_same_token_twice() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 }
 
 #[test]
@@ -272,10 +329,10 @@ fn use_token_expired() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 
     pair.server.handle_incoming = Box::new(|incoming| {
         assert!(incoming.remote_address_validated());
@@ -289,10 +346,10 @@ fn use_token_expired() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
 
     fake_time.advance(lifetime + Duration::from_secs(1));
 
@@ -308,10 +365,50 @@ fn use_token_expired() {
         .unwrap()
         .close(pair.time, VarInt(42), Bytes::new());
     pair.drive();
-    assert_eq!(pair.client.known_connections(), 0);
-    assert_eq!(pair.client.known_cids(), 0);
-    assert_eq!(pair.server.known_connections(), 0);
-    assert_eq!(pair.server.known_cids(), 0);
+    assert_eq!(pair.client.endpoint.known_connections(), 0);
+    assert_eq!(pair.client.endpoint.known_cids(), 0);
+    assert_eq!(pair.server.endpoint.known_connections(), 0);
+    assert_eq!(pair.server.endpoint.known_cids(), 0);
+}
+
+#[test]
+fn resumption_hint() {
+    let _guard = subscribe();
+    let mut server_config = server_config();
+    server_config.validation_token.resumption_hints(true);
+    let mut pair = Pair::new(Default::default(), server_config);
+    pair.latency = Duration::from_millis(10);
+    let client_config = client_config();
+    let (client_ch, server_ch) = pair.connect_with(client_config.clone());
+
+    let s = pair.server_streams(server_ch).open(Dir::Uni).unwrap();
+    const LEN: usize = 256 * 1024;
+    let mut written = 0;
+    while written < LEN {
+        written += pair
+            .server_send(server_ch, s)
+            .write(&[0; 64 * 1024])
+            .unwrap_or(0);
+        pair.step();
+    }
+    pair.server_send(server_ch, s).finish().unwrap();
+    pair.drive();
+    let window = pair.server_conn_mut(server_ch).congestion_state().window();
+    // Replace the tokens issued during the handshake, which carry no bandwidth estimate
+    pair.server_conn_mut(server_ch).refresh_validation_tokens();
+    pair.drive();
+    pair.client
+        .connections
+        .get_mut(&client_ch)
+        .unwrap()
+        .close(pair.time, VarInt(42), Bytes::new());
+    pair.drive();
+
+    let (_client_ch_2, server_ch_2) = pair.connect_with(client_config);
+    let congestion = pair.server_conn_mut(server_ch_2).congestion_state();
+    // Resumed from half of the previous bandwidth-delay product, rather than grown by the handshake
+    assert!(congestion.window() > 2 * congestion.initial_window());
+    assert!(congestion.window() <= window);
 }
 
 pub(super) struct FakeTimeSource(Mutex<SystemTime>);
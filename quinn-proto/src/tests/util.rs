@@ -1,6 +1,6 @@
 use std::{
     cmp,
-    collections::{HashMap, HashSet, VecDeque},
+    collections::{HashSet, VecDeque},
     env,
     io::{self, Write},
     mem,
@@ -11,7 +11,6 @@ use std::{
 };
 
 use assert_matches::assert_matches;
-use bytes::BytesMut;
 use rustls::{
     KeyLogFile,
     client::WebPkiServerVerifier,
@@ -21,7 +20,8 @@ use tracing::{info_span, trace};
 
 use super::crypto::rustls::{QuicClientConfig, QuicServerConfig, configured_provider};
 use super::*;
-use crate::{Duration, Instant};
+pub(super) use crate::sim::IncomingConnectionBehavior;
+use crate::{Duration, Instant, sim::Node};
 
 pub(super) const DEFAULT_MTU: usize = 1452;
 
@@ -135,7 +135,7 @@ impl Pair {
         let span = info_span!("client");
         let _guard = span.enter();
         self.client.drive(self.time, self.server.addr);
-        for (packet, buffer) in self.client.outbound.drain(..) {
+        for (packet, buffer) in self.client.node.outbound.drain(..) {
             let packet_size = packet_size(&packet, &buffer);
             if packet_size > self.mtu {
                 info!(packet_size, "dropping packet (max size exceeded)");
@@ -164,7 +164,7 @@ impl Pair {
         let span = info_span!("server");
         let _guard = span.enter();
         self.server.drive(self.time, self.client.addr);
-        for (packet, buffer) in self.server.outbound.drain(..) {
+        for (packet, buffer) in self.server.node.outbound.drain(..) {
             let packet_size = packet_size(&packet, &buffer);
             if packet_size > self.mtu {
                 info!(packet_size, "dropping packet (max size exceeded)");
@@ -204,12 +204,9 @@ impl Pair {
     pub(super) fn begin_connect(&mut self, config: ClientConfig) -> ConnectionHandle {
         let span = info_span!("client");
         let _guard = span.enter();
-        let (client_ch, client_conn) = self
-            .client
+        self.client
             .connect(self.time, config, self.server.addr, "localhost")
-            .unwrap();
-        self.client.connections.insert(client_ch, client_conn);
-        client_ch
+            .unwrap()
     }
 
     fn finish_connect(&mut self, client_ch: ConnectionHandle, server_ch: ConnectionHandle) {
@@ -287,28 +284,9 @@ impl Default for Pair {
 }
 
 pub(super) struct TestEndpoint {
-    pub(super) endpoint: Endpoint,
-    pub(super) addr: SocketAddr,
+    node: Node,
     socket: Option<UdpSocket>,
-    timeout: Option<Instant>,
-    pub(super) outbound: VecDeque<(Transmit, Bytes)>,
     delayed: VecDeque<(Transmit, Bytes)>,
-    pub(super) inbound: VecDeque<(Instant, Option<EcnCodepoint>, BytesMut)>,
-    accepted: Option<Result<ConnectionHandle, ConnectionError>>,
-    pub(super) connections: HashMap<ConnectionHandle, Connection>,
-    conn_events: HashMap<ConnectionHandle, VecDeque<ConnectionEvent>>,
-    pub(super) captured_packets: Vec<Vec<u8>>,
-    pub(super) capture_inbound_packets: bool,
-    pub(super) handle_incoming: Box<dyn FnMut(&Incoming) -> IncomingConnectionBehavior>,
-    pub(super) waiting_incoming: Vec<Incoming>,
-}
-
-#[derive(Debug, Copy, Clone)]
-pub(super) enum IncomingConnectionBehavior {
-    Accept,
-    Reject,
-    Retry,
-    Wait,
 }
 
 pub(super) fn validate_incoming(incoming: &Incoming) -> IncomingConnectionBehavior {
@@ -330,21 +308,12 @@ impl TestEndpoint {
         } else {
             None
         };
+        let mut node = Node::new(endpoint, addr);
+        node.retain_drained = true;
         Self {
-            endpoint,
-            addr,
+            node,
             socket,
-            timeout: None,
-            outbound: VecDeque::new(),
             delayed: VecDeque::new(),
-            inbound: VecDeque::new(),
-            accepted: None,
-            connections: HashMap::default(),
-            conn_events: HashMap::default(),
-            captured_packets: Vec::new(),
-            capture_inbound_packets: false,
-            handle_incoming: Box::new(|_| IncomingConnectionBehavior::Accept),
-            waiting_incoming: Vec::new(),
         }
     }
 
@@ -362,177 +331,50 @@ impl TestEndpoint {
                 }
             }
         }
-        let buffer_size = self.endpoint.config().get_max_udp_payload_size() as usize;
-        let mut buf = Vec::with_capacity(buffer_size);
-
-        while self.inbound.front().is_some_and(|x| x.0 <= now) {
-            let (recv_time, ecn, packet) = self.inbound.pop_front().unwrap();
-            if let Some(event) = self
-                .endpoint
-                .handle(recv_time, remote, None, ecn, packet, &mut buf)
-            {
-                match event {
-                    DatagramEvent::NewConnection(incoming) => {
-                        match (self.handle_incoming)(&incoming) {
-                            IncomingConnectionBehavior::Accept => {
-                                let _ = self.try_accept(incoming, now);
-                            }
-                            IncomingConnectionBehavior::Reject => {
-                                self.reject(incoming);
-                            }
-                            IncomingConnectionBehavior::Retry => {
-                                self.retry(incoming);
-                            }
-                            IncomingConnectionBehavior::Wait => {
-                                self.waiting_incoming.push(incoming);
-                            }
-                        }
-                    }
-                    DatagramEvent::ConnectionEvent(ch, event) => {
-                        if self.capture_inbound_packets {
-                            let packet = self.connections[&ch].decode_packet(&event);
-                            self.captured_packets.extend(packet);
-                        }
-
-                        self.conn_events.entry(ch).or_default().push_back(event);
-                    }
-                    DatagramEvent::Response(transmit) => {
-                        let size = transmit.size;
-                        self.outbound.extend(split_transmit(transmit, &buf[..size]));
-                        buf.clear();
-                    }
-                }
-            }
-        }
-    }
-
-    pub(super) fn drive_outgoing(&mut self, now: Instant) {
-        let buffer_size = self.endpoint.config().get_max_udp_payload_size() as usize;
-        let mut buf = Vec::with_capacity(buffer_size);
-
-        loop {
-            let mut endpoint_events: Vec<(ConnectionHandle, EndpointEvent)> = vec![];
-            for (ch, conn) in self.connections.iter_mut() {
-                if self.timeout.is_some_and(|x| x <= now) {
-                    self.timeout = None;
-                    conn.handle_timeout(now);
-                }
-
-                for (_, mut events) in self.conn_events.drain() {
-                    for event in events.drain(..) {
-                        conn.handle_event(event);
-                    }
-                }
-
-                while let Some(event) = conn.poll_endpoint_events() {
-                    endpoint_events.push((*ch, event));
-                }
-                while let Some(transmit) = conn.poll_transmit(now, MAX_DATAGRAMS, &mut buf) {
-                    let size = transmit.size;
-                    self.outbound.extend(split_transmit(transmit, &buf[..size]));
-                    buf.clear();
-                }
-                self.timeout = conn.poll_timeout();
-            }
-
-            if endpoint_events.is_empty() {
-                break;
-            }
-
-            for (ch, event) in endpoint_events {
-                if let Some(event) = self.handle_event(ch, event) {
-                    if let Some(conn) = self.connections.get_mut(&ch) {
-                        conn.handle_event(event);
-                    }
-                }
-            }
-        }
-    }
-
-    pub(super) fn next_wakeup(&self) -> Option<Instant> {
-        let next_inbound = self.inbound.front().map(|x| x.0);
-        min_opt(self.timeout, next_inbound)
-    }
-
-    fn is_idle(&self) -> bool {
-        self.connections.values().all(|x| x.is_idle())
+        self.node.drive_incoming(now, remote);
     }
 
     pub(super) fn delay_outbound(&mut self) {
         assert!(self.delayed.is_empty());
-        mem::swap(&mut self.delayed, &mut self.outbound);
+        mem::swap(&mut self.delayed, &mut self.node.outbound);
     }
 
     pub(super) fn finish_delay(&mut self) {
-        self.outbound.extend(self.delayed.drain(..));
-    }
-
-    pub(super) fn try_accept(
-        &mut self,
-        incoming: Incoming,
-        now: Instant,
-    ) -> Result<ConnectionHandle, ConnectionError> {
-        let mut buf = Vec::new();
-        match self.endpoint.accept(incoming, now, &mut buf, None) {
-            Ok((ch, conn)) => {
-                self.connections.insert(ch, conn);
-                self.accepted = Some(Ok(ch));
-                Ok(ch)
-            }
-            Err(error) => {
-                if let Some(transmit) = error.response {
-                    let size = transmit.size;
-                    self.outbound.extend(split_transmit(transmit, &buf[..size]));
-                }
-                self.accepted = Some(Err(error.cause.clone()));
-                Err(error.cause)
-            }
-        }
+        self.node.outbound.extend(self.delayed.drain(..));
     }
 
-    pub(super) fn retry(&mut self, incoming: Incoming) {
-        let mut buf = Vec::new();
-        let transmit = self.endpoint.retry(incoming, &mut buf).unwrap();
-        let size = transmit.size;
-        self.outbound.extend(split_transmit(transmit, &buf[..size]));
-    }
-
-    pub(super) fn reject(&mut self, incoming: Incoming) {
-        let mut buf = Vec::new();
-        let transmit = self.endpoint.refuse(incoming, &mut buf);
-        let size = transmit.size;
-        self.outbound.extend(split_transmit(transmit, &buf[..size]));
+    /// Take the outcome of the latest connection attempt, discarding earlier ones
+    fn take_accepted(&mut self) -> Option<Result<ConnectionHandle, ConnectionError>> {
+        self.node.accepted.drain(..).last()
     }
 
     pub(super) fn assert_accept(&mut self) -> ConnectionHandle {
-        self.accepted
-            .take()
+        self.take_accepted()
             .expect("server didn't try connecting")
             .expect("server experienced error connecting")
     }
 
     pub(super) fn assert_accept_error(&mut self) -> ConnectionError {
-        self.accepted
-            .take()
+        self.take_accepted()
             .expect("server didn't try connecting")
             .expect_err("server did unexpectedly connect without error")
     }
 
     pub(super) fn assert_no_accept(&self) {
-        assert!(self.accepted.is_none(), "server did unexpectedly connect")
+        assert!(self.accepted.is_empty(), "server did unexpectedly connect")
     }
 }
 
 impl ::std::ops::Deref for TestEndpoint {
-    type Target = Endpoint;
-    fn deref(&self) -> &Endpoint {
-        &self.endpoint
+    type Target = Node;
+    fn deref(&self) -> &Node {
+        &self.node
     }
 }
 
 impl ::std::ops::DerefMut for TestEndpoint {
-    fn deref_mut(&mut self) -> &mut Endpoint {
-        &mut self.endpoint
+    fn deref_mut(&mut self) -> &mut Node {
+        &mut self.node
     }
 }
 
@@ -673,35 +515,6 @@ pub(super) fn min_opt<T: Ord>(x: Option<T>, y: Option<T>) -> Option<T> {
     }
 }
 
-/// The maximum of datagrams TestEndpoint will produce via `poll_transmit`
-const MAX_DATAGRAMS: usize = 10;
-
-fn split_transmit(transmit: Transmit, buffer: &[u8]) -> Vec<(Transmit, Bytes)> {
-    let mut buffer = Bytes::copy_from_slice(buffer);
-    let Some(segment_size) = transmit.segment_size else {
-        return vec![(transmit, buffer)];
-    };
-
-    let mut transmits = Vec::new();
-    while !buffer.is_empty() {
-        let end = segment_size.min(buffer.len());
-
-        let contents = buffer.split_to(end);
-        transmits.push((
-            Transmit {
-                destination: transmit.destination,
-                size: contents.len(),
-                ecn: transmit.ecn,
-                segment_size: None,
-                src_ip: transmit.src_ip,
-            },
-            contents,
-        ));
-    }
-
-    transmits
-}
-
 fn packet_size(transmit: &Transmit, buffer: &Bytes) -> usize {
     if transmit.segment_size.is_some() {
         panic!("This transmit is meant to be split into multiple packets!");
@@ -727,7 +540,7 @@ pub(crate) static CERTIFIED_KEY: LazyLock<rcgen::CertifiedKey<rcgen::KeyPair>> =
     LazyLock::new(|| rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap());
 
 #[derive(Default)]
-struct SimpleTokenLog(Mutex<HashSet<u128>>);
+pub(super) struct SimpleTokenLog(Mutex<HashSet<u128>>);
 
 impl TokenLog for SimpleTokenLog {
     fn check_and_insert(
@@ -27,7 +27,8 @@ use crate::{
 /// > Servers are encouraged to allow tokens to be used only once, if possible; tokens MAY include
 /// > additional information about clients to further narrow applicability or reuse.
 ///
-/// `TokenLog` pertains only to tokens provided in NEW_TOKEN frames.
+/// `TokenLog` pertains to tokens provided in NEW_TOKEN frames, and to tokens provided in Retry
+/// packets if configured through [`ServerConfig::retry_token_log`].
 pub trait TokenLog: Send + Sync {
     /// Record that the token was used and, ideally, return a token reuse error if the token may
     /// have been already used previously
@@ -39,7 +40,8 @@ pub trait TokenLog: Send + Sync {
     /// - `nonce`: A server-generated random unique value for the token.
     /// - `issued`: The time the server issued the token.
     /// - `lifetime`: The expiration time of address validation tokens sent via NEW_TOKEN frames,
-    ///   as configured by [`ServerValidationTokenConfig::lifetime`][1].
+    ///   as configured by [`ServerValidationTokenConfig::lifetime`][1], or of tokens sent in
+    ///   Retry packets, as configured by [`ServerConfig::retry_token_lifetime`].
     ///
     /// [1]: crate::ValidationTokenConfig::lifetime
     ///
@@ -108,6 +110,8 @@ pub(crate) struct IncomingToken {
     pub(crate) retry_src_cid: Option<ConnectionId>,
     pub(crate) orig_dst_cid: ConnectionId,
     pub(crate) validated: bool,
+    /// Path characteristics of the previous connection, from a validation token
+    pub(crate) resumption_hint: Option<ResumptionHint>,
 }
 
 impl IncomingToken {
@@ -122,6 +126,7 @@ impl IncomingToken {
             retry_src_cid: None,
             orig_dst_cid: header.dst_cid,
             validated: false,
+            resumption_hint: None,
         };
 
         // Decode token or short-circuit
@@ -155,14 +160,19 @@ impl IncomingToken {
                 if issued + server_config.retry_token_lifetime < server_config.time_source.now() {
                     return Err(InvalidRetryTokenError);
                 }
+                if let Some(log) = &server_config.retry_token_log {
+                    log.check_and_insert(retry.nonce, issued, server_config.retry_token_lifetime)
+                        .map_err(|_| InvalidRetryTokenError)?;
+                }
 
                 Ok(Self {
                     retry_src_cid: Some(header.dst_cid),
                     orig_dst_cid,
                     validated: true,
+                    resumption_hint: None,
                 })
             }
-            TokenPayload::Validation { ip, issued } => {
+            TokenPayload::Validation { ip, issued, hint } => {
                 if ip != remote_address.ip() {
                     return Ok(unvalidated);
                 }
@@ -184,6 +194,7 @@ impl IncomingToken {
                     retry_src_cid: None,
                     orig_dst_cid: header.dst_cid,
                     validated: true,
+                    resumption_hint: hint,
                 })
             }
         }
@@ -228,10 +239,14 @@ impl Token {
                 orig_dst_cid.encode_long(&mut buf);
                 encode_unix_secs(&mut buf, issued);
             }
-            TokenPayload::Validation { ip, issued } => {
+            TokenPayload::Validation { ip, issued, hint } => {
                 buf.put_u8(TokenType::Validation as u8);
                 encode_ip(&mut buf, ip);
                 encode_unix_secs(&mut buf, issued);
+                if let Some(hint) = hint {
+                    buf.write::<u64>(hint.rtt.as_micros().try_into().unwrap_or(u64::MAX));
+                    buf.write::<u64>(hint.bandwidth);
+                }
             }
         }
 
@@ -267,6 +282,13 @@ impl Token {
             TokenType::Validation => TokenPayload::Validation {
                 ip: decode_ip(&mut reader)?,
                 issued: decode_unix_secs(&mut reader)?,
+                hint: match reader.is_empty() {
+                    true => None,
+                    false => Some(ResumptionHint {
+                        rtt: Duration::from_micros((&mut reader).get::<u64>().ok()?),
+                        bandwidth: (&mut reader).get::<u64>().ok()?,
+                    }),
+                },
             },
         };
 
@@ -296,9 +318,23 @@ pub(crate) enum TokenPayload {
         ip: IpAddr,
         /// The time at which this token was issued
         issued: SystemTime,
+        /// Path characteristics to restore if the client reconnects
+        hint: Option<ResumptionHint>,
     },
 }
 
+/// Path characteristics carried from one connection to the next in a validation token
+///
+/// These are only hints: the congestion controller validates them before relying on them, as
+/// in draft-ietf-tsvwg-careful-resume.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct ResumptionHint {
+    /// Smoothed RTT observed on the previous connection
+    pub(crate) rtt: Duration,
+    /// Delivery rate observed on the previous connection, in bytes per second
+    pub(crate) bandwidth: u64,
+}
+
 /// Variant tag for a [`TokenPayload`]
 #[derive(Copy, Clone)]
 #[repr(u8)]
@@ -468,10 +504,12 @@ mod test {
         let payload_1 = TokenPayload::Validation {
             ip: ip_1,
             issued: issued_1,
+            hint: None,
         };
         let TokenPayload::Validation {
             ip: ip_2,
             issued: issued_2,
+            hint: None,
         } = token_round_trip(payload_1)
         else {
             panic!("token decoded as wrong variant");
@@ -479,6 +517,20 @@ mod test {
 
         assert_eq!(ip_1, ip_2);
         assert_eq!(issued_1, issued_2);
+
+        let hint_1 = ResumptionHint {
+            rtt: Duration::from_micros(12_345),
+            bandwidth: 1_000_000,
+        };
+        let payload_1 = TokenPayload::Validation {
+            ip: ip_1,
+            issued: issued_1,
+            hint: Some(hint_1),
+        };
+        let TokenPayload::Validation { hint: hint_2, .. } = token_round_trip(payload_1) else {
+            panic!("token decoded as wrong variant");
+        };
+        assert_eq!(hint_2, Some(hint_1));
     }
 
     #[test]
@@ -11,12 +11,12 @@ use std::{
     net::{Ipv4Addr, Ipv6Addr, SocketAddrV4, SocketAddrV6},
 };
 
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, Bytes};
 use rand::{Rng, RngExt, seq::SliceRandom as _};
 use thiserror::Error;
 
 use crate::{
-    LOC_CID_COUNT, MAX_CID_SIZE, MAX_STREAM_COUNT, RESET_TOKEN_SIZE, ResetToken, Side,
+    Duration, MAX_CID_SIZE, MAX_STREAM_COUNT, RESET_TOKEN_SIZE, ResetToken, Side,
     TIMER_GRANULARITY, TransportError, VarInt,
     cid_generator::ConnectionIdGenerator,
     cid_queue::CidQueue,
@@ -67,7 +67,7 @@ macro_rules! apply_params {
 macro_rules! make_struct {
     {$($(#[$doc:meta])* $name:ident ($id:ident) = $default:expr,)*} => {
         /// Transport parameters used to negotiate connection-level preferences between peers
-        #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+        #[derive(Debug, Clone, Eq, PartialEq)]
         pub struct TransportParameters {
             $($(#[$doc])* pub(crate) $name : VarInt,)*
 
@@ -88,6 +88,9 @@ macro_rules! make_struct {
             /// If a value is provided, it implies that the endpoint supports QUIC Acknowledgement
             /// Frequency
             pub(crate) min_ack_delay: Option<VarInt>,
+            /// Which observed address reports the endpoint is willing to send and wishes to
+            /// receive, if it supports QUIC Address Discovery
+            pub(crate) address_discovery: Option<AddressDiscoveryRole>,
 
             // Server-only
             /// The value of the Destination Connection ID field from the first Initial packet sent
@@ -100,10 +103,15 @@ macro_rules! make_struct {
             pub(crate) stateless_reset_token: Option<ResetToken>,
             /// The server's preferred address for communication after handshake completion
             pub(crate) preferred_address: Option<PreferredAddress>,
-            /// The randomly generated reserved transport parameter to sustain future extensibility
+            /// The randomly generated reserved transport parameters to sustain future extensibility
             /// of transport parameter extensions.
-            /// When present, it is included during serialization but ignored during deserialization.
-            pub(crate) grease_transport_parameter: Option<ReservedTransportParameter>,
+            /// They are included during serialization but ignored during deserialization.
+            pub(crate) grease_transport_parameters: Vec<ReservedTransportParameter>,
+            /// Identifiers and payloads of parameters received from the peer that we don't
+            /// understand, including reserved ones
+            ///
+            /// Never serialized.
+            pub(crate) unknown: Vec<(u64, Bytes)>,
 
             /// Defines the order in which transport parameters are serialized.
             ///
@@ -126,12 +134,14 @@ macro_rules! make_struct {
                     initial_src_cid: None,
                     grease_quic_bit: false,
                     min_ack_delay: None,
+                    address_discovery: None,
 
                     original_dst_cid: None,
                     retry_src_cid: None,
                     stateless_reset_token: None,
                     preferred_address: None,
-                    grease_transport_parameter: None,
+                    grease_transport_parameters: Vec::new(),
+                    unknown: Vec::new(),
                     write_order: None,
                 }
             }
@@ -171,10 +181,17 @@ impl TransportParameters {
                 .datagram_receive_buffer_size
                 .map(|x| (x.min(u16::MAX.into()) as u16).into()),
             grease_quic_bit: endpoint_config.grease_quic_bit,
+            max_ack_delay: VarInt(config.ack_policy_config.max_ack_delay_millis()),
             min_ack_delay: Some(
                 VarInt::from_u64(u64::try_from(TIMER_GRANULARITY.as_micros()).unwrap()).unwrap(),
             ),
-            grease_transport_parameter: Some(ReservedTransportParameter::random(rng)),
+            address_discovery: AddressDiscoveryRole::new(
+                config.send_observed_address_reports,
+                config.receive_observed_address_reports,
+            ),
+            grease_transport_parameters: (0..config.grease.transport_parameters)
+                .map(|_| ReservedTransportParameter::random(rng))
+                .collect(),
             write_order: Some({
                 let mut order = std::array::from_fn(|i| i as u8);
                 order.shuffle(rng);
@@ -207,9 +224,9 @@ impl TransportParameters {
     /// Maximum number of CIDs to issue to this peer
     ///
     /// Consider both a) the active_connection_id_limit from the other end; and
-    /// b) LOC_CID_COUNT used locally
-    pub(crate) fn issue_cids_limit(&self) -> u64 {
-        self.active_connection_id_limit.0.min(LOC_CID_COUNT)
+    /// b) the `local` limit configured via `TransportConfig::max_issued_cids`
+    pub(crate) fn issue_cids_limit(&self, local: u64) -> u64 {
+        self.active_connection_id_limit.0.min(local)
     }
 }
 
@@ -278,6 +295,47 @@ impl PreferredAddress {
     }
 }
 
+/// Participation of an endpoint in QUIC Address Discovery
+///
+/// See <https://datatracker.ietf.org/doc/html/draft-ietf-quic-address-discovery>.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum AddressDiscoveryRole {
+    /// Willing to report observed addresses, but not interested in receiving them
+    SendOnly = 0,
+    /// Interested in receiving observed addresses, but not willing to report them
+    ReceiveOnly = 1,
+    /// Both willing to report and interested in receiving observed addresses
+    Both = 2,
+}
+
+impl AddressDiscoveryRole {
+    pub(crate) fn new(send: bool, receive: bool) -> Option<Self> {
+        match (send, receive) {
+            (true, true) => Some(Self::Both),
+            (true, false) => Some(Self::SendOnly),
+            (false, true) => Some(Self::ReceiveOnly),
+            (false, false) => None,
+        }
+    }
+
+    pub(crate) fn sends(self) -> bool {
+        matches!(self, Self::SendOnly | Self::Both)
+    }
+
+    pub(crate) fn receives(self) -> bool {
+        matches!(self, Self::ReceiveOnly | Self::Both)
+    }
+
+    fn decode<R: Buf>(r: &mut R) -> Result<Self, Error> {
+        Ok(match r.get::<VarInt>()?.0 {
+            0 => Self::SendOnly,
+            1 => Self::ReceiveOnly,
+            2 => Self::Both,
+            _ => return Err(Error::IllegalValue),
+        })
+    }
+}
+
 /// Errors encountered while decoding `TransportParameters`
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Error)]
 pub enum Error {
@@ -316,7 +374,7 @@ impl TransportParameters {
             let id = TransportParameterId::SUPPORTED[*idx as usize];
             match id {
                 TransportParameterId::ReservedTransportParameter => {
-                    if let Some(param) = self.grease_transport_parameter {
+                    for param in &self.grease_transport_parameters {
                         param.write(w);
                     }
                 }
@@ -381,6 +439,13 @@ impl TransportParameters {
                         w.write(x);
                     }
                 }
+                TransportParameterId::AddressDiscovery => {
+                    if let Some(x) = self.address_discovery {
+                        w.write_var(id as u64);
+                        w.write_var(1);
+                        w.write_var(x as u64);
+                    }
+                }
                 id => {
                     macro_rules! write_params {
                         {$($(#[$doc:meta])* $name:ident ($id:ident) = $default:expr,)*} => {
@@ -431,8 +496,8 @@ impl TransportParameters {
             }
             let len = len as usize;
             let Ok(id) = TransportParameterId::try_from(id) else {
-                // unknown transport parameters are ignored
-                r.advance(len);
+                // unknown transport parameters are ignored, but retained for introspection
+                params.unknown.push((id, r.copy_to_bytes(len)));
                 continue;
             };
 
@@ -477,6 +542,12 @@ impl TransportParameters {
                     _ => return Err(Error::Malformed),
                 },
                 TransportParameterId::MinAckDelayDraft07 => params.min_ack_delay = Some(r.get()?),
+                TransportParameterId::AddressDiscovery => {
+                    if len != 1 || params.address_discovery.is_some() {
+                        return Err(Error::Malformed);
+                    }
+                    params.address_discovery = Some(AddressDiscoveryRole::decode(r)?);
+                }
                 _ => {
                     macro_rules! parse {
                         {$($(#[$doc:meta])* $name:ident ($id:ident) = $default:expr,)*} => {
@@ -531,6 +602,115 @@ impl TransportParameters {
     }
 }
 
+/// The transport parameters sent by a peer, see [`Connection::peer_transport_parameters`]
+///
+/// Parameters the peer omitted take their default values.
+///
+/// [`Connection::peer_transport_parameters`]: crate::Connection::peer_transport_parameters
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PeerTransportParameters {
+    /// Time after which the peer closes an idle connection, or `None` if it never does
+    pub max_idle_timeout: Option<Duration>,
+    /// Largest UDP payload the peer is willing to receive
+    pub max_udp_payload_size: u64,
+    /// Initial limit on the amount of data that can be sent on the connection
+    pub initial_max_data: u64,
+    /// Initial limit on the amount of data on bidirectional streams initiated by the peer
+    pub initial_max_stream_data_bidi_local: u64,
+    /// Initial limit on the amount of data on bidirectional streams initiated by us
+    pub initial_max_stream_data_bidi_remote: u64,
+    /// Initial limit on the amount of data on unidirectional streams initiated by us
+    pub initial_max_stream_data_uni: u64,
+    /// Initial number of bidirectional streams we may open
+    pub initial_max_streams_bidi: u64,
+    /// Initial number of unidirectional streams we may open
+    pub initial_max_streams_uni: u64,
+    /// Exponent used by the peer to encode the delay of its acknowledgements
+    pub ack_delay_exponent: u64,
+    /// Longest time the peer delays acknowledgements by
+    pub max_ack_delay: Duration,
+    /// Number of connection IDs the peer is willing to store
+    pub active_connection_id_limit: u64,
+    /// Whether the peer refuses active connection migration
+    pub disable_active_migration: bool,
+    /// Largest application datagram frame the peer accepts, if it supports datagrams at all
+    pub max_datagram_frame_size: Option<u64>,
+    /// Whether the peer accepts packets with any value of the fixed bit
+    pub grease_quic_bit: bool,
+    /// Shortest time the peer can delay acknowledgements by, if it supports the acknowledgement
+    /// frequency extension
+    pub min_ack_delay: Option<Duration>,
+    /// Whether the peer is willing to report the address it observes our packets coming from
+    pub sends_observed_address: bool,
+    /// Whether the peer wants us to report the address we observe its packets coming from
+    pub receives_observed_address: bool,
+    /// Source connection ID of the first Initial packet sent by the peer
+    pub initial_src_cid: Option<ConnectionId>,
+    /// Destination connection ID of the client's first Initial packet, as seen by the server
+    pub original_dst_cid: Option<ConnectionId>,
+    /// Source connection ID of the server's Retry packet, if any
+    pub retry_src_cid: Option<ConnectionId>,
+    /// IPv4 address the server would rather be reached at
+    pub preferred_address_v4: Option<SocketAddrV4>,
+    /// IPv6 address the server would rather be reached at
+    pub preferred_address_v6: Option<SocketAddrV6>,
+    /// Identifiers and raw payloads of parameters that weren't understood, such as reserved
+    /// parameters used for greasing
+    pub unknown: Vec<(u64, Bytes)>,
+}
+
+impl From<&TransportParameters> for PeerTransportParameters {
+    fn from(params: &TransportParameters) -> Self {
+        Self {
+            max_idle_timeout: match params.max_idle_timeout.0 {
+                0 => None,
+                ms => Some(Duration::from_millis(ms)),
+            },
+            max_udp_payload_size: params.max_udp_payload_size.0,
+            initial_max_data: params.initial_max_data.0,
+            initial_max_stream_data_bidi_local: params.initial_max_stream_data_bidi_local.0,
+            initial_max_stream_data_bidi_remote: params.initial_max_stream_data_bidi_remote.0,
+            initial_max_stream_data_uni: params.initial_max_stream_data_uni.0,
+            initial_max_streams_bidi: params.initial_max_streams_bidi.0,
+            initial_max_streams_uni: params.initial_max_streams_uni.0,
+            ack_delay_exponent: params.ack_delay_exponent.0,
+            max_ack_delay: Duration::from_millis(params.max_ack_delay.0),
+            active_connection_id_limit: params.active_connection_id_limit.0,
+            disable_active_migration: params.disable_active_migration,
+            max_datagram_frame_size: params.max_datagram_frame_size.map(|x| x.0),
+            grease_quic_bit: params.grease_quic_bit,
+            min_ack_delay: params.min_ack_delay.map(|x| Duration::from_micros(x.0)),
+            sends_observed_address: params.address_discovery.is_some_and(|x| x.sends()),
+            receives_observed_address: params.address_discovery.is_some_and(|x| x.receives()),
+            initial_src_cid: params.initial_src_cid,
+            original_dst_cid: params.original_dst_cid,
+            retry_src_cid: params.retry_src_cid,
+            preferred_address_v4: params.preferred_address.and_then(|x| x.address_v4),
+            preferred_address_v6: params.preferred_address.and_then(|x| x.address_v6),
+            unknown: params.unknown.clone(),
+        }
+    }
+}
+
+/// A protocol extension, see [`Connection::negotiated_extensions`]
+///
+/// [`Connection::negotiated_extensions`]: crate::Connection::negotiated_extensions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Extension {
+    /// Unreliable application datagrams ([RFC 9221](https://www.rfc-editor.org/rfc/rfc9221))
+    Datagram,
+    /// Acknowledgement frequency negotiation
+    /// ([draft](https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency))
+    AckFrequency,
+    /// Greasing of the fixed bit ([RFC 9287](https://www.rfc-editor.org/rfc/rfc9287))
+    GreaseQuicBit,
+    /// Reports of observed addresses
+    /// ([draft](https://datatracker.ietf.org/doc/html/draft-ietf-quic-address-discovery))
+    AddressDiscovery,
+}
+
 /// A reserved transport parameter.
 ///
 /// It has an identifier of the form 31 * N + 27 for the integer value of N.
@@ -639,11 +819,14 @@ pub(crate) enum TransportParameterId {
 
     // https://datatracker.ietf.org/doc/html/draft-ietf-quic-ack-frequency#section-10.1
     MinAckDelayDraft07 = 0xFF04DE1B,
+
+    // https://datatracker.ietf.org/doc/html/draft-ietf-quic-address-discovery#section-3
+    AddressDiscovery = 0x9f81a176,
 }
 
 impl TransportParameterId {
     /// Array with all supported transport parameter IDs
-    const SUPPORTED: [Self; 21] = [
+    const SUPPORTED: [Self; 22] = [
         Self::MaxIdleTimeout,
         Self::MaxUdpPayloadSize,
         Self::InitialMaxData,
@@ -665,6 +848,7 @@ impl TransportParameterId {
         Self::RetrySourceConnectionId,
         Self::GreaseQuicBit,
         Self::MinAckDelayDraft07,
+        Self::AddressDiscovery,
     ];
 }
 
@@ -704,6 +888,7 @@ impl TryFrom<u64> for TransportParameterId {
             id if Self::RetrySourceConnectionId == id => Self::RetrySourceConnectionId,
             id if Self::GreaseQuicBit == id => Self::GreaseQuicBit,
             id if Self::MinAckDelayDraft07 == id => Self::MinAckDelayDraft07,
+            id if Self::AddressDiscovery == id => Self::AddressDiscovery,
             _ => return Err(()),
         };
         Ok(param)
@@ -721,7 +906,7 @@ fn decode_cid(len: usize, value: &mut Option<ConnectionId>, r: &mut impl Buf) ->
 
 #[cfg(test)]
 mod test {
-    use std::convert::Infallible;
+    use std::{convert::Infallible, mem};
 
     use rand::TryRng;
 
@@ -745,6 +930,7 @@ mod test {
             }),
             grease_quic_bit: true,
             min_ack_delay: Some(2_000u32.into()),
+            address_discovery: Some(AddressDiscoveryRole::ReceiveOnly),
             ..TransportParameters::default()
         };
         params.write(&mut buf);
@@ -754,6 +940,29 @@ mod test {
         );
     }
 
+    #[test]
+    fn unknown_retained() {
+        let mut buf = Vec::new();
+        let params = TransportParameters {
+            grease_transport_parameters: vec![
+                ReservedTransportParameter::random(&mut rand::rng()),
+                ReservedTransportParameter::random(&mut rand::rng()),
+            ],
+            ..TransportParameters::default()
+        };
+        params.write(&mut buf);
+        buf.write_var(0x1234);
+        buf.write_var(3);
+        buf.put_slice(b"abc");
+
+        let read = TransportParameters::read(Side::Client, &mut buf.as_slice()).unwrap();
+        let peer = PeerTransportParameters::from(&read);
+        assert_eq!(peer.unknown.len(), 3);
+        assert_eq!(peer.unknown[2], (0x1234, Bytes::from_static(b"abc")));
+        assert_eq!(peer.max_idle_timeout, None);
+        assert_eq!(peer.max_ack_delay, Duration::from_millis(25));
+    }
+
     #[test]
     fn reserved_transport_parameter_generate_reserved_id() {
         let mut rngs = [
@@ -828,8 +1037,12 @@ mod test {
 
         reserved_parameter.write(&mut buf);
         assert!(!buf.is_empty());
-        let read_params = TransportParameters::read(Side::Server, &mut buf.as_slice()).unwrap();
+        let mut read_params = TransportParameters::read(Side::Server, &mut buf.as_slice()).unwrap();
+        // Retained for introspection only
+        let unknown = mem::take(&mut read_params.unknown);
         assert_eq!(read_params, TransportParameters::default());
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].0, reserved_parameter.id.0);
     }
 
     #[test]
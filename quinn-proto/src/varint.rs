@@ -14,6 +14,7 @@ use arbitrary::Arbitrary;
 // It would be neat if we could express to Rust that the top two bits are available for use as enum
 // discriminants
 #[derive(Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct VarInt(pub(crate) u64);
 
 impl VarInt {
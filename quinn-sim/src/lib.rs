@@ -0,0 +1,177 @@
+//! Deterministic network simulation for quinn-proto
+//!
+//! Re-exports the simulation that quinn-proto's own tests are built on, enabled by its `sim`
+//! feature.
+//!
+//! A [`Network`] connects any number of [`Node`]s, each wrapping a quinn-proto [`Endpoint`] bound to
+//! a socket address, through simulated links. Time is virtual: [`Network::step`] jumps straight to
+//! the next timer expiry or datagram arrival, so simulating minutes of traffic takes milliseconds,
+//! and runs aren't affected by the load of the machine running them.
+//!
+//! Each direction of each pair of nodes is a separate link, modelled by a [`LinkConfig`] with
+//! limited bandwidth, propagation delay, jitter, random loss, reordering and ECN marking. All
+//! randomness of the links is drawn from a generator seeded by the caller, so a failing scenario
+//! can be replayed by reusing its seed. For the endpoints' own random choices to repeat across runs
+//! too, configure them with [`EndpointConfig::rng_seed`].
+//!
+//! ```no_run
+//! # use std::{sync::Arc, time::Duration};
+//! # use quinn_sim::{LinkConfig, Network};
+//! # fn example(server_config: proto::ServerConfig, client_config: proto::ClientConfig) {
+//! let server_addr = "[::1]:4433".parse().unwrap();
+//! let client_addr = "[::1]:44433".parse().unwrap();
+//! let mut network = Network::new(42);
+//! network.add_node(server_addr, Default::default(), Some(Arc::new(server_config)));
+//! network.add_node(client_addr, Default::default(), None);
+//! let mut link = LinkConfig::default();
+//! link.delay(Duration::from_millis(50)).loss(0.01);
+//! network.set_link(client_addr, server_addr, link.clone());
+//! network.set_link(server_addr, client_addr, link);
+//!
+//! let ch = network
+//!     .connect(client_addr, server_addr, client_config, "localhost")
+//!     .unwrap();
+//! network.run_until(network.now() + Duration::from_secs(1));
+//! let conn = network.node_mut(client_addr).connection(ch).unwrap();
+//! # }
+//! ```
+//!
+//! [`Endpoint`]: proto::Endpoint
+//! [`EndpointConfig::rng_seed`]: proto::EndpointConfig::rng_seed
+
+pub use proto::sim::{LinkConfig, LinkStats, Network, Node};
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use bytes::Bytes;
+    use proto::{ClientConfig, Dir, ReadError, ServerConfig};
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+
+    use super::*;
+
+    fn configs() -> (ServerConfig, ClientConfig) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert_der = CertificateDer::from(cert.cert.der().to_vec());
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(cert.signing_key.serialize_der()));
+        let server_config = ServerConfig::with_single_cert(vec![cert_der.clone()], key).unwrap();
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(cert_der).unwrap();
+        let client_config = ClientConfig::with_root_certificates(Arc::new(roots)).unwrap();
+        (server_config, client_config)
+    }
+
+    #[test]
+    fn drained_connections_removed() {
+        let (server_config, client_config) = configs();
+        let server_addr = "[::1]:4433".parse().unwrap();
+        let client_addr = "[::1]:44433".parse().unwrap();
+        let mut network = Network::new(1);
+        network.add_node(
+            server_addr,
+            Default::default(),
+            Some(Arc::new(server_config)),
+        );
+        network.add_node(client_addr, Default::default(), None);
+
+        let first = network
+            .connect(client_addr, server_addr, client_config.clone(), "localhost")
+            .unwrap();
+        network.run_until(network.now() + Duration::from_secs(1));
+        let now = network.now();
+        let conn = network.node_mut(client_addr).connection(first).unwrap();
+        conn.close(now, 0u32.into(), Bytes::new());
+        network.run_until(network.now() + Duration::from_secs(10));
+        assert!(network.node_mut(client_addr).connection(first).is_none());
+
+        // The handle is reused without disturbing anything
+        let second = network
+            .connect(client_addr, server_addr, client_config, "localhost")
+            .unwrap();
+        assert_eq!(second, first);
+        network.run_until(network.now() + Duration::from_secs(1));
+        let conn = network.node_mut(client_addr).connection(second).unwrap();
+        assert!(!conn.is_closed());
+    }
+
+    #[test]
+    fn lossy_transfer() {
+        let (server_config, client_config) = configs();
+
+        let server_addr = "[::1]:4433".parse().unwrap();
+        let client_addr = "[::1]:44433".parse().unwrap();
+        let mut network = Network::new(1);
+        network.add_node(
+            server_addr,
+            Default::default(),
+            Some(Arc::new(server_config)),
+        );
+        network.add_node(client_addr, Default::default(), None);
+        let mut link = LinkConfig::default();
+        link.delay(Duration::from_millis(25))
+            .jitter(Duration::from_millis(5))
+            .bandwidth(Some(1_000_000))
+            .loss(0.05)
+            .reorder(0.05);
+        network.set_default_link(link);
+
+        let client_ch = network
+            .connect(client_addr, server_addr, client_config, "localhost")
+            .unwrap();
+        network.run_until(network.now() + Duration::from_secs(1));
+        let server_ch = network.node_mut(server_addr).accept().unwrap();
+
+        const LEN: usize = 100_000;
+        let conn = network.node_mut(client_addr).connection(client_ch).unwrap();
+        let stream = conn.streams().open(Dir::Uni).unwrap();
+        let mut sent = 0;
+        let mut received = 0;
+        let mut finished = false;
+        while !finished {
+            let conn = network.node_mut(client_addr).connection(client_ch).unwrap();
+            if sent < LEN {
+                sent += conn
+                    .send_stream(stream)
+                    .write(&[0; 1200][..1200.min(LEN - sent)])
+                    .unwrap_or(0);
+                if sent == LEN {
+                    conn.send_stream(stream).finish().unwrap();
+                }
+            }
+            let conn = network.node_mut(server_addr).connection(server_ch).unwrap();
+            if let Some(id) = conn.streams().accept(Dir::Uni) {
+                assert_eq!(id, stream);
+            }
+            let mut recv = conn.recv_stream(stream);
+            if let Ok(mut chunks) = recv.read(true) {
+                loop {
+                    match chunks.next(usize::MAX) {
+                        Ok(Some(chunk)) => received += chunk.bytes.len(),
+                        Ok(None) => {
+                            finished = true;
+                            break;
+                        }
+                        Err(ReadError::Blocked) => break,
+                        Err(e) => panic!("{e}"),
+                    }
+                }
+                let _ = chunks.finalize();
+            }
+            assert!(network.step(), "stalled");
+        }
+
+        assert_eq!(received, LEN);
+        // Can't be faster than the bandwidth permits
+        assert!(network.elapsed() >= Duration::from_millis(100));
+        let rtt = network
+            .node_mut(client_addr)
+            .connection(client_ch)
+            .unwrap()
+            .rtt();
+        assert!(rtt >= Duration::from_millis(50));
+        let stats = network.link_stats(client_addr, server_addr);
+        assert!(stats.lost > 0);
+        assert!(stats.reordered > 0);
+    }
+}
@@ -57,13 +57,8 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             1
         };
         let msg = vec![0xAB; min(MAX_DATAGRAM_SIZE, SEGMENT_SIZE * gso_segments)];
-        let transmit = Transmit {
-            destination: dst_addr,
-            ecn: None,
-            contents: &msg,
-            segment_size: gso_enabled.then_some(SEGMENT_SIZE),
-            src_ip: None,
-        };
+        let transmit =
+            Transmit::new(dst_addr, &msg).with_segment_size(gso_enabled.then_some(SEGMENT_SIZE));
         let gro_segments = if gro_enabled {
             recv_state.gro_segments()
         } else {
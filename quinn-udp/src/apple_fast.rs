@@ -48,6 +48,8 @@ fn send_via_sendmsg_x(
             &Transmit {
                 destination: transmit.destination,
                 ecn: transmit.ecn,
+                dscp: transmit.dscp,
+                flow_label: transmit.flow_label,
                 contents: chunk,
                 segment_size: Some(chunk.len()),
                 src_ip: transmit.src_ip,
@@ -93,15 +95,15 @@ fn prepare_msg_x(
     hdr.msg_control = ctrl.0.as_mut_ptr() as _;
     hdr.msg_controllen = cmsg::LEN as _;
     let mut encoder = unsafe { cmsg::Encoder::new(hdr) };
-    let ecn = transmit.ecn.map_or(0, |x| x as libc::c_int);
+    let tos = transmit.tos() as libc::c_int;
     let is_ipv4 = transmit.destination.is_ipv4()
         || matches!(transmit.destination.ip(), IpAddr::V6(addr) if addr.to_ipv4_mapped().is_some());
     if is_ipv4 {
         if !sendmsg_einval {
-            encoder.push(libc::IPPROTO_IP, libc::IP_TOS, ecn as IpTosTy);
+            encoder.push(libc::IPPROTO_IP, libc::IP_TOS, tos as IpTosTy);
         }
     } else {
-        encoder.push(libc::IPPROTO_IPV6, libc::IPV6_TCLASS, ecn);
+        encoder.push(libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos);
     }
 
     if let Some(ip) = &transmit.src_ip {
@@ -4,7 +4,10 @@ use std::{
     time::Instant,
 };
 
-use super::{IO_ERROR_LOG_INTERVAL, RecvMeta, Transmit, UdpSockRef, log_sendmsg_error};
+use super::{
+    IO_ERROR_LOG_INTERVAL, OffloadStats, RecvMeta, SendStats, Transmit, UdpSockRef,
+    log_sendmsg_error,
+};
 
 /// Fallback UDP socket interface that stubs out all special functionality
 ///
@@ -87,6 +90,18 @@ impl UdpSocketState {
         1
     }
 
+    /// Always zero, as offloads are not supported on this platform
+    #[inline]
+    pub fn offload_stats(&self) -> OffloadStats {
+        OffloadStats::default()
+    }
+
+    /// Always zero, as send errors are not tracked on this platform
+    #[inline]
+    pub fn send_stats(&self) -> SendStats {
+        SendStats::default()
+    }
+
     /// Resize the send buffer of `socket` to `bytes`
     #[inline]
     pub fn set_send_buffer_size(&self, socket: UdpSockRef<'_>, bytes: usize) -> io::Result<()> {
@@ -33,6 +33,8 @@ use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::os::unix::io::AsFd;
 #[cfg(windows)]
 use std::os::windows::io::AsSocket;
+#[cfg(any(unix, windows))]
+use std::sync::atomic::{AtomicUsize, Ordering};
 #[cfg(not(wasm_browser))]
 use std::{sync::Mutex, time::Instant};
 
@@ -144,12 +146,26 @@ impl Default for RecvMeta {
 }
 
 /// An outgoing packet
+///
+/// Constructed with [`Transmit::new()`], so that fields can be added without breaking users.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Transmit<'a> {
     /// The socket this datagram should be sent to
     pub destination: SocketAddr,
     /// Explicit congestion notification bits to set on the packet
     pub ecn: Option<EcnCodepoint>,
+    /// Differentiated Services Code Point to set on the packet
+    ///
+    /// Only the lower 6 bits are used. `None` leaves the DSCP bits cleared, which corresponds to
+    /// the default (best effort) class. Ignored on Windows and on platforms which don't support
+    /// setting `IP_TOS`/`IPV6_TCLASS` via control messages.
+    pub dscp: Option<u8>,
+    /// IPv6 flow label to set on the packet
+    ///
+    /// Only the lower 20 bits are used. `None` leaves the choice of flow label to the operating
+    /// system. Ignored for IPv4 destinations, and currently only honored on Linux.
+    pub flow_label: Option<u32>,
     /// Contents of the datagram
     pub contents: &'a [u8],
     /// The segment size if this transmission contains multiple datagrams.
@@ -159,7 +175,52 @@ pub struct Transmit<'a> {
     pub src_ip: Option<IpAddr>,
 }
 
-impl Transmit<'_> {
+impl<'a> Transmit<'a> {
+    /// Construct a transmit of a single datagram with `contents` to `destination`
+    ///
+    /// Optional fields are unset, and may be set with the `with_*` methods.
+    pub fn new(destination: SocketAddr, contents: &'a [u8]) -> Self {
+        Self {
+            destination,
+            ecn: None,
+            dscp: None,
+            flow_label: None,
+            contents,
+            segment_size: None,
+            src_ip: None,
+        }
+    }
+
+    /// Set [`ecn`](Self::ecn)
+    pub fn with_ecn(mut self, ecn: Option<EcnCodepoint>) -> Self {
+        self.ecn = ecn;
+        self
+    }
+
+    /// Set [`dscp`](Self::dscp)
+    pub fn with_dscp(mut self, dscp: Option<u8>) -> Self {
+        self.dscp = dscp;
+        self
+    }
+
+    /// Set [`flow_label`](Self::flow_label)
+    pub fn with_flow_label(mut self, flow_label: Option<u32>) -> Self {
+        self.flow_label = flow_label;
+        self
+    }
+
+    /// Set [`segment_size`](Self::segment_size)
+    pub fn with_segment_size(mut self, segment_size: Option<usize>) -> Self {
+        self.segment_size = segment_size;
+        self
+    }
+
+    /// Set [`src_ip`](Self::src_ip)
+    pub fn with_src_ip(mut self, src_ip: Option<IpAddr>) -> Self {
+        self.src_ip = src_ip;
+        self
+    }
+
     /// Computes the effective segment-size of the packet.
     ///
     /// Some (older) network drivers don't like being told to do GSO even if
@@ -177,6 +238,28 @@ impl Transmit<'_> {
             size => Some(size),
         }
     }
+
+    /// Computes the IPv4 Type of Service or IPv6 Traffic Class byte from the DSCP and ECN bits
+    #[cfg(unix)]
+    fn tos(&self) -> u8 {
+        self.dscp.map_or(0, |x| x << 2) | self.ecn.map_or(0, |x| x as u8)
+    }
+
+    /// Computes the address to pass to `sendmsg`, carrying the flow label where supported
+    #[cfg(unix)]
+    #[cfg_attr(apple_fast, allow(dead_code))] // Used by send, which is unused when apple_fast
+    fn destination_addr(&self) -> socket2::SockAddr {
+        match (self.destination, self.flow_label) {
+            #[cfg(target_os = "linux")]
+            (SocketAddr::V6(mut addr), Some(label)) => {
+                // The kernel reads `sin6_flowinfo` in network byte order, which std doesn't
+                // convert to
+                addr.set_flowinfo((label & FLOW_LABEL_MASK).to_be());
+                addr.into()
+            }
+            (addr, _) => addr.into(),
+        }
+    }
 }
 
 /// Asynchronous transport-layer errors reported by the operating system
@@ -224,6 +307,164 @@ pub enum TransportErrorPayload {
     Other,
 }
 
+/// Counters describing the use of segmentation offloads by a [`UdpSocketState`]
+///
+/// Returned by `UdpSocketState::offload_stats`. Only transmits and receives which actually
+/// made use of an offload, i.e. carried more than one datagram, are counted. Counters may wrap
+/// on platforms with 32-bit pointers.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct OffloadStats {
+    /// Number of transmits sent using Generic Segmentation Offload (GSO)
+    pub gso_batches: u64,
+    /// Total number of datagrams sent in GSO batches
+    pub gso_segments: u64,
+    /// Number of GSO transmits rejected by the operating system or network driver
+    pub gso_failures: u64,
+    /// Number of received buffers holding multiple datagrams coalesced by Generic Receive Offload
+    /// (GRO)
+    pub gro_batches: u64,
+    /// Total number of datagrams received in GRO batches
+    pub gro_segments: u64,
+}
+
+/// Atomic backing storage for [`OffloadStats`]
+#[cfg(any(unix, windows))]
+#[derive(Debug, Default)]
+struct OffloadCounters {
+    gso_batches: AtomicUsize,
+    gso_segments: AtomicUsize,
+    gso_failures: AtomicUsize,
+    gro_batches: AtomicUsize,
+    gro_segments: AtomicUsize,
+}
+
+#[cfg(any(unix, windows))]
+#[allow(dead_code)] // Not every platform supports every offload
+impl OffloadCounters {
+    fn on_sent(&self, transmit: &Transmit<'_>) {
+        if let Some(segment_size) = transmit.effective_segment_size() {
+            self.gso_batches.fetch_add(1, Ordering::Relaxed);
+            self.gso_segments.fetch_add(
+                transmit.contents.len().div_ceil(segment_size),
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    fn on_gso_failed(&self) {
+        self.gso_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_recv(&self, meta: &[RecvMeta]) {
+        for meta in meta
+            .iter()
+            .filter(|meta| meta.len > meta.stride && meta.stride > 0)
+        {
+            self.gro_batches.fetch_add(1, Ordering::Relaxed);
+            self.gro_segments
+                .fetch_add(meta.len.div_ceil(meta.stride), Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> OffloadStats {
+        OffloadStats {
+            gso_batches: self.gso_batches.load(Ordering::Relaxed) as u64,
+            gso_segments: self.gso_segments.load(Ordering::Relaxed) as u64,
+            gso_failures: self.gso_failures.load(Ordering::Relaxed) as u64,
+            gro_batches: self.gro_batches.load(Ordering::Relaxed) as u64,
+            gro_segments: self.gro_segments.load(Ordering::Relaxed) as u64,
+        }
+    }
+}
+
+/// Counters describing transmits the operating system did not accept immediately
+///
+/// Returned by `UdpSocketState::send_stats`. Counters may wrap on platforms with 32-bit
+/// pointers.
+#[derive(Debug, Default, Copy, Clone)]
+#[non_exhaustive]
+pub struct SendStats {
+    /// Number of transmits rejected because the socket was not writable (`EAGAIN`)
+    ///
+    /// These are returned to the caller, who is expected to retry once the socket becomes
+    /// writable.
+    pub would_block: u64,
+    /// Number of transmits dropped because the operating system ran out of buffer space
+    /// (`ENOBUFS`)
+    ///
+    /// This usually means the interface queue is full.
+    pub no_buffers: u64,
+    /// Number of transmits rejected because their datagrams exceeded the maximum size the
+    /// operating system allows on the path (`EMSGSIZE`)
+    ///
+    /// See [`is_too_big`].
+    pub too_big: u64,
+}
+
+/// Atomic backing storage for [`SendStats`]
+#[cfg(any(unix, windows))]
+#[derive(Debug, Default)]
+struct SendCounters {
+    would_block: AtomicUsize,
+    no_buffers: AtomicUsize,
+    too_big: AtomicUsize,
+}
+
+#[cfg(any(unix, windows))]
+impl SendCounters {
+    fn on_error(&self, e: &std::io::Error) {
+        if e.kind() == std::io::ErrorKind::WouldBlock {
+            self.would_block.fetch_add(1, Ordering::Relaxed);
+        } else if e.raw_os_error() == Some(ENOBUFS) {
+            self.no_buffers.fetch_add(1, Ordering::Relaxed);
+        } else if is_too_big(e) {
+            self.too_big.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> SendStats {
+        SendStats {
+            would_block: self.would_block.load(Ordering::Relaxed) as u64,
+            no_buffers: self.no_buffers.load(Ordering::Relaxed) as u64,
+            too_big: self.too_big.load(Ordering::Relaxed) as u64,
+        }
+    }
+}
+
+#[cfg(unix)]
+const ENOBUFS: i32 = libc::ENOBUFS;
+#[cfg(windows)]
+const ENOBUFS: i32 = windows_sys::Win32::Networking::WinSock::WSAENOBUFS;
+
+/// Whether `err` reports that a transmit's datagrams exceed the maximum size the operating system
+/// currently allows on the path
+///
+/// This is `EMSGSIZE` on Unix and `WSAEMSGSIZE` on Windows. Since the socket forbids
+/// fragmentation wherever `UdpSocketState::may_fragment` is `false`, such datagrams are dropped
+/// locally, e.g. because they exceed the MTU of the outgoing interface or a path MTU learned from
+/// ICMP. `UdpSocketState::send` passes these errors on, allowing the caller to lower its path MTU
+/// estimate right away instead of waiting for the datagrams to be declared lost.
+pub fn is_too_big(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        err.raw_os_error() == Some(libc::EMSGSIZE)
+    }
+    #[cfg(windows)]
+    {
+        err.raw_os_error() == Some(windows_sys::Win32::Networking::WinSock::WSAEMSGSIZE)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = err;
+        false
+    }
+}
+
+/// Bits of the IPv6 flow information field which hold the flow label
+#[cfg(target_os = "linux")]
+const FLOW_LABEL_MASK: u32 = 0x000f_ffff;
+
 /// Log at most 1 IO error per minute
 #[cfg(not(wasm_browser))]
 const IO_ERROR_LOG_INTERVAL: Duration = Duration::from_secs(60);
@@ -243,11 +484,13 @@ fn log_sendmsg_error(
     if now.saturating_duration_since(*last_send_error) > IO_ERROR_LOG_INTERVAL {
         *last_send_error = now;
         log::warn!(
-            "sendmsg error: {:?}, Transmit: {{ destination: {:?}, src_ip: {:?}, ecn: {:?}, len: {:?}, segment_size: {:?} }}",
+            "sendmsg error: {:?}, Transmit: {{ destination: {:?}, src_ip: {:?}, ecn: {:?}, dscp: {:?}, flow_label: {:?}, len: {:?}, segment_size: {:?} }}",
             err,
             transmit.destination,
             transmit.src_ip,
             transmit.ecn,
+            transmit.dscp,
+            transmit.flow_label,
             transmit.contents.len(),
             transmit.segment_size
         );
@@ -347,6 +590,8 @@ mod tests {
         Transmit {
             destination: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 1)),
             ecn: None,
+            dscp: None,
+            flow_label: None,
             contents,
             segment_size,
             src_ip: None,
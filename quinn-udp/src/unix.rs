@@ -15,8 +15,8 @@ use std::{
 use socket2::SockRef;
 
 use super::{
-    EcnCodepoint, IO_ERROR_LOG_INTERVAL, RecvMeta, Transmit, TransportError, UdpSockRef, cmsg,
-    log_sendmsg_error,
+    EcnCodepoint, IO_ERROR_LOG_INTERVAL, OffloadCounters, OffloadStats, RecvMeta, SendCounters,
+    SendStats, Transmit, TransportError, UdpSockRef, cmsg, log_sendmsg_error,
 };
 
 #[cfg(apple_fast)]
@@ -42,6 +42,12 @@ pub struct UdpSocketState {
     /// which is not supported on Linux <3.13 and results in not sending the UDP packet at all.
     sendmsg_einval: AtomicBool,
 
+    /// Usage of segmentation offloads
+    offload: OffloadCounters,
+
+    /// Transmits not accepted by the operating system
+    send_errors: SendCounters,
+
     /// Whether to use Apple's fast `sendmsg_x`/`recvmsg_x` APIs.
     ///
     /// These private APIs provide better performance but may not be available on all
@@ -162,7 +168,7 @@ impl UdpSocketState {
                 }
             }
         }
-        #[cfg(any(target_os = "freebsd", apple))]
+        #[cfg(any(target_os = "freebsd", apple, solarish))]
         {
             if is_ipv4 {
                 // Set `may_fragment` to `true` if this option is not supported on the platform.
@@ -174,6 +180,12 @@ impl UdpSocketState {
                 )?;
             }
         }
+        // No socket option forbids IPv4 fragmentation here, so MTU probes could be fragmented
+        // instead of dropped.
+        #[cfg(any(target_os = "openbsd", target_os = "netbsd", target_os = "dragonfly"))]
+        {
+            may_fragment |= is_ipv4;
+        }
         #[cfg(any(bsd, apple, solarish))]
         // IP_RECVDSTADDR == IP_SENDSRCADDR on FreeBSD
         // macOS uses only IP_RECVDSTADDR, no IP_SENDSRCADDR on macOS (the same on Solaris)
@@ -189,6 +201,17 @@ impl UdpSocketState {
         if !is_ipv4 {
             set_socket_option(&*io, libc::IPPROTO_IPV6, libc::IPV6_RECVPKTINFO, OPTION_ON)?;
             set_socket_option(&*io, libc::IPPROTO_IPV6, libc::IPV6_RECVTCLASS, OPTION_ON)?;
+            // Allow `Transmit::flow_label` to be passed in `sin6_flowinfo`. When unset, the kernel
+            // keeps choosing flow labels itself.
+            #[cfg(target_os = "linux")]
+            {
+                let _ = set_socket_option(
+                    &*io,
+                    libc::IPPROTO_IPV6,
+                    libc::IPV6_FLOWINFO_SEND,
+                    OPTION_ON,
+                );
+            }
             // Linux's IP_PMTUDISC_PROBE allows us to operate under interface MTU rather than the
             // kernel's path MTU guess, but actually disabling fragmentation requires this too. See
             // __ip6_append_data in ip6_output.c.
@@ -217,6 +240,8 @@ impl UdpSocketState {
             gro_segments,
             may_fragment,
             sendmsg_einval: AtomicBool::new(false),
+            offload: OffloadCounters::default(),
+            send_errors: SendCounters::default(),
             #[cfg(apple_fast)]
             apple_fast_path: AtomicBool::new(false),
             #[cfg(apple)]
@@ -226,8 +251,9 @@ impl UdpSocketState {
 
     /// Sends a [`Transmit`] on the given socket
     ///
-    /// This function will only ever return errors of kind [`io::ErrorKind::WouldBlock`].
-    /// All other errors will be logged and converted to `Ok`.
+    /// This function will only ever return errors of kind [`io::ErrorKind::WouldBlock`], or
+    /// errors for which [`is_too_big`](crate::is_too_big) holds. All other errors will be logged
+    /// and converted to `Ok`.
     ///
     /// UDP transmission errors are considered non-fatal because higher-level protocols must
     /// employ retransmits and timeouts anyway in order to deal with UDP's unreliable nature.
@@ -236,12 +262,11 @@ impl UdpSocketState {
     /// If you would like to handle these errors yourself, use [`UdpSocketState::try_send`]
     /// instead.
     pub fn send(&self, socket: UdpSockRef<'_>, transmit: &Transmit<'_>) -> io::Result<()> {
-        match send(self, socket.0, transmit) {
+        match self.try_send(socket, transmit) {
             Ok(()) => Ok(()),
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(e),
-            // - EMSGSIZE is expected for MTU probes. Future work might be able to avoid
-            //   these by automatically clamping the MTUD upper bound to the interface MTU.
-            Err(e) if e.raw_os_error() == Some(libc::EMSGSIZE) => Ok(()),
+            // Expected for MTU probes, and an immediate signal for the caller's MTU discovery
+            Err(e) if crate::is_too_big(&e) => Err(e),
             Err(e) => {
                 log_sendmsg_error(&self.last_send_error, e, transmit);
 
@@ -252,7 +277,7 @@ impl UdpSocketState {
 
     /// Sends a [`Transmit`] on the given socket without any additional error handling
     pub fn try_send(&self, socket: UdpSockRef<'_>, transmit: &Transmit<'_>) -> io::Result<()> {
-        send(self, socket.0, transmit)
+        send(self, socket.0, transmit).inspect_err(|e| self.send_errors.on_error(e))
     }
 
     #[cfg(not(any(
@@ -269,7 +294,9 @@ impl UdpSocketState {
         bufs: &mut [IoSliceMut<'_>],
         meta: &mut [RecvMeta],
     ) -> io::Result<usize> {
-        recv_via_recvmmsg(socket.0, bufs, meta)
+        let n = recv_via_recvmmsg(socket.0, bufs, meta)?;
+        self.offload.on_recv(&meta[..n]);
+        Ok(n)
     }
 
     #[cfg(apple_fast)]
@@ -345,6 +372,16 @@ impl UdpSocketState {
         self.gro_segments
     }
 
+    /// Counters describing the use of GSO and GRO on this socket
+    pub fn offload_stats(&self) -> OffloadStats {
+        self.offload.snapshot()
+    }
+
+    /// Counters describing transmits on this socket that the operating system did not accept
+    pub fn send_stats(&self) -> SendStats {
+        self.send_errors.snapshot()
+    }
+
     /// Resize the send buffer of `socket` to `bytes`
     ///
     /// On Apple platforms, `bytes` is silently raised to a safe minimum if smaller.
@@ -454,7 +491,11 @@ impl UdpSocketState {
     #[cfg(apple)]
     const MIN_SAFE_SNDBUF: usize = 65535 + cmsg::LEN;
 
-    /// Safety net returning `EMSGSIZE` for payload sizes in the bug's region.
+    /// Safety net rejecting payload sizes in the bug's region
+    ///
+    /// The error is of kind [`io::ErrorKind::InvalidInput`] rather than `EMSGSIZE`, since the
+    /// datagram would fit the path: it must not be mistaken for a path MTU signal by
+    /// [`is_too_big`](crate::is_too_big). [`UdpSocketState::send`] logs and drops it.
     #[cfg(apple)]
     pub(crate) fn check_send_buffer_limit(
         &self,
@@ -464,20 +505,17 @@ impl UdpSocketState {
         let needed = resid.saturating_add(hdr.control_len());
         let sndbuf = self.send_buffer_size.load(Ordering::Relaxed);
         if needed > sndbuf {
-            crate::log::debug!("EMSGSIZE for {needed}-byte send: exceeds SO_SNDBUF ({sndbuf})");
-            return Err(io::Error::from_raw_os_error(libc::EMSGSIZE));
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{needed}-byte send exceeds SO_SNDBUF ({sndbuf})"),
+            ));
         }
         Ok(())
     }
 }
 
 #[cfg(not(any(apple, target_os = "openbsd", target_os = "netbsd")))]
-fn send(
-    #[allow(unused_variables)] // only used on Linux
-    state: &UdpSocketState,
-    io: SockRef<'_>,
-    transmit: &Transmit<'_>,
-) -> io::Result<()> {
+fn send(state: &UdpSocketState, io: SockRef<'_>, transmit: &Transmit<'_>) -> io::Result<()> {
     #[allow(unused_mut)] // only mutable on FreeBSD
     let mut encode_src_ip = true;
     #[cfg(target_os = "freebsd")]
@@ -493,7 +531,7 @@ fn send(
     let mut msg_hdr: libc::msghdr = unsafe { mem::zeroed() };
     let mut iovec: libc::iovec = unsafe { mem::zeroed() };
     let mut cmsgs = cmsg::Aligned([0u8; cmsg::LEN]);
-    let dst_addr = socket2::SockAddr::from(transmit.destination);
+    let dst_addr = transmit.destination_addr();
     prepare_msg(
         transmit,
         &dst_addr,
@@ -508,6 +546,7 @@ fn send(
         let n = unsafe { libc::sendmsg(io.as_raw_fd(), &msg_hdr, 0) };
 
         if n >= 0 {
+            state.offload.on_sent(transmit);
             return Ok(());
         }
 
@@ -521,14 +560,22 @@ fn send(
                 // offers no easy way for us to detect this short of an EIO or sometimes EINVAL
                 // when we try to actually send datagrams using it.
                 #[cfg(any(target_os = "linux", target_os = "android"))]
-                if let Some(libc::EIO) | Some(libc::EINVAL) = e.raw_os_error() {
-                    // Prevent new transmits from being scheduled using GSO. Existing GSO transmits
-                    // may already be in the pipeline, so we need to tolerate additional failures.
-                    if state.max_gso_segments() > 1 {
+                if let (Some(libc::EIO) | Some(libc::EINVAL), Some(segment_size)) =
+                    (e.raw_os_error(), transmit.effective_segment_size())
+                {
+                    state.offload.on_gso_failed();
+                    // Some drivers only reject batches above a certain size, so halve the number
+                    // of segments scheduled for new transmits rather than giving up on GSO at
+                    // once. Transmits prepared before an earlier reduction may still be in the
+                    // pipeline, so failures of batches larger than the current limit are
+                    // tolerated without reducing it further.
+                    let segments = transmit.contents.len().div_ceil(segment_size);
+                    let limit = (segments / 2).max(1);
+                    let prev = state.max_gso_segments.fetch_min(limit, Ordering::Relaxed);
+                    if limit < prev {
                         crate::log::info!(
-                            "`libc::sendmsg` failed with {e}; halting segmentation offload"
+                            "`libc::sendmsg` failed with {e}; limiting segmentation offload to {limit} segments"
                         );
-                        state.max_gso_segments.store(1, Ordering::Relaxed);
                     }
                 }
 
@@ -571,7 +618,7 @@ pub(crate) fn send_single(
     let mut hdr: libc::msghdr = unsafe { mem::zeroed() };
     let mut iov: libc::iovec = unsafe { mem::zeroed() };
     let mut ctrl = cmsg::Aligned([0u8; cmsg::LEN]);
-    let addr = socket2::SockAddr::from(transmit.destination);
+    let addr = transmit.destination_addr();
     prepare_msg(
         transmit,
         &addr,
@@ -697,7 +744,7 @@ fn prepare_msg(
     hdr.msg_control = ctrl.0.as_mut_ptr() as _;
     hdr.msg_controllen = cmsg::LEN as _;
     let mut encoder = unsafe { cmsg::Encoder::new(hdr) };
-    let ecn = transmit.ecn.map_or(0, |x| x as libc::c_int);
+    let tos = transmit.tos() as libc::c_int;
     // True for IPv4 or IPv4-Mapped IPv6
     let is_ipv4 = transmit.destination.is_ipv4()
         || matches!(transmit.destination.ip(), IpAddr::V6(addr) if addr.to_ipv4_mapped().is_some());
@@ -705,12 +752,12 @@ fn prepare_msg(
         if !sendmsg_einval {
             #[cfg(not(target_os = "netbsd"))]
             {
-                encoder.push(libc::IPPROTO_IP, libc::IP_TOS, ecn as IpTosTy);
+                encoder.push(libc::IPPROTO_IP, libc::IP_TOS, tos as IpTosTy);
             }
         }
     } else {
         #[cfg(not(target_os = "redox"))]
-        encoder.push(libc::IPPROTO_IPV6, libc::IPV6_TCLASS, ecn);
+        encoder.push(libc::IPPROTO_IPV6, libc::IPV6_TCLASS, tos);
     }
 
     // On apple_fast, prepare_msg is only compiled for send_single (fallback path), while the main
@@ -15,7 +15,8 @@ use libc::{c_int, c_uint};
 use windows_sys::Win32::Networking::WinSock;
 
 use crate::{
-    EcnCodepoint, IO_ERROR_LOG_INTERVAL, RecvMeta, Transmit, UdpSockRef,
+    EcnCodepoint, IO_ERROR_LOG_INTERVAL, OffloadCounters, OffloadStats, RecvMeta, SendCounters,
+    SendStats, Transmit, UdpSockRef,
     cmsg::{self, CMsgHdr},
     log::debug,
     log_sendmsg_error,
@@ -37,6 +38,12 @@ pub struct UdpSocketState {
 
     /// Whether the underlying Winsock provider supports IPv6 ECN socket options/control messages.
     ecn_v6_supported: bool,
+
+    /// Usage of segmentation offloads
+    offload: OffloadCounters,
+
+    /// Transmits not accepted by the operating system
+    send_errors: SendCounters,
 }
 
 impl UdpSocketState {
@@ -160,6 +167,8 @@ impl UdpSocketState {
             max_gso_segments: AtomicUsize::new(max_gso_segments(&*socket.0)),
             ecn_v4_supported,
             ecn_v6_supported,
+            offload: OffloadCounters::default(),
+            send_errors: SendCounters::default(),
         })
     }
 
@@ -187,8 +196,9 @@ impl UdpSocketState {
 
     /// Sends a [`Transmit`] on the given socket.
     ///
-    /// This function will only ever return errors of kind [`io::ErrorKind::WouldBlock`].
-    /// All other errors will be logged and converted to `Ok`.
+    /// This function will only ever return errors of kind [`io::ErrorKind::WouldBlock`], or
+    /// errors for which [`is_too_big`](crate::is_too_big) holds. All other errors will be logged
+    /// and converted to `Ok`.
     ///
     /// UDP transmission errors are considered non-fatal because higher-level protocols must
     /// employ retransmits and timeouts anyway in order to deal with UDP's unreliable nature.
@@ -197,14 +207,11 @@ impl UdpSocketState {
     /// If you would like to handle these errors yourself, use [`UdpSocketState::try_send`]
     /// instead.
     pub fn send(&self, socket: UdpSockRef<'_>, transmit: &Transmit<'_>) -> io::Result<()> {
-        match send(
-            socket,
-            transmit,
-            self.ecn_v4_supported,
-            self.ecn_v6_supported,
-        ) {
+        match self.try_send(socket, transmit) {
             Ok(()) => Ok(()),
             Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(e),
+            // Expected for MTU probes, and an immediate signal for the caller's MTU discovery
+            Err(e) if crate::is_too_big(&e) => Err(e),
             Err(e) => {
                 log_sendmsg_error(&self.last_send_error, e, transmit);
 
@@ -221,6 +228,8 @@ impl UdpSocketState {
             self.ecn_v4_supported,
             self.ecn_v6_supported,
         )
+        .inspect(|()| self.offload.on_sent(transmit))
+        .inspect_err(|e| self.send_errors.on_error(e))
     }
 
     pub fn recv(
@@ -328,6 +337,7 @@ impl UdpSocketState {
             interface_index,
             timestamp: None,
         };
+        self.offload.on_recv(&meta[..1]);
         Ok(1)
     }
 
@@ -351,6 +361,16 @@ impl UdpSocketState {
         64
     }
 
+    /// Counters describing the use of USO and URO on this socket
+    pub fn offload_stats(&self) -> OffloadStats {
+        self.offload.snapshot()
+    }
+
+    /// Counters describing transmits on this socket that the operating system did not accept
+    pub fn send_stats(&self) -> SendStats {
+        self.send_errors.snapshot()
+    }
+
     /// Resize the send buffer of `socket` to `bytes`
     #[inline]
     pub fn set_send_buffer_size(&self, socket: UdpSockRef<'_>, bytes: usize) -> io::Result<()> {
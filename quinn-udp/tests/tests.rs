@@ -29,13 +29,7 @@ fn basic() {
     test_send_recv(
         &send.into(),
         &recv.into(),
-        Transmit {
-            destination: dst_addr,
-            ecn: None,
-            contents: b"hello",
-            segment_size: None,
-            src_ip: None,
-        },
+        Transmit::new(dst_addr, b"hello"),
     );
 }
 
@@ -52,13 +46,7 @@ fn basic_src_ip() {
     test_send_recv(
         &send.into(),
         &recv.into(),
-        Transmit {
-            destination: dst_addr,
-            ecn: None,
-            contents: b"hello",
-            segment_size: None,
-            src_ip: Some(src_ip),
-        },
+        Transmit::new(dst_addr, b"hello").with_src_ip(Some(src_ip)),
     );
 }
 
@@ -70,13 +58,8 @@ fn ecn_v6() {
         test_send_recv(
             &send,
             &recv,
-            Transmit {
-                destination: recv.local_addr().unwrap().as_socket().unwrap(),
-                ecn: Some(codepoint),
-                contents: b"hello",
-                segment_size: None,
-                src_ip: None,
-            },
+            Transmit::new(recv.local_addr().unwrap().as_socket().unwrap(), b"hello")
+                .with_ecn(Some(codepoint)),
         );
     }
 }
@@ -90,17 +73,87 @@ fn ecn_v4() {
         test_send_recv(
             &send,
             &recv,
-            Transmit {
-                destination: recv.local_addr().unwrap().as_socket().unwrap(),
-                ecn: Some(codepoint),
-                contents: b"hello",
-                segment_size: None,
-                src_ip: None,
-            },
+            Transmit::new(recv.local_addr().unwrap().as_socket().unwrap(), b"hello")
+                .with_ecn(Some(codepoint)),
         );
     }
 }
 
+#[test]
+#[cfg(not(any(target_os = "openbsd", target_os = "netbsd", solarish)))]
+fn dscp_preserves_ecn() {
+    for ip in [
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+    ] {
+        let send = Socket::from(UdpSocket::bind((ip, 0)).unwrap());
+        let recv = Socket::from(UdpSocket::bind((ip, 0)).unwrap());
+        // Expedited Forwarding
+        test_send_recv(
+            &send,
+            &recv,
+            Transmit::new(recv.local_addr().unwrap().as_socket().unwrap(), b"hello")
+                .with_ecn(Some(EcnCodepoint::Ect0))
+                .with_dscp(Some(46)),
+        );
+    }
+}
+
+/// Flow labels are only honored on Linux, which can also report them on receipt
+#[test]
+#[cfg(target_os = "linux")]
+fn flow_label() {
+    use std::{mem, os::fd::AsRawFd};
+
+    let send = Socket::from(UdpSocket::bind((Ipv6Addr::LOCALHOST, 0)).unwrap());
+    let recv = Socket::from(UdpSocket::bind((Ipv6Addr::LOCALHOST, 0)).unwrap());
+    let send_state = UdpSocketState::new((&send).into()).unwrap();
+    let on: libc::c_int = 1;
+    let rc = unsafe {
+        libc::setsockopt(
+            recv.as_raw_fd(),
+            libc::IPPROTO_IPV6,
+            libc::IPV6_FLOWINFO,
+            &on as *const _ as *const libc::c_void,
+            size_of_val(&on) as libc::socklen_t,
+        )
+    };
+    assert_eq!(rc, 0, "{}", std::io::Error::last_os_error());
+
+    send_state
+        .try_send(
+            (&send).into(),
+            &Transmit::new(recv.local_addr().unwrap().as_socket().unwrap(), b"hello")
+                .with_ecn(Some(EcnCodepoint::Ect0))
+                .with_flow_label(Some(0x12345)),
+        )
+        .unwrap();
+
+    // Read the flow label from the IPV6_FLOWINFO control message
+    let mut buf = [0u8; 16];
+    let mut control = [0u8; 64];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    let mut hdr = unsafe { mem::zeroed::<libc::msghdr>() };
+    hdr.msg_iov = &mut iov;
+    hdr.msg_iovlen = 1;
+    hdr.msg_control = control.as_mut_ptr().cast();
+    hdr.msg_controllen = control.len() as _;
+    assert_eq!(unsafe { libc::recvmsg(recv.as_raw_fd(), &mut hdr, 0) }, 5);
+    let mut flow_info = None;
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&hdr) };
+    while let Some(c) = unsafe { cmsg.as_ref() } {
+        if c.cmsg_level == libc::IPPROTO_IPV6 && c.cmsg_type == libc::IPV6_FLOWINFO {
+            flow_info = Some(unsafe { (libc::CMSG_DATA(cmsg) as *const u32).read_unaligned() });
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&hdr, cmsg) };
+    }
+    let flow_info = u32::from_be(flow_info.expect("no IPV6_FLOWINFO control message"));
+    assert_eq!(flow_info & 0xf_ffff, 0x12345);
+}
+
 #[test]
 #[cfg(not(any(target_os = "openbsd", target_os = "netbsd", solarish)))]
 fn ecn_v6_dualstack() {
@@ -135,13 +188,7 @@ fn ecn_v6_dualstack() {
             test_send_recv(
                 &send,
                 &recv,
-                Transmit {
-                    destination: dst,
-                    ecn: Some(codepoint),
-                    contents: b"hello",
-                    segment_size: None,
-                    src_ip: None,
-                },
+                Transmit::new(dst, b"hello").with_ecn(Some(codepoint)),
             );
         }
     }
@@ -175,13 +222,7 @@ fn ecn_v4_mapped_v6() {
         test_send_recv(
             &send,
             &recv,
-            Transmit {
-                destination: recv_v4_mapped_v6,
-                ecn: Some(codepoint),
-                contents: b"hello",
-                segment_size: None,
-                src_ip: None,
-            },
+            Transmit::new(recv_v4_mapped_v6, b"hello").with_ecn(Some(codepoint)),
         );
     }
 }
@@ -207,16 +248,66 @@ fn gso() {
     test_send_recv(
         &send.into(),
         &recv.into(),
-        Transmit {
-            destination: dst_addr,
-            ecn: None,
-            contents: &msg,
-            segment_size: Some(SEGMENT_SIZE),
-            src_ip: None,
-        },
+        Transmit::new(dst_addr, &msg).with_segment_size(Some(SEGMENT_SIZE)),
     );
 }
 
+#[test]
+#[cfg_attr(
+    not(any(target_os = "linux", target_os = "windows", target_os = "android")),
+    ignore
+)]
+fn offload_stats() {
+    let send = Socket::from(UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap());
+    let recv = Socket::from(UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap());
+    let send_state = UdpSocketState::new((&send).into()).unwrap();
+    let recv_state = UdpSocketState::new((&recv).into()).unwrap();
+    recv.set_nonblocking(false).unwrap();
+
+    const SEGMENT_SIZE: usize = 128;
+    let max_segments = send_state.max_gso_segments();
+    let msg = vec![0xAB; SEGMENT_SIZE * max_segments];
+    send_state
+        .try_send(
+            (&send).into(),
+            &Transmit::new(recv.local_addr().unwrap().as_socket().unwrap(), &msg)
+                .with_segment_size(Some(SEGMENT_SIZE)),
+        )
+        .unwrap();
+    let stats = send_state.offload_stats();
+    assert_eq!(stats.gso_failures, 0);
+    if max_segments > 1 {
+        assert_eq!(stats.gso_batches, 1);
+        assert_eq!(stats.gso_segments, max_segments as u64);
+    } else {
+        assert_eq!(stats.gso_batches, 0);
+    }
+
+    let mut buf = [0; u16::MAX as usize];
+    let mut meta = RecvMeta::default();
+    let mut datagrams = 0;
+    let mut batches = 0;
+    let mut batched_datagrams = 0;
+    while datagrams < max_segments {
+        recv_state
+            .recv(
+                (&recv).into(),
+                &mut [IoSliceMut::new(&mut buf)],
+                slice::from_mut(&mut meta),
+            )
+            .unwrap();
+        let segments = meta.len / meta.stride;
+        datagrams += segments;
+        if segments > 1 {
+            batches += 1;
+            batched_datagrams += segments as u64;
+        }
+    }
+    let stats = recv_state.offload_stats();
+    assert_eq!(stats.gro_batches, batches);
+    assert_eq!(stats.gro_segments, batched_datagrams);
+}
+
 #[test]
 fn socket_buffers() {
     const BUFFER_SIZE: usize = 123456;
@@ -280,13 +371,7 @@ fn socket_buffers() {
     test_send_recv(
         &send,
         &recv,
-        Transmit {
-            destination: recv.local_addr().unwrap().as_socket().unwrap(),
-            ecn: None,
-            contents: b"hello",
-            segment_size: None,
-            src_ip: None,
-        },
+        Transmit::new(recv.local_addr().unwrap().as_socket().unwrap(), b"hello"),
     );
 }
 
@@ -453,13 +538,7 @@ fn apple_fast_datapath() {
     send_state
         .try_send(
             (&send).into(),
-            &Transmit {
-                destination: dst_addr,
-                ecn: None,
-                contents: &msg,
-                segment_size: Some(SEGMENT_SIZE),
-                src_ip: None,
-            },
+            &Transmit::new(dst_addr, &msg).with_segment_size(Some(SEGMENT_SIZE)),
         )
         .unwrap();
 
@@ -538,16 +617,14 @@ fn assert_min_sndbuf_enforced(state: &UdpSocketState, send: &Socket, dst: Socket
     let sndbuf = state.send_buffer_size(send.into()).unwrap();
     assert!(sndbuf >= 65535 + clen);
 
-    let transmit = Transmit {
-        destination: dst,
-        ecn: Some(EcnCodepoint::Ect0),
-        contents: &vec![0u8; 60_000],
-        segment_size: None,
-        src_ip: None,
-    };
+    let transmit = Transmit::new(dst, &vec![0u8; 60_000]).with_ecn(Some(EcnCodepoint::Ect0));
     match state.try_send(send.into(), &transmit) {
         Ok(()) => {}
         Err(e) if e.raw_os_error() == Some(libc::EMSGSIZE) => {}
+        Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+            // Rejected locally, which must not look like a path MTU signal
+            assert!(!quinn_udp::is_too_big(&e));
+        }
         Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
             panic!("regressed: permanently stuck EWOULDBLOCK sending a large datagram")
         }
@@ -620,16 +697,7 @@ fn recv_transport_error() {
     let dst = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, unused_port));
 
     state
-        .try_send(
-            (&sock).into(),
-            &Transmit {
-                destination: dst,
-                ecn: None,
-                contents: b"hello",
-                segment_size: None,
-                src_ip: None,
-            },
-        )
+        .try_send((&sock).into(), &Transmit::new(dst, b"hello"))
         .unwrap();
 
     let mut received = None;
@@ -681,16 +749,7 @@ fn recv_transport_error_ipv6() {
     let dst = SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::LOCALHOST, unused_port, 0, 0));
 
     state
-        .try_send(
-            (&sock).into(),
-            &Transmit {
-                destination: dst,
-                ecn: None,
-                contents: b"hello",
-                segment_size: None,
-                src_ip: None,
-            },
-        )
+        .try_send((&sock).into(), &Transmit::new(dst, b"hello"))
         .unwrap();
 
     let mut received = None;
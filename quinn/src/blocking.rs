@@ -0,0 +1,426 @@
+//! Blocking wrappers around the asynchronous API
+//!
+//! For applications that don't otherwise use an async runtime, e.g. command line tools. Each
+//! [`Endpoint`] drives its I/O on a background thread running a single-threaded Tokio runtime,
+//! and every method here blocks the calling thread until the corresponding operation completes.
+//! The underlying asynchronous objects remain reachable, e.g. through [`Connection::as_async`],
+//! for functionality not mirrored here.
+//!
+//! Blocking methods panic if called from within an asynchronous execution context.
+
+use std::{
+    fmt,
+    future::Future,
+    io,
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+    thread,
+};
+
+use bytes::Bytes;
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use crate::{
+    ClientConfig, ClosedStream, ConnectionError, EndpointConfig, ReadError, ReadExactError,
+    ReadToEndError, SendDatagramError, ServerConfig, StoppedError, VarInt, WriteError,
+    runtime::TokioRuntime,
+};
+
+/// A QUIC endpoint whose I/O is driven on a background thread
+///
+/// See [`crate::Endpoint`]. May be cloned to obtain another handle to the same endpoint. The
+/// background thread exits once all handles to the endpoint and its connections and streams have
+/// been dropped, abandoning any I/O still in progress; call [`wait_idle`](Self::wait_idle) first
+/// to close connections gracefully.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    inner: crate::Endpoint,
+    driver: Arc<Driver>,
+}
+
+impl Endpoint {
+    /// Construct an endpoint for outgoing connections only, bound to `addr`
+    ///
+    /// See [`crate::Endpoint::client`].
+    #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+    pub fn client(addr: SocketAddr) -> io::Result<Self> {
+        let driver = Driver::new()?;
+        let inner = {
+            let _guard = driver.runtime.enter();
+            crate::Endpoint::client(addr)?
+        };
+        Ok(Self {
+            inner,
+            driver: Arc::new(driver),
+        })
+    }
+
+    /// Construct an endpoint for incoming and outgoing connections, bound to `addr`
+    ///
+    /// See [`crate::Endpoint::server`].
+    #[cfg(any(feature = "aws-lc-rs", feature = "ring"))]
+    pub fn server(config: ServerConfig, addr: SocketAddr) -> io::Result<Self> {
+        let driver = Driver::new()?;
+        let inner = {
+            let _guard = driver.runtime.enter();
+            crate::Endpoint::server(config, addr)?
+        };
+        Ok(Self {
+            inner,
+            driver: Arc::new(driver),
+        })
+    }
+
+    /// Construct an endpoint with arbitrary configuration and a pre-constructed socket
+    ///
+    /// See [`crate::Endpoint::new`].
+    pub fn new(
+        config: EndpointConfig,
+        server_config: Option<ServerConfig>,
+        socket: UdpSocket,
+    ) -> io::Result<Self> {
+        let driver = Driver::new()?;
+        let inner = {
+            let _guard = driver.runtime.enter();
+            crate::Endpoint::new(config, server_config, socket, Arc::new(TokioRuntime))?
+        };
+        Ok(Self {
+            inner,
+            driver: Arc::new(driver),
+        })
+    }
+
+    /// Set the client configuration used by [`connect`](Self::connect)
+    pub fn set_default_client_config(&self, config: ClientConfig) {
+        self.inner.set_default_client_config(config);
+    }
+
+    /// Connect to a remote endpoint, blocking until the handshake completes
+    ///
+    /// See [`crate::Endpoint::connect`].
+    pub fn connect(&self, addr: SocketAddr, server_name: &str) -> Result<Connection, ConnectError> {
+        let connecting = {
+            // The connection's driver is spawned onto the runtime right away
+            let _guard = self.driver.runtime.enter();
+            self.inner.connect(addr, server_name)?
+        };
+        Ok(Connection::new(
+            self.driver.block_on(connecting)?,
+            &self.driver,
+        ))
+    }
+
+    /// Connect to a remote endpoint using a custom configuration
+    ///
+    /// See [`connect`](Self::connect).
+    pub fn connect_with(
+        &self,
+        config: ClientConfig,
+        addr: SocketAddr,
+        server_name: &str,
+    ) -> Result<Connection, ConnectError> {
+        let connecting = {
+            let _guard = self.driver.runtime.enter();
+            self.inner.connect_with(config, addr, server_name)?
+        };
+        Ok(Connection::new(
+            self.driver.block_on(connecting)?,
+            &self.driver,
+        ))
+    }
+
+    /// Wait for the next incoming connection and complete its handshake
+    ///
+    /// Returns `None` if the endpoint is [`close`](Self::close)d. Use [`as_async`](Self::as_async)
+    /// to filter connection attempts before accepting them.
+    pub fn accept(&self) -> Option<Result<Connection, ConnectionError>> {
+        let result = self.driver.block_on(async {
+            let incoming = self.inner.accept().await?;
+            Some(incoming.await)
+        })?;
+        Some(result.map(|conn| Connection::new(conn, &self.driver)))
+    }
+
+    /// Get the local `SocketAddr` the underlying socket is bound to
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    /// Close all of this endpoint's connections immediately and cease accepting new connections
+    ///
+    /// See [`crate::Endpoint::close`].
+    pub fn close(&self, error_code: VarInt, reason: &[u8]) {
+        self.inner.close(error_code, reason);
+    }
+
+    /// Wait for all connections on the endpoint to be cleanly shut down
+    ///
+    /// See [`crate::Endpoint::wait_idle`].
+    pub fn wait_idle(&self) {
+        self.driver.block_on(self.inner.wait_idle());
+    }
+
+    /// The underlying asynchronous endpoint
+    pub fn as_async(&self) -> &crate::Endpoint {
+        &self.inner
+    }
+}
+
+/// A QUIC connection, see [`crate::Connection`]
+///
+/// May be cloned to obtain another handle to the same connection.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    inner: crate::Connection,
+    driver: Arc<Driver>,
+}
+
+impl Connection {
+    fn new(inner: crate::Connection, driver: &Arc<Driver>) -> Self {
+        Self {
+            inner,
+            driver: driver.clone(),
+        }
+    }
+
+    /// Open a unidirectional stream, blocking until the peer's stream limit allows it
+    pub fn open_uni(&self) -> Result<SendStream, ConnectionError> {
+        let stream = self.driver.block_on(self.inner.open_uni())?;
+        Ok(SendStream::new(stream, &self.driver))
+    }
+
+    /// Open a bidirectional stream, blocking until the peer's stream limit allows it
+    ///
+    /// As with [`crate::Connection::open_bi`], the peer only learns about the stream once data
+    /// is written to it.
+    pub fn open_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        let (send, recv) = self.driver.block_on(self.inner.open_bi())?;
+        Ok((
+            SendStream::new(send, &self.driver),
+            RecvStream::new(recv, &self.driver),
+        ))
+    }
+
+    /// Wait for the next unidirectional stream opened by the peer
+    pub fn accept_uni(&self) -> Result<RecvStream, ConnectionError> {
+        let stream = self.driver.block_on(self.inner.accept_uni())?;
+        Ok(RecvStream::new(stream, &self.driver))
+    }
+
+    /// Wait for the next bidirectional stream opened by the peer
+    pub fn accept_bi(&self) -> Result<(SendStream, RecvStream), ConnectionError> {
+        let (send, recv) = self.driver.block_on(self.inner.accept_bi())?;
+        Ok((
+            SendStream::new(send, &self.driver),
+            RecvStream::new(recv, &self.driver),
+        ))
+    }
+
+    /// Transmit `data` as an unreliable, unordered application datagram
+    ///
+    /// See [`crate::Connection::send_datagram`].
+    pub fn send_datagram(&self, data: Bytes) -> Result<(), SendDatagramError> {
+        self.inner.send_datagram(data)
+    }
+
+    /// Wait for the next application datagram from the peer
+    pub fn read_datagram(&self) -> Result<Bytes, ConnectionError> {
+        self.driver.block_on(self.inner.read_datagram())
+    }
+
+    /// Close the connection immediately
+    ///
+    /// See [`crate::Connection::close`].
+    pub fn close(&self, error_code: VarInt, reason: &[u8]) {
+        self.inner.close(error_code, reason);
+    }
+
+    /// Wait for the connection to be closed for any reason
+    pub fn closed(&self) -> ConnectionError {
+        self.driver.block_on(self.inner.closed())
+    }
+
+    /// The peer's UDP address
+    pub fn remote_address(&self) -> SocketAddr {
+        self.inner.remote_address()
+    }
+
+    /// The underlying asynchronous connection
+    pub fn as_async(&self) -> &crate::Connection {
+        &self.inner
+    }
+}
+
+/// A stream that can only be used to send data, see [`crate::SendStream`]
+///
+/// Implements [`io::Write`]. Dropping the stream finishes it, like [`finish`](Self::finish).
+#[derive(Debug)]
+pub struct SendStream {
+    inner: crate::SendStream,
+    driver: Arc<Driver>,
+}
+
+impl SendStream {
+    fn new(inner: crate::SendStream, driver: &Arc<Driver>) -> Self {
+        Self {
+            inner,
+            driver: driver.clone(),
+        }
+    }
+
+    /// Write bytes to the stream, blocking until flow control allows some of them to be sent
+    ///
+    /// Returns the number of bytes written.
+    pub fn write(&mut self, buf: &[u8]) -> Result<usize, WriteError> {
+        self.driver.block_on(self.inner.write(buf))
+    }
+
+    /// Write all of `buf` to the stream
+    pub fn write_all(&mut self, buf: &[u8]) -> Result<(), WriteError> {
+        self.driver.block_on(self.inner.write_all(buf))
+    }
+
+    /// Notify the peer that no more data will ever be written to this stream
+    ///
+    /// See [`crate::SendStream::finish`].
+    pub fn finish(&mut self) -> Result<(), ClosedStream> {
+        self.inner.finish()
+    }
+
+    /// Close the send stream immediately, abandoning any data not yet delivered
+    pub fn reset(&mut self, error_code: VarInt) -> Result<(), ClosedStream> {
+        self.inner.reset(error_code)
+    }
+
+    /// Wait for the stream to be stopped by the peer or for all data to be acknowledged
+    ///
+    /// See [`crate::SendStream::stopped`].
+    pub fn stopped(&mut self) -> Result<Option<VarInt>, StoppedError> {
+        self.driver.block_on(self.inner.stopped())
+    }
+
+    /// The underlying asynchronous stream
+    pub fn as_async(&mut self) -> &mut crate::SendStream {
+        &mut self.inner
+    }
+}
+
+impl io::Write for SendStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(Self::write(self, buf)?)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A stream that can only be used to receive data, see [`crate::RecvStream`]
+///
+/// Implements [`io::Read`].
+#[derive(Debug)]
+pub struct RecvStream {
+    inner: crate::RecvStream,
+    driver: Arc<Driver>,
+}
+
+impl RecvStream {
+    fn new(inner: crate::RecvStream, driver: &Arc<Driver>) -> Self {
+        Self {
+            inner,
+            driver: driver.clone(),
+        }
+    }
+
+    /// Read data contiguously from the stream, blocking until some is available
+    ///
+    /// Yields the number of bytes read into `buf` on success, or `None` if the stream was
+    /// finished.
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<Option<usize>, ReadError> {
+        self.driver.block_on(self.inner.read(buf))
+    }
+
+    /// Read an exact number of bytes contiguously from the stream
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), ReadExactError> {
+        self.driver.block_on(self.inner.read_exact(buf))
+    }
+
+    /// Read the remainder of the stream, failing if it exceeds `size_limit` bytes
+    pub fn read_to_end(&mut self, size_limit: usize) -> Result<Vec<u8>, ReadToEndError> {
+        self.driver.block_on(self.inner.read_to_end(size_limit))
+    }
+
+    /// Stop accepting data, discarding anything not yet read
+    ///
+    /// See [`crate::RecvStream::stop`].
+    pub fn stop(&mut self, error_code: VarInt) -> Result<(), ClosedStream> {
+        self.inner.stop(error_code)
+    }
+
+    /// The underlying asynchronous stream
+    pub fn as_async(&mut self) -> &mut crate::RecvStream {
+        &mut self.inner
+    }
+}
+
+impl io::Read for RecvStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(Self::read(self, buf)?.unwrap_or(0))
+    }
+}
+
+/// Errors in the parameters being used to create a new connection, or in its handshake
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ConnectError {
+    /// The connection could not be initiated
+    #[error(transparent)]
+    Connect(#[from] crate::ConnectError),
+    /// The handshake failed
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+}
+
+/// Runs a Tokio runtime on a background thread until dropped
+struct Driver {
+    runtime: tokio::runtime::Handle,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl Driver {
+    fn new() -> io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let handle = runtime.handle().clone();
+        let (shutdown, stop) = oneshot::channel();
+        thread::Builder::new()
+            .name("quinn-blocking".into())
+            .spawn(move || {
+                // Drives I/O and timers for all blocking callers
+                let _ = runtime.block_on(stop);
+            })?;
+        Ok(Self {
+            runtime: handle,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+impl Drop for Driver {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+impl fmt::Debug for Driver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Driver")
+    }
+}
@@ -1,8 +1,9 @@
 use std::{
     any::Any,
+    collections::VecDeque,
     fmt,
-    future::Future,
-    io,
+    future::{Future, poll_fn},
+    io, mem,
     net::{IpAddr, SocketAddr},
     pin::Pin,
     sync::{
@@ -25,6 +26,7 @@ use tracing::{Instrument, Span, debug_span};
 
 use crate::{
     ConnectionEvent, Duration, Instant, VarInt,
+    endpoint::{PriorityClass, SendMonitor},
     mutex::Mutex,
     recv_stream::RecvStream,
     runtime::{AsyncTimer, Runtime, UdpSender},
@@ -32,8 +34,12 @@ use crate::{
     udp_transmit,
 };
 use proto::{
-    ConnectionError, ConnectionHandle, ConnectionStats, Dir, EndpointEvent, Side, StreamEvent,
-    StreamId, TransportError, TransportErrorCode, congestion::Controller,
+    BandwidthEstimate, ClosedStream, ConnectionDebugState, ConnectionError, ConnectionHandle,
+    ConnectionIdState, ConnectionStats, DatagramFlowStats, Dir, EndpointEvent, Extension,
+    MtuDiscoveryConfig, MtuProbe, PeerTransportParameters, RttHistogram, Side, StreamEvent,
+    StreamGroupStats, StreamId, StreamSchedulerStats, TransmitMarker, TransportConfig,
+    TransportError, TransportErrorCode, TransportEvent, TuneError, congestion::Controller,
+    crypto::PeerCredentials,
 };
 
 /// In-progress connection attempt future
@@ -51,6 +57,7 @@ impl Connecting {
         endpoint_events: mpsc::UnboundedSender<(ConnectionHandle, EndpointEvent)>,
         conn_events: mpsc::UnboundedReceiver<ConnectionEvent>,
         sender: Pin<Box<dyn UdpSender>>,
+        send_monitor: Arc<SendMonitor>,
         runtime: Arc<dyn Runtime>,
     ) -> Self {
         let (on_handshake_data_send, on_handshake_data_recv) = oneshot::channel();
@@ -63,6 +70,7 @@ impl Connecting {
                 conn_events,
                 on_handshake_data_send,
                 sender,
+                send_monitor,
                 runtime.clone(),
             )),
             shared: Shared::default(),
@@ -174,6 +182,37 @@ impl Connecting {
             })
     }
 
+    /// The server name requested by the client
+    ///
+    /// Resolves as soon as the client's first handshake message has been processed, without
+    /// waiting for the rest of the handshake. This allows a server to e.g. pick the task that
+    /// should drive the connection based on the requested name.
+    ///
+    /// Returns `None` for outgoing connections, if the client didn't indicate a server name, or if
+    /// the [`Session`](proto::crypto::Session) isn't the default `rustls` session.
+    #[cfg(any(feature = "rustls-aws-lc-rs", feature = "rustls-ring"))]
+    pub async fn server_name(&mut self) -> Result<Option<String>, ConnectionError> {
+        Ok(self
+            .rustls_handshake_data()
+            .await?
+            .and_then(|x| x.server_name))
+    }
+
+    /// The application protocol negotiated through ALPN
+    ///
+    /// Like [`server_name()`](Self::server_name), available before the handshake completes.
+    #[cfg(any(feature = "rustls-aws-lc-rs", feature = "rustls-ring"))]
+    pub async fn alpn_protocol(&mut self) -> Result<Option<Vec<u8>>, ConnectionError> {
+        Ok(self.rustls_handshake_data().await?.and_then(|x| x.protocol))
+    }
+
+    #[cfg(any(feature = "rustls-aws-lc-rs", feature = "rustls-ring"))]
+    async fn rustls_handshake_data(
+        &mut self,
+    ) -> Result<Option<crate::crypto::rustls::HandshakeData>, ConnectionError> {
+        Ok(self.handshake_data().await?.downcast().ok().map(|x| *x))
+    }
+
     /// The local IP address which was used when the peer established
     /// the connection
     ///
@@ -199,6 +238,20 @@ impl Connecting {
         let conn_ref: &ConnectionRef = self.conn.as_ref().expect("used after yielding Ready");
         conn_ref.state.lock("remote_address").inner.remote_address()
     }
+
+    /// Attach an application-supplied trace context before the handshake completes
+    ///
+    /// See [`Connection::set_trace_context()`].
+    ///
+    /// Will panic if called after `poll` has returned `Ready`.
+    pub fn set_trace_context(&self, context: Option<String>) {
+        let conn_ref: &ConnectionRef = self.conn.as_ref().expect("used after yielding Ready");
+        conn_ref
+            .state
+            .lock("set_trace_context")
+            .inner
+            .set_trace_context(context);
+    }
 }
 
 impl Future for Connecting {
@@ -240,11 +293,16 @@ impl Future for ConnectionDriver {
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         let conn = &mut *self.0.state.lock("poll");
 
-        let span = debug_span!("drive", id = conn.handle.0);
+        let span = debug_span!(
+            "drive",
+            id = conn.handle.0,
+            trace_context = conn.inner.trace_context()
+        );
         let _guard = span.enter();
 
         if let Err(e) = conn.process_conn_events(&self.0.shared, cx) {
             conn.terminate(e, &self.0.shared);
+            conn.release_turn();
             return Poll::Ready(Ok(()));
         }
         let mut keep_going = conn.drive_transmit(cx)?;
@@ -252,7 +310,15 @@ impl Future for ConnectionDriver {
         // might need to reset a timer. Hence, we must loop until neither happens.
         keep_going |= conn.drive_timer(cx);
         conn.forward_endpoint_events();
+        conn.forward_transport_events();
         conn.forward_app_events(&self.0.shared);
+        if mem::take(&mut conn.send_budget_wanted) {
+            self.0.shared.send_budget_available.notify_waiters();
+        }
+
+        if conn.inner.is_close_answered() || conn.inner.is_drained() {
+            self.0.shared.close_answered.notify_waiters();
+        }
 
         if !conn.inner.is_drained() {
             if keep_going {
@@ -351,6 +417,30 @@ impl Connection {
         }
     }
 
+    /// Receive an application datagram of `flow`
+    ///
+    /// Flows are assigned by the configured [`TransportConfig::datagram_classifier`]. Fails with
+    /// [`ReadFlowDatagramError::NoClassifier`] if none is configured.
+    ///
+    /// [`TransportConfig::datagram_classifier`]: crate::TransportConfig::datagram_classifier
+    pub fn read_flow_datagram(&self, flow: u64) -> ReadFlowDatagram<'_> {
+        ReadFlowDatagram {
+            conn: &self.0,
+            flow,
+            notify: self.0.shared.datagram_received.notified(),
+        }
+    }
+
+    /// Statistics of the incoming datagrams of `flow`, if any have been received
+    pub fn datagram_flow_stats(&self, flow: u64) -> Option<DatagramFlowStats> {
+        self.0
+            .state
+            .lock("datagram_flow_stats")
+            .inner
+            .datagrams()
+            .flow_stats(flow)
+    }
+
     /// Wait for the connection to be closed for any reason
     ///
     /// Despite the return type's name, closed connections are often not an error condition at the
@@ -425,6 +515,48 @@ impl Connection {
         conn.close(error_code, Bytes::copy_from_slice(reason), &self.0.shared);
     }
 
+    /// Close the connection, retransmitting CONNECTION_CLOSE until the peer closes in turn
+    ///
+    /// Like [`close()`](Self::close), but makes sure the peer learns `error_code` and `reason` even
+    /// if the first CONNECTION_CLOSE is lost, rather than timing the connection out. Resolves once
+    /// the peer answered with a CONNECTION_CLOSE of its own, returning `true`, or once `timeout`
+    /// elapsed, returning `false`. The peer doesn't acknowledge CONNECTION_CLOSE, so `false` may
+    /// also mean that it was delivered but not answered. Has no effect other than waiting if the connection was already
+    /// closed. See [`proto::Connection::close_and_await_peer()`].
+    pub async fn close_and_await_peer(
+        &self,
+        error_code: VarInt,
+        reason: &[u8],
+        timeout: Duration,
+    ) -> bool {
+        {
+            let conn = &mut *self.0.state.lock("close_and_await_peer");
+            let now = conn.runtime.now();
+            conn.inner.close_and_await_peer(
+                now,
+                error_code,
+                Bytes::copy_from_slice(reason),
+                timeout,
+            );
+            conn.terminate(ConnectionError::LocallyClosed, &self.0.shared);
+            conn.wake();
+        }
+        loop {
+            let notified = {
+                let conn = self.0.state.lock("close_and_await_peer");
+                if conn.inner.is_close_answered() {
+                    return true;
+                }
+                if conn.inner.is_drained() {
+                    return false;
+                }
+                // Construct the future while the lock is held to ensure we can't miss a wakeup
+                self.0.shared.close_answered.notified()
+            };
+            notified.await;
+        }
+    }
+
     /// Wait for the handshake to be confirmed.
     ///
     /// As a server, who must be authenticated by clients,
@@ -485,6 +617,44 @@ impl Connection {
         }
     }
 
+    /// Transmit `data` as an application datagram once the data written to `stream` below
+    /// `offset` has been sent
+    ///
+    /// Prevents a datagram from overtaking stream data it refers to at the sender, which the peer
+    /// may still receive later if it's lost. Datagrams sent afterwards are held back as well.
+    ///
+    /// See [`send_datagram()`] for details.
+    ///
+    /// [`send_datagram()`]: Connection::send_datagram
+    pub fn send_datagram_after(
+        &self,
+        data: Bytes,
+        stream: StreamId,
+        offset: u64,
+    ) -> Result<(), SendDatagramError> {
+        let conn = &mut *self.0.state.lock("send_datagram_after");
+        if let Some(ref x) = conn.error {
+            return Err(SendDatagramError::ConnectionLost(x.clone()));
+        }
+        use proto::SendDatagramError::*;
+        match conn
+            .inner
+            .datagrams()
+            .send_after(data, true, stream, offset)
+        {
+            Ok(()) => {
+                conn.wake();
+                Ok(())
+            }
+            Err(e) => Err(match e {
+                Blocked(..) => unreachable!(),
+                UnsupportedByPeer => SendDatagramError::UnsupportedByPeer,
+                Disabled => SendDatagramError::Disabled,
+                TooLarge => SendDatagramError::TooLarge,
+            }),
+        }
+    }
+
     /// Transmit `data` as an unreliable, unordered application datagram
     ///
     /// Unlike [`send_datagram()`], this method will wait for buffer space during congestion
@@ -534,6 +704,39 @@ impl Connection {
             .send_buffer_space()
     }
 
+    /// Number of bytes that may currently be sent, as limited by congestion control and
+    /// anti-amplification
+    ///
+    /// Lets applications that prioritize data themselves avoid writing more than can be sent
+    /// immediately. Data already written to streams or queued as datagrams consumes this budget
+    /// first.
+    pub fn send_budget(&self) -> u64 {
+        self.0.state.lock("send_budget").inner.send_budget()
+    }
+
+    /// Number of bytes the anti-amplification limit permits sending, or `None` once the peer's
+    /// address is validated
+    ///
+    /// See [`proto::Connection::anti_amplification_budget()`].
+    pub fn anti_amplification_budget(&self) -> Option<u64> {
+        self.0
+            .state
+            .lock("anti_amplification_budget")
+            .inner
+            .anti_amplification_budget()
+    }
+
+    /// Wait until at least `bytes` may be sent, see [`send_budget()`](Self::send_budget)
+    ///
+    /// Waiting for more bytes than the congestion window can grow to never completes.
+    pub fn send_ready(&self, bytes: u64) -> SendReady<'_> {
+        SendReady {
+            conn: &self.0,
+            bytes,
+            notify: self.0.shared.send_budget_available.notified(),
+        }
+    }
+
     /// The side of the connection (client or server)
     pub fn side(&self) -> Side {
         self.0.state.lock("side").inner.side()
@@ -560,16 +763,243 @@ impl Connection {
         self.0.state.lock("local_ip").inner.local_ip()
     }
 
+    /// The address the peer most recently reported to see our packets coming from
+    ///
+    /// This is our reflexive transport address, e.g. the public address a NAT maps us to. Requires
+    /// [`TransportConfig::receive_observed_address_reports`] and a peer that sends such reports;
+    /// changes are also announced as [`TransportEvent::ObservedAddr`].
+    ///
+    /// [`TransportConfig::receive_observed_address_reports`]: crate::TransportConfig::receive_observed_address_reports
+    /// [`TransportEvent::ObservedAddr`]: crate::TransportEvent::ObservedAddr
+    pub fn observed_address(&self) -> Option<SocketAddr> {
+        self.0
+            .state
+            .lock("observed_address")
+            .inner
+            .observed_address()
+    }
+
     /// Current best estimate of this connection's latency (round-trip-time)
     pub fn rtt(&self) -> Duration {
         self.0.state.lock("rtt").inner.rtt()
     }
 
+    /// Treat the connection as app-limited until the data currently in flight is acknowledged
+    ///
+    /// Useful after writing the last of a burst of data. See
+    /// [`proto::Connection::mark_app_limited()`].
+    pub fn mark_app_limited(&self) {
+        self.0
+            .state
+            .lock("mark_app_limited")
+            .inner
+            .mark_app_limited();
+    }
+
+    /// Current rate at which data is delivered to the peer on the active path
+    ///
+    /// Measured from acknowledgements independently of the congestion controller. `None` until
+    /// enough data has been acknowledged.
+    pub fn bandwidth_estimate(&self) -> Option<BandwidthEstimate> {
+        self.0
+            .state
+            .lock("bandwidth_estimate")
+            .inner
+            .bandwidth_estimate()
+    }
+
+    /// Send the client new address validation tokens reflecting the current state of the path
+    ///
+    /// Useful with [`ValidationTokenConfig::resumption_hints`], e.g. after a bulk transfer. Has no
+    /// effect on clients.
+    ///
+    /// [`ValidationTokenConfig::resumption_hints`]: crate::ValidationTokenConfig::resumption_hints
+    pub fn refresh_validation_tokens(&self) {
+        let mut conn = self.0.state.lock("refresh_validation_tokens");
+        conn.inner.refresh_validation_tokens();
+        conn.wake();
+    }
+
+    /// Period of inactivity before sending a keep-alive packet, if enabled
+    ///
+    /// See [`proto::Connection::keep_alive_interval()`].
+    pub fn keep_alive_interval(&self) -> Option<Duration> {
+        self.0
+            .state
+            .lock("keep_alive_interval")
+            .inner
+            .keep_alive_interval()
+    }
+
+    /// Stop sending application data until [`resume_sending()`](Self::resume_sending) is called
+    ///
+    /// No stream data or datagrams are sent in the meantime, while acknowledgements, keep-alives
+    /// and flow control updates still are. Writes to streams and datagrams are buffered up to the
+    /// usual limits, after which they block or displace older datagrams.
+    pub fn pause_sending(&self) {
+        self.0.state.lock("pause_sending").inner.pause_sending();
+    }
+
+    /// Undo [`pause_sending()`](Self::pause_sending)
+    pub fn resume_sending(&self) {
+        let mut conn = self.0.state.lock("resume_sending");
+        conn.inner.resume_sending();
+        // Buffered data may be ready to go
+        conn.wake();
+    }
+
+    /// Whether sending application data is paused, see [`pause_sending()`](Self::pause_sending)
+    pub fn is_sending_paused(&self) -> bool {
+        self.0
+            .state
+            .lock("is_sending_paused")
+            .inner
+            .is_sending_paused()
+    }
+
+    /// Measure the round-trip time on demand
+    ///
+    /// Sends an ACK-eliciting packet without application data, and resolves with the time until
+    /// it was acknowledged. The peer is asked to acknowledge it immediately if it supports the
+    /// acknowledgement frequency extension; otherwise the result may include up to the peer's
+    /// maximum ACK delay. Concurrent calls share a single probe.
+    pub async fn probe_rtt(&self) -> Result<Duration, ConnectionError> {
+        let rx = {
+            let mut state = self.0.state.lock("probe_rtt");
+            if let Some(error) = state.error.as_ref() {
+                return Err(error.clone());
+            }
+            let (tx, rx) = oneshot::channel();
+            state.rtt_probes.push(tx);
+            state.inner.probe_rtt();
+            state.wake();
+            rx
+        };
+        match rx.await {
+            Ok(rtt) => Ok(rtt),
+            // Senders are dropped when the connection is terminated
+            Err(_) => Err(self.0.state.lock("probe_rtt").error.clone().unwrap()),
+        }
+    }
+
+    /// Check that the peer is still reachable at its current address
+    ///
+    /// See [`validate_path_to()`](Self::validate_path_to).
+    pub async fn validate_path(&self) -> Result<bool, ConnectionError> {
+        let remote = self.remote_address();
+        match self.validate_path_to(remote).await {
+            Ok(validated) => Ok(validated),
+            Err(ValidatePathError::ConnectionLost(e)) => Err(e),
+            Err(ValidatePathError::NoConnectionId) => {
+                unreachable!("the current path needs no spare CID")
+            }
+        }
+    }
+
+    /// Check that the peer is reachable at `remote`, e.g. before migrating to a backup address
+    ///
+    /// Sends PATH_CHALLENGE frames to `remote`, and resolves with whether the peer responded
+    /// within three probe timeouts. The connection keeps using its current path regardless of the
+    /// outcome. Concurrent calls for the same address share a single validation.
+    ///
+    /// Addresses other than the current one are probed with a connection ID the peer issued but
+    /// that isn't in use, so that observers can't link the two paths. Fails if there is none.
+    pub async fn validate_path_to(&self, remote: SocketAddr) -> Result<bool, ValidatePathError> {
+        let rx = {
+            let mut state = self.0.state.lock("validate_path_to");
+            if let Some(error) = state.error.as_ref() {
+                return Err(error.clone().into());
+            }
+            let now = state.runtime.now();
+            state.inner.validate_path_to(now, remote).map_err(
+                |proto::ValidatePathError::NoConnectionId| ValidatePathError::NoConnectionId,
+            )?;
+            let (tx, rx) = oneshot::channel();
+            state.path_validations.push((remote, tx));
+            state.wake();
+            rx
+        };
+        match rx.await {
+            Ok(validated) => Ok(validated),
+            // Senders are dropped when the connection is terminated
+            Err(_) => Err(self
+                .0
+                .state
+                .lock("validate_path_to")
+                .error
+                .clone()
+                .unwrap()
+                .into()),
+        }
+    }
+
+    /// The transport parameters sent by the peer
+    ///
+    /// `None` until they have been received during the handshake.
+    pub fn peer_transport_parameters(&self) -> Option<PeerTransportParameters> {
+        self.0
+            .state
+            .lock("peer_transport_parameters")
+            .inner
+            .peer_transport_parameters()
+    }
+
+    /// Protocol extensions supported by both endpoints
+    pub fn negotiated_extensions(&self) -> Vec<Extension> {
+        self.0
+            .state
+            .lock("negotiated_extensions")
+            .inner
+            .negotiated_extensions()
+    }
+
+    /// Distribution of the RTT samples taken on the connection
+    pub fn rtt_histogram(&self) -> RttHistogram {
+        self.0
+            .state
+            .lock("rtt_histogram")
+            .inner
+            .rtt_histogram()
+            .clone()
+    }
+
     /// Returns connection statistics
     pub fn stats(&self) -> ConnectionStats {
         self.0.state.lock("stats").inner.stats()
     }
 
+    /// Snapshot of the connection's internal state, for diagnostics
+    ///
+    /// See [`proto::Connection::debug_state()`].
+    pub fn debug_state(&self) -> ConnectionDebugState {
+        self.0.state.lock("debug_state").inner.debug_state()
+    }
+
+    /// Connection IDs currently in use in either direction
+    ///
+    /// See [`proto::Connection::connection_ids()`].
+    pub fn connection_ids(&self) -> ConnectionIdState {
+        self.0.state.lock("connection_ids").inner.connection_ids()
+    }
+
+    /// Subscribe to observations about the internal behavior of the connection
+    ///
+    /// Only events that occur after this call are reported. The stream ends once the connection
+    /// is closed. Any number of subscriptions may exist at a time, and each receives every event.
+    ///
+    /// Like [`proto::Connection::poll_transport_event`], each subscription buffers a bounded
+    /// number of events, dropping the oldest ones if it isn't polled often enough. Dropped events
+    /// are counted by [`ConnectionEvents::missed`].
+    pub fn events(&self) -> ConnectionEvents {
+        let queue = Arc::new(Mutex::new(EventQueue::default()));
+        let mut state = self.0.state.lock("events");
+        match state.error {
+            None => state.event_subscribers.push(queue.clone()),
+            Some(_) => queue.lock("events").closed = true,
+        }
+        ConnectionEvents(queue)
+    }
+
     /// Current state of the congestion control algorithm, for debugging purposes
     pub fn congestion_state(&self) -> Box<dyn Controller> {
         self.0
@@ -637,6 +1067,20 @@ impl Connection {
             .peer_identity()
     }
 
+    /// Credentials the peer authenticated with, such as its certificate chain
+    ///
+    /// Unlike [`peer_identity()`](Self::peer_identity), requires no knowledge of the configured
+    /// [`Session`](proto::crypto::Session) type. Useful for authorization decisions and public key
+    /// pinning once the handshake completed.
+    pub fn peer_credentials(&self) -> Option<PeerCredentials> {
+        self.0
+            .state
+            .lock("peer_credentials")
+            .inner
+            .crypto_session()
+            .peer_credentials()
+    }
+
     /// A stable identifier for this connection
     ///
     /// Peer addresses and connection IDs can change, but this value will remain
@@ -645,6 +1089,11 @@ impl Connection {
         self.0.stable_id()
     }
 
+    /// The runtime driving this connection
+    pub(crate) fn runtime(&self) -> Arc<dyn Runtime> {
+        self.0.state.lock("runtime").runtime.clone()
+    }
+
     /// Update traffic keys spontaneously
     ///
     /// This primarily exists for testing purposes.
@@ -703,6 +1152,187 @@ impl Connection {
         conn.wake();
     }
 
+    /// Limit the rate at which data is sent on this connection, in bytes per second
+    ///
+    /// See [`proto::Connection::set_max_outgoing_bytes_per_second()`].
+    pub fn set_max_outgoing_bytes_per_second(&self, value: Option<u64>) {
+        let mut conn = self.0.state.lock("set_max_outgoing_bytes_per_second");
+        conn.inner.set_max_outgoing_bytes_per_second(value);
+        // A raised limit may allow sending sooner
+        conn.wake();
+    }
+
+    /// The current limit on the rate at which data is sent on this connection, in bytes per second
+    pub fn max_outgoing_bytes_per_second(&self) -> Option<u64> {
+        self.0
+            .state
+            .lock("max_outgoing_bytes_per_second")
+            .inner
+            .max_outgoing_bytes_per_second()
+    }
+
+    /// Stop the receive half of a stream, which need not have been accepted yet
+    ///
+    /// Discards unread data and asks the peer to stop sending with a `STOP_SENDING` frame. Allows
+    /// turning down streams announced through [`TransportEvent::StreamOpened`] without accepting
+    /// them. Streams stopped this way are still yielded by [`accept_uni()`](Self::accept_uni) and
+    /// [`accept_bi()`](Self::accept_bi). See [`proto::RecvStream::stop()`].
+    ///
+    /// # Panics
+    /// - when applied to a locally initiated unidirectional stream
+    pub fn stop_stream(&self, id: StreamId, error_code: VarInt) -> Result<(), ClosedStream> {
+        let mut conn = self.0.state.lock("stop_stream");
+        conn.inner.recv_stream(id).stop(error_code)?;
+        // Let a pending read observe the stream being closed
+        wake_stream(id, &mut conn.blocked_readers);
+        conn.wake();
+        Ok(())
+    }
+
+    /// Add a stream to an application-defined group, or remove it from its group with `None`
+    ///
+    /// See [`proto::Streams::set_group()`].
+    pub fn set_stream_group(&self, id: StreamId, group: Option<u64>) -> Result<(), ClosedStream> {
+        self.0
+            .state
+            .lock("set_stream_group")
+            .inner
+            .streams()
+            .set_group(id, group)
+    }
+
+    /// Statistics of a group of streams, if it has any members
+    pub fn stream_group_stats(&self, group: u64) -> Option<StreamGroupStats> {
+        self.0
+            .state
+            .lock("stream_group_stats")
+            .inner
+            .streams()
+            .group_stats(group)
+    }
+
+    /// Statistics of how stream data was scheduled for transmission, to help tune priorities
+    pub fn stream_scheduler_stats(&self) -> StreamSchedulerStats {
+        self.0
+            .state
+            .lock("stream_scheduler_stats")
+            .inner
+            .streams()
+            .scheduler_stats()
+    }
+
+    /// Reset and stop every stream of a group with `error_code`, returning its final statistics
+    ///
+    /// See [`proto::Connection::close_stream_group()`].
+    pub fn close_stream_group(&self, group: u64, error_code: VarInt) -> Option<StreamGroupStats> {
+        let mut conn = self.0.state.lock("close_stream_group");
+        let stats = conn.inner.close_stream_group(group, error_code)?;
+        // Streams of the group can no longer be written or read
+        wake_all(&mut conn.blocked_writers);
+        wake_all(&mut conn.blocked_readers);
+        conn.wake();
+        Some(stats)
+    }
+
+    /// Attach an application-supplied trace context, such as a distributed tracing ID
+    ///
+    /// Tracing events emitted while driving this connection are recorded in a span with a
+    /// `trace_context` field, and qlog events are tagged likewise. See
+    /// [`proto::Connection::set_trace_context()`].
+    pub fn set_trace_context(&self, context: Option<String>) {
+        self.0
+            .state
+            .lock("set_trace_context")
+            .inner
+            .set_trace_context(context);
+    }
+
+    /// The trace context attached with [`set_trace_context()`](Self::set_trace_context)
+    pub fn trace_context(&self) -> Option<String> {
+        self.0
+            .state
+            .lock("trace_context")
+            .inner
+            .trace_context()
+            .map(str::to_owned)
+    }
+
+    /// Lower the idle timeout of this connection, or restore the negotiated one with `None`
+    ///
+    /// See [`proto::Connection::set_idle_timeout()`].
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        let mut conn = self.0.state.lock("set_idle_timeout");
+        let now = conn.runtime.now();
+        conn.inner.set_idle_timeout(now, timeout);
+        conn.wake();
+    }
+
+    /// The effective idle timeout, if any
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        self.0.state.lock("idle_timeout").inner.idle_timeout()
+    }
+
+    /// See [`proto::TransportConfig::dscp()`]
+    pub fn set_dscp(&self, dscp: Option<u8>) {
+        let mut conn = self.0.state.lock("set_dscp");
+        conn.inner.set_dscp(dscp);
+    }
+
+    /// See [`proto::TransportConfig::transmit_marker()`]
+    pub fn set_transmit_marker(&self, marker: Option<Arc<dyn TransmitMarker>>) {
+        let mut conn = self.0.state.lock("set_transmit_marker");
+        conn.inner.set_transmit_marker(marker);
+    }
+
+    /// Replace the MTU discovery configuration of this connection
+    ///
+    /// See [`proto::Connection::set_mtu_discovery_config()`].
+    pub fn set_mtu_discovery_config(&self, config: Option<MtuDiscoveryConfig>) {
+        let mut conn = self.0.state.lock("set_mtu_discovery_config");
+        conn.inner.set_mtu_discovery_config(config);
+        // May need to send a probe
+        conn.wake();
+    }
+
+    /// Assign this connection to a priority class
+    ///
+    /// Decides how the connection shares the endpoint's UDP socket with other connections while
+    /// the socket is congested. See [`PriorityClass`]. Defaults to [`PriorityClass::Normal`].
+    pub fn set_priority_class(&self, priority: PriorityClass) {
+        let mut conn = self.0.state.lock("set_priority_class");
+        conn.set_priority_class(priority);
+        conn.wake();
+    }
+
+    /// The priority class of this connection
+    pub fn priority_class(&self) -> PriorityClass {
+        self.0.state.lock("priority_class").priority
+    }
+
+    /// The transport configuration of this connection
+    ///
+    /// See [`proto::Connection::config()`].
+    pub fn config(&self) -> Arc<TransportConfig> {
+        self.0.state.lock("config").inner.config().clone()
+    }
+
+    /// Apply a new transport configuration to this connection
+    ///
+    /// Fails without changing anything if the configuration differs in fields that can't be
+    /// changed on a live connection. See [`proto::Connection::tune()`].
+    pub fn tune(&self, config: Arc<TransportConfig>) -> Result<(), TuneError> {
+        let mut conn = self.0.state.lock("tune");
+        let now = conn.runtime.now();
+        conn.inner.tune(now, config)?;
+        conn.wake();
+        Ok(())
+    }
+
+    /// The most recent MTU probes sent on the current path, oldest first
+    pub fn mtu_probes(&self) -> Vec<MtuProbe> {
+        self.0.state.lock("mtu_probes").inner.mtu_probes().collect()
+    }
+
     /// Modify the number of remotely initiated bidirectional streams that may be concurrently open
     ///
     /// No streams may be opened by the peer unless fewer than `count` are already open. Large
@@ -883,6 +1513,44 @@ impl Future for ReadDatagram<'_> {
     }
 }
 
+pin_project! {
+    /// Future produced by [`Connection::read_flow_datagram`]
+    pub struct ReadFlowDatagram<'a> {
+        conn: &'a ConnectionRef,
+        flow: u64,
+        #[pin]
+        notify: Notified<'a>,
+    }
+}
+
+impl Future for ReadFlowDatagram<'_> {
+    type Output = Result<Bytes, ReadFlowDatagramError>;
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let mut state = this.conn.state.lock("ReadFlowDatagram::poll");
+        match state.inner.datagrams().recv_flow(*this.flow) {
+            Ok(Some(x)) => return Poll::Ready(Ok(x)),
+            Ok(None) => {}
+            Err(proto::RecvFlowError::NoClassifier) => {
+                return Poll::Ready(Err(ReadFlowDatagramError::NoClassifier));
+            }
+        }
+        if let Some(ref e) = state.error {
+            return Poll::Ready(Err(e.clone().into()));
+        }
+        loop {
+            match this.notify.as_mut().poll(ctx) {
+                // `state` lock ensures we didn't race with readiness
+                Poll::Pending => return Poll::Pending,
+                // Spurious wakeup, get a new future
+                Poll::Ready(()) => this
+                    .notify
+                    .set(this.conn.shared.datagram_received.notified()),
+            }
+        }
+    }
+}
+
 pin_project! {
     /// Future produced by [`Connection::send_datagram_wait`]
     pub struct SendDatagram<'a> {
@@ -932,6 +1600,103 @@ impl Future for SendDatagram<'_> {
     }
 }
 
+pin_project! {
+    /// Future produced by [`Connection::send_ready`]
+    pub struct SendReady<'a> {
+        conn: &'a ConnectionRef,
+        bytes: u64,
+        #[pin]
+        notify: Notified<'a>,
+    }
+}
+
+impl Future for SendReady<'_> {
+    type Output = Result<(), ConnectionError>;
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut this = self.project();
+        let mut state = this.conn.state.lock("SendReady::poll");
+        if let Some(ref e) = state.error {
+            return Poll::Ready(Err(e.clone()));
+        }
+        if state.inner.send_budget() >= *this.bytes {
+            return Poll::Ready(Ok(()));
+        }
+        state.send_budget_wanted = true;
+        loop {
+            match this.notify.as_mut().poll(ctx) {
+                // `state` lock ensures we didn't race with readiness
+                Poll::Pending => return Poll::Pending,
+                // Spurious wakeup, get a new future
+                Poll::Ready(()) => this
+                    .notify
+                    .set(this.conn.shared.send_budget_available.notified()),
+            }
+        }
+    }
+}
+
+/// Stream of [`TransportEvent`]s produced by [`Connection::events`]
+#[derive(Debug)]
+pub struct ConnectionEvents(Arc<Mutex<EventQueue>>);
+
+impl ConnectionEvents {
+    /// Wait for the next event
+    ///
+    /// Returns `None` once the connection is closed and all prior events have been returned.
+    pub async fn next(&mut self) -> Option<TransportEvent> {
+        poll_fn(|cx| self.poll_next(cx)).await
+    }
+
+    /// Poll for the next event, registering to be woken if none is available yet
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<TransportEvent>> {
+        let mut queue = self.0.lock("ConnectionEvents::poll_next");
+        if let Some(event) = queue.events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    /// Number of events dropped so far because the subscription fell behind
+    pub fn missed(&self) -> u64 {
+        self.0.lock("ConnectionEvents::missed").missed
+    }
+}
+
+/// Events buffered for a [`ConnectionEvents`]
+#[derive(Debug, Default)]
+struct EventQueue {
+    events: VecDeque<TransportEvent>,
+    /// Events dropped to stay within [`MAX_QUEUED_EVENTS`]
+    missed: u64,
+    /// Whether the connection is closed, so no further events will be queued
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+impl EventQueue {
+    fn push(&mut self, event: TransportEvent) {
+        if self.events.len() >= MAX_QUEUED_EVENTS {
+            self.events.pop_front();
+            self.missed += 1;
+        }
+        self.events.push_back(event);
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct ConnectionRef(Arc<ConnectionInner>);
 
@@ -941,6 +1706,36 @@ impl ConnectionRef {
     }
 }
 
+/// A handle to a connection that doesn't keep it open, unlike [`Connection`]
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+pub(crate) struct WeakConnection(std::sync::Weak<ConnectionInner>);
+
+#[cfg(feature = "metrics")]
+impl WeakConnection {
+    pub(crate) fn new(conn: &Connection) -> Self {
+        Self(Arc::downgrade(&conn.0.0))
+    }
+
+    /// See [`Connection::stable_id()`]
+    pub(crate) fn stable_id(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    /// Statistics and peer address of the connection, and whether it's closed
+    ///
+    /// `None` once the connection has been dropped entirely.
+    pub(crate) fn stats(&self) -> Option<(ConnectionStats, SocketAddr, bool)> {
+        let inner = self.0.upgrade()?;
+        let state = inner.state.lock("stats");
+        Some((
+            state.inner.stats(),
+            state.inner.remote_address(),
+            state.error.is_some(),
+        ))
+    }
+}
+
 impl Clone for ConnectionRef {
     fn clone(&self) -> Self {
         self.shared.ref_count.fetch_add(1, Ordering::Relaxed);
@@ -989,7 +1784,11 @@ pub(crate) struct Shared {
     stream_incoming: [Notify; 2],
     datagram_received: Notify,
     datagrams_unblocked: Notify,
+    /// Notified when the send budget may have grown, if `State::send_budget_wanted` was set
+    send_budget_available: Notify,
     closed: Notify,
+    /// Notified when the peer confirmed our CONNECTION_CLOSE, or the connection drained
+    close_answered: Notify,
     connected: Arc<Notify>,
     /// Number of live handles that can used to initiate or handle I/O; excludes the driver
     ref_count: AtomicUsize,
@@ -1012,10 +1811,26 @@ pub(crate) struct State {
     /// Always set to Some before the connection becomes drained
     pub(crate) error: Option<ConnectionError>,
     sender: Pin<Box<dyn UdpSender>>,
+    send_monitor: Arc<SendMonitor>,
     runtime: Arc<dyn Runtime>,
     send_buffer: Vec<u8>,
     /// We buffer a transmit when the underlying I/O would block
     buffered_transmit: Option<proto::Transmit>,
+    /// When the buffered transmit was first found to block, and whether that stall was reported
+    send_blocked_since: Option<(Instant, bool)>,
+    /// Class deciding whether this connection yields the socket to others while it's congested
+    priority: PriorityClass,
+    /// Whether the connection used up its transmit quantum with more left to send, so that it
+    /// takes turns with other such connections
+    backlogged: bool,
+    /// Queues of transport event subscribers, closed once the connection is closed
+    event_subscribers: Vec<Arc<Mutex<EventQueue>>>,
+    /// Whether a [`SendReady`] future is waiting for the send budget to grow
+    send_budget_wanted: bool,
+    /// Callers of [`Connection::probe_rtt`] awaiting the outstanding probe
+    rtt_probes: Vec<oneshot::Sender<Duration>>,
+    /// Callers of [`Connection::validate_path_to`] awaiting the validation of an address
+    path_validations: Vec<(SocketAddr, oneshot::Sender<bool>)>,
 }
 
 impl State {
@@ -1027,6 +1842,7 @@ impl State {
         conn_events: mpsc::UnboundedReceiver<ConnectionEvent>,
         on_handshake_data: oneshot::Sender<()>,
         sender: Pin<Box<dyn UdpSender>>,
+        send_monitor: Arc<SendMonitor>,
         runtime: Arc<dyn Runtime>,
     ) -> Self {
         Self {
@@ -1045,20 +1861,34 @@ impl State {
             stopped: FxHashMap::default(),
             error: None,
             sender,
+            send_monitor,
             runtime,
             send_buffer: Vec::new(),
             buffered_transmit: None,
+            send_blocked_since: None,
+            priority: PriorityClass::default(),
+            backlogged: false,
+            event_subscribers: Vec::new(),
+            send_budget_wanted: false,
+            rtt_probes: Vec::new(),
+            path_validations: Vec::new(),
         }
     }
 
     fn drive_transmit(&mut self, cx: &mut Context<'_>) -> io::Result<bool> {
+        if self.backlogged && !self.send_monitor.has_turn(self.handle) {
+            return Ok(false);
+        }
         let now = self.runtime.now();
         let mut transmits = 0;
 
+        // The maximum amount of datagrams which will be produced in this call
+        let quantum = self.send_monitor.transmit_quantum(self.priority);
         let max_datagrams = self
             .sender
             .max_transmit_segments()
-            .min(MAX_TRANSMIT_SEGMENTS);
+            .min(MAX_TRANSMIT_SEGMENTS)
+            .min(quantum);
 
         loop {
             // Retry the last transmit, or get a new one.
@@ -1084,31 +1914,82 @@ impl State {
             };
 
             let len = t.size;
-            match self
+            let result = self
                 .sender
                 .as_mut()
-                .poll_send(&udp_transmit(&t, &self.send_buffer[..len]), cx)
-            {
-                Poll::Pending => {
-                    self.buffered_transmit = Some(t);
-                    return Ok(false);
+                .poll_send(&udp_transmit(&t, &self.send_buffer[..len]), cx);
+            if result.is_pending() {
+                self.send_blocked(t.destination, now);
+                self.buffered_transmit = Some(t);
+                return Ok(false);
+            }
+            self.send_unblocked(t.destination, now);
+            match result {
+                Poll::Ready(Err(e)) if udp::is_too_big(&e) => {
+                    let size = t.segment_size.unwrap_or(t.size);
+                    self.inner
+                        .handle_too_big(now, u16::try_from(size).unwrap_or(u16::MAX));
                 }
                 Poll::Ready(Err(e)) => return Err(e),
-                Poll::Ready(Ok(())) => {}
+                _ => {}
             }
 
-            if transmits >= MAX_TRANSMIT_DATAGRAMS {
+            if transmits >= quantum {
                 // TODO: What isn't ideal here yet is that if we don't poll all
                 // datagrams that could be sent we don't go into the `app_limited`
                 // state and CWND continues to grow until we get here the next time.
                 // See https://github.com/quinn-rs/quinn/issues/1126
-                return Ok(true);
+                self.backlogged = true;
+                return Ok(self.send_monitor.yield_turn(self.handle, cx.waker()));
             }
         }
 
+        self.release_turn();
         Ok(false)
     }
 
+    /// Stop taking turns with other connections that have more to send
+    fn release_turn(&mut self) {
+        if mem::take(&mut self.backlogged) {
+            self.send_monitor.release_turn(self.handle);
+        }
+    }
+
+    /// Track a transmit to `remote` that is waiting for the socket to become writable
+    fn send_blocked(&mut self, remote: SocketAddr, now: Instant) {
+        match &mut self.send_blocked_since {
+            None => {
+                self.send_monitor.blocked(self.priority);
+                self.send_blocked_since = Some((now, false));
+            }
+            Some((since, reported @ false)) => {
+                *reported = self
+                    .send_monitor
+                    .check(remote, now.saturating_duration_since(*since));
+            }
+            Some((_, true)) => {}
+        }
+    }
+
+    /// Track that the socket accepted a transmit to `remote` after blocking
+    fn send_unblocked(&mut self, remote: SocketAddr, now: Instant) {
+        let Some((since, reported)) = self.send_blocked_since.take() else {
+            return;
+        };
+        let duration = now.saturating_duration_since(since);
+        self.send_monitor.unblocked(self.priority, duration);
+        if !reported {
+            self.send_monitor.check(remote, duration);
+        }
+    }
+
+    fn set_priority_class(&mut self, priority: PriorityClass) {
+        if self.send_blocked_since.is_some() {
+            self.send_monitor.reclassified(self.priority, priority);
+        }
+        self.priority = priority;
+    }
+
     fn forward_endpoint_events(&mut self) {
         while let Some(event) = self.inner.poll_endpoint_events() {
             // If the endpoint driver is gone, noop.
@@ -1147,6 +2028,22 @@ impl State {
         }
     }
 
+    fn forward_transport_events(&mut self) {
+        while let Some(event) = self.inner.poll_transport_event() {
+            if let TransportEvent::RttProbed { rtt } = event {
+                for x in self.rtt_probes.drain(..) {
+                    let _ = x.send(rtt);
+                }
+            }
+            // Subscribers that were dropped no longer need events
+            self.event_subscribers
+                .retain(|queue| Arc::strong_count(queue) > 1);
+            for queue in &self.event_subscribers {
+                queue.lock("forward_transport_events").push(event.clone());
+            }
+        }
+    }
+
     fn forward_app_events(&mut self, shared: &Shared) {
         while let Some(event) = self.inner.poll() {
             use proto::Event::*;
@@ -1187,6 +2084,11 @@ impl State {
                 DatagramsUnblocked => {
                     shared.datagrams_unblocked.notify_waiters();
                 }
+                PathValidation { remote, validated } => {
+                    for (_, x) in self.path_validations.extract_if(.., |(x, _)| *x == remote) {
+                        let _ = x.send(validated);
+                    }
+                }
                 Stream(StreamEvent::Readable { id }) => wake_stream(id, &mut self.blocked_readers),
                 Stream(StreamEvent::Available { dir }) => {
                     // Might mean any number of streams are ready, so we wake up everyone
@@ -1251,6 +2153,13 @@ impl State {
         }
     }
 
+    /// End the event streams of all subscribers
+    fn close_event_subscribers(&mut self) {
+        for queue in self.event_subscribers.drain(..) {
+            queue.lock("close_event_subscribers").close();
+        }
+    }
+
     /// Used to wake up all blocked futures when the connection becomes closed for any reason
     fn terminate(&mut self, reason: ConnectionError, shared: &Shared) {
         self.error = Some(reason.clone());
@@ -1265,10 +2174,14 @@ impl State {
         shared.stream_incoming[Dir::Bi as usize].notify_waiters();
         shared.datagram_received.notify_waiters();
         shared.datagrams_unblocked.notify_waiters();
+        shared.send_budget_available.notify_waiters();
         shared.handshake_confirmed.notify_waiters();
         wake_all_notify(&mut self.stopped);
         shared.closed.notify_waiters();
         shared.connected.notify_waiters();
+        self.close_event_subscribers();
+        self.rtt_probes.clear();
+        self.path_validations.clear();
     }
 
     fn close(&mut self, error_code: VarInt, reason: Bytes, shared: &Shared) {
@@ -1296,6 +2209,11 @@ impl State {
 
 impl Drop for State {
     fn drop(&mut self) {
+        if self.send_blocked_since.is_some() {
+            self.send_monitor.abandoned(self.priority);
+        }
+        self.release_turn();
+        self.close_event_subscribers();
         if !self.inner.is_drained() {
             // Ensure the endpoint can tidy up
             let _ = self
@@ -1353,11 +2271,32 @@ pub enum SendDatagramError {
     ConnectionLost(#[from] ConnectionError),
 }
 
-/// The maximum amount of datagrams which will be produced in a single `drive_transmit` call
-///
-/// This limits the amount of CPU resources consumed by datagram generation,
-/// and allows other tasks (like receiving ACKs) to run in between.
-const MAX_TRANSMIT_DATAGRAMS: usize = 20;
+/// Errors that can arise when receiving the datagrams of a flow
+#[derive(Debug, Error, Clone, Eq, PartialEq)]
+pub enum ReadFlowDatagramError {
+    /// No [`TransportConfig::datagram_classifier`] is configured, so received datagrams aren't
+    /// assigned to flows
+    ///
+    /// [`TransportConfig::datagram_classifier`]: crate::TransportConfig::datagram_classifier
+    #[error("no datagram classifier configured")]
+    NoClassifier,
+    /// The connection was lost
+    #[error("connection lost")]
+    ConnectionLost(#[from] ConnectionError),
+}
+
+/// Errors that can arise when validating a path
+#[derive(Debug, Error, Clone, Eq, PartialEq)]
+pub enum ValidatePathError {
+    /// The peer hasn't issued a connection ID that isn't in use yet
+    ///
+    /// Another address can't be probed without letting observers link it to the current path.
+    #[error("no spare connection ID to probe the address with")]
+    NoConnectionId,
+    /// The connection was lost
+    #[error("connection lost")]
+    ConnectionLost(#[from] ConnectionError),
+}
 
 /// The maximum amount of datagrams that are sent in a single transmit
 ///
@@ -1365,3 +2304,6 @@ const MAX_TRANSMIT_DATAGRAMS: usize = 20;
 /// memory allocations when calling `poll_transmit()`. Benchmarks have shown
 /// that numbers around 10 are a good compromise.
 const MAX_TRANSMIT_SEGMENTS: usize = 10;
+
+/// Number of events buffered for each [`ConnectionEvents`], matching quinn-proto's own queue
+pub(crate) const MAX_QUEUED_EVENTS: usize = 32;
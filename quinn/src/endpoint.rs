@@ -4,7 +4,7 @@ use std::{
     future::Future,
     io::{self, IoSliceMut},
     mem,
-    net::{SocketAddr, SocketAddrV6},
+    net::{IpAddr, SocketAddr, SocketAddrV6},
     pin::Pin,
     str,
     sync::{
@@ -21,11 +21,11 @@ use std::{
 ))]
 use crate::runtime::default_runtime;
 use crate::{
-    Instant,
+    Duration, Instant,
     runtime::{AsyncUdpSocket, Runtime, UdpSender},
     udp_transmit,
 };
-use bytes::{Bytes, BytesMut};
+use bytes::Bytes;
 use pin_project_lite::pin_project;
 use proto::{
     self as proto, ClientConfig, ConnectError, ConnectionError, ConnectionHandle, DatagramEvent,
@@ -38,13 +38,18 @@ use rustc_hash::FxHashMap;
     any(feature = "aws-lc-rs", feature = "ring"),
 ))]
 use socket2::{Domain, Protocol, Socket, Type};
+use thiserror::Error;
 use tokio::sync::{Notify, futures::Notified, mpsc};
 use tracing::{Instrument, Span};
 use udp::{BATCH_SIZE, RecvMeta};
 
 use crate::{
     ConnectionEvent, EndpointConfig, IO_LOOP_BOUND, RECV_TIME_BOUND, VarInt,
-    connection::Connecting, incoming::Incoming, work_limiter::WorkLimiter,
+    connection::{Connecting, Connection},
+    incoming::Incoming,
+    recv_buffers::RecvBuffers,
+    resolver::{self, Resolver, default_resolver},
+    work_limiter::WorkLimiter,
 };
 
 /// A QUIC endpoint.
@@ -52,11 +57,16 @@ use crate::{
 /// An endpoint corresponds to a single UDP socket, may host many connections, and may act as both
 /// client and server for different connections.
 ///
+/// Received datagrams are routed to their connection by a single driver task, while decryption
+/// and frame processing happen in each connection's own task. On a multi-threaded runtime, the
+/// work of a busy endpoint is therefore spread across cores, with the packets of each connection
+/// still processed in order.
+///
 /// May be cloned to obtain another handle to the same endpoint.
 #[derive(Debug, Clone)]
 pub struct Endpoint {
     pub(crate) inner: EndpointRef,
-    runtime: Arc<dyn Runtime>,
+    pub(crate) runtime: Arc<dyn Runtime>,
 }
 
 impl Endpoint {
@@ -102,7 +112,64 @@ impl Endpoint {
 
     /// Returns relevant stats from this Endpoint
     pub fn stats(&self) -> EndpointStats {
-        self.inner.state.lock().unwrap().stats
+        let state = self.inner.state.lock().unwrap();
+        let mut stats = state.stats;
+        let monitor = state
+            .recv_state
+            .connections
+            .send_monitor
+            .state
+            .lock()
+            .unwrap();
+        stats.send_blocked = monitor.blocked;
+        stats.send_blocked_time = monitor.blocked_time;
+        stats.socket_send = state.socket.send_stats();
+        stats
+    }
+
+    /// Invoke `callback` whenever a connection has been unable to send for at least `threshold`
+    ///
+    /// Transmits are held up when the UDP socket isn't writable, e.g. because the send buffer is
+    /// full. The affected connection keeps the pending transmit and retries it once the socket
+    /// becomes writable again. `callback` is invoked at most once per such stall, from the
+    /// connection's driver task; the stall is checked for whenever the driver runs, so the
+    /// callback may fire somewhat later than `threshold`. It must not block or call into the
+    /// connection.
+    ///
+    /// Replaces any previously set callback.
+    pub fn set_send_blocked_callback(
+        &self,
+        threshold: Duration,
+        callback: impl Fn(SendBlocked) + Send + Sync + 'static,
+    ) {
+        let state = self.inner.state.lock().unwrap();
+        state
+            .recv_state
+            .connections
+            .send_monitor
+            .state
+            .lock()
+            .unwrap()
+            .on_blocked = Some((threshold, Arc::new(callback)));
+    }
+
+    /// Set the number of datagrams a connection may send before yielding to other connections
+    ///
+    /// Connections that have more to send once they've sent this many datagrams take turns in
+    /// round-robin order, each sending up to this many datagrams per turn, while connections with
+    /// less to send aren't held up. This bounds how long a connection with a large backlog can
+    /// hold up others sharing the endpoint's socket and runtime, at the cost of more frequent task
+    /// switches. Smaller values favor fairness and responsiveness, larger values favor the
+    /// throughput of individual connections. Applies to existing and future connections. Defaults
+    /// to 20; a value of 0 is treated as 1.
+    pub fn set_transmit_quantum(&self, datagrams: usize) {
+        let state = self.inner.state.lock().unwrap();
+        state
+            .recv_state
+            .connections
+            .send_monitor
+            .transmit_quantum
+            .store(datagrams.max(1), Ordering::Relaxed);
     }
 
     /// Helper to construct an endpoint for use with both incoming and outgoing connections
@@ -143,6 +210,8 @@ impl Endpoint {
     }
 
     /// Construct an endpoint with arbitrary configuration and socket
+    ///
+    /// The options in [`EndpointConfig::socket_config`] are applied to `socket`.
     #[cfg(not(wasm_browser))]
     pub fn new(
         config: EndpointConfig,
@@ -150,6 +219,7 @@ impl Endpoint {
         socket: std::net::UdpSocket,
         runtime: Arc<dyn Runtime>,
     ) -> io::Result<Self> {
+        configure_socket(&socket, config.get_socket_config())?;
         let socket = runtime.wrap_udp_socket(socket)?;
         Self::new_with_abstract_socket(config, server_config, socket, runtime)
     }
@@ -202,6 +272,14 @@ impl Endpoint {
         self.inner.0.state.lock().unwrap().default_client_config = Some(config);
     }
 
+    /// Set the resolver used by [`connect_to()`](Self::connect_to)
+    ///
+    /// Defaults to [`TokioResolver`](crate::TokioResolver) when the `runtime-tokio` feature is
+    /// enabled.
+    pub fn set_resolver(&self, resolver: Arc<dyn Resolver>) {
+        self.inner.state.lock().unwrap().resolver = Some(resolver);
+    }
+
     /// Connect to a remote endpoint
     ///
     /// `server_name` must be covered by the certificate presented by the server. This prevents a
@@ -262,11 +340,179 @@ impl Endpoint {
             .insert(ch, conn, sender, self.runtime.clone()))
     }
 
+    /// Connect to a server by host name
+    ///
+    /// `target` has the form `host:port`, where `host` is a domain name, an IPv4 address or an
+    /// IPv6 address in brackets. Domain names are looked up using the endpoint's
+    /// [resolver](Self::set_resolver), including any HTTPS/SVCB records advertising alternative
+    /// endpoints, and the resulting addresses are raced as in
+    /// [`connect_racing()`](Self::connect_racing). `host` is used as the server name.
+    pub async fn connect_to(&self, target: &str) -> Result<Connection, ConnectToError> {
+        let config = self
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .default_client_config
+            .clone()
+            .ok_or(ConnectError::NoDefaultClientConfig)?;
+        self.connect_to_with(config, target).await
+    }
+
+    /// Connect to a server by host name using a custom configuration
+    ///
+    /// See [`connect_to()`](Self::connect_to) for details.
+    pub async fn connect_to_with(
+        &self,
+        config: ClientConfig,
+        target: &str,
+    ) -> Result<Connection, ConnectToError> {
+        let (host, port) = target
+            .rsplit_once(':')
+            .and_then(|(host, port)| Some((host, port.parse::<u16>().ok()?)))
+            .ok_or(ConnectToError::InvalidTarget)?;
+        let host = match host.strip_prefix('[') {
+            Some(host) => host
+                .strip_suffix(']')
+                .ok_or(ConnectToError::InvalidTarget)?,
+            // Unbracketed IPv6 addresses would be ambiguous
+            None if host.contains(':') => return Err(ConnectToError::InvalidTarget),
+            None => host,
+        };
+        if host.is_empty() {
+            return Err(ConnectToError::InvalidTarget);
+        }
+
+        let addrs = match host.parse::<IpAddr>() {
+            Ok(ip) => vec![SocketAddr::new(ip, port)],
+            Err(_) => {
+                let resolver = self
+                    .inner
+                    .state
+                    .lock()
+                    .unwrap()
+                    .resolver
+                    .clone()
+                    .or_else(default_resolver)
+                    .ok_or(ConnectToError::NoResolver)?;
+                resolver::resolve(&*resolver, host, port)
+                    .await
+                    .map_err(|e| ConnectToError::Resolve(e.to_string()))?
+            }
+        };
+        Ok(self.connect_racing_with(config, addrs, host).await?)
+    }
+
+    /// Connect to the first of several addresses of a server that responds
+    ///
+    /// Implements the connection racing of Happy Eyeballs ([RFC 8305]): addresses are reordered to
+    /// alternate between IPv6 and IPv4, starting with the family of the first address, and a
+    /// connection attempt is started every 250ms, or as soon as the previous attempt fails, until
+    /// one completes its handshake. The remaining attempts are then abandoned, closing their
+    /// connections. Addresses should be given in order of preference, e.g. as sorted by the
+    /// resolver.
+    ///
+    /// See [`connect()`](Self::connect) for details.
+    ///
+    /// [RFC 8305]: https://www.rfc-editor.org/rfc/rfc8305.html
+    pub async fn connect_racing(
+        &self,
+        addrs: impl IntoIterator<Item = SocketAddr>,
+        server_name: &str,
+    ) -> Result<Connection, ConnectRacingError> {
+        let config = self
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .default_client_config
+            .clone()
+            .ok_or(ConnectError::NoDefaultClientConfig)?;
+        self.connect_racing_with(config, addrs, server_name).await
+    }
+
+    /// Connect to the first of several addresses of a server that responds, using a custom
+    /// configuration
+    ///
+    /// See [`connect_racing()`](Self::connect_racing) for details.
+    pub async fn connect_racing_with(
+        &self,
+        config: ClientConfig,
+        addrs: impl IntoIterator<Item = SocketAddr>,
+        server_name: &str,
+    ) -> Result<Connection, ConnectRacingError> {
+        let mut pending = interleave_families(addrs);
+        let mut attempts = Vec::<Connecting>::new();
+        let mut last_error = None;
+        let mut timer = self.runtime.new_timer(self.runtime.now());
+        let mut start_next = true;
+        std::future::poll_fn(|cx| {
+            loop {
+                if start_next {
+                    start_next = false;
+                    if let Some(addr) = pending.pop_front() {
+                        match self.connect_with(config.clone(), addr, server_name) {
+                            Ok(connecting) => attempts.push(connecting),
+                            Err(e) => {
+                                last_error = Some(e.into());
+                                start_next = true;
+                                continue;
+                            }
+                        }
+                        timer
+                            .as_mut()
+                            .reset(self.runtime.now() + CONNECTION_ATTEMPT_DELAY);
+                    }
+                }
+
+                let mut i = 0;
+                while i < attempts.len() {
+                    match Pin::new(&mut attempts[i]).poll(cx) {
+                        Poll::Ready(Ok(conn)) => return Poll::Ready(Ok(conn)),
+                        Poll::Ready(Err(e)) => {
+                            attempts.swap_remove(i);
+                            last_error = Some(e.into());
+                            start_next = true;
+                        }
+                        Poll::Pending => i += 1,
+                    }
+                }
+                if start_next {
+                    continue;
+                }
+                if pending.is_empty() {
+                    if attempts.is_empty() {
+                        return Poll::Ready(Err(last_error
+                            .take()
+                            .unwrap_or(ConnectRacingError::NoAddresses)));
+                    }
+                    return Poll::Pending;
+                }
+                if timer.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                start_next = true;
+            }
+        })
+        .await
+    }
+
     /// Switch to a new UDP socket
     ///
-    /// See [`Endpoint::rebind_abstract()`] for details.
+    /// The options in [`EndpointConfig::socket_config`] are applied to `socket`. See
+    /// [`Endpoint::rebind_abstract()`] for details.
     #[cfg(not(wasm_browser))]
     pub fn rebind(&self, socket: std::net::UdpSocket) -> io::Result<()> {
+        let config = self
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .inner
+            .config()
+            .get_socket_config()
+            .clone();
+        configure_socket(&socket, &config)?;
         self.rebind_abstract(self.runtime.wrap_udp_socket(socket)?)
     }
 
@@ -373,6 +619,189 @@ pub struct EndpointStats {
     pub refused_handshakes: u64,
     /// Cumulative number of Quic handshakes ignored on this [Endpoint]
     pub ignored_handshakes: u64,
+    /// Cumulative number of times a connection had to wait for the UDP socket to become writable
+    pub send_blocked: u64,
+    /// Cumulative time connections spent waiting for the UDP socket to become writable
+    ///
+    /// Stalls that are still ongoing aren't included.
+    pub send_blocked_time: Duration,
+    /// Transmits the operating system did not accept on the current UDP socket
+    pub socket_send: udp::SendStats,
+}
+
+/// A stall of a connection's transmits, reported by [`Endpoint::set_send_blocked_callback`]
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone)]
+pub struct SendBlocked {
+    /// Address of the connection's peer
+    pub remote: SocketAddr,
+    /// How long the connection has been waiting for the UDP socket to become writable
+    pub duration: Duration,
+}
+
+/// Class of a connection's traffic, set with [`Connection::set_priority_class()`]
+///
+/// While connections of a class are waiting for the endpoint's UDP socket to become writable,
+/// connections of lower classes send a single datagram per pass of their driver, without
+/// batching, so that socket buffer space freed up goes to the waiting connections first.
+/// Connections within the same class share the socket as per
+/// [`Endpoint::set_transmit_quantum()`].
+///
+/// [`Connection::set_priority_class()`]: crate::Connection::set_priority_class
+#[non_exhaustive]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PriorityClass {
+    /// Bulk transfers which should only use send capacity no other connection is waiting for
+    Background,
+    /// The default class
+    #[default]
+    Normal,
+    /// Latency-sensitive traffic, such as control-plane messages
+    Interactive,
+}
+
+/// Default number of datagrams a connection may send per pass of its driver
+///
+/// This limits the amount of CPU resources consumed by datagram generation,
+/// and allows other tasks (like receiving ACKs) to run in between.
+const DEFAULT_TRANSMIT_QUANTUM: usize = 20;
+
+/// Tracks connections waiting for the endpoint's UDP socket to become writable, and how much they
+/// may send at a time
+#[derive(Debug)]
+pub(crate) struct SendMonitor {
+    state: Mutex<SendMonitorState>,
+    transmit_quantum: AtomicUsize,
+    /// Number of connections waiting for the socket, indexed by [`PriorityClass`]
+    waiting: [AtomicUsize; 3],
+}
+
+impl SendMonitor {
+    /// Number of datagrams a connection of class `priority` may send before yielding to other
+    /// connections
+    pub(crate) fn transmit_quantum(&self, priority: PriorityClass) -> usize {
+        let preempted = self.waiting[priority as usize + 1..]
+            .iter()
+            .any(|count| count.load(Ordering::Relaxed) > 0);
+        match preempted {
+            true => 1,
+            false => self.transmit_quantum.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Record that a connection of class `priority` started waiting for the socket
+    pub(crate) fn blocked(&self, priority: PriorityClass) {
+        self.state.lock().unwrap().blocked += 1;
+        self.waiting[priority as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that a connection of class `priority` could send again after waiting for `duration`
+    pub(crate) fn unblocked(&self, priority: PriorityClass, duration: Duration) {
+        self.state.lock().unwrap().blocked_time += duration;
+        self.abandoned(priority);
+    }
+
+    /// Record that a waiting connection was moved from class `from` to class `to`
+    pub(crate) fn reclassified(&self, from: PriorityClass, to: PriorityClass) {
+        self.waiting[from as usize].fetch_sub(1, Ordering::Relaxed);
+        self.waiting[to as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record that a connection of class `priority` stopped waiting without having sent
+    pub(crate) fn abandoned(&self, priority: PriorityClass) {
+        self.waiting[priority as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Record that connection `handle` used up its transmit quantum with more left to send
+    ///
+    /// Such connections take turns, one quantum each, in the order they ran out. Returns whether
+    /// the connection may keep sending right away; otherwise `waker` is woken once it's the
+    /// connection's turn again.
+    pub(crate) fn yield_turn(&self, handle: ConnectionHandle, waker: &Waker) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.turn.is_some_and(|turn| turn != handle) {
+            state.turns.push_back((handle, waker.clone()));
+            return false;
+        }
+        let Some((next, next_waker)) = state.turns.pop_front() else {
+            state.turn = Some(handle);
+            return true;
+        };
+        state.turns.push_back((handle, waker.clone()));
+        state.turn = Some(next);
+        drop(state);
+        next_waker.wake();
+        false
+    }
+
+    /// Whether connection `handle`, after [yielding](Self::yield_turn), may send again
+    pub(crate) fn has_turn(&self, handle: ConnectionHandle) -> bool {
+        self.state.lock().unwrap().turn == Some(handle)
+    }
+
+    /// Record that connection `handle` no longer takes turns, as it sent all it had or went away
+    pub(crate) fn release_turn(&self, handle: ConnectionHandle) {
+        let mut state = self.state.lock().unwrap();
+        if state.turn != Some(handle) {
+            state.turns.retain(|&(waiting, _)| waiting != handle);
+            return;
+        }
+        state.turn = None;
+        let Some((next, waker)) = state.turns.pop_front() else {
+            return;
+        };
+        state.turn = Some(next);
+        drop(state);
+        waker.wake();
+    }
+
+    /// Report a stall of `duration` if it exceeds the configured threshold
+    ///
+    /// Returns whether the callback was invoked.
+    pub(crate) fn check(&self, remote: SocketAddr, duration: Duration) -> bool {
+        let callback = match &self.state.lock().unwrap().on_blocked {
+            Some((threshold, callback)) if duration >= *threshold => callback.clone(),
+            _ => return false,
+        };
+        // Invoked without holding the lock, so the callback may inspect the endpoint's stats
+        callback(SendBlocked { remote, duration });
+        true
+    }
+}
+
+impl Default for SendMonitor {
+    fn default() -> Self {
+        Self {
+            state: Mutex::default(),
+            transmit_quantum: AtomicUsize::new(DEFAULT_TRANSMIT_QUANTUM),
+            waiting: Default::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SendMonitorState {
+    blocked: u64,
+    blocked_time: Duration,
+    on_blocked: Option<(Duration, Arc<SendBlockedCallback>)>,
+    /// The connection currently allowed to send among those that used up their quantum
+    turn: Option<ConnectionHandle>,
+    /// Connections that used up their quantum waiting for their turn, in order
+    turns: VecDeque<(ConnectionHandle, Waker)>,
+}
+
+type SendBlockedCallback = dyn Fn(SendBlocked) + Send + Sync;
+
+impl fmt::Debug for SendMonitorState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendMonitorState")
+            .field("blocked", &self.blocked)
+            .field("blocked_time", &self.blocked_time)
+            .field("threshold", &self.on_blocked.as_ref().map(|(t, _)| t))
+            .field("turn", &self.turn)
+            .field("turns", &self.turns.len())
+            .finish_non_exhaustive()
+    }
 }
 
 /// A future that drives IO on an endpoint
@@ -511,6 +940,7 @@ pub(crate) struct State {
     runtime: Arc<dyn Runtime>,
     stats: EndpointStats,
     default_client_config: Option<ClientConfig>,
+    resolver: Option<Arc<dyn Resolver>>,
 }
 
 #[derive(Debug)]
@@ -649,7 +1079,7 @@ fn respond(
 }
 
 #[inline]
-fn proto_ecn(ecn: udp::EcnCodepoint) -> proto::EcnCodepoint {
+pub(crate) fn proto_ecn(ecn: udp::EcnCodepoint) -> proto::EcnCodepoint {
     match ecn {
         udp::EcnCodepoint::Ect0 => proto::EcnCodepoint::Ect0,
         udp::EcnCodepoint::Ect1 => proto::EcnCodepoint::Ect1,
@@ -665,6 +1095,8 @@ struct ConnectionSet {
     sender: mpsc::UnboundedSender<(ConnectionHandle, EndpointEvent)>,
     /// Set if the endpoint has been manually closed
     close: Option<(VarInt, Bytes)>,
+    /// Shared with connections to track send path backpressure
+    send_monitor: Arc<SendMonitor>,
 }
 
 impl ConnectionSet {
@@ -684,7 +1116,24 @@ impl ConnectionSet {
             .unwrap();
         }
         self.senders.insert(handle, send);
-        Connecting::new(handle, conn, self.sender.clone(), recv, sender, runtime)
+        Connecting::new(
+            handle,
+            conn,
+            self.sender.clone(),
+            recv,
+            sender,
+            self.send_monitor.clone(),
+            runtime,
+        )
+    }
+
+    fn send_event(&mut self, handle: ConnectionHandle, event: proto::ConnectionEvent) {
+        // Ignoring errors from dropped connections that haven't yet been cleaned up
+        let _ = self
+            .senders
+            .get_mut(&handle)
+            .unwrap()
+            .send(ConnectionEvent::Proto(event));
     }
 
     fn is_empty(&self) -> bool {
@@ -692,6 +1141,75 @@ impl ConnectionSet {
     }
 }
 
+/// Delay between connection attempts of [`Endpoint::connect_racing`]
+///
+/// The value recommended by RFC 8305, section 8.
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// Reorder `addrs` to alternate between address families, starting with that of the first address
+///
+/// Preserves the relative order of addresses of the same family.
+fn interleave_families(addrs: impl IntoIterator<Item = SocketAddr>) -> VecDeque<SocketAddr> {
+    let mut addrs = addrs.into_iter().peekable();
+    let first_is_ipv6 = addrs.peek().is_some_and(SocketAddr::is_ipv6);
+    let (mut first, mut second): (VecDeque<_>, VecDeque<_>) =
+        addrs.partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let mut interleaved = VecDeque::with_capacity(first.len() + second.len());
+    loop {
+        match (first.pop_front(), second.pop_front()) {
+            (None, None) => return interleaved,
+            (a, b) => interleaved.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Errors that arise from [`Endpoint::connect_racing`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ConnectRacingError {
+    /// No addresses were given
+    #[error("no addresses to connect to")]
+    NoAddresses,
+    /// The last connection attempt could not be initiated
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+    /// The last connection attempt failed
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+}
+
+/// Errors that arise from [`Endpoint::connect_to`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ConnectToError {
+    /// The target isn't of the form `host:port`
+    #[error("invalid target, expected host:port")]
+    InvalidTarget,
+    /// No resolver is configured to look up the host
+    #[error("no resolver configured")]
+    NoResolver,
+    /// The host could not be resolved
+    #[error("failed to resolve host: {0}")]
+    Resolve(String),
+    /// The host resolved to no addresses
+    #[error("host resolved to no addresses")]
+    NoAddresses,
+    /// The last connection attempt could not be initiated
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+    /// The last connection attempt failed
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+}
+
+impl From<ConnectRacingError> for ConnectToError {
+    fn from(e: ConnectRacingError) -> Self {
+        match e {
+            ConnectRacingError::NoAddresses => Self::NoAddresses,
+            ConnectRacingError::Connect(e) => Self::Connect(e),
+            ConnectRacingError::Connection(e) => Self::Connection(e),
+        }
+    }
+}
+
 fn ensure_ipv6(x: SocketAddr) -> SocketAddrV6 {
     match x {
         SocketAddr::V6(x) => x,
@@ -699,6 +1217,53 @@ fn ensure_ipv6(x: SocketAddr) -> SocketAddrV6 {
     }
 }
 
+/// Apply the options from an [`EndpointConfig`] to a socket before it's handed to the runtime
+#[cfg(not(wasm_browser))]
+fn configure_socket(socket: &std::net::UdpSocket, config: &proto::SocketConfig) -> io::Result<()> {
+    let socket = socket2::SockRef::from(socket);
+    if let Some(size) = config.get_recv_buffer_size() {
+        socket.set_recv_buffer_size(size)?;
+    }
+    if let Some(size) = config.get_send_buffer_size() {
+        socket.set_send_buffer_size(size)?;
+    }
+    if let Some(_interface) = config.get_bind_device() {
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        socket.bind_device(Some(_interface))?;
+        #[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "binding to a device is not supported on this platform",
+        ));
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if let Some(busy_poll) = config.get_busy_poll() {
+        use std::os::fd::AsRawFd;
+
+        let usecs = libc::c_int::try_from(busy_poll.as_micros()).unwrap_or(libc::c_int::MAX);
+        for (name, value) in [(libc::SO_BUSY_POLL, usecs), (libc::SO_PREFER_BUSY_POLL, 1)] {
+            let rc = unsafe {
+                libc::setsockopt(
+                    socket.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    name,
+                    &value as *const _ as _,
+                    size_of_val(&value) as _,
+                )
+            };
+            if rc != 0 {
+                // Spinning in the endpoint driver still helps without kernel support
+                tracing::warn!(
+                    "failed to enable kernel busy polling: {}",
+                    io::Error::last_os_error()
+                );
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
 pin_project! {
     /// Future produced by [`Endpoint::accept`]
     pub struct Accept<'a> {
@@ -770,6 +1335,7 @@ impl EndpointRef {
                 runtime,
                 stats: EndpointStats::default(),
                 default_client_config: None,
+                resolver: None,
             }),
         }))
     }
@@ -809,7 +1375,13 @@ struct RecvState {
     incoming: VecDeque<proto::Incoming>,
     connections: ConnectionSet,
     recv_buf: Box<[u8]>,
+    /// Storage handed to the endpoint for each received datagram
+    recv_buffers: RecvBuffers,
     recv_limiter: WorkLimiter,
+    /// How long to keep polling the socket after receiving datagrams, if busy polling
+    busy_poll: Option<Duration>,
+    /// Time until which the socket is busy polled
+    busy_poll_until: Option<Instant>,
 }
 
 impl RecvState {
@@ -829,10 +1401,20 @@ impl RecvState {
                 senders: FxHashMap::default(),
                 sender,
                 close: None,
+                send_monitor: Arc::new(SendMonitor::default()),
             },
             incoming: VecDeque::new(),
             recv_buf: recv_buf.into(),
+            recv_buffers: RecvBuffers::new(
+                endpoint
+                    .config()
+                    .get_socket_config()
+                    .get_recv_buffer_pool()
+                    .cloned(),
+            ),
             recv_limiter: WorkLimiter::new(RECV_TIME_BOUND),
+            busy_poll: endpoint.config().get_socket_config().get_busy_poll(),
+            busy_poll_until: None,
         }
     }
 
@@ -862,8 +1444,15 @@ impl RecvState {
             match socket.poll_recv(cx, &mut iovs, &mut metas) {
                 Poll::Ready(Ok(msgs)) => {
                     self.recv_limiter.record_work(msgs);
+                    if let Some(busy_poll) = self.busy_poll {
+                        self.busy_poll_until = Some(now + busy_poll);
+                    }
                     for (meta, buf) in metas.iter().zip(iovs.iter()).take(msgs) {
-                        let mut data: BytesMut = buf[0..meta.len].into();
+                        let mut data = self
+                            .recv_buffers
+                            .copy(&buf[0..meta.len], self.connections.senders.len());
+                        // Segments of a GRO batch for the same connection are handled as a unit
+                        let mut batch: Option<(ConnectionHandle, proto::ConnectionEvent)> = None;
                         while !data.is_empty() {
                             let buf = data.split_to(meta.stride.min(data.len()));
                             let mut response_buffer = Vec::new();
@@ -885,14 +1474,21 @@ impl RecvState {
                                     }
                                 }
                                 Some(DatagramEvent::ConnectionEvent(handle, event)) => {
-                                    // Ignoring errors from dropped connections that haven't yet been cleaned up
                                     received_connection_packet = true;
-                                    let _ = self
-                                        .connections
-                                        .senders
-                                        .get_mut(&handle)
-                                        .unwrap()
-                                        .send(ConnectionEvent::Proto(event));
+                                    let event = match &mut batch {
+                                        Some((batch_handle, batch_event))
+                                            if *batch_handle == handle =>
+                                        {
+                                            match batch_event.merge(event) {
+                                                Ok(()) => continue,
+                                                Err(event) => *event,
+                                            }
+                                        }
+                                        _ => event,
+                                    };
+                                    if let Some((handle, event)) = batch.replace((handle, event)) {
+                                        self.connections.send_event(handle, event);
+                                    }
                                 }
                                 Some(DatagramEvent::Response(transmit)) => {
                                     respond(transmit, &response_buffer, sender);
@@ -900,12 +1496,18 @@ impl RecvState {
                                 None => {}
                             }
                         }
+                        if let Some((handle, event)) = batch {
+                            self.connections.send_event(handle, event);
+                        }
                     }
                 }
                 Poll::Pending => {
                     return Ok(PollProgress {
                         received_connection_packet,
-                        keep_going: false,
+                        // Spin rather than waiting for a wakeup while busy polling
+                        keep_going: self
+                            .busy_poll_until
+                            .is_some_and(|until| runtime.now() < until),
                     });
                 }
                 // Ignore ECONNRESET as it's undefined in QUIC and may be injected by an
@@ -942,6 +1544,7 @@ impl fmt::Debug for RecvState {
 struct PollProgress {
     /// Whether a datagram was routed to an existing connection
     received_connection_packet: bool,
-    /// Whether datagram handling was interrupted early by the work limiter for fairness
+    /// Whether datagram handling was interrupted early by the work limiter for fairness, or the
+    /// socket is being busy polled
     keep_going: bool,
 }
@@ -6,7 +6,7 @@ use std::{
     task::{Context, Poll},
 };
 
-use proto::{ConnectionError, ConnectionId, ServerConfig};
+use proto::{ConnectionError, ConnectionId, ServerConfig, VarInt};
 use thiserror::Error;
 
 use crate::{
@@ -46,6 +46,21 @@ impl Incoming {
         state.endpoint.refuse(state.inner);
     }
 
+    /// Reject this incoming connection attempt with an application error code and reason
+    ///
+    /// Unlike [`refuse()`](Self::refuse), the client's connection fails with
+    /// [`ConnectionError::ApplicationClosed`] carrying `error_code` and `reason`, e.g. to explain
+    /// why it was turned away. This requires sending the server's part of the handshake first, so
+    /// that the client can decrypt them.
+    pub fn refuse_with(self, error_code: VarInt, reason: &[u8]) {
+        if let Ok(connecting) = self.accept() {
+            // Always succeeds for incoming connections
+            if let Ok(conn) = connecting.into_0rtt() {
+                conn.close(error_code, reason);
+            }
+        }
+    }
+
     /// Respond with a retry packet, requiring the client to retry with address validation
     ///
     /// Errors if `may_retry()` is false.
@@ -41,30 +41,50 @@
 
 use std::pin::Pin;
 
+#[cfg(all(not(wasm_browser), feature = "runtime-tokio"))]
+pub mod blocking;
 mod connection;
 mod endpoint;
 mod incoming;
+mod message;
+#[cfg(feature = "metrics")]
+mod metrics;
 mod mutex;
+mod pool;
+mod proxy;
+mod recv_buffers;
 mod recv_stream;
+mod resilient;
+mod resolver;
+mod rpc;
 mod runtime;
 mod send_stream;
+mod tap;
 mod work_limiter;
 
 #[cfg(not(wasm_browser))]
-pub(crate) use std::time::{Duration, Instant};
+pub(crate) use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 #[cfg(wasm_browser)]
-pub(crate) use web_time::{Duration, Instant};
+pub(crate) use web_time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[cfg(feature = "bloom")]
 pub use proto::BloomTokenLog;
 pub use proto::{
-    AckFrequencyConfig, ApplicationClose, Chunk, ClientConfig, ClosedStream, ConfigError,
-    ConnectError, ConnectionClose, ConnectionError, ConnectionId, ConnectionIdGenerator,
-    ConnectionStats, Dir, EcnCodepoint, EndpointConfig, FrameStats, FrameType, IdleTimeout,
-    InvalidCid, MtuDiscoveryConfig, NoneTokenLog, NoneTokenStore, PathStats, ServerConfig, Side,
-    StdSystemTime, StreamId, TimeSource, TokenLog, TokenMemoryCache, TokenReuseError, TokenStore,
-    Transmit, TransportConfig, TransportErrorCode, UdpStats, ValidationTokenConfig, VarInt,
-    VarIntBoundsExceeded, Written, congestion, crypto,
+    AckFrequencyConfig, AckPolicyConfig, AdaptiveKeepAliveConfig, ApplicationClose,
+    BandwidthEstimate, Chunk, CidRotationConfig, ClientConfig, ClosedStream, ConfigError,
+    CongestionDebugState, ConnectError, ConnectionClose, ConnectionDebugState, ConnectionError,
+    ConnectionId, ConnectionIdGenerator, ConnectionIdState, ConnectionStats, DatagramClassifier,
+    DatagramFlowStats, DatagramSizeHistogram, Dir, EcnCodepoint, EndpointConfig, Extension,
+    FlowControlDebugState, FrameStats, FrameType, GreaseConfig, HandshakeTimings, IdleTimeout,
+    InvalidCid, LossTrigger, MtuDiscoveryConfig, MtuProbe, MtuProbeOutcome, NoneTokenLog,
+    NoneTokenStore, PaddingPolicy, PathStats, PeerLimitsConfig, PeerTransportParameters,
+    PortHoppingConfig, RecvBufferPoolConfig, RecvStreamDebugState, RttEstimatorConfig,
+    RttHistogram, SendBlockReason, SendFlowControl, SendStreamDebugState, ServerConfig, Side,
+    SocketConfig, SpaceDebugState, SpaceId, StdSystemTime, StreamDebugState, StreamGroupStats,
+    StreamId, StreamSchedulerStats, TimeSource, TimeoutCause, TimeoutTimer, TokenLog,
+    TokenMemoryCache, TokenReuseError, TokenStore, Transmit, TransmitInfo, TransmitKind,
+    TransmitMarker, TransmitMarking, TransportConfig, TransportErrorCode, TransportEvent, UdpStats,
+    ValidationTokenConfig, VarInt, VarIntBoundsExceeded, Written, congestion, crypto,
 };
 #[cfg(feature = "qlog")]
 pub use proto::{QlogConfig, QlogStream};
@@ -73,20 +93,38 @@ pub use rustls;
 pub use udp;
 
 pub use crate::connection::{
-    AcceptBi, AcceptUni, Connecting, Connection, OpenBi, OpenUni, ReadDatagram, SendDatagram,
-    SendDatagramError,
+    AcceptBi, AcceptUni, Connecting, Connection, ConnectionEvents, OpenBi, OpenUni, ReadDatagram,
+    ReadFlowDatagram, ReadFlowDatagramError, SendDatagram, SendDatagramError, SendReady,
+    ValidatePathError,
+};
+pub use crate::endpoint::{
+    Accept, ConnectRacingError, ConnectToError, Endpoint, EndpointStats, PriorityClass, SendBlocked,
 };
-pub use crate::endpoint::{Accept, Endpoint, EndpointStats};
 pub use crate::incoming::{Incoming, IncomingFuture, RetryError};
+pub use crate::message::{MessageError, MessageReceiver, MessageSender, MessageStream};
+#[cfg(feature = "metrics")]
+pub use crate::metrics::MetricsRegistry;
+pub use crate::pool::{ConnectionPool, PoolConfig, PoolError, PooledConnection};
+#[cfg(feature = "runtime-tokio")]
+pub use crate::proxy::Socks5Tunnel;
+pub use crate::proxy::{TunnelSocket, UdpTunnel};
 pub use crate::recv_stream::{ReadError, ReadExactError, ReadToEndError, RecvStream, ResetError};
+pub use crate::resilient::{ReconnectConfig, ReconnectError, ResilientConnection};
+#[cfg(feature = "runtime-tokio")]
+pub use crate::resolver::TokioResolver;
+pub use crate::resolver::{Resolver, ServiceRecord};
+pub use crate::rpc::{RpcClient, RpcError, RpcServer};
 #[cfg(feature = "runtime-smol")]
 pub use crate::runtime::SmolRuntime;
 #[cfg(feature = "runtime-tokio")]
 pub use crate::runtime::TokioRuntime;
 #[cfg(any(feature = "runtime-tokio", feature = "runtime-smol"))]
 pub use crate::runtime::default_runtime;
-pub use crate::runtime::{AsyncTimer, AsyncUdpSocket, Runtime, UdpSender};
+pub use crate::runtime::{
+    AsyncTimer, AsyncUdpSocket, LinkConfig, ManualDriver, MemorySocket, Runtime, UdpSender,
+};
 pub use crate::send_stream::{SendStream, StoppedError, WriteError};
+pub use crate::tap::{PacketTap, PcapWriter, TapSocket, TappedDatagram};
 
 #[cfg(test)]
 mod tests;
@@ -102,13 +140,12 @@ enum ConnectionEvent {
 }
 
 fn udp_transmit<'a>(t: &Transmit, buffer: &'a [u8]) -> udp::Transmit<'a> {
-    udp::Transmit {
-        destination: t.destination,
-        ecn: t.ecn.map(udp_ecn),
-        contents: buffer,
-        segment_size: t.segment_size,
-        src_ip: t.src_ip,
-    }
+    udp::Transmit::new(t.destination, buffer)
+        .with_ecn(t.ecn.map(udp_ecn))
+        .with_dscp(t.dscp)
+        .with_flow_label(t.flow_label)
+        .with_segment_size(t.segment_size)
+        .with_src_ip(t.src_ip)
 }
 
 fn udp_ecn(ecn: EcnCodepoint) -> udp::EcnCodepoint {
@@ -0,0 +1,259 @@
+use bytes::{Bytes, BytesMut};
+use proto::coding::Codec;
+use thiserror::Error;
+
+use crate::{
+    VarInt,
+    recv_stream::{ReadError, ReadExactError, RecvStream},
+    send_stream::{SendStream, WriteError},
+};
+
+/// Largest message accepted by default, see [`MessageReceiver::set_max_message_size`]
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// A bidirectional stream carrying discrete messages
+///
+/// Each message is prefixed with its length, encoded as a QUIC variable-length integer. Use
+/// [`split`](Self::split) to send and receive from different tasks, or [`MessageSender`] and
+/// [`MessageReceiver`] directly for unidirectional streams.
+#[derive(Debug)]
+pub struct MessageStream {
+    sender: MessageSender,
+    receiver: MessageReceiver,
+}
+
+impl MessageStream {
+    /// Frame messages on the two halves of a bidirectional stream
+    pub fn new(send: SendStream, recv: RecvStream) -> Self {
+        Self {
+            sender: MessageSender::new(send),
+            receiver: MessageReceiver::new(recv),
+        }
+    }
+
+    /// Maximum size of messages that may be sent or received
+    ///
+    /// Defaults to 64 KiB.
+    pub fn set_max_message_size(&mut self, value: usize) {
+        self.sender.set_max_message_size(value);
+        self.receiver.set_max_message_size(value);
+    }
+
+    /// Send a message, see [`MessageSender::send`]
+    pub async fn send(&mut self, message: &[u8]) -> Result<(), MessageError> {
+        self.sender.send(message).await
+    }
+
+    /// Receive a message, see [`MessageReceiver::recv`]
+    pub async fn recv(&mut self) -> Result<Option<Bytes>, MessageError> {
+        self.receiver.recv().await
+    }
+
+    /// Serialize and send a value, see [`MessageSender::send_json`]
+    #[cfg(feature = "serde")]
+    pub async fn send_json<T: serde::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), MessageError> {
+        self.sender.send_json(value).await
+    }
+
+    /// Receive and deserialize a value, see [`MessageReceiver::recv_json`]
+    #[cfg(feature = "serde")]
+    pub async fn recv_json<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<T>, MessageError> {
+        self.receiver.recv_json().await
+    }
+
+    /// Separate the sending and receiving halves
+    pub fn split(self) -> (MessageSender, MessageReceiver) {
+        (self.sender, self.receiver)
+    }
+}
+
+/// Sends length-prefixed messages on a [`SendStream`]
+#[derive(Debug)]
+pub struct MessageSender {
+    stream: SendStream,
+    max_size: usize,
+    prefix: Vec<u8>,
+}
+
+impl MessageSender {
+    /// Frame messages sent on `stream`
+    pub fn new(stream: SendStream) -> Self {
+        Self {
+            stream,
+            max_size: DEFAULT_MAX_MESSAGE_SIZE,
+            prefix: Vec::with_capacity(8),
+        }
+    }
+
+    /// Maximum size of messages that may be sent
+    ///
+    /// Sending larger messages fails with [`MessageError::TooLarge`] without writing anything to
+    /// the stream. Defaults to 64 KiB, which should match the limit of the receiver.
+    pub fn set_max_message_size(&mut self, value: usize) {
+        self.max_size = value;
+    }
+
+    /// Send a message
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is *not* cancellation safe. Cancelling it may leave a partial message on the
+    /// stream, after which the stream can no longer be used.
+    pub async fn send(&mut self, message: &[u8]) -> Result<(), MessageError> {
+        if message.len() > self.max_size {
+            return Err(MessageError::TooLarge(message.len() as u64));
+        }
+        self.prefix.clear();
+        VarInt::try_from(message.len())
+            .map_err(|_| MessageError::TooLarge(message.len() as u64))?
+            .encode(&mut self.prefix);
+        self.stream.write_all(&self.prefix).await?;
+        self.stream.write_all(message).await?;
+        Ok(())
+    }
+
+    /// Serialize `value` as JSON and send it as a message
+    #[cfg(feature = "serde")]
+    pub async fn send_json<T: serde::Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), MessageError> {
+        let message = serde_json::to_vec(value).map_err(|e| MessageError::Json(e.to_string()))?;
+        self.send(&message).await
+    }
+
+    /// Notify the peer that no more messages will be sent, see [`SendStream::finish`]
+    pub fn finish(&mut self) -> Result<(), proto::ClosedStream> {
+        self.stream.finish()
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &SendStream {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream
+    pub fn get_mut(&mut self) -> &mut SendStream {
+        &mut self.stream
+    }
+
+    /// Consume the sender, returning the underlying stream
+    pub fn into_inner(self) -> SendStream {
+        self.stream
+    }
+}
+
+/// Receives length-prefixed messages from a [`RecvStream`]
+#[derive(Debug)]
+pub struct MessageReceiver {
+    stream: RecvStream,
+    max_size: usize,
+}
+
+impl MessageReceiver {
+    /// Frame messages received from `stream`
+    pub fn new(stream: RecvStream) -> Self {
+        Self {
+            stream,
+            max_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Maximum size of messages that may be received
+    ///
+    /// Protects against peers announcing messages too large to buffer. Receiving a larger message
+    /// fails with [`MessageError::TooLarge`]. Defaults to 64 KiB.
+    pub fn set_max_message_size(&mut self, value: usize) {
+        self.max_size = value;
+    }
+
+    /// Receive a message
+    ///
+    /// Yields `None` if the stream was finished after the previous message.
+    ///
+    /// # Cancel safety
+    ///
+    /// This method is *not* cancellation safe. Cancelling it may lose part of a message, after
+    /// which the stream can no longer be used.
+    pub async fn recv(&mut self) -> Result<Option<Bytes>, MessageError> {
+        let mut prefix = [0; 8];
+        match self.stream.read_exact(&mut prefix[..1]).await {
+            Ok(()) => {}
+            Err(ReadExactError::FinishedEarly(_)) => return Ok(None),
+            Err(ReadExactError::ReadError(e)) => return Err(e.into()),
+        }
+        let prefix_len = 1 << (prefix[0] >> 6);
+        self.read_exact(&mut prefix[1..prefix_len]).await?;
+        let len = VarInt::decode(&mut &prefix[..prefix_len])
+            .expect("prefix length matches encoding")
+            .into_inner();
+        if len > self.max_size as u64 {
+            return Err(MessageError::TooLarge(len));
+        }
+
+        let mut message = BytesMut::zeroed(len as usize);
+        self.read_exact(&mut message).await?;
+        Ok(Some(message.freeze()))
+    }
+
+    /// Receive a message and deserialize it from JSON
+    #[cfg(feature = "serde")]
+    pub async fn recv_json<T: serde::de::DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<T>, MessageError> {
+        let Some(message) = self.recv().await? else {
+            return Ok(None);
+        };
+        serde_json::from_slice(&message)
+            .map(Some)
+            .map_err(|e| MessageError::Json(e.to_string()))
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &RecvStream {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream
+    pub fn get_mut(&mut self) -> &mut RecvStream {
+        &mut self.stream
+    }
+
+    /// Consume the receiver, returning the underlying stream
+    pub fn into_inner(self) -> RecvStream {
+        self.stream
+    }
+
+    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), MessageError> {
+        self.stream.read_exact(buf).await.map_err(|e| match e {
+            ReadExactError::FinishedEarly(_) => MessageError::Truncated,
+            ReadExactError::ReadError(e) => e.into(),
+        })
+    }
+}
+
+/// Errors that arise from sending or receiving messages
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum MessageError {
+    /// The message exceeds the maximum message size
+    #[error("message of {0} bytes exceeds the size limit")]
+    TooLarge(u64),
+    /// The stream finished in the middle of a message
+    #[error("stream finished in the middle of a message")]
+    Truncated,
+    /// The message could not be serialized or deserialized
+    #[cfg(feature = "serde")]
+    #[error("invalid JSON message: {0}")]
+    Json(String),
+    /// Reading from the stream failed
+    #[error(transparent)]
+    Read(#[from] ReadError),
+    /// Writing to the stream failed
+    #[error(transparent)]
+    Write(#[from] WriteError),
+}
@@ -0,0 +1,215 @@
+use std::sync::Mutex;
+
+use ::metrics::{counter, describe_counter, describe_gauge, gauge};
+use proto::ConnectionStats;
+
+use crate::{
+    connection::{Connection, WeakConnection},
+    endpoint::Endpoint,
+};
+
+/// Publishes statistics of endpoints and connections through the [`metrics`](::metrics) facade
+///
+/// Register endpoints and connections as they are created, and call [`record()`](Self::record)
+/// periodically, e.g. before each scrape, to hand their current statistics to the installed
+/// recorder, such as a Prometheus exporter. Statistics are only read when recording, so
+/// registration has no ongoing cost, and registered connections are not kept open.
+///
+/// Connection counters are summed over all registered connections, in the
+/// `quinn_connections_*` families. [Per-connection labels](Self::set_per_connection_labels)
+/// additionally publish each open connection in the separate `quinn_connection_*` families.
+/// Closed connections are removed when recording, with their final counts retained in the sums.
+#[derive(Debug, Default)]
+pub struct MetricsRegistry {
+    per_connection_labels: bool,
+    state: Mutex<State>,
+}
+
+impl MetricsRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to additionally publish the metrics of each open connection, labeled with its
+    /// [`stable_id`](Connection::stable_id) and peer address
+    ///
+    /// Includes gauges such as RTT and congestion window, which can't be summed meaningfully.
+    /// Creates series for every connection, so only suitable for small numbers of connections.
+    /// Series of closed connections are no longer updated, and are left to the recorder to expire.
+    /// Defaults to `false`.
+    pub fn set_per_connection_labels(&mut self, value: bool) {
+        self.per_connection_labels = value;
+    }
+
+    /// Publish the statistics of `endpoint`, labeled with `name`
+    pub fn register_endpoint(&self, name: &str, endpoint: Endpoint) {
+        let mut state = self.state.lock().unwrap();
+        state.endpoints.push((name.into(), endpoint));
+    }
+
+    /// Publish the statistics of `conn`
+    ///
+    /// The registry doesn't keep the connection open once all other handles are dropped.
+    pub fn register_connection(&self, conn: &Connection) {
+        let conn = Registered {
+            conn: WeakConnection::new(conn),
+            last: conn.stats(),
+        };
+        self.state.lock().unwrap().connections.push(conn);
+    }
+
+    /// Publish the current statistics of all registered endpoints and connections to the
+    /// recorder
+    pub fn record(&self) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        describe();
+        record_endpoints(&state.endpoints);
+
+        let mut totals = state.closed;
+        let per_connection_labels = self.per_connection_labels;
+        state.connections.retain_mut(|registered| {
+            let Some((stats, remote, closed)) = registered.conn.stats() else {
+                // Dropped before it was seen closed, so the last counts are the final ones
+                state.closed.add(&registered.last);
+                totals.add(&registered.last);
+                return false;
+            };
+            totals.add(&stats);
+            if closed {
+                state.closed.add(&stats);
+                return false;
+            }
+            if per_connection_labels {
+                record_connection(registered.conn.stable_id(), remote.to_string(), &stats);
+            }
+            registered.last = stats;
+            true
+        });
+
+        for ((name, ..), total) in COUNTERS.into_iter().zip(totals.0) {
+            counter!(format!("quinn_connections_{name}")).absolute(total);
+        }
+    }
+}
+
+fn describe() {
+    describe_gauge!("quinn_endpoint_open_connections", "Open connections");
+    describe_counter!("quinn_endpoint_handshakes", "QUIC handshakes by outcome");
+    describe_counter!(
+        "quinn_endpoint_send_blocked",
+        "Stalls waiting for the UDP socket"
+    );
+    describe_counter!(
+        "quinn_endpoint_send_blocked_milliseconds",
+        "Time spent waiting for the UDP socket"
+    );
+    describe_counter!(
+        "quinn_endpoint_socket_drops",
+        "Transmits the OS did not accept"
+    );
+    for (name, help, _) in COUNTERS {
+        describe_counter!(format!("quinn_connections_{name}"), help);
+        describe_counter!(format!("quinn_connection_{name}"), help);
+    }
+    describe_gauge!("quinn_connection_rtt_seconds", "Smoothed round-trip time");
+    describe_gauge!("quinn_connection_cwnd_bytes", "Congestion window");
+}
+
+fn record_endpoints(endpoints: &[(String, Endpoint)]) {
+    for (name, endpoint) in endpoints {
+        let stats = endpoint.stats();
+        let labels = [("endpoint", name.clone())];
+        let [label] = &labels;
+        gauge!("quinn_endpoint_open_connections", &labels).set(endpoint.open_connections() as f64);
+        for (outcome, value) in [
+            ("accepted", stats.accepted_handshakes),
+            ("outgoing", stats.outgoing_handshakes),
+            ("refused", stats.refused_handshakes),
+            ("ignored", stats.ignored_handshakes),
+        ] {
+            counter!(
+                "quinn_endpoint_handshakes",
+                &[label.clone(), ("outcome", outcome.into())]
+            )
+            .absolute(value);
+        }
+        counter!("quinn_endpoint_send_blocked", &labels).absolute(stats.send_blocked);
+        // Counters are integers, so time is published in milliseconds rather than seconds
+        counter!("quinn_endpoint_send_blocked_milliseconds", &labels)
+            .absolute(stats.send_blocked_time.as_millis() as u64);
+        for (reason, value) in [
+            ("would_block", stats.socket_send.would_block),
+            ("no_buffers", stats.socket_send.no_buffers),
+            ("too_big", stats.socket_send.too_big),
+        ] {
+            counter!(
+                "quinn_endpoint_socket_drops",
+                &[label.clone(), ("reason", reason.into())]
+            )
+            .absolute(value);
+        }
+    }
+}
+
+fn record_connection(id: usize, remote: String, stats: &ConnectionStats) {
+    let labels = [("connection", id.to_string()), ("remote", remote)];
+    for (name, _, value) in COUNTERS {
+        counter!(format!("quinn_connection_{name}"), &labels).absolute(value(stats));
+    }
+    gauge!("quinn_connection_rtt_seconds", &labels).set(stats.path.rtt.as_secs_f64());
+    gauge!("quinn_connection_cwnd_bytes", &labels).set(stats.path.cwnd as f64);
+}
+
+#[derive(Debug, Default)]
+struct State {
+    endpoints: Vec<(String, Endpoint)>,
+    connections: Vec<Registered>,
+    /// Counts of connections that were removed after being closed
+    closed: Totals,
+}
+
+#[derive(Debug)]
+struct Registered {
+    conn: WeakConnection,
+    /// Statistics as of the last time the connection was recorded
+    last: ConnectionStats,
+}
+
+/// Sums of the [`COUNTERS`] of many connections
+#[derive(Debug, Default, Copy, Clone)]
+struct Totals([u64; COUNTER_COUNT]);
+
+impl Totals {
+    fn add(&mut self, stats: &ConnectionStats) {
+        for (total, (.., value)) in self.0.iter_mut().zip(COUNTERS) {
+            *total += value(stats);
+        }
+    }
+}
+
+type Counter = (&'static str, &'static str, fn(&ConnectionStats) -> u64);
+
+const COUNTER_COUNT: usize = 7;
+
+/// Connection counters: name suffix, description and source
+const COUNTERS: [Counter; COUNTER_COUNT] = [
+    ("sent_bytes", "UDP payload bytes sent", |s| s.udp_tx.bytes),
+    ("received_bytes", "UDP payload bytes received", |s| {
+        s.udp_rx.bytes
+    }),
+    ("sent_datagrams", "UDP datagrams sent", |s| {
+        s.udp_tx.datagrams
+    }),
+    ("received_datagrams", "UDP datagrams received", |s| {
+        s.udp_rx.datagrams
+    }),
+    ("lost_packets", "Packets declared lost", |s| {
+        s.path.lost_packets
+    }),
+    ("lost_bytes", "Bytes declared lost", |s| s.path.lost_bytes),
+    ("congestion_events", "Congestion events", |s| {
+        s.path.congestion_events
+    }),
+];
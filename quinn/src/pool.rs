@@ -0,0 +1,262 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use proto::{ClientConfig, ConnectError, ConnectionError};
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::{Duration, Instant, connection::Connection, endpoint::Endpoint, runtime::Runtime};
+
+/// Configuration of a [`ConnectionPool`]
+#[derive(Debug, Clone, Default)]
+pub struct PoolConfig {
+    client_configs: HashMap<Vec<u8>, ClientConfig>,
+    max_idle: Option<Duration>,
+    max_concurrent_per_host: Option<usize>,
+}
+
+impl PoolConfig {
+    /// Client configuration used for connections negotiating the application protocol `alpn`
+    ///
+    /// `config` must offer `alpn` during the handshake. Connections can only be requested for
+    /// protocols that were configured this way.
+    pub fn client_config(&mut self, alpn: impl Into<Vec<u8>>, config: ClientConfig) -> &mut Self {
+        self.client_configs.insert(alpn.into(), config);
+        self
+    }
+
+    /// Time after which a connection that isn't in use is closed and removed from the pool
+    ///
+    /// Idle connections are evicted whenever a connection is requested, or when
+    /// [`ConnectionPool::evict_idle`] is called. Defaults to `None`, in which case connections are
+    /// kept until they are closed, e.g. by their idle timeout.
+    pub fn max_idle(&mut self, value: Option<Duration>) -> &mut Self {
+        self.max_idle = value;
+        self
+    }
+
+    /// Maximum number of [`PooledConnection`]s to the same host that may be held at a time
+    ///
+    /// Further requests wait until one is dropped. Defaults to `None`, i.e. unlimited.
+    pub fn max_concurrent_per_host(&mut self, value: Option<usize>) -> &mut Self {
+        self.max_concurrent_per_host = value;
+        self
+    }
+}
+
+/// Identity of the server a pooled connection leads to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PoolKey {
+    server_name: String,
+    port: u16,
+    alpn: Vec<u8>,
+}
+
+/// Shares established client connections between users of the same server
+///
+/// Connections are keyed by the server's host name, port and application protocol (ALPN), so that
+/// requests for the same server reuse a single connection as long as it remains open. Concurrent
+/// requests for a server without an open connection wait for a single connection attempt rather
+/// than racing to establish several.
+#[derive(Debug, Clone)]
+pub struct ConnectionPool {
+    endpoint: Endpoint,
+    config: Arc<PoolConfig>,
+    hosts: Arc<Mutex<HashMap<PoolKey, Arc<Host>>>>,
+}
+
+impl ConnectionPool {
+    /// Create a pool of connections made by `endpoint`
+    pub fn new(endpoint: Endpoint, config: PoolConfig) -> Self {
+        Self {
+            endpoint,
+            config: Arc::new(config),
+            hosts: Arc::default(),
+        }
+    }
+
+    /// Get a connection to the server `server_name`, speaking `alpn`
+    ///
+    /// Reuses an open connection if the pool holds one, and otherwise connects to `addr`.
+    pub async fn get(
+        &self,
+        addr: SocketAddr,
+        server_name: &str,
+        alpn: &[u8],
+    ) -> Result<PooledConnection, PoolError> {
+        let config = self
+            .config
+            .client_configs
+            .get(alpn)
+            .ok_or(PoolError::UnknownProtocol)?;
+        self.evict_idle();
+        let key = PoolKey {
+            server_name: server_name.into(),
+            port: addr.port(),
+            alpn: alpn.into(),
+        };
+        let lease = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let host = hosts.entry(key).or_insert_with(|| {
+                Arc::new(Host {
+                    permits: self
+                        .config
+                        .max_concurrent_per_host
+                        .map(|n| Arc::new(Semaphore::new(n))),
+                    conn: tokio::sync::Mutex::new(None),
+                    usage: Mutex::new(Usage {
+                        leases: 0,
+                        last_used: self.endpoint.runtime.now(),
+                    }),
+                })
+            });
+            Lease::new(host.clone(), self.endpoint.runtime.clone())
+        };
+
+        let permit = match &lease.host.permits {
+            Some(permits) => Some(
+                permits
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("semaphore is never closed"),
+            ),
+            None => None,
+        };
+
+        let conn = {
+            let mut conn = lease.host.conn.lock().await;
+            match &*conn {
+                Some(existing) if existing.close_reason().is_none() => existing.clone(),
+                _ => {
+                    let new = self
+                        .endpoint
+                        .connect_with(config.clone(), addr, server_name)?
+                        .await?;
+                    conn.insert(new).clone()
+                }
+            }
+        };
+        Ok(PooledConnection {
+            conn,
+            _lease: lease,
+            _permit: permit,
+        })
+    }
+
+    /// Close and remove connections that have been idle for longer than
+    /// [`PoolConfig::max_idle`], as well as connections that were closed
+    pub fn evict_idle(&self) {
+        let now = self.endpoint.runtime.now();
+        let max_idle = self.config.max_idle;
+        self.hosts.lock().unwrap().retain(|_, host| {
+            let usage = host.usage.lock().unwrap();
+            if usage.leases > 0 {
+                return true;
+            }
+            // No leases means nobody is connecting, so the lock is free
+            let Ok(mut conn) = host.conn.try_lock() else {
+                return true;
+            };
+            let Some(existing) = &*conn else {
+                return false;
+            };
+            if existing.close_reason().is_some() {
+                return false;
+            }
+            if max_idle
+                .is_some_and(|max_idle| now.saturating_duration_since(usage.last_used) >= max_idle)
+            {
+                existing.close(0u32.into(), b"idle");
+                *conn = None;
+                return false;
+            }
+            true
+        });
+    }
+
+    /// Number of servers the pool currently tracks
+    pub fn len(&self) -> usize {
+        self.hosts.lock().unwrap().len()
+    }
+
+    /// Whether the pool currently tracks no servers
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A connection borrowed from a [`ConnectionPool`]
+///
+/// The connection remains open when this is dropped, so that it can be reused. Closing it
+/// explicitly affects all other users of the same connection.
+#[derive(Debug)]
+pub struct PooledConnection {
+    conn: Connection,
+    _lease: Lease,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+    fn deref(&self) -> &Connection {
+        &self.conn
+    }
+}
+
+#[derive(Debug)]
+struct Host {
+    /// Enforces [`PoolConfig::max_concurrent_per_host`]
+    permits: Option<Arc<Semaphore>>,
+    /// Held while connecting, so that concurrent requests share the attempt
+    conn: tokio::sync::Mutex<Option<Connection>>,
+    usage: Mutex<Usage>,
+}
+
+#[derive(Debug)]
+struct Usage {
+    /// Number of requests in progress and connections handed out
+    leases: usize,
+    last_used: Instant,
+}
+
+/// Marks a [`Host`] as in use
+#[derive(Debug)]
+struct Lease {
+    host: Arc<Host>,
+    /// Clock to record the time of last use with
+    runtime: Arc<dyn Runtime>,
+}
+
+impl Lease {
+    fn new(host: Arc<Host>, runtime: Arc<dyn Runtime>) -> Self {
+        host.usage.lock().unwrap().leases += 1;
+        Self { host, runtime }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        let mut usage = self.host.usage.lock().unwrap();
+        usage.leases -= 1;
+        usage.last_used = self.runtime.now();
+    }
+}
+
+/// Errors that arise from requesting a connection from a [`ConnectionPool`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum PoolError {
+    /// No client configuration was registered for the requested application protocol
+    #[error("no client configuration for the requested protocol")]
+    UnknownProtocol,
+    /// The connection could not be initiated
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+    /// The connection could not be established
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+}
@@ -0,0 +1,109 @@
+use std::{
+    fmt, io,
+    io::IoSliceMut,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+};
+
+use udp::{RecvMeta, Transmit};
+
+use crate::runtime::{AsyncUdpSocket, UdpSender};
+
+/// A datagram transport that relays UDP payloads through a proxy
+///
+/// Implement this to run an endpoint over a proxy protocol, such as a SOCKS5 UDP association (see
+/// [`Socks5Tunnel`]) or an HTTP CONNECT-UDP request as specified by RFC 9298, and wrap it in a
+/// [`TunnelSocket`]. The tunnel is responsible for framing; the endpoint only sees the datagrams
+/// exchanged with its peers.
+pub trait UdpTunnel: Send + Sync + fmt::Debug + 'static {
+    /// Send `payload` to `destination` through the proxy
+    ///
+    /// Must not block. Datagrams that can't be sent immediately, e.g. because the tunnel is
+    /// congested, should be dropped; like any other loss, they are recovered by the connection.
+    fn send(&self, destination: SocketAddr, payload: &[u8]) -> io::Result<()>;
+
+    /// Receive a datagram into `buf`, or register to be woken once one is available
+    ///
+    /// Returns the length of the payload and the address of the peer that sent it. Only called
+    /// from the task driving the endpoint.
+    fn poll_recv(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>>;
+
+    /// Address reported as the local address of the endpoint
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+/// An [`AsyncUdpSocket`] that exchanges datagrams through a [`UdpTunnel`]
+///
+/// Pass this to [`Endpoint::new_with_abstract_socket`](crate::Endpoint::new_with_abstract_socket)
+/// to operate from networks that only permit traffic through a proxy, without changes to the rest
+/// of the application. Datagrams aren't batched, and ECN is unavailable.
+#[derive(Debug)]
+pub struct TunnelSocket {
+    tunnel: Arc<dyn UdpTunnel>,
+}
+
+impl TunnelSocket {
+    /// Send and receive all datagrams through `tunnel`
+    pub fn new(tunnel: Arc<dyn UdpTunnel>) -> Self {
+        Self { tunnel }
+    }
+}
+
+impl AsyncUdpSocket for TunnelSocket {
+    fn create_sender(&self) -> Pin<Box<dyn UdpSender>> {
+        Box::pin(TunnelSender {
+            tunnel: self.tunnel.clone(),
+        })
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let (Some(buf), Some(meta)) = (bufs.first_mut(), meta.first_mut()) else {
+            return Poll::Ready(Ok(0));
+        };
+        let (len, source) = ready!(self.tunnel.poll_recv(cx, buf))?;
+        *meta = RecvMeta::default();
+        meta.addr = source;
+        meta.len = len;
+        meta.stride = len;
+        Poll::Ready(Ok(1))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.tunnel.local_addr()
+    }
+}
+
+#[derive(Debug)]
+struct TunnelSender {
+    tunnel: Arc<dyn UdpTunnel>,
+}
+
+impl UdpSender for TunnelSender {
+    fn poll_send(
+        self: Pin<&mut Self>,
+        transmit: &Transmit<'_>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let segment_size = transmit.segment_size.unwrap_or(transmit.contents.len());
+        for payload in transmit.contents.chunks(segment_size.max(1)) {
+            self.tunnel.send(transmit.destination, payload)?;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "runtime-tokio")]
+mod socks5;
+#[cfg(feature = "runtime-tokio")]
+pub use socks5::Socks5Tunnel;
@@ -0,0 +1,266 @@
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    task::{Context, Poll, ready},
+};
+
+use super::UdpTunnel;
+
+/// A [`UdpTunnel`] through a SOCKS5 proxy's UDP association, as specified by RFC 1928
+///
+/// The association lasts as long as the control connection to the proxy, which is held open until
+/// the tunnel is dropped. Fragmented datagrams and datagrams from peers identified by a domain name
+/// are discarded.
+#[derive(Debug)]
+pub struct Socks5Tunnel {
+    _control: tokio::net::TcpStream,
+    socket: tokio::net::UdpSocket,
+    /// Address of the proxy's UDP relay
+    relay: SocketAddr,
+}
+
+impl Socks5Tunnel {
+    /// Establish a UDP association with the SOCKS5 proxy at `proxy`
+    ///
+    /// `credentials` are a username and password used if the proxy requires authentication, as
+    /// specified by RFC 1929.
+    pub async fn connect(proxy: SocketAddr, credentials: Option<(&str, &str)>) -> io::Result<Self> {
+        let control = tokio::net::TcpStream::connect(proxy).await?;
+
+        let methods: &[u8] = match credentials {
+            Some(_) => &[AUTH_NONE, AUTH_PASSWORD],
+            None => &[AUTH_NONE],
+        };
+        let mut greeting = vec![SOCKS_VERSION, methods.len() as u8];
+        greeting.extend_from_slice(methods);
+        write_all(&control, &greeting).await?;
+        let mut reply = [0; 2];
+        read_exact(&control, &mut reply).await?;
+        match (reply, credentials) {
+            ([SOCKS_VERSION, AUTH_NONE], _) => {}
+            ([SOCKS_VERSION, AUTH_PASSWORD], Some((username, password))) => {
+                let (username, password) = (username.as_bytes(), password.as_bytes());
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(invalid_input("SOCKS5 credentials too long"));
+                }
+                let mut request = vec![1, username.len() as u8];
+                request.extend_from_slice(username);
+                request.push(password.len() as u8);
+                request.extend_from_slice(password);
+                write_all(&control, &request).await?;
+                read_exact(&control, &mut reply).await?;
+                if reply[1] != 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        "SOCKS5 authentication failed",
+                    ));
+                }
+            }
+            _ => return Err(unsupported("no acceptable SOCKS5 authentication method")),
+        }
+
+        let unspecified = match proxy {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = tokio::net::UdpSocket::bind(unspecified).await?;
+        // The address datagrams will be sent from isn't known in general, e.g. behind NAT
+        let mut request = vec![SOCKS_VERSION, CMD_UDP_ASSOCIATE, 0];
+        encode_addr(unspecified, &mut request);
+        write_all(&control, &request).await?;
+
+        let mut reply = [0; 4];
+        read_exact(&control, &mut reply).await?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(invalid_data("invalid SOCKS5 reply"));
+        }
+        if reply[1] != 0 {
+            return Err(io::Error::other(format!(
+                "SOCKS5 UDP association rejected with code {}",
+                reply[1]
+            )));
+        }
+        let addr_len = match reply[3] {
+            ATYP_IPV4 => 4,
+            ATYP_IPV6 => 16,
+            _ => return Err(invalid_data("unsupported SOCKS5 relay address")),
+        };
+        let mut bound = vec![0; 1 + addr_len + 2];
+        bound[0] = reply[3];
+        read_exact(&control, &mut bound[1..]).await?;
+        let (mut relay, _) =
+            decode_addr(&bound).ok_or_else(|| invalid_data("invalid SOCKS5 relay address"))?;
+        if relay.ip().is_unspecified() {
+            // The relay is reachable at the address of the proxy itself
+            relay.set_ip(proxy.ip());
+        }
+
+        Ok(Self {
+            _control: control,
+            socket,
+            relay,
+        })
+    }
+}
+
+impl UdpTunnel for Socks5Tunnel {
+    fn send(&self, destination: SocketAddr, payload: &[u8]) -> io::Result<()> {
+        let mut datagram = Vec::with_capacity(MAX_HEADER_LEN + payload.len());
+        encode_header(destination, &mut datagram);
+        datagram.extend_from_slice(payload);
+        match self.socket.try_send_to(&datagram, self.relay) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn poll_recv(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<(usize, SocketAddr)>> {
+        loop {
+            let mut read = tokio::io::ReadBuf::new(buf);
+            let from = ready!(self.socket.poll_recv_from(cx, &mut read))?;
+            let len = read.filled().len();
+            if from != self.relay {
+                continue;
+            }
+            let Some((source, header_len)) = decode_header(&buf[..len]) else {
+                continue;
+            };
+            buf.copy_within(header_len..len, 0);
+            return Poll::Ready(Ok((len - header_len, source)));
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+}
+
+async fn write_all(stream: &tokio::net::TcpStream, mut buf: &[u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        stream.writable().await?;
+        match stream.try_write(buf) {
+            Ok(n) => buf = &buf[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+async fn read_exact(stream: &tokio::net::TcpStream, mut buf: &mut [u8]) -> io::Result<()> {
+    while !buf.is_empty() {
+        stream.readable().await?;
+        match stream.try_read(buf) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => buf = &mut buf[n..],
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Prefix a datagram relayed to `destination` with the SOCKS5 UDP request header
+fn encode_header(destination: SocketAddr, buf: &mut Vec<u8>) {
+    // Reserved, and not a fragment
+    buf.extend_from_slice(&[0, 0, 0]);
+    encode_addr(destination, buf);
+}
+
+/// Parse the SOCKS5 UDP request header of a relayed datagram
+///
+/// Returns the source of the datagram and the length of the header.
+fn decode_header(datagram: &[u8]) -> Option<(SocketAddr, usize)> {
+    match datagram {
+        [0, 0, 0, addr @ ..] => {
+            let (source, len) = decode_addr(addr)?;
+            Some((source, 3 + len))
+        }
+        _ => None,
+    }
+}
+
+fn encode_addr(addr: SocketAddr, buf: &mut Vec<u8>) {
+    match addr {
+        SocketAddr::V4(addr) => {
+            buf.push(ATYP_IPV4);
+            buf.extend_from_slice(&addr.ip().octets());
+        }
+        SocketAddr::V6(addr) => {
+            buf.push(ATYP_IPV6);
+            buf.extend_from_slice(&addr.ip().octets());
+        }
+    }
+    buf.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+/// Parse an IP address and port, returning it along with the number of bytes consumed
+fn decode_addr(buf: &[u8]) -> Option<(SocketAddr, usize)> {
+    let (&atyp, rest) = buf.split_first()?;
+    let (ip, rest) = match atyp {
+        ATYP_IPV4 => {
+            let (ip, rest) = rest.split_first_chunk::<4>()?;
+            (Ipv4Addr::from(*ip).into(), rest)
+        }
+        ATYP_IPV6 => {
+            let (ip, rest) = rest.split_first_chunk::<16>()?;
+            (Ipv6Addr::from(*ip).into(), rest)
+        }
+        _ => return None,
+    };
+    let (port, rest) = rest.split_first_chunk::<2>()?;
+    Some((
+        SocketAddr::new(ip, u16::from_be_bytes(*port)),
+        buf.len() - rest.len(),
+    ))
+}
+
+fn invalid_input(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn unsupported(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, msg)
+}
+
+const SOCKS_VERSION: u8 = 5;
+const AUTH_NONE: u8 = 0;
+const AUTH_PASSWORD: u8 = 2;
+const CMD_UDP_ASSOCIATE: u8 = 3;
+const ATYP_IPV4: u8 = 1;
+const ATYP_IPV6: u8 = 4;
+/// Length of the UDP request header for an IPv6 address
+const MAX_HEADER_LEN: usize = 3 + 1 + 16 + 2;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        for destination in ["192.0.2.1:4433", "[2001:db8::1]:443"] {
+            let destination = destination.parse().unwrap();
+            let mut datagram = Vec::new();
+            encode_header(destination, &mut datagram);
+            let header_len = datagram.len();
+            datagram.extend_from_slice(b"hello");
+            assert_eq!(decode_header(&datagram), Some((destination, header_len)));
+        }
+        // Fragments are unsupported
+        assert_eq!(
+            decode_header(&[0, 0, 1, ATYP_IPV4, 192, 0, 2, 1, 0, 1]),
+            None
+        );
+        // Truncated
+        assert_eq!(decode_header(&[0, 0, 0, ATYP_IPV4, 192, 0, 2, 1, 0]), None);
+    }
+}
@@ -0,0 +1,153 @@
+use std::collections::VecDeque;
+
+use bytes::BytesMut;
+use proto::RecvBufferPoolConfig;
+
+/// Storage for received datagrams
+///
+/// Without a pool, each datagram gets its own allocation. With a pool, datagrams are packed into
+/// shared buffers. A buffer is reused from the start once every datagram split off from it has been
+/// dropped, which `BytesMut::try_reclaim` detects without any bookkeeping.
+#[derive(Debug)]
+pub(crate) struct RecvBuffers {
+    config: Option<RecvBufferPoolConfig>,
+    /// Pooled buffers in the order they are filled, the one currently being filled first
+    pool: VecDeque<BytesMut>,
+}
+
+impl RecvBuffers {
+    pub(crate) fn new(config: Option<RecvBufferPoolConfig>) -> Self {
+        Self {
+            config,
+            pool: VecDeque::new(),
+        }
+    }
+
+    /// Copy `data` into a buffer owned by the caller
+    ///
+    /// `connections` is the number of open connections, which bounds the size of the pool.
+    pub(crate) fn copy(&mut self, data: &[u8], connections: usize) -> BytesMut {
+        let Some(config) = &self.config else {
+            return data.into();
+        };
+        if data.len() > config.get_buffer_size() {
+            return data.into();
+        }
+        let limit = config.get_max_buffers().min(
+            config
+                .get_buffers_per_connection()
+                .saturating_mul(connections.max(1)),
+        );
+
+        let buffer_size = config.get_buffer_size();
+        if !self
+            .pool
+            .front_mut()
+            .is_some_and(|buf| has_room(buf, data.len(), buffer_size))
+        {
+            self.rotate(data.len(), limit, buffer_size);
+        }
+        match self.pool.front_mut() {
+            Some(buf) if buf.capacity() >= data.len() => {
+                buf.extend_from_slice(data);
+                buf.split()
+            }
+            // Every pooled buffer is still in use
+            _ => data.into(),
+        }
+    }
+
+    /// Move on to the next buffer, which should have room for `len` bytes, allocating one if the
+    /// pool is below `limit`
+    ///
+    /// Buffers are filled in turn, so the next one is the least recently filled one, whose
+    /// datagrams are the most likely to have been dropped already. Only that buffer is checked,
+    /// keeping this O(1); if it's still in use, the following call checks the one after it.
+    fn rotate(&mut self, len: usize, limit: usize, buffer_size: usize) {
+        // Drop buffers beyond the limit, e.g. after connections closed
+        self.pool.truncate(limit);
+        if let Some(buf) = self.pool.pop_front() {
+            self.pool.push_back(buf);
+        }
+        if self
+            .pool
+            .front_mut()
+            .is_some_and(|buf| has_room(buf, len, buffer_size))
+        {
+            return;
+        }
+        if self.pool.len() < limit {
+            self.pool.push_front(BytesMut::with_capacity(buffer_size));
+        }
+    }
+}
+
+/// Whether `len` bytes fit into `buf`, reclaiming its storage if it's no longer shared
+fn has_room(buf: &mut BytesMut, len: usize, buffer_size: usize) -> bool {
+    buf.capacity() >= len || (buf.try_reclaim(buffer_size) && buf.capacity() >= len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuse() {
+        let mut config = RecvBufferPoolConfig::default();
+        config
+            .buffer_size(100)
+            .max_buffers(2)
+            .buffers_per_connection(2);
+        let mut buffers = RecvBuffers::new(Some(config));
+
+        let a = buffers.copy(&[1; 60], 1);
+        let ptr = a.as_ptr();
+        // Doesn't fit next to `a`, so a second buffer is allocated
+        let b = buffers.copy(&[2; 60], 1);
+        assert_eq!(&a[..], &[1; 60]);
+        // Both buffers are in use
+        let c = buffers.copy(&[3; 60], 1);
+        assert_eq!(&c[..], &[3; 60]);
+        assert_eq!(buffers.pool.len(), 2);
+
+        // Once `a` is dropped, its buffer is reused from the start
+        drop(a);
+        let d = buffers.copy(&[4; 60], 1);
+        assert_eq!(d.as_ptr(), ptr);
+        assert_eq!(&b[..], &[2; 60]);
+        assert_eq!(&d[..], &[4; 60]);
+    }
+
+    #[test]
+    fn in_turn() {
+        let mut config = RecvBufferPoolConfig::default();
+        config
+            .buffer_size(100)
+            .max_buffers(3)
+            .buffers_per_connection(3);
+        let mut buffers = RecvBuffers::new(Some(config));
+
+        let a = buffers.copy(&[1; 60], 1);
+        let b = buffers.copy(&[2; 60], 1);
+        let ptr = b.as_ptr();
+        let _c = buffers.copy(&[3; 60], 1);
+        assert_eq!(buffers.pool.len(), 3);
+
+        // Only the least recently filled buffer is checked, and it's still in use
+        drop(b);
+        let d = buffers.copy(&[4; 60], 1);
+        assert_ne!(d.as_ptr(), ptr);
+        // The next one is checked by the following call
+        let e = buffers.copy(&[5; 60], 1);
+        assert_eq!(e.as_ptr(), ptr);
+        assert_eq!(&a[..], &[1; 60]);
+        assert_eq!(&e[..], &[5; 60]);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut buffers = RecvBuffers::new(None);
+        assert_eq!(&buffers.copy(&[1; 10], 1)[..], &[1; 10]);
+        assert!(buffers.pool.is_empty());
+    }
+}
@@ -268,6 +268,47 @@ impl RecvStream {
         .await
     }
 
+    /// Reassemble incoming data directly into `buffer`
+    ///
+    /// Read data in place with [`read_ring()`](Self::read_ring). See
+    /// [`proto::RecvStream::set_ring_buffer()`] for details.
+    pub fn set_ring_buffer(&mut self, buffer: Box<[u8]>) -> Result<(), ReadError> {
+        let mut conn = self.conn.state.lock("RecvStream::set_ring_buffer");
+        if self.is_0rtt {
+            conn.check_0rtt().map_err(|()| ReadError::ZeroRttRejected)?;
+        }
+        conn.inner
+            .recv_stream(self.stream)
+            .set_ring_buffer(buffer)?;
+        Ok(())
+    }
+
+    /// Process data in the ring buffer set with [`set_ring_buffer()`](Self::set_ring_buffer)
+    ///
+    /// Waits for data following the last data read, then calls `f` with it as two slices, the
+    /// second of which is non-empty if the data wraps around the end of the ring buffer. `f`
+    /// returns how many bytes it consumed, which are released to make room for more; counts larger
+    /// than the data passed to it are treated as consuming all of it. Yields the number of bytes
+    /// consumed, or `None` if the stream was finished.
+    ///
+    /// This operation is cancel-safe.
+    pub async fn read_ring<F>(&mut self, mut f: F) -> Result<Option<usize>, ReadError>
+    where
+        F: FnMut(&[u8], &[u8]) -> usize,
+    {
+        poll_fn(|cx| {
+            self.poll_read_generic(cx, true, |chunks| match chunks.ring_readable() {
+                Ok(Some([first, second])) => {
+                    let len = f(first, second).min(first.len() + second.len());
+                    chunks.consume(len);
+                    ReadStatus::Readable(len)
+                }
+                res => (None, res.err()).into(),
+            })
+        })
+        .await
+    }
+
     /// Stop accepting data
     ///
     /// Discards unread data and notifies the peer to stop transmitting. Once stopped, further
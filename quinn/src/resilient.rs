@@ -0,0 +1,273 @@
+use std::{
+    fmt,
+    future::{Future, poll_fn},
+    net::SocketAddr,
+    pin::{Pin, pin},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::Poll,
+};
+
+use proto::{ClientConfig, ConnectError, ConnectionError};
+use thiserror::Error;
+use tokio::sync::Notify;
+
+use crate::{Duration, VarInt, connection::Connection, endpoint::Endpoint};
+
+/// Callback run on every newly established connection
+type Hook = Arc<dyn Fn(Connection) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// Backoff policy of a [`ResilientConnection`]
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    multiplier: f64,
+    max_attempts: Option<u32>,
+}
+
+impl ReconnectConfig {
+    /// Delay before retrying after the first failed connection attempt
+    ///
+    /// The first attempt after a connection is lost is made immediately. Defaults to 100ms.
+    pub fn initial_backoff(&mut self, value: Duration) -> &mut Self {
+        self.initial_backoff = value;
+        self
+    }
+
+    /// Upper bound on the delay between connection attempts
+    ///
+    /// Defaults to 30 seconds.
+    pub fn max_backoff(&mut self, value: Duration) -> &mut Self {
+        self.max_backoff = value;
+        self
+    }
+
+    /// Factor by which the delay grows after each failed attempt
+    ///
+    /// Factors below 1, as well as NaN, are treated as 1. Defaults to 2.
+    pub fn multiplier(&mut self, value: f64) -> &mut Self {
+        // `max` ignores NaN
+        self.multiplier = value.max(1.0);
+        self
+    }
+
+    /// Number of consecutive failed attempts after which reconnecting is given up
+    ///
+    /// Defaults to `None`, i.e. retrying indefinitely.
+    pub fn max_attempts(&mut self, value: Option<u32>) -> &mut Self {
+        self.max_attempts = value;
+        self
+    }
+
+    /// Delay before the attempt after the one that was preceded by `backoff`
+    fn next_backoff(&self, backoff: Duration) -> Duration {
+        Duration::try_from_secs_f64(backoff.as_secs_f64() * self.multiplier)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            max_attempts: None,
+        }
+    }
+}
+
+/// A client connection that is re-established when lost
+///
+/// Intended for long-lived channels that must survive network outages or server restarts. Each
+/// call to [`connection()`](Self::connection) yields the current connection if it is still open,
+/// and otherwise reconnects, backing off between failed attempts. Since every attempt uses the
+/// same [`ClientConfig`], session tickets and address validation tokens received on previous
+/// connections are used to resume sessions and skip address validation.
+///
+/// State associated with a connection, such as open streams, is lost when it fails; use
+/// [`set_on_reconnect()`](Self::set_on_reconnect) to restore it.
+pub struct ResilientConnection {
+    endpoint: Endpoint,
+    client_config: ClientConfig,
+    addr: SocketAddr,
+    server_name: String,
+    config: ReconnectConfig,
+    on_reconnect: Option<Hook>,
+    /// Held while reconnecting, so that concurrent callers share the attempt
+    conn: tokio::sync::Mutex<Option<Connection>>,
+    /// Set by [`close`](Self::close), which doesn't wait for an ongoing reconnect
+    closed: AtomicBool,
+    /// Notified when `closed` is set, to cancel an ongoing reconnect
+    on_close: Notify,
+}
+
+impl ResilientConnection {
+    /// Maintain a connection to `server_name` at `addr`
+    ///
+    /// No connection is made until one is requested.
+    pub fn new(
+        endpoint: Endpoint,
+        client_config: ClientConfig,
+        addr: SocketAddr,
+        server_name: &str,
+        config: ReconnectConfig,
+    ) -> Self {
+        Self {
+            endpoint,
+            client_config,
+            addr,
+            server_name: server_name.into(),
+            config,
+            on_reconnect: None,
+            conn: tokio::sync::Mutex::new(None),
+            closed: AtomicBool::new(false),
+            on_close: Notify::new(),
+        }
+    }
+
+    /// Run `hook` on every newly established connection, including the first
+    ///
+    /// Useful to re-subscribe or otherwise replay application state on a new connection. The
+    /// connection is handed out only once `hook` completes.
+    pub fn set_on_reconnect<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(Connection) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.on_reconnect = Some(Arc::new(move |conn| Box::pin(hook(conn))));
+    }
+
+    /// Get an open connection, reconnecting if necessary
+    ///
+    /// Fails if the connection was [`close`](Self::close)d, or if the configured number of
+    /// attempts failed in a row.
+    pub async fn connection(&self) -> Result<Connection, ReconnectError> {
+        let mut current = self.unless_closed(self.conn.lock()).await?;
+        if let Some(conn) = &*current {
+            if conn.close_reason().is_none() {
+                return Ok(conn.clone());
+            }
+        }
+        *current = None;
+
+        let mut backoff = self.config.initial_backoff;
+        let mut attempts = 0;
+        let conn = loop {
+            let e = match self.unless_closed(self.connect()).await? {
+                Ok(conn) => break conn,
+                Err(ReconnectError::Connection(e)) => e,
+                Err(e) => return Err(e),
+            };
+            attempts += 1;
+            if self.config.max_attempts.is_some_and(|max| attempts >= max) {
+                return Err(ReconnectError::Connection(e));
+            }
+            tracing::debug!(error = %e, ?backoff, "reconnect attempt failed");
+            let runtime = self.endpoint.runtime.clone();
+            let mut timer = runtime.new_timer(runtime.now() + backoff);
+            self.unless_closed(poll_fn(|cx| timer.as_mut().poll(cx)))
+                .await?;
+            backoff = self.config.next_backoff(backoff);
+        };
+
+        if let Some(hook) = &self.on_reconnect {
+            hook(conn.clone()).await;
+        }
+        Ok(current.insert(conn).clone())
+    }
+
+    /// Close the current connection, and stop reconnecting
+    ///
+    /// Cancels an ongoing reconnect, which then fails with [`ReconnectError::Closed`].
+    pub async fn close(&self, error_code: VarInt, reason: &[u8]) {
+        self.closed.store(true, Ordering::Release);
+        self.on_close.notify_waiters();
+        if let Some(conn) = self.conn.lock().await.take() {
+            conn.close(error_code, reason);
+        }
+    }
+
+    /// Drive `future` to completion, unless [`close`](Self::close) is called first
+    async fn unless_closed<F: Future>(&self, future: F) -> Result<F::Output, ReconnectError> {
+        let mut future = pin!(future);
+        // Created before checking the flag, so that a concurrent `close` can't be missed
+        let mut on_close = pin!(self.on_close.notified());
+        poll_fn(|cx| {
+            if self.closed.load(Ordering::Acquire) || on_close.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Err(ReconnectError::Closed));
+            }
+            future.as_mut().poll(cx).map(Ok)
+        })
+        .await
+    }
+
+    async fn connect(&self) -> Result<Connection, ReconnectError> {
+        Ok(self
+            .endpoint
+            .connect_with(self.client_config.clone(), self.addr, &self.server_name)?
+            .await?)
+    }
+}
+
+impl fmt::Debug for ResilientConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResilientConnection")
+            .field("addr", &self.addr)
+            .field("server_name", &self.server_name)
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Errors that arise from [`ResilientConnection::connection`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ReconnectError {
+    /// The connection was closed locally
+    #[error("connection closed")]
+    Closed,
+    /// A connection could not be initiated, e.g. due to a configuration error
+    #[error(transparent)]
+    Connect(#[from] ConnectError),
+    /// The last connection attempt failed
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let mut config = ReconnectConfig::default();
+        assert_eq!(
+            config.next_backoff(Duration::from_secs(1)),
+            Duration::from_secs(2)
+        );
+
+        for below_one in [f64::NAN, 0.5, -2.0] {
+            config.multiplier(below_one);
+            assert_eq!(
+                config.next_backoff(Duration::from_secs(1)),
+                Duration::from_secs(1)
+            );
+        }
+
+        for huge in [f64::MAX, f64::INFINITY] {
+            config.multiplier(huge);
+            assert_eq!(
+                config.next_backoff(Duration::from_secs(1)),
+                Duration::from_secs(30)
+            );
+        }
+        assert_eq!(
+            config.next_backoff(Duration::from_secs(1)),
+            Duration::from_secs(30)
+        );
+    }
+}
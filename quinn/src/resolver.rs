@@ -0,0 +1,142 @@
+use std::{
+    fmt,
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::Arc,
+};
+
+/// Maximum number of HTTPS/SVCB alias records followed before giving up on service discovery
+const MAX_ALIASES: usize = 8;
+
+/// Abstracts DNS lookups, for use by [`Endpoint::connect_to`](crate::Endpoint::connect_to)
+pub trait Resolver: Send + Sync + fmt::Debug + 'static {
+    /// Look up the addresses of `host`, combining them with `port`
+    ///
+    /// Addresses should be yielded in order of preference.
+    fn lookup_ip<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>>;
+
+    /// Look up the HTTPS or SVCB records ([RFC 9460]) of `host`
+    ///
+    /// Used to discover alternative endpoints and ports serving `host`. Implementations should
+    /// only yield records advertising a protocol that runs over QUIC, such as `h3`, and may yield
+    /// no records if service discovery is unsupported, which is the default.
+    ///
+    /// [RFC 9460]: https://www.rfc-editor.org/rfc/rfc9460.html
+    fn lookup_service<'a>(
+        &'a self,
+        host: &'a str,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<ServiceRecord>>> + Send + 'a>> {
+        let _ = host;
+        Box::pin(async { Ok(Vec::new()) })
+    }
+}
+
+/// An HTTPS or SVCB record, see [`Resolver::lookup_service`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceRecord {
+    /// Priority of the record, lower values being preferred
+    ///
+    /// A priority of 0 marks an alias record, which delegates to the records of `target`.
+    pub priority: u16,
+    /// Host serving the service, or `None` for the queried host itself
+    pub target: Option<String>,
+    /// Port serving the service, if different from the one requested
+    pub port: Option<u16>,
+    /// Addresses of `target`, which are used instead of looking them up if present
+    pub ipv4_hint: Vec<Ipv4Addr>,
+    /// Addresses of `target`, which are used instead of looking them up if present
+    pub ipv6_hint: Vec<Ipv6Addr>,
+}
+
+/// A [`Resolver`] using the system resolver through Tokio
+///
+/// Doesn't support service discovery.
+#[cfg(feature = "runtime-tokio")]
+#[derive(Debug)]
+pub struct TokioResolver;
+
+#[cfg(feature = "runtime-tokio")]
+impl Resolver for TokioResolver {
+    fn lookup_ip<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>> {
+        Box::pin(async move { Ok(tokio::net::lookup_host((host, port)).await?.collect()) })
+    }
+}
+
+/// The resolver used by endpoints that weren't configured with one
+pub(crate) fn default_resolver() -> Option<Arc<dyn Resolver>> {
+    #[cfg(feature = "runtime-tokio")]
+    {
+        Some(Arc::new(TokioResolver))
+    }
+    #[cfg(not(feature = "runtime-tokio"))]
+    {
+        None
+    }
+}
+
+/// Find the addresses serving `host` on `port`, in order of preference
+///
+/// Endpoints discovered through service records are preferred over the plain addresses of
+/// `host`. Failing service lookups are treated like the absence of records.
+pub(crate) async fn resolve(
+    resolver: &dyn Resolver,
+    host: &str,
+    port: u16,
+) -> io::Result<Vec<SocketAddr>> {
+    let mut addrs = Vec::new();
+    let mut push = |addr: SocketAddr| {
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    };
+
+    let mut name = host.to_owned();
+    let mut records = Vec::new();
+    for _ in 0..MAX_ALIASES {
+        let found = resolver.lookup_service(&name).await.unwrap_or_default();
+        // Alias records take precedence over service records
+        let Some(alias) = found.iter().find(|r| r.priority == 0) else {
+            records = found;
+            break;
+        };
+        match &alias.target {
+            Some(target) => name = target.clone(),
+            // The service is unavailable
+            None => break,
+        }
+    }
+    records.sort_by_key(|r| r.priority);
+
+    for record in records {
+        let target = record.target.as_deref().unwrap_or(&name);
+        let port = record.port.unwrap_or(port);
+        if record.ipv4_hint.is_empty() && record.ipv6_hint.is_empty() {
+            if let Ok(found) = resolver.lookup_ip(target, port).await {
+                found.into_iter().for_each(&mut push);
+            }
+            continue;
+        }
+        let hints = record.ipv6_hint.into_iter().map(IpAddr::V6);
+        let hints = hints.chain(record.ipv4_hint.into_iter().map(IpAddr::V4));
+        hints
+            .map(|ip| SocketAddr::new(ip, port))
+            .for_each(&mut push);
+    }
+
+    match resolver.lookup_ip(host, port).await {
+        Ok(found) => found.into_iter().for_each(push),
+        Err(e) if addrs.is_empty() => return Err(e),
+        Err(_) => {}
+    }
+    Ok(addrs)
+}
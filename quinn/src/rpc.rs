@@ -0,0 +1,251 @@
+use std::{
+    future::{Future, poll_fn},
+    pin::pin,
+    sync::Arc,
+    task::Poll,
+};
+
+use bytes::Bytes;
+use proto::ConnectionError;
+use thiserror::Error;
+
+use crate::{
+    Duration, VarInt,
+    connection::Connection,
+    recv_stream::{ReadError, ReadToEndError, RecvStream},
+    send_stream::{SendStream, WriteError},
+};
+
+/// Largest request or response accepted by default
+const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Error code used to abandon the streams of a call
+const CANCELLED: VarInt = VarInt::from_u32(0);
+
+/// Issues request/response calls to an [`RpcServer`] on the peer
+///
+/// Every call uses a fresh bidirectional stream: the request is the data written by the client
+/// before finishing its side, and the response is the data written by the server before finishing
+/// its side. Dropping the future of a call before it completes cancels it, resetting both
+/// directions of the stream.
+#[derive(Debug, Clone)]
+pub struct RpcClient {
+    conn: Connection,
+    timeout: Option<Duration>,
+    max_response_size: usize,
+}
+
+impl RpcClient {
+    /// Issue calls on `conn`
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn,
+            timeout: None,
+            max_response_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Time after which a call is cancelled and fails with [`RpcError::TimedOut`]
+    ///
+    /// Includes time spent waiting for the peer to allow another stream to be opened. Defaults to
+    /// `None`, i.e. calls only fail when the connection or stream does.
+    pub fn set_timeout(&mut self, value: Option<Duration>) {
+        self.timeout = value;
+    }
+
+    /// Maximum size of responses, beyond which calls fail with [`RpcError::TooLarge`]
+    ///
+    /// Defaults to 64 KiB.
+    pub fn set_max_response_size(&mut self, value: usize) {
+        self.max_response_size = value;
+    }
+
+    /// The connection calls are issued on
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Send `request` and wait for the response
+    pub async fn call(&self, request: &[u8]) -> Result<Bytes, RpcError> {
+        let Some(timeout) = self.timeout else {
+            return self.exchange(request).await;
+        };
+        let runtime = self.conn.runtime();
+        let mut timer = runtime.new_timer(runtime.now() + timeout);
+        let mut exchange = pin!(self.exchange(request));
+        poll_fn(|cx| {
+            if let Poll::Ready(result) = exchange.as_mut().poll(cx) {
+                return Poll::Ready(result);
+            }
+            timer.as_mut().poll(cx).map(|()| Err(RpcError::TimedOut))
+        })
+        .await
+    }
+
+    async fn exchange(&self, request: &[u8]) -> Result<Bytes, RpcError> {
+        let (send, recv) = self.conn.open_bi().await?;
+        let mut call = CallStreams {
+            send,
+            recv,
+            complete: false,
+        };
+        call.send.write_all(request).await.map_err(write_error)?;
+        // Can only fail if the stream was reset, which we'd only do after the call finished
+        let _ = call.send.finish();
+        let response = call
+            .recv
+            .read_to_end(self.max_response_size)
+            .await
+            .map_err(|e| match e {
+                ReadToEndError::TooLong => RpcError::TooLarge,
+                ReadToEndError::Read(e) => read_error(e),
+            })?;
+        call.complete = true;
+        Ok(response.into())
+    }
+}
+
+/// Streams of an outstanding call, which are reset if the call is abandoned
+struct CallStreams {
+    send: SendStream,
+    recv: RecvStream,
+    complete: bool,
+}
+
+impl Drop for CallStreams {
+    fn drop(&mut self) {
+        if !self.complete {
+            let _ = self.send.reset(CANCELLED);
+            let _ = self.recv.stop(CANCELLED);
+        }
+    }
+}
+
+/// Dispatches calls made by an [`RpcClient`] on the peer to a handler
+#[derive(Debug, Clone)]
+pub struct RpcServer {
+    conn: Connection,
+    max_request_size: usize,
+}
+
+impl RpcServer {
+    /// Accept calls on `conn`
+    pub fn new(conn: Connection) -> Self {
+        Self {
+            conn,
+            max_request_size: DEFAULT_MAX_MESSAGE_SIZE,
+        }
+    }
+
+    /// Maximum size of requests, beyond which calls are rejected with error code 0
+    ///
+    /// Defaults to 64 KiB.
+    pub fn set_max_request_size(&mut self, value: usize) {
+        self.max_request_size = value;
+    }
+
+    /// Handle calls until the connection is closed
+    ///
+    /// Each call is handled in a separate task, spawned on the runtime of the connection.
+    /// `handler` yields either the response, or an error code with which to reject the call,
+    /// which the client observes as [`RpcError::Rejected`]. Handlers of calls that the client
+    /// cancels are dropped.
+    ///
+    /// Returns the reason the connection was closed.
+    pub async fn serve<F, Fut>(&self, handler: F) -> ConnectionError
+    where
+        F: Fn(Bytes) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Bytes, VarInt>> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let runtime = self.conn.runtime();
+        loop {
+            let (send, recv) = match self.conn.accept_bi().await {
+                Ok(streams) => streams,
+                Err(e) => return e,
+            };
+            runtime.spawn(Box::pin(handle_call(
+                send,
+                recv,
+                self.max_request_size,
+                handler.clone(),
+            )));
+        }
+    }
+}
+
+async fn handle_call<F, Fut>(
+    mut send: SendStream,
+    mut recv: RecvStream,
+    max_request_size: usize,
+    handler: Arc<F>,
+) where
+    F: Fn(Bytes) -> Fut,
+    Fut: Future<Output = Result<Bytes, VarInt>>,
+{
+    let Ok(request) = recv.read_to_end(max_request_size).await else {
+        let _ = recv.stop(CANCELLED);
+        let _ = send.reset(CANCELLED);
+        return;
+    };
+
+    let mut response = pin!(handler(request.into()));
+    let mut stopped = pin!(send.stopped());
+    let response = poll_fn(|cx| {
+        if let Poll::Ready(response) = response.as_mut().poll(cx) {
+            return Poll::Ready(Some(response));
+        }
+        // The client cancelled the call, or the connection was lost
+        stopped.as_mut().poll(cx).map(|_| None)
+    })
+    .await;
+
+    match response {
+        Some(Ok(response)) if send.write_all(&response).await.is_ok() => {
+            let _ = send.finish();
+        }
+        Some(Err(code)) => {
+            let _ = send.reset(code);
+        }
+        _ => {}
+    }
+}
+
+fn write_error(e: WriteError) -> RpcError {
+    match e {
+        WriteError::Stopped(code) => RpcError::Rejected(code),
+        WriteError::ConnectionLost(e) => RpcError::Connection(e),
+        e => RpcError::Write(e),
+    }
+}
+
+fn read_error(e: ReadError) -> RpcError {
+    match e {
+        ReadError::Reset(code) => RpcError::Rejected(code),
+        ReadError::ConnectionLost(e) => RpcError::Connection(e),
+        e => RpcError::Read(e),
+    }
+}
+
+/// Errors that arise from making a call with an [`RpcClient`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    /// No response arrived before the timeout elapsed
+    #[error("call timed out")]
+    TimedOut,
+    /// The peer rejected the call with an error code
+    #[error("call rejected with code {0}")]
+    Rejected(VarInt),
+    /// The response exceeds the maximum response size
+    #[error("response exceeds the size limit")]
+    TooLarge,
+    /// The connection was lost
+    #[error(transparent)]
+    Connection(#[from] ConnectionError),
+    /// Sending the request failed
+    #[error(transparent)]
+    Write(WriteError),
+    /// Receiving the response failed
+    #[error(transparent)]
+    Read(ReadError),
+}
@@ -0,0 +1,478 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    future::Future,
+    io,
+    net::{IpAddr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+    task::{Context, Poll, Wake, Waker},
+};
+
+use proto::{EcnCodepoint, Transmit};
+use udp::RecvMeta;
+
+use super::{AsyncTimer, AsyncUdpSocket, Runtime, UdpSender};
+use crate::{Instant, endpoint::proto_ecn, udp_ecn};
+
+/// Drives an [`Endpoint`](crate::Endpoint) from an event loop owned by the application
+///
+/// Supplies both the [`Runtime`] and the [`AsyncUdpSocket`] of an endpoint, leaving actual I/O
+/// and the passage of time to the caller, much like the types of `quinn-proto` do. Datagrams
+/// received by the application are fed in through [`handle_datagram`](Self::handle_datagram),
+/// datagrams to be sent are taken out through [`poll_transmit`](Self::poll_transmit), and timers
+/// are replaced by [`poll_timeout`](Self::poll_timeout) and
+/// [`handle_timeout`](Self::handle_timeout). Connections and streams keep their usual async
+/// interface.
+///
+/// The background tasks of the endpoint and its connections only make progress during calls to
+/// `poll_transmit` and `poll_timeout`. After handling input, call `poll_transmit` until it returns
+/// `None`, then wait until the instant returned by `poll_timeout` or until new input arrives.
+/// Work may also become available because the application used a connection or stream, which is
+/// signalled by the callback registered with [`set_notify`](Self::set_notify).
+///
+/// Every transmit describes a single datagram. Dropping the driver drops all tasks spawned on it.
+pub struct ManualDriver {
+    io: Arc<Io>,
+    executor: Arc<Executor>,
+}
+
+impl ManualDriver {
+    /// Create a driver for an endpoint bound to `local_addr`, with the clock starting at `now`
+    pub fn new(local_addr: SocketAddr, now: Instant) -> Self {
+        Self {
+            io: Arc::new(Io {
+                local_addr,
+                state: Mutex::new(IoState {
+                    now,
+                    timers: HashMap::new(),
+                    next_timer: 0,
+                    inbound: VecDeque::new(),
+                    recv_waker: None,
+                    outbound: VecDeque::new(),
+                }),
+            }),
+            executor: Arc::new(Executor {
+                tasks: Mutex::new(Tasks {
+                    slots: Vec::new(),
+                    free: Vec::new(),
+                    ready: VecDeque::new(),
+                    running: false,
+                    notify: None,
+                }),
+                run_lock: Mutex::new(()),
+            }),
+        }
+    }
+
+    /// The runtime to pass to [`Endpoint::new_with_abstract_socket`](crate::Endpoint::new_with_abstract_socket)
+    pub fn runtime(&self) -> Arc<dyn Runtime> {
+        Arc::new(ManualRuntime {
+            io: self.io.clone(),
+            executor: Arc::downgrade(&self.executor),
+        })
+    }
+
+    /// The socket to pass to [`Endpoint::new_with_abstract_socket`](crate::Endpoint::new_with_abstract_socket)
+    pub fn socket(&self) -> Box<dyn AsyncUdpSocket> {
+        Box::new(ManualSocket {
+            io: self.io.clone(),
+        })
+    }
+
+    /// Register a callback invoked whenever a task becomes ready outside of a call to
+    /// [`poll_transmit`](Self::poll_transmit) or [`poll_timeout`](Self::poll_timeout)
+    ///
+    /// The callback may be invoked from any thread, and should arrange for the event loop to poll
+    /// the driver again soon. It must not call into the driver itself.
+    pub fn set_notify(&self, notify: impl Fn() + Send + Sync + 'static) {
+        self.executor.tasks.lock().unwrap().notify = Some(Arc::new(notify));
+    }
+
+    /// Process a datagram received from `remote`
+    ///
+    /// `local_ip` is the destination address of the datagram, if known.
+    pub fn handle_datagram(
+        &self,
+        now: Instant,
+        remote: SocketAddr,
+        local_ip: Option<IpAddr>,
+        ecn: Option<EcnCodepoint>,
+        data: &[u8],
+    ) {
+        let waker = {
+            let mut state = self.io.state.lock().unwrap();
+            state.inbound.push_back(Inbound {
+                remote,
+                local_ip,
+                ecn,
+                contents: data.into(),
+            });
+            state.recv_waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        self.handle_timeout(now);
+    }
+
+    /// Advance the clock to `now`, expiring any timers that are due
+    ///
+    /// The clock never goes backwards; earlier instants are ignored.
+    pub fn handle_timeout(&self, now: Instant) {
+        let expired = {
+            let mut state = self.io.state.lock().unwrap();
+            state.now = state.now.max(now);
+            let now = state.now;
+            let mut expired = Vec::new();
+            state.timers.retain(|_, (deadline, waker)| {
+                if *deadline > now {
+                    return true;
+                }
+                expired.push(waker.clone());
+                false
+            });
+            expired
+        };
+        for waker in expired {
+            waker.wake();
+        }
+    }
+
+    /// Run ready tasks, then return the next datagram to send, if any
+    ///
+    /// The contents of the datagram are written to `buf`, replacing what it held before.
+    pub fn poll_transmit(&self, buf: &mut Vec<u8>) -> Option<Transmit> {
+        self.executor.run();
+        let outbound = self.io.state.lock().unwrap().outbound.pop_front()?;
+        buf.clear();
+        buf.extend_from_slice(&outbound.contents);
+        Some(outbound.transmit)
+    }
+
+    /// Run ready tasks, then return the instant at which [`handle_timeout`](Self::handle_timeout)
+    /// should next be called, if any
+    pub fn poll_timeout(&self) -> Option<Instant> {
+        self.executor.run();
+        let state = self.io.state.lock().unwrap();
+        state.timers.values().map(|&(deadline, _)| deadline).min()
+    }
+}
+
+impl fmt::Debug for ManualDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManualDriver")
+            .field("local_addr", &self.io.local_addr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Clock, timers and datagram queues shared by the runtime and the socket
+struct Io {
+    local_addr: SocketAddr,
+    state: Mutex<IoState>,
+}
+
+struct IoState {
+    now: Instant,
+    /// Deadlines of pending timers, and the tasks waiting for them
+    timers: HashMap<u64, (Instant, Waker)>,
+    next_timer: u64,
+    /// Datagrams handed to the driver that the endpoint hasn't received yet
+    inbound: VecDeque<Inbound>,
+    /// Task to wake when a datagram is handed to the driver
+    recv_waker: Option<Waker>,
+    /// Datagrams sent by the endpoint that the application hasn't polled yet
+    outbound: VecDeque<Outbound>,
+}
+
+struct Inbound {
+    remote: SocketAddr,
+    local_ip: Option<IpAddr>,
+    ecn: Option<EcnCodepoint>,
+    contents: Box<[u8]>,
+}
+
+struct Outbound {
+    transmit: Transmit,
+    contents: Box<[u8]>,
+}
+
+/// Single-threaded executor for the tasks spawned on a [`ManualDriver`]
+///
+/// Only the driver holds strong references, so that dropping it drops the tasks, which in turn
+/// hold the runtime.
+struct Executor {
+    tasks: Mutex<Tasks>,
+    /// Held while running tasks, so that a task that is woken while being polled isn't missed
+    run_lock: Mutex<()>,
+}
+
+impl Executor {
+    fn spawn(self: &Arc<Self>, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        let id = {
+            let mut tasks = self.tasks.lock().unwrap();
+            match tasks.free.pop() {
+                Some(id) => {
+                    tasks.slots[id] = Some(future);
+                    id
+                }
+                None => {
+                    tasks.slots.push(Some(future));
+                    tasks.slots.len() - 1
+                }
+            }
+        };
+        self.schedule(id);
+    }
+
+    fn schedule(&self, id: usize) {
+        let notify = {
+            let mut tasks = self.tasks.lock().unwrap();
+            tasks.ready.push_back(id);
+            if tasks.running {
+                None
+            } else {
+                tasks.notify.clone()
+            }
+        };
+        if let Some(notify) = notify {
+            notify();
+        }
+    }
+
+    /// Poll ready tasks until none are left
+    fn run(self: &Arc<Self>) {
+        let _guard = self.run_lock.lock().unwrap();
+        self.tasks.lock().unwrap().running = true;
+        loop {
+            let (id, mut future) = {
+                let mut tasks = self.tasks.lock().unwrap();
+                let Some(id) = tasks.ready.pop_front() else {
+                    tasks.running = false;
+                    return;
+                };
+                // Tasks that have finished may still be woken
+                let Some(future) = tasks.slots[id].take() else {
+                    continue;
+                };
+                (id, future)
+            };
+
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                executor: Arc::downgrade(self),
+            }));
+            if future
+                .as_mut()
+                .poll(&mut Context::from_waker(&waker))
+                .is_pending()
+            {
+                self.tasks.lock().unwrap().slots[id] = Some(future);
+                continue;
+            }
+            self.tasks.lock().unwrap().free.push(id);
+            // Dropped without holding the lock, since dropping a task may spawn or wake others
+            drop(future);
+        }
+    }
+}
+
+struct Tasks {
+    slots: Vec<Option<Pin<Box<dyn Future<Output = ()> + Send>>>>,
+    free: Vec<usize>,
+    ready: VecDeque<usize>,
+    /// Whether the driver is currently running tasks, in which case it needn't be notified
+    running: bool,
+    notify: Option<Arc<dyn Fn() + Send + Sync>>,
+}
+
+struct TaskWaker {
+    id: usize,
+    executor: Weak<Executor>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        if let Some(executor) = self.executor.upgrade() {
+            executor.schedule(self.id);
+        }
+    }
+}
+
+struct ManualRuntime {
+    io: Arc<Io>,
+    executor: Weak<Executor>,
+}
+
+impl Runtime for ManualRuntime {
+    fn new_timer(&self, i: Instant) -> Pin<Box<dyn AsyncTimer>> {
+        let id = {
+            let mut state = self.io.state.lock().unwrap();
+            state.next_timer += 1;
+            state.next_timer
+        };
+        Box::pin(ManualTimer {
+            id,
+            deadline: i,
+            io: self.io.clone(),
+        })
+    }
+
+    fn spawn(&self, future: Pin<Box<dyn Future<Output = ()> + Send>>) {
+        // Tasks spawned after the driver was dropped could never run
+        if let Some(executor) = self.executor.upgrade() {
+            executor.spawn(future);
+        }
+    }
+
+    #[cfg(not(wasm_browser))]
+    fn wrap_udp_socket(&self, _: std::net::UdpSocket) -> io::Result<Box<dyn AsyncUdpSocket>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "sockets of a manually driven endpoint are owned by the application",
+        ))
+    }
+
+    fn now(&self) -> Instant {
+        self.io.state.lock().unwrap().now
+    }
+}
+
+impl fmt::Debug for ManualRuntime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ManualRuntime")
+    }
+}
+
+struct ManualTimer {
+    id: u64,
+    deadline: Instant,
+    io: Arc<Io>,
+}
+
+impl AsyncTimer for ManualTimer {
+    fn reset(mut self: Pin<&mut Self>, i: Instant) {
+        self.deadline = i;
+    }
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.io.state.lock().unwrap();
+        if state.now >= self.deadline {
+            state.timers.remove(&self.id);
+            return Poll::Ready(());
+        }
+        state
+            .timers
+            .insert(self.id, (self.deadline, cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl Drop for ManualTimer {
+    fn drop(&mut self) {
+        self.io.state.lock().unwrap().timers.remove(&self.id);
+    }
+}
+
+impl fmt::Debug for ManualTimer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManualTimer")
+            .field("deadline", &self.deadline)
+            .finish_non_exhaustive()
+    }
+}
+
+struct ManualSocket {
+    io: Arc<Io>,
+}
+
+impl AsyncUdpSocket for ManualSocket {
+    fn create_sender(&self) -> Pin<Box<dyn UdpSender>> {
+        Box::pin(ManualSender {
+            io: self.io.clone(),
+        })
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = self.io.state.lock().unwrap();
+        let mut received = 0;
+        for (buf, meta) in bufs.iter_mut().zip(meta.iter_mut()) {
+            let Some(datagram) = state.inbound.pop_front() else {
+                break;
+            };
+            // Like a real socket, truncate datagrams that don't fit the buffer
+            let len = datagram.contents.len().min(buf.len());
+            buf[..len].copy_from_slice(&datagram.contents[..len]);
+            *meta = RecvMeta::default();
+            meta.addr = datagram.remote;
+            meta.len = len;
+            meta.stride = len;
+            meta.ecn = datagram.ecn.map(udp_ecn);
+            meta.dst_ip = datagram.local_ip;
+            received += 1;
+        }
+
+        if received > 0 {
+            return Poll::Ready(Ok(received));
+        }
+        state.recv_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.io.local_addr)
+    }
+}
+
+impl fmt::Debug for ManualSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManualSocket")
+            .field("local_addr", &self.io.local_addr)
+            .finish_non_exhaustive()
+    }
+}
+
+struct ManualSender {
+    io: Arc<Io>,
+}
+
+impl UdpSender for ManualSender {
+    fn poll_send(
+        self: Pin<&mut Self>,
+        transmit: &udp::Transmit<'_>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut state = self.io.state.lock().unwrap();
+        let segment_size = transmit
+            .segment_size
+            .unwrap_or(transmit.contents.len())
+            .max(1);
+        for contents in transmit.contents.chunks(segment_size) {
+            state.outbound.push_back(Outbound {
+                transmit: Transmit {
+                    destination: transmit.destination,
+                    ecn: transmit.ecn.map(proto_ecn),
+                    dscp: transmit.dscp,
+                    flow_label: transmit.flow_label,
+                    size: contents.len(),
+                    segment_size: None,
+                    src_ip: transmit.src_ip,
+                },
+                contents: contents.into(),
+            });
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl fmt::Debug for ManualSender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ManualSender")
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use rand::{RngExt, SeedableRng, rngs::StdRng};
+use udp::{EcnCodepoint, RecvMeta, Transmit};
+
+use super::{AsyncTimer, AsyncUdpSocket, Runtime, UdpSender};
+use crate::{Duration, Instant};
+
+/// An [`AsyncUdpSocket`] connected to a peer through a simulated in-memory link
+///
+/// Allows testing applications without touching real sockets. Every datagram sent by one socket of
+/// a [`pair`](Self::pair) is subject to the conditions described by its [`LinkConfig`] before it
+/// can be received by the other, which makes it possible to reproduce adverse network conditions
+/// deterministically. Datagrams addressed to anything but the peer are discarded.
+///
+/// Pass the sockets to [`Endpoint::new_with_abstract_socket`](crate::Endpoint::new_with_abstract_socket)
+/// to use them.
+pub struct MemorySocket {
+    addr: SocketAddr,
+    peer: SocketAddr,
+    /// Datagrams headed to this socket
+    inbound: Arc<Link>,
+    /// Datagrams headed to the peer
+    outbound: Arc<Link>,
+    runtime: Arc<dyn Runtime>,
+    /// Wakes the receiving task when the next datagram in flight arrives
+    ///
+    /// Only accessed through `&mut self`; the `Mutex` just makes the socket `Sync`.
+    timer: Mutex<Option<Pin<Box<dyn AsyncTimer>>>>,
+}
+
+impl MemorySocket {
+    /// Create two sockets bound to `a` and `b` which exchange datagrams with each other
+    ///
+    /// Both directions of the link behave according to `config`, but make independent random
+    /// decisions. Time is measured using `runtime`, so a runtime with a simulated clock yields fully
+    /// reproducible behavior.
+    pub fn pair(
+        a: SocketAddr,
+        b: SocketAddr,
+        config: LinkConfig,
+        runtime: Arc<dyn Runtime>,
+    ) -> (Self, Self) {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let a_to_b = Arc::new(Link::new(config.clone(), StdRng::from_rng(&mut rng)));
+        let b_to_a = Arc::new(Link::new(config, rng));
+        (
+            Self {
+                addr: a,
+                peer: b,
+                inbound: b_to_a.clone(),
+                outbound: a_to_b.clone(),
+                runtime: runtime.clone(),
+                timer: Mutex::new(None),
+            },
+            Self {
+                addr: b,
+                peer: a,
+                inbound: a_to_b,
+                outbound: b_to_a,
+                runtime,
+                timer: Mutex::new(None),
+            },
+        )
+    }
+}
+
+impl AsyncUdpSocket for MemorySocket {
+    fn create_sender(&self) -> Pin<Box<dyn UdpSender>> {
+        Box::pin(MemorySender {
+            addr: self.addr,
+            peer: self.peer,
+            link: self.outbound.clone(),
+            runtime: self.runtime.clone(),
+        })
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let next_arrival =
+                match self
+                    .inbound
+                    .recv(cx, self.runtime.now(), self.addr, bufs, meta)
+                {
+                    Ok(n) => return Poll::Ready(Ok(n)),
+                    Err(None) => return Poll::Pending,
+                    Err(Some(next_arrival)) => next_arrival,
+                };
+            let timer = self.timer.get_mut().unwrap();
+            let timer = match timer {
+                Some(timer) => {
+                    timer.as_mut().reset(next_arrival);
+                    timer
+                }
+                None => timer.insert(self.runtime.new_timer(next_arrival)),
+            };
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+
+    fn may_fragment(&self) -> bool {
+        false
+    }
+}
+
+impl fmt::Debug for MemorySocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySocket")
+            .field("addr", &self.addr)
+            .field("peer", &self.peer)
+            .finish_non_exhaustive()
+    }
+}
+
+struct MemorySender {
+    addr: SocketAddr,
+    peer: SocketAddr,
+    link: Arc<Link>,
+    runtime: Arc<dyn Runtime>,
+}
+
+impl UdpSender for MemorySender {
+    fn poll_send(
+        self: Pin<&mut Self>,
+        transmit: &Transmit<'_>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        if transmit.destination == self.peer {
+            self.link.send(self.runtime.now(), self.addr, transmit);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn max_transmit_segments(&self) -> usize {
+        udp::BATCH_SIZE
+    }
+}
+
+impl fmt::Debug for MemorySender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MemorySender")
+    }
+}
+
+/// Conditions simulated by the link between the two sockets of a [`MemorySocket::pair`]
+///
+/// The default is a perfect link, which delivers every datagram instantly and in order.
+#[derive(Debug, Clone)]
+pub struct LinkConfig {
+    pub(crate) latency: Duration,
+    pub(crate) jitter: Duration,
+    pub(crate) loss: f64,
+    pub(crate) reorder: f64,
+    pub(crate) bandwidth: Option<u64>,
+    pub(crate) seed: u64,
+}
+
+impl LinkConfig {
+    /// Base one-way delay of every datagram
+    pub fn latency(&mut self, value: Duration) -> &mut Self {
+        self.latency = value;
+        self
+    }
+
+    /// Maximum random delay added to the latency of each datagram
+    ///
+    /// Jitter alone does not reorder datagrams: a datagram is never delivered before one that was
+    /// sent earlier, unless it was picked for reordering.
+    pub fn jitter(&mut self, value: Duration) -> &mut Self {
+        self.jitter = value;
+        self
+    }
+
+    /// Probability that a datagram is lost, from 0 to 1
+    pub fn loss(&mut self, value: f64) -> &mut Self {
+        self.loss = value.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Probability that a datagram skips the latency and jitter, from 0 to 1
+    ///
+    /// Such datagrams overtake datagrams sent before them that are still in flight.
+    pub fn reorder(&mut self, value: f64) -> &mut Self {
+        self.reorder = value.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Rate in bytes per second at which datagrams are put on the link, or `None` for unlimited
+    ///
+    /// Datagrams sent faster than this are queued without bound, delaying their delivery.
+    pub fn bandwidth(&mut self, value: Option<u64>) -> &mut Self {
+        self.bandwidth = value.filter(|&rate| rate > 0);
+        self
+    }
+
+    /// Seed for the random decisions made by the link
+    ///
+    /// Links constructed with the same configuration make the same decisions, given datagrams are
+    /// sent at the same times.
+    pub fn seed(&mut self, value: u64) -> &mut Self {
+        self.seed = value;
+        self
+    }
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            loss: 0.0,
+            reorder: 0.0,
+            bandwidth: None,
+            seed: 0,
+        }
+    }
+}
+
+/// One direction of the simulated link
+#[derive(Debug)]
+struct Link {
+    config: LinkConfig,
+    state: Mutex<LinkState>,
+}
+
+impl Link {
+    fn new(config: LinkConfig, rng: StdRng) -> Self {
+        Self {
+            config,
+            state: Mutex::new(LinkState {
+                rng,
+                in_flight: BTreeMap::new(),
+                next_seq: 0,
+                busy_until: None,
+                last_arrival: None,
+                waker: None,
+            }),
+        }
+    }
+
+    fn send(&self, now: Instant, source: SocketAddr, transmit: &Transmit<'_>) {
+        let mut state = self.state.lock().unwrap();
+        let segment_size = transmit
+            .segment_size
+            .unwrap_or(transmit.contents.len())
+            .max(1);
+        for contents in transmit.contents.chunks(segment_size) {
+            let mut departure = now;
+            if let Some(bandwidth) = self.config.bandwidth {
+                let start = state
+                    .busy_until
+                    .map_or(now, |busy_until| busy_until.max(now));
+                departure =
+                    start + Duration::from_secs_f64(contents.len() as f64 / bandwidth as f64);
+                state.busy_until = Some(departure);
+            }
+
+            if state.rng.random_bool(self.config.loss) {
+                continue;
+            }
+
+            let arrival = if state.rng.random_bool(self.config.reorder) {
+                departure
+            } else {
+                let jitter = self.config.jitter.mul_f64(state.rng.random());
+                let arrival = departure + self.config.latency + jitter;
+                let arrival = state.last_arrival.map_or(arrival, |last| arrival.max(last));
+                state.last_arrival = Some(arrival);
+                arrival
+            };
+
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.in_flight.insert(
+                (arrival, seq),
+                Datagram {
+                    source,
+                    ecn: transmit.ecn,
+                    contents: contents.into(),
+                },
+            );
+        }
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Copy datagrams that have arrived by `now` into `bufs`
+    ///
+    /// If none have, registers `cx` to be woken when one is sent, and returns the time at which the
+    /// next datagram in flight arrives, if any.
+    fn recv(
+        &self,
+        cx: &mut Context<'_>,
+        now: Instant,
+        destination: SocketAddr,
+        bufs: &mut [io::IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Result<usize, Option<Instant>> {
+        let mut state = self.state.lock().unwrap();
+        let mut received = 0;
+        for (buf, meta) in bufs.iter_mut().zip(meta.iter_mut()) {
+            let Some(entry) = state.in_flight.first_entry() else {
+                break;
+            };
+            if entry.key().0 > now {
+                break;
+            }
+            let datagram = entry.remove();
+            // Like a real socket, truncate datagrams that don't fit the buffer
+            let len = datagram.contents.len().min(buf.len());
+            buf[..len].copy_from_slice(&datagram.contents[..len]);
+            *meta = RecvMeta::default();
+            meta.addr = datagram.source;
+            meta.len = len;
+            meta.stride = len;
+            meta.ecn = datagram.ecn;
+            meta.dst_ip = Some(destination.ip());
+            received += 1;
+        }
+
+        if received > 0 {
+            return Ok(received);
+        }
+        state.waker = Some(cx.waker().clone());
+        Err(state.in_flight.keys().next().map(|&(arrival, _)| arrival))
+    }
+}
+
+#[derive(Debug)]
+struct LinkState {
+    rng: StdRng,
+    /// Datagrams that haven't been received yet, keyed by arrival time and order of sending
+    in_flight: BTreeMap<(Instant, u64), Datagram>,
+    next_seq: u64,
+    /// Time at which the link has finished putting all datagrams sent so far on the wire
+    busy_until: Option<Instant>,
+    /// Arrival time of the last datagram that wasn't picked for reordering
+    last_arrival: Option<Instant>,
+    /// Task to wake when a datagram is sent
+    waker: Option<Waker>,
+}
+
+#[derive(Debug)]
+struct Datagram {
+    source: SocketAddr,
+    ecn: Option<EcnCodepoint>,
+    contents: Box<[u8]>,
+}
@@ -76,6 +76,13 @@ pub trait AsyncUdpSocket: Send + Sync + Debug + 'static {
     fn may_fragment(&self) -> bool {
         true
     }
+
+    /// Counters describing transmits the operating system did not accept
+    ///
+    /// Sockets that don't track these report zeroes.
+    fn send_stats(&self) -> udp::SendStats {
+        udp::SendStats::default()
+    }
 }
 
 /// An object for asynchronously writing to an associated [`AsyncUdpSocket`].
@@ -238,6 +245,12 @@ pub fn default_runtime() -> Option<Arc<dyn Runtime>> {
     None
 }
 
+mod manual;
+pub use manual::ManualDriver;
+
+mod memory;
+pub use memory::{LinkConfig, MemorySocket};
+
 #[cfg(feature = "runtime-tokio")]
 mod tokio;
 #[cfg(feature = "runtime-tokio")]
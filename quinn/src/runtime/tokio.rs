@@ -103,6 +103,10 @@ impl AsyncUdpSocket for UdpSocket {
         self.inner.may_fragment()
     }
 
+    fn send_stats(&self) -> udp::SendStats {
+        self.inner.send_stats()
+    }
+
     fn max_receive_segments(&self) -> usize {
         self.inner.gro_segments()
     }
@@ -6,7 +6,7 @@ use std::{
 };
 
 use bytes::Bytes;
-use proto::{ClosedStream, ConnectionError, FinishError, StreamId, Written};
+use proto::{ClosedStream, ConnectionError, FinishError, SendFlowControl, StreamId, Written};
 use thiserror::Error;
 
 use crate::{
@@ -233,6 +233,14 @@ impl SendStream {
         conn.inner.send_stream(self.stream).priority()
     }
 
+    /// Get the send-side flow control state of the stream
+    ///
+    /// See [`proto::Connection::send_flow_control()`].
+    pub fn flow_control(&self) -> Result<SendFlowControl, ClosedStream> {
+        let conn = self.conn.state.lock("SendStream::flow_control");
+        conn.inner.send_flow_control(self.stream)
+    }
+
     /// Completes when the peer stops the stream or reads the stream to completion
     ///
     /// Yields `Some` with the stop error code if the peer stops the stream. Yields `None` if the
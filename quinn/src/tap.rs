@@ -0,0 +1,399 @@
+use std::{
+    fmt,
+    io::{self, IoSliceMut, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, ready},
+};
+
+use udp::{EcnCodepoint, RecvMeta, Transmit};
+
+use crate::{
+    SystemTime, UNIX_EPOCH,
+    runtime::{AsyncUdpSocket, UdpSender},
+};
+
+/// Observes UDP datagrams sent and received through a [`TapSocket`]
+///
+/// Datagrams are reported as they appear on the wire, i.e. encrypted. Callbacks are invoked from
+/// the tasks driving the endpoint and its connections, so they should return quickly.
+pub trait PacketTap: Send + Sync + fmt::Debug + 'static {
+    /// Called for every datagram that was successfully handed to the socket for sending
+    fn sent(&self, datagram: &TappedDatagram<'_>);
+
+    /// Called for every datagram received from the socket
+    fn received(&self, datagram: &TappedDatagram<'_>);
+}
+
+/// A single UDP datagram observed by a [`PacketTap`]
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub struct TappedDatagram<'a> {
+    /// Address the datagram was sent from
+    ///
+    /// For sent datagrams, this is the local address of the socket, which may be unspecified.
+    pub source: SocketAddr,
+    /// Address the datagram was sent to
+    pub destination: SocketAddr,
+    /// Explicit congestion notification bits set on the datagram
+    pub ecn: Option<EcnCodepoint>,
+    /// UDP payload of the datagram
+    pub contents: &'a [u8],
+}
+
+/// An [`AsyncUdpSocket`] that reports all datagrams passing through it to a [`PacketTap`]
+///
+/// Wrap a socket in this before passing it to
+/// [`Endpoint::new_with_abstract_socket`](crate::Endpoint::new_with_abstract_socket) to capture
+/// traffic, for example with a [`PcapWriter`].
+#[derive(Debug)]
+pub struct TapSocket {
+    inner: Box<dyn AsyncUdpSocket>,
+    tap: Arc<dyn PacketTap>,
+}
+
+impl TapSocket {
+    /// Report all datagrams sent and received on `inner` to `tap`
+    pub fn new(inner: Box<dyn AsyncUdpSocket>, tap: Arc<dyn PacketTap>) -> Self {
+        Self { inner, tap }
+    }
+}
+
+impl AsyncUdpSocket for TapSocket {
+    fn create_sender(&self) -> Pin<Box<dyn UdpSender>> {
+        Box::pin(TapSender {
+            inner: self.inner.create_sender(),
+            tap: self.tap.clone(),
+            local_addr: self.inner.local_addr().ok(),
+        })
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &mut [IoSliceMut<'_>],
+        meta: &mut [RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        let n = ready!(self.inner.poll_recv(cx, bufs, meta))?;
+        let local_addr = self.inner.local_addr().ok();
+        for (meta, buf) in meta.iter().zip(bufs.iter()).take(n) {
+            let destination = match (meta.dst_ip, local_addr) {
+                (Some(ip), Some(local_addr)) => SocketAddr::new(ip, local_addr.port()),
+                (Some(ip), None) => SocketAddr::new(ip, 0),
+                (None, Some(local_addr)) => local_addr,
+                (None, None) => unspecified(meta.addr),
+            };
+            for contents in buf[..meta.len].chunks(meta.stride.max(1)) {
+                self.tap.received(&TappedDatagram {
+                    source: meta.addr,
+                    destination,
+                    ecn: meta.ecn,
+                    contents,
+                });
+            }
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn max_receive_segments(&self) -> usize {
+        self.inner.max_receive_segments()
+    }
+
+    fn may_fragment(&self) -> bool {
+        self.inner.may_fragment()
+    }
+
+    fn send_stats(&self) -> udp::SendStats {
+        self.inner.send_stats()
+    }
+}
+
+#[derive(Debug)]
+struct TapSender {
+    inner: Pin<Box<dyn UdpSender>>,
+    tap: Arc<dyn PacketTap>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl UdpSender for TapSender {
+    fn poll_send(
+        mut self: Pin<&mut Self>,
+        transmit: &Transmit<'_>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        ready!(self.inner.as_mut().poll_send(transmit, cx))?;
+        let source = match (transmit.src_ip, self.local_addr) {
+            (Some(ip), Some(local_addr)) => SocketAddr::new(ip, local_addr.port()),
+            (Some(ip), None) => SocketAddr::new(ip, 0),
+            (None, Some(local_addr)) => local_addr,
+            (None, None) => unspecified(transmit.destination),
+        };
+        let segment_size = transmit.segment_size.unwrap_or(transmit.contents.len());
+        for contents in transmit.contents.chunks(segment_size.max(1)) {
+            self.tap.sent(&TappedDatagram {
+                source,
+                destination: transmit.destination,
+                ecn: transmit.ecn,
+                contents,
+            });
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn max_transmit_segments(&self) -> usize {
+        self.inner.max_transmit_segments()
+    }
+}
+
+fn unspecified(like: SocketAddr) -> SocketAddr {
+    match like {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    }
+}
+
+/// A [`PacketTap`] that writes datagrams to a pcapng capture file
+///
+/// Datagrams are recorded with synthesized IP and UDP headers, which can be opened with e.g.
+/// Wireshark. UDP checksums are left empty.
+///
+/// When the `rustls` feature is enabled, this also implements [`rustls::KeyLog`]. Setting it as
+/// the `key_log` of the rustls client or server configuration embeds the TLS secrets in the
+/// capture as decryption secrets blocks, allowing the QUIC packets to be decrypted without a
+/// separate key log file.
+///
+/// Failures to write are logged and otherwise ignored.
+pub struct PcapWriter {
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl PcapWriter {
+    /// Start a capture written to `output`
+    ///
+    /// Fails if the section header can't be written. Consider wrapping files in an
+    /// [`io::BufWriter`]; the capture is flushed when the `PcapWriter` is dropped.
+    pub fn new(output: impl Write + Send + 'static) -> io::Result<Self> {
+        let mut output = Box::new(output) as Box<dyn Write + Send>;
+        let mut block = Vec::new();
+        // Section header block
+        block.extend_from_slice(&0x1a2b_3c4d_u32.to_le_bytes());
+        block.extend_from_slice(&1u16.to_le_bytes());
+        block.extend_from_slice(&0u16.to_le_bytes());
+        // Section length unknown
+        block.extend_from_slice(&(-1i64).to_le_bytes());
+        write_block(&mut output, 0x0a0d_0d0a, &block)?;
+
+        // Interface description block
+        block.clear();
+        block.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        block.extend_from_slice(&0u16.to_le_bytes());
+        // No snapshot length limit
+        block.extend_from_slice(&0u32.to_le_bytes());
+        write_block(&mut output, 0x0000_0001, &block)?;
+
+        Ok(Self {
+            output: Mutex::new(output),
+        })
+    }
+
+    fn write_packet(&self, datagram: &TappedDatagram<'_>) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let packet = ip_packet(datagram);
+        let mut block = Vec::with_capacity(20 + packet.len() + 3);
+        // Interface ID
+        block.extend_from_slice(&0u32.to_le_bytes());
+        block.extend_from_slice(&((timestamp >> 32) as u32).to_le_bytes());
+        block.extend_from_slice(&(timestamp as u32).to_le_bytes());
+        // Captured and original length
+        block.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        block.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        block.extend_from_slice(&packet);
+        // Enhanced packet block
+        self.write(0x0000_0006, &block);
+    }
+
+    #[cfg(any(feature = "rustls-aws-lc-rs", feature = "rustls-ring"))]
+    fn write_secret(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        use fmt::Write as _;
+
+        let mut line =
+            String::with_capacity(label.len() + 2 * (client_random.len() + secret.len()) + 3);
+        line.push_str(label);
+        line.push(' ');
+        for byte in client_random {
+            let _ = write!(line, "{byte:02x}");
+        }
+        line.push(' ');
+        for byte in secret {
+            let _ = write!(line, "{byte:02x}");
+        }
+        line.push('\n');
+        let mut block = Vec::with_capacity(8 + line.len() + 3);
+        // TLS key log
+        block.extend_from_slice(&0x544c_534b_u32.to_le_bytes());
+        block.extend_from_slice(&(line.len() as u32).to_le_bytes());
+        block.extend_from_slice(line.as_bytes());
+        // Decryption secrets block
+        self.write(0x0000_000a, &block);
+    }
+
+    fn write(&self, block_type: u32, body: &[u8]) {
+        let mut output = self.output.lock().unwrap();
+        if let Err(e) = write_block(&mut *output, block_type, body) {
+            tracing::warn!("failed to write packet capture: {e}");
+        }
+    }
+}
+
+impl PacketTap for PcapWriter {
+    fn sent(&self, datagram: &TappedDatagram<'_>) {
+        self.write_packet(datagram);
+    }
+
+    fn received(&self, datagram: &TappedDatagram<'_>) {
+        self.write_packet(datagram);
+    }
+}
+
+#[cfg(any(feature = "rustls-aws-lc-rs", feature = "rustls-ring"))]
+impl rustls::KeyLog for PcapWriter {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        self.write_secret(label, client_random, secret);
+    }
+}
+
+impl Drop for PcapWriter {
+    fn drop(&mut self) {
+        if let Ok(output) = self.output.get_mut() {
+            let _ = output.flush();
+        }
+    }
+}
+
+impl fmt::Debug for PcapWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapWriter").finish_non_exhaustive()
+    }
+}
+
+/// Raw IPv4 or IPv6 packets, distinguished by the version field
+const LINKTYPE_RAW: u16 = 101;
+
+/// Write a pcapng block, padding `body` to a multiple of 4 bytes
+fn write_block(output: &mut dyn Write, block_type: u32, body: &[u8]) -> io::Result<()> {
+    let padding = (4 - body.len() % 4) % 4;
+    let total_len = (12 + body.len() + padding) as u32;
+    output.write_all(&block_type.to_le_bytes())?;
+    output.write_all(&total_len.to_le_bytes())?;
+    output.write_all(body)?;
+    output.write_all(&[0; 3][..padding])?;
+    output.write_all(&total_len.to_le_bytes())
+}
+
+/// Synthesize an IP packet carrying `datagram`
+fn ip_packet(datagram: &TappedDatagram<'_>) -> Vec<u8> {
+    let udp_len = 8 + datagram.contents.len();
+    let ecn = datagram.ecn.map_or(0, |ecn| ecn as u8);
+    let mut packet = Vec::with_capacity(40 + udp_len);
+    match (
+        to_canonical(datagram.source.ip()),
+        to_canonical(datagram.destination.ip()),
+    ) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            let mut header = [0; 20];
+            // Version 4, 5 words of header
+            header[0] = 0x45;
+            header[1] = ecn;
+            header[2..4].copy_from_slice(&((20 + udp_len) as u16).to_be_bytes());
+            // Don't fragment
+            header[6] = 0x40;
+            // TTL
+            header[8] = 64;
+            header[9] = UDP;
+            header[12..16].copy_from_slice(&source.octets());
+            header[16..20].copy_from_slice(&destination.octets());
+            let checksum = ipv4_checksum(&header);
+            header[10..12].copy_from_slice(&checksum.to_be_bytes());
+            packet.extend_from_slice(&header);
+        }
+        (source, destination) => {
+            let mut header = [0; 40];
+            // Version 6, traffic class, no flow label
+            header[0] = 0x60 | (ecn >> 4);
+            header[1] = ecn << 4;
+            header[4..6].copy_from_slice(&(udp_len as u16).to_be_bytes());
+            header[6] = UDP;
+            // Hop limit
+            header[7] = 64;
+            header[8..24].copy_from_slice(&to_ipv6(source).octets());
+            header[24..40].copy_from_slice(&to_ipv6(destination).octets());
+            packet.extend_from_slice(&header);
+        }
+    }
+    packet.extend_from_slice(&datagram.source.port().to_be_bytes());
+    packet.extend_from_slice(&datagram.destination.port().to_be_bytes());
+    packet.extend_from_slice(&(udp_len as u16).to_be_bytes());
+    // No checksum
+    packet.extend_from_slice(&[0; 2]);
+    packet.extend_from_slice(datagram.contents);
+    packet
+}
+
+const UDP: u8 = 17;
+
+fn ipv4_checksum(header: &[u8]) -> u16 {
+    let mut sum = header
+        .chunks(2)
+        .map(|word| u32::from(u16::from_be_bytes([word[0], word[1]])))
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+fn to_canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => IpAddr::V4(ip),
+            None => IpAddr::V6(ip),
+        },
+        ip => ip,
+    }
+}
+
+fn to_ipv6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv4_header_checksum() {
+        let packet = ip_packet(&TappedDatagram {
+            source: "192.0.2.1:4433".parse().unwrap(),
+            destination: "[::ffff:192.0.2.2]:443".parse().unwrap(),
+            ecn: Some(EcnCodepoint::Ect0),
+            contents: b"hello",
+        });
+        assert_eq!(packet.len(), 20 + 8 + 5);
+        assert_eq!(packet[0], 0x45);
+        assert_eq!(packet[1], EcnCodepoint::Ect0 as u8);
+        // A valid header sums to zero
+        assert_eq!(ipv4_checksum(&packet[..20]), 0);
+        assert_eq!(&packet[28..], b"hello");
+    }
+}
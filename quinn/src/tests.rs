@@ -7,22 +7,26 @@ use rustls::crypto::ring::default_provider;
 
 use std::{
     convert::TryInto,
+    fmt,
     future::Future,
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
-    pin::pin,
+    pin::{Pin, pin},
     str,
     sync::{
-        Arc,
-        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
     },
     task::{Context, Poll, RawWaker, RawWakerVTable, Waker},
 };
 
 use crate::runtime::TokioRuntime;
-use crate::{Duration, Instant};
+use crate::{Duration, Instant, connection::MAX_QUEUED_EVENTS, endpoint::SendMonitor};
 use bytes::Bytes;
-use proto::{RandomConnectionIdGenerator, crypto::rustls::QuicClientConfig};
+use proto::{
+    ConnectionHandle, RandomConnectionIdGenerator,
+    crypto::rustls::{KeyLogWriter, QuicClientConfig, QuicServerConfig},
+};
 use rand::{Rng, SeedableRng, rngs::StdRng};
 use rustls::{
     RootCertStore,
@@ -37,7 +41,14 @@ use tracing::{error_span, info};
 use tracing_futures::Instrument as _;
 use tracing_subscriber::EnvFilter;
 
-use super::{ClientConfig, Endpoint, EndpointConfig, RecvStream, SendStream, TransportConfig};
+use super::{
+    AsyncUdpSocket, ClientConfig, ConnectRacingError, ConnectToError, ConnectionPool, Dir,
+    Endpoint, EndpointConfig, LinkConfig, ManualDriver, MemorySocket, MessageError,
+    MessageReceiver, MessageSender, MessageStream, PcapWriter, PoolConfig, PoolError,
+    PriorityClass, ReadFlowDatagramError, ReconnectConfig, ReconnectError, RecvStream,
+    ResilientConnection, Resolver, RpcClient, RpcError, RpcServer, SendStream, ServiceRecord,
+    Socks5Tunnel, TapSocket, TransportConfig, TransportEvent, TunnelSocket, UdpSender, blocking,
+};
 
 #[test]
 fn handshake_timeout() {
@@ -72,7 +83,7 @@ fn handshake_timeout() {
             .unwrap()
             .await
         {
-            Err(crate::ConnectionError::TimedOut) => {}
+            Err(crate::ConnectionError::TimedOut(_)) => {}
             Err(e) => panic!("unexpected error: {e:?}"),
             Ok(_) => panic!("unexpected success"),
         }
@@ -137,6 +148,37 @@ fn local_addr() {
     );
 }
 
+#[test]
+fn socket_config() {
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let probe = socket2::SockRef::from(&socket);
+    let default_size = probe.recv_buffer_size().unwrap();
+    let size = 2 * default_size;
+
+    let mut socket_config = crate::SocketConfig::default();
+    socket_config.recv_buffer_size(Some(size));
+    let mut config = EndpointConfig::default();
+    config.socket_config(socket_config);
+
+    let probe = socket.try_clone().unwrap();
+    let runtime = rt_basic();
+    let ep = {
+        let _guard = runtime.enter();
+        Endpoint::new(config, None, socket, Arc::new(TokioRuntime)).unwrap()
+    };
+    // The kernel may round or double the requested size
+    assert!(socket2::SockRef::from(&probe).recv_buffer_size().unwrap() >= size);
+
+    // The options are also applied when switching sockets
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let probe = socket.try_clone().unwrap();
+    {
+        let _guard = runtime.enter();
+        ep.rebind(socket).unwrap();
+    }
+    assert!(socket2::SockRef::from(&probe).recv_buffer_size().unwrap() >= size);
+}
+
 #[test]
 fn read_after_close() {
     let _guard = subscribe();
@@ -291,6 +333,34 @@ impl EndpointFactory {
     }
 
     fn endpoint_with_config(&self, transport_config: TransportConfig) -> Endpoint {
+        let (server_config, client_config) = self.configs(transport_config);
+        let endpoint = Endpoint::new(
+            self.endpoint_config.clone(),
+            Some(server_config),
+            UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap(),
+            Arc::new(TokioRuntime),
+        )
+        .unwrap();
+        endpoint.set_default_client_config(client_config);
+
+        endpoint
+    }
+
+    fn endpoint_with_socket(&self, socket: Box<dyn AsyncUdpSocket>) -> Endpoint {
+        let (server_config, client_config) = self.configs(TransportConfig::default());
+        let endpoint = Endpoint::new_with_abstract_socket(
+            self.endpoint_config.clone(),
+            Some(server_config),
+            socket,
+            Arc::new(TokioRuntime),
+        )
+        .unwrap();
+        endpoint.set_default_client_config(client_config);
+
+        endpoint
+    }
+
+    fn configs(&self, transport_config: TransportConfig) -> (crate::ServerConfig, ClientConfig) {
         let key = PrivateKeyDer::Pkcs8(self.cert.signing_key.serialize_der().into());
         let transport_config = Arc::new(transport_config);
         let mut server_config =
@@ -299,18 +369,10 @@ impl EndpointFactory {
 
         let mut roots = RootCertStore::empty();
         roots.add(self.cert.cert.der().clone()).unwrap();
-        let endpoint = Endpoint::new(
-            self.endpoint_config.clone(),
-            Some(server_config),
-            UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap(),
-            Arc::new(TokioRuntime),
-        )
-        .unwrap();
         let mut client_config = ClientConfig::with_root_certificates(Arc::new(roots)).unwrap();
         client_config.transport_config(transport_config);
-        endpoint.set_default_client_config(client_config);
 
-        endpoint
+        (server_config, client_config)
     }
 }
 
@@ -793,10 +855,117 @@ async fn stream_id_flow_control() {
 }
 
 #[tokio::test]
-async fn two_datagram_readers() {
+async fn probe_rtt() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let _server = server.unwrap();
+
+    let pings = client.stats().frame_tx.ping;
+    let (a, b) = tokio::join!(client.probe_rtt(), client.probe_rtt());
+    assert!(a.unwrap() < Duration::from_secs(1));
+    assert!(b.is_ok());
+    // Concurrent calls share a probe
+    assert_eq!(client.stats().frame_tx.ping, pings + 1);
+
+    client.close(0u32.into(), b"done");
+    assert_eq!(
+        client.probe_rtt().await,
+        Err(crate::ConnectionError::LocallyClosed)
+    );
+}
+
+#[tokio::test]
+async fn validate_path() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let _server = server.unwrap();
+
+    let challenges = client.stats().frame_tx.path_challenge;
+    let (a, b) = tokio::join!(client.validate_path(), client.validate_path());
+    assert_eq!(a, Ok(true));
+    assert_eq!(b, Ok(true));
+    // Concurrent calls share a validation
+    assert_eq!(client.stats().frame_tx.path_challenge, challenges + 1);
+
+    client.close(0u32.into(), b"done");
+    assert_eq!(
+        client.validate_path().await,
+        Err(crate::ConnectionError::LocallyClosed)
+    );
+}
+
+#[tokio::test]
+async fn refuse_with_reason() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let client = endpoint
+        .connect(endpoint.local_addr().unwrap(), "localhost")
+        .unwrap();
+    let server = async {
+        endpoint
+            .accept()
+            .await
+            .unwrap()
+            .refuse_with(42u32.into(), b"over quota");
+    };
+    let (client, ()) = tokio::join!(client, server);
+    let err = match client {
+        Ok(conn) => conn.closed().await,
+        Err(e) => e,
+    };
+    let crate::ConnectionError::ApplicationClosed(close) = err else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(close.error_code, 42u32.into());
+    assert_eq!(&close.reason[..], b"over quota");
+}
+
+#[tokio::test]
+async fn early_server_name() {
     let _guard = subscribe();
     let endpoint = endpoint();
 
+    let client = endpoint
+        .connect(endpoint.local_addr().unwrap(), "localhost")
+        .unwrap();
+    let server = async {
+        let mut connecting = endpoint.accept().await.unwrap().accept().unwrap();
+        assert_eq!(
+            connecting.server_name().await.unwrap().as_deref(),
+            Some("localhost")
+        );
+        assert_eq!(connecting.alpn_protocol().await.unwrap(), None);
+        connecting.await
+    };
+    let (client, server) = tokio::join!(client, server);
+    client.unwrap();
+    server.unwrap();
+}
+
+#[tokio::test]
+async fn connection_events() {
+    let _guard = subscribe();
+    let mut cfg = TransportConfig::default();
+    cfg.max_concurrent_uni_streams(0u32.into());
+    let endpoint = endpoint_with_config(cfg);
+
     let (client, server) = tokio::join!(
         endpoint
             .connect(endpoint.local_addr().unwrap(), "localhost")
@@ -805,27 +974,1504 @@ async fn two_datagram_readers() {
     );
     let client = client.unwrap();
     let server = server.unwrap();
+    let mut client_events = client.events();
+    let mut server_events = server.events();
 
-    let done = tokio::sync::Notify::new();
-    let (a, b, ()) = tokio::join!(
-        async {
-            let x = client.read_datagram().await.unwrap();
-            done.notify_waiters();
-            x
-        },
+    client.force_key_update();
+    client.send_datagram(Bytes::from_static(b"ping")).unwrap();
+    assert_eq!(server.read_datagram().await.unwrap(), &b"ping"[..]);
+    while server_events.next().await.unwrap() != (TransportEvent::KeysUpdated { remote: true }) {}
+
+    // The server doesn't allow any unidirectional streams
+    timeout(Duration::from_millis(100), client.open_uni())
+        .await
+        .unwrap_err();
+    let mut blocked = false;
+    let mut keys_updated = false;
+    while !(blocked && keys_updated) {
+        match client_events.next().await.unwrap() {
+            TransportEvent::StreamsBlocked { dir } => {
+                assert_eq!(dir, Dir::Uni);
+                blocked = true;
+            }
+            TransportEvent::KeysUpdated { remote } => {
+                assert!(!remote);
+                keys_updated = true;
+            }
+            _ => {}
+        }
+    }
+
+    client.close(0u32.into(), b"done");
+    while client_events.next().await.is_some() {}
+    // Subscriptions made after closing end immediately
+    assert!(client.events().next().await.is_none());
+}
+
+#[tokio::test]
+async fn connection_events_bounded() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+    let mut server_events = server.events();
+
+    // Each stream opened by the client is announced to the server, which doesn't poll its events
+    const STREAMS: usize = 2 * MAX_QUEUED_EVENTS;
+    for _ in 0..STREAMS {
+        let mut send = client.open_uni().await.unwrap();
+        send.write_all(b"hi").await.unwrap();
+        send.finish().unwrap();
+    }
+    for _ in 0..STREAMS {
+        server.accept_uni().await.unwrap();
+    }
+
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut queued = 0;
+    while let Poll::Ready(event) = server_events.poll_next(&mut cx) {
+        event.unwrap();
+        queued += 1;
+    }
+    assert_eq!(queued, MAX_QUEUED_EVENTS);
+    assert!(server_events.missed() >= (STREAMS - MAX_QUEUED_EVENTS) as u64);
+}
+
+#[tokio::test]
+async fn message_stream() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    let large = gen_data(100_000, 11);
+    join!(
         async {
-            let x = client.read_datagram().await.unwrap();
-            done.notify_waiters();
-            x
+            let (send, recv) = server.accept_bi().await.unwrap();
+            let mut stream = MessageStream::new(send, recv);
+            while let Some(message) = stream.recv().await.unwrap() {
+                stream.send(&message).await.unwrap();
+            }
+            stream.split().0.finish().unwrap();
+
+            let mut recv = MessageReceiver::new(server.accept_uni().await.unwrap());
+            // Exceeds the default limit
+            assert_eq!(
+                recv.recv().await,
+                Err(MessageError::TooLarge(large.len() as u64))
+            );
         },
         async {
-            server.send_datagram(b"one"[..].into()).unwrap();
-            done.notified().await;
-            server.send_datagram_wait(b"two"[..].into()).await.unwrap();
+            let (send, recv) = client.open_bi().await.unwrap();
+            let mut stream = MessageStream::new(send, recv);
+            for message in [&b"hello"[..], b"", &[0xff; 1000]] {
+                stream.send(message).await.unwrap();
+                assert_eq!(stream.recv().await.unwrap().unwrap(), message);
+            }
+            #[cfg(feature = "serde")]
+            {
+                stream.send_json(&("hello", 42)).await.unwrap();
+                let echoed = stream.recv_json::<(String, u32)>().await.unwrap();
+                assert_eq!(echoed, Some(("hello".into(), 42)));
+            }
+            let (mut send, mut recv) = stream.split();
+            send.finish().unwrap();
+            assert_eq!(recv.recv().await, Ok(None));
+
+            let mut send = MessageSender::new(client.open_uni().await.unwrap());
+            assert_eq!(
+                send.send(&large).await,
+                Err(MessageError::TooLarge(large.len() as u64))
+            );
+            send.set_max_message_size(large.len());
+            let _ = send.send(&large).await;
         }
     );
-    assert!(*a == *b"one" || *b == *b"one");
-    assert!(*a == *b"two" || *b == *b"two");
+}
+
+#[tokio::test]
+async fn rpc() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let mut client = RpcClient::new(client.unwrap());
+    let server = RpcServer::new(server.unwrap());
+
+    let (cancelled_send, mut cancelled_recv) = tokio::sync::mpsc::unbounded_channel::<()>();
+    tokio::spawn(async move {
+        server
+            .serve(move |request| {
+                let cancelled = cancelled_send.clone();
+                async move {
+                    match &request[..] {
+                        b"reject" => Err(7u32.into()),
+                        b"hang" => {
+                            // Reports when the handler is dropped
+                            struct Guard(tokio::sync::mpsc::UnboundedSender<()>);
+                            impl Drop for Guard {
+                                fn drop(&mut self) {
+                                    let _ = self.0.send(());
+                                }
+                            }
+                            let _guard = Guard(cancelled);
+                            std::future::pending().await
+                        }
+                        _ => Ok(request.to_ascii_uppercase().into()),
+                    }
+                }
+            })
+            .await
+    });
+
+    let (a, b) = tokio::join!(client.call(b"hello"), client.call(b"world"));
+    assert_eq!(a.unwrap(), &b"HELLO"[..]);
+    assert_eq!(b.unwrap(), &b"WORLD"[..]);
+    assert_eq!(
+        client.call(b"reject").await,
+        Err(RpcError::Rejected(7u32.into()))
+    );
+
+    client.set_timeout(Some(Duration::from_millis(50)));
+    assert_eq!(client.call(b"hang").await, Err(RpcError::TimedOut));
+    // The server drops the handler of the cancelled call
+    cancelled_recv.recv().await.unwrap();
+    assert_eq!(client.call(b"again").await.unwrap(), &b"AGAIN"[..]);
+}
+
+#[tokio::test]
+async fn connection_pool() {
+    let _guard = subscribe();
+    let factory = EndpointFactory::new();
+    let (_, client_config) = factory.configs(TransportConfig::default());
+    let endpoint = factory.endpoint();
+    let addr = endpoint.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let server = tokio::spawn({
+        let endpoint = endpoint.clone();
+        let accepted = accepted.clone();
+        async move {
+            while let Some(incoming) = endpoint.accept().await {
+                accepted.fetch_add(1, Ordering::Relaxed);
+                let conn = incoming.await.unwrap();
+                tokio::spawn(async move { conn.closed().await });
+            }
+        }
+    });
+
+    let mut config = PoolConfig::default();
+    config
+        .client_config(b"test".to_vec(), client_config)
+        .max_concurrent_per_host(Some(1))
+        .max_idle(Some(Duration::ZERO));
+    let pool = ConnectionPool::new(endpoint.clone(), config);
+    assert_eq!(
+        pool.get(addr, "localhost", b"other").await.unwrap_err(),
+        PoolError::UnknownProtocol
+    );
+
+    let first = pool.get(addr, "localhost", b"test").await.unwrap();
+    let id = first.stable_id();
+    // Only one connection to the host may be held at a time
+    timeout(
+        Duration::from_millis(100),
+        pool.get(addr, "localhost", b"test"),
+    )
+    .await
+    .unwrap_err();
+    let (second, ()) = tokio::join!(pool.get(addr, "localhost", b"test"), async { drop(first) });
+    let second = second.unwrap();
+    assert_eq!(second.stable_id(), id);
+    assert_eq!(accepted.load(Ordering::Relaxed), 1);
+
+    // Connections are only evicted when nobody holds them
+    pool.evict_idle();
+    assert_eq!(pool.len(), 1);
+    let conn = (*second).clone();
+    drop(second);
+    pool.evict_idle();
+    assert!(pool.is_empty());
+    assert!(conn.close_reason().is_some());
+
+    let third = pool.get(addr, "localhost", b"test").await.unwrap();
+    assert_ne!(third.stable_id(), id);
+    assert_eq!(accepted.load(Ordering::Relaxed), 2);
+    drop(third);
+    server.abort();
+}
+
+#[tokio::test]
+async fn connect_racing() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let server_addr = endpoint.local_addr().unwrap();
+    // Swallows the first attempt's packets without ever responding
+    let silent = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap();
+    let addrs = [
+        silent.local_addr().unwrap(),
+        server_addr,
+        // Can't be reached from an IPv4 endpoint
+        SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), server_addr.port()),
+    ];
+
+    let start = Instant::now();
+    let (client, server) = tokio::join!(endpoint.connect_racing(addrs, "localhost"), async {
+        endpoint.accept().await.unwrap().await
+    });
+    let client = client.unwrap();
+    let server = server.unwrap();
+    assert_eq!(client.remote_address(), server_addr);
+    assert!(start.elapsed() >= Duration::from_millis(250));
+    drop((client, server));
+
+    assert_eq!(
+        endpoint
+            .connect_racing(None, "localhost")
+            .await
+            .unwrap_err(),
+        ConnectRacingError::NoAddresses
+    );
+}
+
+/// Serves service records for `localhost` pointing at `port`, and only resolves that port
+#[derive(Debug)]
+struct StaticResolver {
+    port: u16,
+}
+
+impl Resolver for StaticResolver {
+    fn lookup_ip<'a>(
+        &'a self,
+        host: &'a str,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<SocketAddr>>> + Send + 'a>> {
+        let result = if host == "localhost" && port == self.port {
+            Ok(vec![SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port)])
+        } else {
+            Err(io::ErrorKind::NotFound.into())
+        };
+        Box::pin(async move { result })
+    }
+
+    fn lookup_service<'a>(
+        &'a self,
+        host: &'a str,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Vec<ServiceRecord>>> + Send + 'a>> {
+        let mut records = Vec::new();
+        if host == "localhost" {
+            records.push(ServiceRecord {
+                priority: 1,
+                port: Some(self.port),
+                ..ServiceRecord::default()
+            });
+        }
+        Box::pin(async { Ok(records) })
+    }
+}
+
+#[tokio::test]
+async fn connect_to() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let port = endpoint.local_addr().unwrap().port();
+    endpoint.set_resolver(Arc::new(StaticResolver { port }));
+
+    // The port is only discovered through the service record
+    let (client, server) = tokio::join!(endpoint.connect_to("localhost:443"), async {
+        endpoint.accept().await.unwrap().await
+    });
+    assert_eq!(client.unwrap().remote_address().port(), port);
+    drop(server.unwrap());
+
+    for target in ["localhost", "localhost:port", ":443", "[::1:443", "::1:443"] {
+        assert_eq!(
+            endpoint.connect_to(target).await.unwrap_err(),
+            ConnectToError::InvalidTarget
+        );
+    }
+    assert!(matches!(
+        endpoint.connect_to("unknown.localhost:443").await,
+        Err(ConnectToError::Resolve(_))
+    ));
+}
+
+#[tokio::test]
+async fn resilient_connection() {
+    let _guard = subscribe();
+    let factory = EndpointFactory::new();
+    let server = factory.endpoint();
+    let client = factory.endpoint();
+    let server_addr = server.local_addr().unwrap();
+    let (_, client_config) = factory.configs(TransportConfig::default());
+
+    let mut config = ReconnectConfig::default();
+    config.initial_backoff(Duration::from_millis(10));
+    let mut conn =
+        ResilientConnection::new(client, client_config, server_addr, "localhost", config);
+    let established = Arc::new(AtomicUsize::new(0));
+    conn.set_on_reconnect({
+        let established = established.clone();
+        move |_| {
+            established.fetch_add(1, Ordering::Relaxed);
+            async {}
+        }
+    });
+
+    let (first, server_conn) = tokio::join!(conn.connection(), async {
+        server.accept().await.unwrap().await.unwrap()
+    });
+    let first = first.unwrap();
+    assert_eq!(
+        conn.connection().await.unwrap().stable_id(),
+        first.stable_id()
+    );
+    assert_eq!(established.load(Ordering::Relaxed), 1);
+
+    server_conn.close(0u32.into(), b"restart");
+    first.closed().await;
+    let (second, _server_conn) = tokio::join!(conn.connection(), async {
+        server.accept().await.unwrap().await.unwrap()
+    });
+    assert_ne!(second.unwrap().stable_id(), first.stable_id());
+    assert_eq!(established.load(Ordering::Relaxed), 2);
+
+    conn.close(0u32.into(), b"done").await;
+    assert_eq!(conn.connection().await.unwrap_err(), ReconnectError::Closed);
+}
+
+#[tokio::test]
+async fn resilient_connection_close_while_reconnecting() {
+    let _guard = subscribe();
+    let factory = EndpointFactory::new();
+    let client = factory.endpoint();
+    // Nothing answers on this socket, so attempts time out quickly
+    let unreachable = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let mut transport = TransportConfig::default();
+    transport.max_idle_timeout(Some(Duration::from_millis(50).try_into().unwrap()));
+    let (_, client_config) = factory.configs(transport);
+
+    let conn = ResilientConnection::new(
+        client,
+        client_config,
+        unreachable.local_addr().unwrap(),
+        "localhost",
+        ReconnectConfig::default(),
+    );
+    let (result, ()) = timeout(Duration::from_secs(5), async {
+        tokio::join!(conn.connection(), async {
+            // Let a few attempts fail, so that reconnecting is backing off
+            sleep(Duration::from_millis(300)).await;
+            conn.close(0u32.into(), b"done").await;
+        })
+    })
+    .await
+    .unwrap();
+    assert_eq!(result.unwrap_err(), ReconnectError::Closed);
+}
+
+#[tokio::test]
+async fn send_ready() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    let initial = client.send_budget();
+    assert!(initial > 0);
+    let mut send = client.open_uni().await.unwrap();
+    send.write_all(&vec![0; initial as usize]).await.unwrap();
+    tokio::spawn(async move {
+        let mut recv = server.accept_uni().await.unwrap();
+        recv.read_to_end(usize::MAX).await.unwrap();
+    });
+    // The budget recovers once the data is acknowledged
+    timeout(Duration::from_secs(10), client.send_ready(initial))
+        .await
+        .unwrap()
+        .unwrap();
+
+    client.close(0u32.into(), b"done");
+    assert_eq!(
+        client.send_ready(u64::MAX).await.unwrap_err(),
+        crate::ConnectionError::LocallyClosed
+    );
+}
+
+#[tokio::test]
+async fn transmit_quantum() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    endpoint.set_transmit_quantum(1);
+    let server_addr = endpoint.local_addr().unwrap();
+    let server = endpoint.clone();
+    tokio::spawn(async move {
+        while let Some(incoming) = server.accept().await {
+            tokio::spawn(async move {
+                let conn = incoming.await.unwrap();
+                while let Ok(streams) = conn.accept_bi().await {
+                    tokio::spawn(echo(streams));
+                }
+            });
+        }
+    });
+
+    // Bulk transfers take turns, and don't prevent a concurrent connection from completing small
+    // exchanges
+    let mut bulk_tasks = Vec::new();
+    for seed in 0..2 {
+        let bulk = endpoint
+            .connect(server_addr, "localhost")
+            .unwrap()
+            .await
+            .unwrap();
+        let data = gen_data(1024 * 1024, seed);
+        bulk_tasks.push(tokio::spawn(async move {
+            let (mut send, mut recv) = bulk.open_bi().await.unwrap();
+            send.write_all(&data).await.unwrap();
+            send.finish().unwrap();
+            assert_eq!(recv.read_to_end(usize::MAX).await.unwrap(), data);
+        }));
+    }
+    let interactive = endpoint
+        .connect(server_addr, "localhost")
+        .unwrap()
+        .await
+        .unwrap();
+    for i in 0..10u8 {
+        let (mut send, mut recv) = interactive.open_bi().await.unwrap();
+        send.write_all(&[i]).await.unwrap();
+        send.finish().unwrap();
+        assert_eq!(recv.read_to_end(1).await.unwrap(), [i]);
+    }
+    for bulk_task in bulk_tasks {
+        timeout(Duration::from_secs(30), bulk_task)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
+
+#[test]
+fn transmit_turns() {
+    let monitor = SendMonitor::default();
+    let (a, b, c) = (
+        ConnectionHandle(0),
+        ConnectionHandle(1),
+        ConnectionHandle(2),
+    );
+    let (wake_a, count_a) = new_count_waker();
+    let (wake_b, count_b) = new_count_waker();
+    let (wake_c, count_c) = new_count_waker();
+
+    // A connection with a backlog keeps sending while no other one is waiting
+    assert!(monitor.yield_turn(a, &wake_a));
+    assert!(monitor.yield_turn(a, &wake_a));
+    assert!(!monitor.yield_turn(b, &wake_b));
+    assert!(!monitor.yield_turn(c, &wake_c));
+    assert!(!monitor.has_turn(b));
+
+    // Turns are handed on in order, one quantum at a time
+    assert!(!monitor.yield_turn(a, &wake_a));
+    assert_eq!((count_b.wakes(), count_c.wakes()), (1, 0));
+    assert!(monitor.has_turn(b) && !monitor.has_turn(a) && !monitor.has_turn(c));
+    assert!(!monitor.yield_turn(b, &wake_b));
+    assert_eq!(count_c.wakes(), 1);
+    assert!(monitor.has_turn(c));
+
+    // Connections that are done sending hand on the turn, or leave the queue
+    monitor.release_turn(a);
+    monitor.release_turn(c);
+    assert_eq!((count_a.wakes(), count_b.wakes()), (0, 2));
+    assert!(monitor.has_turn(b));
+    monitor.release_turn(b);
+    assert!(monitor.yield_turn(c, &wake_c));
+}
+
+#[cfg(feature = "metrics")]
+#[tokio::test]
+async fn metrics_registry() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let (client, server) = (client.unwrap(), server.unwrap());
+
+    let recorder = CapturingRecorder::default();
+    let mut registry = crate::MetricsRegistry::new();
+    registry.set_per_connection_labels(true);
+    registry.register_endpoint("test", endpoint.clone());
+    registry.register_connection(&client);
+    ::metrics::with_local_recorder(&recorder, || registry.record());
+    assert_eq!(
+        recorder.get("quinn_endpoint_handshakes{endpoint=test,outcome=outgoing}"),
+        Some(1)
+    );
+    let labels = format!(
+        "connection={},remote={}",
+        client.stable_id(),
+        client.remote_address()
+    );
+    assert!(
+        recorder
+            .get(&format!("quinn_connection_rtt_seconds{{{labels}}}"))
+            .is_some()
+    );
+    // Sums and per-connection series are separate families
+    let sent = client.stats().udp_tx.bytes;
+    assert_eq!(recorder.get("quinn_connections_sent_bytes"), Some(sent));
+    assert_eq!(
+        recorder.get(&format!("quinn_connection_sent_bytes{{{labels}}}")),
+        Some(sent)
+    );
+
+    // The registry doesn't keep connections open
+    drop(client);
+    timeout(Duration::from_secs(5), server.closed())
+        .await
+        .unwrap();
+
+    // Dropped connections keep contributing to the sums
+    ::metrics::with_local_recorder(&recorder, || registry.record());
+    assert!(recorder.get("quinn_connections_sent_bytes").unwrap() >= sent);
+}
+
+/// A [`metrics::Recorder`](::metrics::Recorder) keeping the latest value of each series
+#[cfg(feature = "metrics")]
+#[derive(Default)]
+struct CapturingRecorder(
+    Mutex<std::collections::HashMap<String, Arc<std::sync::atomic::AtomicU64>>>,
+);
+
+#[cfg(feature = "metrics")]
+impl CapturingRecorder {
+    /// Counter value or gauge bits of the series `name{label=value,...}`
+    fn get(&self, key: &str) -> Option<u64> {
+        let series = self.0.lock().unwrap();
+        Some(series.get(key)?.load(Ordering::Relaxed))
+    }
+
+    fn register(&self, key: &::metrics::Key) -> Arc<std::sync::atomic::AtomicU64> {
+        let mut name = key.name().to_owned();
+        let labels = key
+            .labels()
+            .map(|label| format!("{}={}", label.key(), label.value()))
+            .collect::<Vec<_>>();
+        if !labels.is_empty() {
+            name = format!("{name}{{{}}}", labels.join(","));
+        }
+        self.0.lock().unwrap().entry(name).or_default().clone()
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl ::metrics::Recorder for CapturingRecorder {
+    fn describe_counter(
+        &self,
+        _: ::metrics::KeyName,
+        _: Option<::metrics::Unit>,
+        _: ::metrics::SharedString,
+    ) {
+    }
+
+    fn describe_gauge(
+        &self,
+        _: ::metrics::KeyName,
+        _: Option<::metrics::Unit>,
+        _: ::metrics::SharedString,
+    ) {
+    }
+
+    fn describe_histogram(
+        &self,
+        _: ::metrics::KeyName,
+        _: Option<::metrics::Unit>,
+        _: ::metrics::SharedString,
+    ) {
+    }
+
+    fn register_counter(
+        &self,
+        key: &::metrics::Key,
+        _: &::metrics::Metadata<'_>,
+    ) -> ::metrics::Counter {
+        ::metrics::Counter::from_arc(self.register(key))
+    }
+
+    fn register_gauge(
+        &self,
+        key: &::metrics::Key,
+        _: &::metrics::Metadata<'_>,
+    ) -> ::metrics::Gauge {
+        ::metrics::Gauge::from_arc(self.register(key))
+    }
+
+    fn register_histogram(
+        &self,
+        _: &::metrics::Key,
+        _: &::metrics::Metadata<'_>,
+    ) -> ::metrics::Histogram {
+        ::metrics::Histogram::noop()
+    }
+}
+
+#[tokio::test]
+async fn busy_poll() {
+    let _guard = subscribe();
+    let mut factory = EndpointFactory::new();
+    let mut socket_config = crate::SocketConfig::default();
+    socket_config.busy_poll(Some(Duration::from_millis(10)));
+    factory.endpoint_config.socket_config(socket_config);
+    let endpoint = factory.endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    server.send_datagram(b"spinning"[..].into()).unwrap();
+    assert_eq!(*client.read_datagram().await.unwrap(), *b"spinning");
+
+    // The driver must still wake up for new datagrams once it stopped spinning
+    sleep(Duration::from_millis(50)).await;
+    server.send_datagram(b"parked"[..].into()).unwrap();
+    assert_eq!(*client.read_datagram().await.unwrap(), *b"parked");
+}
+
+#[tokio::test]
+async fn recv_buffer_pool() {
+    let _guard = subscribe();
+    let mut factory = EndpointFactory::new();
+    let mut pool = crate::RecvBufferPoolConfig::default();
+    // Small enough for buffers to be reused while received data is still buffered
+    pool.buffer_size(4 * 1024).max_buffers(4);
+    let mut socket_config = crate::SocketConfig::default();
+    socket_config.recv_buffer_pool(Some(pool));
+    factory.endpoint_config.socket_config(socket_config);
+    let endpoint = factory.endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    let data = (0..256 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+    let mut send = client.open_uni().await.unwrap();
+    send.write_all(&data).await.unwrap();
+    send.finish().unwrap();
+    let mut recv = server.accept_uni().await.unwrap();
+    assert_eq!(recv.read_to_end(usize::MAX).await.unwrap(), data);
+}
+
+#[tokio::test]
+async fn close_and_await_peer() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    let code = crate::VarInt::from_u32(42);
+    assert!(
+        client
+            .close_and_await_peer(code, b"done", Duration::from_secs(5))
+            .await
+    );
+    assert!(matches!(
+        server.closed().await,
+        crate::ConnectionError::ApplicationClosed(close) if close.error_code == code
+    ));
+}
+
+#[tokio::test]
+async fn ring_buffer() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    let data = gen_data(256 * 1024, 42);
+    let mut send = client.open_uni().await.unwrap();
+    let write = async {
+        send.write_all(&data).await.unwrap();
+        send.finish().unwrap();
+    };
+    let read = async {
+        let mut recv = server.accept_uni().await.unwrap();
+        recv.set_ring_buffer(vec![0; 16 * 1024].into()).unwrap();
+        let mut received = Vec::new();
+        while let Some(len) = recv
+            .read_ring(|first, second| {
+                received.extend_from_slice(first);
+                received.extend_from_slice(second);
+                // Overstating the amount consumed releases no more than was readable
+                usize::MAX
+            })
+            .await
+            .unwrap()
+        {
+            assert!(len <= 16 * 1024);
+        }
+        received
+    };
+    let ((), received) = tokio::join!(write, read);
+    assert_eq!(received, data);
+}
+
+#[test]
+fn blocking_echo() {
+    use std::io::{Read, Write};
+    let _guard = subscribe();
+    let factory = EndpointFactory::new();
+    let (server_config, client_config) = factory.configs(TransportConfig::default());
+    let server = blocking::Endpoint::new(
+        EndpointConfig::default(),
+        Some(server_config),
+        UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap(),
+    )
+    .unwrap();
+    let server_addr = server.local_addr().unwrap();
+
+    let server_thread = std::thread::spawn(move || {
+        let conn = server.accept().unwrap().unwrap();
+        let (mut send, mut recv) = conn.accept_bi().unwrap();
+        let mut data = Vec::new();
+        // Through the `std::io` traits
+        Read::read_to_end(&mut recv, &mut data).unwrap();
+        Write::write_all(&mut send, &data).unwrap();
+        send.finish().unwrap();
+        conn.closed();
+    });
+
+    let client = blocking::Endpoint::new(
+        EndpointConfig::default(),
+        None,
+        UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0)).unwrap(),
+    )
+    .unwrap();
+    let conn = client
+        .connect_with(client_config, server_addr, "localhost")
+        .unwrap();
+    let (mut send, mut recv) = conn.open_bi().unwrap();
+    let data = gen_data(64 * 1024, 3);
+    send.write_all(&data).unwrap();
+    send.finish().unwrap();
+    assert!(recv.read_to_end(usize::MAX).unwrap() == data);
+    conn.close(0u32.into(), b"done");
+    client.wait_idle();
+    server_thread.join().unwrap();
+}
+
+#[tokio::test(start_paused = true)]
+async fn memory_socket() {
+    let _guard = subscribe();
+    const LATENCY: Duration = Duration::from_millis(25);
+    let mut link = LinkConfig::default();
+    link.latency(LATENCY)
+        .jitter(Duration::from_millis(5))
+        .loss(0.05)
+        .reorder(0.05)
+        .bandwidth(Some(1_000_000))
+        .seed(42);
+    let server_addr = "10.0.0.1:4433".parse().unwrap();
+    let (server_socket, client_socket) = MemorySocket::pair(
+        server_addr,
+        "10.0.0.2:4433".parse().unwrap(),
+        link,
+        Arc::new(TokioRuntime),
+    );
+    let factory = EndpointFactory::new();
+    let server = factory.endpoint_with_socket(Box::new(server_socket));
+    let client = factory.endpoint_with_socket(Box::new(client_socket));
+
+    let start = tokio::time::Instant::now();
+    let data = gen_data(100 * 1024, 7);
+    join!(
+        async {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            echo(conn.accept_bi().await.unwrap()).await;
+            conn.closed().await;
+        },
+        async {
+            let conn = client
+                .connect(server_addr, "localhost")
+                .unwrap()
+                .await
+                .unwrap();
+            let (mut send, mut recv) = conn.open_bi().await.unwrap();
+            send.write_all(&data).await.unwrap();
+            send.finish().unwrap();
+            let echoed = recv.read_to_end(usize::MAX).await.unwrap();
+            assert!(echoed == data);
+            // Recovered from the simulated loss
+            assert!(conn.stats().path.lost_packets > 0);
+            conn.close(0u32.into(), b"done");
+        }
+    );
+    // At least the handshake and the stream data each take a round trip
+    assert!(start.elapsed() >= 4 * LATENCY);
+}
+
+#[tokio::test(start_paused = true)]
+async fn manual_driver() {
+    let _guard = subscribe();
+    let now = || tokio::time::Instant::now().into_std();
+    let server_addr: SocketAddr = "10.0.0.1:4433".parse().unwrap();
+    let client_addr: SocketAddr = "10.0.0.2:4433".parse().unwrap();
+    let server_driver = Arc::new(ManualDriver::new(server_addr, now()));
+    let client_driver = Arc::new(ManualDriver::new(client_addr, now()));
+    // Stands in for the wakeup mechanism of an application's event loop
+    let notify = Arc::new(tokio::sync::Notify::new());
+    for driver in [&server_driver, &client_driver] {
+        let notify = notify.clone();
+        driver.set_notify(move || notify.notify_one());
+    }
+
+    let (server_config, client_config) = EndpointFactory::new().configs(TransportConfig::default());
+    let server = Endpoint::new_with_abstract_socket(
+        EndpointConfig::default(),
+        Some(server_config),
+        server_driver.socket(),
+        server_driver.runtime(),
+    )
+    .unwrap();
+    let client = Endpoint::new_with_abstract_socket(
+        EndpointConfig::default(),
+        None,
+        client_driver.socket(),
+        client_driver.runtime(),
+    )
+    .unwrap();
+    client.set_default_client_config(client_config);
+
+    let event_loop = tokio::spawn({
+        let (server_driver, client_driver) = (server_driver.clone(), client_driver.clone());
+        async move {
+            let mut buf = Vec::new();
+            loop {
+                for (from, from_addr, to) in [
+                    (&server_driver, server_addr, &client_driver),
+                    (&client_driver, client_addr, &server_driver),
+                ] {
+                    while let Some(transmit) = from.poll_transmit(&mut buf) {
+                        assert_eq!(transmit.size, buf.len());
+                        to.handle_datagram(now(), from_addr, None, transmit.ecn, &buf);
+                    }
+                }
+                let timeout = [server_driver.poll_timeout(), client_driver.poll_timeout()]
+                    .into_iter()
+                    .flatten()
+                    .min();
+                match timeout {
+                    Some(timeout) => {
+                        tokio::select! {
+                            _ = notify.notified() => {}
+                            _ = tokio::time::sleep_until(timeout.into()) => {}
+                        }
+                    }
+                    None => notify.notified().await,
+                }
+                server_driver.handle_timeout(now());
+                client_driver.handle_timeout(now());
+            }
+        }
+    });
+
+    let data = gen_data(100 * 1024, 5);
+    join!(
+        async {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            echo(conn.accept_bi().await.unwrap()).await;
+            conn.closed().await;
+        },
+        async {
+            let conn = client
+                .connect(server_addr, "localhost")
+                .unwrap()
+                .await
+                .unwrap();
+            let (mut send, mut recv) = conn.open_bi().await.unwrap();
+            send.write_all(&data).await.unwrap();
+            send.finish().unwrap();
+            let echoed = recv.read_to_end(usize::MAX).await.unwrap();
+            assert!(echoed == data);
+            conn.close(0u32.into(), b"done");
+        }
+    );
+    event_loop.abort();
+}
+
+#[tokio::test]
+async fn packet_capture() {
+    let _guard = subscribe();
+    let capture = SharedBuf::default();
+    let pcap = Arc::new(PcapWriter::new(capture.clone()).unwrap());
+
+    let server_addr = "10.0.0.1:4433".parse().unwrap();
+    let (server_socket, client_socket) = MemorySocket::pair(
+        server_addr,
+        "10.0.0.2:4433".parse().unwrap(),
+        LinkConfig::default(),
+        Arc::new(TokioRuntime),
+    );
+    let factory = EndpointFactory::new();
+    let server = factory.endpoint_with_socket(Box::new(server_socket));
+    let client = factory.endpoint_with_socket(Box::new(TapSocket::new(
+        Box::new(client_socket),
+        pcap.clone(),
+    )));
+
+    let mut roots = RootCertStore::empty();
+    roots.add(factory.cert.cert.der().clone()).unwrap();
+    let mut client_crypto = rustls::ClientConfig::builder_with_provider(default_provider().into())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    client_crypto.key_log = pcap.clone();
+    let client_config =
+        ClientConfig::new(Arc::new(QuicClientConfig::try_from(client_crypto).unwrap()));
+
+    join!(
+        async {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            echo(conn.accept_bi().await.unwrap()).await;
+            conn.closed().await;
+        },
+        async {
+            let conn = client
+                .connect_with(client_config, server_addr, "localhost")
+                .unwrap()
+                .await
+                .unwrap();
+            let (mut send, mut recv) = conn.open_bi().await.unwrap();
+            send.write_all(b"hello").await.unwrap();
+            send.finish().unwrap();
+            assert_eq!(recv.read_to_end(usize::MAX).await.unwrap(), b"hello");
+            conn.close(0u32.into(), b"done");
+        }
+    );
+    client.wait_idle().await;
+
+    let capture = capture.0.lock().unwrap();
+    let mut blocks = Vec::new();
+    let mut rest = &capture[..];
+    while !rest.is_empty() {
+        let block_type = u32::from_le_bytes(rest[..4].try_into().unwrap());
+        let len = u32::from_le_bytes(rest[4..8].try_into().unwrap()) as usize;
+        assert_eq!(len % 4, 0);
+        assert_eq!(rest[len - 4..len], rest[4..8]);
+        blocks.push((block_type, &rest[8..len - 4]));
+        rest = &rest[len..];
+    }
+    // Section header and interface description
+    assert_eq!(blocks[0].0, 0x0a0d_0d0a);
+    assert_eq!(blocks[1].0, 0x0000_0001);
+    // Decryption secrets for at least the handshake and 1-RTT keys
+    assert!(blocks.iter().filter(|(ty, _)| *ty == 0x0000_000a).count() >= 2);
+    // Enhanced packet blocks carrying IPv4 packets in both directions
+    let sources = blocks
+        .iter()
+        .filter(|(ty, _)| *ty == 0x0000_0006)
+        .map(|(_, body)| {
+            let packet = &body[20..];
+            assert_eq!(packet[0], 0x45);
+            Ipv4Addr::from(<[u8; 4]>::try_from(&packet[12..16]).unwrap())
+        })
+        .collect::<Vec<_>>();
+    assert!(sources.contains(&Ipv4Addr::new(10, 0, 0, 1)));
+    assert!(sources.contains(&Ipv4Addr::new(10, 0, 0, 2)));
+}
+
+#[tokio::test]
+async fn runtime_key_log() {
+    let _guard = subscribe();
+    let factory = EndpointFactory::new();
+    let server = factory.endpoint();
+    let server_addr = server.local_addr().unwrap();
+    let client = factory.endpoint();
+
+    // Enable key logging on the running server endpoint only
+    let server_log = SharedBuf::default();
+    let key = PrivateKeyDer::Pkcs8(factory.cert.signing_key.serialize_der().into());
+    let server_crypto = rustls::ServerConfig::builder_with_provider(default_provider().into())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(vec![factory.cert.cert.der().clone()], key)
+        .unwrap();
+    let server_crypto = QuicServerConfig::try_from(server_crypto)
+        .unwrap()
+        .with_key_log(Arc::new(KeyLogWriter::new(server_log.clone())));
+    server.set_server_config(Some(crate::ServerConfig::with_crypto(Arc::new(
+        server_crypto,
+    ))));
+
+    // And on a single outgoing connection of the client
+    let client_log = SharedBuf::default();
+    let mut roots = RootCertStore::empty();
+    roots.add(factory.cert.cert.der().clone()).unwrap();
+    let client_crypto = rustls::ClientConfig::builder_with_provider(default_provider().into())
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let client_crypto = QuicClientConfig::try_from(client_crypto)
+        .unwrap()
+        .with_key_log(Arc::new(KeyLogWriter::new(client_log.clone())));
+    let client_config = ClientConfig::new(Arc::new(client_crypto));
+
+    let server_task = tokio::spawn(async move {
+        for _ in 0..2 {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            conn.closed().await;
+        }
+    });
+    let conn = client
+        .connect_with(client_config, server_addr, "localhost")
+        .unwrap()
+        .await
+        .unwrap();
+    conn.close(0u32.into(), b"done");
+    let unlogged = client
+        .connect(server_addr, "localhost")
+        .unwrap()
+        .await
+        .unwrap();
+    unlogged.close(0u32.into(), b"done");
+    server_task.await.unwrap();
+
+    let secrets = |log: &SharedBuf| {
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        log.lines()
+            .map(|line| line.split(' ').next().unwrap().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let client_secrets = secrets(&client_log);
+    let server_secrets = secrets(&server_log);
+    for label in [
+        "CLIENT_HANDSHAKE_TRAFFIC_SECRET",
+        "SERVER_HANDSHAKE_TRAFFIC_SECRET",
+        "CLIENT_TRAFFIC_SECRET_0",
+        "SERVER_TRAFFIC_SECRET_0",
+    ] {
+        // The client logged its first connection only, the server both
+        assert_eq!(client_secrets.iter().filter(|x| *x == label).count(), 1);
+        assert_eq!(server_secrets.iter().filter(|x| *x == label).count(), 2);
+    }
+}
+
+#[tokio::test]
+async fn socks5_tunnel() {
+    let _guard = subscribe();
+    let proxy = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let proxy_addr = proxy.local_addr().unwrap();
+    let relay = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+    let relay_addr = relay.local_addr().unwrap();
+    std::thread::spawn(move || run_socks5_proxy(proxy, relay));
+
+    let factory = EndpointFactory::new();
+    let server = factory.endpoint();
+    let server_addr = server.local_addr().unwrap();
+    let tunnel = Socks5Tunnel::connect(proxy_addr, Some(("user", "secret")))
+        .await
+        .unwrap();
+    let client = factory.endpoint_with_socket(Box::new(TunnelSocket::new(Arc::new(tunnel))));
+
+    join!(
+        async {
+            let conn = server.accept().await.unwrap().await.unwrap();
+            // The server only sees the relay
+            assert_eq!(conn.remote_address(), relay_addr);
+            echo(conn.accept_bi().await.unwrap()).await;
+            conn.closed().await;
+        },
+        async {
+            let conn = client
+                .connect(server_addr, "localhost")
+                .unwrap()
+                .await
+                .unwrap();
+            let (mut send, mut recv) = conn.open_bi().await.unwrap();
+            send.write_all(b"hello").await.unwrap();
+            send.finish().unwrap();
+            assert_eq!(recv.read_to_end(usize::MAX).await.unwrap(), b"hello");
+            conn.close(0u32.into(), b"done");
+        }
+    );
+    client.wait_idle().await;
+}
+
+/// A minimal SOCKS5 proxy supporting a single UDP association between IPv4 peers
+fn run_socks5_proxy(listener: std::net::TcpListener, relay: UdpSocket) {
+    use std::io::{Read, Write};
+
+    let (mut control, _) = listener.accept().unwrap();
+    let mut buf = [0; 512];
+    // Greeting offering password authentication
+    control.read_exact(&mut buf[..2]).unwrap();
+    let methods = buf[1] as usize;
+    control.read_exact(&mut buf[..methods]).unwrap();
+    assert!(buf[..methods].contains(&2));
+    control.write_all(&[5, 2]).unwrap();
+    control.read_exact(&mut buf[..2]).unwrap();
+    let len = buf[1] as usize;
+    control.read_exact(&mut buf[..len + 1]).unwrap();
+    assert_eq!(&buf[..len], b"user");
+    let len = buf[len] as usize;
+    control.read_exact(&mut buf[..len]).unwrap();
+    assert_eq!(&buf[..len], b"secret");
+    control.write_all(&[1, 0]).unwrap();
+    // UDP association
+    control.read_exact(&mut buf[..10]).unwrap();
+    assert_eq!(buf[..4], [5, 3, 0, 1]);
+    let mut reply = vec![5, 0, 0, 1];
+    reply.extend_from_slice(&[0; 4]);
+    reply.extend_from_slice(&relay.local_addr().unwrap().port().to_be_bytes());
+    control.write_all(&reply).unwrap();
+
+    relay
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut client = None;
+    let mut buf = [0; 2048];
+    while let Ok((len, from)) = relay.recv_from(&mut buf) {
+        if client.is_none_or(|client| client == from) {
+            client = Some(from);
+            assert_eq!(buf[..4], [0, 0, 0, 1]);
+            let ip = Ipv4Addr::from(<[u8; 4]>::try_from(&buf[4..8]).unwrap());
+            let port = u16::from_be_bytes([buf[8], buf[9]]);
+            relay.send_to(&buf[10..len], (ip, port)).unwrap();
+        } else {
+            let SocketAddr::V4(from) = from else {
+                unreachable!()
+            };
+            let mut datagram = vec![0, 0, 0, 1];
+            datagram.extend_from_slice(&from.ip().octets());
+            datagram.extend_from_slice(&from.port().to_be_bytes());
+            datagram.extend_from_slice(&buf[..len]);
+            relay.send_to(&datagram, client.unwrap()).unwrap();
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn send_blocked() {
+    let _guard = subscribe();
+    const STALL: Duration = Duration::from_millis(100);
+    let server_addr = "10.0.0.1:4433".parse().unwrap();
+    let (server_socket, client_socket) = MemorySocket::pair(
+        server_addr,
+        "10.0.0.2:4433".parse().unwrap(),
+        LinkConfig::default(),
+        Arc::new(TokioRuntime),
+    );
+    let gate = Arc::new(SendGate::default());
+    let factory = EndpointFactory::new();
+    let server = factory.endpoint_with_socket(Box::new(server_socket));
+    let client = factory.endpoint_with_socket(Box::new(GatedSocket {
+        inner: Box::new(client_socket),
+        gate: gate.clone(),
+    }));
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    client.set_send_blocked_callback(STALL / 2, {
+        let reports = reports.clone();
+        move |event| reports.lock().unwrap().push(event)
+    });
+
+    let (client_conn, server_conn) = join!(
+        async {
+            client
+                .connect(server_addr, "localhost")
+                .unwrap()
+                .await
+                .unwrap()
+        },
+        async { server.accept().await.unwrap().await.unwrap() },
+    );
+    assert_eq!(client.stats().send_blocked, 0);
+
+    gate.close();
+    client_conn.send_datagram(b"stalled"[..].into()).unwrap();
+    sleep(STALL).await;
+    gate.open();
+    assert_eq!(*server_conn.read_datagram().await.unwrap(), *b"stalled");
+
+    let stats = client.stats();
+    assert_eq!(stats.send_blocked, 1);
+    assert!(stats.send_blocked_time >= STALL);
+    let reports = reports.lock().unwrap();
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].remote, server_addr);
+    assert!(reports[0].duration >= STALL / 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn priority_classes() {
+    let _guard = subscribe();
+    let monitor = SendMonitor::default();
+    let quantum = monitor.transmit_quantum(PriorityClass::Normal);
+    assert!(quantum > 1);
+
+    // Lower classes yield to waiting connections of higher classes only
+    monitor.blocked(PriorityClass::Interactive);
+    assert_eq!(monitor.transmit_quantum(PriorityClass::Background), 1);
+    assert_eq!(monitor.transmit_quantum(PriorityClass::Normal), 1);
+    assert_eq!(
+        monitor.transmit_quantum(PriorityClass::Interactive),
+        quantum
+    );
+    monitor.blocked(PriorityClass::Background);
+    assert_eq!(
+        monitor.transmit_quantum(PriorityClass::Interactive),
+        quantum
+    );
+    monitor.unblocked(PriorityClass::Interactive, Duration::ZERO);
+    assert_eq!(monitor.transmit_quantum(PriorityClass::Normal), quantum);
+    monitor.reclassified(PriorityClass::Background, PriorityClass::Interactive);
+    assert_eq!(monitor.transmit_quantum(PriorityClass::Normal), 1);
+    monitor.abandoned(PriorityClass::Interactive);
+    assert_eq!(monitor.transmit_quantum(PriorityClass::Normal), quantum);
+
+    // Reassigning a blocked connection doesn't count as another stall
+    let server_addr = "10.0.0.1:4433".parse().unwrap();
+    let (server_socket, client_socket) = MemorySocket::pair(
+        server_addr,
+        "10.0.0.2:4433".parse().unwrap(),
+        LinkConfig::default(),
+        Arc::new(TokioRuntime),
+    );
+    let gate = Arc::new(SendGate::default());
+    let factory = EndpointFactory::new();
+    let server = factory.endpoint_with_socket(Box::new(server_socket));
+    let client = factory.endpoint_with_socket(Box::new(GatedSocket {
+        inner: Box::new(client_socket),
+        gate: gate.clone(),
+    }));
+    let (client_conn, server_conn) = join!(
+        async {
+            client
+                .connect(server_addr, "localhost")
+                .unwrap()
+                .await
+                .unwrap()
+        },
+        async { server.accept().await.unwrap().await.unwrap() },
+    );
+    assert_eq!(client_conn.priority_class(), PriorityClass::Normal);
+
+    gate.close();
+    client_conn.send_datagram(b"control"[..].into()).unwrap();
+    sleep(Duration::from_millis(10)).await;
+    client_conn.set_priority_class(PriorityClass::Interactive);
+    assert_eq!(client_conn.priority_class(), PriorityClass::Interactive);
+    gate.open();
+    assert_eq!(*server_conn.read_datagram().await.unwrap(), *b"control");
+    assert_eq!(client.stats().send_blocked, 1);
+}
+
+/// Lets a test hold up all transmits of a [`GatedSocket`]
+#[derive(Default)]
+struct SendGate {
+    closed: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl SendGate {
+    fn close(&self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+
+    fn open(&self) {
+        self.closed.store(false, Ordering::Relaxed);
+        for waker in self.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for SendGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SendGate")
+            .field("closed", &self.closed)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct GatedSocket {
+    inner: Box<dyn AsyncUdpSocket>,
+    gate: Arc<SendGate>,
+}
+
+impl AsyncUdpSocket for GatedSocket {
+    fn create_sender(&self) -> Pin<Box<dyn UdpSender>> {
+        Box::pin(GatedSender {
+            inner: self.inner.create_sender(),
+            gate: self.gate.clone(),
+        })
+    }
+
+    fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+        bufs: &mut [io::IoSliceMut<'_>],
+        meta: &mut [udp::RecvMeta],
+    ) -> Poll<io::Result<usize>> {
+        self.inner.poll_recv(cx, bufs, meta)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+
+    fn may_fragment(&self) -> bool {
+        self.inner.may_fragment()
+    }
+}
+
+#[derive(Debug)]
+struct GatedSender {
+    inner: Pin<Box<dyn UdpSender>>,
+    gate: Arc<SendGate>,
+}
+
+impl UdpSender for GatedSender {
+    fn poll_send(
+        mut self: Pin<&mut Self>,
+        transmit: &udp::Transmit<'_>,
+        cx: &mut Context<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.gate.closed.load(Ordering::Relaxed) {
+            self.gate.wakers.lock().unwrap().push(cx.waker().clone());
+            return Poll::Pending;
+        }
+        self.inner.as_mut().poll_send(transmit, cx)
+    }
+
+    fn max_transmit_segments(&self) -> usize {
+        self.inner.max_transmit_segments()
+    }
+}
+
+/// A writer that can be inspected after being moved into a [`PcapWriter`]
+#[derive(Clone, Default)]
+struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn two_datagram_readers() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    let done = tokio::sync::Notify::new();
+    let (a, b, ()) = tokio::join!(
+        async {
+            let x = client.read_datagram().await.unwrap();
+            done.notify_waiters();
+            x
+        },
+        async {
+            let x = client.read_datagram().await.unwrap();
+            done.notify_waiters();
+            x
+        },
+        async {
+            server.send_datagram(b"one"[..].into()).unwrap();
+            done.notified().await;
+            server.send_datagram_wait(b"two"[..].into()).await.unwrap();
+        }
+    );
+    assert!(*a == *b"one" || *b == *b"one");
+    assert!(*a == *b"two" || *b == *b"two");
+}
+
+#[tokio::test]
+async fn read_flow_datagram_without_classifier() {
+    let _guard = subscribe();
+    let endpoint = endpoint();
+
+    let (client, server) = tokio::join!(
+        endpoint
+            .connect(endpoint.local_addr().unwrap(), "localhost")
+            .unwrap(),
+        async { endpoint.accept().await.unwrap().await }
+    );
+    let client = client.unwrap();
+    let server = server.unwrap();
+
+    client.send_datagram(b"flow"[..].into()).unwrap();
+    assert_eq!(
+        timeout(Duration::from_secs(5), server.read_flow_datagram(0))
+            .await
+            .unwrap(),
+        Err(ReadFlowDatagramError::NoClassifier)
+    );
+    assert_eq!(*server.read_datagram().await.unwrap(), *b"flow");
 }
 
 #[tokio::test]